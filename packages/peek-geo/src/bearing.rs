@@ -0,0 +1,157 @@
+use geo::HaversineBearing;
+
+use crate::coordinates::Coordinates;
+
+/// Initial great-circle bearing from `from` to `to`, in degrees clockwise from north [0, 360)
+///
+/// θ = atan2(sin Δλ · cos φ2, cos φ1 · sin φ2 − sin φ1 · cos φ2 · cos Δλ)
+pub fn bearing_degrees(from: &Coordinates, to: &Coordinates) -> f64 {
+    from.to_geo_point()
+        .haversine_bearing(to.to_geo_point())
+        .rem_euclid(360.0)
+}
+
+/// Eight-point compass direction, coarse enough to share in aggregate statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompassBucket {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl CompassBucket {
+    const ALL: [Self; 8] = [
+        Self::N,
+        Self::NE,
+        Self::E,
+        Self::SE,
+        Self::S,
+        Self::SW,
+        Self::W,
+        Self::NW,
+    ];
+
+    /// Bucket a bearing in degrees; each bucket spans 45° centered on its direction
+    pub fn from_degrees(degrees: f64) -> Self {
+        let index = ((degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8;
+        Self::ALL[index]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::N => "N",
+            Self::NE => "NE",
+            Self::E => "E",
+            Self::SE => "SE",
+            Self::S => "S",
+            Self::SW => "SW",
+            Self::W => "W",
+            Self::NW => "NW",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::destination;
+    use proptest::prelude::*;
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates::new(latitude, longitude).unwrap()
+    }
+
+    /// Signed difference between two bearings, in (-180, 180]
+    fn bearing_error(bearing: f64, expected: f64) -> f64 {
+        (bearing - expected + 180.0).rem_euclid(360.0) - 180.0
+    }
+
+    fn assert_bearing(from: Coordinates, to: Coordinates, expected: f64) {
+        let bearing = bearing_degrees(&from, &to);
+        assert!(
+            bearing_error(bearing, expected).abs() < 0.01,
+            "expected {}°, got {}°",
+            expected,
+            bearing
+        );
+    }
+
+    #[test]
+    fn test_cardinal_directions() {
+        let origin = point(37.7749, -122.4194);
+        assert_bearing(origin, point(37.7849, -122.4194), 0.0);
+        assert_bearing(origin, point(37.7649, -122.4194), 180.0);
+        // Due east/west along a parallel is within a hair of 90°/270° over a short hop
+        assert_bearing(origin, point(37.7749, -122.4094), 89.997);
+        assert_bearing(origin, point(37.7749, -122.4294), 270.003);
+    }
+
+    #[test]
+    fn test_known_long_distance_bearing() {
+        // London to Paris: initial bearing ≈ 148.1°
+        let london = point(51.5074, -0.1278);
+        let paris = point(48.8566, 2.3522);
+        let bearing = bearing_degrees(&london, &paris);
+        assert!((bearing - 148.1).abs() < 0.1, "got {}", bearing);
+    }
+
+    #[test]
+    fn test_compass_buckets() {
+        assert_eq!(CompassBucket::from_degrees(0.0), CompassBucket::N);
+        assert_eq!(CompassBucket::from_degrees(359.0), CompassBucket::N);
+        assert_eq!(CompassBucket::from_degrees(22.4), CompassBucket::N);
+        assert_eq!(CompassBucket::from_degrees(22.5), CompassBucket::NE);
+        assert_eq!(CompassBucket::from_degrees(90.0), CompassBucket::E);
+        assert_eq!(CompassBucket::from_degrees(200.0), CompassBucket::S);
+        assert_eq!(CompassBucket::from_degrees(315.0), CompassBucket::NW);
+        assert_eq!(CompassBucket::from_degrees(-45.0), CompassBucket::NW);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_reverse_bearing_differs_by_half_turn(
+            latitude in -70.0..70.0,
+            longitude in -179.0..179.0,
+            d_lat in -0.05..0.05,
+            d_lon in -0.05..0.05,
+        ) {
+            // Nearby pairs, as for joiners around an anchor; meridian convergence over
+            // a few kilometers keeps the deviation from exactly 180° far below 0.1°
+            let a = point(latitude, longitude);
+            let b = point(latitude + d_lat, longitude + d_lon);
+            prop_assume!(a != b);
+
+            let forward = bearing_degrees(&a, &b);
+            let reverse = bearing_degrees(&b, &a);
+            prop_assert!((0.0..360.0).contains(&forward));
+            let difference = (reverse - forward).rem_euclid(360.0);
+            prop_assert!((difference - 180.0).abs() < 0.1, "{}° vs {}°", forward, reverse);
+        }
+
+        #[test]
+        fn prop_bearing_points_back_along_a_destination(
+            latitude in -80.0..80.0,
+            longitude in -180.0..=180.0,
+            meters in 10.0..5_000.0,
+            bearing in 0.0..360.0,
+        ) {
+            let from = point(latitude, longitude);
+            let to = destination(&from, meters, bearing).unwrap();
+            let measured = bearing_degrees(&from, &to);
+            prop_assert!(bearing_error(measured, bearing).abs() < 0.01, "{}° vs {}°", measured, bearing);
+        }
+
+        #[test]
+        fn prop_every_bearing_lands_in_the_nearest_bucket(degrees in -720.0..720.0) {
+            let bucket = CompassBucket::from_degrees(degrees);
+            let index = CompassBucket::ALL.iter().position(|b| *b == bucket).unwrap();
+            let center = index as f64 * 45.0;
+            prop_assert!(bearing_error(degrees, center).abs() <= 22.5 + 1e-9);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use crate::coordinates::{Coordinates, InvalidGeohash};
+
+/// A decoded geohash cell: its center and half-extents in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub center: Coordinates,
+    pub half_height_degrees: f64,
+    pub half_width_degrees: f64,
+}
+
+/// Decode a geohash into the cell it names
+pub fn decode_cell(cell: &str) -> Result<Cell, InvalidGeohash> {
+    let (center, lon_error, lat_error) = geohash::decode(cell)?;
+    Ok(Cell {
+        center: Coordinates::from_geohash_coord(center)?,
+        half_height_degrees: lat_error,
+        half_width_degrees: lon_error,
+    })
+}
+
+/// The eight cells around `cell`, clockwise from north
+pub fn neighbor_cells(cell: &str) -> Result<[String; 8], InvalidGeohash> {
+    let n = geohash::neighbors(cell)?;
+    Ok([n.n, n.ne, n.e, n.se, n.s, n.sw, n.w, n.nw])
+}
+
+/// Whether `position` is in the `precision` character cell `anchor` or one of its neighbors
+///
+/// Neighbors are counted on the cell grid so the ring wraps across the antimeridian and stops at
+/// the poles. Toward the poles cells narrow east-west, so the ring widens to 1/cos(latitude)
+/// columns to keep covering about the same ground. An anchor of any other length, or one that
+/// does not decode, matches nothing.
+pub fn matches_cell_or_neighbor(position: &Coordinates, anchor: &str, precision: usize) -> bool {
+    if anchor.len() != precision {
+        return false;
+    }
+
+    let Ok(anchor) = decode_cell(anchor) else {
+        return false;
+    };
+    // The position's own cell center on the same grid
+    let Ok(user) = position
+        .geohash(precision)
+        .map_err(InvalidGeohash::from)
+        .and_then(|cell| decode_cell(&cell))
+    else {
+        return false;
+    };
+
+    let (user, center) = (user.center, anchor.center);
+    let rows = ((user.latitude() - center.latitude()) / (2.0 * anchor.half_height_degrees))
+        .round()
+        .abs();
+    let mut d_lon = user.longitude() - center.longitude();
+    if d_lon > 180.0 {
+        d_lon -= 360.0;
+    } else if d_lon < -180.0 {
+        d_lon += 360.0;
+    }
+    let columns = (d_lon / (2.0 * anchor.half_width_degrees)).round().abs();
+    // Sized at the neighbor row nearest the pole, where columns are narrowest
+    let poleward_edge = (center.latitude().abs() + 3.0 * anchor.half_height_degrees).min(90.0);
+    let max_columns = (1.0 / poleward_edge.to_radians().cos()).floor().max(1.0);
+
+    rows <= 1.0 && columns <= max_columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::{destination, haversine_meters};
+    use proptest::prelude::*;
+
+    const PRECISION: usize = 8;
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates::new(latitude, longitude).unwrap()
+    }
+
+    #[test]
+    fn test_decode_cell_reports_center_and_extent() {
+        let cell = decode_cell("9q8yyk8y").unwrap();
+        assert!((cell.center.latitude() - 37.7749).abs() < 0.001);
+        assert!((cell.center.longitude() + 122.4194).abs() < 0.001);
+        // Level 8 cells are 38.2m x 19.1m at the equator: 0.000171° x 0.000343°
+        assert!((cell.half_height_degrees * 2.0 - 0.000171).abs() < 1e-6);
+        assert!((cell.half_width_degrees * 2.0 - 0.000343).abs() < 1e-6);
+        assert!(matches!(
+            decode_cell("9q8yyioa"),
+            Err(InvalidGeohash::Geohash(_))
+        ));
+    }
+
+    #[test]
+    fn test_neighbor_cells_surround_the_cell() {
+        let around = neighbor_cells("9q8yyk8y").unwrap();
+        let center = decode_cell("9q8yyk8y").unwrap().center;
+        let north = decode_cell(&around[0]).unwrap().center;
+        let east = decode_cell(&around[2]).unwrap().center;
+        assert!(north.latitude() > center.latitude());
+        assert!(east.longitude() > center.longitude());
+        for cell in &around {
+            assert_eq!(cell.len(), 8);
+            assert!(matches_cell_or_neighbor(
+                &decode_cell(cell).unwrap().center,
+                "9q8yyk8y",
+                PRECISION
+            ));
+        }
+        assert!(neighbor_cells("not a cell").is_err());
+    }
+
+    #[test]
+    fn test_anchor_of_the_wrong_length_or_alphabet_matches_nothing() {
+        let user = point(37.7749, -122.4194);
+        let cell = user.geohash(PRECISION).unwrap();
+        assert!(matches_cell_or_neighbor(&user, &cell, PRECISION));
+        assert!(!matches_cell_or_neighbor(&user, &cell[..7], PRECISION));
+        assert!(!matches_cell_or_neighbor(&user, &cell, 9));
+        assert!(!matches_cell_or_neighbor(&user, "9q8yyioa", PRECISION));
+        assert!(!matches_cell_or_neighbor(&user, "........", PRECISION));
+    }
+
+    #[test]
+    fn test_neighbor_across_the_antimeridian_is_accepted() {
+        let anchor = point(0.0, 179.9999).geohash(PRECISION).unwrap();
+        let user = point(0.0, -179.9999);
+        assert!(haversine_meters(&point(0.0, 179.9999), &user) < 25.0);
+        assert!(matches_cell_or_neighbor(&user, &anchor, PRECISION));
+        assert!(!matches_cell_or_neighbor(
+            &point(0.0, -179.999),
+            &anchor,
+            PRECISION
+        ));
+    }
+
+    /// Anchors anywhere, weighted toward where cells misbehave: high latitudes where they
+    /// narrow, both sides of the antimeridian, and the equator
+    fn anchor() -> impl Strategy<Value = Coordinates> {
+        prop_oneof![
+            (-80.0..80.0, -180.0..180.0),
+            (80.0..89.99, -180.0..180.0),
+            (-89.99..-80.0, -180.0..180.0),
+            (-80.0..80.0, 179.99..180.0),
+            (-80.0..80.0, -180.0..-179.99),
+            (-0.001..0.001, -180.0..180.0),
+        ]
+        .prop_map(|(latitude, longitude): (f64, f64)| point(latitude, longitude))
+    }
+
+    // Agreement with the great-circle distance at precision 8: a user within one cell height
+    // of the anchor is always accepted, and the widest ring never reaches this far
+    const ALWAYS_ACCEPTED_METERS: f64 = 18.0;
+    const NEVER_ACCEPTED_METERS: f64 = 125.0;
+
+    proptest! {
+        #[test]
+        fn prop_user_within_10m_is_accepted(
+            anchor in anchor(),
+            meters in 0.0..10.0,
+            bearing in 0.0..360.0,
+        ) {
+            let cell = anchor.geohash(PRECISION).unwrap();
+            let user = destination(&anchor, meters, bearing).unwrap();
+            prop_assert!(matches_cell_or_neighbor(&user, &cell, PRECISION), "{:?} from {}", user, cell);
+        }
+
+        #[test]
+        fn prop_user_beyond_200m_is_rejected(
+            anchor in anchor(),
+            meters in 200.0..5_000.0,
+            bearing in 0.0..360.0,
+        ) {
+            let cell = anchor.geohash(PRECISION).unwrap();
+            let user = destination(&anchor, meters, bearing).unwrap();
+            prop_assert!(!matches_cell_or_neighbor(&user, &cell, PRECISION), "{:?} from {}", user, cell);
+        }
+
+        #[test]
+        fn prop_geohash_agrees_with_great_circle_distance(
+            anchor in anchor(),
+            meters in 0.0..300.0,
+            bearing in 0.0..360.0,
+        ) {
+            let cell = anchor.geohash(PRECISION).unwrap();
+            let user = destination(&anchor, meters, bearing).unwrap();
+            let distance = haversine_meters(&anchor, &user);
+            let accepted = matches_cell_or_neighbor(&user, &cell, PRECISION);
+            if distance <= ALWAYS_ACCEPTED_METERS {
+                prop_assert!(accepted, "{:?} is {:.1}m from {}", user, distance, cell);
+            }
+            if distance > NEVER_ACCEPTED_METERS {
+                prop_assert!(!accepted, "{:?} is {:.1}m from {}", user, distance, cell);
+            }
+        }
+
+        #[test]
+        fn prop_ring_is_the_cell_and_its_neighbors_at_any_precision(
+            anchor in (-70.0..70.0, -180.0..180.0)
+                .prop_map(|(latitude, longitude): (f64, f64)| point(latitude, longitude)),
+            precision in 4usize..=10,
+        ) {
+            let cell = anchor.geohash(precision).unwrap();
+            let grid = decode_cell(&cell).unwrap();
+            prop_assert!(matches_cell_or_neighbor(&anchor, &cell, precision));
+            for neighbor in neighbor_cells(&cell).unwrap() {
+                let center = decode_cell(&neighbor).unwrap().center;
+                prop_assert!(matches_cell_or_neighbor(&center, &cell, precision), "{} from {}", neighbor, cell);
+
+                // Two rows north or south of the anchor is always outside the ring
+                let outer = neighbor_cells(&neighbor).unwrap();
+                for beyond in [&outer[0], &outer[4]] {
+                    let center = decode_cell(beyond).unwrap().center;
+                    let rows = ((center.latitude() - grid.center.latitude())
+                        / (2.0 * grid.half_height_degrees))
+                        .round()
+                        .abs();
+                    if rows >= 2.0 {
+                        prop_assert!(!matches_cell_or_neighbor(&center, &cell, precision), "{} from {}", beyond, cell);
+                    }
+                }
+            }
+        }
+    }
+}
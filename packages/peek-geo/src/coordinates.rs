@@ -0,0 +1,178 @@
+use geohash::{Coord, GeohashError};
+
+/// A position known to be finite and within latitude/longitude range
+///
+/// geohash and geo both put longitude on x and latitude on y, and building their types by
+/// hand is how the axes get swapped. Fields are private, so every conversion goes through
+/// the named methods here.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Coordinates that are not finite or out of latitude/longitude range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Coordinates must be finite and within latitude/longitude range")]
+pub struct InvalidCoordinates;
+
+/// Why a geohash read from metadata or config does not name a usable position
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidGeohash {
+    #[error("Invalid geohash: {0}")]
+    Geohash(#[from] GeohashError),
+    #[error("Geohash center is not a valid position: {0}")]
+    Position(#[from] InvalidCoordinates),
+}
+
+impl Coordinates {
+    pub fn new(latitude: f64, longitude: f64) -> Result<Self, InvalidCoordinates> {
+        if !latitude.is_finite()
+            || !longitude.is_finite()
+            || !(-90.0..=90.0).contains(&latitude)
+            || !(-180.0..=180.0).contains(&longitude)
+        {
+            return Err(InvalidCoordinates);
+        }
+        Ok(Self {
+            latitude,
+            longitude,
+        })
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    /// A point decoded from a geohash, such as the center of an anchor cell
+    pub fn from_geohash_coord(coord: Coord) -> Result<Self, InvalidCoordinates> {
+        Self::new(coord.y, coord.x)
+    }
+
+    /// Center of the geohash cell `cell`, which may come from anywhere (metadata, config)
+    pub fn from_geohash(cell: &str) -> Result<Self, InvalidGeohash> {
+        let (center, _, _) = geohash::decode(cell)?;
+        Ok(Self::from_geohash_coord(center)?)
+    }
+
+    pub fn to_geohash_coord(&self) -> Coord {
+        Coord {
+            x: self.longitude,
+            y: self.latitude,
+        }
+    }
+
+    pub fn to_geo_point(&self) -> geo::Point {
+        geo::Point::new(self.longitude, self.latitude)
+    }
+
+    /// The geohash cell of `precision` characters containing this position
+    pub fn geohash(&self, precision: usize) -> Result<String, GeohashError> {
+        geohash::encode(self.to_geohash_coord(), precision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_rejects_out_of_range_and_non_finite() {
+        for (latitude, longitude) in [
+            (f64::NAN, 0.0),
+            (0.0, f64::NAN),
+            (f64::INFINITY, 0.0),
+            (0.0, f64::NEG_INFINITY),
+            (-90.5, 0.0),
+            (91.0, 0.0),
+            (0.0, 180.5),
+            (0.0, -1_000.0),
+            (f64::MAX, f64::MIN),
+        ] {
+            assert_eq!(
+                Coordinates::new(latitude, longitude),
+                Err(InvalidCoordinates),
+                "({}, {})",
+                latitude,
+                longitude
+            );
+        }
+        assert!(Coordinates::new(-90.0, 180.0).is_ok());
+        assert!(Coordinates::new(90.0, -180.0).is_ok());
+    }
+
+    #[test]
+    fn test_axes_are_never_swapped() {
+        // Asymmetric spot: Sydney swapped would be off the map entirely
+        let sydney = Coordinates::new(-33.8688, 151.2093).unwrap();
+        assert_eq!(sydney.to_geohash_coord().x, 151.2093);
+        assert_eq!(sydney.to_geohash_coord().y, -33.8688);
+        assert_eq!(sydney.to_geo_point().x(), 151.2093);
+        assert_eq!(sydney.to_geo_point().y(), -33.8688);
+        assert!(sydney.geohash(8).unwrap().starts_with("r3gx2"));
+        assert_eq!(
+            Coordinates::new(151.2093, -33.8688),
+            Err(InvalidCoordinates)
+        );
+    }
+
+    #[test]
+    fn test_geohashes_from_outside_decode_to_typed_errors() {
+        let center = Coordinates::from_geohash("9q8yyk8y").unwrap();
+        assert!((center.latitude() - 37.7749).abs() < 0.001);
+        assert!((center.longitude() + 122.4194).abs() < 0.001);
+
+        // "i", "l", "o" and "a" are not in the geohash alphabet
+        for garbage in ["9q8yyioa", "ABC!", "geohash with spaces"] {
+            assert!(
+                matches!(
+                    Coordinates::from_geohash(garbage),
+                    Err(InvalidGeohash::Geohash(_))
+                ),
+                "{:?} decoded",
+                garbage
+            );
+        }
+    }
+
+    #[test]
+    fn test_encoding_refuses_unusable_precision() {
+        let position = Coordinates::new(37.7749, -122.4194).unwrap();
+        assert!(position.geohash(0).is_err());
+        assert!(position.geohash(13).is_err());
+        assert_eq!(position.geohash(12).unwrap().len(), 12);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_geohash_round_trip_stays_in_the_cell(
+            latitude in -90.0..=90.0,
+            longitude in -180.0..=180.0,
+            precision in 1usize..=12,
+        ) {
+            let position = Coordinates::new(latitude, longitude).unwrap();
+            let cell = position.geohash(precision).unwrap();
+            let (center, lon_error, lat_error) = geohash::decode(&cell).unwrap();
+            let center = Coordinates::from_geohash_coord(center).unwrap();
+
+            // A swap would land up to 180° away, far outside the cell's half-extent
+            prop_assert!((center.latitude() - latitude).abs() <= lat_error + 1e-9);
+            prop_assert!((center.longitude() - longitude).abs() <= lon_error + 1e-9);
+            prop_assert_eq!(center.geohash(precision).unwrap(), cell);
+        }
+
+        #[test]
+        fn prop_every_valid_position_is_accepted_unchanged(
+            latitude in -90.0..=90.0,
+            longitude in -180.0..=180.0,
+        ) {
+            let position = Coordinates::new(latitude, longitude).unwrap();
+            prop_assert_eq!((position.latitude(), position.longitude()), (latitude, longitude));
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use geohash::GeohashError;
+
+use crate::coordinates::{Coordinates, InvalidCoordinates, InvalidGeohash};
+use crate::distance::{destination, haversine_meters};
+
+/// Farthest a display location may sit from the actual location
+pub const MAX_DISPLAY_OFFSET_METERS: f64 = 750.0;
+
+/// Radius of the fog circle drawn around a display location; the actual location is inside it
+pub const FOG_RADIUS_METERS: f64 = 1_000.0;
+
+/// Display locations are 9 character geohashes, about 5m across
+pub const DISPLAY_GEOHASH_PRECISION: usize = 9;
+
+/// How far and in which direction a display location is moved from the actual one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayOffset {
+    pub distance_meters: f64,
+    pub bearing_degrees: f64,
+}
+
+/// Why no display location could be derived for a community
+#[derive(Debug, thiserror::Error)]
+pub enum DisplayLocationError {
+    #[error("Display offset is not a valid position: {0}")]
+    Offset(#[from] InvalidCoordinates),
+    #[error("Failed to encode display location: {0}")]
+    Geohash(#[from] GeohashError),
+}
+
+/// The display geohash for `actual` moved by `offset`
+///
+/// The same inputs always give the same cell; callers pick the offset, usually at random. The
+/// distance is capped at MAX_DISPLAY_OFFSET_METERS, so the actual location is always within the
+/// fog circle centered on the result.
+pub fn display_location(
+    actual: Coordinates,
+    offset: DisplayOffset,
+) -> Result<String, DisplayLocationError> {
+    let distance_meters = offset.distance_meters.clamp(0.0, MAX_DISPLAY_OFFSET_METERS);
+    let display = destination(&actual, distance_meters, offset.bearing_degrees)?;
+    Ok(display.geohash(DISPLAY_GEOHASH_PRECISION)?)
+}
+
+/// Whether `actual` lies within the fog circle drawn around `display_geohash`
+pub fn verify_display_location(
+    actual: &Coordinates,
+    display_geohash: &str,
+) -> Result<bool, InvalidGeohash> {
+    let display = Coordinates::from_geohash(display_geohash)?;
+    Ok(haversine_meters(actual, &display) <= FOG_RADIUS_METERS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates::new(latitude, longitude).unwrap()
+    }
+
+    fn offset(distance_meters: f64, bearing_degrees: f64) -> DisplayOffset {
+        DisplayOffset {
+            distance_meters,
+            bearing_degrees,
+        }
+    }
+
+    #[test]
+    fn test_display_location_is_deterministic() {
+        let actual = point(40.7128, -74.0060);
+        let first = display_location(actual, offset(500.0, 45.0)).unwrap();
+        assert_eq!(
+            first,
+            display_location(actual, offset(500.0, 45.0)).unwrap()
+        );
+        assert_eq!(first.len(), DISPLAY_GEOHASH_PRECISION);
+        assert_ne!(
+            first,
+            display_location(actual, offset(500.0, 46.0)).unwrap()
+        );
+
+        // No offset leaves the marker on the actual location's own cell
+        assert_eq!(
+            display_location(actual, offset(0.0, 123.0)).unwrap(),
+            actual.geohash(DISPLAY_GEOHASH_PRECISION).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_offsets_beyond_the_maximum_are_capped() {
+        let actual = point(37.7749, -122.4194);
+        assert_eq!(
+            display_location(actual, offset(50_000.0, 90.0)).unwrap(),
+            display_location(actual, offset(MAX_DISPLAY_OFFSET_METERS, 90.0)).unwrap()
+        );
+        assert!(matches!(
+            display_location(actual, offset(f64::NAN, 90.0)),
+            Err(DisplayLocationError::Offset(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_display_location() {
+        let actual = point(37.7793, -122.4193);
+        let display = display_location(actual, offset(MAX_DISPLAY_OFFSET_METERS, 200.0)).unwrap();
+        assert!(verify_display_location(&actual, &display).unwrap());
+
+        let far = destination(&actual, 1_200.0, 200.0)
+            .unwrap()
+            .geohash(DISPLAY_GEOHASH_PRECISION)
+            .unwrap();
+        assert!(!verify_display_location(&actual, &far).unwrap());
+        assert!(matches!(
+            verify_display_location(&actual, "9q8yyioa"),
+            Err(InvalidGeohash::Geohash(_))
+        ));
+    }
+
+    #[test]
+    fn test_display_location_near_the_antimeridian_and_poles() {
+        // Offsets from these spots cross lon 180 or graze a pole
+        for (lat, lon) in [
+            (-16.5, 179.999),
+            (65.0, -179.9995),
+            (89.9999, 0.0),
+            (-90.0, 45.0),
+        ] {
+            for bearing in (0..360).step_by(15) {
+                let actual = point(lat, lon);
+                let display =
+                    display_location(actual, offset(MAX_DISPLAY_OFFSET_METERS, bearing as f64))
+                        .unwrap_or_else(|e| panic!("({}, {}) at {}°: {}", lat, lon, bearing, e));
+                assert!(verify_display_location(&actual, &display).unwrap());
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_display_stays_within_the_maximum_offset(
+            latitude in -90.0..=90.0,
+            longitude in -180.0..=180.0,
+            distance_meters in 0.0..=MAX_DISPLAY_OFFSET_METERS,
+            bearing_degrees in 0.0..360.0,
+        ) {
+            let actual = point(latitude, longitude);
+            let display = display_location(actual, offset(distance_meters, bearing_degrees)).unwrap();
+            prop_assert_eq!(display.len(), DISPLAY_GEOHASH_PRECISION);
+
+            // The cell center may sit a few meters off the offset point itself
+            let center = Coordinates::from_geohash(&display).unwrap();
+            let distance = haversine_meters(&actual, &center);
+            prop_assert!(distance <= MAX_DISPLAY_OFFSET_METERS + 5.0, "{} is {}m away", display, distance);
+            prop_assert!(verify_display_location(&actual, &display).unwrap());
+        }
+
+        #[test]
+        fn prop_any_finite_offset_keeps_the_actual_location_in_the_fog(
+            latitude in -90.0..=90.0,
+            longitude in -180.0..=180.0,
+            distance_meters in -1e9..1e9,
+            bearing_degrees in -1e6..1e6,
+        ) {
+            let actual = point(latitude, longitude);
+            let display = display_location(actual, offset(distance_meters, bearing_degrees)).unwrap();
+            prop_assert!(verify_display_location(&actual, &display).unwrap());
+        }
+    }
+}
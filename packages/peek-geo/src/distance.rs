@@ -0,0 +1,160 @@
+use std::f64::consts::PI;
+
+use crate::coordinates::{Coordinates, InvalidCoordinates};
+
+/// Mean Earth radius in meters, for every distance in this crate
+pub const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// Great-circle distance between two positions in meters (haversine formula)
+pub fn haversine_meters(from: &Coordinates, to: &Coordinates) -> f64 {
+    let (lat1, lat2) = (from.latitude().to_radians(), to.latitude().to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.longitude() - from.longitude()).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    // Rounding can push a just past 1 for antipodal points
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+}
+
+/// The point `meters` from `from` along the initial bearing `bearing_degrees`
+///
+/// A path across the antimeridian wraps to the other side, and rounding at a pole may
+/// overshoot 90 by a hair, so the result is clamped. Only non-finite inputs fail.
+pub fn destination(
+    from: &Coordinates,
+    meters: f64,
+    bearing_degrees: f64,
+) -> Result<Coordinates, InvalidCoordinates> {
+    let bearing_radians = bearing_degrees * PI / 180.0;
+    let lat_rad = from.latitude() * PI / 180.0;
+    let lon_rad = from.longitude() * PI / 180.0;
+    let angular_distance = meters / EARTH_RADIUS_METERS;
+
+    let to_lat_rad = (lat_rad.sin() * angular_distance.cos()
+        + lat_rad.cos() * angular_distance.sin() * bearing_radians.cos())
+    .asin();
+    let to_lon_rad = lon_rad
+        + (bearing_radians.sin() * angular_distance.sin() * lat_rad.cos())
+            .atan2(angular_distance.cos() - lat_rad.sin() * to_lat_rad.sin());
+
+    Coordinates::new(
+        (to_lat_rad * 180.0 / PI).clamp(-90.0, 90.0),
+        wrap_longitude(to_lon_rad * 180.0 / PI),
+    )
+}
+
+/// The same meridian expressed within -180..=180
+pub fn wrap_longitude(longitude: f64) -> f64 {
+    if (-180.0..=180.0).contains(&longitude) {
+        longitude
+    } else {
+        (longitude + 180.0).rem_euclid(360.0) - 180.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates::new(latitude, longitude).unwrap()
+    }
+
+    #[test]
+    fn test_known_distances() {
+        // San Francisco to Palo Alto, about 44km
+        let distance = haversine_meters(&point(37.7749, -122.4194), &point(37.4419, -122.1430));
+        assert!((distance - 44_000.0).abs() < 5_000.0, "got {}", distance);
+
+        // 0.0009° of latitude is about 100m anywhere
+        let distance = haversine_meters(&point(37.7749, -122.4194), &point(37.7758, -122.4194));
+        assert!((distance - 100.0).abs() < 1.0, "got {}", distance);
+
+        // London to Paris, about 344km
+        let distance = haversine_meters(&point(51.5074, -0.1278), &point(48.8566, 2.3522));
+        assert!((distance - 343_500.0).abs() < 1_000.0, "got {}", distance);
+    }
+
+    #[test]
+    fn test_distance_across_the_antimeridian_and_between_poles() {
+        let east = point(0.0, 179.9999);
+        let west = point(0.0, -179.9999);
+        assert!(haversine_meters(&east, &west) < 25.0);
+
+        let half_circumference = PI * EARTH_RADIUS_METERS;
+        let poles = haversine_meters(&point(90.0, 0.0), &point(-90.0, 0.0));
+        assert!((poles - half_circumference).abs() < 1e-6);
+        let antipodes = haversine_meters(&point(0.0, 0.0), &point(0.0, 180.0));
+        assert!((antipodes - half_circumference).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_destination_wraps_and_clamps() {
+        let across = destination(&point(-16.5, 179.999), 500.0, 90.0).unwrap();
+        assert!(across.longitude() < -179.99, "{:?}", across);
+
+        let over_the_pole = destination(&point(89.9999, 0.0), 750.0, 0.0).unwrap();
+        assert!(over_the_pole.latitude() <= 90.0);
+
+        assert_eq!(
+            destination(&point(0.0, 0.0), f64::NAN, 0.0),
+            Err(InvalidCoordinates)
+        );
+        assert_eq!(
+            destination(&point(0.0, 0.0), 10.0, f64::INFINITY),
+            Err(InvalidCoordinates)
+        );
+    }
+
+    #[test]
+    fn test_wrap_longitude() {
+        assert_eq!(wrap_longitude(180.5), -179.5);
+        assert_eq!(wrap_longitude(-181.0), 179.0);
+        assert_eq!(wrap_longitude(-180.0), -180.0);
+        assert_eq!(wrap_longitude(180.0), 180.0);
+        assert_eq!(wrap_longitude(540.0), -180.0);
+        assert_eq!(wrap_longitude(12.5), 12.5);
+    }
+
+    fn anywhere() -> impl Strategy<Value = Coordinates> {
+        (-90.0..=90.0, -180.0..=180.0)
+            .prop_map(|(latitude, longitude): (f64, f64)| point(latitude, longitude))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_distance_is_a_symmetric_bounded_metric(a in anywhere(), b in anywhere()) {
+            let forward = haversine_meters(&a, &b);
+            prop_assert!((forward - haversine_meters(&b, &a)).abs() < 1e-6);
+            prop_assert!((0.0..=PI * EARTH_RADIUS_METERS + 1e-6).contains(&forward));
+            prop_assert_eq!(haversine_meters(&a, &a), 0.0);
+        }
+
+        #[test]
+        fn prop_triangle_inequality_holds(a in anywhere(), b in anywhere(), c in anywhere()) {
+            let direct = haversine_meters(&a, &c);
+            let via = haversine_meters(&a, &b) + haversine_meters(&b, &c);
+            prop_assert!(direct <= via + 1e-3, "{} > {}", direct, via);
+        }
+
+        #[test]
+        fn prop_destination_lands_at_the_requested_distance(
+            from in (-89.0..89.0, -180.0..=180.0)
+                .prop_map(|(latitude, longitude): (f64, f64)| point(latitude, longitude)),
+            meters in 0.0..20_000.0,
+            bearing in 0.0..360.0,
+        ) {
+            let to = destination(&from, meters, bearing).unwrap();
+            let distance = haversine_meters(&from, &to);
+            prop_assert!((distance - meters).abs() < 0.01, "{} vs {}", distance, meters);
+        }
+
+        #[test]
+        fn prop_wrapped_longitude_is_in_range_and_the_same_meridian(longitude in -2_000.0..2_000.0) {
+            let wrapped = wrap_longitude(longitude);
+            prop_assert!((-180.0..=180.0).contains(&wrapped));
+            let turns = (longitude - wrapped) / 360.0;
+            prop_assert!((turns - turns.round()).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Location math shared by Peek services
+//!
+//! Every position is a `Coordinates`, checked on construction, and every conversion to the
+//! geohash and geo crates goes through it, so no caller builds their (longitude, latitude)
+//! types by hand. Distances are great-circle on a spherical Earth of `EARTH_RADIUS_METERS`.
+
+pub mod bearing;
+pub mod cell;
+pub mod coordinates;
+pub mod display;
+pub mod distance;
+
+pub use bearing::{bearing_degrees, CompassBucket};
+pub use cell::{decode_cell, matches_cell_or_neighbor, neighbor_cells, Cell};
+pub use coordinates::{Coordinates, InvalidCoordinates, InvalidGeohash};
+pub use display::{
+    display_location, verify_display_location, DisplayLocationError, DisplayOffset,
+    DISPLAY_GEOHASH_PRECISION, FOG_RADIUS_METERS, MAX_DISPLAY_OFFSET_METERS,
+};
+pub use distance::{destination, haversine_meters, wrap_longitude, EARTH_RADIUS_METERS};
+pub use geohash::GeohashError;
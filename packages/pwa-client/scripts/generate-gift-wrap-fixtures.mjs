@@ -0,0 +1,132 @@
+// Writes the JavaScript-generated gift wrap vectors checked by the validation service's
+// conformance suite (src/test_gift_wrap.rs) and by tests/contracts/gift-wrap-fixtures.test.ts.
+//
+// Keys, timestamps and NIP-44 nonces are all fixed, so rerunning only changes signatures.
+// Payloads mirror the wire contract fixtures in the validation service (src/test_wire_contract.rs);
+// rerun with `npm run fixtures:gift-wrap` whenever a request variant is added or changed.
+
+import { createHash } from 'node:crypto';
+import { writeFileSync } from 'node:fs';
+import { fileURLToPath } from 'node:url';
+import { finalizeEvent, getEventHash, getPublicKey } from 'nostr-tools/pure';
+import * as nip44 from 'nostr-tools/nip44';
+
+const FIXTURES_DIR = new URL('../../validation-service/tests/fixtures/', import.meta.url);
+
+// Same identities as the Rust vectors: the client sends, the service receives
+const CLIENT_SECRET = '0000000000000000000000000000000000000000000000000000000000000002';
+const SERVICE_SECRET = '0000000000000000000000000000000000000000000000000000000000000001';
+const EPHEMERAL_SECRET = '0000000000000000000000000000000000000000000000000000000000000003';
+
+const RUMOR_CREATED_AT = 1700000000;
+const WRAP_CREATED_AT = 1699900000;
+const EXPIRATION = 1700259200;
+
+const REQUEST_KIND = 27492;
+const SEAL_KIND = 13;
+const GIFT_WRAP_KIND = 1059;
+
+const COMMUNITY_ID = '3a7e5c59-c0a1-4876-acf1-56189b86aa0d';
+const APPLICANT = '79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798';
+const LOCATION = { latitude: 37.7749, longitude: -122.4194, accuracy: 12.5, timestamp: 1760000000 };
+
+// One entry per request fixture in the wire contract, in the same order
+const REQUESTS = [
+  { type: 'location_validation', community_id: COMMUNITY_ID, location: LOCATION, active_until: 1760086400 },
+  { type: 'location_validation', community_id: COMMUNITY_ID, location: LOCATION },
+  { type: 'location_validation', community_id: COMMUNITY_ID, location: LOCATION, force: true },
+  { type: 'preview_request', community_id: COMMUNITY_ID },
+  { type: 'add_anchor', community_id: COMMUNITY_ID, location: LOCATION },
+  { type: 'update_metadata', community_id: COMMUNITY_ID, join_mode: 'approval' },
+  { type: 'approve_join', community_id: COMMUNITY_ID, pubkey: APPLICANT, approve: true },
+  { type: 'cancel_join_request', community_id: COMMUNITY_ID },
+];
+
+const bytes = (hex) => Uint8Array.from(Buffer.from(hex, 'hex'));
+
+// Deterministic stand-in for the random 32-byte nonce nip44.encrypt would draw
+const nonce = (label) => createHash('sha256').update(`peek-gift-wrap-fixture:${label}`).digest();
+
+function encrypt(plaintext, secretHex, recipientPubkey, label) {
+  const conversationKey = nip44.v2.utils.getConversationKey(bytes(secretHex), recipientPubkey);
+  return nip44.v2.encrypt(plaintext, conversationKey, nonce(label));
+}
+
+// Rumor, seal and gift wrap built the way NostrLocationService does, minus the randomness
+function vector(name, payload) {
+  const senderPubkey = getPublicKey(bytes(CLIENT_SECRET));
+  const recipientPubkey = getPublicKey(bytes(SERVICE_SECRET));
+
+  const rumor = {
+    pubkey: senderPubkey,
+    created_at: RUMOR_CREATED_AT,
+    kind: REQUEST_KIND,
+    tags: [],
+    content: JSON.stringify(payload),
+  };
+  rumor.id = getEventHash(rumor);
+
+  const seal = finalizeEvent(
+    {
+      kind: SEAL_KIND,
+      content: encrypt(JSON.stringify(rumor), CLIENT_SECRET, recipientPubkey, `${name}:seal`),
+      tags: [],
+      created_at: WRAP_CREATED_AT,
+    },
+    bytes(CLIENT_SECRET)
+  );
+
+  const giftWrap = finalizeEvent(
+    {
+      kind: GIFT_WRAP_KIND,
+      content: encrypt(JSON.stringify(seal), EPHEMERAL_SECRET, recipientPubkey, `${name}:wrap`),
+      tags: [
+        ['p', recipientPubkey],
+        ['expiration', String(EXPIRATION)],
+      ],
+      created_at: WRAP_CREATED_AT,
+    },
+    bytes(EPHEMERAL_SECRET)
+  );
+
+  return {
+    name,
+    sender_secret: CLIENT_SECRET,
+    sender_pubkey: senderPubkey,
+    recipient_secret: SERVICE_SECRET,
+    recipient_pubkey: recipientPubkey,
+    rumor: {
+      id: rumor.id,
+      pubkey: rumor.pubkey,
+      created_at: rumor.created_at,
+      kind: rumor.kind,
+      tags: rumor.tags,
+      content: rumor.content,
+    },
+    gift_wrap: {
+      id: giftWrap.id,
+      pubkey: giftWrap.pubkey,
+      created_at: giftWrap.created_at,
+      kind: giftWrap.kind,
+      tags: giftWrap.tags,
+      content: giftWrap.content,
+      sig: giftWrap.sig,
+    },
+  };
+}
+
+function write(file, description, vectors) {
+  const url = new URL(file, FIXTURES_DIR);
+  writeFileSync(url, JSON.stringify({ description, vectors }, null, 2) + '\n');
+  console.log(`Wrote ${vectors.length} vector(s) to ${fileURLToPath(url)}`);
+}
+
+write('gift_wrap_js.json', 'JavaScript (nostr-tools) gift wraps from a client to the service', [
+  vector('location_validation', REQUESTS[0]),
+]);
+
+write(
+  'gift_wrap_js_variants.json',
+  'JavaScript (nostr-tools) gift wraps of every client request variant',
+  REQUESTS.map((request, i) => vector(`${String(i).padStart(2, '0')}_${request.type}`, request))
+);
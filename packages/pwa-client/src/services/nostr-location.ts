@@ -46,7 +46,8 @@ type Rumor = UnsignedEvent & { id: string };
 export interface LocationData {
   latitude: number;
   longitude: number;
-  accuracy: number;
+  // Meters; -1, 0 or absent mean unknown, which can join but not create a community
+  accuracy?: number;
   timestamp: number;
 }
 
@@ -54,13 +55,31 @@ export interface LocationData {
 export type ServiceRequest =
   | {
       type: 'location_validation';
+      // Community UUID, or the slug its admins set for shareable links
       community_id: string;
       location: LocationData;
     }
   | {
       type: 'preview_request';
+      // Community UUID or slug, as for location_validation
       community_id: string;
     }
+  | {
+      // At most 20 ids; more are refused with BATCH_TOO_LARGE
+      type: 'preview_batch';
+      community_ids: string[];
+    }
+  | {
+      // Admin-only; limited to a few exports per admin per day
+      type: 'export_community';
+      community_id: string;
+    }
+  | {
+      // Admin-only; at most 100 hex or npub keys, within the community's import allowance
+      type: 'bulk_add_members';
+      community_id: string;
+      pubkeys: string[];
+    }
 ;
 
 // Unified response types using discriminated union
@@ -74,6 +93,17 @@ export type ServiceResponse =
       is_member?: boolean;
       error?: string;
       error_code?: string;
+      // Present when membership was granted: compact JSON claims and the service's
+      // Schnorr signature over their SHA-256, verifiable with the service pubkey
+      attestation?: { payload: string; sig: string };
+      // Only on the response that first adds the member, when the community set one
+      welcome?: { text: string; rules?: string[] };
+      // Name and picture of the joined community, only on successful responses
+      community_name?: string;
+      picture?: string;
+      // Set once the member is added: AUTH (NIP-42) to the relay before subscribing to the group
+      auth_required?: boolean;
+      auth_scope?: 'group' | 'relay';
     }
   | {
       type: 'preview_response';
@@ -86,10 +116,73 @@ export type ServiceResponse =
       is_public?: boolean;
       is_open?: boolean;
       created_at?: number;
+      // Only on communities with a member cap
+      max_members?: number;
+      is_full?: boolean;
+      // The requester's own membership, absent when the community was not found
+      is_member?: boolean;
+      role?: 'admin' | 'member';
+      error?: string;
+    }
+  | {
+      type: 'preview_batch_response';
+      success: boolean;
+      // One entry per requested id, in request order, each with its own success/error
+      results: CommunityPreviewResponse[];
+      error?: string;
+      error_code?: string;
+    }
+  | {
+      type: 'export_community_response';
+      success: boolean;
+      // Inline when small enough, otherwise export_url points at the uploaded document
+      export?: CommunityExport;
+      export_url?: string;
+      error?: string;
+      error_code?: string;
+    }
+  | {
+      type: 'bulk_add_members_response';
+      success: boolean;
+      // One entry per requested pubkey, in request order; empty when the batch was refused
+      outcomes: { pubkey: string; status: 'added' | 'already_member' | 'invalid' | 'failed' }[];
       error?: string;
+      error_code?: string;
     }
 ;
 
+// Document returned by export_community, format "peek.community-export"
+export interface CommunityExport {
+  format: string;
+  version: number;
+  exported_at: number;
+  community_id: string;
+  group_id: string;
+  metadata: {
+    name: string;
+    about: string | null;
+    rules: string[];
+    picture: string | null;
+    welcome: string | null;
+    // Anchor geohash cells as published on the group, never raw coordinates
+    geohash_cells: string[];
+    join_mode: 'auto' | 'approval';
+    max_members: number | null;
+    active_until: number | null;
+    archived: boolean;
+    updated_at: number;
+  };
+  members: { pubkey: string; role: 'admin' | 'member'; joined_at: number | null }[];
+  stats: {
+    member_count: number;
+    admin_count: number;
+    anchor_count: number;
+    first_join_at: number | null;
+    // The relay's member list may have been cut short
+    members_incomplete?: boolean;
+  };
+}
+
 // Legacy interfaces for backwards compatibility
 export interface LocationValidationRequest {
   type?: 'location_validation';
@@ -106,6 +199,11 @@ export interface LocationValidationResponse {
   is_member?: boolean;
   error?: string;
   error_code?: string;
+  welcome?: { text: string; rules?: string[] };
+  community_name?: string;
+  picture?: string;
+  auth_required?: boolean;
+  auth_scope?: 'group' | 'relay';
 }
 
 export interface CommunityPreviewResponse {
@@ -120,6 +218,8 @@ export interface CommunityPreviewResponse {
   is_public?: boolean;
   is_open?: boolean;
   created_at?: number;
+  is_member?: boolean;
+  role?: 'admin' | 'member';
   error?: string;
 }
 
@@ -0,0 +1,29 @@
+import { describe, it, expect } from 'vitest';
+import { generateStickerSVG } from './stickerGenerator';
+
+const COMMUNITY_ID = '3a7e5c59-c0a1-4876-acf1-56189b86aa0d';
+
+describe('generateStickerSVG', () => {
+  it('reprints a community byte for byte', async () => {
+    const first = await generateStickerSVG(COMMUNITY_ID);
+    const second = await generateStickerSVG(COMMUNITY_ID);
+
+    expect(first.svg).toBe(second.svg);
+    expect(first.communityId).toBe(COMMUNITY_ID);
+    expect(first.url).toBe(`https://peek.verse.app/c/${COMMUNITY_ID}`);
+  });
+
+  it('gives each new sticker its own community', async () => {
+    const first = await generateStickerSVG();
+    const second = await generateStickerSVG();
+
+    expect(first.communityId).not.toBe(second.communityId);
+    expect(first.svg).not.toBe(second.svg);
+  });
+
+  it('refuses community IDs that are not v4 UUIDs', async () => {
+    await expect(
+      generateStickerSVG('3a7e5c59-c0a1-1876-acf1-56189b86aa0d')
+    ).rejects.toThrow('v4 UUID');
+  });
+});
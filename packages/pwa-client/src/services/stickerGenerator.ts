@@ -7,10 +7,22 @@
 
 import QRCode from 'qrcode';
 
+// Version nibble 4 and RFC 4122 variant; the validation service refuses any other community ID
+const UUID_V4_PATTERN = /^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$/i;
+
+/**
+ * Whether a community ID is a random (v4) UUID
+ */
+export function isValidCommunityId(communityId: string): boolean {
+  return UUID_V4_PATTERN.test(communityId);
+}
+
 /**
  * Generate a styled Peek sticker SVG with QR code
  *
- * @param communityId Optional community UUID. If not provided, generates a new one.
+ * Nothing but the community ID varies, so a reprint of a community is byte-identical.
+ *
+ * @param communityId Optional community UUID (must be v4). If not provided, generates a new one.
  * @returns Object with SVG string, community ID, and URL
  */
 export async function generateStickerSVG(communityId?: string): Promise<{
@@ -18,8 +30,11 @@ export async function generateStickerSVG(communityId?: string): Promise<{
   communityId: string;
   url: string;
 }> {
-  // Generate UUID if not provided
+  // Generate UUID if not provided; stickers only ever carry random v4 IDs
   const uuid = communityId || crypto.randomUUID();
+  if (!isValidCommunityId(uuid)) {
+    throw new Error(`Community ID must be a v4 UUID: ${uuid}`);
+  }
   const url = `https://peek.verse.app/c/${uuid}`;
 
   // Generate QR code as SVG (library returns complete <svg> element)
@@ -0,0 +1,73 @@
+/**
+ * Contract Test: Gift wrap conformance vectors shared with the validation service
+ *
+ * Opens every vector in packages/validation-service/tests/fixtures/gift_wrap_*.json with
+ * nostr-tools: Rust-generated wraps (service → client) and our own generated wraps
+ * (scripts/generate-gift-wrap-fixtures.mjs). The Rust suite checks the same files from its side.
+ */
+
+import { describe, it, expect } from 'vitest'
+import { readdirSync, readFileSync } from 'node:fs'
+import { fileURLToPath } from 'node:url'
+import { getEventHash, getPublicKey, verifyEvent } from 'nostr-tools/pure'
+import type { Event } from 'nostr-tools'
+import * as nip44 from 'nostr-tools/nip44'
+
+interface GiftWrapVector {
+  name: string
+  sender_secret: string
+  sender_pubkey: string
+  recipient_secret: string
+  recipient_pubkey: string
+  rumor: { id: string; pubkey: string; created_at: number; kind: number; tags: string[][]; content: string }
+  gift_wrap: Event
+}
+
+const FIXTURES_DIR = fileURLToPath(new URL('../../../validation-service/tests/fixtures/', import.meta.url))
+
+const files = readdirSync(FIXTURES_DIR).filter(file => /^gift_wrap_.*\.json$/.test(file))
+
+const bytes = (hex: string) => Uint8Array.from(Buffer.from(hex, 'hex'))
+
+function open(payload: string, recipientSecret: string, senderPubkey: string): string {
+  const conversationKey = nip44.v2.utils.getConversationKey(bytes(recipientSecret), senderPubkey)
+  return nip44.v2.decrypt(payload, conversationKey)
+}
+
+describe('Gift wrap conformance vectors', () => {
+  it('finds both Rust- and JavaScript-generated vector files', () => {
+    expect(files).toContain('gift_wrap_rust.json')
+    expect(files).toContain('gift_wrap_js.json')
+    expect(files).toContain('gift_wrap_js_variants.json')
+  })
+
+  for (const file of files) {
+    const { vectors } = JSON.parse(readFileSync(FIXTURES_DIR + file, 'utf8')) as { vectors: GiftWrapVector[] }
+
+    describe(file, () => {
+      for (const vector of vectors) {
+        it(`opens ${vector.name}`, () => {
+          expect(getPublicKey(bytes(vector.recipient_secret))).toBe(vector.recipient_pubkey)
+
+          const wrap = vector.gift_wrap
+          expect(verifyEvent(wrap)).toBe(true)
+          expect(wrap.kind).toBe(1059)
+          expect(wrap.tags[0]).toEqual(['p', vector.recipient_pubkey])
+          expect(wrap.tags.slice(1).every(tag => tag[0] === 'expiration')).toBe(true)
+
+          const seal = JSON.parse(open(wrap.content, vector.recipient_secret, wrap.pubkey)) as Event
+          expect(verifyEvent(seal)).toBe(true)
+          expect(seal.kind).toBe(13)
+          expect(seal.tags).toEqual([])
+          expect(seal.pubkey).toBe(vector.sender_pubkey)
+
+          const rumor = JSON.parse(open(seal.content, vector.recipient_secret, seal.pubkey))
+          expect(rumor.pubkey).toBe(seal.pubkey)
+          expect(getEventHash(rumor)).toBe(rumor.id)
+          expect(rumor).toEqual(vector.rumor)
+          expect(JSON.parse(rumor.content)).toHaveProperty('type')
+        })
+      }
+    })
+  }
+})
@@ -0,0 +1,87 @@
+//! Allocations and time for caching group ids as owned Strings vs interned Arc<str>
+//!
+//! Replays 10k synthetic requests spread over 200 groups. Each request refreshes its group's
+//! entry in a cache the way the admin list cache and group locks do. Run with
+//! `cargo bench --bench interning`; allocation counts are printed before the timings.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use validation_service::libraries::intern::Interner;
+
+const REQUESTS: usize = 10_000;
+const GROUPS: usize = 200;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Group ids as they arrive: borrowed from parsed requests, in request order
+fn workload() -> Vec<String> {
+    (0..REQUESTS)
+        .map(|i| format!("peek-{:08x}", (i * 7919) % GROUPS))
+        .collect()
+}
+
+fn owned_keys(requests: &[String]) -> usize {
+    let mut cache: HashMap<String, usize> = HashMap::new();
+    for (i, group_id) in requests.iter().enumerate() {
+        cache.insert(group_id.to_string(), i);
+    }
+    cache.len()
+}
+
+fn interned_keys(requests: &[String], interner: &Interner) -> usize {
+    let mut cache: HashMap<Arc<str>, usize> = HashMap::new();
+    for (i, group_id) in requests.iter().enumerate() {
+        cache.insert(interner.intern(group_id), i);
+    }
+    cache.len()
+}
+
+fn allocations(run: impl FnOnce() -> usize) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(run());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_interning(c: &mut Criterion) {
+    let requests = workload();
+    let interner = Interner::new(GROUPS * 2);
+    // Warm the pool, as a running service would have
+    interned_keys(&requests, &interner);
+
+    println!(
+        "{} requests over {} groups: {} allocations with owned keys, {} interned",
+        REQUESTS,
+        GROUPS,
+        allocations(|| owned_keys(&requests)),
+        allocations(|| interned_keys(&requests, &interner))
+    );
+
+    let mut group = c.benchmark_group("group_id_cache");
+    group.bench_function("owned", |b| b.iter(|| owned_keys(black_box(&requests))));
+    group.bench_function("interned", |b| {
+        b.iter(|| interned_keys(black_box(&requests), &interner))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_interning);
+criterion_main!(benches);
@@ -1,27 +1,341 @@
+use nostr_sdk::Keys;
 use serde::Deserialize;
 
+use crate::libraries::relay_url::RelayUrl;
+use crate::libraries::secret::SecretString;
+use crate::models::ProtocolConfig;
+use crate::services::discovery_map::DiscoveryMapSigner;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     #[serde(default = "default_port")]
     pub port: u16,
 
+    // Serve /metrics on this port instead of the public one; unset keeps it public
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+
+    // Address the metrics server binds, e.g. loopback or a cluster-internal interface
+    #[serde(default = "default_metrics_bind")]
+    pub metrics_bind: String,
+
+    // Move the admin API to the metrics server too
+    #[serde(default)]
+    pub metrics_serve_admin: bool,
+
+    // Serve the OpenAPI description at /api/openapi.json and Swagger UI at /api/docs
+    #[serde(default)]
+    pub enable_docs: bool,
+
     #[serde(default = "default_relay_url")]
-    pub relay_url: String,
+    pub relay_url: RelayUrl,
 
     // Public relay URL to return to browser clients (defaults to relay_url if not specified)
     #[serde(default = "default_relay_url")]
-    pub public_relay_url: String,
+    pub public_relay_url: RelayUrl,
+
+    // Group whose metadata must be readable through public_relay_url at startup; unset only
+    // checks that the relay is reachable
+    #[serde(default)]
+    pub public_relay_sentinel_group: Option<String>,
+
+    // Soft launch: validate and answer every request, but create, join and publish nothing
+    #[serde(default)]
+    pub read_only_mode: bool,
+
+    // Relay's secret key for managing groups and accessing all events (hex or nsec)
+    // Emptied by take_keys at startup; use the parsed Keys instead
+    pub relay_secret_key: SecretString,
+
+    // Service private key for NIP-59 gift wrap communication (hex or nsec)
+    pub service_secret_key: SecretString,
+
+    // How often to close time-boxed communities whose active_until has passed (seconds)
+    #[serde(default = "default_archive_sweep_interval_secs")]
+    pub archive_sweep_interval_secs: u64,
+
+    // Maximum number of QR anchor locations a single community may span
+    #[serde(default = "default_max_anchors")]
+    pub max_anchors: usize,
+
+    // Maximum number of a requester's NIP-65 inbox relays to also deliver responses to (0 disables)
+    #[serde(default = "default_inbox_fanout_max")]
+    pub inbox_fanout_max: usize,
+
+    // Relays requesters' NIP-65 relay lists are looked up on (comma separated; empty disables)
+    #[serde(default = "default_inbox_index_relays")]
+    pub inbox_index_relays: Vec<String>,
+
+    // Total time the discovery map may spend reverse-geocoding unnamed communities (milliseconds)
+    #[serde(default = "default_discovery_geocode_budget_ms")]
+    pub discovery_geocode_budget_ms: u64,
+
+    // How often the background task geocodes localities for the discovery data (seconds)
+    #[serde(default = "default_discovery_locality_interval_secs")]
+    pub discovery_locality_interval_secs: u64,
+
+    // How often to compare the published discovery map with live group metadata (seconds)
+    #[serde(default = "default_discovery_reconcile_interval_secs")]
+    pub discovery_reconcile_interval_secs: u64,
+
+    // Republish the discovery map once it is this old, even if nothing drifted (seconds)
+    #[serde(default = "default_discovery_map_max_age_secs")]
+    pub discovery_map_max_age_secs: u64,
+
+    // How often to re-resolve a sample of cached UUID → group mappings on the relay (seconds)
+    #[serde(default = "default_group_mapping_check_interval_secs")]
+    pub group_mapping_check_interval_secs: u64,
+
+    // Cached mappings re-resolved per check
+    #[serde(default = "default_group_mapping_check_sample")]
+    pub group_mapping_check_sample: usize,
+
+    // Maximum concurrent borrows of pooled relay clients (inbox fan-out and other ad-hoc relay sets)
+    #[serde(default = "default_client_pool_max")]
+    pub client_pool_max: usize,
+
+    // Pooled relay clients unused for this long are disconnected (seconds)
+    #[serde(default = "default_client_pool_idle_secs")]
+    pub client_pool_idle_secs: u64,
+
+    // Maximum group creations running at once, protecting the relay key from rate limits
+    #[serde(default = "default_max_concurrent_creations")]
+    pub max_concurrent_creations: usize,
+
+    // Creations allowed to wait for a free slot; any beyond this are told to retry
+    #[serde(default = "default_creation_queue_limit")]
+    pub creation_queue_limit: usize,
+
+    // Consecutive relay calls ending disconnected before requests are refused outright (0 disables)
+    #[serde(default = "default_relay_circuit_failure_threshold")]
+    pub relay_circuit_failure_threshold: u32,
+
+    // How long an open relay circuit refuses requests before probing the relay (seconds)
+    #[serde(default = "default_relay_circuit_cool_down_secs")]
+    pub relay_circuit_cool_down_secs: u64,
+
+    // Recent group events referenced in the NIP-29 "previous" tag of each management event we
+    // sign (0 for relays that don't support it)
+    #[serde(default = "default_nip29_previous_refs")]
+    pub nip29_previous_refs: usize,
+
+    // Upper bound on response gift wrap expiration; clients may request a shorter one (seconds)
+    #[serde(default = "default_response_expiration_max_secs")]
+    pub response_expiration_max_secs: u64,
+
+    // Maximum number of failed responses waiting to be retried; further failures are dropped
+    #[serde(default = "default_response_retry_capacity")]
+    pub response_retry_capacity: usize,
+
+    // How long a failed response keeps being retried before it is dropped (seconds)
+    #[serde(default = "default_response_retry_max_secs")]
+    pub response_retry_max_secs: u64,
+
+    // Re-send subscriptions on a relay that has delivered no events for this long (seconds)
+    #[serde(default = "default_subscription_silence_secs")]
+    pub subscription_silence_secs: u64,
+
+    // Reconnect a relay instead after this many consecutive silent windows
+    #[serde(default = "default_subscription_reconnect_after")]
+    pub subscription_reconnect_after: u32,
+
+    // Pending join requests in approval-mode communities expire after this long (seconds)
+    #[serde(default = "default_join_request_ttl_secs")]
+    pub join_request_ttl_secs: u64,
+
+    // How often to sweep join queues for expired requests (seconds)
+    #[serde(default = "default_join_request_sweep_interval_secs")]
+    pub join_request_sweep_interval_secs: u64,
+
+    // Joins within this long of the first are announced to admins together (seconds)
+    #[serde(default = "default_join_notification_window_secs")]
+    pub join_notification_window_secs: u64,
+
+    // Relay reads for a single request must finish within this, or the client is asked to retry (seconds)
+    #[serde(default = "default_request_deadline_secs")]
+    pub request_deadline_secs: u64,
+
+    // How often to look for groups the relay key still administers and retry removing it (seconds)
+    #[serde(default = "default_admin_audit_interval_secs")]
+    pub admin_audit_interval_secs: u64,
+
+    // How often to sweep orphaned groups (duplicate UUIDs, abandoned creations); 0 leaves it to the admin API (seconds)
+    #[serde(default)]
+    pub orphan_sweep_interval_secs: u64,
+
+    // Duplicate groups younger than this are left alone, in case their creation is still running (seconds)
+    #[serde(default = "default_orphan_quarantine_secs")]
+    pub orphan_quarantine_secs: u64,
+
+    // Groups with no members are deleted once they have seen no activity for this many days
+    #[serde(default = "default_orphan_idle_days")]
+    pub orphan_idle_days: u64,
+
+    // How often to warn and remove inactive members of communities that set inactive_prune_days (seconds)
+    #[serde(default = "default_inactive_prune_interval_secs")]
+    pub inactive_prune_interval_secs: u64,
+
+    // Report what inactive member pruning would do without warning or removing anyone
+    #[serde(default)]
+    pub inactive_prune_dry_run: bool,
+
+    // Maximum concurrent community event streams (server-sent events)
+    #[serde(default = "default_event_stream_max_connections")]
+    pub event_stream_max_connections: usize,
+
+    // Interval between keep-alive comments on idle event streams (seconds)
+    #[serde(default = "default_event_stream_heartbeat_secs")]
+    pub event_stream_heartbeat_secs: u64,
+
+    // Key that signs discovery maps: "relay" (default) or "service"
+    #[serde(default)]
+    pub discovery_map_signer: DiscoveryMapSigner,
+
+    // Publish one discovery map per display geohash prefix (comma separated) instead of a single map
+    #[serde(default)]
+    pub discovery_map_prefixes: Vec<String>,
+
+    // Bearer token for mutating /api/admin endpoints; unset disables them
+    #[serde(default)]
+    pub admin_api_token: Option<String>,
 
-    // Relay's secret key for managing groups and accessing all events
-    pub relay_secret_key: String,
+    // Named admin API tokens as label:token (comma separated); actions are audited under the label
+    #[serde(default)]
+    pub admin_tokens: Vec<String>,
 
-    // Service private key for NIP-59 gift wrap communication (hex format)
-    pub service_secret_key: String,
+    // Ceiling on a metadata edit's serialized size; overflow moves to a kind 30078 extension (bytes)
+    #[serde(default = "default_metadata_max_event_bytes")]
+    pub metadata_max_event_bytes: usize,
+
+    // Accept any well-formed community UUID, not just v4, for stickers printed before the check
+    #[serde(default)]
+    pub allow_any_uuid: bool,
+
+    // Previews of nonexistent communities one requester may ask for per minute (0 disables)
+    #[serde(default = "default_preview_miss_limit")]
+    pub preview_miss_limit: usize,
+
+    // Also refuse to create communities in open ocean, per the bundled coarse landmask
+    #[serde(default)]
+    pub landmask_check: bool,
+
+    // LOCATION_INVALID answers one pubkey may get at one community within the probe window
+    // before its answers lose distance detail and are delayed (0 disables)
+    #[serde(default = "default_probe_rejection_threshold")]
+    pub probe_rejection_threshold: usize,
+
+    #[serde(default = "default_probe_window_secs")]
+    pub probe_window_secs: u64,
+
+    // Attempts from a probing pair are refused for this long after each rejection (0: none)
+    #[serde(default)]
+    pub probe_cool_down_secs: u64,
+
+    // Most random delay added to a probing pair's answers (milliseconds)
+    #[serde(default = "default_probe_jitter_max_ms")]
+    pub probe_jitter_max_ms: u64,
+
+    // Joins from farther than this from every anchor are rejected as very far without the
+    // precise checks, and counted per community (kilometers, 0 disables)
+    #[serde(default = "default_far_scan_radius_km")]
+    pub far_scan_radius_km: u64,
+
+    // Failed joins in one spot at least 10km from every anchor, from several people over more
+    // than a day, after which the community is flagged as a possible sticker reuse (0 disables)
+    #[serde(default = "default_sticker_reuse_min_failures")]
+    pub sticker_reuse_min_failures: u32,
+
+    // Refuse new members of flagged communities until the operator clears the flag; stickers
+    // are not signed yet, so nobody new gets in on location alone
+    #[serde(default)]
+    pub sticker_reuse_require_signed: bool,
+
+    // Community exports one admin may request per day (0 disables the limit)
+    #[serde(default = "default_export_daily_limit")]
+    pub export_daily_limit: usize,
+
+    // Exports larger than this are uploaded to the media server instead of sent inline (bytes)
+    #[serde(default = "default_export_inline_max_bytes")]
+    pub export_inline_max_bytes: usize,
+
+    // Blossom server receiving exports too large to send inline; unset refuses them
+    #[serde(default)]
+    pub export_media_server: Option<String>,
+
+    // Members one community's admins may add by pubkey instead of by scan (0 disables it)
+    #[serde(default = "default_bulk_add_allowance")]
+    pub bulk_add_allowance: usize,
+
+    // Bulk adds sent to the relay per second
+    #[serde(default = "default_bulk_add_per_second")]
+    pub bulk_add_per_second: usize,
+
+    // Advertise the web app as the NIP-89 handler for Peek groups and community links
+    #[serde(default)]
+    pub publish_app_handler: bool,
+
+    // Web app origin the NIP-89 handler's URL templates point at
+    #[serde(default = "default_app_url")]
+    pub app_url: String,
+
+    // GeoJSON country polygons for tagging new communities (builds with country-lookup only)
+    #[serde(default)]
+    pub country_boundaries_path: Option<String>,
+
+    // ISO 3166-1 alpha-2 codes new communities may be created in (comma separated); empty allows all
+    #[serde(default)]
+    pub country_allowlist: Vec<String>,
+
+    // ISO codes new communities may not be created in; existing ones stay readable
+    #[serde(default)]
+    pub country_denylist: Vec<String>,
+
+    // Circles no community marker may appear in, as latitude:longitude:radius_meters (comma separated)
+    #[serde(default)]
+    pub exclusion_zones: Vec<String>,
+
+    // Receives a JSON POST when something needs an operator's attention; unset only logs
+    #[serde(default)]
+    pub operator_webhook_url: Option<String>,
+
+    // Protocol namespace (kinds, tag prefixes), loaded separately from the same environment
+    #[serde(skip)]
+    pub protocol: ProtocolConfig,
+}
+
+/// The service's two identities, parsed once from the configured secrets
+#[derive(Clone)]
+pub struct ServiceKeys {
+    // Gift wrap recipient and signer of attestations and the service descriptor
+    pub service: Keys,
+    // Relay admin for NIP-29 group management
+    pub relay: Keys,
+}
+
+/// Which configured secret could not be parsed; never carries the value itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidSecretKey {
+    #[error("SERVICE_SECRET_KEY is not a valid hex or nsec secret key")]
+    Service,
+    #[error("RELAY_SECRET_KEY is not a valid hex or nsec secret key")]
+    Relay,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self, envy::Error> {
-        envy::from_env::<Config>()
+        let mut config = envy::from_env::<Config>()?;
+        config.protocol = envy::from_env::<ProtocolConfig>()?;
+        Ok(config)
+    }
+
+    /// Parse both secret keys and remove the raw strings, so clones of the config carry none
+    pub fn take_keys(&mut self) -> Result<ServiceKeys, InvalidSecretKey> {
+        let service = std::mem::take(&mut self.service_secret_key);
+        let relay = std::mem::take(&mut self.relay_secret_key);
+        Ok(ServiceKeys {
+            service: Keys::parse(service.expose()).map_err(|_| InvalidSecretKey::Service)?,
+            relay: Keys::parse(relay.expose()).map_err(|_| InvalidSecretKey::Relay)?,
+        })
     }
 }
 
@@ -29,10 +343,78 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             port: default_port(),
+            metrics_port: None,
+            metrics_bind: default_metrics_bind(),
+            metrics_serve_admin: false,
+            enable_docs: false,
             relay_url: default_relay_url(),
             public_relay_url: default_relay_url(),
-            relay_secret_key: String::new(), // Must be provided via environment
-            service_secret_key: String::new(), // Must be provided via environment
+            public_relay_sentinel_group: None,
+            read_only_mode: false,
+            relay_secret_key: SecretString::default(), // Must be provided via environment
+            service_secret_key: SecretString::default(), // Must be provided via environment
+            archive_sweep_interval_secs: default_archive_sweep_interval_secs(),
+            max_anchors: default_max_anchors(),
+            inbox_fanout_max: default_inbox_fanout_max(),
+            inbox_index_relays: default_inbox_index_relays(),
+            discovery_geocode_budget_ms: default_discovery_geocode_budget_ms(),
+            discovery_locality_interval_secs: default_discovery_locality_interval_secs(),
+            discovery_reconcile_interval_secs: default_discovery_reconcile_interval_secs(),
+            discovery_map_max_age_secs: default_discovery_map_max_age_secs(),
+            group_mapping_check_interval_secs: default_group_mapping_check_interval_secs(),
+            group_mapping_check_sample: default_group_mapping_check_sample(),
+            client_pool_max: default_client_pool_max(),
+            client_pool_idle_secs: default_client_pool_idle_secs(),
+            max_concurrent_creations: default_max_concurrent_creations(),
+            creation_queue_limit: default_creation_queue_limit(),
+            relay_circuit_failure_threshold: default_relay_circuit_failure_threshold(),
+            relay_circuit_cool_down_secs: default_relay_circuit_cool_down_secs(),
+            nip29_previous_refs: default_nip29_previous_refs(),
+            response_expiration_max_secs: default_response_expiration_max_secs(),
+            response_retry_capacity: default_response_retry_capacity(),
+            response_retry_max_secs: default_response_retry_max_secs(),
+            subscription_silence_secs: default_subscription_silence_secs(),
+            subscription_reconnect_after: default_subscription_reconnect_after(),
+            join_request_ttl_secs: default_join_request_ttl_secs(),
+            join_request_sweep_interval_secs: default_join_request_sweep_interval_secs(),
+            join_notification_window_secs: default_join_notification_window_secs(),
+            request_deadline_secs: default_request_deadline_secs(),
+            admin_audit_interval_secs: default_admin_audit_interval_secs(),
+            orphan_sweep_interval_secs: 0,
+            orphan_quarantine_secs: default_orphan_quarantine_secs(),
+            orphan_idle_days: default_orphan_idle_days(),
+            inactive_prune_interval_secs: default_inactive_prune_interval_secs(),
+            inactive_prune_dry_run: false,
+            event_stream_max_connections: default_event_stream_max_connections(),
+            event_stream_heartbeat_secs: default_event_stream_heartbeat_secs(),
+            discovery_map_signer: DiscoveryMapSigner::default(),
+            discovery_map_prefixes: Vec::new(),
+            admin_api_token: None,
+            admin_tokens: Vec::new(),
+            metadata_max_event_bytes: default_metadata_max_event_bytes(),
+            allow_any_uuid: false,
+            preview_miss_limit: default_preview_miss_limit(),
+            landmask_check: false,
+            probe_rejection_threshold: default_probe_rejection_threshold(),
+            probe_window_secs: default_probe_window_secs(),
+            probe_cool_down_secs: 0,
+            probe_jitter_max_ms: default_probe_jitter_max_ms(),
+            far_scan_radius_km: default_far_scan_radius_km(),
+            sticker_reuse_min_failures: default_sticker_reuse_min_failures(),
+            sticker_reuse_require_signed: false,
+            export_daily_limit: default_export_daily_limit(),
+            export_inline_max_bytes: default_export_inline_max_bytes(),
+            export_media_server: None,
+            bulk_add_allowance: default_bulk_add_allowance(),
+            bulk_add_per_second: default_bulk_add_per_second(),
+            publish_app_handler: false,
+            app_url: default_app_url(),
+            country_boundaries_path: None,
+            country_allowlist: Vec::new(),
+            country_denylist: Vec::new(),
+            exclusion_zones: Vec::new(),
+            operator_webhook_url: None,
+            protocol: ProtocolConfig::default(),
         }
     }
 }
@@ -41,6 +423,241 @@ fn default_port() -> u16 {
     3000
 }
 
-fn default_relay_url() -> String {
-    "wss://communities2.nos.social".to_string()
+fn default_metrics_bind() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_relay_url() -> RelayUrl {
+    RelayUrl::parse("wss://communities2.nos.social").expect("default relay URL is valid")
+}
+
+fn default_archive_sweep_interval_secs() -> u64 {
+    300
+}
+
+fn default_max_anchors() -> usize {
+    5
+}
+
+fn default_inbox_fanout_max() -> usize {
+    3
+}
+
+fn default_inbox_index_relays() -> Vec<String> {
+    vec![
+        "wss://purplepag.es".to_string(),
+        "wss://relay.nos.social".to_string(),
+    ]
+}
+
+fn default_discovery_geocode_budget_ms() -> u64 {
+    3000
+}
+
+fn default_discovery_locality_interval_secs() -> u64 {
+    3600
+}
+
+fn default_discovery_reconcile_interval_secs() -> u64 {
+    900
+}
+
+fn default_discovery_map_max_age_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_group_mapping_check_interval_secs() -> u64 {
+    600
+}
+
+fn default_group_mapping_check_sample() -> usize {
+    20
+}
+
+fn default_client_pool_max() -> usize {
+    16
+}
+
+fn default_client_pool_idle_secs() -> u64 {
+    60
+}
+
+fn default_max_concurrent_creations() -> usize {
+    3
+}
+
+fn default_creation_queue_limit() -> usize {
+    20
+}
+
+fn default_response_expiration_max_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_response_retry_capacity() -> usize {
+    256
+}
+
+fn default_response_retry_max_secs() -> u64 {
+    600
+}
+
+fn default_subscription_silence_secs() -> u64 {
+    300
+}
+
+fn default_subscription_reconnect_after() -> u32 {
+    3
+}
+
+fn default_join_request_ttl_secs() -> u64 {
+    72 * 60 * 60
+}
+
+fn default_join_request_sweep_interval_secs() -> u64 {
+    600
+}
+
+fn default_join_notification_window_secs() -> u64 {
+    60
+}
+
+fn default_request_deadline_secs() -> u64 {
+    8
+}
+
+fn default_admin_audit_interval_secs() -> u64 {
+    3600
+}
+
+fn default_orphan_quarantine_secs() -> u64 {
+    86400
+}
+
+fn default_orphan_idle_days() -> u64 {
+    30
+}
+
+fn default_inactive_prune_interval_secs() -> u64 {
+    6 * 60 * 60
+}
+
+fn default_event_stream_max_connections() -> usize {
+    512
+}
+
+fn default_event_stream_heartbeat_secs() -> u64 {
+    15
+}
+
+fn default_metadata_max_event_bytes() -> usize {
+    4096
+}
+
+fn default_preview_miss_limit() -> usize {
+    20
+}
+
+fn default_probe_rejection_threshold() -> usize {
+    10
+}
+
+fn default_probe_window_secs() -> u64 {
+    3600
+}
+
+fn default_probe_jitter_max_ms() -> u64 {
+    3000
+}
+
+fn default_far_scan_radius_km() -> u64 {
+    50
+}
+
+fn default_sticker_reuse_min_failures() -> u32 {
+    10
+}
+
+fn default_export_daily_limit() -> usize {
+    3
+}
+
+fn default_export_inline_max_bytes() -> usize {
+    32 * 1024
+}
+
+fn default_bulk_add_allowance() -> usize {
+    200
+}
+
+fn default_bulk_add_per_second() -> usize {
+    5
+}
+
+fn default_app_url() -> String {
+    "https://peek.hol.is".to_string()
+}
+
+fn default_relay_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_relay_circuit_cool_down_secs() -> u64 {
+    30
+}
+
+fn default_nip29_previous_refs() -> usize {
+    3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::ToBech32;
+
+    const SERVICE_KEY: &str = "3f7a1b2c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f8";
+
+    fn config_with_keys(service: &str, relay: &str) -> Config {
+        Config {
+            service_secret_key: SecretString::from(service),
+            relay_secret_key: SecretString::from(relay),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_config_debug_never_shows_key_material() {
+        let relay = Keys::generate();
+        let relay_nsec = relay.secret_key().to_bech32().unwrap();
+        let config = config_with_keys(SERVICE_KEY, &relay_nsec);
+
+        for printed in [format!("{:?}", config), format!("{:#?}", config)] {
+            assert!(!printed.contains(SERVICE_KEY));
+            assert!(!printed.contains(&relay_nsec));
+            assert!(!printed.contains(&relay.secret_key().to_secret_hex()));
+            assert!(printed.contains("[redacted]"));
+        }
+    }
+
+    #[test]
+    fn test_take_keys_parses_once_and_clears_the_config() {
+        let relay = Keys::generate();
+        let mut config = config_with_keys(SERVICE_KEY, &relay.secret_key().to_bech32().unwrap());
+
+        let keys = config.take_keys().unwrap();
+        assert_eq!(keys.relay.public_key(), relay.public_key());
+        assert_eq!(keys.service.secret_key().to_secret_hex(), SERVICE_KEY);
+        assert!(config.service_secret_key.is_empty());
+        assert!(config.relay_secret_key.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_keys_are_reported_without_their_value() {
+        let mut config = config_with_keys("not-a-key-but-secret", SERVICE_KEY);
+        let err = config.take_keys().err().unwrap();
+        assert_eq!(err, InvalidSecretKey::Service);
+        assert!(!err.to_string().contains("not-a-key-but-secret"));
+        // Both raw strings are dropped even when parsing fails
+        assert!(config.relay_secret_key.is_empty());
+    }
 }
@@ -0,0 +1,78 @@
+//! Local NIP-29 relay for frontend development, so no real groups relay or hosted keys are needed
+//!
+//! Serves the in-memory relay from `fake_relay` on ws://localhost:7777 (`--port` to change).
+//! Its key is RELAY_SECRET_KEY when set, so the service's .env works unchanged; otherwise a
+//! fresh key is generated and printed. `--seed` pre-creates a couple of communities whose
+//! admin key is printed too.
+//!
+//! `cargo run --bin dev_relay -- --seed`, then run the service with RELAY_URL=ws://localhost:7777.
+
+use nostr_sdk::prelude::*;
+use validation_service::fake_relay::FakeRelay;
+use validation_service::models::ProtocolConfig;
+
+const DEFAULT_PORT: u16 = 7777;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "validation_service=info".into()),
+        )
+        .init();
+
+    let mut port = DEFAULT_PORT;
+    let mut seed = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = true,
+            "--port" => {
+                port = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("--port needs a port number")?
+            }
+            other => return Err(format!("Unknown argument {}", other).into()),
+        }
+    }
+
+    let keys = match std::env::var("RELAY_SECRET_KEY") {
+        Ok(secret) => Keys::parse(&zeroize::Zeroizing::new(secret))?,
+        Err(_) => {
+            let keys = Keys::generate();
+            println!(
+                "RELAY_SECRET_KEY unset, generated one; give the service RELAY_SECRET_KEY={}",
+                keys.secret_key().to_secret_hex()
+            );
+            keys
+        }
+    };
+    let relay = FakeRelay::new(keys);
+
+    if seed {
+        let protocol: ProtocolConfig = envy::from_env()?;
+        let admin = Keys::generate();
+        println!(
+            "Seeded communities, admin nsec {}",
+            admin.secret_key().to_bech32()?
+        );
+        for community in relay.seed(&protocol, admin.public_key()) {
+            println!(
+                "  {} ({}): community {}",
+                community.name, community.group_id, community.community_id
+            );
+        }
+    }
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+    println!(
+        "Dev relay {} listening on ws://localhost:{}",
+        relay.public_key().to_bech32()?,
+        port
+    );
+    relay.serve(listener).await?;
+    Ok(())
+}
@@ -0,0 +1,772 @@
+//! In-memory NIP-29 relay for local development and integration tests
+//!
+//! Speaks just enough NIP-01 (EVENT, REQ, CLOSE), NIP-42 (AUTH) and NIP-29 for the service's
+//! create, join and admin flows. Management events (kinds 9000-9009) signed by the relay key
+//! or a group admin change the group, and after each one the relay re-signs the group's 39000
+//! (metadata), 39001 (admins) and 39002 (members) events with its own key, like groups_relay.
+//! Every other event is stored as is, replaceable and addressable kinds keeping only their
+//! latest version. Nothing is persisted and AUTH is offered but never required.
+//!
+//! `cargo run --bin dev_relay` serves it on its own; tests start one in-process with `spawn`.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::{SinkExt, StreamExt};
+use nostr_sdk::prelude::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::libraries::display_location::generate_display_location;
+use crate::models::{Coordinates, ProtocolConfig};
+
+/// Events queued for each live subscriber before it starts missing some
+const LIVE_CAPACITY: usize = 1024;
+
+/// Largest filter limit served, advertised in the NIP-11 document
+const MAX_LIMIT: usize = 5000;
+
+/// Communities created by `seed`: name, latitude, longitude
+const SEED_COMMUNITIES: &[(&str, f64, f64)] = &[
+    ("Dev Coffee", 37.7749, -122.4194),
+    ("Dev Park", 37.7694, -122.4862),
+];
+
+/// A NIP-29 group as the relay tracks it
+#[derive(Debug, Clone, Default)]
+struct Group {
+    // Tags of the latest kind 9002 edit, without its h and previous tags
+    metadata: Vec<Tag>,
+    admins: BTreeSet<PublicKey>,
+    members: BTreeSet<PublicKey>,
+}
+
+#[derive(Default)]
+struct Store {
+    events: Vec<Event>,
+    groups: BTreeMap<String, Group>,
+    // REQ filters received per kind they asked for
+    reqs: BTreeMap<u16, usize>,
+}
+
+/// A community pre-created by `seed`
+#[derive(Debug, Clone)]
+pub struct SeededCommunity {
+    pub community_id: Uuid,
+    pub group_id: String,
+    pub name: String,
+}
+
+pub struct FakeRelay {
+    keys: Keys,
+    store: Mutex<Store>,
+    live: broadcast::Sender<Event>,
+    // Added before answering each client message, like a distant relay
+    latency: Mutex<Duration>,
+}
+
+impl FakeRelay {
+    /// A relay signing its group state with `keys`; the service's RELAY_SECRET_KEY must match
+    pub fn new(keys: Keys) -> Arc<Self> {
+        Arc::new(Self {
+            keys,
+            store: Mutex::new(Store::default()),
+            live: broadcast::channel(LIVE_CAPACITY).0,
+            latency: Mutex::new(Duration::ZERO),
+        })
+    }
+
+    /// Answer every later client message only after `latency`
+    /// Messages on one connection are answered in order, so the delays add up
+    pub fn set_latency(&self, latency: Duration) {
+        *self.latency.lock().unwrap() = latency;
+    }
+
+    /// REQ filters received so far that asked for `kind`
+    pub fn reqs_for_kind(&self, kind: u16) -> usize {
+        let store = self.store.lock().unwrap();
+        store.reqs.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.keys.public_key()
+    }
+
+    /// WebSocket relay and NIP-11 document on `/`
+    pub fn router(self: &Arc<Self>) -> Router {
+        Router::new().route("/", get(root)).with_state(self.clone())
+    }
+
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> std::io::Result<()> {
+        axum::serve(listener, self.router()).await
+    }
+
+    /// Serve on an ephemeral local port in the background and return its ws:// URL
+    pub async fn spawn(self: Arc<Self>) -> std::io::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}", listener.local_addr()?);
+        tokio::spawn(self.serve(listener));
+        Ok(url)
+    }
+
+    /// Members of a group, or None if it does not exist
+    pub fn members(&self, group_id: &str) -> Option<BTreeSet<PublicKey>> {
+        let store = self.store.lock().unwrap();
+        store
+            .groups
+            .get(group_id)
+            .map(|group| group.members.clone())
+    }
+
+    /// Events stored so far, the relay's own group state events included
+    pub fn event_count(&self) -> usize {
+        self.store.lock().unwrap().events.len()
+    }
+
+    /// Admins of a group, or None if it does not exist
+    pub fn admins(&self, group_id: &str) -> Option<BTreeSet<PublicKey>> {
+        let store = self.store.lock().unwrap();
+        store.groups.get(group_id).map(|group| group.admins.clone())
+    }
+
+    /// Delete a group and its state events directly, as an operator's own tooling might
+    pub fn delete_group(&self, group_id: &str) {
+        remove_group(&mut self.store.lock().unwrap(), group_id);
+    }
+
+    /// Add a tag to a group's metadata directly, as an operator's own tooling might
+    pub fn add_metadata_tag(&self, group_id: &str, tag: Tag) {
+        let mut store = self.store.lock().unwrap();
+        let Some(group) = store.groups.get_mut(group_id) else {
+            return;
+        };
+        group.metadata.push(tag);
+        for event in self.group_state(&store, group_id) {
+            store_event(&mut store, event);
+        }
+    }
+
+    /// Pre-create a group with exactly `metadata`, e.g. one from before a tag was introduced,
+    /// with `admin` as its only member
+    pub fn seed_group(&self, group_id: &str, metadata: Vec<Tag>, admin: PublicKey) {
+        let group = Group {
+            metadata,
+            admins: BTreeSet::from([admin]),
+            members: BTreeSet::from([admin]),
+        };
+        let mut store = self.store.lock().unwrap();
+        store.groups.insert(group_id.to_string(), group);
+        for event in self.group_state(&store, group_id) {
+            store_event(&mut store, event);
+        }
+    }
+
+    /// Stored events of `kind`, oldest first
+    pub fn events(&self, kind: u16) -> Vec<Event> {
+        let store = self.store.lock().unwrap();
+        store
+            .events
+            .iter()
+            .filter(|event| event.kind.as_u16() == kind)
+            .cloned()
+            .collect()
+    }
+
+    /// Pre-create communities the way the service would, with `admin` as their only member
+    pub fn seed(&self, protocol: &ProtocolConfig, admin: PublicKey) -> Vec<SeededCommunity> {
+        SEED_COMMUNITIES
+            .iter()
+            .enumerate()
+            .map(|(index, (name, latitude, longitude))| {
+                let community_id = Uuid::from_u128(0x4000_8000_0000_0000_0001 + index as u128);
+                let group_id = format!("{}seed{}", protocol.group_id_prefix, index + 1);
+                let location =
+                    Coordinates::new(*latitude, *longitude).expect("seed coordinates are valid");
+                let metadata = vec![
+                    Tag::custom(TagKind::Custom("name".into()), [name.to_string()]),
+                    Tag::custom(
+                        TagKind::Custom("about".into()),
+                        ["Location-based community".to_string()],
+                    ),
+                    Tag::custom(TagKind::Custom("private".into()), Vec::<String>::new()),
+                    Tag::custom(TagKind::Custom("closed".into()), Vec::<String>::new()),
+                    Tag::custom(
+                        TagKind::Custom("g".into()),
+                        [location.geohash(8).expect("seed coordinates encode")],
+                    ),
+                    Tag::custom(
+                        TagKind::Custom("dg".into()),
+                        [generate_display_location(location).expect("seed coordinates encode")],
+                    ),
+                    Tag::custom(
+                        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+                        [protocol.uuid_tag(&community_id)],
+                    ),
+                    Tag::custom(
+                        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K)),
+                        [protocol.uuid_namespace.clone()],
+                    ),
+                ];
+                self.seed_group(&group_id, metadata, admin);
+                SeededCommunity {
+                    community_id,
+                    group_id,
+                    name: name.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    /// NIP-11 information document
+    fn information(&self) -> Value {
+        json!({
+            "name": "peek dev relay",
+            "description": "In-memory NIP-29 relay for local Peek development",
+            "pubkey": self.keys.public_key().to_hex(),
+            "supported_nips": [1, 11, 29, 42],
+            "software": "validation-service fake_relay",
+            "limitation": {
+                "max_limit": MAX_LIMIT,
+                "auth_required": false,
+            },
+        })
+    }
+
+    /// Store an event, applying NIP-29 management events to their group first
+    /// The error is the OK message, prefixed per NIP-01 (`invalid:`, `restricted:`, ...)
+    fn publish(&self, event: Event) -> Result<(), String> {
+        if event.verify().is_err() {
+            return Err("invalid: bad signature".to_string());
+        }
+        let kind = event.kind.as_u16();
+        if kind == 22242 {
+            return Err("invalid: AUTH events go in an AUTH message".to_string());
+        }
+        if (20000..30000).contains(&kind) {
+            let _ = self.live.send(event);
+            return Ok(());
+        }
+
+        let mut store = self.store.lock().unwrap();
+        if store.events.iter().any(|stored| stored.id == event.id) {
+            return Ok(());
+        }
+        let group_id = tag_value(&event, "h").map(str::to_string);
+        let mut changed_group = None;
+        if (9000..=9009).contains(&kind) {
+            let group_id = group_id.ok_or("invalid: missing h tag")?;
+            self.manage(&mut store, &group_id, &event)?;
+            changed_group = Some(group_id);
+        } else if let Some(group) = group_id.and_then(|id| store.groups.get(&id)) {
+            // Anyone may ask to join (9021) or leave (9022); everything else is members only
+            let join_or_leave = kind == 9021 || kind == 9022;
+            if !join_or_leave
+                && event.pubkey != self.keys.public_key()
+                && !group.members.contains(&event.pubkey)
+            {
+                return Err("restricted: not a member of this group".to_string());
+            }
+        }
+
+        let mut published = vec![event.clone()];
+        store_event(&mut store, event);
+        if let Some(group_id) = changed_group {
+            for state in self.group_state(&store, &group_id) {
+                published.push(state.clone());
+                store_event(&mut store, state);
+            }
+        }
+        drop(store);
+        for event in published {
+            let _ = self.live.send(event);
+        }
+        Ok(())
+    }
+
+    /// Apply a NIP-29 management event to its group
+    fn manage(&self, store: &mut Store, group_id: &str, event: &Event) -> Result<(), String> {
+        let kind = event.kind.as_u16();
+        if kind == 9007 {
+            if store.groups.contains_key(group_id) {
+                return Err("duplicate: group already exists".to_string());
+            }
+            store.groups.insert(
+                group_id.to_string(),
+                Group {
+                    metadata: Vec::new(),
+                    admins: BTreeSet::from([event.pubkey]),
+                    members: BTreeSet::from([event.pubkey]),
+                },
+            );
+            return Ok(());
+        }
+
+        let relay_pubkey = self.keys.public_key();
+        let group = store
+            .groups
+            .get_mut(group_id)
+            .ok_or("invalid: group not found")?;
+        if event.pubkey != relay_pubkey && !group.admins.contains(&event.pubkey) {
+            return Err("restricted: only group admins can do this".to_string());
+        }
+        match kind {
+            // put-user: roles after the pubkey replace the user's current ones
+            9000 => {
+                for (pubkey, roles) in tagged_users(event) {
+                    group.members.insert(pubkey);
+                    if roles.iter().any(|role| role == "admin") {
+                        group.admins.insert(pubkey);
+                    } else {
+                        group.admins.remove(&pubkey);
+                    }
+                }
+            }
+            // remove-user
+            9001 => {
+                for (pubkey, _) in tagged_users(event) {
+                    group.members.remove(&pubkey);
+                    group.admins.remove(&pubkey);
+                }
+            }
+            // edit-metadata replaces every metadata tag
+            9002 => {
+                group.metadata = event
+                    .tags
+                    .iter()
+                    .filter(|tag| {
+                        !matches!(
+                            tag.as_slice().first().map(String::as_str),
+                            Some("h" | "previous")
+                        )
+                    })
+                    .cloned()
+                    .collect();
+            }
+            // delete-event
+            9005 => {
+                let deleted: BTreeSet<&str> = event
+                    .tags
+                    .iter()
+                    .filter_map(|tag| match tag.as_slice() {
+                        [name, id, ..] if name == "e" => Some(id.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                store
+                    .events
+                    .retain(|stored| !deleted.contains(stored.id.to_hex().as_str()));
+            }
+            // delete-group takes its state events with it
+            9008 => remove_group(store, group_id),
+            // Role and invite events are kept but change nothing here
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Signed 39000, 39001 and 39002 events for the group's current state
+    fn group_state(&self, store: &Store, group_id: &str) -> Vec<Event> {
+        let Some(group) = store.groups.get(group_id) else {
+            return Vec::new();
+        };
+        let user_tags = |users: &BTreeSet<PublicKey>, role: Option<&str>| {
+            users
+                .iter()
+                .map(|pubkey| {
+                    let mut values = vec![pubkey.to_hex()];
+                    values.extend(role.map(str::to_string));
+                    Tag::custom(TagKind::Custom("p".into()), values)
+                })
+                .collect::<Vec<_>>()
+        };
+        [
+            (39000, group.metadata.clone()),
+            (39001, user_tags(&group.admins, Some("admin"))),
+            (39002, user_tags(&group.members, None)),
+        ]
+        .into_iter()
+        .filter_map(|(kind, tags)| {
+            EventBuilder::new(Kind::from(kind), "")
+                .tag(Tag::identifier(group_id))
+                .tags(tags)
+                .sign_with_keys(&self.keys)
+                .ok()
+        })
+        .collect()
+    }
+
+    fn count_reqs(&self, filters: &[Filter]) {
+        let mut store = self.store.lock().unwrap();
+        for kind in filters
+            .iter()
+            .flat_map(|filter| filter.kinds.iter().flatten())
+        {
+            *store.reqs.entry(kind.as_u16()).or_default() += 1;
+        }
+    }
+
+    /// Stored events matching any filter, each filter's newest `limit` first
+    fn query(&self, filters: &[Filter]) -> Vec<Event> {
+        let store = self.store.lock().unwrap();
+        let mut seen = BTreeSet::new();
+        let mut results = Vec::new();
+        for filter in filters {
+            let mut matched: Vec<&Event> = store
+                .events
+                .iter()
+                .filter(|event| matches(filter, event))
+                .collect();
+            matched.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            let limit = filter.limit.unwrap_or(MAX_LIMIT).min(MAX_LIMIT);
+            for event in matched.into_iter().take(limit) {
+                if seen.insert(event.id) {
+                    results.push(event.clone());
+                }
+            }
+        }
+        results
+    }
+
+    /// One client connection: a NIP-42 challenge, then messages until either side closes
+    async fn connection(self: Arc<Self>, socket: WebSocket) {
+        let (mut sink, mut stream) = socket.split();
+        let mut live = self.live.subscribe();
+        let challenge = Uuid::new_v4().to_string();
+        let mut subscriptions: HashMap<String, Vec<Filter>> = HashMap::new();
+
+        let hello = json!(["AUTH", challenge]).to_string();
+        if sink.send(Message::Text(hello)).await.is_err() {
+            return;
+        }
+        loop {
+            let replies = tokio::select! {
+                incoming = stream.next() => match incoming {
+                    Some(Ok(Message::Text(text))) => {
+                        let latency = *self.latency.lock().unwrap();
+                        if !latency.is_zero() {
+                            tokio::time::sleep(latency).await;
+                        }
+                        self.handle(&text, &challenge, &mut subscriptions)
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
+                event = live.recv() => match event {
+                    Ok(event) => subscriptions
+                        .iter()
+                        .filter(|(_, filters)| filters.iter().any(|filter| matches(filter, &event)))
+                        .map(|(id, _)| json!(["EVENT", id, event]))
+                        .collect(),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("Dev relay subscriber missed {} live events", missed);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            for reply in replies {
+                if sink.send(Message::Text(reply.to_string())).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Replies to one client message
+    fn handle(
+        &self,
+        text: &str,
+        challenge: &str,
+        subscriptions: &mut HashMap<String, Vec<Filter>>,
+    ) -> Vec<Value> {
+        let Ok(Value::Array(message)) = serde_json::from_str::<Value>(text) else {
+            return vec![json!(["NOTICE", "invalid: message is not a JSON array"])];
+        };
+        let event = || {
+            message
+                .get(1)
+                .and_then(|value| serde_json::from_value::<Event>(value.clone()).ok())
+        };
+        match message.first().and_then(Value::as_str) {
+            Some("EVENT") => {
+                let Some(event) = event() else {
+                    return vec![json!(["NOTICE", "invalid: unreadable event"])];
+                };
+                let id = event.id;
+                match self.publish(event) {
+                    Ok(()) => vec![json!(["OK", id, true, ""])],
+                    Err(reason) => vec![json!(["OK", id, false, reason])],
+                }
+            }
+            Some("REQ") => {
+                let Some(subscription_id) = message.get(1).and_then(Value::as_str) else {
+                    return vec![json!(["NOTICE", "invalid: REQ without a subscription id"])];
+                };
+                let filters: Vec<Filter> = message[2..]
+                    .iter()
+                    .filter_map(|filter| serde_json::from_value(filter.clone()).ok())
+                    .collect();
+                self.count_reqs(&filters);
+                let mut replies: Vec<Value> = self
+                    .query(&filters)
+                    .into_iter()
+                    .map(|event| json!(["EVENT", subscription_id, event]))
+                    .collect();
+                replies.push(json!(["EOSE", subscription_id]));
+                subscriptions.insert(subscription_id.to_string(), filters);
+                replies
+            }
+            Some("CLOSE") => {
+                if let Some(subscription_id) = message.get(1).and_then(Value::as_str) {
+                    subscriptions.remove(subscription_id);
+                }
+                Vec::new()
+            }
+            Some("AUTH") => {
+                let Some(event) = event() else {
+                    return vec![json!(["NOTICE", "invalid: unreadable AUTH event"])];
+                };
+                let valid = event.kind == Kind::Authentication
+                    && event.verify().is_ok()
+                    && tag_value(&event, "challenge") == Some(challenge);
+                let reason = if valid {
+                    ""
+                } else {
+                    "auth-required: bad AUTH event"
+                };
+                vec![json!(["OK", event.id, valid, reason])]
+            }
+            _ => vec![json!(["NOTICE", "unsupported: unknown message type"])],
+        }
+    }
+}
+
+async fn root(State(relay): State<Arc<FakeRelay>>, ws: Option<WebSocketUpgrade>) -> Response {
+    match ws {
+        Some(ws) => ws
+            .on_upgrade(move |socket| relay.connection(socket))
+            .into_response(),
+        None => Json(relay.information()).into_response(),
+    }
+}
+
+/// Keep an event, replacing the older version of a replaceable or addressable one
+fn store_event(store: &mut Store, event: Event) {
+    let kind = event.kind.as_u16();
+    let replaceable = kind == 0 || kind == 3 || (10000..20000).contains(&kind);
+    let addressable = (30000..40000).contains(&kind);
+    if replaceable || addressable {
+        let identifier = event.tags.identifier().map(str::to_string);
+        store.events.retain(|stored| {
+            !(stored.kind == event.kind
+                && stored.pubkey == event.pubkey
+                && (replaceable || stored.tags.identifier() == identifier.as_deref()))
+        });
+    }
+    store.events.push(event);
+}
+
+/// Drop a group along with its 39000-39003 state events
+fn remove_group(store: &mut Store, group_id: &str) {
+    store.groups.remove(group_id);
+    store.events.retain(|stored| {
+        !((39000..=39003).contains(&stored.kind.as_u16())
+            && stored.tags.identifier() == Some(group_id))
+    });
+}
+
+/// First value of the first tag called `name`
+fn tag_value<'a>(event: &'a Event, name: &str) -> Option<&'a str> {
+    event.tags.iter().find_map(|tag| match tag.as_slice() {
+        [tag_name, value, ..] if tag_name == name => Some(value.as_str()),
+        _ => None,
+    })
+}
+
+/// Pubkeys in the event's p tags, with the roles listed after each
+fn tagged_users(event: &Event) -> Vec<(PublicKey, Vec<String>)> {
+    event
+        .tags
+        .iter()
+        .filter_map(|tag| match tag.as_slice() {
+            [name, pubkey, roles @ ..] if name == "p" => {
+                Some((PublicKey::from_hex(pubkey).ok()?, roles.to_vec()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// NIP-01 filter matching; search is not supported and matches nothing
+fn matches(filter: &Filter, event: &Event) -> bool {
+    filter
+        .ids
+        .as_ref()
+        .is_none_or(|ids| ids.contains(&event.id))
+        && filter
+            .authors
+            .as_ref()
+            .is_none_or(|authors| authors.contains(&event.pubkey))
+        && filter
+            .kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(&event.kind))
+        && filter.since.is_none_or(|since| event.created_at >= since)
+        && filter.until.is_none_or(|until| event.created_at <= until)
+        && filter.search.is_none()
+        && filter.generic_tags.iter().all(|(name, values)| {
+            event.tags.iter().any(|tag| match tag.as_slice() {
+                [tag_name, value, ..] => {
+                    tag_name.len() == 1
+                        && tag_name.starts_with(name.as_char())
+                        && values.contains(value)
+                }
+                _ => false,
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(keys: &Keys, kind: u16, tags: Vec<Tag>) -> Event {
+        EventBuilder::new(Kind::from(kind), "")
+            .tags(tags)
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    fn h(group_id: &str) -> Tag {
+        Tag::custom(TagKind::Custom("h".into()), [group_id])
+    }
+
+    fn p(pubkey: &PublicKey, role: &str) -> Tag {
+        Tag::custom(
+            TagKind::Custom("p".into()),
+            [pubkey.to_hex(), role.to_string()],
+        )
+    }
+
+    fn state(relay: &FakeRelay, kind: u16, group_id: &str) -> Option<Event> {
+        let filter = Filter::new().kind(Kind::from(kind)).identifier(group_id);
+        relay.query(&[filter]).into_iter().next()
+    }
+
+    #[test]
+    fn test_management_events_drive_group_state() {
+        let relay_keys = Keys::generate();
+        let relay = FakeRelay::new(relay_keys.clone());
+        let creator = Keys::generate().public_key();
+
+        relay
+            .publish(signed(&relay_keys, 9007, vec![h("peek-abc")]))
+            .unwrap();
+        relay
+            .publish(signed(
+                &relay_keys,
+                9000,
+                vec![h("peek-abc"), p(&creator, "admin")],
+            ))
+            .unwrap();
+        relay
+            .publish(signed(
+                &relay_keys,
+                9001,
+                vec![h("peek-abc"), p(&relay_keys.public_key(), "")],
+            ))
+            .unwrap();
+        relay
+            .publish(signed(
+                &relay_keys,
+                9002,
+                vec![
+                    h("peek-abc"),
+                    Tag::custom(TagKind::Custom("name".into()), ["Café"]),
+                    Tag::custom(TagKind::Custom("g".into()), ["9q8yyk8y"]),
+                ],
+            ))
+            .unwrap();
+
+        assert_eq!(relay.admins("peek-abc"), Some(BTreeSet::from([creator])));
+        assert_eq!(relay.members("peek-abc"), Some(BTreeSet::from([creator])));
+
+        let metadata = state(&relay, 39000, "peek-abc").unwrap();
+        assert_eq!(metadata.pubkey, relay_keys.public_key());
+        assert_eq!(tag_value(&metadata, "name"), Some("Café"));
+        assert_eq!(tag_value(&metadata, "g"), Some("9q8yyk8y"));
+        assert_eq!(tag_value(&metadata, "h"), None);
+        let members = state(&relay, 39002, "peek-abc").unwrap();
+        assert_eq!(tag_value(&members, "p"), Some(creator.to_hex().as_str()));
+        // Only the latest version of each state event is kept
+        assert_eq!(
+            relay.query(&[Filter::new().kind(Kind::from(39000))]).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_outsiders_cannot_manage_or_post() {
+        let relay_keys = Keys::generate();
+        let relay = FakeRelay::new(relay_keys.clone());
+        let outsider = Keys::generate();
+        relay
+            .publish(signed(&relay_keys, 9007, vec![h("peek-abc")]))
+            .unwrap();
+
+        let err = relay
+            .publish(signed(
+                &outsider,
+                9000,
+                vec![h("peek-abc"), p(&outsider.public_key(), "admin")],
+            ))
+            .unwrap_err();
+        assert!(err.starts_with("restricted:"));
+        let err = relay
+            .publish(signed(&outsider, 9, vec![h("peek-abc")]))
+            .unwrap_err();
+        assert!(err.starts_with("restricted:"));
+        let err = relay
+            .publish(signed(&relay_keys, 9007, vec![h("peek-abc")]))
+            .unwrap_err();
+        assert!(err.starts_with("duplicate:"));
+        let err = relay
+            .publish(signed(&relay_keys, 9000, vec![h("peek-missing")]))
+            .unwrap_err();
+        assert!(err.starts_with("invalid:"));
+    }
+
+    #[test]
+    fn test_filters_match_tags_and_limits() {
+        let keys = Keys::generate();
+        let relay = FakeRelay::new(keys.clone());
+        let protocol = ProtocolConfig::default();
+        let seeded = relay.seed(&protocol, keys.public_key());
+        assert_eq!(seeded.len(), SEED_COMMUNITIES.len());
+
+        let by_uuid = Filter::new().kind(Kind::from(39000)).custom_tag(
+            SingleLetterTag::lowercase(Alphabet::I),
+            protocol.uuid_tag(&seeded[1].community_id),
+        );
+        let found = relay.query(&[by_uuid]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].tags.identifier(),
+            Some(seeded[1].group_id.as_str())
+        );
+
+        let limited = Filter::new().kind(Kind::from(39002)).limit(1);
+        assert_eq!(relay.query(&[limited]).len(), 1);
+        let other_author = Filter::new()
+            .kind(Kind::from(39000))
+            .author(Keys::generate().public_key());
+        assert!(relay.query(&[other_author]).is_empty());
+    }
+}
@@ -0,0 +1,1867 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tracing::{error, info, warn};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use uuid::Uuid;
+
+use super::api_docs::{route_all, ErrorBody, RouteTable};
+use super::community_preview::PreviewState;
+use crate::models::Coordinates;
+use crate::services::admin_audit::{AdminFootprintAudit, AdminFootprintSource, FootprintReport};
+use crate::services::admin_jobs::{AdminJobs, Job, JobProgress};
+use crate::services::audit_trail::{
+    AuditAction, AuditActor, AuditEntry, AuditFilter, AuditOutcome, AuditTrail,
+};
+use crate::services::community_clusters::{
+    ClusterDefinitions, ClusterUpdateError, CommunityClusters,
+};
+use crate::services::execution::{ExecutionMode, MutationPlan};
+use crate::services::far_scans::{FarScanCount, FarScans};
+use crate::services::inactive_prune::{PruneLog, PruneRun};
+use crate::services::orphan_sweep::{OrphanSource, OrphanSweep};
+use crate::services::relay::{CommunityRefresh, GroupMetadata};
+use crate::services::relay_access::{DiscoveryPublisher, GroupReader, GroupWriter};
+use crate::services::sticker_reuse::{ReuseFlag, StickerReuse};
+use crate::services::venue_hours::lookup_venue_hours;
+
+/// Rebuilds and republishes the discovery map(s) from current group metadata
+pub trait DiscoveryMapRefresh: Send + Sync + 'static {
+    fn publish_discovery_map(
+        &self,
+        mode: ExecutionMode,
+    ) -> impl Future<Output = anyhow::Result<MutationPlan>> + Send;
+}
+
+impl DiscoveryMapRefresh for DiscoveryPublisher {
+    async fn publish_discovery_map(&self, mode: ExecutionMode) -> anyhow::Result<MutationPlan> {
+        let mut execution = self.execution(mode);
+        DiscoveryPublisher::publish_discovery_map(self, &mut execution).await?;
+        Ok(execution.into_plan())
+    }
+}
+
+/// Drops and reloads everything cached about one community
+pub trait CommunityCacheRefresh: Send + Sync + 'static {
+    fn refresh_community(
+        &self,
+        community_id: Uuid,
+    ) -> impl Future<Output = anyhow::Result<CommunityRefresh>> + Send;
+}
+
+/// The relay-side caches and the HTTP preview cache
+///
+/// A refresh also looks the venue's opening hours up again, since OSM may have gained or
+/// changed them since the community was created.
+pub struct CommunityCaches {
+    groups: GroupWriter,
+    previews: Arc<PreviewState<GroupReader>>,
+}
+
+impl CommunityCaches {
+    pub fn new(groups: GroupWriter, previews: Arc<PreviewState<GroupReader>>) -> Self {
+        Self { groups, previews }
+    }
+
+    /// Store the venue hours found at the community's first anchor, if they changed
+    async fn refresh_venue_hours(&self, group_id: &str, metadata: &mut GroupMetadata) {
+        let Some(location) = metadata
+            .geohash
+            .as_deref()
+            .and_then(|anchor| Coordinates::from_geohash(anchor).ok())
+        else {
+            return;
+        };
+        let hours = match lookup_venue_hours(&location).await {
+            Ok(hours) => hours,
+            Err(e) => {
+                warn!("Venue hours lookup for {} failed: {}", group_id, e);
+                return;
+            }
+        };
+        if hours == metadata.venue_hours {
+            return;
+        }
+        match self.groups.set_venue_hours(group_id, hours.as_ref()).await {
+            Ok(()) => metadata.venue_hours = hours,
+            Err(e) => warn!("Failed to store venue hours of {}: {}", group_id, e),
+        }
+    }
+}
+
+impl CommunityCacheRefresh for CommunityCaches {
+    async fn refresh_community(&self, community_id: Uuid) -> anyhow::Result<CommunityRefresh> {
+        let mut refresh = self
+            .groups
+            .reader()
+            .refresh_community(&community_id)
+            .await?;
+        if let Some((group_id, metadata)) = &mut refresh.reloaded {
+            self.refresh_venue_hours(group_id, metadata).await;
+        }
+        // Dropped last, so a preview loaded during the reload is not left behind
+        if self.previews.invalidate(&community_id).await {
+            refresh.purged.push("preview");
+        }
+        Ok(refresh)
+    }
+}
+
+/// A community as reloaded by a cache refresh, for the operator to confirm its state
+#[derive(Debug, Serialize, ToSchema)]
+struct RefreshedCommunity {
+    community_id: Uuid,
+    group_id: String,
+    purged: Vec<&'static str>,
+    name: String,
+    about: Option<String>,
+    member_count: u32,
+    is_public: bool,
+    is_open: bool,
+    archived: bool,
+    anchors: Vec<String>,
+    created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    venue_hours: Option<String>,
+}
+
+/// A refresh of a community the relay does not have; its stale cache entries are still dropped
+#[derive(Debug, Serialize, ToSchema)]
+struct MissingCommunity {
+    error: &'static str,
+    stale_entry_purged: bool,
+    purged: Vec<&'static str>,
+}
+
+/// Body of GET /api/admin/inactive-prune
+#[derive(Debug, Serialize, ToSchema)]
+struct PruneRunList {
+    runs: Vec<PruneRun>,
+}
+
+/// Body of GET /api/admin/far-scans
+#[derive(Debug, Serialize, ToSchema)]
+struct FarScanList {
+    communities: Vec<FarScanCount>,
+}
+
+/// Body of GET /api/admin/sticker-reuse
+#[derive(Debug, Serialize, ToSchema)]
+struct ReuseFlagList {
+    communities: Vec<ReuseFlag>,
+}
+
+/// Body of POST /api/admin/community/:uuid/sticker-reuse/clear
+#[derive(Debug, Serialize, ToSchema)]
+struct ClearedReuseFlag {
+    cleared: ReuseFlag,
+}
+
+/// Body of GET /api/admin/audit
+#[derive(Debug, Serialize, ToSchema)]
+struct AuditEntryList {
+    entries: Vec<AuditEntry>,
+}
+
+/// Bearer tokens for the admin API, each with the label its actions are audited under
+#[derive(Clone, Default)]
+pub struct AdminTokens {
+    // (label, token)
+    tokens: Vec<(String, String)>,
+}
+
+/// Why ADMIN_TOKENS was refused; never carries a token
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidAdminTokens {
+    #[error("entries must be label:token with neither part empty")]
+    Malformed,
+    #[error("token label {0} is used more than once")]
+    DuplicateLabel(String),
+    #[error("tokens labelled {0} and {1} are the same")]
+    DuplicateToken(String, String),
+}
+
+impl AdminTokens {
+    /// ADMIN_TOKENS entries, plus ADMIN_API_TOKEN labelled "default" when set
+    pub fn parse(
+        default_token: Option<&str>,
+        entries: &[String],
+    ) -> Result<Self, InvalidAdminTokens> {
+        let mut tokens: Vec<(String, String)> = Vec::new();
+        if let Some(token) = default_token.filter(|token| !token.is_empty()) {
+            tokens.push(("default".to_string(), token.to_string()));
+        }
+        for entry in entries.iter().map(|entry| entry.trim()) {
+            if entry.is_empty() {
+                continue;
+            }
+            let (label, token) = entry
+                .split_once(':')
+                .filter(|(label, token)| !label.is_empty() && !token.is_empty())
+                .ok_or(InvalidAdminTokens::Malformed)?;
+            if let Some((existing, _)) = tokens.iter().find(|(_, known)| known == token) {
+                return Err(InvalidAdminTokens::DuplicateToken(
+                    existing.clone(),
+                    label.to_string(),
+                ));
+            }
+            if tokens.iter().any(|(known, _)| known == label) {
+                return Err(InvalidAdminTokens::DuplicateLabel(label.to_string()));
+            }
+            tokens.push((label.to_string(), token.to_string()));
+        }
+        Ok(Self { tokens })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Labels only, for the startup log
+    pub fn labels(&self) -> Vec<&str> {
+        self.tokens
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+
+    /// Compares against every token in constant time, so timing tells neither which token
+    /// matched nor how much of one did
+    fn label_for(&self, presented: &str) -> Option<&str> {
+        let mut found = None;
+        for (label, token) in &self.tokens {
+            if bool::from(token.as_bytes().ct_eq(presented.as_bytes())) {
+                found = Some(label.as_str());
+            }
+        }
+        found
+    }
+}
+
+pub struct AdminState<S, D, O, C> {
+    audit: Arc<AdminFootprintAudit<S>>,
+    discovery: D,
+    sweep: Arc<OrphanSweep<O>>,
+    caches: C,
+    // Recent inactive member prune runs, shared with the scheduled task
+    prune_log: Arc<PruneLog>,
+    // Joins rejected as very far, per community, counted by the gift wrap handler
+    far_scans: Arc<FarScans>,
+    // Communities whose failed joins cluster away from their anchors, flagged by the handler
+    sticker_reuse: Arc<StickerReuse>,
+    // Audits, sweeps and republishes run here in the background
+    jobs: Arc<AdminJobs>,
+    // Who did what, shared with the gift wrap handler
+    audit_trail: Arc<AuditTrail>,
+    // Exclusive community clusters, enforced by the gift wrap handler on join
+    clusters: Arc<CommunityClusters>,
+    // Bearer tokens for the endpoints behind them; none disables those endpoints
+    tokens: AdminTokens,
+}
+
+impl<
+        S: AdminFootprintSource,
+        D: DiscoveryMapRefresh,
+        O: OrphanSource,
+        C: CommunityCacheRefresh,
+    > AdminState<S, D, O, C>
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        audit: Arc<AdminFootprintAudit<S>>,
+        discovery: D,
+        sweep: Arc<OrphanSweep<O>>,
+        caches: C,
+        prune_log: Arc<PruneLog>,
+        far_scans: Arc<FarScans>,
+        sticker_reuse: Arc<StickerReuse>,
+        jobs: Arc<AdminJobs>,
+        audit_trail: Arc<AuditTrail>,
+        clusters: Arc<CommunityClusters>,
+        tokens: AdminTokens,
+    ) -> Self {
+        Self {
+            audit,
+            discovery,
+            sweep,
+            caches,
+            prune_log,
+            far_scans,
+            sticker_reuse,
+            jobs,
+            audit_trail,
+            clusters,
+            tokens,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MutationQuery {
+    /// Do every read and report the events that would be published, without sending any
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Routes under /api/admin
+///
+/// GET /api/admin/relay-footprint is read-only, but lists groups the relay key administers, so
+/// it sits behind the admin bearer token. GET /api/admin/inactive-prune lists the recent
+/// inactive member prune runs, with who was warned and removed, so it sits behind the token
+/// too, as does GET /api/admin/far-scans, which counts joins per community from
+/// far outside it: many suggest a copied sticker or a group created at a spoofed spot. GET
+/// /api/admin/sticker-reuse lists communities whose failed joins cluster in one spot away from
+/// their anchors, also behind the token.
+///
+/// The mutating endpoints require the token and accept ?dry_run=true, which does every read and
+/// reports the events that would be published without sending any. Refreshing a community's
+/// caches and clearing its sticker reuse flag publish nothing, so they take no dry run.
+///
+/// Audits, orphan sweeps and discovery map refreshes can outlast an HTTP request, so they are
+/// queued as jobs: the POST answers 202 with the job, and GET /api/admin/jobs/:id (also behind
+/// the token) reports its progress and, once done, the result the POST used to return.
+///
+/// Every POST that gets past the token is recorded in the audit trail under the token's label,
+/// whether it succeeds, is rejected or fails; queued jobs are recorded once they finish. GET
+/// /api/admin/audit lists the trail, filtered by ?community_id= and a ?since= / ?until= range.
+///
+/// GET /api/admin/clusters lists the community clusters and PUT replaces them all, both behind
+/// the token. The PUT is validated before anything is stored, so it takes no dry run, and is
+/// audited like the POSTs.
+pub fn router<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    state: Arc<AdminState<S, D, O, C>>,
+) -> Router {
+    route_all(routes()).with_state(state)
+}
+
+/// The admin routes before their state is attached
+pub(crate) fn routes<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>() -> RouteTable<Arc<AdminState<S, D, O, C>>> {
+    vec![
+        (
+            "/api/admin/relay-footprint",
+            get(relay_footprint::<S, D, O, C>),
+        ),
+        (
+            "/api/admin/relay-footprint/audit",
+            post(run_audit::<S, D, O, C>),
+        ),
+        (
+            "/api/admin/discovery-map/refresh",
+            post(refresh_discovery_map::<S, D, O, C>),
+        ),
+        (
+            "/api/admin/orphan-groups/sweep",
+            post(sweep_orphan_groups::<S, D, O, C>),
+        ),
+        (
+            "/api/admin/community/:uuid/refresh",
+            post(refresh_community::<S, D, O, C>),
+        ),
+        (
+            "/api/admin/inactive-prune",
+            get(inactive_prune_log::<S, D, O, C>),
+        ),
+        ("/api/admin/far-scans", get(far_scans::<S, D, O, C>)),
+        (
+            "/api/admin/sticker-reuse",
+            get(sticker_reuse_flags::<S, D, O, C>),
+        ),
+        (
+            "/api/admin/community/:uuid/sticker-reuse/clear",
+            post(clear_sticker_reuse::<S, D, O, C>),
+        ),
+        ("/api/admin/jobs/:id", get(admin_job::<S, D, O, C>)),
+        ("/api/admin/audit", get(audit_entries::<S, D, O, C>)),
+        (
+            "/api/admin/clusters",
+            get(community_clusters::<S, D, O, C>).put(replace_clusters::<S, D, O, C>),
+        ),
+    ]
+}
+
+/// OpenAPI description of the admin routes
+#[derive(OpenApi)]
+#[openapi(paths(
+    relay_footprint,
+    run_audit,
+    refresh_discovery_map,
+    sweep_orphan_groups,
+    refresh_community,
+    inactive_prune_log,
+    far_scans,
+    sticker_reuse_flags,
+    clear_sticker_reuse,
+    admin_job,
+    audit_entries,
+    community_clusters,
+    replace_clusters,
+))]
+pub struct AdminApi;
+
+/// Groups the relay key still administers, as of the last audit pass
+#[utoipa::path(
+    get,
+    path = "/api/admin/relay-footprint",
+    tag = "admin",
+    operation_id = "relay_footprint",
+    responses(
+        (status = 200, description = "Report of the last audit pass", body = FootprintReport),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 503, description = "No audit has completed yet", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn relay_footprint<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(response) = authorize(&headers, &state.tokens) {
+        return response;
+    }
+    match state.audit.last_report() {
+        Some(report) => Json(report).into_response(),
+        None => error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No admin audit has completed yet",
+        ),
+    }
+}
+
+/// Run an audit pass now, retrying relay admin removals
+#[utoipa::path(
+    post,
+    path = "/api/admin/relay-footprint/audit",
+    tag = "admin",
+    operation_id = "run_relay_footprint_audit",
+    params(MutationQuery),
+    responses(
+        (status = 202, description = "Job queued; its result is the audit report with the removals sent", body = Job),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 503, description = "Too many admin jobs queued", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn run_audit<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    Query(query): Query<MutationQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let actor = match authorize(&headers, &state.tokens) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+
+    let mode = ExecutionMode::from_dry_run(query.dry_run);
+    let task = state.clone();
+    submit_job(
+        &state.jobs,
+        &state.audit_trail,
+        AuditAction::new(actor, "relay_footprint_audit", dry_run_params(&query)),
+        move |progress| async move {
+            let run = task
+                .audit
+                .run_reporting(mode, &|scan| progress.report(scan))
+                .await?;
+            info!(
+                "Admin audit via API ({:?}): {} group(s) flagged",
+                mode, run.report.flagged
+            );
+            Ok(serde_json::to_value(run)?)
+        },
+    )
+}
+
+/// Republish the discovery map(s)
+#[utoipa::path(
+    post,
+    path = "/api/admin/discovery-map/refresh",
+    tag = "admin",
+    operation_id = "refresh_discovery_map",
+    params(MutationQuery),
+    responses(
+        (status = 202, description = "Job queued; its result lists the map events published", body = Job),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 503, description = "Too many admin jobs queued", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn refresh_discovery_map<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    Query(query): Query<MutationQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let actor = match authorize(&headers, &state.tokens) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+
+    let mode = ExecutionMode::from_dry_run(query.dry_run);
+    let task = state.clone();
+    submit_job(
+        &state.jobs,
+        &state.audit_trail,
+        AuditAction::new(actor, "discovery_map_refresh", dry_run_params(&query)),
+        move |_| async move {
+            let plan = task.discovery.publish_discovery_map(mode).await?;
+            info!(
+                "Discovery map refresh via API ({:?}): {} event(s)",
+                mode,
+                plan.events.len()
+            );
+            Ok(serde_json::to_value(plan)?)
+        },
+    )
+}
+
+/// Delete groups left behind by duplicate or abandoned community creations
+#[utoipa::path(
+    post,
+    path = "/api/admin/orphan-groups/sweep",
+    tag = "admin",
+    operation_id = "sweep_orphan_groups",
+    params(MutationQuery),
+    responses(
+        (status = 202, description = "Job queued; its result is the sweep report with the deletions sent", body = Job),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 503, description = "Too many admin jobs queued", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn sweep_orphan_groups<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    Query(query): Query<MutationQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let actor = match authorize(&headers, &state.tokens) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+
+    let mode = ExecutionMode::from_dry_run(query.dry_run);
+    let task = state.clone();
+    submit_job(
+        &state.jobs,
+        &state.audit_trail,
+        AuditAction::new(actor, "orphan_group_sweep", dry_run_params(&query)),
+        move |progress| async move {
+            let run = task
+                .sweep
+                .run_reporting(mode, &|scan| progress.report(scan))
+                .await?;
+            info!(
+                "Orphan group sweep via API ({:?}): {} of {} group(s) orphaned",
+                mode,
+                run.report.orphans.len(),
+                run.report.scanned
+            );
+            Ok(serde_json::to_value(run)?)
+        },
+    )
+}
+
+/// Recent inactive member prune runs, oldest first, dry runs included
+#[utoipa::path(
+    get,
+    path = "/api/admin/inactive-prune",
+    tag = "admin",
+    operation_id = "inactive_prune_runs",
+    responses(
+        (status = 200, description = "Recent prune runs", body = PruneRunList),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn inactive_prune_log<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(response) = authorize(&headers, &state.tokens) {
+        return response;
+    }
+    Json(PruneRunList {
+        runs: state.prune_log.recent(),
+    })
+    .into_response()
+}
+
+/// Communities with joins from far outside them, most first, since the service started
+#[utoipa::path(
+    get,
+    path = "/api/admin/far-scans",
+    tag = "admin",
+    operation_id = "far_scans",
+    responses(
+        (status = 200, description = "Far scan counts per community", body = FarScanList),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn far_scans<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(response) = authorize(&headers, &state.tokens) {
+        return response;
+    }
+    Json(FarScanList {
+        communities: state.far_scans.counts(),
+    })
+    .into_response()
+}
+
+/// Communities flagged as a possible sticker reuse, most recently flagged first
+#[utoipa::path(
+    get,
+    path = "/api/admin/sticker-reuse",
+    tag = "admin",
+    operation_id = "sticker_reuse_flags",
+    responses(
+        (status = 200, description = "Flagged communities", body = ReuseFlagList),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn sticker_reuse_flags<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(response) = authorize(&headers, &state.tokens) {
+        return response;
+    }
+    Json(ReuseFlagList {
+        communities: state.sticker_reuse.flags(),
+    })
+    .into_response()
+}
+
+/// Drop a community's sticker reuse flag once the sticker has been checked, letting new members
+/// in again and counting its failed joins from scratch
+#[utoipa::path(
+    post,
+    path = "/api/admin/community/{uuid}/sticker-reuse/clear",
+    tag = "admin",
+    operation_id = "clear_sticker_reuse",
+    params(("uuid" = String, Path, description = "Community UUID")),
+    responses(
+        (status = 200, description = "The flag that was cleared", body = ClearedReuseFlag),
+        (status = 400, description = "Not a UUID", body = ErrorBody),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 404, description = "Community is not flagged", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn clear_sticker_reuse<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    Path(uuid): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let actor = match authorize(&headers, &state.tokens) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    let action = AuditAction::new(actor, "sticker_reuse_clear", community_params(&uuid));
+    let Ok(community_id) = Uuid::parse_str(&uuid) else {
+        return rejected(&state.audit_trail, action, "Invalid community id");
+    };
+    let action = action.community(&uuid);
+
+    match state.sticker_reuse.clear(&community_id) {
+        Some(flag) => {
+            info!(
+                "Sticker reuse flag cleared via API for {} ({} failed joins around {})",
+                community_id, flag.failures, flag.cluster
+            );
+            state.audit_trail.record(action, AuditOutcome::Ok, None);
+            Json(ClearedReuseFlag { cleared: flag }).into_response()
+        }
+        None => {
+            state.audit_trail.record(
+                action,
+                AuditOutcome::Rejected,
+                Some("Community is not flagged".to_string()),
+            );
+            error_response(StatusCode::NOT_FOUND, "Community is not flagged")
+        }
+    }
+}
+
+/// Status, progress and, once finished, the result or error of an admin job
+#[utoipa::path(
+    get,
+    path = "/api/admin/jobs/{id}",
+    tag = "admin",
+    operation_id = "admin_job",
+    params(("id" = String, Path, description = "Job id, as returned when it was queued")),
+    responses(
+        (status = 200, description = "The job", body = Job),
+        (status = 400, description = "Not a UUID", body = ErrorBody),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 404, description = "No such job, or it finished too long ago", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn admin_job<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(response) = authorize(&headers, &state.tokens) {
+        return response;
+    }
+    let Ok(id) = Uuid::parse_str(&id) else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid job id");
+    };
+
+    match state.jobs.get(&id) {
+        Some(job) => Json(job).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, "Job not found"),
+    }
+}
+
+/// Drop everything cached about one community and return it as reloaded from the relay
+#[utoipa::path(
+    post,
+    path = "/api/admin/community/{uuid}/refresh",
+    tag = "admin",
+    operation_id = "refresh_community",
+    params(("uuid" = String, Path, description = "Community UUID")),
+    responses(
+        (status = 200, description = "The community as reloaded", body = RefreshedCommunity),
+        (status = 400, description = "Not a UUID", body = ErrorBody),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 404, description = "No such community; stale entries were still purged", body = MissingCommunity),
+        (status = 503, description = "The relay could not be read", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn refresh_community<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    Path(uuid): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let actor = match authorize(&headers, &state.tokens) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    let action = AuditAction::new(actor, "community_cache_refresh", community_params(&uuid));
+    let Ok(community_id) = Uuid::parse_str(&uuid) else {
+        return rejected(&state.audit_trail, action, "Invalid community id");
+    };
+    let action = action.community(&uuid);
+
+    match state.caches.refresh_community(community_id).await {
+        Ok(CommunityRefresh {
+            purged,
+            reloaded: Some((group_id, metadata)),
+        }) => {
+            info!(
+                "Cache refresh via API for {} ({}): purged {:?}",
+                community_id, group_id, purged
+            );
+            state
+                .audit_trail
+                .record(action.group(&group_id), AuditOutcome::Ok, None);
+            Json(RefreshedCommunity {
+                community_id,
+                group_id,
+                purged,
+                name: metadata.name,
+                about: metadata.about,
+                member_count: metadata.member_count,
+                is_public: metadata.is_public,
+                is_open: metadata.is_open,
+                archived: metadata.archived,
+                anchors: metadata.anchors,
+                created_at: metadata.created_at.as_u64(),
+                venue_hours: metadata
+                    .venue_hours
+                    .map(|hours| hours.opening_hours().to_string()),
+            })
+            .into_response()
+        }
+        // Stale entries for a community the relay no longer has are still purged
+        Ok(CommunityRefresh {
+            purged,
+            reloaded: None,
+        }) => {
+            state.audit_trail.record(
+                action,
+                AuditOutcome::Rejected,
+                Some("Community not found".to_string()),
+            );
+            (
+                StatusCode::NOT_FOUND,
+                Json(MissingCommunity {
+                    error: "Community not found",
+                    stale_entry_purged: !purged.is_empty(),
+                    purged,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!(
+                "❌ Cache refresh via API for {} failed: {}",
+                community_id, e
+            );
+            state
+                .audit_trail
+                .record(action, AuditOutcome::Failed, Some(e.to_string()));
+            error_response(StatusCode::SERVICE_UNAVAILABLE, "Community refresh failed")
+        }
+    }
+}
+
+/// Recorded admin actions, oldest first, for one community and/or a time range
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit",
+    tag = "admin",
+    operation_id = "audit_entries",
+    params(AuditFilter),
+    responses(
+        (status = 200, description = "Matching entries", body = AuditEntryList),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn audit_entries<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    Query(filter): Query<AuditFilter>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(response) = authorize(&headers, &state.tokens) {
+        return response;
+    }
+    Json(AuditEntryList {
+        entries: state.audit_trail.entries(&filter),
+    })
+    .into_response()
+}
+
+/// Community clusters, the exclusive ones limiting users to one of their communities
+#[utoipa::path(
+    get,
+    path = "/api/admin/clusters",
+    tag = "admin",
+    operation_id = "community_clusters",
+    responses(
+        (status = 200, description = "Every cluster", body = ClusterDefinitions),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn community_clusters<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(response) = authorize(&headers, &state.tokens) {
+        return response;
+    }
+    Json(state.clusters.definitions()).into_response()
+}
+
+/// Replace every community cluster; joins are checked against the new ones once stored
+#[utoipa::path(
+    put,
+    path = "/api/admin/clusters",
+    tag = "admin",
+    operation_id = "replace_community_clusters",
+    request_body = ClusterDefinitions,
+    responses(
+        (status = 200, description = "The clusters as stored", body = ClusterDefinitions),
+        (status = 400, description = "Invalid clusters, e.g. a duplicate name or community", body = ErrorBody),
+        (status = 401, description = "Missing or unknown admin token", body = ErrorBody),
+        (status = 403, description = "No admin token is configured", body = ErrorBody),
+        (status = 503, description = "The relay did not take the clusters", body = ErrorBody),
+    ),
+    security(("admin_token" = []))
+)]
+async fn replace_clusters<
+    S: AdminFootprintSource,
+    D: DiscoveryMapRefresh,
+    O: OrphanSource,
+    C: CommunityCacheRefresh,
+>(
+    State(state): State<Arc<AdminState<S, D, O, C>>>,
+    headers: HeaderMap,
+    Json(definitions): Json<ClusterDefinitions>,
+) -> Response {
+    let actor = match authorize(&headers, &state.tokens) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    let params = serde_json::to_value(&definitions).unwrap_or_default();
+    let action = AuditAction::new(actor, "community_clusters_replace", params);
+
+    match state.clusters.replace(definitions.clone()).await {
+        Ok(()) => {
+            info!(
+                "Community clusters replaced via API: {} cluster(s), {} exclusive",
+                definitions.clusters.len(),
+                definitions
+                    .clusters
+                    .iter()
+                    .filter(|cluster| cluster.exclusive)
+                    .count()
+            );
+            state.audit_trail.record(action, AuditOutcome::Ok, None);
+            Json(definitions).into_response()
+        }
+        Err(ClusterUpdateError::Invalid(e)) => rejected(&state.audit_trail, action, &e.to_string()),
+        Err(e @ ClusterUpdateError::Relay(_)) => {
+            error!("❌ Storing community clusters failed: {}", e);
+            state
+                .audit_trail
+                .record(action, AuditOutcome::Failed, Some(e.to_string()));
+            error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Storing the clusters failed",
+            )
+        }
+    }
+}
+
+/// The label of the token presented, as the actor to audit the request under
+fn authorize(headers: &HeaderMap, tokens: &AdminTokens) -> Result<AuditActor, Response> {
+    if tokens.is_empty() {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            "Admin API token not configured",
+        ));
+    }
+    let label = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|presented| tokens.label_for(presented));
+    match label {
+        Some(label) => Ok(AuditActor::Token(label.to_string())),
+        None => Err(error_response(
+            StatusCode::UNAUTHORIZED,
+            "Invalid admin token",
+        )),
+    }
+}
+
+fn dry_run_params(query: &MutationQuery) -> serde_json::Value {
+    serde_json::json!({ "dry_run": query.dry_run })
+}
+
+fn community_params(community_id: &str) -> serde_json::Value {
+    serde_json::json!({ "community_id": community_id })
+}
+
+/// Record `action` as rejected and answer 400
+fn rejected(trail: &AuditTrail, action: AuditAction, message: &str) -> Response {
+    trail.record(action, AuditOutcome::Rejected, Some(message.to_string()));
+    error_response(StatusCode::BAD_REQUEST, message)
+}
+
+/// Queue an admin job and answer 202 with it, or 503 when the queue is full
+///
+/// `action` is recorded in the audit trail once the job finishes, or at once if it is refused.
+fn submit_job<F, Fut>(
+    jobs: &AdminJobs,
+    trail: &Arc<AuditTrail>,
+    action: AuditAction,
+    work: F,
+) -> Response
+where
+    F: FnOnce(JobProgress) -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<serde_json::Value>> + Send + 'static,
+{
+    let kind = action.action;
+    let finished = (trail.clone(), action.clone());
+    let audited = move |progress| async move {
+        let (trail, action) = finished;
+        let result = work(progress).await;
+        match &result {
+            Ok(_) => trail.record(action, AuditOutcome::Ok, None),
+            Err(e) => trail.record(action, AuditOutcome::Failed, Some(e.to_string())),
+        }
+        result
+    };
+    match jobs.submit(kind, audited) {
+        Ok(job) => {
+            info!("Queued admin job {} ({})", job.id, kind);
+            (StatusCode::ACCEPTED, Json(job)).into_response()
+        }
+        Err(e) => {
+            error!("❌ Not queueing admin job {}: {}", kind, e);
+            trail.record(
+                action,
+                AuditOutcome::Rejected,
+                Some("Too many admin jobs queued".to_string()),
+            );
+            error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Too many admin jobs queued",
+            )
+        }
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::clock::SystemClock;
+    use crate::libraries::exclusion_zones::ExclusionZones;
+    use crate::libraries::relay_url::RelayUrl;
+    use crate::libraries::test_support::ManualClock;
+    use crate::models::ProtocolConfig;
+    use crate::services::admin_jobs::run_job_runner;
+    use crate::services::discovery_map::DiscoveryMaps;
+    use crate::services::execution::PlannedEvent;
+    use crate::services::inactive_prune::{PruneReport, PruneRun, PrunedMember};
+    use crate::services::orphan_sweep::{SweepCandidate, SweepPolicy};
+    use crate::services::previous_refs::PreviousRefs;
+    use crate::services::relay::RelayService;
+    use crate::services::relay_circuit::RelayCircuit;
+    use axum::http::HeaderValue;
+    use axum_test::TestServer;
+    use nostr_sdk::{EventBuilder, Keys, Kind, Tag, TagKind, Timestamp};
+    use std::time::Duration;
+    use validation_service::fake_relay::FakeRelay;
+
+    const TOKEN: &str = "s3cret";
+
+    fn bearer(token: &str) -> HeaderValue {
+        HeaderValue::from_str(&format!("Bearer {}", token)).unwrap()
+    }
+
+    struct StubbornRelay;
+
+    impl AdminFootprintSource for StubbornRelay {
+        async fn groups_with_relay_admin(&self) -> anyhow::Result<Vec<String>> {
+            Ok(vec!["peek-stubborn".to_string()])
+        }
+
+        async fn remove_relay_admin(
+            &self,
+            group_id: &str,
+            plan: &mut MutationPlan,
+        ) -> anyhow::Result<bool> {
+            plan.events.push(PlannedEvent {
+                kind: 9001,
+                group_id: Some(group_id.to_string()),
+                d_tag: None,
+                pubkeys: vec!["relay".to_string()],
+            });
+            Ok(false)
+        }
+    }
+
+    struct PlannedMaps;
+
+    impl DiscoveryMapRefresh for PlannedMaps {
+        async fn publish_discovery_map(&self, mode: ExecutionMode) -> anyhow::Result<MutationPlan> {
+            let mut plan = MutationPlan::new(mode);
+            plan.events.push(PlannedEvent {
+                kind: 30078,
+                group_id: None,
+                d_tag: Some("peek.discovery-map".to_string()),
+                pubkeys: Vec::new(),
+            });
+            Ok(plan)
+        }
+    }
+
+    /// Two groups for one sticker; the empty, newer one is the orphan
+    struct DuplicateGroups;
+
+    impl OrphanSource for DuplicateGroups {
+        async fn sweep_candidates(&self) -> anyhow::Result<Vec<SweepCandidate>> {
+            let group = |group_id: &str, created_at: u64, members: usize| SweepCandidate {
+                group_id: group_id.to_string(),
+                community_id: uuid::Uuid::from_u128(1),
+                created_at,
+                last_activity: created_at,
+                members,
+            };
+            Ok(vec![
+                group("peek-kept", 1_750_000_000, 3),
+                group("peek-dup", 1_750_000_060, 0),
+            ])
+        }
+
+        async fn delete_group(
+            &self,
+            group_id: &str,
+            plan: &mut MutationPlan,
+        ) -> anyhow::Result<()> {
+            plan.events.push(PlannedEvent {
+                kind: 9008,
+                group_id: Some(group_id.to_string()),
+                d_tag: None,
+                pubkeys: Vec::new(),
+            });
+            Ok(())
+        }
+    }
+
+    const KNOWN: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+    const DELETED: &str = "9b1f0c7e-5d2a-4c8e-b6f3-1a2b3c4d5e6f";
+
+    /// KNOWN is cached and on the relay; DELETED is only left in the mapping cache
+    struct CachedCommunities;
+
+    impl CommunityCacheRefresh for CachedCommunities {
+        async fn refresh_community(&self, community_id: Uuid) -> anyhow::Result<CommunityRefresh> {
+            if community_id == Uuid::parse_str(KNOWN).unwrap() {
+                let event = EventBuilder::new(Kind::from(39000), "")
+                    .tags([
+                        Tag::identifier("peek-3a7e5c59"),
+                        Tag::custom(TagKind::Name, ["Gate B Lounge"]),
+                        Tag::custom(TagKind::Custom("g".into()), ["9q8yyk8y"]),
+                    ])
+                    .custom_created_at(Timestamp::from(1_759_163_304))
+                    .sign_with_keys(&Keys::generate())
+                    .unwrap();
+                return Ok(CommunityRefresh {
+                    purged: vec!["group_mapping", "nearby_index", "preview"],
+                    reloaded: Some((
+                        "peek-3a7e5c59".to_string(),
+                        GroupMetadata::from_event(&event, 4),
+                    )),
+                });
+            }
+            let stale = community_id == Uuid::parse_str(DELETED).unwrap();
+            Ok(CommunityRefresh {
+                purged: if stale { vec!["group_mapping"] } else { vec![] },
+                reloaded: None,
+            })
+        }
+    }
+
+    /// Clusters stored on an in-process relay
+    async fn relay_clusters() -> Arc<CommunityClusters> {
+        let relay_keys = Keys::generate();
+        let url = FakeRelay::new(relay_keys.clone()).spawn().await.unwrap();
+        let protocol = ProtocolConfig::default();
+        let relay_service = RelayService::new(
+            RelayUrl::parse(&url).unwrap(),
+            relay_keys,
+            protocol.clone(),
+            Duration::from_millis(500),
+            DiscoveryMaps {
+                d_tag: protocol.discovery_map_d_tag.clone(),
+                prefixes: Vec::new(),
+                signer: None,
+                exclusion_zones: ExclusionZones::default(),
+            },
+            4096,
+            RelayCircuit::new(Arc::new(SystemClock), 0, Duration::from_secs(30)),
+            PreviousRefs::new(3),
+        )
+        .await
+        .unwrap();
+        Arc::new(CommunityClusters::new(DiscoveryPublisher::new(Arc::new(
+            relay_service,
+        ))))
+    }
+
+    async fn setup(token: Option<&str>) -> (Arc<AdminFootprintAudit<StubbornRelay>>, TestServer) {
+        setup_with(
+            token,
+            Arc::new(PruneLog::new(10)),
+            far_scans(),
+            sticker_reuse(),
+        )
+        .await
+    }
+
+    fn far_scans() -> Arc<FarScans> {
+        Arc::new(FarScans::new(50, Arc::new(ManualClock::new(1_760_000_000))))
+    }
+
+    fn sticker_reuse() -> Arc<StickerReuse> {
+        Arc::new(StickerReuse::new(
+            10,
+            Arc::new(ManualClock::new(1_760_000_000)),
+        ))
+    }
+
+    async fn setup_with(
+        token: Option<&str>,
+        prune_log: Arc<PruneLog>,
+        far_scans: Arc<FarScans>,
+        sticker_reuse: Arc<StickerReuse>,
+    ) -> (Arc<AdminFootprintAudit<StubbornRelay>>, TestServer) {
+        let tokens = AdminTokens::parse(token, &[]).unwrap();
+        let (audit, server, _) = setup_audited(tokens, prune_log, far_scans, sticker_reuse).await;
+        (audit, server)
+    }
+
+    async fn setup_audited(
+        tokens: AdminTokens,
+        prune_log: Arc<PruneLog>,
+        far_scans: Arc<FarScans>,
+        sticker_reuse: Arc<StickerReuse>,
+    ) -> (
+        Arc<AdminFootprintAudit<StubbornRelay>>,
+        TestServer,
+        Arc<ManualClock>,
+    ) {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let (audit_trail, _) = AuditTrail::new(clock.clone(), 100);
+        let (jobs, queue) = AdminJobs::new(clock.clone(), 10);
+        let jobs = Arc::new(jobs);
+        tokio::spawn(run_job_runner(jobs.clone(), queue));
+        let audit = Arc::new(AdminFootprintAudit::new(StubbornRelay, clock.clone()));
+        let sweep = Arc::new(OrphanSweep::new(
+            DuplicateGroups,
+            clock.clone(),
+            SweepPolicy {
+                quarantine_secs: 86400,
+                idle_secs: 30 * 86400,
+            },
+        ));
+        let state = AdminState::new(
+            audit.clone(),
+            PlannedMaps,
+            sweep,
+            CachedCommunities,
+            prune_log,
+            far_scans,
+            sticker_reuse,
+            jobs,
+            Arc::new(audit_trail),
+            relay_clusters().await,
+            tokens,
+        )
+        .await;
+        (
+            audit,
+            TestServer::new(router(Arc::new(state))).unwrap(),
+            clock,
+        )
+    }
+
+    /// Poll the job a POST queued until it finishes, and return it
+    async fn finished_job(
+        server: &TestServer,
+        queued: axum_test::TestResponse,
+    ) -> serde_json::Value {
+        queued.assert_status(StatusCode::ACCEPTED);
+        let id = queued.json::<serde_json::Value>()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        for _ in 0..100 {
+            let job = server
+                .get(&format!("/api/admin/jobs/{}", id))
+                .add_header(header::AUTHORIZATION, bearer(TOKEN))
+                .await
+                .json::<serde_json::Value>();
+            if job["status"] == "done" || job["status"] == "failed" {
+                return job;
+            }
+            tokio::task::yield_now().await;
+        }
+        panic!("admin job {} never finished", id);
+    }
+
+    #[tokio::test]
+    async fn test_footprint_served_after_first_audit() {
+        let (audit, server) = setup(Some(TOKEN)).await;
+
+        server
+            .get("/api/admin/relay-footprint")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+
+        audit.run().await.unwrap();
+        server
+            .get("/api/admin/relay-footprint")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        let response = server
+            .get("/api/admin/relay-footprint")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        response.assert_status_ok();
+        response.assert_json(&serde_json::json!({
+            "audited_at": 1_760_000_000u64,
+            "flagged": 1,
+            "removed": 0,
+            "remaining": ["peek-stubborn"]
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_audit_reports_plan_and_records_nothing() {
+        let (audit, server) = setup(Some(TOKEN)).await;
+
+        let queued = server
+            .post("/api/admin/relay-footprint/audit")
+            .add_query_param("dry_run", "true")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        let job = finished_job(&server, queued).await;
+        assert_eq!(job["status"], "done");
+        assert_eq!(
+            job["progress"],
+            serde_json::json!({ "total": 1, "processed": 1, "errors": 1 })
+        );
+        assert_eq!(
+            job["result"],
+            serde_json::json!({
+                "dry_run": true,
+                "events": [{ "kind": 9001, "group_id": "peek-stubborn", "pubkeys": ["relay"] }],
+                "report": {
+                    "audited_at": 1_760_000_000u64,
+                    "flagged": 1,
+                    "removed": 0,
+                    "remaining": ["peek-stubborn"]
+                }
+            })
+        );
+        assert_eq!(audit.last_report(), None);
+
+        let refresh = server
+            .post("/api/admin/discovery-map/refresh")
+            .add_query_param("dry_run", "true")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        let job = finished_job(&server, refresh).await;
+        assert_eq!(
+            job["result"],
+            serde_json::json!({
+                "dry_run": true,
+                "events": [{ "kind": 30078, "d_tag": "peek.discovery-map" }]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_orphan_sweep_reports_deletions() {
+        let (_, server) = setup(Some(TOKEN)).await;
+
+        let queued = server
+            .post("/api/admin/orphan-groups/sweep")
+            .add_query_param("dry_run", "true")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        let job = finished_job(&server, queued).await;
+        assert_eq!(job["kind"], "orphan_group_sweep");
+        assert_eq!(job["status"], "done");
+        assert_eq!(
+            job["result"],
+            serde_json::json!({
+                "dry_run": true,
+                "events": [{ "kind": 9008, "group_id": "peek-dup" }],
+                "report": {
+                    "swept_at": 1_760_000_000u64,
+                    "scanned": 2,
+                    "orphans": [{
+                        "group_id": "peek-dup",
+                        "community_id": "00000000-0000-0000-0000-000000000001",
+                        "members": 0,
+                        "reason": "duplicate",
+                        "survivor": "peek-kept"
+                    }],
+                    "failed": []
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_community_refresh_returns_fresh_values_or_404() {
+        let (_, server) = setup(Some(TOKEN)).await;
+
+        let response = server
+            .post(&format!("/api/admin/community/{}/refresh", KNOWN))
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        response.assert_status_ok();
+        response.assert_json(&serde_json::json!({
+            "community_id": KNOWN,
+            "group_id": "peek-3a7e5c59",
+            "purged": ["group_mapping", "nearby_index", "preview"],
+            "name": "Gate B Lounge",
+            "about": null,
+            "member_count": 4,
+            "is_public": false,
+            "is_open": false,
+            "archived": false,
+            "anchors": ["9q8yyk8y"],
+            "created_at": 1_759_163_304u64
+        }));
+
+        let deleted = server
+            .post(&format!("/api/admin/community/{}/refresh", DELETED))
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        deleted.assert_status(StatusCode::NOT_FOUND);
+        deleted.assert_json(&serde_json::json!({
+            "error": "Community not found",
+            "stale_entry_purged": true,
+            "purged": ["group_mapping"]
+        }));
+
+        let unknown = server
+            .post(&format!("/api/admin/community/{}/refresh", Uuid::new_v4()))
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        unknown.assert_status(StatusCode::NOT_FOUND);
+        assert_eq!(
+            unknown.json::<serde_json::Value>()["stale_entry_purged"],
+            false
+        );
+
+        server
+            .post("/api/admin/community/not-a-uuid/refresh")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .post(&format!("/api/admin/community/{}/refresh", KNOWN))
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_inactive_prune_log_lists_runs_behind_the_token() {
+        let log = Arc::new(PruneLog::new(10));
+        let (_, server) = setup_with(Some(TOKEN), log.clone(), far_scans(), sticker_reuse()).await;
+        server
+            .get("/api/admin/inactive-prune")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        log.record(PruneRun {
+            plan: MutationPlan::new(ExecutionMode::DryRun),
+            report: PruneReport {
+                ran_at: 1_760_000_000,
+                groups: 1,
+                warned: Vec::new(),
+                removed: vec![PrunedMember {
+                    group_id: "peek-quiet".to_string(),
+                    community_id: Uuid::from_u128(1),
+                    pubkey: "quiet".to_string(),
+                    inactive_days: 31,
+                }],
+                failed: Vec::new(),
+                skipped_groups: Vec::new(),
+            },
+        });
+        let runs = server
+            .get("/api/admin/inactive-prune")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(runs["runs"][0]["dry_run"], true);
+        assert_eq!(runs["runs"][0]["report"]["removed"][0]["pubkey"], "quiet");
+    }
+
+    #[tokio::test]
+    async fn test_far_scans_are_listed_behind_the_token() {
+        let far = far_scans();
+        let (_, server) = setup_with(
+            Some(TOKEN),
+            Arc::new(PruneLog::new(10)),
+            far.clone(),
+            sticker_reuse(),
+        )
+        .await;
+        server
+            .get("/api/admin/far-scans")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        let community_id = Uuid::parse_str(KNOWN).unwrap();
+        let tokyo = Coordinates::new(35.6762, 139.6503).unwrap();
+        for _ in 0..2 {
+            far.check(community_id, &tokyo, &["9q8yyk8y".to_string()]);
+        }
+        let listed = server
+            .get("/api/admin/far-scans")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(listed["communities"][0]["community_id"], KNOWN);
+        assert_eq!(listed["communities"][0]["far_scans"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_sticker_reuse_flags_are_listed_and_cleared_behind_the_token() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let reuse = Arc::new(StickerReuse::new(3, clock.clone()));
+        let (_, server) = setup_with(
+            Some(TOKEN),
+            Arc::new(PruneLog::new(10)),
+            far_scans(),
+            reuse.clone(),
+        )
+        .await;
+        server
+            .get("/api/admin/sticker-reuse")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        // Three people failing in Oakland over two days, 13km from the anchor
+        let community_id = Uuid::parse_str(KNOWN).unwrap();
+        let oakland = Coordinates::new(37.8044, -122.2712).unwrap();
+        for pubkey in ["a", "b", "c"] {
+            reuse.record_failure(community_id, pubkey, &oakland, Some(13_000.0));
+            clock.advance(86_400);
+        }
+        let listed = server
+            .get("/api/admin/sticker-reuse")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(listed["communities"][0]["community_id"], KNOWN);
+        assert_eq!(listed["communities"][0]["cluster"], "9q9p1");
+        assert_eq!(listed["communities"][0]["distance_km"], 13);
+
+        let clear = format!("/api/admin/community/{}/sticker-reuse/clear", KNOWN);
+        server
+            .post(&clear)
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        let cleared = server
+            .post(&clear)
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(cleared["cleared"]["failures"], 3);
+        assert!(!reuse.is_flagged(&community_id));
+        server
+            .post(&clear)
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_admin_actions_are_audited_per_token() {
+        let tokens = AdminTokens::parse(
+            None,
+            &["alice:s3cret".to_string(), "bob:hunter2".to_string()],
+        )
+        .unwrap();
+        let (_, server, clock) = setup_audited(
+            tokens,
+            Arc::new(PruneLog::new(10)),
+            far_scans(),
+            sticker_reuse(),
+        )
+        .await;
+        server
+            .post(&format!("/api/admin/community/{}/refresh", KNOWN))
+            .add_header(header::AUTHORIZATION, bearer("s3cret"))
+            .await
+            .assert_status_ok();
+        clock.advance(60);
+        // Refused actions are recorded too
+        server
+            .post(&format!(
+                "/api/admin/community/{}/sticker-reuse/clear",
+                KNOWN
+            ))
+            .add_header(header::AUTHORIZATION, bearer("hunter2"))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        server
+            .post("/api/admin/community/not-a-uuid/refresh")
+            .add_header(header::AUTHORIZATION, bearer("hunter2"))
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        clock.advance(60);
+        let queued = server
+            .post("/api/admin/discovery-map/refresh")
+            .add_query_param("dry_run", "true")
+            .add_header(header::AUTHORIZATION, bearer("hunter2"))
+            .await;
+        finished_job(&server, queued).await;
+        // Without a valid token there is nobody to attribute the attempt to
+        server
+            .post("/api/admin/discovery-map/refresh")
+            .add_header(header::AUTHORIZATION, bearer("wrong"))
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        let entries = server
+            .get("/api/admin/audit")
+            .add_header(header::AUTHORIZATION, bearer("s3cret"))
+            .await
+            .json::<serde_json::Value>()["entries"]
+            .clone();
+        let summary: Vec<(&str, &str, &str)> = entries
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                (
+                    entry["actor"]["token"].as_str().unwrap(),
+                    entry["action"].as_str().unwrap(),
+                    entry["outcome"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("alice", "community_cache_refresh", "ok"),
+                ("bob", "sticker_reuse_clear", "rejected"),
+                ("bob", "community_cache_refresh", "rejected"),
+                ("bob", "discovery_map_refresh", "ok"),
+            ]
+        );
+        assert_eq!(entries[0]["community_id"], KNOWN);
+        assert_eq!(entries[0]["group_id"], "peek-3a7e5c59");
+        assert_eq!(entries[1]["detail"], "Community is not flagged");
+        assert!(entries[2].get("community_id").is_none());
+
+        let for_known = server
+            .get("/api/admin/audit")
+            .add_query_param("community_id", KNOWN)
+            .add_header(header::AUTHORIZATION, bearer("hunter2"))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(for_known["entries"].as_array().unwrap().len(), 2);
+        let window = server
+            .get("/api/admin/audit")
+            .add_query_param("since", 1_760_000_030u64)
+            .add_query_param("until", 1_760_000_090u64)
+            .add_header(header::AUTHORIZATION, bearer("s3cret"))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(window["entries"].as_array().unwrap().len(), 2);
+        assert_eq!(window["entries"][0]["at"], 1_760_000_060u64);
+
+        server
+            .get("/api/admin/audit")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_clusters_are_replaced_and_listed_behind_the_token() {
+        let (_, server) = setup(Some(TOKEN)).await;
+        let derby = serde_json::json!({
+            "clusters": [{ "name": "derby", "communities": [KNOWN, DELETED], "exclusive": true }]
+        });
+        server
+            .put("/api/admin/clusters")
+            .json(&derby)
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        server
+            .get("/api/admin/clusters")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        // A community listed twice is refused and nothing changes
+        let refused = server
+            .put("/api/admin/clusters")
+            .json(&serde_json::json!({
+                "clusters": [{ "name": "derby", "communities": [KNOWN, KNOWN], "exclusive": true }]
+            }))
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await;
+        refused.assert_status(StatusCode::BAD_REQUEST);
+        assert!(refused.json::<serde_json::Value>()["error"]
+            .as_str()
+            .unwrap()
+            .contains(KNOWN));
+
+        server
+            .put("/api/admin/clusters")
+            .json(&derby)
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .assert_status_ok();
+        let listed = server
+            .get("/api/admin/clusters")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .json::<serde_json::Value>();
+        assert_eq!(listed, derby);
+
+        let audited = server
+            .get("/api/admin/audit")
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .json::<serde_json::Value>();
+        let outcomes: Vec<&str> = audited["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["outcome"].as_str().unwrap())
+            .collect();
+        assert_eq!(outcomes, vec!["rejected", "ok"]);
+        assert_eq!(
+            audited["entries"][1]["action"],
+            "community_clusters_replace"
+        );
+    }
+
+    #[test]
+    fn test_admin_tokens_parse_labels_and_refuse_ambiguity() {
+        let tokens = AdminTokens::parse(
+            Some("legacy"),
+            &[" alice:s3cret".to_string(), "".to_string()],
+        )
+        .unwrap();
+        assert_eq!(tokens.labels(), vec!["default", "alice"]);
+        assert_eq!(tokens.label_for("s3cret"), Some("alice"));
+        assert_eq!(tokens.label_for("legacy"), Some("default"));
+        assert_eq!(tokens.label_for("alice"), None);
+        assert!(AdminTokens::parse(Some(""), &[]).unwrap().is_empty());
+
+        let parse = |entries: &[&str]| {
+            AdminTokens::parse(
+                None,
+                &entries.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            )
+            .err()
+        };
+        assert_eq!(parse(&["alice"]), Some(InvalidAdminTokens::Malformed));
+        assert_eq!(parse(&[":s3cret"]), Some(InvalidAdminTokens::Malformed));
+        assert_eq!(
+            parse(&["alice:a", "alice:b"]),
+            Some(InvalidAdminTokens::DuplicateLabel("alice".to_string()))
+        );
+        assert_eq!(
+            parse(&["alice:same", "bob:same"]),
+            Some(InvalidAdminTokens::DuplicateToken(
+                "alice".to_string(),
+                "bob".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mutating_endpoints_require_token() {
+        let (_, server) = setup(Some(TOKEN)).await;
+        server
+            .post("/api/admin/discovery-map/refresh")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        server
+            .post("/api/admin/relay-footprint/audit")
+            .add_header(header::AUTHORIZATION, bearer("wrong"))
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        server
+            .post("/api/admin/orphan-groups/sweep")
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        server
+            .get(&format!("/api/admin/jobs/{}", Uuid::new_v4()))
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        server
+            .get(&format!("/api/admin/jobs/{}", Uuid::new_v4()))
+            .add_header(header::AUTHORIZATION, bearer(TOKEN))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        let (_, unconfigured) = setup(None).await;
+        unconfigured
+            .post("/api/admin/discovery-map/refresh")
+            .add_header(header::AUTHORIZATION, bearer(""))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+}
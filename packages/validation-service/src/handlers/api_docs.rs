@@ -0,0 +1,340 @@
+//! OpenAPI description of the HTTP API, for frontend and ops tooling to generate clients from
+//!
+//! Assembled from the utoipa annotations each handler module keeps next to its routes, so it
+//! moves with the code; a test fails when a route is served without being described. With
+//! ENABLE_DOCS it is served at /api/openapi.json, with Swagger UI at /api/docs. The payloads of
+//! gift-wrapped requests are not HTTP and are described by /api/schema instead.
+
+use axum::routing::MethodRouter;
+use axum::Router;
+use serde::Serialize;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::OpenApi as OpenApiDocument;
+use utoipa::{Modify, OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::admin::AdminApi;
+use super::community_events::EventsApi;
+use super::community_preview::PreviewApi;
+use super::discovery::DiscoveryApi;
+use super::listeners::MetricsApi;
+use super::schema::SchemaApi;
+use super::service_info::ServiceInfoApi;
+use super::HealthApi;
+
+/// Where the OpenAPI document is served
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+
+/// Where Swagger UI is served
+pub const DOCS_PATH: &str = "/api/docs";
+
+/// Security scheme of the endpoints behind an ADMIN_API_TOKEN / ADMIN_TOKENS bearer token
+pub const ADMIN_SECURITY_SCHEME: &str = "admin_token";
+
+/// Paths served by the same handler as a documented one: (alias, documented path, operation id)
+const ROUTE_ALIASES: &[(&str, &str, &str)] = &[
+    ("/health", "/api/health", "health_root"),
+    ("/api/service-pubkey", "/api/service-info", "service_pubkey"),
+    (
+        "/.well-known/peek.json",
+        "/api/service-info",
+        "well_known_service_info",
+    ),
+];
+
+/// A handler module's routes as (axum path, handlers) pairs
+///
+/// Routers are built from these tables, so the docs test checks the very paths the server
+/// routes rather than a copy of them.
+pub(crate) type RouteTable<S> = Vec<(&'static str, MethodRouter<S>)>;
+
+/// A router serving every route of `table`
+pub(crate) fn route_all<S: Clone + Send + Sync + 'static>(table: RouteTable<S>) -> Router<S> {
+    table
+        .into_iter()
+        .fold(Router::new(), |router, (path, handlers)| {
+            router.route(path, handlers)
+        })
+}
+
+/// The `{"error": ...}` body failed requests are answered with
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Peek validation service",
+        description = "Community previews, discovery, live community events and the operator admin API"
+    ),
+    modifiers(&AdminBearer),
+    tags(
+        (name = "communities", description = "Public views of one community"),
+        (name = "discovery", description = "Finding communities"),
+        (name = "service", description = "Health, capabilities and protocol description"),
+        (name = "admin", description = "Operator endpoints, behind the admin token"),
+    )
+)]
+struct ApiDoc;
+
+/// Declares the bearer token the admin paths refer to
+struct AdminBearer;
+
+impl Modify for AdminBearer {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                ADMIN_SECURITY_SCHEME,
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+    }
+}
+
+/// The whole HTTP API, public and admin routes alike
+pub fn openapi() -> OpenApiDocument {
+    let mut doc = ApiDoc::openapi();
+    // utoipa takes the license from Cargo.toml, which names none
+    doc.info.license = None;
+    for api in [
+        HealthApi::openapi(),
+        PreviewApi::openapi(),
+        EventsApi::openapi(),
+        DiscoveryApi::openapi(),
+        ServiceInfoApi::openapi(),
+        SchemaApi::openapi(),
+        MetricsApi::openapi(),
+        AdminApi::openapi(),
+    ] {
+        doc.merge(api);
+    }
+
+    for (alias, path, operation_id) in ROUTE_ALIASES {
+        let mut item = doc.paths.paths[*path].clone();
+        if let Some(operation) = item.get.as_mut() {
+            operation.operation_id = Some(operation_id.to_string());
+        }
+        doc.paths.paths.insert(alias.to_string(), item);
+    }
+    doc
+}
+
+/// Routes for GET /api/openapi.json and Swagger UI under /api/docs
+pub fn router() -> Router {
+    SwaggerUi::new(DOCS_PATH)
+        .url(OPENAPI_PATH, openapi())
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::{
+        admin, community_events, community_preview, discovery, health_routes, listeners, schema,
+        service_info,
+    };
+    use crate::services::relay_access::{DiscoveryPublisher, GroupReader, GroupWriter};
+    use axum_test::TestServer;
+    use serde_json::Value;
+    use std::collections::{BTreeSet, HashSet};
+
+    /// Paths of a route table, in OpenAPI's `{param}` form rather than axum's `:param`
+    fn route_paths<S>(table: RouteTable<S>) -> BTreeSet<String> {
+        table
+            .into_iter()
+            .map(|(path, _)| {
+                path.split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(param) => format!("{{{}}}", param),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect()
+    }
+
+    /// Every path main.rs serves, whichever server it ends up on
+    fn served_paths() -> BTreeSet<String> {
+        let mut paths = BTreeSet::new();
+        paths.extend(route_paths(health_routes()));
+        paths.extend(route_paths(community_preview::routes::<GroupReader>()));
+        paths.extend(route_paths(community_events::routes::<GroupReader>()));
+        paths.extend(route_paths(discovery::routes::<GroupReader>()));
+        paths.extend(route_paths(service_info::routes()));
+        paths.extend(route_paths(schema::routes()));
+        paths.extend(route_paths(listeners::metrics_routes()));
+        paths.extend(route_paths(admin::routes::<
+            GroupWriter,
+            DiscoveryPublisher,
+            GroupWriter,
+            admin::CommunityCaches,
+        >()));
+        paths
+    }
+
+    fn spec() -> Value {
+        serde_json::to_value(openapi()).unwrap()
+    }
+
+    fn operations(spec: &Value) -> Vec<(String, String, Value)> {
+        spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .iter()
+                    .map(move |(method, operation)| {
+                        (path.clone(), method.clone(), operation.clone())
+                    })
+            })
+            .collect()
+    }
+
+    fn refs(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(target)) = map.get("$ref") {
+                    found.push(target.clone());
+                }
+                map.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_served_route_is_documented() {
+        let documented: BTreeSet<String> = openapi().paths.paths.keys().cloned().collect();
+        let served = served_paths();
+        assert!(served.contains("/api/community/{uuid}/preview"));
+
+        let undocumented: Vec<_> = served.difference(&documented).collect();
+        assert!(
+            undocumented.is_empty(),
+            "undocumented routes: {:?}",
+            undocumented
+        );
+        let unserved: Vec<_> = documented.difference(&served).collect();
+        assert!(
+            unserved.is_empty(),
+            "documented but not served: {:?}",
+            unserved
+        );
+    }
+
+    #[test]
+    fn test_spec_is_well_formed() {
+        let spec = spec();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3.1"));
+        // Parses back as an OpenAPI document
+        serde_json::from_value::<OpenApiDocument>(spec.clone()).unwrap();
+
+        let mut targets = Vec::new();
+        refs(&spec, &mut targets);
+        assert!(!targets.is_empty());
+        for target in targets {
+            let name = target
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected reference {}", target));
+            assert!(
+                spec["components"]["schemas"].get(name).is_some(),
+                "{} is referenced but not defined",
+                name
+            );
+        }
+
+        let mut operation_ids = HashSet::new();
+        for (path, method, operation) in operations(&spec) {
+            let id = operation["operationId"].as_str().unwrap().to_string();
+            assert!(
+                operation_ids.insert(id.clone()),
+                "operation id {} is reused",
+                id
+            );
+            assert!(
+                !operation["responses"].as_object().unwrap().is_empty(),
+                "{} {} has no responses",
+                method,
+                path
+            );
+            let declared: HashSet<&str> = operation["parameters"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|param| param["in"] == "path")
+                .map(|param| param["name"].as_str().unwrap())
+                .collect();
+            for param in path
+                .split('/')
+                .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            {
+                assert!(
+                    declared.contains(param),
+                    "{} {} does not declare {}",
+                    method,
+                    path,
+                    param
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_admin_endpoints_require_the_bearer_token() {
+        let spec = spec();
+        assert_eq!(
+            spec["components"]["securitySchemes"][ADMIN_SECURITY_SCHEME],
+            serde_json::json!({ "type": "http", "scheme": "bearer" })
+        );
+
+        let admin: Vec<_> = operations(&spec)
+            .into_iter()
+            .filter(|(path, _, _)| path.starts_with("/api/admin/"))
+            .collect();
+        assert_eq!(admin.len(), 13);
+        for (path, method, operation) in admin {
+            assert_eq!(
+                operation["security"],
+                serde_json::json!([{ ADMIN_SECURITY_SCHEME: [] }]),
+                "{} {}",
+                method,
+                path
+            );
+        }
+        // Nothing public asks for it
+        assert!(operations(&spec)
+            .iter()
+            .filter(|(path, _, _)| !path.starts_with("/api/admin/"))
+            .all(|(_, _, operation)| operation["security"].is_null()));
+    }
+
+    #[test]
+    fn test_aliases_share_the_documented_operation() {
+        let spec = spec();
+        let info = &spec["paths"]["/api/service-info"]["get"];
+        let well_known = &spec["paths"]["/.well-known/peek.json"]["get"];
+        assert_eq!(well_known["responses"], info["responses"]);
+        assert_eq!(well_known["operationId"], "well_known_service_info");
+        assert_eq!(info["operationId"], "service_info");
+    }
+
+    #[tokio::test]
+    async fn test_spec_and_swagger_ui_are_served() {
+        let server = TestServer::new(router()).unwrap();
+
+        let response = server.get(OPENAPI_PATH).await;
+        response.assert_status_ok();
+        assert_eq!(response.json::<Value>(), spec());
+
+        let ui = server.get(&format!("{}/", DOCS_PATH)).await;
+        ui.assert_status_ok();
+        assert!(ui.text().contains("swagger"));
+    }
+}
@@ -0,0 +1,273 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Json, Router,
+};
+use futures::stream::{self, Stream};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, OwnedSemaphorePermit, Semaphore};
+use tracing::{error, info};
+use utoipa::OpenApi;
+
+use super::api_docs::{route_all, ErrorBody, RouteTable};
+use crate::libraries::community_id::CommunityIdPolicy;
+use crate::services::group_feed::{GroupDelta, GroupFeed, GroupFeedRelay, GroupWatch};
+
+pub struct EventsState<R> {
+    feed: Arc<GroupFeed<R>>,
+    // One permit per open SSE connection
+    connections: Arc<Semaphore>,
+    heartbeat: Duration,
+    id_policy: CommunityIdPolicy,
+}
+
+impl<R: GroupFeedRelay> EventsState<R> {
+    pub fn new(
+        feed: Arc<GroupFeed<R>>,
+        max_connections: usize,
+        heartbeat: Duration,
+        id_policy: CommunityIdPolicy,
+    ) -> Self {
+        Self {
+            feed,
+            connections: Arc::new(Semaphore::new(max_connections)),
+            heartbeat,
+            id_policy,
+        }
+    }
+}
+
+/// Route for GET /api/communities/:uuid/events (server-sent group change deltas)
+pub fn router<R: GroupFeedRelay>(state: Arc<EventsState<R>>) -> Router {
+    route_all(routes()).with_state(state)
+}
+
+/// The event stream route before its state is attached
+pub(crate) fn routes<R: GroupFeedRelay>() -> RouteTable<Arc<EventsState<R>>> {
+    vec![("/api/communities/:uuid/events", get(events_handler::<R>))]
+}
+
+/// OpenAPI description of the event stream route
+#[derive(OpenApi)]
+#[openapi(paths(events_handler))]
+pub struct EventsApi;
+
+/// Live changes to a community, as server-sent events
+///
+/// Each event's data is one JSON group delta. Deltas carry absolute values, so a client that
+/// falls behind and misses some is still current after the next one.
+#[utoipa::path(
+    get,
+    path = "/api/communities/{uuid}/events",
+    tag = "communities",
+    operation_id = "community_events",
+    params(("uuid" = String, Path, description = "Community UUID")),
+    responses(
+        (status = 200, description = "Stream of group deltas", content_type = "text/event-stream", body = GroupDelta),
+        (status = 404, description = "No such community", body = ErrorBody),
+        (status = 503, description = "Too many open streams, or the relay could not be read", body = ErrorBody),
+    )
+)]
+async fn events_handler<R: GroupFeedRelay>(
+    State(state): State<Arc<EventsState<R>>>,
+    Path(uuid): Path<String>,
+) -> Response {
+    let community_id = match state.id_policy.parse(&uuid) {
+        Ok(id) => id,
+        Err(_) => return not_found(),
+    };
+
+    let Ok(permit) = state.connections.clone().try_acquire_owned() else {
+        return unavailable("Too many open event streams, try again later");
+    };
+
+    let group_id = match state.feed.find_group(community_id).await {
+        Ok(Some(group_id)) => group_id,
+        Ok(None) => return not_found(),
+        Err(e) => {
+            error!(
+                "❌ Failed to resolve {} for event stream: {}",
+                community_id, e
+            );
+            return unavailable("Community events temporarily unavailable");
+        }
+    };
+
+    let watch = state.feed.watch(&group_id).await;
+    info!(
+        "📡 Event stream opened for {} ({} groups watched)",
+        community_id,
+        state.feed.watched_groups()
+    );
+
+    Sse::new(delta_stream(watch, permit))
+        .keep_alive(KeepAlive::new().interval(state.heartbeat))
+        .into_response()
+}
+
+/// SSE events for a watch; the connection permit is released when the client goes away
+fn delta_stream<R: GroupFeedRelay>(
+    watch: GroupWatch<R>,
+    permit: OwnedSemaphorePermit,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold((watch, permit), |(mut watch, permit)| async move {
+        loop {
+            match watch.deltas.recv().await {
+                Ok(delta) => {
+                    let event = Event::default()
+                        .json_data(&delta)
+                        .expect("delta serializes");
+                    return Some((Ok(event), (watch, permit)));
+                }
+                // Deltas are absolute values, so skipping some still leaves the client current
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+fn not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": "Community not found" })),
+    )
+        .into_response()
+}
+
+fn unavailable(message: &str) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "error": message })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, BodyDataStream};
+    use axum::http::{header, Request};
+    use futures::StreamExt;
+    use nostr_sdk::{EventBuilder, Keys, Kind, Tag, TagKind, Timestamp};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    const KNOWN: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+
+    struct FakeRelay;
+
+    impl GroupFeedRelay for FakeRelay {
+        async fn find_group(&self, community_id: Uuid) -> anyhow::Result<Option<String>> {
+            Ok((community_id == Uuid::parse_str(KNOWN).unwrap()).then(|| "peek-abc123".into()))
+        }
+
+        async fn subscribe(&self, _group_ids: Vec<String>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn setup(max_connections: usize, heartbeat: Duration) -> (Arc<GroupFeed<FakeRelay>>, Router) {
+        let feed = Arc::new(GroupFeed::new(FakeRelay));
+        let state = EventsState::new(
+            feed.clone(),
+            max_connections,
+            heartbeat,
+            CommunityIdPolicy::default(),
+        );
+        (feed, router(Arc::new(state)))
+    }
+
+    fn metadata(name: &str, created_at: u64) -> nostr_sdk::Event {
+        EventBuilder::new(Kind::from(39000), "")
+            .tags([
+                Tag::identifier("peek-abc123"),
+                Tag::custom(TagKind::Name, [name]),
+            ])
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    async fn open(app: &Router, uuid: &str) -> Response {
+        let request = Request::get(format!("/api/communities/{}/events", uuid))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    async fn next_frame(body: &mut BodyDataStream) -> String {
+        let chunk = tokio::time::timeout(Duration::from_secs(2), body.next())
+            .await
+            .expect("no SSE frame within 2s")
+            .expect("stream ended")
+            .unwrap();
+        String::from_utf8(chunk.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_streams_metadata_update_as_delta() {
+        let (feed, app) = setup(4, Duration::from_secs(60));
+
+        let response = open(&app, KNOWN).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        let mut body = response.into_body().into_data_stream();
+
+        // The relay replays the stored event first, then the rename arrives
+        feed.handle_event(&metadata("Cafe", 100));
+        feed.handle_event(&metadata("Blue Cafe", 110));
+
+        assert_eq!(
+            next_frame(&mut body).await,
+            "data: {\"type\":\"name_changed\",\"name\":\"Blue Cafe\"}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_keeps_idle_stream_alive() {
+        let (_feed, app) = setup(4, Duration::from_millis(50));
+        let mut body = open(&app, KNOWN).await.into_body().into_data_stream();
+
+        assert!(next_frame(&mut body).await.starts_with(':'));
+    }
+
+    #[tokio::test]
+    async fn test_connection_cap_and_release() {
+        let (feed, app) = setup(1, Duration::from_secs(60));
+
+        let first = open(&app, KNOWN).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(
+            open(&app, KNOWN).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        // Closing the stream frees both the permit and the group watch
+        drop(first);
+        assert_eq!(feed.watched_groups(), 0);
+        assert_eq!(open(&app, KNOWN).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_community_is_not_found() {
+        let (_feed, app) = setup(4, Duration::from_secs(60));
+
+        let unknown = open(&app, "5a1b7c59-c0a1-4876-acf1-56189b86aa0d").await;
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            open(&app, "not-a-uuid").await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,420 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{error, info};
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+use super::api_docs::{route_all, ErrorBody, RouteTable};
+use crate::libraries::community_id::CommunityIdPolicy;
+use crate::services::quiet_hours::QuietHours;
+use crate::services::relay::GroupMetadata;
+use crate::services::relay_access::GroupReader;
+
+// How long a loaded preview is served from memory before asking the relay again
+const PREVIEW_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// Unfurl bots and CDNs may reuse a preview for a few minutes
+const PREVIEW_CACHE_CONTROL: &str = "public, max-age=300, stale-while-revalidate=600";
+
+/// Where the HTTP preview endpoints get community metadata from
+pub trait PreviewSource: Send + Sync + 'static {
+    /// Metadata for a community UUID, or None if no such community exists
+    fn load_metadata(
+        &self,
+        community_id: Uuid,
+    ) -> impl Future<Output = anyhow::Result<Option<GroupMetadata>>> + Send;
+}
+
+impl PreviewSource for GroupReader {
+    async fn load_metadata(&self, community_id: Uuid) -> anyhow::Result<Option<GroupMetadata>> {
+        let Some(group_id) = self.find_group_by_uuid(&community_id).await? else {
+            return Ok(None);
+        };
+        Ok(Some(self.get_group_metadata(&group_id).await?))
+    }
+}
+
+/// Public community preview for link unfurling (same data as the Nostr preview)
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HttpPreview {
+    pub exists: bool,
+    pub name: Option<String>,
+    pub about: Option<String>,
+    pub member_count: Option<u32>,
+    pub created_at: Option<u64>,
+    pub is_open: Option<bool>,
+    // Archived communities take no new members, so unfurls should not invite people to join
+    pub archived: Option<bool>,
+    pub age_restricted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl HttpPreview {
+    fn missing() -> Self {
+        Self {
+            exists: false,
+            name: None,
+            about: None,
+            member_count: None,
+            created_at: None,
+            is_open: None,
+            archived: None,
+            age_restricted: None,
+            quiet_hours: None,
+        }
+    }
+
+    fn from_metadata(metadata: GroupMetadata) -> Self {
+        Self {
+            exists: true,
+            name: Some(metadata.name),
+            about: metadata.about,
+            member_count: Some(metadata.member_count),
+            created_at: Some(metadata.created_at.as_u64()),
+            is_open: Some(metadata.is_open),
+            archived: Some(metadata.archived),
+            age_restricted: Some(metadata.age_restricted),
+            quiet_hours: metadata.quiet_hours,
+        }
+    }
+}
+
+pub struct PreviewState<S> {
+    source: S,
+    id_policy: CommunityIdPolicy,
+    cache: RwLock<HashMap<Uuid, (Instant, HttpPreview)>>,
+}
+
+impl<S: PreviewSource> PreviewState<S> {
+    pub fn new(source: S, id_policy: CommunityIdPolicy) -> Self {
+        Self {
+            source,
+            id_policy,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Drop a community's cached preview; true if one was cached
+    pub async fn invalidate(&self, community_id: &Uuid) -> bool {
+        self.cache.write().await.remove(community_id).is_some()
+    }
+
+    async fn preview(&self, community_id: Uuid) -> anyhow::Result<HttpPreview> {
+        if let Some((loaded_at, preview)) = self.cache.read().await.get(&community_id) {
+            if loaded_at.elapsed() < PREVIEW_CACHE_TTL {
+                return Ok(preview.clone());
+            }
+        }
+
+        let preview = match self.source.load_metadata(community_id).await? {
+            Some(metadata) => HttpPreview::from_metadata(metadata),
+            None => HttpPreview::missing(),
+        };
+
+        self.cache
+            .write()
+            .await
+            .insert(community_id, (Instant::now(), preview.clone()));
+        Ok(preview)
+    }
+}
+
+/// Routes for GET /api/community/:uuid/preview and /api/community/:uuid/og-image
+pub fn router<S: PreviewSource>(state: Arc<PreviewState<S>>) -> Router {
+    route_all(routes()).with_state(state)
+}
+
+/// The preview routes before their state is attached
+pub(crate) fn routes<S: PreviewSource>() -> RouteTable<Arc<PreviewState<S>>> {
+    vec![
+        ("/api/community/:uuid/preview", get(preview_handler::<S>)),
+        ("/api/community/:uuid/og-image", get(og_image_handler::<S>)),
+    ]
+}
+
+/// OpenAPI description of the preview routes
+#[derive(OpenApi)]
+#[openapi(paths(preview_handler, og_image_handler))]
+pub struct PreviewApi;
+
+/// Public preview of a community, for link unfurling
+///
+/// A well-formed UUID with no community behind it answers 200 with `exists: false`.
+#[utoipa::path(
+    get,
+    path = "/api/community/{uuid}/preview",
+    tag = "communities",
+    operation_id = "community_preview",
+    params(("uuid" = String, Path, description = "Community UUID")),
+    responses(
+        (status = 200, description = "Preview of the community", body = HttpPreview),
+        (status = 404, description = "Not a community UUID", body = ErrorBody),
+        (status = 503, description = "The relay could not be read", body = ErrorBody),
+    )
+)]
+async fn preview_handler<S: PreviewSource>(
+    State(state): State<Arc<PreviewState<S>>>,
+    Path(uuid): Path<String>,
+) -> Response {
+    let community_id = match state.id_policy.parse(&uuid) {
+        Ok(id) => id,
+        Err(_) => return not_found(),
+    };
+
+    match state.preview(community_id).await {
+        Ok(preview) => {
+            info!(
+                "🔗 HTTP preview for {} (exists: {})",
+                community_id, preview.exists
+            );
+            (
+                [(header::CACHE_CONTROL, PREVIEW_CACHE_CONTROL)],
+                Json(preview),
+            )
+                .into_response()
+        }
+        Err(e) => unavailable(community_id, e),
+    }
+}
+
+/// 1200x630 SVG social card with the community's name and member count
+#[utoipa::path(
+    get,
+    path = "/api/community/{uuid}/og-image",
+    tag = "communities",
+    operation_id = "community_og_image",
+    params(("uuid" = String, Path, description = "Community UUID")),
+    responses(
+        (status = 200, description = "Social card", content_type = "image/svg+xml", body = String),
+        (status = 404, description = "No such community", body = ErrorBody),
+        (status = 503, description = "The relay could not be read", body = ErrorBody),
+    )
+)]
+async fn og_image_handler<S: PreviewSource>(
+    State(state): State<Arc<PreviewState<S>>>,
+    Path(uuid): Path<String>,
+) -> Response {
+    let community_id = match state.id_policy.parse(&uuid) {
+        Ok(id) => id,
+        Err(_) => return not_found(),
+    };
+
+    match state.preview(community_id).await {
+        Ok(preview) if preview.exists => (
+            [
+                (header::CONTENT_TYPE, "image/svg+xml"),
+                (header::CACHE_CONTROL, PREVIEW_CACHE_CONTROL),
+            ],
+            render_social_card(&preview),
+        )
+            .into_response(),
+        Ok(_) => not_found(),
+        Err(e) => unavailable(community_id, e),
+    }
+}
+
+fn not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": "Community not found" })),
+    )
+        .into_response()
+}
+
+fn unavailable(community_id: Uuid, e: anyhow::Error) -> Response {
+    error!("❌ Failed to load HTTP preview for {}: {}", community_id, e);
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "error": "Community preview temporarily unavailable" })),
+    )
+        .into_response()
+}
+
+/// 1200x630 social card with the community name and member count
+fn render_social_card(preview: &HttpPreview) -> String {
+    let name = preview.name.as_deref().unwrap_or("Peek community");
+    let members = match preview.member_count {
+        Some(1) => "1 member".to_string(),
+        Some(count) => format!("{} members", count),
+        None => String::new(),
+    };
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="1200" height="630" viewBox="0 0 1200 630">
+<rect width="1200" height="630" fill="#0f172a"/>
+<text x="80" y="300" font-family="sans-serif" font-size="72" font-weight="bold" fill="#ffffff">{}</text>
+<text x="80" y="390" font-family="sans-serif" font-size="40" fill="#94a3b8">{}</text>
+<text x="80" y="560" font-family="sans-serif" font-size="32" fill="#64748b">Peek · Scan the QR code on site to join</text>
+</svg>"##,
+        escape_xml(&truncate_chars(name, 40)),
+        escape_xml(&members)
+    )
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+    use nostr_sdk::{EventBuilder, Keys, Kind, Tag, TagKind};
+
+    const KNOWN: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+
+    struct FakeSource;
+
+    impl PreviewSource for FakeSource {
+        async fn load_metadata(&self, community_id: Uuid) -> anyhow::Result<Option<GroupMetadata>> {
+            if community_id != Uuid::parse_str(KNOWN).unwrap() {
+                return Ok(None);
+            }
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tags([
+                    Tag::identifier(format!("peek-{}", KNOWN)),
+                    Tag::custom(TagKind::Custom("name".into()), ["Blue Bottle <Coffee>"]),
+                    Tag::custom(
+                        TagKind::Custom("about".into()),
+                        ["Location-based community"],
+                    ),
+                    Tag::custom(TagKind::Custom("closed".into()), Vec::<String>::new()),
+                ])
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            Ok(Some(GroupMetadata::from_event(&event, 3)))
+        }
+    }
+
+    fn server() -> TestServer {
+        server_with(CommunityIdPolicy::default())
+    }
+
+    fn server_with(id_policy: CommunityIdPolicy) -> TestServer {
+        TestServer::new(router(Arc::new(PreviewState::new(FakeSource, id_policy)))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_preview_json_shape() {
+        let response = server()
+            .get(&format!("/api/community/{}/preview", KNOWN))
+            .await;
+        response.assert_status_ok();
+        assert_eq!(
+            response.header(header::CACHE_CONTROL),
+            PREVIEW_CACHE_CONTROL
+        );
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["exists"], true);
+        assert_eq!(body["name"], "Blue Bottle <Coffee>");
+        assert_eq!(body["about"], "Location-based community");
+        assert_eq!(body["member_count"], 3);
+        assert_eq!(body["is_open"], false);
+        assert_eq!(body["archived"], false);
+        assert_eq!(body["age_restricted"], false);
+        assert!(body.get("quiet_hours").is_none());
+        assert!(body["created_at"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_preview_unknown_community_reports_not_existing() {
+        let response = server()
+            .get(&format!("/api/community/{}/preview", Uuid::new_v4()))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["exists"], false);
+        assert!(body["name"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_drops_only_that_community() {
+        let state = PreviewState::new(FakeSource, CommunityIdPolicy::default());
+        let known = Uuid::parse_str(KNOWN).unwrap();
+        let other = Uuid::new_v4();
+        state.preview(known).await.unwrap();
+        state.preview(other).await.unwrap();
+
+        assert!(state.invalidate(&known).await);
+        assert!(!state.invalidate(&known).await);
+        let cache = state.cache.read().await;
+        assert!(!cache.contains_key(&known));
+        assert!(cache.contains_key(&other));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_uuid_is_404() {
+        let server = server();
+        server
+            .get("/api/community/not-a-uuid/preview")
+            .await
+            .assert_status_not_found();
+        server
+            .get("/api/community/not-a-uuid/og-image")
+            .await
+            .assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_non_v4_uuid_is_404_unless_any_allowed() {
+        let v1 = "6fa459ea-ee8a-11ca-8a3b-0002a5d5c51b";
+        server()
+            .get(&format!("/api/community/{}/preview", v1))
+            .await
+            .assert_status_not_found();
+        server()
+            .get(&format!("/api/community/{}/preview", Uuid::nil()))
+            .await
+            .assert_status_not_found();
+
+        let response = server_with(CommunityIdPolicy::new(true))
+            .get(&format!("/api/community/{}/preview", v1))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["exists"], false);
+    }
+
+    #[tokio::test]
+    async fn test_og_image_is_escaped_svg() {
+        let server = server();
+        let response = server
+            .get(&format!("/api/community/{}/og-image", KNOWN))
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.header(header::CONTENT_TYPE), "image/svg+xml");
+        let svg = response.text();
+        assert!(svg.contains("Blue Bottle &lt;Coffee&gt;"));
+        assert!(svg.contains("3 members"));
+
+        server
+            .get(&format!("/api/community/{}/og-image", Uuid::new_v4()))
+            .await
+            .assert_status_not_found();
+    }
+}
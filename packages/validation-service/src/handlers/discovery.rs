@@ -0,0 +1,367 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use tracing::error;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use super::api_docs::{route_all, ErrorBody, RouteTable};
+use crate::services::community_search::{CommunityDiscoveryData, DEFAULT_SEARCH_LIMIT};
+use crate::services::discovery_map::DiscoveryMapContent;
+use crate::services::relay_access::GroupReader;
+
+// The maps are republished whenever a community is created, so a short cache is enough
+const DISCOVERY_CACHE_CONTROL: &str = "public, max-age=60";
+
+/// Where the HTTP discovery endpoint reads the published maps from
+pub trait DiscoveryMapSource: Send + Sync + 'static {
+    /// Latest content of every configured discovery map
+    fn load_maps(&self) -> impl Future<Output = anyhow::Result<Vec<DiscoveryMapContent>>> + Send;
+
+    /// Ranked matches from the in-memory search index (no relay query)
+    fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        include_about: bool,
+    ) -> impl Future<Output = Vec<CommunityDiscoveryData>> + Send;
+}
+
+impl DiscoveryMapSource for GroupReader {
+    async fn load_maps(&self) -> anyhow::Result<Vec<DiscoveryMapContent>> {
+        Ok(self.fetch_discovery_maps().await?)
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        include_about: bool,
+    ) -> Vec<CommunityDiscoveryData> {
+        self.search_communities(query, limit, include_about).await
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DiscoveryMapQuery {
+    /// Also list archived communities, which the default map leaves out
+    #[serde(default)]
+    include_archived: bool,
+    /// Leave out communities marked 18+, for clients that never show them
+    #[serde(default)]
+    exclude_age_restricted: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchQuery {
+    /// Text to find in community names
+    #[serde(default)]
+    q: String,
+    /// Most results to return; 20 if unset, never more than 50
+    limit: Option<usize>,
+    /// Also match the about text
+    #[serde(default)]
+    about: bool,
+}
+
+/// Body of GET /api/discovery/search
+#[derive(Debug, Serialize, ToSchema)]
+struct SearchResults {
+    /// Best match first
+    results: Vec<CommunityDiscoveryData>,
+}
+
+/// Routes for GET /api/discovery-map?include_archived=true&exclude_age_restricted=true (all
+/// configured maps merged into one) and GET /api/discovery/search?q=...&limit=N&about=true (community name search)
+pub fn router<S: DiscoveryMapSource>(source: Arc<S>) -> Router {
+    route_all(routes()).with_state(source)
+}
+
+/// The discovery routes before their state is attached
+pub(crate) fn routes<S: DiscoveryMapSource>() -> RouteTable<Arc<S>> {
+    vec![
+        ("/api/discovery-map", get(discovery_map::<S>)),
+        ("/api/discovery/search", get(search::<S>)),
+    ]
+}
+
+/// OpenAPI description of the discovery routes
+#[derive(OpenApi)]
+#[openapi(paths(discovery_map, search))]
+pub struct DiscoveryApi;
+
+/// Every configured discovery map merged into one
+#[utoipa::path(
+    get,
+    path = "/api/discovery-map",
+    tag = "discovery",
+    operation_id = "discovery_map",
+    params(DiscoveryMapQuery),
+    responses(
+        (status = 200, description = "Display geohashes and labels of listed communities", body = DiscoveryMapContent),
+        (status = 503, description = "The maps could not be read", body = ErrorBody),
+    )
+)]
+async fn discovery_map<S: DiscoveryMapSource>(
+    State(source): State<Arc<S>>,
+    Query(query): Query<DiscoveryMapQuery>,
+) -> Response {
+    match source.load_maps().await {
+        Ok(maps) => {
+            let merged = DiscoveryMapContent::merge(maps);
+            let map = if query.include_archived {
+                merged.with_archived()
+            } else {
+                merged.without_archived()
+            };
+            let map = if query.exclude_age_restricted {
+                map.without_age_restricted()
+            } else {
+                map
+            };
+            (
+                [(header::CACHE_CONTROL, DISCOVERY_CACHE_CONTROL)],
+                Json(map),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("❌ Failed to load discovery maps: {}", e);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({ "error": "Discovery map temporarily unavailable" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Communities by name, from the in-memory search index
+#[utoipa::path(
+    get,
+    path = "/api/discovery/search",
+    tag = "discovery",
+    operation_id = "search_communities",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching communities", body = SearchResults),
+        (status = 400, description = "Empty query", body = ErrorBody),
+    )
+)]
+async fn search<S: DiscoveryMapSource>(
+    State(source): State<Arc<S>>,
+    Query(query): Query<SearchQuery>,
+) -> Response {
+    if query.q.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Search query must not be empty" })),
+        )
+            .into_response();
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    let results = source.search(&query.q, limit, query.about).await;
+    Json(SearchResults { results }).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProtocolConfig;
+    use crate::services::community_search::{SearchIndex, MAX_SEARCH_LIMIT};
+    use axum_test::TestServer;
+    use nostr_sdk::{EventBuilder, Keys, Kind, Tag, TagKind};
+    use std::collections::{BTreeMap, HashMap};
+
+    /// One map per prefix, as published with DISCOVERY_MAP_PREFIXES=9q,u4
+    struct PrefixMaps;
+
+    impl DiscoveryMapSource for PrefixMaps {
+        async fn load_maps(&self) -> anyhow::Result<Vec<DiscoveryMapContent>> {
+            Ok(vec![
+                DiscoveryMapContent {
+                    geohashes: vec!["9q8yyk8yz".to_string(), "9q9p1dhf7".to_string()],
+                    labels: BTreeMap::from([
+                        ("9q8yyk8yz".to_string(), "Blue Bottle".to_string()),
+                        ("9q9p1dhf7".to_string(), "The Night Owl".to_string()),
+                    ]),
+                    archived: Vec::new(),
+                    age_restricted: vec!["9q9p1dhf7".to_string()],
+                    updated_at: 1_760_000_000,
+                },
+                DiscoveryMapContent {
+                    geohashes: vec!["u4pruydqq".to_string()],
+                    labels: BTreeMap::from([
+                        ("u4pruydqq".to_string(), "Kaffebar".to_string()),
+                        ("u4zzzzzzz".to_string(), "Closed Pop-up".to_string()),
+                    ]),
+                    archived: vec!["u4zzzzzzz".to_string()],
+                    age_restricted: Vec::new(),
+                    updated_at: 1_760_000_500,
+                },
+            ])
+        }
+
+        async fn search(
+            &self,
+            _query: &str,
+            _limit: usize,
+            _include_about: bool,
+        ) -> Vec<CommunityDiscoveryData> {
+            Vec::new()
+        }
+    }
+
+    /// Search over a fixed index of communities named "Café 0", "Café 1", ...
+    struct IndexedCafes(SearchIndex);
+
+    impl IndexedCafes {
+        fn new(count: usize) -> Self {
+            let events: Vec<_> = (0..count)
+                .map(|n| {
+                    EventBuilder::new(Kind::from(39000), "")
+                        .tags([
+                            Tag::identifier(format!("peek-{}", n)),
+                            Tag::custom(TagKind::Name, [format!("Café {}", n)]),
+                        ])
+                        .sign_with_keys(&Keys::generate())
+                        .unwrap()
+                })
+                .collect();
+            Self(SearchIndex::from_events(
+                events.iter(),
+                &HashMap::new(),
+                &ProtocolConfig::default(),
+            ))
+        }
+    }
+
+    impl DiscoveryMapSource for IndexedCafes {
+        async fn load_maps(&self) -> anyhow::Result<Vec<DiscoveryMapContent>> {
+            Ok(Vec::new())
+        }
+
+        async fn search(
+            &self,
+            query: &str,
+            limit: usize,
+            include_about: bool,
+        ) -> Vec<CommunityDiscoveryData> {
+            self.0.search(query, limit, include_about)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_aggregates_prefix_maps() {
+        let server = TestServer::new(router(Arc::new(PrefixMaps))).unwrap();
+
+        // The archived pop-up in the u4 map is left out by default
+        let response = server.get("/api/discovery-map").await;
+        response.assert_status_ok();
+        response.assert_json(&serde_json::json!({
+            "geohashes": ["9q8yyk8yz", "9q9p1dhf7", "u4pruydqq"],
+            "labels": {
+                "9q8yyk8yz": "Blue Bottle",
+                "9q9p1dhf7": "The Night Owl",
+                "u4pruydqq": "Kaffebar"
+            },
+            "age_restricted": ["9q9p1dhf7"],
+            "updated_at": 1_760_000_500u64
+        }));
+        assert_eq!(
+            response.header(header::CACHE_CONTROL),
+            DISCOVERY_CACHE_CONTROL
+        );
+    }
+
+    #[tokio::test]
+    async fn test_archived_communities_only_with_include_archived() {
+        let server = TestServer::new(router(Arc::new(PrefixMaps))).unwrap();
+
+        let response = server
+            .get("/api/discovery-map")
+            .add_query_param("include_archived", "true")
+            .await;
+        response.assert_status_ok();
+        response.assert_json(&serde_json::json!({
+            "geohashes": ["9q8yyk8yz", "9q9p1dhf7", "u4pruydqq", "u4zzzzzzz"],
+            "labels": {
+                "9q8yyk8yz": "Blue Bottle",
+                "9q9p1dhf7": "The Night Owl",
+                "u4pruydqq": "Kaffebar",
+                "u4zzzzzzz": "Closed Pop-up"
+            },
+            "archived": ["u4zzzzzzz"],
+            "age_restricted": ["9q9p1dhf7"],
+            "updated_at": 1_760_000_500u64
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_age_restricted_communities_left_out_on_request() {
+        let server = TestServer::new(router(Arc::new(PrefixMaps))).unwrap();
+
+        let response = server
+            .get("/api/discovery-map")
+            .add_query_param("exclude_age_restricted", "true")
+            .await;
+        response.assert_status_ok();
+        response.assert_json(&serde_json::json!({
+            "geohashes": ["9q8yyk8yz", "u4pruydqq"],
+            "labels": {
+                "9q8yyk8yz": "Blue Bottle",
+                "u4pruydqq": "Kaffebar"
+            },
+            "updated_at": 1_760_000_500u64
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_search_folds_accents_and_caps_limit() {
+        let server = TestServer::new(router(Arc::new(IndexedCafes::new(60)))).unwrap();
+
+        let response = server
+            .get("/api/discovery/search")
+            .add_query_param("q", "cafe 7")
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["results"][0]["name"], "Café 7");
+        assert_eq!(body["results"][0]["group_id"], "peek-7");
+
+        let everything: serde_json::Value = server
+            .get("/api/discovery/search")
+            .add_query_param("q", "CAFÉ")
+            .add_query_param("limit", "500")
+            .await
+            .json();
+        assert_eq!(
+            everything["results"].as_array().unwrap().len(),
+            MAX_SEARCH_LIMIT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_search_query_is_rejected() {
+        let server = TestServer::new(router(Arc::new(IndexedCafes::new(1)))).unwrap();
+
+        server
+            .get("/api/discovery/search")
+            .add_query_param("q", "  ")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        server
+            .get("/api/discovery/search")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
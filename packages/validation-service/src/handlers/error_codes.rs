@@ -0,0 +1,451 @@
+//! Error codes, client message keys and message parameters for service responses
+//!
+//! The `error` field stays a human-readable English string for older clients. Localized
+//! clients render `message_key` with `params` instead, so every code, key and parameter
+//! is defined here and nowhere else.
+
+use std::collections::BTreeMap;
+
+/// Coarse distance from the requester to the nearest anchor
+/// Buckets rather than meters so a failed join does not reveal the anchor's position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceBucket {
+    Under100m,
+    From100mTo1km,
+    From1kmTo10km,
+    Over10km,
+    // Beyond the far-scan radius, rejected before the precise checks
+    VeryFar,
+    Unknown,
+}
+
+impl DistanceBucket {
+    pub fn from_meters(meters: Option<f64>) -> Self {
+        match meters {
+            Some(m) if m < 100.0 => Self::Under100m,
+            Some(m) if m < 1_000.0 => Self::From100mTo1km,
+            Some(m) if m < 10_000.0 => Self::From1kmTo10km,
+            Some(m) if m.is_finite() => Self::Over10km,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Under100m => "under_100m",
+            Self::From100mTo1km => "100m_to_1km",
+            Self::From1kmTo10km => "1km_to_10km",
+            Self::Over10km => "over_10km",
+            Self::VeryFar => "very_far",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Every error a service response can carry
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationErrorCode {
+    InvalidId,
+    NearbyCommunityExists,
+    RetryLater,
+    CommunityError,
+    LocationInvalid { distance_bucket: DistanceBucket },
+    GroupNotFound,
+    GroupLookupFailed,
+    CommunityClosed,
+    GroupAddFailed,
+    AccuracyTooLow { max_accuracy_meters: f64 },
+    NotAdmin,
+    AnchorLimitReached { max_anchors: usize },
+    AnchorAddFailed,
+    InvalidPubkey,
+    JoinRequestNotFound,
+    JoinRequestFailed,
+    MetadataUpdateFailed,
+    JoinRequestExpired,
+    InvalidLocationData,
+    InvalidMetadata { fields: String },
+    MembershipUnconfirmed,
+    ImplausibleLocation,
+    CommunityFull,
+    BatchTooLarge { max_batch: usize },
+    CommunityArchived,
+    ServiceMisconfigured,
+    SlugTaken { slug: String },
+    ServiceUnavailable { retry_after_secs: u64 },
+    AccuracyUnknown,
+    ExportLimitReached { max_exports: usize },
+    ExportFailed,
+    BulkAddLimitReached { remaining: usize },
+    CommunityNotScanned,
+    RegionUnsupported,
+    SignedStickerRequired,
+    AlreadyInCluster { community_id: String },
+}
+
+impl ValidationErrorCode {
+    /// Stable machine-readable code sent as `error_code`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidId => "INVALID_ID",
+            Self::NearbyCommunityExists => "NEARBY_COMMUNITY_EXISTS",
+            Self::RetryLater => "RETRY_LATER",
+            Self::CommunityError => "COMMUNITY_ERROR",
+            Self::LocationInvalid { .. } => "LOCATION_INVALID",
+            Self::GroupNotFound => "GROUP_NOT_FOUND",
+            Self::GroupLookupFailed => "GROUP_LOOKUP_FAILED",
+            Self::CommunityClosed => "COMMUNITY_CLOSED",
+            Self::GroupAddFailed => "GROUP_ADD_FAILED",
+            Self::AccuracyTooLow { .. } => "ACCURACY_TOO_LOW",
+            Self::NotAdmin => "NOT_ADMIN",
+            Self::AnchorLimitReached { .. } => "ANCHOR_LIMIT_REACHED",
+            Self::AnchorAddFailed => "ANCHOR_ADD_FAILED",
+            Self::InvalidPubkey => "INVALID_PUBKEY",
+            Self::JoinRequestNotFound => "JOIN_REQUEST_NOT_FOUND",
+            Self::JoinRequestFailed => "JOIN_REQUEST_FAILED",
+            Self::MetadataUpdateFailed => "METADATA_UPDATE_FAILED",
+            Self::JoinRequestExpired => "JOIN_REQUEST_EXPIRED",
+            Self::InvalidLocationData => "INVALID_LOCATION_DATA",
+            Self::InvalidMetadata { .. } => "INVALID_METADATA",
+            Self::MembershipUnconfirmed => "MEMBERSHIP_UNCONFIRMED",
+            Self::ImplausibleLocation => "IMPLAUSIBLE_LOCATION",
+            Self::CommunityFull => "COMMUNITY_FULL",
+            Self::BatchTooLarge { .. } => "BATCH_TOO_LARGE",
+            Self::CommunityArchived => "COMMUNITY_ARCHIVED",
+            Self::ServiceMisconfigured => "SERVICE_MISCONFIGURED",
+            Self::SlugTaken { .. } => "SLUG_TAKEN",
+            Self::ServiceUnavailable { .. } => "SERVICE_UNAVAILABLE",
+            Self::AccuracyUnknown => "ACCURACY_UNKNOWN",
+            Self::ExportLimitReached { .. } => "EXPORT_LIMIT_REACHED",
+            Self::ExportFailed => "EXPORT_FAILED",
+            Self::BulkAddLimitReached { .. } => "BULK_ADD_LIMIT_REACHED",
+            Self::CommunityNotScanned => "COMMUNITY_NOT_SCANNED",
+            Self::RegionUnsupported => "REGION_UNSUPPORTED",
+            Self::SignedStickerRequired => "SIGNED_STICKER_REQUIRED",
+            Self::AlreadyInCluster { .. } => "ALREADY_IN_CLUSTER",
+        }
+    }
+
+    /// Key clients look up in their translation catalogs
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            Self::InvalidId => "error.invalid_id",
+            Self::NearbyCommunityExists => "error.nearby_community_exists",
+            Self::RetryLater => "error.retry_later",
+            Self::CommunityError => "error.community_error",
+            Self::LocationInvalid { .. } => "error.location_invalid",
+            Self::GroupNotFound => "error.group_not_found",
+            Self::GroupLookupFailed => "error.group_lookup_failed",
+            Self::CommunityClosed => "error.community_closed",
+            Self::GroupAddFailed => "error.group_add_failed",
+            Self::AccuracyTooLow { .. } => "error.accuracy_too_low",
+            Self::NotAdmin => "error.not_admin",
+            Self::AnchorLimitReached { .. } => "error.anchor_limit_reached",
+            Self::AnchorAddFailed => "error.anchor_add_failed",
+            Self::InvalidPubkey => "error.invalid_pubkey",
+            Self::JoinRequestNotFound => "error.join_request_not_found",
+            Self::JoinRequestFailed => "error.join_request_failed",
+            Self::MetadataUpdateFailed => "error.metadata_update_failed",
+            Self::JoinRequestExpired => "error.join_request_expired",
+            Self::InvalidLocationData => "error.invalid_location_data",
+            Self::InvalidMetadata { .. } => "error.invalid_metadata",
+            Self::MembershipUnconfirmed => "error.membership_unconfirmed",
+            Self::ImplausibleLocation => "error.implausible_location",
+            Self::CommunityFull => "error.community_full",
+            Self::BatchTooLarge { .. } => "error.batch_too_large",
+            Self::CommunityArchived => "error.community_archived",
+            Self::ServiceMisconfigured => "error.service_misconfigured",
+            Self::SlugTaken { .. } => "error.slug_taken",
+            Self::ServiceUnavailable { .. } => "error.service_unavailable",
+            Self::AccuracyUnknown => "error.accuracy_unknown",
+            Self::ExportLimitReached { .. } => "error.export_limit_reached",
+            Self::ExportFailed => "error.export_failed",
+            Self::BulkAddLimitReached { .. } => "error.bulk_add_limit_reached",
+            Self::CommunityNotScanned => "error.community_not_scanned",
+            Self::RegionUnsupported => "error.region_unsupported",
+            Self::SignedStickerRequired => "error.signed_sticker_required",
+            Self::AlreadyInCluster { .. } => "error.already_in_cluster",
+        }
+    }
+
+    /// English reference template for the message key; `{name}` placeholders come from `params`
+    pub fn template(&self) -> &'static str {
+        match self {
+            Self::InvalidId => "This QR code is not a valid community link",
+            Self::NearbyCommunityExists => "A community already exists at this location",
+            Self::RetryLater => "Please try again in a moment",
+            Self::CommunityError => "The community could not be loaded",
+            Self::LocationInvalid { .. } => {
+                "You are not close enough to this community ({distance_bucket})"
+            }
+            Self::GroupNotFound => "Community not found",
+            Self::GroupLookupFailed => "The community could not be looked up",
+            Self::CommunityClosed => "This community is no longer accepting new members",
+            Self::GroupAddFailed => "You could not be added to the community",
+            Self::AccuracyTooLow { .. } => {
+                "Location accuracy must be within {max_accuracy_meters}m to add an anchor"
+            }
+            Self::NotAdmin => "Only community admins can do this",
+            Self::AnchorLimitReached { .. } => {
+                "Communities may have at most {max_anchors} locations"
+            }
+            Self::AnchorAddFailed => "The location could not be added",
+            Self::InvalidPubkey => "This is not a valid user key",
+            Self::JoinRequestNotFound => "There is no pending join request for this user",
+            Self::JoinRequestFailed => "Your join request could not be submitted",
+            Self::MetadataUpdateFailed => "The community settings could not be updated",
+            Self::JoinRequestExpired => "Your join request expired before an admin responded",
+            Self::InvalidLocationData => "Your device reported an unusable location",
+            Self::InvalidMetadata { .. } => {
+                "These community details are too long or empty: {fields}"
+            }
+            Self::MembershipUnconfirmed => {
+                "We could not confirm you joined yet, please scan again in a moment"
+            }
+            Self::ImplausibleLocation => {
+                "A community cannot be started here, your location looks wrong"
+            }
+            Self::CommunityFull => "This community is full and is not accepting new members",
+            Self::BatchTooLarge { .. } => "At most {max_batch} communities can be loaded at once",
+            Self::CommunityArchived => {
+                "This community has been archived and is not accepting new members"
+            }
+            Self::ServiceMisconfigured => {
+                "Communities cannot be created or joined right now, the service needs attention"
+            }
+            Self::SlugTaken { .. } => "The link name {slug} is already used by another community",
+            Self::ServiceUnavailable { .. } => {
+                "The service cannot reach its relay right now, please retry in {retry_after_secs}s"
+            }
+            Self::AccuracyUnknown => {
+                "Your device did not report how accurate its location is; a precise fix is needed to create a community"
+            }
+            Self::ExportLimitReached { .. } => {
+                "Communities can be exported at most {max_exports} times a day"
+            }
+            Self::ExportFailed => "The community could not be exported",
+            Self::BulkAddLimitReached { .. } => {
+                "This community can add {remaining} more members by pubkey"
+            }
+            Self::CommunityNotScanned => {
+                "Members can only be added once the community has been created by scanning its sticker"
+            }
+            Self::RegionUnsupported => "Communities cannot be started in this region",
+            Self::SignedStickerRequired => {
+                "This community's sticker may have been copied, so it is not taking new members for now"
+            }
+            Self::AlreadyInCluster { .. } => {
+                "You already belong to community {community_id}, and may only be in one of these communities; ask to transfer to move here"
+            }
+        }
+    }
+
+    /// Values for the template placeholders
+    pub fn params(&self) -> BTreeMap<String, String> {
+        let mut params = BTreeMap::new();
+        match self {
+            Self::LocationInvalid { distance_bucket } => {
+                params.insert(
+                    "distance_bucket".to_string(),
+                    distance_bucket.as_str().to_string(),
+                );
+            }
+            Self::AccuracyTooLow {
+                max_accuracy_meters,
+            } => {
+                params.insert(
+                    "max_accuracy_meters".to_string(),
+                    max_accuracy_meters.to_string(),
+                );
+            }
+            Self::AnchorLimitReached { max_anchors } => {
+                params.insert("max_anchors".to_string(), max_anchors.to_string());
+            }
+            Self::InvalidMetadata { fields } => {
+                params.insert("fields".to_string(), fields.clone());
+            }
+            Self::BatchTooLarge { max_batch } => {
+                params.insert("max_batch".to_string(), max_batch.to_string());
+            }
+            Self::SlugTaken { slug } => {
+                params.insert("slug".to_string(), slug.clone());
+            }
+            Self::ServiceUnavailable { retry_after_secs } => {
+                params.insert("retry_after_secs".to_string(), retry_after_secs.to_string());
+            }
+            Self::ExportLimitReached { max_exports } => {
+                params.insert("max_exports".to_string(), max_exports.to_string());
+            }
+            Self::BulkAddLimitReached { remaining } => {
+                params.insert("remaining".to_string(), remaining.to_string());
+            }
+            Self::AlreadyInCluster { community_id } => {
+                params.insert("community_id".to_string(), community_id.clone());
+            }
+            _ => {}
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // Exhaustive on purpose: a new variant fails to compile here until it is numbered,
+    // and then fails `test_every_code_has_a_unique_message_key` until it has a sample
+    const VARIANT_COUNT: usize = 36;
+
+    fn variant_index(code: &ValidationErrorCode) -> usize {
+        match code {
+            ValidationErrorCode::InvalidId => 0,
+            ValidationErrorCode::NearbyCommunityExists => 1,
+            ValidationErrorCode::RetryLater => 2,
+            ValidationErrorCode::CommunityError => 3,
+            ValidationErrorCode::LocationInvalid { .. } => 4,
+            ValidationErrorCode::GroupNotFound => 5,
+            ValidationErrorCode::GroupLookupFailed => 6,
+            ValidationErrorCode::CommunityClosed => 7,
+            ValidationErrorCode::GroupAddFailed => 8,
+            ValidationErrorCode::AccuracyTooLow { .. } => 9,
+            ValidationErrorCode::NotAdmin => 10,
+            ValidationErrorCode::AnchorLimitReached { .. } => 11,
+            ValidationErrorCode::AnchorAddFailed => 12,
+            ValidationErrorCode::InvalidPubkey => 13,
+            ValidationErrorCode::JoinRequestNotFound => 14,
+            ValidationErrorCode::JoinRequestFailed => 15,
+            ValidationErrorCode::MetadataUpdateFailed => 16,
+            ValidationErrorCode::JoinRequestExpired => 17,
+            ValidationErrorCode::InvalidLocationData => 18,
+            ValidationErrorCode::InvalidMetadata { .. } => 19,
+            ValidationErrorCode::MembershipUnconfirmed => 20,
+            ValidationErrorCode::ImplausibleLocation => 21,
+            ValidationErrorCode::CommunityFull => 22,
+            ValidationErrorCode::BatchTooLarge { .. } => 23,
+            ValidationErrorCode::CommunityArchived => 24,
+            ValidationErrorCode::ServiceMisconfigured => 25,
+            ValidationErrorCode::SlugTaken { .. } => 26,
+            ValidationErrorCode::ServiceUnavailable { .. } => 27,
+            ValidationErrorCode::AccuracyUnknown => 28,
+            ValidationErrorCode::ExportLimitReached { .. } => 29,
+            ValidationErrorCode::ExportFailed => 30,
+            ValidationErrorCode::BulkAddLimitReached { .. } => 31,
+            ValidationErrorCode::CommunityNotScanned => 32,
+            ValidationErrorCode::RegionUnsupported => 33,
+            ValidationErrorCode::SignedStickerRequired => 34,
+            ValidationErrorCode::AlreadyInCluster { .. } => 35,
+        }
+    }
+
+    fn all_codes() -> Vec<ValidationErrorCode> {
+        vec![
+            ValidationErrorCode::InvalidId,
+            ValidationErrorCode::NearbyCommunityExists,
+            ValidationErrorCode::RetryLater,
+            ValidationErrorCode::CommunityError,
+            ValidationErrorCode::LocationInvalid {
+                distance_bucket: DistanceBucket::From100mTo1km,
+            },
+            ValidationErrorCode::GroupNotFound,
+            ValidationErrorCode::GroupLookupFailed,
+            ValidationErrorCode::CommunityClosed,
+            ValidationErrorCode::GroupAddFailed,
+            ValidationErrorCode::AccuracyTooLow {
+                max_accuracy_meters: 20.0,
+            },
+            ValidationErrorCode::NotAdmin,
+            ValidationErrorCode::AnchorLimitReached { max_anchors: 5 },
+            ValidationErrorCode::AnchorAddFailed,
+            ValidationErrorCode::InvalidPubkey,
+            ValidationErrorCode::JoinRequestNotFound,
+            ValidationErrorCode::JoinRequestFailed,
+            ValidationErrorCode::MetadataUpdateFailed,
+            ValidationErrorCode::JoinRequestExpired,
+            ValidationErrorCode::InvalidLocationData,
+            ValidationErrorCode::InvalidMetadata {
+                fields: "name, about".to_string(),
+            },
+            ValidationErrorCode::MembershipUnconfirmed,
+            ValidationErrorCode::ImplausibleLocation,
+            ValidationErrorCode::CommunityFull,
+            ValidationErrorCode::BatchTooLarge { max_batch: 20 },
+            ValidationErrorCode::CommunityArchived,
+            ValidationErrorCode::ServiceMisconfigured,
+            ValidationErrorCode::SlugTaken {
+                slug: "blue-bottle-mission".to_string(),
+            },
+            ValidationErrorCode::ServiceUnavailable {
+                retry_after_secs: 30,
+            },
+            ValidationErrorCode::AccuracyUnknown,
+            ValidationErrorCode::ExportLimitReached { max_exports: 3 },
+            ValidationErrorCode::ExportFailed,
+            ValidationErrorCode::BulkAddLimitReached { remaining: 40 },
+            ValidationErrorCode::CommunityNotScanned,
+            ValidationErrorCode::RegionUnsupported,
+            ValidationErrorCode::SignedStickerRequired,
+            ValidationErrorCode::AlreadyInCluster {
+                community_id: "9b1f0c7e-5d2a-4c8e-b6f3-1a2b3c4d5e6f".to_string(),
+            },
+        ]
+    }
+
+    /// `{name}` placeholders referenced by a template
+    fn placeholders(template: &str) -> HashSet<String> {
+        template
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_code_has_a_unique_message_key() {
+        let codes = all_codes();
+        let covered: HashSet<usize> = codes.iter().map(variant_index).collect();
+        assert_eq!(covered, (0..VARIANT_COUNT).collect::<HashSet<_>>());
+
+        let mut keys = HashSet::new();
+        let mut error_codes = HashSet::new();
+        for code in &codes {
+            assert!(code.message_key().starts_with("error."));
+            assert!(keys.insert(code.message_key()), "{:?} reuses a key", code);
+            assert!(error_codes.insert(code.code()), "{:?} reuses a code", code);
+        }
+    }
+
+    #[test]
+    fn test_template_params_are_always_populated() {
+        for code in all_codes() {
+            let params = code.params();
+            let expected = placeholders(code.template());
+            let provided: HashSet<String> = params.keys().cloned().collect();
+            assert_eq!(expected, provided, "params for {:?}", code);
+            assert!(params.values().all(|v| !v.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_distance_buckets() {
+        assert_eq!(
+            DistanceBucket::from_meters(Some(40.0)).as_str(),
+            "under_100m"
+        );
+        assert_eq!(
+            DistanceBucket::from_meters(Some(450.0)).as_str(),
+            "100m_to_1km"
+        );
+        assert_eq!(
+            DistanceBucket::from_meters(Some(2_500.0)).as_str(),
+            "1km_to_10km"
+        );
+        assert_eq!(
+            DistanceBucket::from_meters(Some(80_000.0)).as_str(),
+            "over_10km"
+        );
+        assert_eq!(DistanceBucket::from_meters(None).as_str(), "unknown");
+        // Only the far-scan check reports this one
+        assert_eq!(DistanceBucket::VeryFar.as_str(), "very_far");
+    }
+}
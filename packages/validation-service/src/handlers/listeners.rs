@@ -0,0 +1,246 @@
+//! The public HTTP server and, with METRICS_PORT set, a second one for operators
+//!
+//! The internal server carries /metrics, and the admin API too if asked, and is meant to be
+//! bound to loopback or a cluster-internal interface. The public server then leaves those
+//! routes out entirely rather than guarding them. Both stop on the same shutdown signal.
+
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use std::future::{Future, IntoFuture};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use utoipa::OpenApi;
+
+use super::api_docs::{route_all, RouteTable};
+use crate::services::metrics;
+
+/// Routes for GET /metrics, in the Prometheus text format
+pub fn metrics_router() -> Router {
+    route_all(metrics_routes())
+}
+
+/// The metrics route as a table, for the docs test
+pub(crate) fn metrics_routes() -> RouteTable<()> {
+    vec![("/metrics", get(render_metrics))]
+}
+
+/// OpenAPI description of the metrics route
+#[derive(OpenApi)]
+#[openapi(paths(render_metrics))]
+pub struct MetricsApi;
+
+/// Prometheus metrics; served on METRICS_PORT instead of the public port when that is set
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "service",
+    operation_id = "metrics",
+    responses((status = 200, description = "Prometheus text format", content_type = "text/plain", body = String))
+)]
+async fn render_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
+
+/// Everything the HTTP servers serve apart from /metrics
+pub struct Routes {
+    // Sticker, preview, discovery and health endpoints
+    pub public: Router,
+    pub admin: Router,
+}
+
+/// The operators' server: where it listens and whether it takes the admin API along
+pub struct InternalServer {
+    pub listener: TcpListener,
+    pub with_admin: bool,
+}
+
+/// Serve `routes` until `shutdown` completes, then let both servers finish in-flight requests
+///
+/// Without an internal server, /metrics and the admin API are served publicly as before.
+pub async fn serve(
+    public: TcpListener,
+    internal: Option<InternalServer>,
+    routes: Routes,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let (stop, stopped) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown.await;
+        let _ = stop.send(true);
+    });
+    let signal = |mut stopped: watch::Receiver<bool>| async move {
+        let _ = stopped.wait_for(|stopped| *stopped).await;
+    };
+
+    let Some(internal) = internal else {
+        let app = routes.public.merge(routes.admin).merge(metrics_router());
+        return axum::serve(public, app)
+            .with_graceful_shutdown(signal(stopped))
+            .await;
+    };
+    let (public_app, internal_app) = if internal.with_admin {
+        (routes.public, metrics_router().merge(routes.admin))
+    } else {
+        (routes.public.merge(routes.admin), metrics_router())
+    };
+    tokio::try_join!(
+        axum::serve(public, public_app)
+            .with_graceful_shutdown(signal(stopped.clone()))
+            .into_future(),
+        axum::serve(internal.listener, internal_app)
+            .with_graceful_shutdown(signal(stopped))
+            .into_future(),
+    )?;
+    Ok(())
+}
+
+/// Resolves on Ctrl-C, or SIGTERM as sent by container runtimes
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use std::net::SocketAddr;
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    fn routes() -> Routes {
+        Routes {
+            public: Router::new().route("/health", get(|| async { "ok" })),
+            admin: Router::new().route("/api/admin/inactive-prune", get(|| async { "runs" })),
+        }
+    }
+
+    async fn listener() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    /// Boot the servers on ephemeral ports; sending on the returned channel shuts them down
+    async fn boot(
+        internal: Option<bool>,
+    ) -> (
+        SocketAddr,
+        Option<SocketAddr>,
+        oneshot::Sender<()>,
+        JoinHandle<std::io::Result<()>>,
+    ) {
+        let (public, public_addr) = listener().await;
+        let (internal, internal_addr) = match internal {
+            Some(with_admin) => {
+                let (listener, addr) = listener().await;
+                (
+                    Some(InternalServer {
+                        listener,
+                        with_admin,
+                    }),
+                    Some(addr),
+                )
+            }
+            None => (None, None),
+        };
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(public, internal, routes(), async {
+            let _ = stopped.await;
+        }));
+        (public_addr, internal_addr, stop, server)
+    }
+
+    async fn status(addr: SocketAddr, path: &str) -> StatusCode {
+        let response = reqwest::get(format!("http://{}{}", addr, path))
+            .await
+            .unwrap();
+        StatusCode::from_u16(response.status().as_u16()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_served_only_on_the_internal_port() {
+        metrics::increment("peek_listener_test_total", &[]);
+        let (public, internal, stop, server) = boot(Some(false)).await;
+        let internal = internal.unwrap();
+
+        assert_eq!(status(public, "/metrics").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(public, "/health").await, StatusCode::OK);
+        assert_eq!(
+            status(public, "/api/admin/inactive-prune").await,
+            StatusCode::OK
+        );
+
+        let response = reqwest::get(format!("http://{}/metrics", internal))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/plain; version=0.0.4"
+        );
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("peek_listener_test_total"));
+        assert_eq!(status(internal, "/health").await, StatusCode::NOT_FOUND);
+
+        // One signal stops both servers
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_api_can_move_to_the_internal_port() {
+        let (public, internal, stop, server) = boot(Some(true)).await;
+        let internal = internal.unwrap();
+
+        assert_eq!(
+            status(public, "/api/admin/inactive-prune").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(internal, "/api/admin/inactive-prune").await,
+            StatusCode::OK
+        );
+        assert_eq!(status(internal, "/metrics").await, StatusCode::OK);
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_without_an_internal_port_everything_stays_public() {
+        let (public, internal, stop, server) = boot(None).await;
+        assert!(internal.is_none());
+
+        assert_eq!(status(public, "/metrics").await, StatusCode::OK);
+        assert_eq!(
+            status(public, "/api/admin/inactive-prune").await,
+            StatusCode::OK
+        );
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
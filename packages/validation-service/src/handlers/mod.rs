@@ -1,13 +1,278 @@
+pub mod admin;
+pub mod api_docs;
+pub mod community_events;
+pub mod community_preview;
+pub mod discovery;
+pub mod error_codes;
+pub mod listeners;
 pub mod nostr_validation;
+pub mod schema;
+pub mod service_info;
 
-use axum::{response::IntoResponse, Json};
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::services::public_relay::PublicRelayStatus;
+use crate::services::relay_authorization::{RelayKeyAuthorization, RelayKeyStatus};
+use crate::services::relay_circuit::{CircuitStatus, RelayCircuit};
+use crate::services::relay_limits::RelayLimits;
+use crate::services::subscription_watchdog::SubscriptionWatchdog;
+use api_docs::{route_all, RouteTable};
 
 pub use nostr_validation::NostrValidationHandler;
 
-pub async fn health() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "healthy",
-        "service": "validation-service",
-        "version": env!("CARGO_PKG_VERSION")
-    }))
+pub struct HealthState {
+    pub watchdog: Arc<SubscriptionWatchdog>,
+    // NIP-11 limits of each relay the service writes to, as fetched at connect time
+    pub relay_limits: BTreeMap<String, RelayLimits>,
+    // Whether public_relay_url served our groups when probed at startup
+    pub public_relay: PublicRelayStatus,
+    // Whether the groups relay still accepts management events from the relay key
+    pub relay_key: Arc<RelayKeyAuthorization>,
+    // Open while the groups relay is unreachable and requests are refused outright
+    pub relay_circuit: Arc<RelayCircuit>,
+    // READ_ONLY_MODE: requests are validated and answered but nothing reaches the relay
+    pub read_only: bool,
+}
+
+/// Body of GET /health
+#[derive(Debug, Serialize, ToSchema)]
+struct HealthReport {
+    status: HealthStatus,
+    mode: ServiceMode,
+    service: &'static str,
+    version: &'static str,
+    relays: BTreeMap<String, RelayActivity>,
+    relay_limits: BTreeMap<String, RelayLimits>,
+    public_relay: PublicRelayStatus,
+    relay_key: RelayKeyStatus,
+    relay_circuit: CircuitStatus,
+}
+
+/// Degraded while the public relay failed its check, the relay key is refused or the relay
+/// circuit is not closed; the service still answers what it can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum HealthStatus {
+    Healthy,
+    Degraded,
+}
+
+/// READ_ONLY_MODE reports read_only
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ServiceMode {
+    Live,
+    ReadOnly,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct RelayActivity {
+    /// Seconds since the relay last delivered an event, null if none yet
+    last_event_age_secs: Option<u64>,
+}
+
+/// Routes for GET /health and /api/health
+pub fn health_router(state: Arc<HealthState>) -> Router {
+    route_all(health_routes()).with_state(state)
+}
+
+/// The health routes before their state is attached
+pub(crate) fn health_routes() -> RouteTable<Arc<HealthState>> {
+    vec![("/health", get(health)), ("/api/health", get(health))]
+}
+
+/// OpenAPI description of the health routes; /health is added as an alias by api_docs
+#[derive(OpenApi)]
+#[openapi(paths(health))]
+pub struct HealthApi;
+
+/// Service status and the state of the relays it depends on
+///
+/// Answers 200 when degraded too, with `status` saying so.
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    tag = "service",
+    responses((status = 200, description = "Service status", body = HealthReport))
+)]
+async fn health(State(state): State<Arc<HealthState>>) -> Json<HealthReport> {
+    let relays = state
+        .watchdog
+        .last_event_ages()
+        .into_iter()
+        .map(|(relay, age)| {
+            (
+                relay,
+                RelayActivity {
+                    last_event_age_secs: age,
+                },
+            )
+        })
+        .collect();
+
+    let relay_key = state.relay_key.status();
+    let relay_circuit = state.relay_circuit.status();
+    let status = if state.public_relay.is_degraded()
+        || relay_key.is_unauthorized()
+        || !relay_circuit.is_closed()
+    {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Healthy
+    };
+
+    Json(HealthReport {
+        status,
+        mode: if state.read_only {
+            ServiceMode::ReadOnly
+        } else {
+            ServiceMode::Live
+        },
+        service: "validation-service",
+        version: env!("CARGO_PKG_VERSION"),
+        relays,
+        relay_limits: state.relay_limits.clone(),
+        public_relay: state.public_relay.clone(),
+        relay_key,
+        relay_circuit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::clock::SystemClock;
+    use crate::libraries::test_support::ManualClock;
+    use crate::services::relay::RelayError;
+    use axum::response::IntoResponse;
+    use nostr_sdk::Kind;
+    use std::time::Duration;
+
+    fn circuit() -> Arc<RelayCircuit> {
+        Arc::new(RelayCircuit::new(
+            Arc::new(ManualClock::new(1_760_000_000)),
+            2,
+            Duration::from_secs(30),
+        ))
+    }
+
+    fn health_state(
+        public_relay: PublicRelayStatus,
+        relay_key: Arc<RelayKeyAuthorization>,
+        relay_circuit: Arc<RelayCircuit>,
+    ) -> Arc<HealthState> {
+        Arc::new(HealthState {
+            watchdog: Arc::new(SubscriptionWatchdog::new(60, 3, Arc::new(SystemClock))),
+            relay_limits: BTreeMap::new(),
+            public_relay,
+            relay_key,
+            relay_circuit,
+            read_only: false,
+        })
+    }
+
+    async fn body(state: Arc<HealthState>) -> serde_json::Value {
+        let response = health(State(state)).await.into_response();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_failed_public_relay_check_reports_degraded() {
+        let state = |public_relay| {
+            health_state(
+                public_relay,
+                Arc::new(RelayKeyAuthorization::new(Arc::new(SystemClock))),
+                circuit(),
+            )
+        };
+
+        let degraded = body(state(PublicRelayStatus::Degraded {
+            reason: "wss://peek.example is unreachable: could not connect".to_string(),
+        }))
+        .await;
+        assert_eq!(degraded["status"], "degraded");
+        assert_eq!(degraded["public_relay"]["status"], "degraded");
+        assert_eq!(
+            degraded["public_relay"]["reason"],
+            "wss://peek.example is unreachable: could not connect"
+        );
+
+        let healthy = body(state(PublicRelayStatus::Reachable)).await;
+        assert_eq!(healthy["status"], "healthy");
+        assert_eq!(healthy["public_relay"]["status"], "reachable");
+    }
+
+    #[tokio::test]
+    async fn test_refused_relay_key_reports_degraded() {
+        let relay_key = Arc::new(RelayKeyAuthorization::new(Arc::new(SystemClock)));
+        let state = health_state(PublicRelayStatus::Reachable, relay_key.clone(), circuit());
+        assert_eq!(
+            body(state.clone()).await["relay_key"]["status"],
+            "authorized"
+        );
+
+        let refused = relay_key
+            .send(Kind::from(9007), || async {
+                Err(RelayError::Other(
+                    "restricted: only relay admins can create groups".to_string(),
+                ))
+            })
+            .await;
+        assert!(matches!(refused, Err(RelayError::RelayKeyUnauthorized(_))));
+
+        let degraded = body(state).await;
+        assert_eq!(degraded["status"], "degraded");
+        assert_eq!(degraded["relay_key"]["status"], "unauthorized");
+        assert_eq!(
+            degraded["relay_key"]["reason"],
+            "restricted: only relay admins can create groups"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_relay_circuit_reports_degraded() {
+        let relay_circuit = circuit();
+        let state = health_state(
+            PublicRelayStatus::Reachable,
+            Arc::new(RelayKeyAuthorization::new(Arc::new(SystemClock))),
+            relay_circuit.clone(),
+        );
+        let healthy = body(state.clone()).await;
+        assert_eq!(healthy["status"], "healthy");
+        assert_eq!(healthy["relay_circuit"]["state"], "closed");
+
+        relay_circuit.record(false);
+        relay_circuit.record(false);
+        let degraded = body(state).await;
+        assert_eq!(degraded["status"], "degraded");
+        assert_eq!(degraded["relay_circuit"]["state"], "open");
+        assert_eq!(degraded["relay_circuit"]["retry_after_secs"], 30);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mode_is_reported() {
+        let state = |read_only| {
+            Arc::new(HealthState {
+                read_only,
+                ..Arc::into_inner(health_state(
+                    PublicRelayStatus::Reachable,
+                    Arc::new(RelayKeyAuthorization::new(Arc::new(SystemClock))),
+                    circuit(),
+                ))
+                .unwrap()
+            })
+        };
+
+        assert_eq!(body(state(false)).await["mode"], "live");
+        let read_only = body(state(true)).await;
+        assert_eq!(read_only["mode"], "read_only");
+        assert_eq!(read_only["status"], "healthy");
+    }
 }
@@ -1,48 +1,238 @@
-use geohash::{encode, neighbors, Coord};
+use futures::FutureExt;
+use nostr_sdk::nips::{nip04, nip44};
 use nostr_sdk::prelude::*;
+use peek_geo::{bearing_degrees, haversine_meters, matches_cell_or_neighbor, CompassBucket};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+#[cfg(feature = "country-lookup")]
+use crate::libraries::country::CountryGate;
+
+use super::error_codes::{DistanceBucket, ValidationErrorCode};
+use super::service_info::{AppHandler, ServiceDescriptor};
 use crate::{
-    config::Config,
-    models::LocationPoint,
+    config::{Config, ServiceKeys},
+    libraries::{
+        attestation::{Attestation, AttestationClaims},
+        clock::{Clock, Deadline, SystemClock},
+        community_id::{CommunityIdPolicy, CommunityRef, InvalidCommunityId, UnknownIdLimiter},
+        exclusion_zones::ExclusionZones,
+        plausibility::check_plausible_location,
+        relay_url::RelayUrl,
+        rng::ThreadRngSource,
+        sanitize::MetadataText,
+    },
+    models::{check_location_data, AccuracyReading, Coordinates, InvalidLocationData, LocationFix},
     services::{
-        community::CommunityService, gift_wrap::GiftWrapService,
-        migration_monitor::MigrationMonitor, relay::RelayService,
+        audit_trail::{AuditAction, AuditActor, AuditOutcome, AuditTrail},
+        bulk_members::{BulkAddError, BulkAddOutcome, BulkAddStatus, BulkAdder},
+        client_pool::ClientPool,
+        community::{CommunityError, CommunityLookup, CommunityMetadata, CommunityService},
+        community_clusters::{ClusterConflict, ClusterTransferError, CommunityClusters},
+        community_export::{
+            BlossomUploader, CommunityExport, CommunityExporter, ExportDelivery, ExportError,
+            ExportLimiter,
+        },
+        execution::ExecutionMode,
+        far_scans::FarScans,
+        gift_wrap::GiftWrapService,
+        in_flight::{InFlight, Outcome},
+        inactive_prune::{InactivePrune, InactivityWarning, PruneLog},
+        inbox_relays::InboxRelayResolver,
+        join_notifications::{JoinBatch, JoinBatcher},
+        join_requests::{
+            cancel_join, request_join, resolve_join, sweep_expired_requests, JoinMode,
+            JoinRequestStatus,
+        },
+        location_probing::{LocationProbeGuard, ProbePolicy, ProbeStatus},
+        metrics,
+        migration_monitor::MigrationMonitor,
+        operator_webhook::{OperatorAlert, OperatorWebhook},
+        quiet_hours::{PushKind, QuietHours, QuietHoursQueue, MAX_HELD_PUSHES},
+        relay::{GroupMetadata, RelayError},
+        relay_access::{GroupReader, GroupWriter},
+        relay_limits::{read_auth_scope, AuthScope},
+        response_retry::{
+            run_retry_worker, GiftWrapResponseSender, QueuedResponse, ResponseRetryQueue,
+            RetryPolicy,
+        },
+        sticker_reuse::{ReuseFlag, StickerReuse},
+        subscription_watchdog::{SubscriptionWatchdog, WatchdogAction},
+        venue_hours::VenueHoursStatus,
     },
 };
 
-// Custom event kinds for Peek location validation (ephemeral range)
-const LOCATION_VALIDATION_REQUEST_KIND: Kind = Kind::Custom(27492);
-const LOCATION_VALIDATION_RESPONSE_KIND: Kind = Kind::Custom(27493);
+// Request/response kinds are namespaced per deployment in config.protocol
 const MIGRATION_KIND: Kind = Kind::Custom(1776);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Admins adding a new anchor must report at least this GPS accuracy
+pub const MAX_ANCHOR_ACCURACY_METERS: f64 = 20.0;
+
+// Geohash level used for anchors; users match the anchor cell or one of its neighbors
+pub const ANCHOR_GEOHASH_PRECISION: usize = 8;
+
+// Most communities a single preview_batch request may ask for
+pub const MAX_PREVIEW_BATCH: usize = 20;
+
+// Every ServiceRequest "type" tag this service understands
+pub const SUPPORTED_REQUEST_TYPES: &[&str] = &[
+    "location_validation",
+    "preview_request",
+    "preview_batch",
+    "add_anchor",
+    "update_metadata",
+    "approve_join",
+    "cancel_join_request",
+    "archive_community",
+    "unarchive_community",
+    "export_community",
+    "bulk_add_members",
+];
+
+// Request tag asking for the response as a NIP-17 chat message instead of the response kind
+const REPLY_KIND_TAG: &str = "reply_kind";
+
+// Request tag naming the sending app and version, e.g. ["client", "peek-web", "1.4.2"]
+const CLIENT_TAG: &str = "client";
+
+// Client names and versions are cut to this many characters before logging or labelling
+const MAX_CLIENT_FIELD_CHARS: usize = 32;
+
+// Rumors older than this are treated as replays; requests are answered within seconds
+const MAX_RUMOR_AGE_SECS: u64 = 24 * 60 * 60;
+
+// Allowed client clock skew for rumors dated in the future
+const MAX_RUMOR_FUTURE_SECS: u64 = 5 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct LocationData {
     pub latitude: f64,
     pub longitude: f64,
-    pub accuracy: f64,
+    // iOS sends -1 and Android 0 for an unknown accuracy; the web may leave it out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy: Option<f64>,
     pub timestamp: i64,
 }
 
+impl LocationData {
+    /// Reject non-finite, out-of-range or nonsensical accuracy values before any processing
+    ///
+    /// The fields stay raw f64 on the wire so garbage is answered with INVALID_LOCATION_DATA
+    /// rather than a parse failure; only the checked fix goes any further.
+    pub fn check(&self) -> Result<LocationFix, InvalidLocationData> {
+        check_location_data(self.latitude, self.longitude, self.accuracy)
+    }
+}
+
 // Unified request types using serde's tag attribute
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum ServiceRequest {
     #[serde(rename = "location_validation")]
     LocationValidation {
         community_id: String,
         location: LocationData,
+        // Optional unix timestamp after which a newly created community stops accepting joins
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        active_until: Option<u64>,
+        // Create a new community even if one with members is already anchored at this spot
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        force: bool,
+        // Set from the sticker's metadata for venues legitimately far from land or near a pole
+        // (boats, research stations); skips the creation plausibility gate
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        remote_venue: bool,
+        // Member cap for a newly created community; absent or zero is unlimited
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_members: Option<u32>,
+        // Set from the sticker's metadata by venues like bars, marking a new community 18+
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        age_restricted: bool,
+        // Leave the community of the same exclusive cluster the requester belongs to instead
+        // of being refused with ALREADY_IN_CLUSTER; its admins are refused regardless
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        transfer: bool,
     },
     #[serde(rename = "preview_request")]
     PreviewRequest { community_id: String },
+    // Several previews in one round trip, for clients showing nearby communities on a map
+    #[serde(rename = "preview_batch")]
+    PreviewBatch { community_ids: Vec<String> },
+    // Admin-only: add another QR anchor location to an existing community
+    #[serde(rename = "add_anchor")]
+    AddAnchor {
+        community_id: String,
+        location: LocationData,
+    },
+    // Admin-only: change community settings; absent fields are left unchanged
+    #[serde(rename = "update_metadata")]
+    UpdateMetadata {
+        community_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        join_mode: Option<JoinMode>,
+        // New member cap; zero removes it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_members: Option<u32>,
+        // Sanitized before publishing; rejected with INVALID_METADATA if unusable
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        about: Option<String>,
+        // Greeting for new members; an empty string removes it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        welcome: Option<String>,
+        // Shareable alias accepted wherever a community UUID is; an empty string removes it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        slug: Option<String>,
+        // Whether admins get member_joined pushes; on unless turned off
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        join_notifications: Option<bool>,
+        // Mark or unmark the community 18+; ages are never checked, clients just confirm
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        age_restricted: Option<bool>,
+        // Days without activity after which a member is warned and, a week later, removed;
+        // at least 14, zero turns it off
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        inactive_prune_days: Option<u32>,
+        // Nightly window, in local time and an IANA timezone, when routine pushes such as
+        // member_joined are held until it ends; a start equal to the end turns it off
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        quiet_hours: Option<QuietHours>,
+    },
+    // Admin-only: approve or reject a pending join request in an approval-mode community
+    #[serde(rename = "approve_join")]
+    ApproveJoin {
+        community_id: String,
+        pubkey: String,
+        approve: bool,
+    },
+    // Applicant withdraws their own pending join request
+    #[serde(rename = "cancel_join_request")]
+    CancelJoinRequest { community_id: String },
+    // Admin-only: stop new joins and hide from discovery, keeping the community readable
+    #[serde(rename = "archive_community")]
+    ArchiveCommunity { community_id: String },
+    // Admin-only: undo archive_community
+    #[serde(rename = "unarchive_community")]
+    UnarchiveCommunity { community_id: String },
+    // Admin-only: metadata, members and stats as one document, inline or as a download URL
+    #[serde(rename = "export_community")]
+    ExportCommunity { community_id: String },
+    // Admin-only: add members by pubkey, for crews moving over from another group chat
+    #[serde(rename = "bulk_add_members")]
+    BulkAddMembers {
+        community_id: String,
+        pubkeys: Vec<String>,
+    },
 }
 
 // Unified response types using serde's tag attribute
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum ServiceResponse {
     #[serde(rename = "location_validation_response")]
@@ -54,25 +244,562 @@ pub enum ServiceResponse {
         is_member: Option<bool>,
         error: Option<String>,
         error_code: Option<String>,
+        // Localizable form of `error`, see handlers::error_codes
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+        // Set with NEARBY_COMMUNITY_EXISTS so the client can offer to join it instead
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        existing_community: Option<ExistingCommunity>,
+        // Pending when the community requires admin approval before adding the member
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<JoinRequestStatus>,
+        // Signed proof of the membership grant, only on responses that grant membership
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attestation: Option<Attestation>,
+        // The community's welcome message, only on the response that first adds the member
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        welcome: Option<Welcome>,
+        // Name and picture of the joined community, only on successful responses
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        community_name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        picture: Option<String>,
+        // Whether the community is marked 18+, also only on successful responses
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        age_restricted: Option<bool>,
+        // Set once the member is added: whether they must NIP-42 AUTH before subscribing,
+        // and whether the group or the whole relay asks for it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        auth_required: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        auth_scope: Option<AuthScope>,
+        // Set by a read-only deployment: every check ran, but nothing was created or joined
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dry_run: Option<bool>,
     },
     #[serde(rename = "preview_response")]
-    Preview {
+    Preview(PreviewResult),
+    // One result per requested id, in request order, each with its own success and error
+    #[serde(rename = "preview_batch_response")]
+    PreviewBatch {
         success: bool,
-        name: Option<String>,
-        picture: Option<String>,
-        about: Option<String>,
-        rules: Option<Vec<String>>,
-        member_count: Option<u32>,
-        members: Option<Vec<String>>,
-        is_public: Option<bool>,
-        is_open: Option<bool>,
-        created_at: Option<u64>,
+        results: Vec<PreviewResult>,
+        error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "add_anchor_response")]
+    AddAnchor {
+        success: bool,
+        anchor_count: Option<usize>,
+        error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "update_metadata_response")]
+    UpdateMetadata {
+        success: bool,
+        error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "approve_join_response")]
+    ApproveJoin {
+        success: bool,
+        status: Option<JoinRequestStatus>,
+        error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    // Unsolicited: sent to admins when members join, at most once per notification window
+    #[serde(rename = "member_joined")]
+    MemberJoined {
+        community_id: String,
+        community_name: String,
+        member_count: u32,
+        // Everyone who joined since the last notification, in join order
+        pubkeys: Vec<String>,
+    },
+    // Unsolicited: sent to a quiet member of a community that prunes inactive members, a week
+    // before they are removed
+    #[serde(rename = "inactivity_warning")]
+    InactivityWarning {
+        community_id: String,
+        community_name: String,
+        inactive_days: u64,
+        // Unix time from which they are removed unless they post in the community
+        remove_after: u64,
+    },
+    // Unsolicited: sent to admins when failed joins keep clustering far from every anchor,
+    // which suggests the community's sticker was copied and put up somewhere else
+    #[serde(rename = "sticker_reuse_suspected")]
+    StickerReuseSuspected {
+        community_id: String,
+        community_name: String,
+        // Geohash-5 cell, about 5km across, where the failed joins cluster
+        cluster: String,
+        // From the cluster to the nearest anchor
+        distance_km: u64,
+        failures: u32,
+    },
+    // Unsolicited: sent to admins when a request is queued, and to the applicant once resolved
+    #[serde(rename = "join_request_update")]
+    JoinRequestUpdate {
+        community_id: String,
+        pubkey: String,
+        status: JoinRequestStatus,
+        // Set with JOIN_REQUEST_EXPIRED so clients can explain the expiry
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "cancel_join_request_response")]
+    CancelJoinRequest {
+        success: bool,
+        status: Option<JoinRequestStatus>,
+        error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    // Answers both archive_community and unarchive_community with the resulting state
+    #[serde(rename = "archive_community_response")]
+    ArchiveCommunity {
+        success: bool,
+        archived: Option<bool>,
+        error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    // Carries either the export itself or, when it is too large to send, where to fetch it
+    #[serde(rename = "export_community_response")]
+    ExportCommunity {
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        export: Option<Box<CommunityExport>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        export_url: Option<String>,
+        error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
+    },
+    // One outcome per requested pubkey, in request order; empty when the batch was refused
+    #[serde(rename = "bulk_add_members_response")]
+    BulkAddMembers {
+        success: bool,
+        outcomes: Vec<BulkAddOutcome>,
         error: Option<String>,
+        error_code: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        params: Option<BTreeMap<String, String>>,
     },
 }
 
+impl ServiceRequest {
+    /// Request type and community under which a repeat of this request shares the in-flight one
+    ///
+    /// Only requests whose repeat can only mean "the same again" coalesce; edits, anchors and
+    /// approvals may carry different content and always run on their own.
+    fn coalescing_scope(&self) -> Option<(&'static str, &str)> {
+        match self {
+            Self::LocationValidation { community_id, .. } => {
+                Some(("location_validation", community_id))
+            }
+            Self::CancelJoinRequest { community_id } => Some(("cancel_join_request", community_id)),
+            Self::ArchiveCommunity { community_id } => Some(("archive_community", community_id)),
+            Self::UnarchiveCommunity { community_id } => {
+                Some(("unarchive_community", community_id))
+            }
+            _ => None,
+        }
+    }
+
+    /// The admin action this request asks for, to be recorded in the audit trail
+    ///
+    /// Every request that changes a community on an admin's say-so is recorded, whoever sent
+    /// it, so a non-admin trying one shows up as rejected. The request itself is only hashed.
+    fn audit_action(&self, sender: &PublicKey) -> Option<AuditAction> {
+        let (action, community_id) = match self {
+            Self::AddAnchor { community_id, .. } => ("add_anchor", community_id),
+            Self::UpdateMetadata { community_id, .. } => ("update_metadata", community_id),
+            Self::ApproveJoin { community_id, .. } => ("approve_join", community_id),
+            Self::ArchiveCommunity { community_id } => ("archive_community", community_id),
+            Self::UnarchiveCommunity { community_id } => ("unarchive_community", community_id),
+            Self::BulkAddMembers { community_id, .. } => ("bulk_add_members", community_id),
+            _ => return None,
+        };
+        let params = serde_json::to_value(self).unwrap_or_default();
+        Some(
+            AuditAction::new(AuditActor::Pubkey(sender.to_hex()), action, params)
+                .community(community_id),
+        )
+    }
+
+    /// Failed response of the type that answers this request
+    pub(crate) fn failure_response(
+        &self,
+        error: String,
+        code: ValidationErrorCode,
+    ) -> ServiceResponse {
+        let (error, error_code) = (Some(error), Some(code.code().to_string()));
+        let (message_key, params) = (Some(code.message_key().to_string()), Some(code.params()));
+        match self {
+            Self::LocationValidation { .. } => {
+                LocationValidationResponse::failure(error.unwrap_or_default(), code)
+                    .into_service_response(None)
+            }
+            Self::PreviewRequest { .. } => ServiceResponse::Preview(PreviewResult {
+                error_code,
+                ..PreviewResult::failure(error.unwrap_or_default())
+            }),
+            Self::PreviewBatch { .. } => ServiceResponse::PreviewBatch {
+                success: false,
+                results: Vec::new(),
+                error,
+                error_code,
+                message_key,
+                params,
+            },
+            Self::AddAnchor { .. } => ServiceResponse::AddAnchor {
+                success: false,
+                anchor_count: None,
+                error,
+                error_code,
+                message_key,
+                params,
+            },
+            Self::UpdateMetadata { .. } => ServiceResponse::UpdateMetadata {
+                success: false,
+                error,
+                error_code,
+                message_key,
+                params,
+            },
+            Self::ApproveJoin { .. } => ServiceResponse::ApproveJoin {
+                success: false,
+                status: None,
+                error,
+                error_code,
+                message_key,
+                params,
+            },
+            Self::CancelJoinRequest { .. } => ServiceResponse::CancelJoinRequest {
+                success: false,
+                status: None,
+                error,
+                error_code,
+                message_key,
+                params,
+            },
+            Self::ArchiveCommunity { .. } | Self::UnarchiveCommunity { .. } => {
+                ServiceResponse::ArchiveCommunity {
+                    success: false,
+                    archived: None,
+                    error,
+                    error_code,
+                    message_key,
+                    params,
+                }
+            }
+            Self::ExportCommunity { .. } => ServiceResponse::ExportCommunity {
+                success: false,
+                export: None,
+                export_url: None,
+                error,
+                error_code,
+                message_key,
+                params,
+            },
+            Self::BulkAddMembers { .. } => ServiceResponse::BulkAddMembers {
+                success: false,
+                outcomes: Vec::new(),
+                error,
+                error_code,
+                message_key,
+                params,
+            },
+        }
+    }
+}
+
+/// Sender, request type and community of a request that can be coalesced
+type InFlightKey = (PublicKey, &'static str, String);
+
+impl ServiceResponse {
+    /// How an audited request ended, with the error code when it did not succeed
+    ///
+    /// Codes for relay and service trouble mean the action was attempted and failed; any other
+    /// code means the service refused it.
+    fn audit_outcome(&self) -> (AuditOutcome, Option<String>) {
+        let response = serde_json::to_value(self).unwrap_or_default();
+        if response["success"] == true {
+            return (AuditOutcome::Ok, None);
+        }
+        let code = response["error_code"].as_str().map(str::to_string);
+        let failed = code
+            .as_deref()
+            .is_some_and(|code| code.ends_with("_FAILED") || code == "SERVICE_UNAVAILABLE");
+        let outcome = if failed {
+            AuditOutcome::Failed
+        } else {
+            AuditOutcome::Rejected
+        };
+        (outcome, code)
+    }
+
+    /// Join request status push; expirations carry the JOIN_REQUEST_EXPIRED code
+    pub fn join_request_update(
+        community_id: String,
+        pubkey: String,
+        status: JoinRequestStatus,
+    ) -> Self {
+        let code = (status == JoinRequestStatus::Expired)
+            .then_some(ValidationErrorCode::JoinRequestExpired);
+        Self::JoinRequestUpdate {
+            community_id,
+            pubkey,
+            status,
+            error_code: code.as_ref().map(|c| c.code().to_string()),
+            message_key: code.as_ref().map(|c| c.message_key().to_string()),
+            params: code.as_ref().map(|c| c.params()),
+        }
+    }
+
+    /// The push telling a quiet member when they will be removed
+    pub fn inactivity_warning(warning: &InactivityWarning) -> Self {
+        Self::InactivityWarning {
+            community_id: warning.community_id.to_string(),
+            community_name: warning.community_name.clone(),
+            inactive_days: warning.inactive_days,
+            remove_after: warning.remove_after,
+        }
+    }
+
+    /// The alert telling admins that joins keep failing around a spot far from their anchors
+    pub fn sticker_reuse_suspected(flag: &ReuseFlag, community_name: String) -> Self {
+        Self::StickerReuseSuspected {
+            community_id: flag.community_id.to_string(),
+            community_name,
+            cluster: flag.cluster.clone(),
+            distance_km: flag.distance_km,
+            failures: flag.failures,
+        }
+    }
+}
+
+/// Community preview returned in ServiceResponse::Preview
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PreviewResult {
+    pub success: bool,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+    pub about: Option<String>,
+    pub rules: Option<Vec<String>>,
+    pub member_count: Option<u32>,
+    pub members: Option<Vec<String>>,
+    pub is_public: Option<bool>,
+    pub is_open: Option<bool>,
+    pub created_at: Option<u64>,
+    // Time-boxed communities whose deadline has passed are flagged as archived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<bool>,
+    // Member cap, only on communities that set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_members: Option<u32>,
+    // Whether the cap is reached, so clients can say so before the user tries to join
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_full: Option<bool>,
+    // Marked 18+ by the venue, so clients can ask for confirmation before showing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_restricted: Option<bool>,
+    // When routine pushes are held back, so joiners know what to expect; absent if never
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+    // The venue's OSM opening hours and whether it is open now; absent if OSM has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venue_hours: Option<VenueHoursStatus>,
+    // The requester's membership, so members are not offered "Join"; absent if not found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_member: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<MemberRole>,
+    pub error: Option<String>,
+    // Only set when the client can act on the failure, e.g. retry after relay reads overran the
+    // request deadline, or stop offering "Join" on an archived community
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+}
+
+impl PreviewResult {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+
+    fn retry_later(error: impl Into<String>) -> Self {
+        Self {
+            error_code: Some(ValidationErrorCode::RetryLater.code().to_string()),
+            ..Self::failure(error)
+        }
+    }
+
+    fn invalid_id(error: impl Into<String>) -> Self {
+        Self {
+            error_code: Some(ValidationErrorCode::InvalidId.code().to_string()),
+            ..Self::failure(error)
+        }
+    }
+
+    fn archived() -> Self {
+        Self {
+            error_code: Some(ValidationErrorCode::CommunityArchived.code().to_string()),
+            ..Self::failure("Community has been archived")
+        }
+    }
+
+    fn from_metadata(
+        metadata: GroupMetadata,
+        members: Option<Vec<String>>,
+        now: Timestamp,
+    ) -> Self {
+        let archived = metadata.archived || metadata.is_expired_at(now);
+        let is_full = metadata.is_full();
+        // Without a known venue timezone, the admins' quiet hours timezone is the best guess
+        let venue_hours = metadata.venue_hours.as_ref().map(|hours| {
+            hours.status(
+                now.as_u64(),
+                metadata.quiet_hours.map(|quiet| quiet.timezone()),
+            )
+        });
+        Self {
+            success: true,
+            name: Some(metadata.name),
+            picture: metadata.picture,
+            about: metadata.about,
+            rules: metadata.rules,
+            member_count: Some(metadata.member_count),
+            members,
+            is_public: Some(metadata.is_public),
+            is_open: Some(metadata.is_open),
+            created_at: Some(metadata.created_at.as_u64()),
+            archived: Some(archived),
+            max_members: metadata.max_members,
+            is_full: Some(is_full),
+            age_restricted: Some(metadata.age_restricted),
+            quiet_hours: metadata.quiet_hours,
+            venue_hours,
+            is_member: None,
+            role: None,
+            error: None,
+            error_code: None,
+        }
+    }
+
+    /// Add the requester's membership, as found by `requester_role`
+    fn for_requester(self, role: Option<MemberRole>) -> Self {
+        Self {
+            is_member: Some(role.is_some()),
+            role,
+            ..self
+        }
+    }
+}
+
+/// A requester's role in a community, shown on previews
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberRole {
+    Admin,
+    Member,
+}
+
+/// The requester's role given whether they are on the member list and the group's admin list
+/// `admins` is only read for members; None when it could not be read
+fn requester_role(
+    pubkey: &PublicKey,
+    is_member: bool,
+    admins: Option<&[PublicKey]>,
+) -> Option<MemberRole> {
+    if !is_member {
+        return None;
+    }
+    if admins.is_some_and(|admins| admins.contains(pubkey)) {
+        Some(MemberRole::Admin)
+    } else {
+        Some(MemberRole::Member)
+    }
+}
+
+/// Welcome message and rules shown to a member right after their first join
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Welcome {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<String>,
+}
+
+/// The welcome for a user joining `community`, unless they were already a member
+/// A member list that may be missing members could be hiding them, so it sends none either
+fn first_join_welcome(community: &CommunityMetadata, already_member: bool) -> Option<Welcome> {
+    if already_member || community.members.possibly_incomplete {
+        return None;
+    }
+    community.welcome.as_ref().map(|text| Welcome {
+        text: text.clone(),
+        rules: community.rules.clone(),
+    })
+}
+
+/// A community already anchored where the requester tried to create a new one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExistingCommunity {
+    pub community_id: Option<String>,
+    pub group_id: String,
+    pub preview: PreviewResult,
+}
+
+/// Requester choices that only apply if a location validation ends up creating the community
+#[derive(Debug, Clone, Copy, Default)]
+struct CreationOptions {
+    active_until: Option<Timestamp>,
+    force: bool,
+    remote_venue: bool,
+    max_members: Option<u32>,
+    age_restricted: bool,
+}
+
 // Legacy types for backwards compatibility
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LocationValidationRequest {
     #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
     pub request_type: Option<String>,
@@ -80,7 +807,7 @@ pub struct LocationValidationRequest {
     pub location: LocationData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LocationValidationResponse {
     #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
     pub response_type: Option<String>,
@@ -91,6 +818,85 @@ pub struct LocationValidationResponse {
     pub is_member: Option<bool>,
     pub error: Option<String>,
     pub error_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub existing_community: Option<ExistingCommunity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<JoinRequestStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub welcome: Option<Welcome>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub community_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub picture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_restricted: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_required: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_scope: Option<AuthScope>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<bool>,
+}
+
+impl LocationValidationResponse {
+    fn into_service_response(self, attestation: Option<Attestation>) -> ServiceResponse {
+        ServiceResponse::LocationValidation {
+            success: self.success,
+            group_id: self.group_id,
+            relay_url: self.relay_url,
+            is_admin: self.is_admin,
+            is_member: self.is_member,
+            error: self.error,
+            error_code: self.error_code,
+            message_key: self.message_key,
+            params: self.params,
+            existing_community: self.existing_community,
+            status: self.status,
+            attestation,
+            welcome: self.welcome,
+            community_name: self.community_name,
+            picture: self.picture,
+            age_restricted: self.age_restricted,
+            auth_required: self.auth_required,
+            auth_scope: self.auth_scope,
+            dry_run: self.dry_run,
+        }
+    }
+
+    fn failure(error: impl Into<String>, code: ValidationErrorCode) -> Self {
+        Self {
+            response_type: Some("location_validation_response".to_string()),
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some(error.into()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        }
+    }
+}
+
+/// A push waiting for its community's quiet hours to end
+struct HeldPush {
+    recipient: PublicKey,
+    update: ServiceResponse,
+    what: &'static str,
 }
 
 #[derive(Clone)]
@@ -98,34 +904,75 @@ pub struct NostrValidationHandler {
     client: Client,
     service_keys: Keys,
     community_service: Arc<CommunityService>,
-    relay_service: Arc<RwLock<RelayService>>,
+    groups: GroupReader,
+    writer: GroupWriter,
     config: Config,
     gift_wrap_service: Arc<GiftWrapService>,
     migration_monitor: Arc<MigrationMonitor>,
+    inbox_relays: Arc<InboxRelayResolver>,
+    response_retry: ResponseRetryQueue,
+    watchdog: Arc<SubscriptionWatchdog>,
+    // Previews of nonexistent communities, per requester
+    preview_misses: Arc<UnknownIdLimiter>,
+    // Rejected locations per (requester, community), to spot anchor probing
+    location_probes: Arc<LocationProbeGuard>,
+    // Joins from far outside a community, rejected before the precise checks and counted
+    far_scans: Arc<FarScans>,
+    // Failed joins clustering away from a community's anchors, a sign of a copied sticker
+    sticker_reuse: Arc<StickerReuse>,
+    // Admin-level requests, recorded with who sent them and how they ended
+    audit_trail: Arc<AuditTrail>,
+    // Communities a member may only belong to one of, defined through the admin API
+    clusters: Arc<CommunityClusters>,
+    // Admin exports, rate limited per admin
+    exporter: Arc<CommunityExporter<BlossomUploader>>,
+    // Admin adds by pubkey, within each community's allowance
+    bulk_adder: Arc<BulkAdder>,
+    // Requests being processed, so re-sent copies share their result
+    in_flight: Arc<InFlight<InFlightKey, ServiceResponse>>,
+    // Country tag and jurisdiction filter for new communities
+    #[cfg(feature = "country-lookup")]
+    country_gate: Option<Arc<CountryGate>>,
+    // Sensitive areas; new communities anchored in one are flagged to the operator
+    exclusion_zones: Arc<ExclusionZones>,
+    operator_webhook: OperatorWebhook,
+    // New members awaiting the next notification to their community's admins
+    join_batcher: Arc<JoinBatcher>,
+    // Warns and removes quiet members of communities that opted in
+    inactive_prune: Arc<InactivePrune>,
+    // Routine pushes generated during their community's quiet hours
+    quiet_queue: Arc<QuietHoursQueue<HeldPush>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl NostrValidationHandler {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         config: Config,
+        keys: ServiceKeys,
         community_service: Arc<CommunityService>,
-        relay_service: Arc<RwLock<RelayService>>,
+        groups: GroupReader,
+        writer: GroupWriter,
+        client_pool: Arc<ClientPool>,
+        watchdog: Arc<SubscriptionWatchdog>,
+        prune_log: Arc<PruneLog>,
+        far_scans: Arc<FarScans>,
+        sticker_reuse: Arc<StickerReuse>,
+        audit_trail: Arc<AuditTrail>,
+        clusters: Arc<CommunityClusters>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        // Parse the service's secret key from hex (for gift wrap recipient identity)
-        let secret_key = SecretKey::from_hex(&config.service_secret_key)
-            .map_err(|e| format!("Failed to parse service secret key: {}", e))?;
-        let service_keys = Keys::new(secret_key);
+        // Gift wrap recipient identity
+        let ServiceKeys {
+            service: service_keys,
+            relay: relay_keys,
+        } = keys;
 
         info!(
             "Service pubkey (gift wrap recipient): {}",
             service_keys.public_key().to_bech32()?
         );
 
-        // Parse relay secret key for authentication (admin privileges)
-        let relay_secret_key = SecretKey::from_bech32(&config.relay_secret_key)
-            .or_else(|_| SecretKey::from_hex(&config.relay_secret_key))
-            .map_err(|e| format!("Failed to parse relay secret key: {}", e))?;
-        let relay_keys = Keys::new(relay_secret_key);
-
+        // Relay keys authenticate with admin privileges
         info!(
             "Relay pubkey (authentication): {}",
             relay_keys.public_key().to_bech32()?
@@ -135,24 +982,10 @@ impl NostrValidationHandler {
         let client = Client::new(relay_keys.clone());
 
         // Add relays for receiving gift wraps
-        // Use only local relay for development/testing environments
-        let relays = if config.relay_url.starts_with("ws://localhost")
-            || config.relay_url.starts_with("ws://127.0.0.1")
-            || config.relay_url.starts_with("ws://groups_relay")
-            || config.relay_url.starts_with("ws://host.docker.internal")
-        {
-            vec![config.relay_url.clone()]
-        } else {
-            vec![
-                config.relay_url.clone(),
-                "wss://relay.damus.io".to_string(),
-                "wss://relay.nostr.band".to_string(),
-                "wss://nos.lol".to_string(),
-            ]
-        };
+        let relays = listening_relays(&config);
 
         for relay_url in &relays {
-            client.add_relay(relay_url).await?;
+            client.add_relay(relay_url.as_str()).await?;
         }
 
         // Enable automatic authentication for NIP-42
@@ -165,19 +998,120 @@ impl NostrValidationHandler {
         );
 
         // Create gift wrap service
-        let gift_wrap_service = Arc::new(GiftWrapService::new(service_keys.clone()));
+        let gift_wrap_service = Arc::new(GiftWrapService::new(
+            service_keys.clone(),
+            client_pool.clone(),
+        ));
 
         // Create migration monitor (uses relay service's authenticated client)
-        let migration_monitor = Arc::new(MigrationMonitor::new(relay_service.clone()));
+        let migration_monitor = Arc::new(MigrationMonitor::new(groups.clone(), writer.clone()));
+
+        // Resolve requesters' NIP-65 inbox relays for response delivery
+        let inbox_relays = Arc::new(InboxRelayResolver::new(
+            config.inbox_fanout_max,
+            config.inbox_index_relays.clone(),
+            config.relay_url.is_local(),
+            client_pool,
+        ));
+
+        // Responses that fail to send are retried in the background with backoff
+        let (response_retry, retry_rx) = ResponseRetryQueue::new(config.response_retry_capacity);
+        let retry_sender = GiftWrapResponseSender {
+            gift_wrap_service: gift_wrap_service.clone(),
+            client: client.clone(),
+        };
+        let retry_policy = RetryPolicy {
+            base_delay: std::time::Duration::from_secs(2),
+            max_delay: std::time::Duration::from_secs(60),
+            max_elapsed: std::time::Duration::from_secs(config.response_retry_max_secs),
+        };
+        tokio::spawn(run_retry_worker(retry_rx, retry_sender, retry_policy));
+
+        let preview_misses = Arc::new(UnknownIdLimiter::new(config.preview_miss_limit));
+        let location_probes = Arc::new(LocationProbeGuard::new(
+            ProbePolicy {
+                threshold: config.probe_rejection_threshold,
+                window_secs: config.probe_window_secs,
+                cool_down_secs: config.probe_cool_down_secs,
+                max_jitter: std::time::Duration::from_millis(config.probe_jitter_max_ms),
+            },
+            Arc::new(ThreadRngSource),
+        ));
+        // Exports too large to send inline need a media server to go through
+        let exporter = Arc::new(CommunityExporter::new(
+            config
+                .export_media_server
+                .as_deref()
+                .map(|server| BlossomUploader::new(server, service_keys.clone())),
+            ExportLimiter::new(config.export_daily_limit),
+            config.export_inline_max_bytes,
+        ));
+        let bulk_adder = Arc::new(BulkAdder::new(
+            config.bulk_add_allowance,
+            config.bulk_add_per_second,
+        ));
+        // Jurisdiction filters that cannot be applied must stop startup, not be ignored
+        #[cfg(feature = "country-lookup")]
+        let country_gate = CountryGate::load(
+            config.country_boundaries_path.as_deref(),
+            &config.country_allowlist,
+            &config.country_denylist,
+        )?
+        .map(Arc::new);
+        #[cfg(not(feature = "country-lookup"))]
+        if config.country_boundaries_path.is_some()
+            || config
+                .country_allowlist
+                .iter()
+                .chain(&config.country_denylist)
+                .any(|code| !code.trim().is_empty())
+        {
+            return Err("COUNTRY_* settings need a build with --features country-lookup".into());
+        }
+        let exclusion_zones = Arc::new(ExclusionZones::parse(&config.exclusion_zones)?);
+        let operator_webhook = OperatorWebhook::new(config.operator_webhook_url.clone());
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let join_batcher = Arc::new(JoinBatcher::new(
+            config.join_notification_window_secs,
+            clock.clone(),
+        ));
+        let inactive_prune = Arc::new(InactivePrune::new(
+            writer.clone(),
+            clock.clone(),
+            &relay_keys.public_key(),
+            prune_log,
+        ));
+        let quiet_queue = Arc::new(QuietHoursQueue::new(MAX_HELD_PUSHES, clock.clone()));
 
         Ok(Self {
             client,
             service_keys,
             community_service,
-            relay_service,
+            groups,
+            writer,
             config,
             gift_wrap_service,
             migration_monitor,
+            inbox_relays,
+            response_retry,
+            watchdog,
+            preview_misses,
+            location_probes,
+            far_scans,
+            sticker_reuse,
+            audit_trail,
+            clusters,
+            exporter,
+            bulk_adder,
+            in_flight: Arc::new(InFlight::new()),
+            #[cfg(feature = "country-lookup")]
+            country_gate,
+            exclusion_zones,
+            operator_webhook,
+            join_batcher,
+            inactive_prune,
+            quiet_queue,
+            clock,
         })
     }
 
@@ -185,8 +1119,15 @@ impl NostrValidationHandler {
     pub async fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
         info!("Starting NIP-59 gift wrap listener and migration monitor");
 
+        // Advertise capabilities so clients don't have to hard-code them
+        self.publish_service_descriptor().await;
+        if self.config.publish_app_handler {
+            self.publish_app_handler().await;
+        }
+
         // Start migration monitor using the same client as gift wrap listener
-        self.migration_monitor
+        let migration_subscription = self
+            .migration_monitor
             .start_monitoring(&self.client)
             .await?;
 
@@ -206,7 +1147,47 @@ impl NostrValidationHandler {
         );
 
         // Subscribe to the filter
-        self.client.subscribe(filter, None).await?;
+        let gift_wrap_subscription = self.client.subscribe(filter.clone(), None).await?.val;
+
+        // Re-assert both subscriptions on relays that go quiet
+        for relay_url in listening_relays(&self.config) {
+            self.watchdog.watch(relay_url.as_str());
+        }
+        let subscriptions = vec![
+            (gift_wrap_subscription, filter),
+            (
+                migration_subscription,
+                MigrationMonitor::subscription_filter(),
+            ),
+        ];
+        let watchdog_handler = self.clone();
+        tokio::spawn(async move {
+            watchdog_handler
+                .run_subscription_watchdog(subscriptions)
+                .await
+        });
+
+        // Expire join requests admins never got to
+        let sweeper = self.clone();
+        tokio::spawn(async move { sweeper.run_join_request_sweeper().await });
+
+        // Tell admins about new members once each community's burst has settled
+        let notifier = self.clone();
+        tokio::spawn(async move { notifier.run_join_notifier().await });
+
+        // Warn, then remove, members who went quiet in communities that asked for it
+        if self.config.inactive_prune_interval_secs > 0 {
+            let pruner = self.clone();
+            tokio::spawn(async move { pruner.run_inactive_prune().await });
+        }
+
+        // Send what quiet hours held back once each community's window ends
+        let flusher = self.clone();
+        tokio::spawn(async move { flusher.run_quiet_hours_flush().await });
+
+        // Tell admins when their community's sticker looks copied
+        let reuse_notifier = self.clone();
+        tokio::spawn(async move { reuse_notifier.run_sticker_reuse_alerts().await });
 
         info!("Starting notification handler, waiting for gift wraps and migrations...");
 
@@ -221,6 +1202,8 @@ impl NostrValidationHandler {
                         event, relay_url, ..
                     } = notification
                     {
+                        handler.watchdog.record_event(relay_url.as_str());
+
                         if event.kind == Kind::GiftWrap {
                             info!(
                                 "📦 Received gift wrap from {} via {} (event: {})",
@@ -235,10 +1218,13 @@ impl NostrValidationHandler {
                             // Clone the event and process it with the actual handler
                             let gift_wrap = event.as_ref().clone();
 
-                            // Process the gift wrap using the real handle_gift_wrap method
-                            if let Err(e) = handler.handle_gift_wrap(gift_wrap).await {
-                                error!("❌ Failed to handle gift wrap: {}", e);
-                            }
+                            // On its own task, so a retry arriving mid-request is seen while
+                            // the first copy is still in flight and can be coalesced onto it
+                            tokio::spawn(contain_panic("gift_wrap", async move {
+                                if let Err(e) = handler.handle_gift_wrap(gift_wrap).await {
+                                    error!("❌ Failed to handle gift wrap: {}", e);
+                                }
+                            }));
                         } else if event.kind == MIGRATION_KIND {
                             info!(
                                 "🔄 Received migration event from {} via {} (event: {})",
@@ -250,14 +1236,18 @@ impl NostrValidationHandler {
                                 event.id.to_hex()
                             );
 
-                            // Process migration event
-                            if let Err(e) = handler
-                                .migration_monitor
-                                .handle_migration_event(event.as_ref().clone())
-                                .await
-                            {
-                                error!("❌ Failed to handle migration event: {}", e);
-                            }
+                            // Process migration event; it runs on the listener itself, so a panic
+                            // here must not unwind any further
+                            contain_panic("migration", async {
+                                if let Err(e) = handler
+                                    .migration_monitor
+                                    .handle_migration_event(event.as_ref().clone())
+                                    .await
+                                {
+                                    error!("❌ Failed to handle migration event: {}", e);
+                                }
+                            })
+                            .await;
                         } else {
                             debug!("⏩ Ignoring event kind {}", event.kind.as_u16());
                         }
@@ -272,149 +1262,552 @@ impl NostrValidationHandler {
         Ok(())
     }
 
-    /// Handle a received gift wrap event
-    async fn handle_gift_wrap(&self, gift_wrap: Event) -> Result<(), Box<dyn std::error::Error>> {
-        let handle_start = std::time::Instant::now();
-        info!(
-            "⏱️ 🎁 handle_gift_wrap called at {:?} - processing event {}",
-            handle_start,
-            gift_wrap.id.to_string()
-        );
-
-        info!(
-            "📦 Received gift wrap from {} (event: {})",
-            gift_wrap.pubkey.to_bech32()?,
-            &gift_wrap.id.to_string()[0..8]
-        );
-
-        // Unwrap the gift wrap using service keys (gift wrap is addressed to service pubkey)
-        // Note: client uses relay keys for auth, but gift wraps are encrypted to service keys
-        let unwrap_start = std::time::Instant::now();
-        info!("⏱️ Starting unwrap at {:?}", unwrap_start);
-        let unwrapped =
-            nostr_sdk::nips::nip59::extract_rumor(&self.service_keys, &gift_wrap).await?;
-        let unwrap_duration = unwrap_start.elapsed();
-        info!("⏱️ Unwrap completed in {:?}ms", unwrap_duration.as_millis());
-        let rumor = unwrapped.rumor;
-
-        // The actual sender is in the rumor pubkey, not the unwrapped.sender (which is ephemeral)
-        let actual_sender = rumor.pubkey;
-
-        info!(
-            "🔓 Unwrapped gift wrap - ephemeral sender: {} actual sender: {} (kind: {})",
-            unwrapped.sender.to_bech32()?,
-            actual_sender.to_bech32()?,
-            rumor.kind
-        );
-
-        // Log the full decrypted content for debugging
-        info!("📝 Decrypted rumor content: {}", rumor.content);
-        info!("🏷️ Rumor tags: {:?}", rumor.tags);
-        info!("🆔 Rumor ID: {:?}", rumor.id);
+    /// Periodically re-send our subscriptions on relays that have stopped delivering events
+    async fn run_subscription_watchdog(&self, subscriptions: Vec<(SubscriptionId, Filter)>) {
+        let silence_secs = self.config.subscription_silence_secs;
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs((silence_secs / 4).max(1)));
 
-        // Check if it's a request we handle
-        if rumor.kind != LOCATION_VALIDATION_REQUEST_KIND {
-            debug!("Ignoring non-validation rumor kind: {}", rumor.kind);
-            return Ok(());
+        loop {
+            interval.tick().await;
+            for action in self.watchdog.check() {
+                match action {
+                    WatchdogAction::Resubscribe(relay_url) => {
+                        warn!(
+                            "🔁 No events from {} for {}s, re-sending subscriptions",
+                            relay_url, silence_secs
+                        );
+                        metrics::increment(
+                            "peek_relay_resubscriptions_total",
+                            &[("relay", &relay_url)],
+                        );
+                        // Reusing the subscription ids replaces the REQ instead of duplicating it
+                        for (id, filter) in &subscriptions {
+                            if let Err(e) = self
+                                .client
+                                .subscribe_with_id_to(
+                                    [relay_url.as_str()],
+                                    id.clone(),
+                                    filter.clone(),
+                                    None,
+                                )
+                                .await
+                            {
+                                warn!("Failed to re-subscribe {} on {}: {}", id, relay_url, e);
+                            }
+                        }
+                    }
+                    WatchdogAction::Reconnect(relay_url) => {
+                        warn!(
+                            "🔌 {} still silent after re-subscribing, reconnecting",
+                            relay_url
+                        );
+                        metrics::increment("peek_relay_reconnects_total", &[("relay", &relay_url)]);
+                        // The pool re-sends active subscriptions once the relay reconnects
+                        if let Err(e) = self.client.disconnect_relay(relay_url.as_str()).await {
+                            warn!("Failed to disconnect {}: {}", relay_url, e);
+                        }
+                        if let Err(e) = self.client.connect_relay(relay_url.as_str()).await {
+                            warn!("Failed to reconnect {}: {}", relay_url, e);
+                        }
+                    }
+                }
+            }
         }
+    }
 
-        // Try to parse as unified request first, fall back to legacy format
-        let parse_start = std::time::Instant::now();
-        info!("⏱️ Starting request parsing at {:?}", parse_start);
-        let response = if let Ok(request) = serde_json::from_str::<ServiceRequest>(&rumor.content) {
-            // Handle unified request format
-            match request {
-                ServiceRequest::LocationValidation {
-                    community_id,
-                    location,
-                } => {
-                    info!(
-                        "📍 Location validation request for community: {} from user: {}",
-                        community_id,
-                        actual_sender.to_bech32()?
-                    );
-                    debug!(
-                        "   Location: ({:.6}, {:.6}) accuracy: {:.1}m",
-                        location.latitude, location.longitude, location.accuracy
-                    );
-
-                    let process_start = std::time::Instant::now();
-                    info!(
-                        "⏱️ Starting location validation processing at {:?}",
-                        process_start
-                    );
-                    let result = self
-                        .process_location_validation(community_id, location, actual_sender)
-                        .await;
-                    let process_duration = process_start.elapsed();
-                    info!(
-                        "⏱️ Location validation completed in {:?}ms",
-                        process_duration.as_millis()
-                    );
+    /// Send each closed batch of joins to its community's admins, unless they opted out
+    async fn run_join_notifier(&self) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
 
-                    ServiceResponse::LocationValidation {
-                        success: result.success,
-                        group_id: result.group_id,
-                        relay_url: result.relay_url,
-                        is_admin: result.is_admin,
-                        is_member: result.is_member,
-                        error: result.error,
-                        error_code: result.error_code,
+        loop {
+            interval.tick().await;
+            for batch in self.join_batcher.take_due() {
+                // Read when sending, so the name, count and opt-out are current
+                let metadata = match self.groups.get_group_metadata(&batch.group_id).await {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        warn!(
+                            "Not notifying admins of {} about {} joins: {}",
+                            batch.group_id,
+                            batch.pubkeys.len(),
+                            e
+                        );
+                        continue;
                     }
+                };
+                let Some(notification) = member_joined_notification(&batch, &metadata) else {
+                    continue;
+                };
+                let admins = match self.groups.get_group_admins(&batch.group_id).await {
+                    Ok(admins) => admins,
+                    Err(e) => {
+                        warn!("Failed to read admins of {}: {}", batch.group_id, e);
+                        continue;
+                    }
+                };
+                metrics::increment("peek_member_joined_notifications_total", &[]);
+                for admin in admins {
+                    self.push_or_hold(
+                        metadata.quiet_hours.as_ref(),
+                        PushKind::Routine,
+                        admin,
+                        notification.clone(),
+                        "member-joined",
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+
+    /// Periodically prune inactive members, gift-wrapping each new warning to its member
+    /// Warnings are only sent by executed runs; a dry run remembers none, so it has none to send
+    async fn run_inactive_prune(&self) {
+        let mode = ExecutionMode::from_dry_run(self.config.inactive_prune_dry_run)
+            .limited_to(self.writer.mode());
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+            self.config.inactive_prune_interval_secs,
+        ));
+
+        loop {
+            interval.tick().await;
+            let run = match self.inactive_prune.run_with(mode).await {
+                Ok(run) => run,
+                Err(e) => {
+                    error!("Failed to prune inactive members: {}", e);
+                    continue;
                 }
-                ServiceRequest::PreviewRequest { community_id } => {
+            };
+            if !run.report.removed.is_empty() || !run.report.failed.is_empty() {
+                info!(
+                    "🧹 Inactive member prune ({:?}): {} warned, {} removed, {} failed",
+                    mode,
+                    run.report.warned.len(),
+                    run.report.removed.len(),
+                    run.report.failed.len()
+                );
+            }
+            if run.plan.dry_run {
+                continue;
+            }
+            // Quiet hours per group, read once however many of its members are warned
+            let mut quiet_hours: BTreeMap<&str, Option<QuietHours>> = BTreeMap::new();
+            for warning in &run.report.warned {
+                let Ok(member) = PublicKey::from_hex(&warning.pubkey) else {
+                    continue;
+                };
+                let hours = match quiet_hours.get(warning.group_id.as_str()) {
+                    Some(hours) => *hours,
+                    None => {
+                        let hours = self
+                            .groups
+                            .get_group_metadata(&warning.group_id)
+                            .await
+                            .ok()
+                            .and_then(|metadata| metadata.quiet_hours);
+                        quiet_hours.insert(&warning.group_id, hours);
+                        hours
+                    }
+                };
+                self.push_or_hold(
+                    hours.as_ref(),
+                    PushKind::Routine,
+                    member,
+                    ServiceResponse::inactivity_warning(warning),
+                    "inactivity-warning",
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Send held pushes whose community's quiet hours have ended
+    async fn run_quiet_hours_flush(&self) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+
+        loop {
+            interval.tick().await;
+            let due = self.quiet_queue.take_due();
+            metrics::set_gauge(
+                "peek_quiet_hours_held",
+                &[],
+                self.quiet_queue.held_count() as u64,
+            );
+            if !due.is_empty() {
+                info!("🌙 Quiet hours over, sending {} held pushes", due.len());
+            }
+            for push in due {
+                self.push_update(push.recipient, &push.update, push.what)
+                    .await;
+            }
+        }
+    }
+
+    /// Send each new possible sticker reuse flag to its community's admins
+    async fn run_sticker_reuse_alerts(&self) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+
+        loop {
+            interval.tick().await;
+            for flag in self.sticker_reuse.take_unannounced() {
+                self.notify_sticker_reuse(&flag).await;
+            }
+        }
+    }
+
+    async fn notify_sticker_reuse(&self, flag: &ReuseFlag) {
+        let group_id = match self.groups.find_group_by_uuid(&flag.community_id).await {
+            Ok(Some(group_id)) => group_id,
+            Ok(None) => {
+                warn!(
+                    "Not notifying admins of possible sticker reuse: no group for {}",
+                    flag.community_id
+                );
+                return;
+            }
+            Err(e) => {
+                warn!(
+                    "Not notifying admins of {} about possible sticker reuse: {}",
+                    flag.community_id, e
+                );
+                return;
+            }
+        };
+        let (metadata, admins) = match tokio::try_join!(
+            self.groups.get_group_metadata(&group_id),
+            self.groups.get_group_admins(&group_id)
+        ) {
+            Ok(read) => read,
+            Err(e) => {
+                warn!(
+                    "Not notifying admins of {} about possible sticker reuse: {}",
+                    group_id, e
+                );
+                return;
+            }
+        };
+        let alert = ServiceResponse::sticker_reuse_suspected(flag, metadata.name);
+        // Admins may want to check the sticker or warn members, so this is never held
+        for admin in admins {
+            self.push_or_hold(
+                None,
+                PushKind::Moderation,
+                admin,
+                alert.clone(),
+                "sticker-reuse",
+            )
+            .await;
+        }
+    }
+
+    /// Periodically expire pending join requests older than the configured TTL
+    async fn run_join_request_sweeper(&self) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+            self.config.join_request_sweep_interval_secs.max(1),
+        ));
+
+        loop {
+            interval.tick().await;
+            let swept = sweep_expired_requests(
+                &self.writer,
+                self.clock.now_unix(),
+                self.config.join_request_ttl_secs,
+                |expired| async move {
                     info!(
-                        "🔍 Community preview request for: {} from user: {}",
-                        community_id,
-                        actual_sender.to_bech32()?
+                        "⌛ Join request from {} for group {} expired",
+                        expired.pubkey, expired.group_id
                     );
+                    metrics::increment("peek_join_requests_total", &[("status", "expired")]);
 
-                    let result = self.process_preview(community_id).await;
-
-                    ServiceResponse::Preview {
-                        success: result.0,
-                        name: result.1,
-                        picture: result.2,
-                        about: result.3,
-                        rules: result.4,
-                        member_count: result.5,
-                        members: result.6,
-                        is_public: result.7,
-                        is_open: result.8,
-                        created_at: result.9,
-                        error: result.10,
+                    let Some(community_id) = expired.community_id else {
+                        warn!(
+                            "Join queue for {} has no community id, not notifying {}",
+                            expired.group_id, expired.pubkey
+                        );
+                        return;
+                    };
+                    match PublicKey::from_hex(&expired.pubkey) {
+                        Ok(applicant) => {
+                            let update = ServiceResponse::join_request_update(
+                                community_id,
+                                expired.pubkey,
+                                JoinRequestStatus::Expired,
+                            );
+                            self.push_update(applicant, &update, "join-request-update")
+                                .await;
+                        }
+                        Err(e) => warn!("Invalid pubkey in join queue {}: {}", expired.pubkey, e),
                     }
+                },
+            )
+            .await;
+
+            match swept {
+                Ok(0) => {}
+                Ok(count) => info!("Expired {} stale join requests", count),
+                Err(e) => error!("Failed to sweep join requests: {}", e),
+            }
+        }
+    }
+
+    /// Publish the replaceable service descriptor event signed by the service key
+    /// Failure only costs discoverability, so it is logged and not propagated
+    async fn publish_service_descriptor(&self) {
+        let descriptor =
+            ServiceDescriptor::from_config(&self.config, &self.service_keys.public_key());
+        let event = match descriptor
+            .to_event_builder()
+            .sign_with_keys(&self.service_keys)
+        {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Failed to sign service descriptor: {}", e);
+                return;
+            }
+        };
+
+        match self.client.send_event(&event).await {
+            Ok(_) => info!("📣 Published service descriptor {}", event.id),
+            Err(e) => warn!("Failed to publish service descriptor: {}", e),
+        }
+    }
+
+    /// Publish the NIP-89 handler and recommendation, unless the relay already has this handler
+    /// Like the descriptor, failure only costs discoverability
+    async fn publish_app_handler(&self) {
+        let service_pubkey = self.service_keys.public_key();
+        let handler = AppHandler::from_config(&self.config);
+        let published = self
+            .client
+            .fetch_events(
+                AppHandler::published_filter(&service_pubkey),
+                std::time::Duration::from_secs(5),
+            )
+            .await;
+        if let Ok(events) = published {
+            if events
+                .first()
+                .is_some_and(|event| handler.is_current(event))
+            {
+                debug!("NIP-89 app handler is up to date");
+                return;
+            }
+        }
+
+        for (name, builder) in [
+            ("app handler", handler.to_event_builder()),
+            (
+                "app recommendation",
+                handler.recommendation_builder(&service_pubkey),
+            ),
+        ] {
+            let event = match builder.sign_with_keys(&self.service_keys) {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("Failed to sign {}: {}", name, e);
+                    return;
                 }
+            };
+            match self.client.send_event(&event).await {
+                Ok(_) => info!("📣 Published {} {}", name, event.id),
+                Err(e) => warn!("Failed to publish {}: {}", name, e),
+            }
+        }
+    }
+
+    /// Handle a received gift wrap event
+    /// Everything logged while handling it carries the sending client once the rumor is open
+    #[tracing::instrument(
+        name = "gift_wrap",
+        skip_all,
+        fields(event = %gift_wrap.id, client = tracing::field::Empty)
+    )]
+    async fn handle_gift_wrap(&self, gift_wrap: Event) -> Result<(), Box<dyn std::error::Error>> {
+        let handle_start = std::time::Instant::now();
+        info!(
+            "⏱️ 🎁 handle_gift_wrap called at {:?} - processing event {}",
+            handle_start,
+            gift_wrap.id.to_string()
+        );
+
+        info!(
+            "📦 Received gift wrap from {} (event: {})",
+            gift_wrap.pubkey.to_bech32()?,
+            &gift_wrap.id.to_string()[0..8]
+        );
+
+        // Requests the sender declared void after a deadline (NIP-40) are never acted on
+        let now = self.clock.now();
+        if is_expired_at(&gift_wrap.tags, now) {
+            debug!("Dropping expired gift wrap {}", gift_wrap.id);
+            metrics::increment("peek_requests_expired_total", &[("layer", "gift_wrap")]);
+            return Ok(());
+        }
+
+        // Cheap namespace check before the expensive unwrap: if the sender advertised the
+        // inner kind with a k-tag on the wrapper, skip wraps meant for another deployment
+        if let Some(hinted_kind) = gift_wrap
+            .tags
+            .find(TagKind::SingleLetter(SingleLetterTag::lowercase(
+                Alphabet::K,
+            )))
+            .and_then(|tag| tag.content())
+            .and_then(|kind| kind.parse::<u16>().ok())
+        {
+            if hinted_kind != self.config.protocol.request_kind {
+                debug!(
+                    "Ignoring gift wrap {} for foreign kind {}",
+                    gift_wrap.id, hinted_kind
+                );
+                return Ok(());
+            }
+        }
+
+        // Unwrap the gift wrap using service keys (gift wrap is addressed to service pubkey)
+        // Note: client uses relay keys for auth, but gift wraps are encrypted to service keys
+        let unwrap_start = std::time::Instant::now();
+        info!("⏱️ Starting unwrap at {:?}", unwrap_start);
+        let unwrapped = match unwrap_gift_wrap(&self.service_keys, &gift_wrap) {
+            Ok(unwrapped) => unwrapped,
+            Err(UnwrapError::Undecryptable(layer)) => {
+                warn!(
+                    "🚫 Dropping gift wrap {}: no supported scheme decrypts the {}",
+                    gift_wrap.id, layer
+                );
+                metrics::increment("peek_gift_wraps_undecryptable_total", &[("layer", layer)]);
+                return Ok(());
+            }
+            Err(UnwrapError::Rejected(rejection)) => {
+                warn!(
+                    "🚫 Dropping gift wrap {}: rumor rejected ({})",
+                    gift_wrap.id,
+                    rejection.reason()
+                );
+                metrics::increment(
+                    "peek_rumors_rejected_total",
+                    &[("reason", rejection.reason())],
+                );
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let unwrap_duration = unwrap_start.elapsed();
+        info!("⏱️ Unwrap completed in {:?}ms", unwrap_duration.as_millis());
+        // Legacy traffic shows up here until the clients still sending it are gone
+        metrics::increment(
+            "peek_gift_wraps_unwrapped_total",
+            &[("scheme", unwrapped.scheme.as_str())],
+        );
+
+        // Never trust correlation (rumor id, tags) from a rumor that looks spliced or replayed
+        if let Err(rejection) = verify_rumor(
+            &unwrapped.raw_rumor,
+            &unwrapped.rumor,
+            self.config.protocol.request_kind(),
+            &self.service_keys.public_key(),
+            self.clock.now(),
+        ) {
+            if rejection == RumorRejection::UnexpectedKind {
+                debug!(
+                    "Ignoring non-validation rumor kind: {}",
+                    unwrapped.rumor.kind
+                );
+            } else {
+                warn!(
+                    "🚫 Dropping gift wrap {}: rumor rejected ({})",
+                    gift_wrap.id,
+                    rejection.reason()
+                );
+            }
+            metrics::increment(
+                "peek_rumors_rejected_total",
+                &[("reason", rejection.reason())],
+            );
+            return Ok(());
+        }
+        let rumor = unwrapped.rumor;
+
+        if is_expired_at(&rumor.tags, now) {
+            debug!(
+                "Dropping gift wrap {} with expired rumor {:?}",
+                gift_wrap.id, rumor.id
+            );
+            metrics::increment("peek_requests_expired_total", &[("layer", "rumor")]);
+            return Ok(());
+        }
+
+        let actual_sender = rumor.pubkey;
+
+        // Only for telling misbehaving app versions apart; never changes how the request is handled
+        let client = ClientFingerprint::from_tags(&rumor.tags);
+        tracing::Span::current().record("client", tracing::field::display(&client));
+
+        info!(
+            "🔓 Unwrapped gift wrap - sender: {} (kind: {})",
+            actual_sender.to_bech32()?,
+            rumor.kind
+        );
+
+        // Log the full decrypted content for debugging
+        info!("📝 Decrypted rumor content: {}", rumor.content);
+        info!("🏷️ Rumor tags: {:?}", rumor.tags);
+        info!("🆔 Rumor ID: {:?}", rumor.id);
+
+        // Try to parse as unified request first, fall back to legacy format
+        let parse_start = std::time::Instant::now();
+        info!("⏱️ Starting request parsing at {:?}", parse_start);
+        let response = if let Ok(request) = serde_json::from_str::<ServiceRequest>(&rumor.content) {
+            client.record_request("typed");
+            // A re-sent copy of a request still being processed shares its result
+            let key = request
+                .coalescing_scope()
+                .map(|(request_type, community_id)| {
+                    (actual_sender, request_type, community_id.to_string())
+                });
+            let work = self.process_request(request, actual_sender);
+            match key {
+                Some(key) => self.coalesced(key, work).await?,
+                None => work.await?,
             }
         } else if let Ok(legacy_request) =
             serde_json::from_str::<LocationValidationRequest>(&rumor.content)
         {
             // Handle legacy format (without type field)
+            client.record_request("legacy");
             info!(
                 "📍 Location validation request (legacy format) for community: {} from user: {}",
                 legacy_request.community_id,
                 actual_sender.to_bech32()?
             );
 
-            let result = self
-                .process_location_validation(
-                    legacy_request.community_id,
-                    legacy_request.location,
-                    actual_sender,
-                )
-                .await;
+            let key = (
+                actual_sender,
+                "location_validation",
+                legacy_request.community_id.clone(),
+            );
+            let work = async {
+                let result = self
+                    .process_location_validation(
+                        legacy_request.community_id.clone(),
+                        legacy_request.location,
+                        actual_sender,
+                        CreationOptions::default(),
+                        false,
+                    )
+                    .await;
 
-            ServiceResponse::LocationValidation {
-                success: result.success,
-                group_id: result.group_id,
-                relay_url: result.relay_url,
-                is_admin: result.is_admin,
-                is_member: result.is_member,
-                error: result.error,
-                error_code: result.error_code,
-            }
+                let attestation =
+                    self.attest(&legacy_request.community_id, &actual_sender, &result);
+                Ok(result.into_service_response(attestation))
+            };
+            self.coalesced(key, work).await?
         } else {
-            error!("Failed to parse request from rumor content");
+            client.record_request("unparseable");
+            error!(
+                "Failed to parse request from rumor content (client {})",
+                client
+            );
             return Ok(());
         };
 
@@ -435,22 +1828,124 @@ impl NostrValidationHandler {
                     info!("   Error: {}", err);
                 }
             }
-            ServiceResponse::Preview {
+            ServiceResponse::Preview(preview) => {
+                info!(
+                    "✅ Preview complete - success: {}, name: {:?}, members: {:?}, member_list_count: {:?}",
+                    preview.success, preview.name, preview.member_count, preview.members.as_ref().map(|m| m.len())
+                );
+                if let Some(ref err) = preview.error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::PreviewBatch {
                 success,
-                name,
-                member_count,
-                members,
+                results,
                 error,
                 ..
             } => {
                 info!(
-                    "✅ Preview complete - success: {}, name: {:?}, members: {:?}, member_list_count: {:?}",
-                    success, name, member_count, members.as_ref().map(|m| m.len())
+                    "✅ Preview batch complete - success: {}, found: {}/{}",
+                    success,
+                    results.iter().filter(|preview| preview.success).count(),
+                    results.len()
+                );
+                if let Some(ref err) = error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::AddAnchor {
+                success,
+                anchor_count,
+                error,
+                ..
+            } => {
+                info!(
+                    "✅ Add anchor complete - success: {}, anchors: {:?}",
+                    success, anchor_count
+                );
+                if let Some(ref err) = error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::UpdateMetadata { success, error, .. } => {
+                info!("✅ Update metadata complete - success: {}", success);
+                if let Some(ref err) = error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::ApproveJoin {
+                success,
+                status,
+                error,
+                ..
+            } => {
+                info!(
+                    "✅ Approve join complete - success: {}, status: {:?}",
+                    success, status
+                );
+                if let Some(ref err) = error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::CancelJoinRequest { success, error, .. } => {
+                info!("✅ Cancel join request complete - success: {}", success);
+                if let Some(ref err) = error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::ArchiveCommunity {
+                success,
+                archived,
+                error,
+                ..
+            } => {
+                info!(
+                    "✅ Archive complete - success: {}, archived: {:?}",
+                    success, archived
+                );
+                if let Some(ref err) = error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::ExportCommunity {
+                success,
+                export_url,
+                error,
+                ..
+            } => {
+                info!(
+                    "✅ Export complete - success: {}, uploaded: {}",
+                    success,
+                    export_url.is_some()
+                );
+                if let Some(ref err) = error {
+                    info!("   Error: {}", err);
+                }
+            }
+            ServiceResponse::BulkAddMembers {
+                success,
+                outcomes,
+                error,
+                ..
+            } => {
+                let added = outcomes
+                    .iter()
+                    .filter(|outcome| outcome.status == BulkAddStatus::Added)
+                    .count();
+                info!(
+                    "✅ Bulk add complete - success: {}, added: {}/{}",
+                    success,
+                    added,
+                    outcomes.len()
                 );
                 if let Some(ref err) = error {
                     info!("   Error: {}", err);
                 }
             }
+            ServiceResponse::JoinRequestUpdate { .. }
+            | ServiceResponse::MemberJoined { .. }
+            | ServiceResponse::InactivityWarning { .. }
+            | ServiceResponse::StickerReuseSuspected { .. } => {}
         }
 
         // Send gift-wrapped response back with reference to request ID
@@ -474,22 +1969,54 @@ impl NostrValidationHandler {
             }
         }
 
-        // Send to the seal's sender (ephemeral key that client can decrypt)
-        // Note: We identify the user by rumor.pubkey but respond to unwrapped.sender
-        // This allows bunker identities to decrypt responses with their ephemeral key
+        // Send to the seal's signer, which unwrap_gift_wrap checked is the rumor's pubkey
         let response_recipient = unwrapped.sender;
         info!(
             "🔐 Attempting to send response to recipient: {} ({})",
             response_recipient.to_bech32()?,
             response_recipient.to_hex()
         );
+
+        // Also deliver to the user's NIP-65 inbox relays for clients that only read there
+        let inbox_relays = self.inbox_relays.read_relays(&actual_sender).await;
+
+        // Clients that only surface NIP-17 chats can opt into a kind 14 response
+        let reply_kind = requested_reply_kind(&rumor.tags);
+        let (response_kind, response_content, response_tags) = response_rumor(
+            &response,
+            response_json,
+            &rumor_id,
+            &response_recipient,
+            reply_kind,
+            self.config.protocol.response_kind(),
+        );
         info!(
-            "   User identified as: {} (from rumor.pubkey)",
-            actual_sender.to_bech32()?
+            "📝 Response kind {} content length: {} chars",
+            response_kind,
+            response_content.len()
+        );
+        let response_builder =
+            EventBuilder::new(response_kind, response_content.clone()).tags(response_tags.clone());
+
+        // Honor the client's requested expiration, within our own limit
+        let requested_expiration = [expiration_of(&gift_wrap.tags), expiration_of(&rumor.tags)]
+            .into_iter()
+            .flatten()
+            .min();
+        let expiration = response_expiration(
+            requested_expiration,
+            self.clock.now(),
+            self.config.response_expiration_max_secs,
         );
 
         match self
-            .send_service_response(response_recipient, response_json, &rumor_id)
+            .send_service_response(
+                response_recipient,
+                response_builder,
+                &rumor_id,
+                &inbox_relays,
+                expiration,
+            )
             .await
         {
             Ok(_) => {
@@ -509,152 +2036,821 @@ impl NostrValidationHandler {
                 error!("❌ Failed to send gift-wrapped response: {}", e);
                 error!("   Recipient pubkey hex: {}", actual_sender.to_hex());
                 error!("   Recipient pubkey npub: {}", actual_sender.to_bech32()?);
-                return Err(format!("Failed to send response: {}", e).into());
+                // Side effects such as a join have already happened; only delivery is retried
+                self.response_retry.enqueue(QueuedResponse::new(
+                    response_recipient,
+                    response_kind,
+                    response_content,
+                    response_tags,
+                    rumor_id.clone(),
+                    inbox_relays,
+                    expiration,
+                ));
             }
         }
 
         Ok(())
     }
 
-    /// Process a location validation request
-    async fn process_location_validation(
+    /// Dispatch a typed request, recording admin-level ones in the audit trail
+    async fn process_request(
         &self,
-        community_id: String,
-        location: LocationData,
-        sender_pubkey: PublicKey,
-    ) -> LocationValidationResponse {
-        let process_start = std::time::Instant::now();
-        info!(
-            "⏱️ process_location_validation started at {:?}",
-            process_start
-        );
-        // Parse community ID
-        let community_uuid = match Uuid::parse_str(&community_id) {
-            Ok(id) => id,
-            Err(e) => {
-                return LocationValidationResponse {
-                    response_type: Some("location_validation_response".to_string()),
-                    success: false,
-                    group_id: None,
-                    relay_url: None,
-                    is_admin: None,
-                    is_member: None,
-                    error: Some(format!("Invalid community ID: {}", e)),
-                    error_code: Some("INVALID_ID".to_string()),
-                };
-            }
-        };
+        request: ServiceRequest,
+        actual_sender: PublicKey,
+    ) -> Result<ServiceResponse, Box<dyn std::error::Error>> {
+        let audited = request.audit_action(&actual_sender);
+        let response = self.dispatch_request(request, actual_sender).await?;
+        if let Some(action) = audited {
+            let (outcome, error_code) = response.audit_outcome();
+            self.audit_trail.record(action, outcome, error_code);
+        }
+        Ok(response)
+    }
 
-        // Extract location
-        let user_location = LocationPoint {
-            latitude: location.latitude,
-            longitude: location.longitude,
-        };
+    /// Dispatch a typed request to its processor
+    async fn dispatch_request(
+        &self,
+        request: ServiceRequest,
+        actual_sender: PublicKey,
+    ) -> Result<ServiceResponse, Box<dyn std::error::Error>> {
+        // The relay is known to be down: answer now rather than let every read time out
+        if let Some(retry_after_secs) = self.groups.relay_circuit().retry_after() {
+            warn!(
+                "🔌 Refusing request from {}: relay circuit open for another {}s",
+                actual_sender.to_bech32()?,
+                retry_after_secs
+            );
+            metrics::increment(
+                "peek_requests_refused_total",
+                &[("reason", "relay_circuit_open")],
+            );
+            return Ok(request.failure_response(
+                "The relay is unreachable, please retry later".to_string(),
+                ValidationErrorCode::ServiceUnavailable { retry_after_secs },
+            ));
+        }
 
-        // Get or create community
-        let community_start = std::time::Instant::now();
-        info!("⏱️ Getting/creating community at {:?}", community_start);
-        let (community, is_new) = match self
-            .community_service
-            .get_or_create(
-                community_uuid,
-                community_uuid.to_string(),
-                user_location.clone(),
-                sender_pubkey.to_hex(),
-            )
-            .await
-        {
-            Ok(result) => {
-                let community_duration = community_start.elapsed();
+        let response = match request {
+            ServiceRequest::LocationValidation {
+                community_id,
+                location,
+                active_until,
+                force,
+                remote_venue,
+                max_members,
+                age_restricted,
+                transfer,
+            } => {
                 info!(
-                    "⏱️ Community get/create took {:?}ms, is_new: {}",
-                    community_duration.as_millis(),
-                    result.1
+                    "📍 Location validation request for community: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+                debug!(
+                    "   Location: ({:.6}, {:.6}) accuracy: {:?}m",
+                    location.latitude, location.longitude, location.accuracy
+                );
+
+                let process_start = std::time::Instant::now();
+                info!(
+                    "⏱️ Starting location validation processing at {:?}",
+                    process_start
+                );
+                let result = self
+                    .process_location_validation(
+                        community_id.clone(),
+                        location,
+                        actual_sender,
+                        CreationOptions {
+                            active_until: active_until.map(Timestamp::from),
+                            force,
+                            remote_venue,
+                            max_members,
+                            age_restricted,
+                        },
+                        transfer,
+                    )
+                    .await;
+                let process_duration = process_start.elapsed();
+                info!(
+                    "⏱️ Location validation completed in {:?}ms",
+                    process_duration.as_millis()
+                );
+                metrics::observe(
+                    "peek_request_duration_seconds",
+                    &[("request_type", "location_validation")],
+                    process_duration.as_secs_f64(),
+                );
+
+                let attestation = self.attest(&community_id, &actual_sender, &result);
+                result.into_service_response(attestation)
+            }
+            ServiceRequest::PreviewRequest { community_id } => {
+                info!(
+                    "🔍 Community preview request for: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                let process_start = std::time::Instant::now();
+                let preview = self.process_preview(community_id, actual_sender).await;
+                metrics::observe(
+                    "peek_request_duration_seconds",
+                    &[("request_type", "preview_request")],
+                    process_start.elapsed().as_secs_f64(),
+                );
+
+                ServiceResponse::Preview(preview)
+            }
+            ServiceRequest::PreviewBatch { community_ids } => {
+                info!(
+                    "🔍 Community preview batch of {} from user: {}",
+                    community_ids.len(),
+                    actual_sender.to_bech32()?
+                );
+
+                let process_start = std::time::Instant::now();
+                let response = self
+                    .process_preview_batch(community_ids, actual_sender)
+                    .await;
+                metrics::observe(
+                    "peek_request_duration_seconds",
+                    &[("request_type", "preview_batch")],
+                    process_start.elapsed().as_secs_f64(),
+                );
+
+                response
+            }
+            ServiceRequest::AddAnchor {
+                community_id,
+                location,
+            } => {
+                info!(
+                    "📌 Add anchor request for community: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                self.process_add_anchor(community_id, location, actual_sender)
+                    .await
+            }
+            ServiceRequest::UpdateMetadata {
+                community_id,
+                join_mode,
+                max_members,
+                name,
+                about,
+                welcome,
+                slug,
+                join_notifications,
+                age_restricted,
+                inactive_prune_days,
+                quiet_hours,
+            } => {
+                info!(
+                    "🛠️ Update metadata request for community: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                let text = MetadataText {
+                    name,
+                    about,
+                    welcome,
+                };
+                self.process_update_metadata(
+                    community_id,
+                    join_mode,
+                    max_members,
+                    text,
+                    slug,
+                    join_notifications,
+                    age_restricted,
+                    inactive_prune_days,
+                    quiet_hours,
+                    actual_sender,
+                )
+                .await
+            }
+            ServiceRequest::ApproveJoin {
+                community_id,
+                pubkey,
+                approve,
+            } => {
+                info!(
+                    "🙋 Join {} for {} in community: {} from user: {}",
+                    if approve { "approval" } else { "rejection" },
+                    pubkey,
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                self.process_approve_join(community_id, pubkey, approve, actual_sender)
+                    .await
+            }
+            ServiceRequest::CancelJoinRequest { community_id } => {
+                info!(
+                    "🙅 Cancel join request for community: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                self.process_cancel_join_request(community_id, actual_sender)
+                    .await
+            }
+            ServiceRequest::ArchiveCommunity { community_id } => {
+                info!(
+                    "🗄️ Archive request for community: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                self.process_set_archived(community_id, true, actual_sender)
+                    .await
+            }
+            ServiceRequest::UnarchiveCommunity { community_id } => {
+                info!(
+                    "🗄️ Unarchive request for community: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                self.process_set_archived(community_id, false, actual_sender)
+                    .await
+            }
+            ServiceRequest::ExportCommunity { community_id } => {
+                info!(
+                    "📦 Export request for community: {} from user: {}",
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                self.process_export_community(community_id, actual_sender)
+                    .await
+            }
+            ServiceRequest::BulkAddMembers {
+                community_id,
+                pubkeys,
+            } => {
+                info!(
+                    "👥 Bulk add of {} pubkeys to community: {} from user: {}",
+                    pubkeys.len(),
+                    community_id,
+                    actual_sender.to_bech32()?
+                );
+
+                self.process_bulk_add_members(community_id, pubkeys, actual_sender)
+                    .await
+            }
+        };
+        Ok(response)
+    }
+
+    /// Run `work` unless the same sender already has this request in flight, then share its result
+    /// Each copy is still answered under its own correlation id by the caller
+    async fn coalesced(
+        &self,
+        key: InFlightKey,
+        work: impl std::future::Future<Output = Result<ServiceResponse, Box<dyn std::error::Error>>>,
+    ) -> Result<ServiceResponse, Box<dyn std::error::Error>> {
+        let request_type = key.1;
+        let (response, outcome) = self.in_flight.run(key, work).await;
+        if outcome == Outcome::Joined {
+            info!(
+                "🔁 Coalesced duplicate {} request onto the one in flight",
+                request_type
+            );
+            metrics::increment(
+                "peek_requests_coalesced_total",
+                &[("request_type", request_type)],
+            );
+        }
+        response
+    }
+
+    /// Deadline for the relay reads of a request starting now
+    fn request_deadline(&self) -> Deadline {
+        Deadline::after(self.clock.clone(), self.config.request_deadline_secs)
+    }
+
+    /// Country to tag a new community at `location` with, or why it may not be created there
+    #[cfg(feature = "country-lookup")]
+    fn creation_country(
+        &self,
+        location: &Coordinates,
+    ) -> Result<Option<String>, crate::libraries::country::RegionUnsupported> {
+        match &self.country_gate {
+            Some(gate) => gate.check(location.latitude(), location.longitude()),
+            None => Ok(None),
+        }
+    }
+
+    #[cfg(not(feature = "country-lookup"))]
+    fn creation_country(
+        &self,
+        _location: &Coordinates,
+    ) -> Result<Option<String>, std::convert::Infallible> {
+        Ok(None)
+    }
+
+    /// Response for a location validation whose relay reads overran the request deadline
+    fn deadline_exceeded(stage: &'static str) -> LocationValidationResponse {
+        warn!(
+            "⏰ Location validation overran the request deadline during {}",
+            stage
+        );
+        metrics::increment("peek_request_deadline_exceeded_total", &[("stage", stage)]);
+        LocationValidationResponse::failure(
+            "Relay did not answer in time, please retry",
+            ValidationErrorCode::RetryLater,
+        )
+    }
+
+    /// Response for a community that could not be looked up or created
+    fn community_failure(error: CommunityError, now: Timestamp) -> LocationValidationResponse {
+        match error {
+            // Soft failure: offer the existing community instead of fragmenting the spot
+            CommunityError::NearbyExists(nearby) => LocationValidationResponse {
+                existing_community: Some(ExistingCommunity {
+                    community_id: nearby.community_id.map(|id| id.to_string()),
+                    group_id: nearby.group_id,
+                    preview: PreviewResult::from_metadata(nearby.metadata, None, now),
+                }),
+                ..LocationValidationResponse::failure(
+                    "A community already exists at this location",
+                    ValidationErrorCode::NearbyCommunityExists,
+                )
+            },
+            CommunityError::Timeout { stage } => Self::deadline_exceeded(stage),
+            CommunityError::Busy(_) => LocationValidationResponse::failure(
+                "Too many communities are being created right now, please retry",
+                ValidationErrorCode::RetryLater,
+            ),
+            // Relay answered before auth completed - ask the client to retry
+            // rather than risk routing an existing community down the creation path
+            CommunityError::Relay(RelayError::QueryInconclusive(_)) => {
+                LocationValidationResponse::failure(
+                    "Community lookup was inconclusive, please retry",
+                    ValidationErrorCode::RetryLater,
+                )
+            }
+            CommunityError::Relay(RelayError::CircuitOpen { retry_after_secs }) => {
+                LocationValidationResponse::failure(
+                    "The relay is unreachable, please retry later",
+                    ValidationErrorCode::ServiceUnavailable { retry_after_secs },
+                )
+            }
+            CommunityError::Relay(e @ RelayError::RelayKeyUnauthorized(_)) => {
+                LocationValidationResponse::failure(
+                    format!("Community could not be created: {}", e),
+                    ValidationErrorCode::ServiceMisconfigured,
+                )
+            }
+            CommunityError::Relay(e) => LocationValidationResponse::failure(
+                format!("Failed to lookup group: {}", e),
+                ValidationErrorCode::GroupLookupFailed,
+            ),
+            CommunityError::NotFound(_) => LocationValidationResponse::failure(
+                "Group not found after creation",
+                ValidationErrorCode::GroupNotFound,
+            ),
+            e @ (CommunityError::Corrupted(_)
+            | CommunityError::CreationFailed { .. }
+            | CommunityError::Geohash(_)) => LocationValidationResponse::failure(
+                format!("Failed to get/create community: {}", e),
+                ValidationErrorCode::CommunityError,
+            ),
+        }
+    }
+
+    /// Process a location validation request
+    /// In read-only mode every check still runs, and the response is flagged as a dry run
+    async fn process_location_validation(
+        &self,
+        community_id: String,
+        location: LocationData,
+        sender_pubkey: PublicKey,
+        creation: CreationOptions,
+        transfer: bool,
+    ) -> LocationValidationResponse {
+        let mut response = self
+            .validate_location(community_id, location, sender_pubkey, creation, transfer)
+            .await;
+        if self.writer.mode() == ExecutionMode::DryRun {
+            response.dry_run = Some(true);
+        }
+        response
+    }
+
+    async fn validate_location(
+        &self,
+        community_id: String,
+        location: LocationData,
+        sender_pubkey: PublicKey,
+        creation: CreationOptions,
+        transfer: bool,
+    ) -> LocationValidationResponse {
+        let process_start = std::time::Instant::now();
+        info!(
+            "⏱️ process_location_validation started at {:?}",
+            process_start
+        );
+        // Garbage coordinates must never reach community creation or membership changes
+        let fix = match location.check() {
+            Ok(fix) => fix,
+            Err(e) => {
+                return LocationValidationResponse::failure(
+                    e.to_string(),
+                    ValidationErrorCode::InvalidLocationData,
                 );
-                result
             }
+        };
+        let user_location = fix.coordinates;
+        // Parse community ID, or the slug standing in for it
+        let community_ref = match self.parse_community_ref(&community_id) {
+            Ok(community_ref) => community_ref,
             Err(e) => {
-                return LocationValidationResponse {
-                    response_type: Some("location_validation_response".to_string()),
-                    success: false,
-                    group_id: None,
-                    relay_url: None,
-                    is_admin: None,
-                    is_member: None,
-                    error: Some(format!("Failed to get/create community: {}", e)),
-                    error_code: Some("COMMUNITY_ERROR".to_string()),
+                return LocationValidationResponse::failure(
+                    format!("Invalid community ID: {}", e),
+                    ValidationErrorCode::InvalidId,
+                );
+            }
+        };
+
+        // Relay reads share one deadline; an overrun asks the client to retry instead of hanging
+        let deadline = self.request_deadline();
+
+        // A slug only names an existing community, so an unknown one is never created
+        let community_uuid = match tokio::time::timeout(
+            deadline.remaining(),
+            self.resolve_community_ref(community_ref),
+        )
+        .await
+        {
+            Ok(Ok(Some(id))) => id,
+            Ok(Ok(None)) => {
+                return LocationValidationResponse::failure(
+                    "Community not found",
+                    ValidationErrorCode::GroupNotFound,
+                );
+            }
+            Ok(Err(e)) => {
+                return LocationValidationResponse::failure(
+                    format!("Failed to lookup group: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                );
+            }
+            Err(_) => return Self::deadline_exceeded("slug lookup"),
+        };
+
+        // Requesters probing for the anchor are held off before their location is looked at
+        let probe_status =
+            self.location_probes
+                .status(&sender_pubkey, &community_uuid, self.clock.now_unix());
+        if let ProbeStatus::CoolingDown { retry_after_secs } = probe_status {
+            metrics::increment(
+                "peek_location_probing_responses_total",
+                &[("mode", "cooling_down")],
+            );
+            return LocationValidationResponse::failure(
+                format!(
+                    "Too many attempts at this community, please try again in {}s",
+                    retry_after_secs
+                ),
+                ValidationErrorCode::RetryLater,
+            );
+        }
+
+        // Look up the community, creating it if nobody has joined yet
+        let community_start = std::time::Instant::now();
+        info!("⏱️ Getting/creating community at {:?}", community_start);
+        let lookup = match tokio::time::timeout(
+            deadline.remaining(),
+            self.community_service.lookup(&community_uuid, &deadline),
+        )
+        .await
+        {
+            Ok(Ok(lookup)) => lookup,
+            Ok(Err(e)) => return Self::community_failure(e, self.clock.now()),
+            Err(_) => return Self::deadline_exceeded("community lookup"),
+        };
+
+        // A new community's group id comes from its creation; a dry run never stores one
+        let (community, created_group) = match lookup {
+            CommunityLookup::Existing(community) => (community, None),
+            CommunityLookup::Absent => {
+                // The first member's fix becomes the anchor, so it has to be a real one
+                if let Err(code) = FixUse::Create.check_accuracy(fix.accuracy) {
+                    return LocationValidationResponse::failure(
+                        "Location accuracy is unknown; a precise fix is needed to create a community",
+                        code,
+                    );
+                }
+                // A community pinned at sea or at 0,0 would sit on the discovery map forever
+                if !creation.remote_venue {
+                    if let Err(e) = check_plausible_location(
+                        user_location.latitude(),
+                        user_location.longitude(),
+                        self.config.landmask_check,
+                    ) {
+                        metrics::increment(
+                            "peek_implausible_locations_total",
+                            &[("reason", e.as_str())],
+                        );
+                        return LocationValidationResponse::failure(
+                            e.to_string(),
+                            ValidationErrorCode::ImplausibleLocation,
+                        );
+                    }
+                }
+                // Some operators may not host communities in certain jurisdictions; existing
+                // communities there stay previewable and joinable
+                let country = match self.creation_country(&user_location) {
+                    Ok(country) => country,
+                    Err(e) => {
+                        metrics::increment("peek_region_refusals_total", &[]);
+                        return LocationValidationResponse::failure(
+                            e.to_string(),
+                            ValidationErrorCode::RegionUnsupported,
+                        );
+                    }
+                };
+                // Whoever starts a community of an exclusive cluster becomes its first member
+                let moving = match self
+                    .cluster_check(&community_uuid, &sender_pubkey, transfer, &deadline)
+                    .await
+                {
+                    Ok(moving) => moving,
+                    Err(response) => return response,
                 };
+                // Creation is never cut short once started: abandoning it halfway would
+                // leave a group without its admin, so the deadline only stops it starting
+                let creation = self.community_service.create(
+                    community_uuid,
+                    user_location,
+                    sender_pubkey.to_hex(),
+                    creation.active_until,
+                    creation.max_members,
+                    creation.age_restricted,
+                    country,
+                    creation.force,
+                    &deadline,
+                );
+                match self
+                    .after_transfer(moving.as_ref(), &sender_pubkey, creation)
+                    .await
+                {
+                    Ok(Ok(created)) => (created.metadata, Some(created.group_id)),
+                    Ok(Err(e)) => return Self::community_failure(e, self.clock.now()),
+                    Err(response) => return response,
+                }
             }
         };
+        let is_new = created_group.is_some();
+        info!(
+            "⏱️ Community get/create took {:?}ms, is_new: {}",
+            community_start.elapsed().as_millis(),
+            is_new
+        );
 
         // If not a new community, validate location using geohash
         if !is_new {
-            // Validate user is within the geohash area (includes neighbors)
-            if !validate_geohash_location(&user_location, &community.geohash) {
-                return LocationValidationResponse {
-                    response_type: Some("location_validation_response".to_string()),
-                    success: false,
-                    group_id: None,
-                    relay_url: None,
-                    is_admin: None,
-                    is_member: None,
-                    error: Some("Location outside community area".to_string()),
-                    error_code: Some("LOCATION_INVALID".to_string()),
-                };
+            // Nobody tens of kilometers from every anchor is at the sticker, so skip the
+            // precise checks; the counts point the operator at misplaced or copied stickers
+            let rejection = if let Some(meters) =
+                self.far_scans
+                    .check(community_uuid, &user_location, &community.anchors)
+            {
+                metrics::increment("peek_far_scans_total", &[]);
+                Some((DistanceBucket::VeryFar, Some(meters)))
+            } else if !validate_any_anchor(&user_location, &community.anchors) {
+                // Validate user is within the geohash area of any anchor (includes neighbors)
+                let nearest = nearest_anchor(&user_location, &community.anchors);
+                let nearest_meters = nearest.as_ref().map(|(_, meters)| *meters);
+                let distance_bucket = DistanceBucket::from_meters(nearest_meters);
+
+                // Operator-facing only: the rejected user never learns which side of the anchor they were on
+                let approach = nearest
+                    .map(|(center, _)| {
+                        CompassBucket::from_degrees(bearing_degrees(&center, &user_location))
+                            .as_str()
+                    })
+                    .unwrap_or("unknown");
+                metrics::increment(
+                    "peek_location_rejections_total",
+                    &[
+                        ("distance_bucket", distance_bucket.as_str()),
+                        ("bearing", approach),
+                    ],
+                );
+                Some((distance_bucket, nearest_meters))
+            } else {
+                None
+            };
+
+            if let Some((distance_bucket, nearest_meters)) = rejection {
+                self.location_probes.record_rejection(
+                    &sender_pubkey,
+                    &community_uuid,
+                    self.clock.now_unix(),
+                );
+                if let Some(flag) = self.sticker_reuse.record_failure(
+                    community_uuid,
+                    &sender_pubkey.to_hex(),
+                    &user_location,
+                    nearest_meters,
+                ) {
+                    metrics::increment("peek_sticker_reuse_flags_total", &[]);
+                    warn!(
+                        "🚩 Possible sticker reuse for community {}: {} failed joins around {}, {}km from its anchors",
+                        community_uuid, flag.failures, flag.cluster, flag.distance_km
+                    );
+                }
+                if probe_status == ProbeStatus::Degraded {
+                    metrics::increment(
+                        "peek_location_probing_responses_total",
+                        &[("mode", "degraded")],
+                    );
+                    tokio::time::sleep(self.location_probes.jitter()).await;
+                }
+                return LocationValidationResponse::failure(
+                    "Location outside community area",
+                    ValidationErrorCode::LocationInvalid {
+                        distance_bucket: reported_distance(distance_bucket, probe_status),
+                    },
+                );
             }
+            self.location_probes
+                .record_accepted(&sender_pubkey, &community_uuid);
 
             // Note: We no longer check GPS accuracy server-side since it's self-reported
             // and can be spoofed via Nostr messages. The geohash matching provides
-            // the actual security.
+            // the actual security, so an unknown accuracy is only counted.
+            let _ = FixUse::Join.check_accuracy(fix.accuracy);
         }
 
-        // Get the group ID by looking up the UUID
-        let group_id = match self
-            .relay_service
-            .read()
-            .await
-            .find_group_by_uuid(&community_uuid)
-            .await
+        // Get the group ID by looking up the UUID (cached by the lookup above)
+        let group_id = match created_group {
+            Some(group_id) => group_id,
+            None => {
+                let group_lookup = self.community_service.group_id(&community_uuid, &deadline);
+                match tokio::time::timeout(deadline.remaining(), group_lookup).await {
+                    Ok(Ok(id)) => id,
+                    Ok(Err(e)) => return Self::community_failure(e, self.clock.now()),
+                    Err(_) => return Self::deadline_exceeded("group lookup"),
+                }
+            }
+        };
+
+        // A sticker inside an exclusion zone still gets its community, but someone should look
+        if is_new {
+            if let Some(alert) = OperatorAlert::anchor_in_zone(
+                &self.exclusion_zones,
+                community_uuid,
+                &group_id,
+                &user_location,
+            ) {
+                let webhook = self.operator_webhook.clone();
+                tokio::spawn(async move { webhook.notify(&alert).await });
+            }
+        }
+
+        // Time-boxed communities refuse new joins after their deadline,
+        // but existing members can still re-validate
+        // Membership comes from the member list read alongside the metadata
+        let already_member = community.has_member(&sender_pubkey.to_hex());
+        // Members of a private group, or of any group on an AUTH-only relay, read nothing until
+        // their client authenticates, which would otherwise look just like a failed join
+        let auth_scope = read_auth_scope(community.is_public, self.groups.relay_limits());
+        // Stickers are not signed yet, so a community whose sticker may have been copied takes
+        // nobody new on location alone until the operator clears the flag; members still pass
+        if !is_new
+            && !already_member
+            && self.config.sticker_reuse_require_signed
+            && self.sticker_reuse.is_flagged(&community_uuid)
         {
-            Ok(Some(id)) => id,
-            Ok(None) => {
-                return LocationValidationResponse {
-                    response_type: Some("location_validation_response".to_string()),
-                    success: false,
-                    group_id: None,
-                    relay_url: None,
-                    is_admin: None,
-                    is_member: None,
-                    error: Some("Group not found after creation".to_string()),
-                    error_code: Some("GROUP_NOT_FOUND".to_string()),
-                };
+            info!(
+                "🚩 Community {} is flagged for possible sticker reuse, refusing new member {}",
+                group_id,
+                sender_pubkey.to_hex()
+            );
+            return LocationValidationResponse::failure(
+                "Joining this community needs a signed sticker",
+                ValidationErrorCode::SignedStickerRequired,
+            );
+        }
+        // Archived communities stay readable for members but take nobody new
+        if !is_new && !already_member && community.archived {
+            info!(
+                "🗄️ Community {} is archived, refusing new member {}",
+                group_id,
+                sender_pubkey.to_hex()
+            );
+            return LocationValidationResponse::failure(
+                "Community has been archived",
+                ValidationErrorCode::CommunityArchived,
+            );
+        }
+        if !is_new && !community.accepts_new_members_at(self.clock.now()) {
+            if !already_member {
+                info!(
+                    "⏰ Community {} expired at {:?}, refusing new member {}",
+                    group_id,
+                    community.active_until,
+                    sender_pubkey.to_hex()
+                );
+                return LocationValidationResponse::failure(
+                    "Community is no longer accepting new members",
+                    ValidationErrorCode::CommunityClosed,
+                );
             }
-            Err(e) => {
-                return LocationValidationResponse {
-                    response_type: Some("location_validation_response".to_string()),
-                    success: false,
-                    group_id: None,
-                    relay_url: None,
-                    is_admin: None,
-                    is_member: None,
-                    error: Some(format!("Failed to lookup group: {}", e)),
-                    error_code: Some("GROUP_LOOKUP_FAILED".to_string()),
-                };
+
+            return LocationValidationResponse {
+                response_type: Some("location_validation_response".to_string()),
+                success: true,
+                group_id: Some(group_id),
+                relay_url: Some(
+                    community
+                        .relay_url_or(self.config.public_relay_url.as_str())
+                        .to_string(),
+                ),
+                is_admin: Some(false),
+                is_member: Some(true),
+                error: None,
+                error_code: None,
+                message_key: None,
+                params: None,
+                existing_community: None,
+                status: None,
+                welcome: None,
+                community_name: Some(community.name.clone()),
+                picture: community.picture.clone(),
+                age_restricted: Some(community.age_restricted),
+                auth_required: Some(auth_scope.is_some()),
+                auth_scope,
+                dry_run: None,
+            };
+        }
+
+        // Full communities take no new members; existing members still re-validate
+        if !is_new && !already_member && community.is_full() {
+            info!(
+                "🚪 Community {} is at its cap of {:?} members, refusing {}",
+                group_id,
+                community.max_members,
+                sender_pubkey.to_hex()
+            );
+            metrics::increment("peek_community_full_rejections_total", &[]);
+            return LocationValidationResponse::failure(
+                "Community is full",
+                ValidationErrorCode::CommunityFull,
+            );
+        }
+
+        // Members of another community of an exclusive cluster are refused, or moved over if
+        // they asked to transfer. Approval-mode communities take no transfers: the applicant
+        // would lose their old community while still waiting to be accepted
+        let moving = if !is_new && !already_member {
+            let transfer = transfer && community.join_mode != JoinMode::Approval;
+            match self
+                .cluster_check(&community_uuid, &sender_pubkey, transfer, &deadline)
+                .await
+            {
+                Ok(moving) => moving,
+                Err(response) => return response,
             }
+        } else {
+            None
         };
 
+        // Approval-mode communities queue new joiners for an admin instead of adding them
+        if !is_new && community.join_mode == JoinMode::Approval {
+            return self
+                .queue_join_request(
+                    &community,
+                    &community_id,
+                    &group_id,
+                    &sender_pubkey,
+                    already_member,
+                )
+                .await;
+        }
+
         // If not a new community (user is joining existing), add them as a member
         if !is_new {
             // For existing groups, just add the user
             let add_user_start = std::time::Instant::now();
             info!("⏱️ Adding user to existing group at {:?}", add_user_start);
-            match self
-                .relay_service
-                .write()
-                .await
-                .add_user_to_group(&group_id, &sender_pubkey.to_hex(), false)
+            let sender_hex = sender_pubkey.to_hex();
+            let add = self.writer.add_group_member(&group_id, &sender_hex, false);
+            let added = match self
+                .after_transfer(moving.as_ref(), &sender_pubkey, add)
                 .await
             {
+                Ok(added) => added,
+                Err(response) => return response,
+            };
+            match added {
                 Ok(_) => {
                     let add_duration = add_user_start.elapsed();
                     info!(
@@ -663,18 +2859,30 @@ impl NostrValidationHandler {
                         group_id,
                         add_duration.as_millis()
                     );
+                    // Re-validations are not news to the admins, and dry runs added nobody
+                    if !already_member && self.writer.mode() == ExecutionMode::Execute {
+                        self.join_batcher
+                            .record(&group_id, &community_id, &sender_pubkey.to_hex());
+                    }
+                }
+                Err(e @ RelayError::MembershipUnconfirmed(_)) => {
+                    warn!("⚠️ {}", e);
+                    return LocationValidationResponse::failure(
+                        "Your membership could not be confirmed yet, please try again",
+                        ValidationErrorCode::MembershipUnconfirmed,
+                    );
+                }
+                Err(e @ RelayError::RelayKeyUnauthorized(_)) => {
+                    return LocationValidationResponse::failure(
+                        format!("Failed to add user to group: {}", e),
+                        ValidationErrorCode::ServiceMisconfigured,
+                    );
                 }
                 Err(e) => {
-                    return LocationValidationResponse {
-                        response_type: Some("location_validation_response".to_string()),
-                        success: false,
-                        group_id: None,
-                        relay_url: None,
-                        is_admin: None,
-                        is_member: None,
-                        error: Some(format!("Failed to add user to group: {}", e)),
-                        error_code: Some("GROUP_ADD_FAILED".to_string()),
-                    };
+                    return LocationValidationResponse::failure(
+                        format!("Failed to add user to group: {}", e),
+                        ValidationErrorCode::GroupAddFailed,
+                    );
                 }
             }
         }
@@ -689,237 +2897,2977 @@ impl NostrValidationHandler {
             response_type: Some("location_validation_response".to_string()),
             success: true,
             group_id: Some(group_id),
-            relay_url: Some(self.config.public_relay_url.clone()),
+            relay_url: Some(
+                community
+                    .relay_url_or(self.config.public_relay_url.as_str())
+                    .to_string(),
+            ),
             is_admin: Some(is_new),
             is_member: Some(true),
             error: None,
             error_code: None,
+            message_key: None,
+            params: None,
+            existing_community: None,
+            status: None,
+            // Only the join that added the member carries it; creators and re-validations do not
+            welcome: first_join_welcome(&community, is_new || already_member),
+            community_name: Some(community.name.clone()),
+            picture: community.picture.clone(),
+            age_restricted: Some(community.age_restricted),
+            auth_required: Some(auth_scope.is_some()),
+            auth_scope,
+            dry_run: None,
         }
     }
 
-    /// Process a community preview request
-    async fn process_preview(
+    /// The community of the same exclusive cluster the requester has to leave to join this one
+    ///
+    /// Without `transfer` such a membership refuses the join with ALREADY_IN_CLUSTER, naming
+    /// the community; with it, the community is returned for the join to move them out of.
+    async fn cluster_check(
         &self,
-        community_id: String,
-    ) -> (
-        bool,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<Vec<String>>,
-        Option<u32>,
-        Option<Vec<String>>,
-        Option<bool>,
-        Option<bool>,
-        Option<u64>,
-        Option<String>,
-    ) {
-        info!("🔎 Processing preview for community: {}", community_id);
-
-        // Parse community ID
-        let community_uuid = match Uuid::parse_str(&community_id) {
-            Ok(id) => id,
-            Err(e) => {
-                error!("❌ Invalid community ID: {}", e);
-                return (
-                    false,                                        // success
-                    None,                                         // name
-                    None,                                         // picture
-                    None,                                         // about
-                    None,                                         // rules
-                    None,                                         // member_count
-                    None,                                         // members
-                    None,                                         // is_public
-                    None,                                         // is_open
-                    None,                                         // created_at
-                    Some(format!("Invalid community ID: {}", e)), // error
-                );
+        community_id: &Uuid,
+        sender_pubkey: &PublicKey,
+        transfer: bool,
+        deadline: &Deadline,
+    ) -> Result<Option<ClusterConflict>, LocationValidationResponse> {
+        let check = self
+            .clusters
+            .conflict(&self.groups, community_id, &sender_pubkey.to_hex());
+        let conflict = match tokio::time::timeout(deadline.remaining(), check).await {
+            Ok(Ok(conflict)) => conflict,
+            Ok(Err(e)) => {
+                return Err(LocationValidationResponse::failure(
+                    format!("Failed to check cluster membership: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                ))
             }
+            Err(_) => return Err(Self::deadline_exceeded("cluster check")),
         };
+        match conflict {
+            Some(conflict) if !transfer => {
+                info!(
+                    "🚧 {} is a member of {} in exclusive cluster {}, refusing them {}",
+                    sender_pubkey.to_hex(),
+                    conflict.group_id,
+                    conflict.cluster,
+                    community_id
+                );
+                metrics::increment("peek_cluster_rejections_total", &[]);
+                Err(LocationValidationResponse::failure(
+                    format!(
+                        "Already a member of {} in the same exclusive cluster",
+                        conflict.community_name
+                    ),
+                    ValidationErrorCode::AlreadyInCluster {
+                        community_id: conflict.community_id.to_string(),
+                    },
+                ))
+            }
+            conflict => Ok(conflict),
+        }
+    }
 
-        // Look up the group ID from UUID
-        let group_id = match self
-            .relay_service
-            .read()
-            .await
-            .find_group_by_uuid(&community_uuid)
+    /// Run `join`, first moving the requester out of `moving` when they asked to transfer
+    async fn after_transfer<T, E: std::fmt::Display>(
+        &self,
+        moving: Option<&ClusterConflict>,
+        sender_pubkey: &PublicKey,
+        join: impl std::future::Future<Output = Result<T, E>>,
+    ) -> Result<Result<T, E>, LocationValidationResponse> {
+        let Some(conflict) = moving else {
+            return Ok(join.await);
+        };
+        conflict
+            .transfer(&self.writer, sender_pubkey, join)
             .await
+            .map_err(|e| match e {
+                // Their role stays, so they stay, and the join is refused as without transfer
+                ClusterTransferError::Administrator(_) => {
+                    info!(
+                        "🚧 {} administers {} in exclusive cluster {}, refusing their transfer",
+                        sender_pubkey.to_hex(),
+                        conflict.group_id,
+                        conflict.cluster
+                    );
+                    metrics::increment("peek_cluster_rejections_total", &[]);
+                    LocationValidationResponse::failure(
+                        e.to_string(),
+                        ValidationErrorCode::AlreadyInCluster {
+                            community_id: conflict.community_id.to_string(),
+                        },
+                    )
+                }
+                ClusterTransferError::Relay(e) => {
+                    warn!(
+                        "⚠️ Could not move {} out of {}: {}",
+                        sender_pubkey.to_hex(),
+                        conflict.group_id,
+                        e
+                    );
+                    LocationValidationResponse::failure(
+                        format!("Failed to leave {}: {}", conflict.community_name, e),
+                        ValidationErrorCode::GroupAddFailed,
+                    )
+                }
+            })
+    }
+
+    /// Queue a location-validated user for admin approval, notifying admins on first request
+    /// Existing members re-validating are answered as members without queueing
+    async fn queue_join_request(
+        &self,
+        community: &CommunityMetadata,
+        community_id: &str,
+        group_id: &str,
+        sender_pubkey: &PublicKey,
+        already_member: bool,
+    ) -> LocationValidationResponse {
+        let pubkey_hex = sender_pubkey.to_hex();
+
+        let mut status = None;
+        let mut admins_to_notify = Vec::new();
+        if !already_member {
+            let queued = request_join(
+                &self.writer.lock_group(group_id).await,
+                group_id,
+                community_id,
+                &pubkey_hex,
+                self.clock.now_unix(),
+            )
+            .await;
+            match queued {
+                Ok(newly_queued) => {
+                    info!(
+                        "🙋 Join request from {} for group {} is pending approval",
+                        pubkey_hex, group_id
+                    );
+                    // Repeat validations keep the original request without re-notifying admins,
+                    // and a dry run queued nothing to notify them about
+                    if newly_queued && self.writer.mode() == ExecutionMode::Execute {
+                        metrics::increment("peek_join_requests_total", &[("status", "pending")]);
+                        admins_to_notify = self
+                            .groups
+                            .get_group_admins(group_id)
+                            .await
+                            .unwrap_or_default();
+                    }
+                    status = Some(JoinRequestStatus::Pending);
+                }
+                Err(e) => {
+                    return LocationValidationResponse::failure(
+                        format!("Failed to queue join request: {}", e),
+                        ValidationErrorCode::JoinRequestFailed,
+                    );
+                }
+            }
+        }
+
+        let update = ServiceResponse::join_request_update(
+            community_id.to_string(),
+            pubkey_hex,
+            JoinRequestStatus::Pending,
+        );
+        // Admins may need to act on a pending request; moderation alerts are never held, so
+        // there is no need to read the community's quiet hours
+        for admin in admins_to_notify {
+            self.push_or_hold(
+                None,
+                PushKind::Moderation,
+                admin,
+                update.clone(),
+                "join-request-update",
+            )
+            .await;
+        }
+
+        let auth_scope = read_auth_scope(community.is_public, self.groups.relay_limits());
+        LocationValidationResponse {
+            response_type: Some("location_validation_response".to_string()),
+            success: true,
+            group_id: Some(group_id.to_string()),
+            relay_url: Some(
+                community
+                    .relay_url_or(self.config.public_relay_url.as_str())
+                    .to_string(),
+            ),
+            is_admin: Some(false),
+            is_member: Some(already_member),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+            existing_community: None,
+            status,
+            welcome: None,
+            community_name: Some(community.name.clone()),
+            picture: community.picture.clone(),
+            age_restricted: Some(community.age_restricted),
+            // Pending applicants cannot read the group yet either way
+            auth_required: already_member.then_some(auth_scope.is_some()),
+            auth_scope: auth_scope.filter(|_| already_member),
+            dry_run: None,
+        }
+    }
+
+    /// Parse a community ID from a request under the configured UUID policy
+    fn parse_community_id(&self, community_id: &str) -> Result<Uuid, InvalidCommunityId> {
+        CommunityIdPolicy::new(self.config.allow_any_uuid).parse(community_id)
+    }
+
+    /// Parse a community ID that may also be a slug, for requests reachable from shared links
+    fn parse_community_ref(&self, community_id: &str) -> Result<CommunityRef, InvalidCommunityId> {
+        CommunityIdPolicy::new(self.config.allow_any_uuid).parse_ref(community_id)
+    }
+
+    /// The community UUID a request names; None if no community uses the slug
+    async fn resolve_community_ref(
+        &self,
+        community_ref: CommunityRef,
+    ) -> Result<Option<Uuid>, RelayError> {
+        match community_ref {
+            CommunityRef::Id(id) => Ok(Some(id)),
+            CommunityRef::Slug(slug) => Ok(self
+                .groups
+                .find_group_by_slug(&slug)
+                .await?
+                .map(|owner| owner.community_id)),
+        }
+    }
+
+    /// Process a community preview request
+    async fn process_preview(&self, community_id: String, sender: PublicKey) -> PreviewResult {
+        info!("🔎 Processing preview for community: {}", community_id);
+
+        // Parse community ID, or the slug standing in for it
+        let community_ref = match self.parse_community_ref(&community_id) {
+            Ok(community_ref) => community_ref,
+            Err(e) => {
+                error!("❌ Invalid community ID: {}", e);
+                return PreviewResult::invalid_id(format!("Invalid community ID: {}", e));
+            }
+        };
+
+        // Requesters that keep asking for communities that don't exist are probing the ID space
+        if self
+            .preview_misses
+            .is_limited(&sender, self.clock.now_unix())
+        {
+            warn!(
+                "🚫 Preview for {} refused: too many unknown communities from {}",
+                community_id, sender
+            );
+            metrics::increment("peek_preview_miss_limited_total", &[]);
+            return PreviewResult::retry_later(
+                "Too many lookups of unknown communities, please try again later",
+            );
+        }
+
+        // Relay reads share one deadline; an overrun asks the client to retry instead of hanging
+        match tokio::time::timeout(
+            self.request_deadline().remaining(),
+            self.fetch_preview(community_ref, sender),
+        )
+        .await
+        {
+            Ok(preview) => preview,
+            Err(_) => {
+                warn!(
+                    "⏰ Preview for {} overran the request deadline",
+                    community_id
+                );
+                metrics::increment(
+                    "peek_request_deadline_exceeded_total",
+                    &[("stage", "preview")],
+                );
+                PreviewResult::retry_later("Community preview timed out, please retry")
+            }
+        }
+    }
+
+    /// Process a batch of community previews, answered together in request order
+    async fn process_preview_batch(
+        &self,
+        community_ids: Vec<String>,
+        sender: PublicKey,
+    ) -> ServiceResponse {
+        let requested = community_ids.len();
+        match preview_batch(community_ids, |community_id| {
+            self.process_preview(community_id, sender)
+        })
+        .await
         {
+            Ok(results) => ServiceResponse::PreviewBatch {
+                success: true,
+                results,
+                error: None,
+                error_code: None,
+                message_key: None,
+                params: None,
+            },
+            Err(code) => {
+                warn!(
+                    "🚫 Preview batch of {} from {} refused: over the limit of {}",
+                    requested, sender, MAX_PREVIEW_BATCH
+                );
+                metrics::increment("peek_preview_batch_rejected_total", &[]);
+                ServiceResponse::PreviewBatch {
+                    success: false,
+                    results: Vec::new(),
+                    error: Some(format!(
+                        "At most {} communities can be previewed at once",
+                        MAX_PREVIEW_BATCH
+                    )),
+                    error_code: Some(code.code().to_string()),
+                    message_key: Some(code.message_key().to_string()),
+                    params: Some(code.params()),
+                }
+            }
+        }
+    }
+
+    /// Relay reads behind a preview: group lookup, then metadata and members concurrently
+    async fn fetch_preview(&self, community_ref: CommunityRef, sender: PublicKey) -> PreviewResult {
+        let community_uuid = match self.resolve_community_ref(community_ref).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                self.preview_misses
+                    .record_miss(&sender, self.clock.now_unix());
+                return PreviewResult::failure("Community not found");
+            }
+            Err(e) => {
+                error!("❌ Failed to lookup community slug: {}", e);
+                return PreviewResult::failure(format!("Failed to lookup community: {}", e));
+            }
+        };
+
+        // Look up the group ID from UUID
+        let group_id = match self.groups.find_group_by_uuid(&community_uuid).await {
             Ok(Some(id)) => id,
             Ok(None) => {
                 error!("❌ Group not found for UUID: {}", community_uuid);
-                return (
-                    false,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    Some("Community not found".to_string()),
-                );
+                self.preview_misses
+                    .record_miss(&sender, self.clock.now_unix());
+                return PreviewResult::failure("Community not found");
             }
             Err(e) => {
                 error!(
                     "❌ Failed to lookup group for UUID {}: {}",
                     community_uuid, e
                 );
-                return (
-                    false,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    Some(format!("Failed to lookup community: {}", e)),
-                );
+                return PreviewResult::failure(format!("Failed to lookup community: {}", e));
             }
         };
 
         info!("📋 Fetching metadata for group: {}", group_id);
 
-        // Try to fetch NIP-29 group metadata from relay
-        match self
-            .relay_service
-            .read()
-            .await
-            .get_group_metadata(&group_id)
-            .await
-        {
-            Ok(metadata) => {
+        // Try to fetch NIP-29 group metadata and members from relay
+        match self.groups.get_group_snapshot(&group_id).await {
+            Ok(snapshot) => {
                 info!(
                     "✅ Found community metadata: name={}, members={}",
-                    metadata.name, metadata.member_count
+                    snapshot.metadata.name, snapshot.metadata.member_count
                 );
 
-                // Fetch member list (limit to first 20 for performance)
-                let members = self
-                    .relay_service
-                    .read()
-                    .await
-                    .get_group_members(&group_id)
-                    .await
-                    .ok()
-                    .map(|m| m.into_iter().take(20).collect::<Vec<_>>());
-
-                (
-                    true,
-                    Some(metadata.name),
-                    metadata.picture,
-                    metadata.about,
-                    metadata.rules,
-                    Some(metadata.member_count),
-                    members,
-                    Some(metadata.is_public),
-                    Some(metadata.is_open),
-                    Some(metadata.created_at.as_u64()),
-                    None,
-                )
+                // Membership comes from the member list already read; only members need the
+                // admin list, which is cached, so strangers never cost an extra read
+                let sender_hex = sender.to_hex();
+                let is_member = snapshot.members.contains(&sender_hex);
+                // Archived communities stay visible to their members only
+                if snapshot.metadata.archived && !is_member {
+                    info!("🗄️ Community {} is archived, hiding preview", group_id);
+                    return PreviewResult::archived();
+                }
+                let admins = if is_member {
+                    match self.groups.cached_group_admins(&group_id).await {
+                        Ok(admins) => Some(admins),
+                        Err(e) => {
+                            warn!(
+                                "⚠️ Failed to read admins of {} for preview: {}",
+                                group_id, e
+                            );
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                let role = requester_role(&sender, is_member, admins.as_deref());
+
+                // Member list is limited to the first 20 for performance
+                let members = snapshot
+                    .members
+                    .pubkeys
+                    .into_iter()
+                    .take(20)
+                    .collect::<Vec<_>>();
+
+                PreviewResult::from_metadata(snapshot.metadata, Some(members), self.clock.now())
+                    .for_requester(role)
             }
             Err(e) => {
                 error!("❌ Failed to fetch community metadata: {}", e);
-                (
-                    false,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    None,
-                    Some(format!("Failed to fetch community metadata: {}", e)),
-                )
+                PreviewResult::failure(format!("Failed to fetch community metadata: {}", e))
             }
         }
     }
 
-    /// Send a gift-wrapped response back to the requester
-    async fn send_service_response(
+    /// Process an admin request to add another anchor location to a community
+    async fn process_add_anchor(
         &self,
-        recipient: PublicKey,
-        response_json: String,
-        request_id: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        info!(
-            "🎁 Creating gift wrap for recipient: {} ({})",
-            recipient.to_bech32()?,
-            recipient.to_hex()
-        );
-        info!("📝 Response content length: {} chars", response_json.len());
-        info!("🔗 Request ID reference: {}", request_id);
+        community_id: String,
+        location: LocationData,
+        sender_pubkey: PublicKey,
+    ) -> ServiceResponse {
+        let failure = |error: String, code: ValidationErrorCode| ServiceResponse::AddAnchor {
+            success: false,
+            anchor_count: None,
+            error: Some(error),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
 
-        // Use the centralized gift wrap service
-        let tags = vec![Tag::custom(
-            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::E)),
-            vec![request_id.to_string()],
-        )];
+        let fix = match location.check() {
+            Ok(fix) => fix,
+            Err(e) => return failure(e.to_string(), ValidationErrorCode::InvalidLocationData),
+        };
+        let anchor_location = fix.coordinates;
 
-        let event_id = self
-            .gift_wrap_service
-            .create_and_send_gift_wrap(
-                &self.client,
-                &recipient,
-                response_json,
-                LOCATION_VALIDATION_RESPONSE_KIND,
-                tags,
-            )
-            .await?;
+        let community_uuid = match self.parse_community_id(&community_id) {
+            Ok(id) => id,
+            Err(e) => {
+                return failure(
+                    format!("Invalid community ID: {}", e),
+                    ValidationErrorCode::InvalidId,
+                )
+            }
+        };
 
-        info!(
-            "✅ Gift wrap sent successfully: {} to {}",
-            event_id,
-            recipient.to_bech32()?
-        );
+        // The admin must be standing at the new anchor with a good fix
+        if let Err(code) = FixUse::AddAnchor.check_accuracy(fix.accuracy) {
+            return failure(
+                format!(
+                    "Location accuracy must be within {}m to add an anchor",
+                    MAX_ANCHOR_ACCURACY_METERS
+                ),
+                code,
+            );
+        }
 
-        Ok(())
+        let group_id = match self.groups.find_group_by_uuid(&community_uuid).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                return failure(
+                    "Community not found".to_string(),
+                    ValidationErrorCode::GroupNotFound,
+                )
+            }
+            Err(e) => {
+                return failure(
+                    format!("Failed to lookup group: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                )
+            }
+        };
+
+        match self.groups.is_group_admin(&group_id, &sender_pubkey).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return failure(
+                    "Only community admins can add anchors".to_string(),
+                    ValidationErrorCode::NotAdmin,
+                )
+            }
+            Err(e) => {
+                return failure(
+                    format!("Failed to verify admin status: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                )
+            }
+        }
+
+        match self
+            .writer
+            .add_group_anchor(&group_id, anchor_location, self.config.max_anchors)
+            .await
+        {
+            Ok(anchor_count) => ServiceResponse::AddAnchor {
+                success: true,
+                anchor_count: Some(anchor_count),
+                error: None,
+                error_code: None,
+                message_key: None,
+                params: None,
+            },
+            Err(RelayError::AnchorLimitReached(max)) => failure(
+                format!("Communities may have at most {} anchors", max),
+                ValidationErrorCode::AnchorLimitReached { max_anchors: max },
+            ),
+            Err(e @ RelayError::RelayKeyUnauthorized(_)) => failure(
+                format!("Failed to add anchor: {}", e),
+                ValidationErrorCode::ServiceMisconfigured,
+            ),
+            Err(e) => failure(
+                format!("Failed to add anchor: {}", e),
+                ValidationErrorCode::AnchorAddFailed,
+            ),
+        }
     }
-}
 
-/// Validate location using geohash neighbor matching
-fn validate_geohash_location(user_location: &LocationPoint, community_geohash: &str) -> bool {
-    // Ensure the community geohash is level 8
-    if community_geohash.len() != 8 {
-        return false;
+    /// Process an admin request to change community settings
+    #[allow(clippy::too_many_arguments)]
+    async fn process_update_metadata(
+        &self,
+        community_id: String,
+        join_mode: Option<JoinMode>,
+        max_members: Option<u32>,
+        text: MetadataText,
+        slug: Option<String>,
+        join_notifications: Option<bool>,
+        age_restricted: Option<bool>,
+        inactive_prune_days: Option<u32>,
+        quiet_hours: Option<QuietHours>,
+        sender_pubkey: PublicKey,
+    ) -> ServiceResponse {
+        let failure = |error: String, code: ValidationErrorCode| ServiceResponse::UpdateMetadata {
+            success: false,
+            error: Some(error),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
+
+        let group_id = match self.admin_group(&community_id, &sender_pubkey).await {
+            Ok(group_id) => group_id,
+            Err((error, code)) => return failure(error, code),
+        };
+
+        if join_mode.is_some()
+            || max_members.is_some()
+            || !text.is_empty()
+            || slug.is_some()
+            || join_notifications.is_some()
+            || age_restricted.is_some()
+            || inactive_prune_days.is_some()
+            || quiet_hours.is_some()
+        {
+            match self
+                .writer
+                .update_group_metadata(
+                    &group_id,
+                    join_mode,
+                    max_members,
+                    &text,
+                    slug.as_deref(),
+                    join_notifications,
+                    age_restricted,
+                    inactive_prune_days,
+                    quiet_hours,
+                )
+                .await
+            {
+                Ok(()) => {}
+                Err(RelayError::InvalidMetadata(e)) => {
+                    return failure(
+                        e.to_string(),
+                        ValidationErrorCode::InvalidMetadata {
+                            fields: e.fields.join(", "),
+                        },
+                    );
+                }
+                Err(RelayError::SlugTaken(taken)) => {
+                    return failure(
+                        format!("Slug {} is already used by another community", taken),
+                        ValidationErrorCode::SlugTaken { slug: taken },
+                    );
+                }
+                Err(e) => {
+                    return failure(
+                        format!("Failed to update metadata: {}", e),
+                        ValidationErrorCode::MetadataUpdateFailed,
+                    );
+                }
+            }
+        }
+
+        ServiceResponse::UpdateMetadata {
+            success: true,
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
     }
 
-    // Encode user location to level 8
-    let user_geohash = match encode(
-        Coord {
-            x: user_location.longitude,
-            y: user_location.latitude,
-        },
-        8,
-    ) {
-        Ok(hash) => hash,
-        Err(_) => return false,
-    };
+    /// Process an admin request to archive or unarchive a community
+    async fn process_set_archived(
+        &self,
+        community_id: String,
+        archived: bool,
+        sender_pubkey: PublicKey,
+    ) -> ServiceResponse {
+        let failure =
+            |error: String, code: ValidationErrorCode| ServiceResponse::ArchiveCommunity {
+                success: false,
+                archived: None,
+                error: Some(error),
+                error_code: Some(code.code().to_string()),
+                message_key: Some(code.message_key().to_string()),
+                params: Some(code.params()),
+            };
+
+        let group_id = match self.admin_group(&community_id, &sender_pubkey).await {
+            Ok(group_id) => group_id,
+            Err((error, code)) => return failure(error, code),
+        };
+
+        // The discovery map drops or restores the community on its next reconciliation pass
+        if let Err(e) = self.writer.set_group_archived(&group_id, archived).await {
+            return failure(
+                format!("Failed to update archive state: {}", e),
+                ValidationErrorCode::MetadataUpdateFailed,
+            );
+        }
+        metrics::increment(
+            "peek_community_archive_changes_total",
+            &[("archived", if archived { "true" } else { "false" })],
+        );
 
-    // Check if user is in same cell
-    if user_geohash == community_geohash {
-        return true;
+        ServiceResponse::ArchiveCommunity {
+            success: true,
+            archived: Some(archived),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
     }
 
-    // Check all 8 neighboring cells
-    match neighbors(community_geohash) {
-        Ok(neighbor_set) => {
-            user_geohash == neighbor_set.n
-                || user_geohash == neighbor_set.ne
-                || user_geohash == neighbor_set.e
-                || user_geohash == neighbor_set.se
-                || user_geohash == neighbor_set.s
-                || user_geohash == neighbor_set.sw
-                || user_geohash == neighbor_set.w
-                || user_geohash == neighbor_set.nw
+    /// Process an admin request to export a community
+    async fn process_export_community(
+        &self,
+        community_id: String,
+        sender_pubkey: PublicKey,
+    ) -> ServiceResponse {
+        let failure = |error: String, code: ValidationErrorCode| ServiceResponse::ExportCommunity {
+            success: false,
+            export: None,
+            export_url: None,
+            error: Some(error),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
+
+        let community_uuid = match self.parse_community_id(&community_id) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                return failure(
+                    format!("Invalid community ID: {}", e),
+                    ValidationErrorCode::InvalidId,
+                )
+            }
+        };
+        let group_id = match self.groups.find_group_by_uuid(&community_uuid).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                return failure(
+                    "Community not found".to_string(),
+                    ValidationErrorCode::GroupNotFound,
+                )
+            }
+            Err(e) => {
+                return failure(
+                    format!("Failed to lookup group: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                )
+            }
+        };
+
+        let delivery = self
+            .exporter
+            .export(
+                &self.groups,
+                community_uuid,
+                &group_id,
+                &sender_pubkey,
+                self.clock.now_unix(),
+            )
+            .await;
+        let (export, export_url) = match delivery {
+            Ok(ExportDelivery::Inline(export)) => (Some(export), None),
+            Ok(ExportDelivery::Uploaded { url }) => (None, Some(url)),
+            Err(e) => {
+                let code = match e {
+                    ExportError::NotAdmin => ValidationErrorCode::NotAdmin,
+                    ExportError::LimitReached(max_exports) => {
+                        ValidationErrorCode::ExportLimitReached { max_exports }
+                    }
+                    ExportError::Relay(_) => ValidationErrorCode::GroupLookupFailed,
+                    ExportError::TooLargeToSend(_) | ExportError::Upload(_) => {
+                        ValidationErrorCode::ExportFailed
+                    }
+                };
+                warn!("Export of {} refused or failed: {}", community_id, e);
+                return failure(e.to_string(), code);
+            }
+        };
+
+        ServiceResponse::ExportCommunity {
+            success: true,
+            export,
+            export_url,
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
         }
-        Err(_) => false,
+    }
+
+    /// Process an admin request to add members by pubkey
+    async fn process_bulk_add_members(
+        &self,
+        community_id: String,
+        pubkeys: Vec<String>,
+        sender_pubkey: PublicKey,
+    ) -> ServiceResponse {
+        let failure = |error: String, code: ValidationErrorCode| ServiceResponse::BulkAddMembers {
+            success: false,
+            outcomes: Vec::new(),
+            error: Some(error),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
+
+        let community_uuid = match self.parse_community_id(&community_id) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                return failure(
+                    format!("Invalid community ID: {}", e),
+                    ValidationErrorCode::InvalidId,
+                )
+            }
+        };
+        // No group yet means nobody has scanned the sticker to create the community
+        let group_id = match self.groups.find_group_by_uuid(&community_uuid).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                return failure(
+                    "Community not found".to_string(),
+                    ValidationErrorCode::GroupNotFound,
+                )
+            }
+            Err(e) => {
+                return failure(
+                    format!("Failed to lookup group: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                )
+            }
+        };
+
+        let added = self
+            .bulk_adder
+            .add(
+                &self.writer,
+                community_uuid,
+                &group_id,
+                &sender_pubkey,
+                &pubkeys,
+            )
+            .await;
+        let outcomes = match added {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                let code = match e {
+                    BulkAddError::NotAdmin => ValidationErrorCode::NotAdmin,
+                    BulkAddError::TooMany(max_batch) => {
+                        ValidationErrorCode::BatchTooLarge { max_batch }
+                    }
+                    BulkAddError::NotScanned => ValidationErrorCode::CommunityNotScanned,
+                    BulkAddError::AllowanceExhausted(remaining) => {
+                        ValidationErrorCode::BulkAddLimitReached { remaining }
+                    }
+                    BulkAddError::Full(_) => ValidationErrorCode::CommunityFull,
+                    BulkAddError::Relay(_) => ValidationErrorCode::GroupLookupFailed,
+                };
+                warn!("Bulk add to {} refused or failed: {}", community_id, e);
+                return failure(e.to_string(), code);
+            }
+        };
+
+        ServiceResponse::BulkAddMembers {
+            success: true,
+            outcomes,
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    /// Process an admin decision on a pending join request, notifying the applicant
+    async fn process_approve_join(
+        &self,
+        community_id: String,
+        pubkey: String,
+        approve: bool,
+        sender_pubkey: PublicKey,
+    ) -> ServiceResponse {
+        let failure = |error: String, code: ValidationErrorCode| ServiceResponse::ApproveJoin {
+            success: false,
+            status: None,
+            error: Some(error),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
+
+        let applicant =
+            match PublicKey::from_bech32(&pubkey).or_else(|_| PublicKey::from_hex(&pubkey)) {
+                Ok(applicant) => applicant,
+                Err(e) => {
+                    return failure(
+                        format!("Invalid pubkey: {}", e),
+                        ValidationErrorCode::InvalidPubkey,
+                    )
+                }
+            };
+
+        let group_id = match self.admin_group(&community_id, &sender_pubkey).await {
+            Ok(group_id) => group_id,
+            Err((error, code)) => return failure(error, code),
+        };
+
+        // The queue edit and the member add happen under one hold of the group
+        let locked = self.writer.lock_group(&group_id).await;
+        let group = group_id.as_str();
+        let resolved = resolve_join(&locked, group, &applicant.to_hex(), approve, |member| {
+            let locked = &locked;
+            async move { locked.add_member(group, &member).await }
+        })
+        .await;
+        drop(locked);
+
+        let status = match resolved {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                return failure(
+                    "No pending join request for this user".to_string(),
+                    ValidationErrorCode::JoinRequestNotFound,
+                )
+            }
+            Err(e) => {
+                return failure(
+                    format!("Failed to resolve join request: {}", e),
+                    ValidationErrorCode::GroupAddFailed,
+                )
+            }
+        };
+
+        let status_label = if approve { "approved" } else { "rejected" };
+        info!(
+            "🙋 Join request from {} for group {} {} by {}",
+            applicant.to_hex(),
+            group_id,
+            status_label,
+            sender_pubkey.to_hex()
+        );
+        metrics::increment("peek_join_requests_total", &[("status", status_label)]);
+
+        let update = ServiceResponse::join_request_update(community_id, applicant.to_hex(), status);
+        self.push_update(applicant, &update, "join-request-update")
+            .await;
+
+        ServiceResponse::ApproveJoin {
+            success: true,
+            status: Some(status),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    /// Process an applicant withdrawing their own pending join request
+    async fn process_cancel_join_request(
+        &self,
+        community_id: String,
+        sender_pubkey: PublicKey,
+    ) -> ServiceResponse {
+        let failure =
+            |error: String, code: ValidationErrorCode| ServiceResponse::CancelJoinRequest {
+                success: false,
+                status: None,
+                error: Some(error),
+                error_code: Some(code.code().to_string()),
+                message_key: Some(code.message_key().to_string()),
+                params: Some(code.params()),
+            };
+
+        let community_uuid = match self.parse_community_id(&community_id) {
+            Ok(id) => id,
+            Err(e) => {
+                return failure(
+                    format!("Invalid community ID: {}", e),
+                    ValidationErrorCode::InvalidId,
+                )
+            }
+        };
+
+        let group_id = match self.groups.find_group_by_uuid(&community_uuid).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                return failure(
+                    "Community not found".to_string(),
+                    ValidationErrorCode::GroupNotFound,
+                )
+            }
+            Err(e) => {
+                return failure(
+                    format!("Failed to lookup group: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                )
+            }
+        };
+
+        let locked = self.writer.lock_group(&group_id).await;
+        match cancel_join(&locked, &group_id, &sender_pubkey.to_hex()).await {
+            Ok(true) => {
+                info!(
+                    "🙅 Join request from {} for group {} cancelled",
+                    sender_pubkey.to_hex(),
+                    group_id
+                );
+                metrics::increment("peek_join_requests_total", &[("status", "cancelled")]);
+                ServiceResponse::CancelJoinRequest {
+                    success: true,
+                    status: Some(JoinRequestStatus::Cancelled),
+                    error: None,
+                    error_code: None,
+                    message_key: None,
+                    params: None,
+                }
+            }
+            Ok(false) => failure(
+                "No pending join request to cancel".to_string(),
+                ValidationErrorCode::JoinRequestNotFound,
+            ),
+            Err(e) => failure(
+                format!("Failed to cancel join request: {}", e),
+                ValidationErrorCode::JoinRequestFailed,
+            ),
+        }
+    }
+
+    /// Service-signed proof that `member` now belongs to the validated community's group
+    /// None unless the response grants membership (failures and pending requests carry none)
+    fn attest(
+        &self,
+        community_id: &str,
+        member: &PublicKey,
+        result: &LocationValidationResponse,
+    ) -> Option<Attestation> {
+        // A dry run granted nothing, so there is nothing to attest
+        if !result.success || result.is_member != Some(true) || result.dry_run.is_some() {
+            return None;
+        }
+        let claims = AttestationClaims {
+            community_id: community_id.to_string(),
+            pubkey: member.to_hex(),
+            group_id: result.group_id.clone()?,
+            granted_at: self.clock.now_unix(),
+        };
+        Some(Attestation::sign(&claims, &self.service_keys))
+    }
+
+    /// Resolve a community to its group id, requiring `sender_pubkey` to be one of its admins
+    async fn admin_group(
+        &self,
+        community_id: &str,
+        sender_pubkey: &PublicKey,
+    ) -> Result<String, (String, ValidationErrorCode)> {
+        let community_uuid = self.parse_community_id(community_id).map_err(|e| {
+            (
+                format!("Invalid community ID: {}", e),
+                ValidationErrorCode::InvalidId,
+            )
+        })?;
+
+        let group_id = match self.groups.find_group_by_uuid(&community_uuid).await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                return Err((
+                    "Community not found".to_string(),
+                    ValidationErrorCode::GroupNotFound,
+                ))
+            }
+            Err(e) => {
+                return Err((
+                    format!("Failed to lookup group: {}", e),
+                    ValidationErrorCode::GroupLookupFailed,
+                ))
+            }
+        };
+
+        match self.groups.is_group_admin(&group_id, sender_pubkey).await {
+            Ok(true) => Ok(group_id),
+            Ok(false) => Err((
+                "Only community admins can do this".to_string(),
+                ValidationErrorCode::NotAdmin,
+            )),
+            Err(e) => Err((
+                format!("Failed to verify admin status: {}", e),
+                ValidationErrorCode::GroupLookupFailed,
+            )),
+        }
+    }
+
+    /// Push an update now, or hold it until the end of `quiet_hours` if it is routine and they
+    /// are in effect; held pushes are sent by run_quiet_hours_flush
+    async fn push_or_hold(
+        &self,
+        quiet_hours: Option<&QuietHours>,
+        kind: PushKind,
+        recipient: PublicKey,
+        update: ServiceResponse,
+        what: &'static str,
+    ) {
+        let push = HeldPush {
+            recipient,
+            update,
+            what,
+        };
+        match self.quiet_queue.hold(quiet_hours, kind, push) {
+            Some(push) => {
+                self.push_update(push.recipient, &push.update, push.what)
+                    .await
+            }
+            None => metrics::increment("peek_quiet_hours_held_total", &[("what", what)]),
+        }
+    }
+
+    /// Push an update to an admin or applicant outside any request/response exchange
+    /// There is no request to correlate with, so the rumor has no e tag; `what` names the
+    /// update in logs and in the retry queue
+    async fn push_update(&self, recipient: PublicKey, update: &ServiceResponse, what: &str) {
+        let content = match serde_json::to_string(update) {
+            Ok(content) => content,
+            Err(e) => {
+                error!("Failed to serialize {}: {}", what, e);
+                return;
+            }
+        };
+        let kind = self.config.protocol.response_kind();
+        let inbox_relays = self.inbox_relays.read_relays(&recipient).await;
+        let expiration = response_expiration(
+            None,
+            self.clock.now(),
+            self.config.response_expiration_max_secs,
+        );
+
+        let rumor = EventBuilder::new(kind, content.clone());
+        if let Err(e) = self
+            .gift_wrap_service
+            .create_and_send_gift_wrap(&self.client, &recipient, rumor, expiration, &inbox_relays)
+            .await
+        {
+            warn!("Failed to send {} to {}: {}", what, recipient.to_hex(), e);
+            self.response_retry.enqueue(QueuedResponse::new(
+                recipient,
+                kind,
+                content,
+                Vec::new(),
+                format!("{}:{}", what, recipient.to_hex()),
+                inbox_relays,
+                expiration,
+            ));
+        }
+    }
+
+    /// Send a gift-wrapped response back to the requester
+    async fn send_service_response(
+        &self,
+        recipient: PublicKey,
+        rumor: EventBuilder,
+        request_id: &str,
+        inbox_relays: &[String],
+        expiration: Timestamp,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        info!(
+            "🎁 Creating gift wrap for recipient: {} ({})",
+            recipient.to_bech32()?,
+            recipient.to_hex()
+        );
+        info!("🔗 Request ID reference: {}", request_id);
+
+        // Use the centralized gift wrap service
+        let event_id = self
+            .gift_wrap_service
+            .create_and_send_gift_wrap(&self.client, &recipient, rumor, expiration, inbox_relays)
+            .await?;
+
+        info!(
+            "✅ Gift wrap sent successfully: {} to {}",
+            event_id,
+            recipient.to_bech32()?
+        );
+
+        Ok(())
+    }
+}
+
+/// Public relays clients may send gift-wrapped requests through
+const PUBLIC_LISTENING_RELAYS: &[&str] = &[
+    "wss://relay.damus.io",
+    "wss://relay.nostr.band",
+    "wss://nos.lol",
+];
+
+/// Relays the service listens on for gift-wrapped requests, each once
+/// Use only local relay for development/testing environments
+pub fn listening_relays(config: &Config) -> Vec<RelayUrl> {
+    let mut relays = vec![config.relay_url.clone()];
+    if !config.relay_url.is_local() {
+        for url in PUBLIC_LISTENING_RELAYS {
+            let url = RelayUrl::parse(url).expect("listening relay URLs are valid");
+            if !relays.contains(&url) {
+                relays.push(url);
+            }
+        }
+    }
+    relays
+}
+
+/// A decrypted gift wrap: the seal signer plus the rumor both parsed and as raw JSON
+struct UnwrappedRumor {
+    sender: PublicKey,
+    rumor: UnsignedEvent,
+    raw_rumor: serde_json::Value,
+    // Oldest scheme either layer was encrypted with
+    scheme: WrapScheme,
+}
+
+/// Encryption a gift wrap layer was opened with, oldest last
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum WrapScheme {
+    Nip44,
+    // Older clients that never moved off NIP-04 for their wraps
+    Nip04,
+}
+
+impl WrapScheme {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WrapScheme::Nip44 => "nip44",
+            WrapScheme::Nip04 => "nip04",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum UnwrapError {
+    #[error("Expected gift wrap, got kind {0}")]
+    NotGiftWrap(Kind),
+    #[error("No supported scheme decrypts the {0}")]
+    Undecryptable(&'static str),
+    #[error("Malformed {layer}: {reason}")]
+    Malformed { layer: &'static str, reason: String },
+    #[error("Rumor rejected ({})", .0.reason())]
+    Rejected(RumorRejection),
+}
+
+fn malformed(layer: &'static str, reason: impl ToString) -> UnwrapError {
+    UnwrapError::Malformed {
+        layer,
+        reason: reason.to_string(),
+    }
+}
+
+/// Decrypt one layer with NIP-44, whose payload names its own version, then with NIP-04
+fn decrypt_layer(
+    secret_key: &SecretKey,
+    counterparty: &PublicKey,
+    content: &str,
+    layer: &'static str,
+) -> Result<(String, WrapScheme), UnwrapError> {
+    if let Ok(plaintext) = nip44::decrypt(secret_key, counterparty, content) {
+        return Ok((plaintext, WrapScheme::Nip44));
+    }
+    // NIP-04 payloads carry their IV after "?iv="; nothing else is worth a second attempt
+    if content.contains("?iv=") {
+        if let Ok(plaintext) = nip04::decrypt(secret_key, counterparty, content) {
+            return Ok((plaintext, WrapScheme::Nip04));
+        }
+    }
+    Err(UnwrapError::Undecryptable(layer))
+}
+
+/// Unwrap a NIP-59 gift wrap addressed to `keys`, keeping the raw rumor JSON
+/// The raw form lets verify_rumor see fields UnsignedEvent would silently drop (e.g. sig)
+fn unwrap_gift_wrap(keys: &Keys, gift_wrap: &Event) -> Result<UnwrappedRumor, UnwrapError> {
+    if gift_wrap.kind != Kind::GiftWrap {
+        return Err(UnwrapError::NotGiftWrap(gift_wrap.kind));
+    }
+
+    let (seal_json, wrap_scheme) = decrypt_layer(
+        keys.secret_key(),
+        &gift_wrap.pubkey,
+        &gift_wrap.content,
+        "gift wrap",
+    )?;
+    let seal = Event::from_json(seal_json).map_err(|e| malformed("seal", e))?;
+    seal.verify().map_err(|e| malformed("seal", e))?;
+    if seal.kind != Kind::Seal {
+        return Err(malformed("seal", format!("kind {}", seal.kind)));
+    }
+
+    let (rumor_json, seal_scheme) =
+        decrypt_layer(keys.secret_key(), &seal.pubkey, &seal.content, "seal")?;
+    let raw_rumor: serde_json::Value =
+        serde_json::from_str(&rumor_json).map_err(|e| malformed("rumor", e))?;
+    let mut rumor = UnsignedEvent::from_json(&rumor_json).map_err(|e| malformed("rumor", e))?;
+    rumor.ensure_id();
+    // The rumor is unsigned, so only the seal's signature says who sent it
+    if rumor.pubkey != seal.pubkey {
+        return Err(UnwrapError::Rejected(RumorRejection::SenderMismatch));
+    }
+
+    Ok(UnwrappedRumor {
+        sender: seal.pubkey,
+        rumor,
+        raw_rumor,
+        scheme: wrap_scheme.max(seal_scheme),
+    })
+}
+
+/// Why a rumor was dropped before processing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RumorRejection {
+    UnexpectedKind,
+    Signed,
+    IdMismatch,
+    CreatedAtOutOfRange,
+    ForeignRecipient,
+    // Rumor claims a pubkey other than the seal's signer
+    SenderMismatch,
+}
+
+impl RumorRejection {
+    fn reason(&self) -> &'static str {
+        match self {
+            RumorRejection::UnexpectedKind => "unexpected_kind",
+            RumorRejection::Signed => "signed",
+            RumorRejection::IdMismatch => "id_mismatch",
+            RumorRejection::CreatedAtOutOfRange => "created_at_out_of_range",
+            RumorRejection::ForeignRecipient => "foreign_recipient",
+            RumorRejection::SenderMismatch => "sender_mismatch",
+        }
+    }
+}
+
+/// Check an unwrapped rumor before trusting its id and tags for response correlation
+/// Rumors must be our request kind, unsigned, carry a matching id if they claim one,
+/// be recently dated, and not be addressed (via p tags) to anyone but the service.
+fn verify_rumor(
+    raw_rumor: &serde_json::Value,
+    rumor: &UnsignedEvent,
+    request_kind: Kind,
+    service_pubkey: &PublicKey,
+    now: Timestamp,
+) -> Result<(), RumorRejection> {
+    if rumor.kind != request_kind {
+        return Err(RumorRejection::UnexpectedKind);
+    }
+
+    if raw_rumor.get("sig").is_some() {
+        return Err(RumorRejection::Signed);
+    }
+
+    let mut recomputed = rumor.clone();
+    recomputed.id = None;
+    recomputed.ensure_id();
+    if recomputed.id != rumor.id {
+        return Err(RumorRejection::IdMismatch);
+    }
+
+    let created_at = rumor.created_at.as_u64();
+    let now = now.as_u64();
+    if created_at > now + MAX_RUMOR_FUTURE_SECS || created_at + MAX_RUMOR_AGE_SECS < now {
+        return Err(RumorRejection::CreatedAtOutOfRange);
+    }
+
+    let addressed_elsewhere = rumor.tags.iter().any(|tag| match tag.as_slice() {
+        [name, pubkey, ..] if name == "p" => pubkey != &service_pubkey.to_hex(),
+        _ => false,
+    });
+    if addressed_elsewhere {
+        return Err(RumorRejection::ForeignRecipient);
+    }
+
+    Ok(())
+}
+
+/// NIP-40 expiration timestamp from an event's tags, if present and well-formed
+fn expiration_of(tags: &Tags) -> Option<Timestamp> {
+    tags.iter().find_map(|tag| match tag.as_slice() {
+        [name, value, ..] if name == "expiration" => value.parse::<u64>().ok().map(Timestamp::from),
+        _ => None,
+    })
+}
+
+/// Whether the tags carry a NIP-40 expiration at or before `now`
+fn is_expired_at(tags: &Tags, now: Timestamp) -> bool {
+    expiration_of(tags).is_some_and(|expiration| expiration <= now)
+}
+
+/// Expiration for a response gift wrap: the client's requested one, capped at `max_secs` from now
+fn response_expiration(requested: Option<Timestamp>, now: Timestamp, max_secs: u64) -> Timestamp {
+    let cap = now + max_secs;
+    requested.map_or(cap, |requested| requested.min(cap))
+}
+
+/// Reply kind requested via a `reply_kind` rumor tag
+/// Only kind 14 (NIP-17 chat message) is supported; anything else keeps the default response kind
+fn requested_reply_kind(tags: &Tags) -> Option<Kind> {
+    let value = tags.iter().find_map(|tag| match tag.as_slice() {
+        [name, value, ..] if name == REPLY_KIND_TAG => Some(value.clone()),
+        _ => None,
+    })?;
+
+    match value.parse::<u16>().map(Kind::from) {
+        Ok(Kind::PrivateDirectMessage) => Some(Kind::PrivateDirectMessage),
+        _ => {
+            debug!("Ignoring unsupported reply_kind: {}", value);
+            None
+        }
+    }
+}
+
+/// Sending app as declared by the rumor's `client` tag, "unknown" where absent
+/// Values are client-controlled, so both parts are cut short and stripped of control characters
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClientFingerprint {
+    name: String,
+    version: String,
+}
+
+impl ClientFingerprint {
+    fn from_tags(tags: &Tags) -> Self {
+        let field = |value: Option<&String>| {
+            let cleaned: String = value
+                .map(|v| v.trim())
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| !c.is_control())
+                .take(MAX_CLIENT_FIELD_CHARS)
+                .collect();
+            if cleaned.is_empty() {
+                "unknown".to_string()
+            } else {
+                cleaned
+            }
+        };
+        let tag = tags
+            .iter()
+            .map(|tag| tag.as_slice())
+            .find(|values| values.first().is_some_and(|name| name == CLIENT_TAG));
+        Self {
+            name: field(tag.and_then(|values| values.get(1))),
+            version: field(tag.and_then(|values| values.get(2))),
+        }
+    }
+
+    /// Count a request against this client; `format` is "typed", "legacy" or "unparseable"
+    /// Clients beyond the metric's series cap are counted as client and version "other"
+    fn record_request(&self, format: &'static str) {
+        metrics::increment(
+            "peek_client_requests_total",
+            &[
+                ("client", self.name.as_str()),
+                ("version", self.version.as_str()),
+                ("format", format),
+            ],
+        );
+    }
+}
+
+impl std::fmt::Display for ClientFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.name, self.version)
+    }
+}
+
+/// Build the response rumor's kind, content and tags
+/// The e tag always correlates the response with the request. Kind 14 responses carry
+/// a human-readable summary line before the JSON and a p tag for the recipient, as NIP-17 requires.
+fn response_rumor(
+    response: &ServiceResponse,
+    response_json: String,
+    request_id: &str,
+    recipient: &PublicKey,
+    reply_kind: Option<Kind>,
+    response_kind: Kind,
+) -> (Kind, String, Vec<Tag>) {
+    let mut tags = vec![Tag::custom(
+        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::E)),
+        vec![request_id.to_string()],
+    )];
+
+    match reply_kind {
+        Some(kind @ Kind::PrivateDirectMessage) => {
+            tags.push(Tag::public_key(*recipient));
+            let content = format!("{}\n{}", response_summary(response), response_json);
+            (kind, content, tags)
+        }
+        _ => (response_kind, response_json, tags),
+    }
+}
+
+/// One-line summary shown by chat clients above the JSON payload
+fn response_summary(response: &ServiceResponse) -> String {
+    let (action, success, error) = match response {
+        ServiceResponse::LocationValidation { success, error, .. } => {
+            ("Location check", *success, error)
+        }
+        ServiceResponse::Preview(preview) => ("Community preview", preview.success, &preview.error),
+        ServiceResponse::PreviewBatch { success, error, .. } => {
+            ("Community previews", *success, error)
+        }
+        ServiceResponse::AddAnchor { success, error, .. } => ("Add anchor", *success, error),
+        ServiceResponse::UpdateMetadata { success, error, .. } => {
+            ("Update community settings", *success, error)
+        }
+        ServiceResponse::ApproveJoin { success, error, .. } => ("Join approval", *success, error),
+        ServiceResponse::CancelJoinRequest { success, error, .. } => {
+            ("Cancel join request", *success, error)
+        }
+        ServiceResponse::ArchiveCommunity {
+            success,
+            archived,
+            error,
+            ..
+        } => {
+            let action = if *archived == Some(false) {
+                "Unarchive community"
+            } else {
+                "Archive community"
+            };
+            (action, *success, error)
+        }
+        ServiceResponse::ExportCommunity { success, error, .. } => {
+            ("Export community", *success, error)
+        }
+        ServiceResponse::BulkAddMembers { success, error, .. } => ("Add members", *success, error),
+        ServiceResponse::JoinRequestUpdate { status, .. } => {
+            return format!("Peek: Join request {}", join_status_label(*status));
+        }
+        ServiceResponse::MemberJoined {
+            community_name,
+            pubkeys,
+            ..
+        } => {
+            return match pubkeys.len() {
+                1 => format!("Peek: A new member joined {}", community_name),
+                n => format!("Peek: {} new members joined {}", n, community_name),
+            };
+        }
+        ServiceResponse::InactivityWarning { community_name, .. } => {
+            return format!(
+                "Peek: Post in {} within a week to stay a member",
+                community_name
+            );
+        }
+        ServiceResponse::StickerReuseSuspected {
+            community_name,
+            distance_km,
+            ..
+        } => {
+            return format!(
+                "Peek: The sticker for {} may have been copied, joins keep failing {}km away",
+                community_name, distance_km
+            );
+        }
+    };
+
+    match (success, error) {
+        (true, _) => format!("Peek: {} succeeded", action),
+        (false, Some(error)) => format!("Peek: {} failed: {}", action, error),
+        (false, None) => format!("Peek: {} failed", action),
+    }
+}
+
+/// The member_joined push for a closed batch; None when the community opted out
+fn member_joined_notification(
+    batch: &JoinBatch,
+    metadata: &GroupMetadata,
+) -> Option<ServiceResponse> {
+    metadata
+        .join_notifications
+        .then(|| ServiceResponse::MemberJoined {
+            community_id: batch.community_id.clone(),
+            community_name: metadata.name.clone(),
+            member_count: metadata.member_count,
+            pubkeys: batch.pubkeys.clone(),
+        })
+}
+
+/// Lowercase wording for a join request status in chat summaries
+fn join_status_label(status: JoinRequestStatus) -> &'static str {
+    match status {
+        JoinRequestStatus::Pending => "pending approval",
+        JoinRequestStatus::Approved => "approved",
+        JoinRequestStatus::Rejected => "rejected",
+        JoinRequestStatus::Expired => "expired",
+        JoinRequestStatus::Cancelled => "cancelled",
+    }
+}
+
+/// What a checked location fix is about to be used for; each use trusts accuracy differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FixUse {
+    Join,
+    Create,
+    AddAnchor,
+}
+
+impl FixUse {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FixUse::Join => "join",
+            FixUse::Create => "create",
+            FixUse::AddAnchor => "add_anchor",
+        }
+    }
+
+    /// Joins rely on the geohash check alone; a fix that becomes an anchor needs a known accuracy
+    fn check_accuracy(self, accuracy: AccuracyReading) -> Result<(), ValidationErrorCode> {
+        if accuracy.is_unknown() {
+            metrics::increment("peek_accuracy_unknown_total", &[("use", self.as_str())]);
+        }
+        match (self, accuracy) {
+            (FixUse::Join, _) => Ok(()),
+            (FixUse::Create, AccuracyReading::Unknown) => Err(ValidationErrorCode::AccuracyUnknown),
+            (FixUse::Create, AccuracyReading::Meters(_)) => Ok(()),
+            (FixUse::AddAnchor, AccuracyReading::Meters(meters))
+                if meters <= MAX_ANCHOR_ACCURACY_METERS =>
+            {
+                Ok(())
+            }
+            (FixUse::AddAnchor, _) => Err(ValidationErrorCode::AccuracyTooLow {
+                max_accuracy_meters: MAX_ANCHOR_ACCURACY_METERS,
+            }),
+        }
+    }
+}
+
+/// The distance detail a rejected requester gets; a probing one learns nothing from it
+fn reported_distance(bucket: DistanceBucket, probe_status: ProbeStatus) -> DistanceBucket {
+    match probe_status {
+        ProbeStatus::Normal => bucket,
+        ProbeStatus::Degraded | ProbeStatus::CoolingDown { .. } => DistanceBucket::Unknown,
+    }
+}
+
+/// Validate location against every anchor of a multi-anchor community
+fn validate_any_anchor(user_location: &Coordinates, anchors: &[String]) -> bool {
+    anchors
+        .iter()
+        .any(|anchor| validate_geohash_location(user_location, anchor))
+}
+
+/// Closest anchor cell center to the user and its great-circle distance in meters
+fn nearest_anchor(user_location: &Coordinates, anchors: &[String]) -> Option<(Coordinates, f64)> {
+    anchors
+        .iter()
+        .filter_map(|anchor| Coordinates::from_geohash(anchor).ok())
+        .map(|center| (center, haversine_meters(user_location, &center)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Whether the user is in the anchor cell or one of its neighbors, at anchor precision
+fn validate_geohash_location(user_location: &Coordinates, community_geohash: &str) -> bool {
+    matches_cell_or_neighbor(user_location, community_geohash, ANCHOR_GEOHASH_PRECISION)
+}
+
+/// Run the processing of one incoming event, turning a panic into a logged error and a metric
+///
+/// A bug reachable from one malformed request must cost that request only: the listener and
+/// every other request keep going. The panic message itself still goes to stderr via the hook.
+async fn contain_panic(event: &'static str, work: impl std::future::Future<Output = ()>) {
+    if let Err(panic) = AssertUnwindSafe(work).catch_unwind().await {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        metrics::increment("peek_event_panics_total", &[("event", event)]);
+        error!("💥 Processing a {} event panicked: {}", event, message);
+    }
+}
+
+/// Previews for up to MAX_PREVIEW_BATCH ids, looked up concurrently and returned in request order
+async fn preview_batch<F, Fut>(
+    community_ids: Vec<String>,
+    preview: F,
+) -> Result<Vec<PreviewResult>, ValidationErrorCode>
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = PreviewResult>,
+{
+    if community_ids.len() > MAX_PREVIEW_BATCH {
+        return Err(ValidationErrorCode::BatchTooLarge {
+            max_batch: MAX_PREVIEW_BATCH,
+        });
+    }
+    Ok(futures::future::join_all(community_ids.into_iter().map(preview)).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+    use crate::services::creation_limit::CreationShed;
+    use crate::services::gift_wrap::{
+        response_tags, ECHOABLE_TAG_KINDS, MAX_RESPONSE_TAGS, MAX_RESPONSE_TAG_VALUE_CHARS,
+    };
+    use crate::services::member_set::MemberSet;
+    use crate::services::previous_refs::PreviousRefs;
+    use crate::services::relay::RelayService;
+    use crate::services::relay_access::DiscoveryPublisher;
+    use crate::services::relay_circuit::RelayCircuit;
+    use crate::services::{creation_limit::CreationLimiter, discovery_map::DiscoveryMaps};
+    use std::time::Duration;
+    use validation_service::fake_relay::{FakeRelay, SeededCommunity};
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates::new(latitude, longitude).unwrap()
+    }
+
+    #[test]
+    fn test_join_at_second_anchor() {
+        // Two entrances of the same venue, a few hundred meters apart
+        let front = point(37.7749, -122.4194).geohash(8).unwrap();
+        let back = point(37.7770, -122.4160).geohash(8).unwrap();
+        let anchors = vec![front.clone(), back.clone()];
+
+        // Standing at the back entrance only matches the second anchor
+        let user = point(37.7770, -122.4160);
+        assert!(!validate_geohash_location(&user, &front));
+        assert!(validate_any_anchor(&user, &anchors));
+
+        // Somewhere else entirely matches neither
+        assert!(!validate_any_anchor(&point(37.8000, -122.4000), &anchors));
+    }
+
+    #[test]
+    fn test_no_anchors_rejects() {
+        assert!(!validate_any_anchor(&point(37.7749, -122.4194), &[]));
+    }
+
+    #[test]
+    fn test_undecodable_anchors_are_skipped_not_fatal() {
+        let user = point(37.7749, -122.4194);
+        let good = user.geohash(ANCHOR_GEOHASH_PRECISION).unwrap();
+        // "i", "l", "o" and "a" are not in the geohash alphabet
+        let garbage = vec!["9q8yyioa".to_string(), "........".to_string()];
+        assert!(!validate_any_anchor(&user, &garbage));
+        assert!(nearest_anchor(&user, &garbage).is_none());
+
+        let mixed = vec![garbage[0].clone(), good];
+        assert!(validate_any_anchor(&user, &mixed));
+        assert!(nearest_anchor(&user, &mixed).is_some_and(|(_, meters)| meters < 25.0));
+    }
+
+    #[tokio::test]
+    async fn test_a_panicking_event_does_not_stop_the_ones_after_it() {
+        let handled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        for n in 0..3 {
+            let handled = handled.clone();
+            contain_panic("gift_wrap", async move {
+                if n == 1 {
+                    panic!("request {} hit a bug", n);
+                }
+                handled.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            })
+            .await;
+        }
+        assert_eq!(handled.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // Spawned like gift wraps are, the task finishes normally instead of as a panic
+        let spawned = tokio::spawn(contain_panic("gift_wrap", async {
+            let garbage: Vec<f64> = Vec::new();
+            let _ = garbage[3];
+        }));
+        assert!(spawned.await.is_ok());
+    }
+
+    #[test]
+    fn test_preview_flags_full_communities() {
+        let metadata_at = |member_count: u32, tags: Vec<Tag>| {
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tags(tags)
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            GroupMetadata::from_event(&event, member_count)
+        };
+        let cap = || {
+            vec![Tag::custom(
+                TagKind::Custom(crate::services::relay::MAX_MEMBERS_TAG.into()),
+                ["50"],
+            )]
+        };
+        let now = Timestamp::from(1_760_000_000);
+
+        let full = PreviewResult::from_metadata(metadata_at(50, cap()), None, now);
+        assert_eq!(full.max_members, Some(50));
+        assert_eq!(full.is_full, Some(true));
+
+        let open = PreviewResult::from_metadata(metadata_at(49, cap()), None, now);
+        assert_eq!(open.is_full, Some(false));
+
+        let uncapped = PreviewResult::from_metadata(metadata_at(5_000, vec![]), None, now);
+        let json = serde_json::to_value(&uncapped).unwrap();
+        assert_eq!(json["is_full"], false);
+        assert!(json.get("max_members").is_none());
+    }
+
+    #[test]
+    fn test_preview_carries_the_age_restriction() {
+        let preview = |tags: Vec<Tag>| {
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tags(tags)
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            let metadata = GroupMetadata::from_event(&event, 3);
+            PreviewResult::from_metadata(metadata, None, Timestamp::from(1_760_000_000))
+        };
+
+        let restricted = preview(vec![Tag::custom(
+            TagKind::Custom(crate::services::relay::AGE_RESTRICTED_TAG.into()),
+            ["true"],
+        )]);
+        assert_eq!(restricted.age_restricted, Some(true));
+
+        // Unmarked communities say so explicitly, so clients need not guess from absence
+        let json = serde_json::to_value(preview(vec![])).unwrap();
+        assert_eq!(json["age_restricted"], false);
+    }
+
+    #[test]
+    fn test_community_errors_map_to_error_codes() {
+        let now = Timestamp::from(1_760_000_000);
+        let id = Uuid::new_v4();
+        let code = |error| {
+            NostrValidationHandler::community_failure(error, now)
+                .error_code
+                .unwrap()
+        };
+
+        // An overrun asks the client to retry rather than leaving it waiting
+        assert_eq!(
+            code(CommunityError::Timeout {
+                stage: "group creation"
+            }),
+            "RETRY_LATER"
+        );
+        assert_eq!(
+            code(CommunityError::Relay(RelayError::QueryInconclusive(
+                "peek-abc123".to_string()
+            ))),
+            "RETRY_LATER"
+        );
+        // Retrying cannot help while the relay refuses the service's key
+        assert_eq!(
+            code(CommunityError::Relay(RelayError::RelayKeyUnauthorized(
+                "restricted: not a relay admin".to_string()
+            ))),
+            "SERVICE_MISCONFIGURED"
+        );
+        // A shed creation sent nothing to the relay, so retrying is safe
+        assert_eq!(
+            code(CommunityError::Busy(CreationShed::QueueFull(20))),
+            "RETRY_LATER"
+        );
+        assert_eq!(code(CommunityError::Corrupted(id)), "COMMUNITY_ERROR");
+        assert_eq!(
+            code(CommunityError::CreationFailed {
+                step: "group creation",
+                reason: "relay rejected event".to_string()
+            }),
+            "COMMUNITY_ERROR"
+        );
+        assert_eq!(code(CommunityError::NotFound(id)), "GROUP_NOT_FOUND");
+        assert_eq!(
+            code(CommunityError::Relay(RelayError::Other(
+                "closed".to_string()
+            ))),
+            "GROUP_LOOKUP_FAILED"
+        );
+    }
+
+    #[test]
+    fn test_preview_reports_the_requesters_role() {
+        let admin = Keys::generate().public_key();
+        let member = Keys::generate().public_key();
+        let stranger = Keys::generate().public_key();
+        let admins = [admin];
+        let members = [admin.to_hex(), member.to_hex()];
+        let preview = |pubkey: &PublicKey| {
+            let is_member = members.contains(&pubkey.to_hex());
+            let metadata = GroupMetadata::from_event(
+                &EventBuilder::new(Kind::from(39000), "")
+                    .sign_with_keys(&Keys::generate())
+                    .unwrap(),
+                2,
+            );
+            PreviewResult::from_metadata(metadata, None, Timestamp::from(1_760_000_000))
+                .for_requester(requester_role(pubkey, is_member, Some(&admins)))
+        };
+
+        let as_admin = preview(&admin);
+        assert_eq!(as_admin.is_member, Some(true));
+        assert_eq!(as_admin.role, Some(MemberRole::Admin));
+
+        let as_member = preview(&member);
+        assert_eq!(as_member.is_member, Some(true));
+        assert_eq!(as_member.role, Some(MemberRole::Member));
+
+        let as_stranger = preview(&stranger);
+        assert_eq!(as_stranger.is_member, Some(false));
+        assert_eq!(as_stranger.role, None);
+
+        // An unreadable admin list still shows a member as one
+        assert_eq!(requester_role(&admin, true, None), Some(MemberRole::Member));
+
+        // Missing communities say nothing about membership
+        let missing = PreviewResult::failure("Community not found");
+        assert_eq!(missing.is_member, None);
+        assert_eq!(missing.role, None);
+    }
+
+    #[test]
+    fn test_preview_of_archived_community_still_succeeds() {
+        let metadata = |tags: Vec<Tag>| {
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tags(tags)
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            GroupMetadata::from_event(&event, 12)
+        };
+        let now = Timestamp::from(1_760_000_000);
+
+        // What members get; fetch_preview turns strangers away before building it
+        let archived = PreviewResult::from_metadata(
+            metadata(vec![Tag::custom(
+                TagKind::Custom("archived".into()),
+                Vec::<String>::new(),
+            )]),
+            None,
+            now,
+        );
+        assert!(archived.success);
+        assert_eq!(archived.archived, Some(true));
+        assert_eq!(archived.member_count, Some(12));
+
+        // Unarchiving removes the marker, and the preview goes back to normal
+        let restored = PreviewResult::from_metadata(metadata(vec![]), None, now);
+        assert_eq!(restored.archived, Some(false));
+    }
+
+    /// A handler wired like main's against an in-process relay holding the first seed
+    /// community, with `tags` added to its metadata
+    async fn handler_with_seed(tags: Vec<Tag>) -> (NostrValidationHandler, SeededCommunity, Keys) {
+        let (handler, seeded, admin, _) = handler_on_fake_relay(Config::default(), tags).await;
+        (handler, seeded, admin)
+    }
+
+    /// As `handler_with_seed`, starting from `config` and also returning the relay
+    async fn handler_on_fake_relay(
+        config: Config,
+        tags: Vec<Tag>,
+    ) -> (
+        NostrValidationHandler,
+        SeededCommunity,
+        Keys,
+        Arc<FakeRelay>,
+    ) {
+        let relay_keys = Keys::generate();
+        let admin = Keys::generate();
+        let relay = FakeRelay::new(relay_keys.clone());
+        let seeded = relay
+            .seed(
+                &validation_service::models::ProtocolConfig::default(),
+                admin.public_key(),
+            )
+            .into_iter()
+            .next()
+            .unwrap();
+        for tag in tags {
+            relay.add_metadata_tag(&seeded.group_id, tag);
+        }
+        let config = Config {
+            relay_url: RelayUrl::parse(&relay.clone().spawn().await.unwrap()).unwrap(),
+            ..config
+        };
+
+        let relay_service = Arc::new(
+            RelayService::new(
+                config.relay_url.clone(),
+                relay_keys.clone(),
+                config.protocol.clone(),
+                Duration::from_millis(500),
+                DiscoveryMaps {
+                    d_tag: config.protocol.discovery_map_d_tag.clone(),
+                    prefixes: Vec::new(),
+                    signer: None,
+                    exclusion_zones: ExclusionZones::default(),
+                },
+                config.metadata_max_event_bytes,
+                RelayCircuit::new(Arc::new(SystemClock), 0, Duration::from_secs(30)),
+                PreviousRefs::new(3),
+            )
+            .await
+            .unwrap(),
+        );
+        let groups = GroupReader::new(relay_service.clone());
+        let writer = GroupWriter::new(relay_service.clone());
+
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let community_service = Arc::new(CommunityService::new(
+            groups.clone(),
+            writer.clone(),
+            CreationLimiter::new(1, 1),
+        ));
+        let (audit_trail, _entries) = AuditTrail::new(clock.clone(), 16);
+        let handler = NostrValidationHandler::new(
+            config,
+            ServiceKeys {
+                service: Keys::generate(),
+                relay: relay_keys,
+            },
+            community_service,
+            groups,
+            writer,
+            Arc::new(ClientPool::new(4, Duration::from_secs(30))),
+            Arc::new(SubscriptionWatchdog::new(60, 3, clock.clone())),
+            Arc::new(PruneLog::new(16)),
+            Arc::new(FarScans::new(50, clock.clone())),
+            Arc::new(StickerReuse::new(3, clock)),
+            Arc::new(audit_trail),
+            Arc::new(CommunityClusters::new(DiscoveryPublisher::new(
+                relay_service,
+            ))),
+        )
+        .await
+        .unwrap();
+        (handler, seeded, admin, relay)
+    }
+
+    fn archived_tag() -> Tag {
+        Tag::custom(TagKind::Custom("archived".into()), Vec::<String>::new())
+    }
+
+    fn active_until_tag(offset_secs: i64) -> Tag {
+        let until = Timestamp::now().as_u64() as i64 + offset_secs;
+        Tag::custom(TagKind::Custom("active_until".into()), [until.to_string()])
+    }
+
+    /// A stranger standing right at seed 0's sticker
+    async fn join_as_stranger(
+        handler: &NostrValidationHandler,
+        community: &SeededCommunity,
+    ) -> LocationValidationResponse {
+        handler
+            .process_location_validation(
+                community.community_id.to_string(),
+                LocationData {
+                    latitude: 37.7749,
+                    longitude: -122.4194,
+                    accuracy: Some(10.0),
+                    timestamp: Timestamp::now().as_u64() as i64,
+                },
+                Keys::generate().public_key(),
+                CreationOptions::default(),
+                false,
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_joining_an_archived_community_is_refused() {
+        let (handler, archived, _) = handler_with_seed(vec![archived_tag()]).await;
+
+        // Right at the sticker, so only the archived marker can turn the stranger away
+        let response = join_as_stranger(&handler, &archived).await;
+
+        assert!(!response.success);
+        assert_eq!(response.error_code.as_deref(), Some("COMMUNITY_ARCHIVED"));
+        assert_eq!(response.group_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_previewing_an_archived_community_is_refused_to_strangers_only() {
+        let (handler, archived, admin) = handler_with_seed(vec![archived_tag()]).await;
+        let community_id = archived.community_id.to_string();
+
+        let stranger = handler
+            .process_preview(community_id.clone(), Keys::generate().public_key())
+            .await;
+        assert!(!stranger.success);
+        assert_eq!(stranger.error_code.as_deref(), Some("COMMUNITY_ARCHIVED"));
+        assert_eq!(stranger.name, None);
+
+        // Archived communities stay readable for their members
+        let member = handler
+            .process_preview(community_id, admin.public_key())
+            .await;
+        assert!(member.success);
+        assert_eq!(member.archived, Some(true));
+        assert_eq!(member.name.as_deref(), Some(archived.name.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_joining_before_the_deadline_succeeds() {
+        let (handler, community, _) = handler_with_seed(vec![active_until_tag(3600)]).await;
+
+        let response = join_as_stranger(&handler, &community).await;
+
+        assert!(response.success, "{:?}", response.error);
+        assert_eq!(
+            response.group_id.as_deref(),
+            Some(community.group_id.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_joining_after_the_deadline_is_refused() {
+        let (handler, community, _) = handler_with_seed(vec![active_until_tag(-60)]).await;
+
+        let response = join_as_stranger(&handler, &community).await;
+
+        assert!(!response.success);
+        assert_eq!(response.error_code.as_deref(), Some("COMMUNITY_CLOSED"));
+        assert_eq!(response.group_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_previewing_after_the_deadline_shows_it_archived() {
+        let (handler, community, _) = handler_with_seed(vec![active_until_tag(-60)]).await;
+
+        // Expired is not archived by an admin, so strangers can still look
+        let preview = handler
+            .process_preview(
+                community.community_id.to_string(),
+                Keys::generate().public_key(),
+            )
+            .await;
+
+        assert!(preview.success);
+        assert_eq!(preview.archived, Some(true));
+        assert_eq!(preview.name.as_deref(), Some(community.name.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_default_kind_gift_wrap_is_ignored_by_a_namespaced_service() {
+        let acme = Config {
+            protocol: crate::models::ProtocolConfig {
+                request_kind: 27592,
+                response_kind: 27593,
+                ..Default::default()
+            },
+            ..Config::default()
+        };
+        let (handler, community, _, relay) = handler_on_fake_relay(acme, Vec::new()).await;
+        let service_pubkey = handler.service_keys.public_key();
+        let user = Keys::generate();
+        let request = serde_json::to_string(&LocationValidationRequest {
+            request_type: None,
+            community_id: community.community_id.to_string(),
+            location: LocationData {
+                latitude: 37.7749,
+                longitude: -122.4194,
+                accuracy: Some(10.0),
+                timestamp: Timestamp::now().as_u64() as i64,
+            },
+        })
+        .unwrap();
+        let (sender, service, request) = (&user, &service_pubkey, &request);
+        let wrap = move |kind: u16, hint: Option<u16>| {
+            let rumor =
+                EventBuilder::new(Kind::Custom(kind), request.clone()).build(sender.public_key());
+            let hint = hint.map(|kind| {
+                Tag::custom(
+                    TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K)),
+                    [kind.to_string()],
+                )
+            });
+            EventBuilder::gift_wrap(sender, service, rumor, hint)
+        };
+        let is_member = || {
+            relay
+                .members(&community.group_id)
+                .unwrap()
+                .contains(&user.public_key())
+        };
+
+        // Default-kind requests, with or without a k-tag hint on the wrapper, change nothing
+        for hint in [Some(27492), None] {
+            handler
+                .handle_gift_wrap(wrap(27492, hint).await.unwrap())
+                .await
+                .unwrap();
+            assert!(!is_member());
+        }
+
+        // The same request in the deployment's own kind joins the user
+        handler
+            .handle_gift_wrap(wrap(27592, Some(27592)).await.unwrap())
+            .await
+            .unwrap();
+        assert!(is_member());
+    }
+
+    #[test]
+    fn test_listening_relays_are_each_listed_once() {
+        let config = |relay_url: &str| Config {
+            relay_url: RelayUrl::parse(relay_url).unwrap(),
+            ..Config::default()
+        };
+        let as_strings = |relays: Vec<RelayUrl>| -> Vec<String> {
+            relays.iter().map(RelayUrl::to_string).collect()
+        };
+
+        assert_eq!(
+            as_strings(listening_relays(&config("ws://LOCALHOST:8080/"))),
+            vec!["ws://localhost:8080"]
+        );
+        // A groups relay that is also one of the public ones is not connected to twice
+        assert_eq!(
+            as_strings(listening_relays(&config("wss://Nos.lol/"))),
+            vec![
+                "wss://nos.lol",
+                "wss://relay.damus.io",
+                "wss://relay.nostr.band"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_member_joined_notification_carries_the_batch_unless_opted_out() {
+        let metadata = |tags: Vec<Tag>| {
+            let mut all = vec![Tag::custom(TagKind::Name, ["Blue Bottle"])];
+            all.extend(tags);
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tags(all)
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            GroupMetadata::from_event(&event, 14)
+        };
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let batcher = JoinBatcher::new(60, clock.clone());
+        batcher.record("peek-abc", "3a7e5c59", "alice");
+        clock.advance(10);
+        batcher.record("peek-abc", "3a7e5c59", "bob");
+        clock.advance(50);
+        let batch = batcher.take_due().pop().unwrap();
+
+        assert_eq!(
+            member_joined_notification(&batch, &metadata(vec![])),
+            Some(ServiceResponse::MemberJoined {
+                community_id: "3a7e5c59".to_string(),
+                community_name: "Blue Bottle".to_string(),
+                member_count: 14,
+                pubkeys: vec!["alice".to_string(), "bob".to_string()],
+            })
+        );
+        let opted_out = metadata(vec![Tag::custom(
+            TagKind::Custom("join_notifications".into()),
+            ["off"],
+        )]);
+        assert_eq!(member_joined_notification(&batch, &opted_out), None);
+        assert_eq!(
+            response_summary(&member_joined_notification(&batch, &metadata(vec![])).unwrap()),
+            "Peek: 2 new members joined Blue Bottle"
+        );
+    }
+
+    #[test]
+    fn test_sticker_reuse_alert_reports_the_cluster() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let reuse = StickerReuse::new(3, clock.clone());
+        let community_id = Uuid::from_u128(7);
+        let oakland = Coordinates::new(37.8044, -122.2712).unwrap();
+        for pubkey in ["alice", "bob", "carol"] {
+            reuse.record_failure(community_id, pubkey, &oakland, Some(13_000.0));
+            clock.advance(86_400);
+        }
+        let flag = reuse.take_unannounced().pop().unwrap();
+
+        let alert = ServiceResponse::sticker_reuse_suspected(&flag, "Blue Bottle".to_string());
+        assert_eq!(
+            alert,
+            ServiceResponse::StickerReuseSuspected {
+                community_id: community_id.to_string(),
+                community_name: "Blue Bottle".to_string(),
+                cluster: "9q9p1".to_string(),
+                distance_km: 13,
+                failures: 3,
+            }
+        );
+        assert_eq!(
+            response_summary(&alert),
+            "Peek: The sticker for Blue Bottle may have been copied, joins keep failing 13km away"
+        );
+    }
+
+    #[test]
+    fn test_welcome_is_sent_on_first_join_only() {
+        let mut community = CommunityMetadata {
+            name: "Blue Bottle".to_string(),
+            picture: None,
+            geohash: "9q8yyk8y".to_string(),
+            anchors: vec!["9q8yyk8y".to_string()],
+            active_until: None,
+            join_mode: JoinMode::Auto,
+            max_members: None,
+            archived: false,
+            members: MemberSet::complete(vec!["admin".to_string()]),
+            welcome: Some("Welcome to Blue Bottle! Say hi in the chat.".to_string()),
+            rules: vec!["Be kind".to_string()],
+            relay_url: None,
+            is_public: false,
+            age_restricted: false,
+        };
+
+        let welcome = first_join_welcome(&community, false).unwrap();
+        assert_eq!(welcome.text, "Welcome to Blue Bottle! Say hi in the chat.");
+        assert_eq!(welcome.rules, vec!["Be kind"]);
+
+        // Re-validating after the join finds them in the member list and gets no repeat
+        community.members.pubkeys.push("newcomer".to_string());
+        assert_eq!(
+            first_join_welcome(&community, community.has_member("newcomer")),
+            None
+        );
+
+        // A partial member list may be hiding them, so no welcome either
+        community.members = MemberSet {
+            possibly_incomplete: true,
+            ..MemberSet::complete(vec!["admin".to_string()])
+        };
+        assert_eq!(first_join_welcome(&community, false), None);
+
+        community.welcome = None;
+        assert_eq!(first_join_welcome(&community, false), None);
+    }
+
+    #[test]
+    fn test_only_repeatable_requests_coalesce() {
+        let join: ServiceRequest = serde_json::from_str(
+            r#"{"type":"location_validation","community_id":"3a7e5c59","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            join.coalescing_scope(),
+            Some(("location_validation", "3a7e5c59"))
+        );
+
+        let cancel = ServiceRequest::CancelJoinRequest {
+            community_id: "3a7e5c59".to_string(),
+        };
+        assert_eq!(
+            cancel.coalescing_scope(),
+            Some(("cancel_join_request", "3a7e5c59"))
+        );
+
+        // A second edit may carry different values and must not be answered with the first
+        let edit = ServiceRequest::UpdateMetadata {
+            community_id: "3a7e5c59".to_string(),
+            join_mode: None,
+            max_members: None,
+            name: Some("Blue Bottle".to_string()),
+            about: None,
+            welcome: None,
+            slug: None,
+            join_notifications: None,
+            age_restricted: None,
+            inactive_prune_days: None,
+            quiet_hours: None,
+        };
+        assert_eq!(edit.coalescing_scope(), None);
+    }
+
+    #[test]
+    fn test_admin_requests_are_audited_with_their_outcome() {
+        let sender = Keys::generate().public_key();
+        let community_id = "3A7E5C59-C0A1-4876-ACF1-56189B86AA0D";
+        let archive = ServiceRequest::ArchiveCommunity {
+            community_id: community_id.to_string(),
+        };
+        let action = archive.audit_action(&sender).unwrap();
+        assert_eq!(action.actor, AuditActor::Pubkey(sender.to_hex()));
+        assert_eq!(action.action, "archive_community");
+        assert_eq!(
+            action.community_id.as_deref(),
+            Some("3a7e5c59-c0a1-4876-acf1-56189b86aa0d")
+        );
+
+        // Joins, previews and exports change nothing an admin decided
+        let preview = ServiceRequest::PreviewRequest {
+            community_id: community_id.to_string(),
+        };
+        assert!(preview.audit_action(&sender).is_none());
+
+        let refused =
+            archive.failure_response("Not an admin".to_string(), ValidationErrorCode::NotAdmin);
+        assert_eq!(
+            refused.audit_outcome(),
+            (AuditOutcome::Rejected, Some("NOT_ADMIN".to_string()))
+        );
+        let failed = archive.failure_response(
+            "Could not update".to_string(),
+            ValidationErrorCode::MetadataUpdateFailed,
+        );
+        assert_eq!(
+            failed.audit_outcome(),
+            (
+                AuditOutcome::Failed,
+                Some("METADATA_UPDATE_FAILED".to_string())
+            )
+        );
+        let archived = ServiceResponse::ArchiveCommunity {
+            success: true,
+            archived: Some(true),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        };
+        assert_eq!(archived.audit_outcome(), (AuditOutcome::Ok, None));
+    }
+
+    fn request_tags(tags: Vec<Tag>) -> Tags {
+        EventBuilder::new(Kind::Custom(27492), "")
+            .tags(tags)
+            .build(Keys::generate().public_key())
+            .tags
+    }
+
+    fn e_tag_values(tags: &[Tag]) -> Vec<String> {
+        tags.iter()
+            .filter(|t| t.kind() == TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::E)))
+            .filter_map(|t| t.content().map(String::from))
+            .collect()
+    }
+
+    /// Stand-in for process_preview: ids starting with "known" exist, slower the earlier they are
+    async fn fake_preview(community_id: String) -> PreviewResult {
+        let delay = 30u64.saturating_sub(community_id.len() as u64);
+        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+        if community_id.starts_with("known") {
+            PreviewResult {
+                success: true,
+                name: Some(community_id),
+                ..Default::default()
+            }
+        } else {
+            PreviewResult::failure("Community not found")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preview_batch_keeps_request_order_with_mixed_results() {
+        let ids = ["known-a", "missing", "known-bb", "known-ccc", "gone"];
+        let results = preview_batch(ids.iter().map(|id| id.to_string()).collect(), fake_preview)
+            .await
+            .unwrap();
+
+        // The later, faster lookups finish first but stay in their requested slots
+        let summary: Vec<(bool, Option<&str>, Option<&str>)> = results
+            .iter()
+            .map(|r| (r.success, r.name.as_deref(), r.error.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (true, Some("known-a"), None),
+                (false, None, Some("Community not found")),
+                (true, Some("known-bb"), None),
+                (true, Some("known-ccc"), None),
+                (false, None, Some("Community not found")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preview_batch_is_capped() {
+        let ids = |count: usize| (0..count).map(|i| format!("known-{}", i)).collect();
+
+        let at_cap = preview_batch(ids(MAX_PREVIEW_BATCH), fake_preview)
+            .await
+            .unwrap();
+        assert_eq!(at_cap.len(), MAX_PREVIEW_BATCH);
+        assert!(preview_batch(Vec::new(), fake_preview)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let over = preview_batch(ids(MAX_PREVIEW_BATCH + 1), fake_preview).await;
+        assert_eq!(
+            over,
+            Err(ValidationErrorCode::BatchTooLarge { max_batch: 20 })
+        );
+    }
+
+    #[test]
+    fn test_nearest_anchor_bearing_points_at_the_user() {
+        let anchor = "9q8yyk8y".to_string();
+        let (center, _) = nearest_anchor(&point(37.7749, -122.4194), &[anchor.clone()]).unwrap();
+
+        // ~1km east of the anchor approaches from the east
+        let east = point(center.latitude(), center.longitude() + 0.0114);
+        let (center, meters) = nearest_anchor(&east, &[anchor]).unwrap();
+        assert!((900.0..1_100.0).contains(&meters), "got {}m", meters);
+        assert_eq!(
+            CompassBucket::from_degrees(bearing_degrees(&center, &east)),
+            CompassBucket::E
+        );
+    }
+
+    #[test]
+    fn test_probing_requester_loses_distance_detail_then_cools_down() {
+        let probes = LocationProbeGuard::new(
+            ProbePolicy {
+                threshold: 10,
+                window_secs: 3_600,
+                cool_down_secs: 600,
+                max_jitter: std::time::Duration::from_millis(3_000),
+            },
+            Arc::new(ThreadRngSource),
+        );
+        let prober = Keys::generate().public_key();
+        let community = Uuid::new_v4();
+        let just_outside = DistanceBucket::Under100m;
+
+        // Ten guesses a minute apart walking along the boundary, each answered in full
+        for minute in 0..10 {
+            let status = probes.status(&prober, &community, 1_000 + minute * 60);
+            assert_eq!(reported_distance(just_outside, status), just_outside);
+            probes.record_rejection(&prober, &community, 1_000 + minute * 60);
+        }
+
+        // The eleventh is refused outright until the cool-down after the last miss passes
+        assert_eq!(
+            probes.status(&prober, &community, 1_600),
+            ProbeStatus::CoolingDown {
+                retry_after_secs: 540
+            }
+        );
+        let degraded = probes.status(&prober, &community, 2_140);
+        assert_eq!(degraded, ProbeStatus::Degraded);
+        assert_eq!(
+            reported_distance(just_outside, degraded),
+            DistanceBucket::Unknown
+        );
+        assert!(probes.jitter() <= std::time::Duration::from_millis(3_000));
+
+        // Getting in clears the record
+        probes.record_accepted(&prober, &community);
+        let status = probes.status(&prober, &community, 2_140);
+        assert_eq!(reported_distance(just_outside, status), just_outside);
+    }
+
+    #[test]
+    fn test_garbage_location_data_is_refused() {
+        let valid = LocationData {
+            latitude: 37.7749,
+            longitude: -122.4194,
+            accuracy: Some(12.5),
+            timestamp: 1760000000,
+        };
+        assert!(valid.check().is_ok());
+
+        for accuracy in [-5.0, f64::NAN, f64::INFINITY, 50_000.0] {
+            let location = LocationData {
+                accuracy: Some(accuracy),
+                ..valid.clone()
+            };
+            assert!(location.check().is_err(), "accuracy {} accepted", accuracy);
+        }
+        let location = LocationData {
+            latitude: f64::NAN,
+            ..valid.clone()
+        };
+        assert_eq!(location.check(), Err(InvalidLocationData::Coordinates));
+
+        let response = LocationValidationResponse::failure(
+            InvalidLocationData::Accuracy.to_string(),
+            ValidationErrorCode::InvalidLocationData,
+        );
+        assert_eq!(
+            response.error_code.as_deref(),
+            Some("INVALID_LOCATION_DATA")
+        );
+        assert!(!response.success);
+    }
+
+    #[test]
+    fn test_unknown_accuracy_blocks_creation_but_not_joining() {
+        let reading = |accuracy: Option<f64>| {
+            LocationData {
+                latitude: 37.7749,
+                longitude: -122.4194,
+                accuracy,
+                timestamp: 1760000000,
+            }
+            .check()
+            .unwrap()
+            .accuracy
+        };
+
+        // -1 from iOS, 0 from Android, and nothing at all from the web
+        for accuracy in [Some(-1.0), Some(0.0), None] {
+            let unknown = reading(accuracy);
+            assert_eq!(unknown, AccuracyReading::Unknown, "{:?}", accuracy);
+            assert_eq!(FixUse::Join.check_accuracy(unknown), Ok(()));
+            assert_eq!(
+                FixUse::Create.check_accuracy(unknown),
+                Err(ValidationErrorCode::AccuracyUnknown)
+            );
+            assert_eq!(
+                FixUse::AddAnchor.check_accuracy(unknown),
+                Err(ValidationErrorCode::AccuracyTooLow {
+                    max_accuracy_meters: MAX_ANCHOR_ACCURACY_METERS
+                })
+            );
+        }
+
+        let normal = reading(Some(12.5));
+        assert_eq!(normal, AccuracyReading::Meters(12.5));
+        for fix_use in [FixUse::Join, FixUse::Create, FixUse::AddAnchor] {
+            assert_eq!(fix_use.check_accuracy(normal), Ok(()));
+        }
+        // Coarse but plausible fixes still join and create; only anchors need 20m
+        let coarse = reading(Some(150.0));
+        assert_eq!(FixUse::Join.check_accuracy(coarse), Ok(()));
+        assert_eq!(FixUse::Create.check_accuracy(coarse), Ok(()));
+        assert!(FixUse::AddAnchor.check_accuracy(coarse).is_err());
+    }
+
+    #[test]
+    fn test_requested_reply_kind() {
+        let dm = request_tags(vec![Tag::custom(
+            TagKind::Custom(REPLY_KIND_TAG.into()),
+            ["14"],
+        )]);
+        assert_eq!(requested_reply_kind(&dm), Some(Kind::PrivateDirectMessage));
+
+        let unsupported = request_tags(vec![Tag::custom(
+            TagKind::Custom(REPLY_KIND_TAG.into()),
+            ["4"],
+        )]);
+        assert_eq!(requested_reply_kind(&unsupported), None);
+        assert_eq!(requested_reply_kind(&request_tags(vec![])), None);
+    }
+
+    #[test]
+    fn test_client_tag_labels_request_metrics() {
+        let count = |client: &str, version: &str, format: &str| {
+            metrics::get(
+                "peek_client_requests_total",
+                &[("client", client), ("version", version), ("format", format)],
+            )
+        };
+
+        let tagged = ClientFingerprint::from_tags(&request_tags(vec![Tag::custom(
+            TagKind::Custom(CLIENT_TAG.into()),
+            ["peek-web-test", "1.4.2"],
+        )]));
+        assert_eq!(tagged.to_string(), "peek-web-test/1.4.2");
+        let before = count("peek-web-test", "1.4.2", "legacy");
+        tagged.record_request("legacy");
+        assert_eq!(count("peek-web-test", "1.4.2", "legacy"), before + 1);
+
+        let untagged = ClientFingerprint::from_tags(&request_tags(vec![]));
+        assert_eq!(untagged.to_string(), "unknown/unknown");
+        let before = count("unknown", "unknown", "unparseable");
+        untagged.record_request("unparseable");
+        assert_eq!(count("unknown", "unknown", "unparseable"), before + 1);
+
+        // A name without a version still identifies the app
+        let unversioned = ClientFingerprint::from_tags(&request_tags(vec![Tag::custom(
+            TagKind::Custom(CLIENT_TAG.into()),
+            ["peek-ios-test"],
+        )]));
+        assert_eq!(unversioned.to_string(), "peek-ios-test/unknown");
+    }
+
+    #[test]
+    fn test_client_tag_values_are_truncated_and_cleaned() {
+        let absurd = "x".repeat(10_000);
+        let client = ClientFingerprint::from_tags(&request_tags(vec![Tag::custom(
+            TagKind::Custom(CLIENT_TAG.into()),
+            [format!("peek\nweb\u{1b}[31m{}", absurd), absurd],
+        )]));
+        assert_eq!(client.name.chars().count(), MAX_CLIENT_FIELD_CHARS);
+        assert!(client.name.starts_with("peekweb[31mxxx"));
+        assert_eq!(client.version, "x".repeat(MAX_CLIENT_FIELD_CHARS));
+    }
+
+    #[test]
+    fn test_default_response_rumor_keeps_json_and_correlation() {
+        let recipient = Keys::generate().public_key();
+        let response = ServiceResponse::AddAnchor {
+            success: true,
+            anchor_count: Some(2),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        };
+        let json = serde_json::to_string(&response).unwrap();
+
+        let (kind, content, tags) = response_rumor(
+            &response,
+            json.clone(),
+            "request-id",
+            &recipient,
+            None,
+            Kind::Custom(27493),
+        );
+
+        assert_eq!(kind, Kind::Custom(27493));
+        assert_eq!(content, json);
+        assert_eq!(e_tag_values(&tags), vec!["request-id".to_string()]);
+        assert!(!tags
+            .iter()
+            .any(|t| t.kind() == TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::P))));
+    }
+
+    #[test]
+    fn test_dm_response_rumor_prefixes_summary_and_keeps_correlation() {
+        let recipient = Keys::generate().public_key();
+        let response = ServiceResponse::Preview(PreviewResult::failure("Community not found"));
+        let json = serde_json::to_string(&response).unwrap();
+
+        let (kind, content, tags) = response_rumor(
+            &response,
+            json.clone(),
+            "request-id",
+            &recipient,
+            Some(Kind::PrivateDirectMessage),
+            Kind::Custom(27493),
+        );
+
+        assert_eq!(kind, Kind::PrivateDirectMessage);
+        let (summary, payload) = content.split_once('\n').unwrap();
+        assert_eq!(
+            summary,
+            "Peek: Community preview failed: Community not found"
+        );
+        assert_eq!(payload, json);
+        assert_eq!(e_tag_values(&tags), vec!["request-id".to_string()]);
+        assert!(tags.contains(&Tag::public_key(recipient)));
+    }
+
+    #[test]
+    fn test_request_tags_echoed_into_a_response_are_limited_by_the_policy() {
+        let recipient = Keys::generate().public_key();
+        let bogus: Vec<Tag> = (0..200)
+            .map(|i| match i % 2 {
+                0 => Tag::parse([format!("client-{}", i), "x".repeat(5000)]).unwrap(),
+                _ => Tag::parse(["e".to_string(), "a".repeat(5000)]).unwrap(),
+            })
+            .collect();
+        let request = request_tags(bogus);
+        let response = ServiceResponse::Preview(PreviewResult::failure("Community not found"));
+        let json = serde_json::to_string(&response).unwrap();
+
+        let (_, _, tags) = response_rumor(
+            &response,
+            json,
+            "request-id",
+            &recipient,
+            Some(Kind::PrivateDirectMessage),
+            Kind::Custom(27493),
+        );
+        let sent = response_tags(tags.into_iter().chain(request.iter().cloned()));
+
+        assert_eq!(sent.len(), MAX_RESPONSE_TAGS);
+        assert_eq!(e_tag_values(&sent[..1]), vec!["request-id".to_string()]);
+        assert_eq!(sent[1], Tag::public_key(recipient));
+        assert!(sent.iter().all(|tag| {
+            let values = tag.as_slice();
+            ECHOABLE_TAG_KINDS.contains(&values[0].as_str())
+                && values
+                    .iter()
+                    .all(|v| v.chars().count() <= MAX_RESPONSE_TAG_VALUE_CHARS)
+        }));
+    }
+
+    const REQUEST_KIND: Kind = Kind::Custom(27492);
+    const NOW: u64 = 1_760_000_000;
+
+    #[test]
+    fn test_expired_outer_gift_wrap_is_dropped() {
+        let gift_wrap = EventBuilder::new(Kind::GiftWrap, "")
+            .tags([Tag::expiration(Timestamp::from(NOW - 1))])
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+        assert!(is_expired_at(&gift_wrap.tags, Timestamp::from(NOW)));
+    }
+
+    #[test]
+    fn test_expired_inner_rumor_is_dropped() {
+        let tags = request_tags(vec![Tag::expiration(Timestamp::from(NOW))]);
+        assert!(is_expired_at(&tags, Timestamp::from(NOW)));
+    }
+
+    #[test]
+    fn test_unexpired_or_unmarked_requests_pass_through() {
+        let future = request_tags(vec![Tag::expiration(Timestamp::from(NOW + 60))]);
+        assert!(!is_expired_at(&future, Timestamp::from(NOW)));
+        assert_eq!(expiration_of(&future), Some(Timestamp::from(NOW + 60)));
+
+        let unmarked = request_tags(vec![]);
+        assert!(!is_expired_at(&unmarked, Timestamp::from(NOW)));
+        assert_eq!(expiration_of(&unmarked), None);
+    }
+
+    #[test]
+    fn test_response_expiration_is_capped() {
+        let now = Timestamp::from(NOW);
+        let week = 7 * 24 * 60 * 60;
+
+        // No request: our own limit
+        assert_eq!(response_expiration(None, now, week), now + week);
+        // A shorter requested expiration is honored
+        let soon = Timestamp::from(NOW + 300);
+        assert_eq!(response_expiration(Some(soon), now, week), soon);
+        // A longer one is capped
+        let later = Timestamp::from(NOW + 2 * week);
+        assert_eq!(response_expiration(Some(later), now, week), now + week);
+    }
+
+    fn rumor(kind: Kind, created_at: u64, tags: Vec<Tag>) -> (serde_json::Value, UnsignedEvent) {
+        let mut rumor = EventBuilder::new(kind, "{}")
+            .tags(tags)
+            .custom_created_at(Timestamp::from(created_at))
+            .build(Keys::generate().public_key());
+        rumor.ensure_id();
+        (serde_json::to_value(&rumor).unwrap(), rumor)
+    }
+
+    fn verify(
+        raw: &serde_json::Value,
+        rumor: &UnsignedEvent,
+        service: &PublicKey,
+    ) -> Result<(), RumorRejection> {
+        verify_rumor(raw, rumor, REQUEST_KIND, service, Timestamp::from(NOW))
+    }
+
+    #[test]
+    fn test_verify_rumor_accepts_well_formed_request() {
+        let service = Keys::generate().public_key();
+        let (raw, rumor) = rumor(REQUEST_KIND, NOW - 5, vec![Tag::public_key(service)]);
+        assert_eq!(verify(&raw, &rumor, &service), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_rumor_rejects_unexpected_kind() {
+        let service = Keys::generate().public_key();
+        let (raw, rumor) = rumor(Kind::TextNote, NOW, vec![]);
+        assert_eq!(
+            verify(&raw, &rumor, &service),
+            Err(RumorRejection::UnexpectedKind)
+        );
+    }
+
+    #[test]
+    fn test_verify_rumor_rejects_signed_rumor() {
+        let service = Keys::generate().public_key();
+        let (mut raw, rumor) = rumor(REQUEST_KIND, NOW, vec![]);
+        raw["sig"] = serde_json::Value::String("00".repeat(64));
+        assert_eq!(verify(&raw, &rumor, &service), Err(RumorRejection::Signed));
+    }
+
+    #[test]
+    fn test_verify_rumor_rejects_mismatched_id() {
+        let service = Keys::generate().public_key();
+        let (raw, mut spliced) = rumor(REQUEST_KIND, NOW, vec![]);
+        let (_, other) = rumor(REQUEST_KIND, NOW - 60, vec![]);
+        spliced.id = other.id;
+        assert_eq!(
+            verify(&raw, &spliced, &service),
+            Err(RumorRejection::IdMismatch)
+        );
+    }
+
+    #[test]
+    fn test_verify_rumor_rejects_stale_or_future_created_at() {
+        let service = Keys::generate().public_key();
+        let clock = ManualClock::new(NOW);
+        let (raw, rumor) = rumor(REQUEST_KIND, NOW, vec![]);
+        let check =
+            |clock: &ManualClock| verify_rumor(&raw, &rumor, REQUEST_KIND, &service, clock.now());
+
+        assert_eq!(check(&clock), Ok(()));
+
+        // Replayed a day later
+        clock.advance(MAX_RUMOR_AGE_SECS + 1);
+        assert_eq!(check(&clock), Err(RumorRejection::CreatedAtOutOfRange));
+
+        // Dated further ahead than the allowed clock skew
+        clock.set(NOW - MAX_RUMOR_FUTURE_SECS - 1);
+        assert_eq!(check(&clock), Err(RumorRejection::CreatedAtOutOfRange));
+        clock.advance(1);
+        assert_eq!(check(&clock), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_rumor_rejects_foreign_p_tag() {
+        let service = Keys::generate().public_key();
+        let someone_else = Keys::generate().public_key();
+        let (raw, rumor) = rumor(REQUEST_KIND, NOW, vec![Tag::public_key(someone_else)]);
+        assert_eq!(
+            verify(&raw, &rumor, &service),
+            Err(RumorRejection::ForeignRecipient)
+        );
+    }
+
+    /// A request from `client` sealed and wrapped for `service` with the given schemes
+    fn wrap_with(client: &Keys, service: &Keys, wrap: WrapScheme, seal: WrapScheme) -> Event {
+        fn encrypt(scheme: WrapScheme, from: &Keys, to: &Keys, plaintext: String) -> String {
+            match scheme {
+                WrapScheme::Nip44 => nip44::encrypt(
+                    from.secret_key(),
+                    &to.public_key(),
+                    plaintext,
+                    nip44::Version::V2,
+                )
+                .unwrap(),
+                WrapScheme::Nip04 => {
+                    nip04::encrypt(from.secret_key(), &to.public_key(), plaintext).unwrap()
+                }
+            }
+        }
+
+        let mut rumor = EventBuilder::new(REQUEST_KIND, "{}").build(client.public_key());
+        rumor.ensure_id();
+        let seal = EventBuilder::new(Kind::Seal, encrypt(seal, client, service, rumor.as_json()))
+            .sign_with_keys(client)
+            .unwrap();
+        let ephemeral = Keys::generate();
+        EventBuilder::new(
+            Kind::GiftWrap,
+            encrypt(wrap, &ephemeral, service, seal.as_json()),
+        )
+        .tag(Tag::public_key(service.public_key()))
+        .sign_with_keys(&ephemeral)
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_gift_wraps_open_with_each_supported_scheme() {
+        let client = Keys::generate();
+        let service = Keys::generate();
+
+        let mut rumor = EventBuilder::new(REQUEST_KIND, "{}").build(client.public_key());
+        rumor.ensure_id();
+        let current = EventBuilder::gift_wrap(&client, &service.public_key(), rumor.clone(), [])
+            .await
+            .unwrap();
+        let unwrapped = unwrap_gift_wrap(&service, &current).unwrap();
+        assert_eq!(unwrapped.scheme, WrapScheme::Nip44);
+        assert_eq!(unwrapped.rumor.id, rumor.id);
+
+        for (wrap, seal, expected) in [
+            (WrapScheme::Nip44, WrapScheme::Nip44, WrapScheme::Nip44),
+            (WrapScheme::Nip04, WrapScheme::Nip04, WrapScheme::Nip04),
+            (WrapScheme::Nip44, WrapScheme::Nip04, WrapScheme::Nip04),
+            (WrapScheme::Nip04, WrapScheme::Nip44, WrapScheme::Nip04),
+        ] {
+            let gift_wrap = wrap_with(&client, &service, wrap, seal);
+            let unwrapped = unwrap_gift_wrap(&service, &gift_wrap).unwrap();
+            assert_eq!(
+                unwrapped.scheme, expected,
+                "{:?} wrap, {:?} seal",
+                wrap, seal
+            );
+            assert_eq!(unwrapped.sender, client.public_key());
+            assert_eq!(unwrapped.rumor.pubkey, client.public_key());
+        }
+    }
+
+    #[test]
+    fn test_rumor_claiming_another_sender_is_dropped() {
+        let attacker = Keys::generate();
+        let victim = Keys::generate();
+        let service = Keys::generate();
+
+        // Sealed by the attacker, but the unsigned rumor names the victim as its author
+        let mut rumor = EventBuilder::new(REQUEST_KIND, "{}").build(victim.public_key());
+        rumor.ensure_id();
+        let encrypt = |from: &Keys, plaintext: String| {
+            nip44::encrypt(
+                from.secret_key(),
+                &service.public_key(),
+                plaintext,
+                nip44::Version::V2,
+            )
+            .unwrap()
+        };
+        let seal = EventBuilder::new(Kind::Seal, encrypt(&attacker, rumor.as_json()))
+            .sign_with_keys(&attacker)
+            .unwrap();
+        let ephemeral = Keys::generate();
+        let gift_wrap = EventBuilder::new(Kind::GiftWrap, encrypt(&ephemeral, seal.as_json()))
+            .tag(Tag::public_key(service.public_key()))
+            .sign_with_keys(&ephemeral)
+            .unwrap();
+
+        let rejected = unwrap_gift_wrap(&service, &gift_wrap);
+        assert!(matches!(
+            rejected,
+            Err(UnwrapError::Rejected(RumorRejection::SenderMismatch))
+        ));
+        assert_eq!(RumorRejection::SenderMismatch.reason(), "sender_mismatch");
+    }
+
+    #[test]
+    fn test_undecryptable_gift_wraps_name_the_layer() {
+        let client = Keys::generate();
+        let service = Keys::generate();
+        let ephemeral = Keys::generate();
+        let garbage = |content: &str| {
+            EventBuilder::new(Kind::GiftWrap, content)
+                .tag(Tag::public_key(service.public_key()))
+                .sign_with_keys(&ephemeral)
+                .unwrap()
+        };
+        for content in [
+            "not a payload",
+            "bm90IGNpcGhlcnRleHQ=?iv=AAAAAAAAAAAAAAAAAAAAAA==",
+        ] {
+            assert!(matches!(
+                unwrap_gift_wrap(&service, &garbage(content)),
+                Err(UnwrapError::Undecryptable("gift wrap"))
+            ));
+        }
+
+        // Addressed to someone else: no scheme opens it with the service key
+        let elsewhere = wrap_with(
+            &client,
+            &Keys::generate(),
+            WrapScheme::Nip04,
+            WrapScheme::Nip04,
+        );
+        assert!(matches!(
+            unwrap_gift_wrap(&service, &elsewhere),
+            Err(UnwrapError::Undecryptable("gift wrap"))
+        ));
     }
 }
@@ -0,0 +1,127 @@
+//! JSON Schema of the gift-wrapped request/response payloads, for clients to generate types from
+//!
+//! Derived from ServiceRequest and ServiceResponse themselves, so it cannot drift from what the
+//! service parses and sends. It carries the protocol version so clients can pin against it; CI
+//! writes it with `validation-service schema` and diffs it against schema/protocol.json.
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use utoipa::OpenApi;
+
+use super::api_docs::{route_all, RouteTable};
+use super::nostr_validation::{ServiceRequest, ServiceResponse};
+use super::service_info::PROTOCOL_VERSION;
+
+/// The schema document: ServiceRequest and ServiceResponse plus every type they refer to
+pub fn protocol_schema() -> Value {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let request = gen.subschema_for::<ServiceRequest>();
+    let response = gen.subschema_for::<ServiceResponse>();
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Peek gift wrap protocol",
+        "description": "Payloads of gift-wrapped requests to the validation service and its replies",
+        "protocol_version": PROTOCOL_VERSION,
+        "oneOf": [request, response],
+        "definitions": gen.definitions(),
+    })
+}
+
+/// Pretty-printed schema with a trailing newline, as served and as committed
+pub fn protocol_schema_text() -> String {
+    let mut text = serde_json::to_string_pretty(&protocol_schema()).expect("schema serializes");
+    text.push('\n');
+    text
+}
+
+/// Routes for GET /api/schema
+pub fn router() -> Router {
+    route_all(routes()).with_state(Arc::new(protocol_schema_text()))
+}
+
+/// The schema route before the schema text is attached
+pub(crate) fn routes() -> RouteTable<Arc<String>> {
+    vec![("/api/schema", get(schema))]
+}
+
+/// OpenAPI description of the schema route
+#[derive(OpenApi)]
+#[openapi(paths(schema))]
+pub struct SchemaApi;
+
+/// JSON Schema of the gift-wrapped request and response payloads
+#[utoipa::path(
+    get,
+    path = "/api/schema",
+    tag = "service",
+    operation_id = "protocol_schema",
+    responses((status = 200, description = "Draft-07 JSON Schema", content_type = "application/schema+json", body = Object))
+)]
+async fn schema(State(text): State<Arc<String>>) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "application/schema+json"),
+            (header::CACHE_CONTROL, "public, max-age=300"),
+        ],
+        text.as_ref().clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum_test::TestServer;
+
+    #[test]
+    fn test_schema_is_versioned_and_names_both_directions() {
+        let schema = protocol_schema();
+        assert_eq!(schema["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(
+            schema["oneOf"],
+            json!([
+                { "$ref": "#/definitions/ServiceRequest" },
+                { "$ref": "#/definitions/ServiceResponse" },
+            ])
+        );
+        for name in [
+            "LocationData",
+            "PreviewResult",
+            "QuietHours",
+            "CommunityExport",
+        ] {
+            assert!(
+                schema["definitions"].get(name).is_some(),
+                "{} is not defined",
+                name
+            );
+        }
+        // The wire form, not the parsed one
+        assert_eq!(
+            schema["definitions"]["QuietHours"]["properties"]["start"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn test_committed_snapshot_is_current() {
+        // Same check as CI's regenerate-and-diff step, so a stale snapshot fails locally too
+        assert!(
+            protocol_schema_text() == include_str!("../../schema/protocol.json"),
+            "schema/protocol.json is stale; run `cargo run --bin validation-service -- schema schema/protocol.json`"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_schema_is_served() {
+        let server = TestServer::new(router()).unwrap();
+        let response = server.get("/api/schema").await;
+        response.assert_status_ok();
+        assert_eq!(
+            response.header(header::CONTENT_TYPE),
+            "application/schema+json"
+        );
+        assert_eq!(response.json::<Value>(), protocol_schema());
+    }
+}
@@ -0,0 +1,377 @@
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Json, Router};
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa::{OpenApi, ToSchema};
+
+use super::api_docs::{route_all, RouteTable};
+use super::nostr_validation::{
+    listening_relays, ANCHOR_GEOHASH_PRECISION, MAX_ANCHOR_ACCURACY_METERS, SUPPORTED_REQUEST_TYPES,
+};
+use crate::config::Config;
+use crate::libraries::relay_url::RelayUrl;
+
+// Bump when request/response payloads change incompatibly
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Parameterized replaceable kind for application handler style descriptors
+const SERVICE_DESCRIPTOR_KIND: u16 = 31990;
+
+// d tag identifying the descriptor among the service key's replaceable events
+const SERVICE_DESCRIPTOR_D_TAG: &str = "peek.service";
+
+/// Machine-readable description of this deployment, published as a Nostr event and over HTTP
+/// Both are generated from this struct so they can never disagree
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ServiceDescriptor {
+    pub service_pubkey: String,
+    pub service_npub: String,
+    pub protocol_version: u32,
+    pub request_kind: u16,
+    pub response_kind: u16,
+    pub reply_kinds: Vec<u16>,
+    pub request_types: Vec<String>,
+    pub relay_url: String,
+    pub inbox_relays: Vec<String>,
+    pub anchor_geohash_precision: usize,
+    pub matches_neighbor_cells: bool,
+    pub max_anchor_accuracy_meters: f64,
+    pub max_anchors: usize,
+    // Sticker signatures are not verified by this service
+    pub signed_stickers_required: bool,
+    // Joins that add a member carry auth_required, true when the client must NIP-42 AUTH to the
+    // relay before subscribing, and auth_scope saying whether the group or the relay asks for it
+    pub reports_auth_required: bool,
+}
+
+impl ServiceDescriptor {
+    pub fn from_config(config: &Config, service_pubkey: &PublicKey) -> Self {
+        Self {
+            service_pubkey: service_pubkey.to_hex(),
+            service_npub: service_pubkey
+                .to_bech32()
+                .expect("public keys always encode as npub"),
+            protocol_version: PROTOCOL_VERSION,
+            request_kind: config.protocol.request_kind,
+            response_kind: config.protocol.response_kind,
+            reply_kinds: vec![
+                config.protocol.response_kind,
+                Kind::PrivateDirectMessage.as_u16(),
+            ],
+            request_types: SUPPORTED_REQUEST_TYPES
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+            relay_url: config.public_relay_url.to_string(),
+            inbox_relays: listening_relays(config)
+                .iter()
+                .map(RelayUrl::to_string)
+                .collect(),
+            anchor_geohash_precision: ANCHOR_GEOHASH_PRECISION,
+            matches_neighbor_cells: true,
+            max_anchor_accuracy_meters: MAX_ANCHOR_ACCURACY_METERS,
+            max_anchors: config.max_anchors,
+            signed_stickers_required: false,
+            reports_auth_required: true,
+        }
+    }
+
+    /// Replaceable descriptor event, to be signed with the service key
+    pub fn to_event_builder(&self) -> EventBuilder {
+        let content = serde_json::to_string(self).expect("descriptor serializes");
+        EventBuilder::new(Kind::from(SERVICE_DESCRIPTOR_KIND), content).tags([
+            Tag::identifier(SERVICE_DESCRIPTOR_D_TAG),
+            Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K)),
+                [self.request_kind.to_string()],
+            ),
+        ])
+    }
+}
+
+/// NIP-89 handler kind, announcing which kinds and links an app can open
+const APP_HANDLER_KIND: u16 = 31990;
+
+/// NIP-89 recommendation kind, pointing clients at a handler for one kind
+const APP_RECOMMENDATION_KIND: u16 = 31989;
+
+// d tag of the handler event, next to the service descriptor under the same key
+const APP_HANDLER_D_TAG: &str = "peek.app";
+
+// NIP-29 group metadata, the event other clients meet a Peek group through
+const GROUP_METADATA_KIND: u16 = 39000;
+
+/// NIP-89 application handler advertising the web app for Peek groups and community links
+///
+/// Built from the same config as the service descriptor, so the kinds it claims are the ones
+/// this deployment actually answers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppHandler {
+    pub app_url: String,
+    pub relay_url: String,
+    pub kinds: Vec<u16>,
+    // Community links carry a UUID in this namespace rather than a NIP-19 entity
+    pub uuid_namespace: String,
+}
+
+impl AppHandler {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            app_url: config.app_url.trim_end_matches('/').to_string(),
+            relay_url: config.public_relay_url.to_string(),
+            kinds: vec![
+                GROUP_METADATA_KIND,
+                config.protocol.request_kind,
+                config.protocol.response_kind,
+            ],
+            uuid_namespace: config.protocol.uuid_namespace.clone(),
+        }
+    }
+
+    /// Link template for a group's kind 39000 metadata, encoded as an naddr
+    pub fn group_url_template(&self) -> String {
+        format!("{}/<bech32>", self.app_url)
+    }
+
+    /// Link template for a community UUID, as printed on stickers
+    pub fn community_url_template(&self) -> String {
+        format!("{}/c/<uuid>", self.app_url)
+    }
+
+    /// Replaceable handler event, to be signed with the service key
+    pub fn to_event_builder(&self) -> EventBuilder {
+        let content = serde_json::json!({
+            "name": "Peek",
+            "about": "Location-based communities you join by being there",
+            "website": self.app_url,
+        })
+        .to_string();
+        let k = TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K));
+        let mut tags = vec![Tag::identifier(APP_HANDLER_D_TAG)];
+        tags.extend(
+            self.kinds
+                .iter()
+                .map(|kind| Tag::custom(k.clone(), [kind.to_string()])),
+        );
+        tags.push(Tag::custom(
+            TagKind::Custom("web".into()),
+            [self.group_url_template(), "naddr".to_string()],
+        ));
+        tags.push(Tag::custom(
+            TagKind::Custom("web".into()),
+            [self.community_url_template(), self.uuid_namespace.clone()],
+        ));
+        EventBuilder::new(Kind::from(APP_HANDLER_KIND), content).tags(tags)
+    }
+
+    /// The service key's recommendation of this handler for group metadata events
+    pub fn recommendation_builder(&self, service_pubkey: &PublicKey) -> EventBuilder {
+        let handler = format!(
+            "{}:{}:{}",
+            APP_HANDLER_KIND,
+            service_pubkey.to_hex(),
+            APP_HANDLER_D_TAG
+        );
+        EventBuilder::new(Kind::from(APP_RECOMMENDATION_KIND), "").tags([
+            Tag::identifier(GROUP_METADATA_KIND.to_string()),
+            Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::A)),
+                [handler, self.relay_url.clone(), "web".to_string()],
+            ),
+        ])
+    }
+
+    /// Filter for the handler event currently published under the service key
+    pub fn published_filter(service_pubkey: &PublicKey) -> Filter {
+        Filter::new()
+            .kind(Kind::from(APP_HANDLER_KIND))
+            .author(*service_pubkey)
+            .identifier(APP_HANDLER_D_TAG)
+    }
+
+    /// Whether a published handler event already says what this config would publish
+    pub fn is_current(&self, published: &Event) -> bool {
+        let wanted = self.to_event_builder().build(published.pubkey);
+        let tags = |tags: &Tags| -> Vec<Vec<String>> {
+            tags.iter().map(|tag| tag.clone().to_vec()).collect()
+        };
+        published.content == wanted.content && tags(&published.tags) == tags(&wanted.tags)
+    }
+}
+
+/// Routes serving the descriptor: GET /api/service-info, and the paths clients look up the
+/// service key at (/.well-known/peek.json, /api/service-pubkey) so rotating it needs no deploy
+pub fn router(descriptor: Arc<ServiceDescriptor>) -> Router {
+    route_all(routes()).with_state(descriptor)
+}
+
+/// The descriptor routes before their state is attached
+pub(crate) fn routes() -> RouteTable<Arc<ServiceDescriptor>> {
+    vec![
+        ("/api/service-info", get(service_info)),
+        ("/api/service-pubkey", get(service_info)),
+        ("/.well-known/peek.json", get(service_info)),
+    ]
+}
+
+/// OpenAPI description of the descriptor routes; the other two paths are added as aliases by
+/// api_docs
+#[derive(OpenApi)]
+#[openapi(paths(service_info))]
+pub struct ServiceInfoApi;
+
+/// What this deployment supports and the key to send gift-wrapped requests to
+#[utoipa::path(
+    get,
+    path = "/api/service-info",
+    tag = "service",
+    responses((status = 200, description = "Service descriptor", body = ServiceDescriptor))
+)]
+async fn service_info(State(descriptor): State<Arc<ServiceDescriptor>>) -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(descriptor.as_ref().clone()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::secret::SecretString;
+    use axum_test::TestServer;
+
+    fn config() -> Config {
+        Config {
+            public_relay_url: RelayUrl::parse("wss://peek.hol.is/").unwrap(),
+            max_anchors: 3,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_descriptor_reflects_config() {
+        let pubkey = Keys::generate().public_key();
+        let descriptor = ServiceDescriptor::from_config(&config(), &pubkey);
+
+        assert_eq!(descriptor.service_pubkey, pubkey.to_hex());
+        assert_eq!(descriptor.relay_url, "wss://peek.hol.is");
+        assert_eq!(descriptor.max_anchors, 3);
+        assert_eq!(descriptor.request_kind, 27492);
+        assert_eq!(descriptor.reply_kinds, vec![27493, 14]);
+        assert_eq!(descriptor.request_types, SUPPORTED_REQUEST_TYPES);
+        assert!(descriptor.reports_auth_required);
+    }
+
+    #[tokio::test]
+    async fn test_event_content_matches_http_mirror() {
+        let keys = Keys::generate();
+        let descriptor = Arc::new(ServiceDescriptor::from_config(
+            &config(),
+            &keys.public_key(),
+        ));
+
+        let event = descriptor.to_event_builder().sign_with_keys(&keys).unwrap();
+        assert_eq!(event.kind, Kind::from(SERVICE_DESCRIPTOR_KIND));
+        assert_eq!(event.tags.identifier(), Some(SERVICE_DESCRIPTOR_D_TAG));
+
+        let server = TestServer::new(router(descriptor)).unwrap();
+        let response = server.get("/api/service-info").await;
+        response.assert_status_ok();
+
+        let from_event: serde_json::Value = serde_json::from_str(&event.content).unwrap();
+        let from_http: serde_json::Value = response.json();
+        assert_eq!(from_event, from_http);
+    }
+
+    fn tag_values(event: &Event, name: &str) -> Vec<Vec<String>> {
+        event
+            .tags
+            .iter()
+            .map(|tag| tag.clone().to_vec())
+            .filter(|tag| tag[0] == name)
+            .map(|tag| tag[1..].to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_app_handler_advertises_group_and_community_links() {
+        let keys = Keys::generate();
+        let config = Config {
+            app_url: "https://peek.example/".to_string(),
+            ..config()
+        };
+        let handler = AppHandler::from_config(&config);
+
+        let event = handler.to_event_builder().sign_with_keys(&keys).unwrap();
+        assert_eq!(event.kind, Kind::from(APP_HANDLER_KIND));
+        assert_eq!(event.tags.identifier(), Some(APP_HANDLER_D_TAG));
+        assert_eq!(
+            tag_values(&event, "k"),
+            vec![vec!["39000"], vec!["27492"], vec!["27493"]]
+        );
+        assert_eq!(
+            tag_values(&event, "web"),
+            vec![
+                vec!["https://peek.example/<bech32>", "naddr"],
+                vec!["https://peek.example/c/<uuid>", "peek:uuid"],
+            ]
+        );
+        let content: serde_json::Value = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content["website"], "https://peek.example");
+        assert!(handler.is_current(&event));
+
+        // A changed namespace is a different handler and gets republished
+        let mut renamed = config.clone();
+        renamed.protocol.request_kind = 27592;
+        assert!(!AppHandler::from_config(&renamed).is_current(&event));
+
+        let recommendation = handler
+            .recommendation_builder(&keys.public_key())
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert_eq!(recommendation.kind, Kind::from(APP_RECOMMENDATION_KIND));
+        assert_eq!(recommendation.tags.identifier(), Some("39000"));
+        assert_eq!(
+            tag_values(&recommendation, "a"),
+            vec![vec![
+                format!("31990:{}:peek.app", keys.public_key().to_hex()),
+                "wss://peek.hol.is".to_string(),
+                "web".to_string(),
+            ]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_well_known_document_names_the_handler_service_key() {
+        let service = Keys::generate();
+        let mut config = Config {
+            service_secret_key: SecretString::from(service.secret_key().to_secret_hex().as_str()),
+            relay_secret_key: SecretString::from(
+                Keys::generate().secret_key().to_secret_hex().as_str(),
+            ),
+            ..config()
+        };
+        // The same parsed keys main hands to NostrValidationHandler
+        let keys = config.take_keys().unwrap();
+        let descriptor = ServiceDescriptor::from_config(&config, &keys.service.public_key());
+
+        // Nothing but the config-derived descriptor is needed to serve it
+        let server = TestServer::new(router(Arc::new(descriptor))).unwrap();
+        for path in ["/.well-known/peek.json", "/api/service-pubkey"] {
+            let response = server.get(path).await;
+            response.assert_status_ok();
+            assert_eq!(
+                response.header(header::CACHE_CONTROL),
+                "public, max-age=300"
+            );
+
+            let document: serde_json::Value = response.json();
+            let npub = document["service_npub"].as_str().unwrap();
+            assert_eq!(PublicKey::parse(npub).unwrap(), service.public_key());
+            assert_eq!(document["service_pubkey"], service.public_key().to_hex());
+            assert_eq!(document["relay_url"], "wss://peek.hol.is");
+            assert_eq!(document["protocol_version"], PROTOCOL_VERSION);
+            assert!(document["inbox_relays"].is_array());
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod fake_relay;
 pub mod libraries;
 pub mod models;
 pub mod services;
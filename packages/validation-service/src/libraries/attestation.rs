@@ -0,0 +1,123 @@
+use nostr_sdk::hashes::{sha256, Hash};
+use nostr_sdk::secp256k1::{schnorr::Signature, Message, Secp256k1, XOnlyPublicKey};
+use nostr_sdk::{Keys, PublicKey};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What the service vouches for: `pubkey` was granted membership of `group_id`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationClaims {
+    pub community_id: String,
+    pub pubkey: String,
+    pub group_id: String,
+    pub granted_at: u64,
+}
+
+/// Membership grant a client can store and verify offline against the service pubkey
+///
+/// `payload` is the compact JSON of the claims exactly as signed, and `sig` the hex Schnorr
+/// signature over its SHA-256 by the service key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Attestation {
+    pub payload: String,
+    pub sig: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidAttestation {
+    #[error("attestation signature is malformed")]
+    MalformedSignature,
+    #[error("attestation signature does not match the service key")]
+    BadSignature,
+    #[error("attestation payload is not valid claims")]
+    MalformedPayload,
+}
+
+impl Attestation {
+    /// Sign `claims` with the service key
+    pub fn sign(claims: &AttestationClaims, service_keys: &Keys) -> Self {
+        let payload = serde_json::to_string(claims).expect("claims serialize");
+        let sig = service_keys.sign_schnorr(&digest(&payload));
+        Self {
+            payload,
+            sig: sig.to_string(),
+        }
+    }
+
+    /// The signed claims, if `service_pubkey` signed exactly this payload
+    /// For clients holding an attestation; the service itself only signs
+    #[allow(dead_code)]
+    pub fn verify(
+        &self,
+        service_pubkey: &PublicKey,
+    ) -> Result<AttestationClaims, InvalidAttestation> {
+        let sig =
+            Signature::from_str(&self.sig).map_err(|_| InvalidAttestation::MalformedSignature)?;
+        let signer = XOnlyPublicKey::from_slice(&service_pubkey.to_bytes())
+            .map_err(|_| InvalidAttestation::BadSignature)?;
+        Secp256k1::verification_only()
+            .verify_schnorr(&sig, &digest(&self.payload), &signer)
+            .map_err(|_| InvalidAttestation::BadSignature)?;
+        serde_json::from_str(&self.payload).map_err(|_| InvalidAttestation::MalformedPayload)
+    }
+}
+
+fn digest(payload: &str) -> Message {
+    Message::from_digest(sha256::Hash::hash(payload.as_bytes()).to_byte_array())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims() -> AttestationClaims {
+        AttestationClaims {
+            community_id: "3a7e5c59-c0a1-4876-acf1-56189b86aa0d".to_string(),
+            pubkey: Keys::generate().public_key().to_hex(),
+            group_id: "peek-k3q9x2".to_string(),
+            granted_at: 1_760_000_000,
+        }
+    }
+
+    #[test]
+    fn test_attestation_round_trips_with_the_service_key_only() {
+        let service = Keys::generate();
+        let claims = claims();
+        let attestation = Attestation::sign(&claims, &service);
+
+        assert!(attestation.payload.starts_with(r#"{"community_id":"#));
+        assert_eq!(attestation.verify(&service.public_key()), Ok(claims));
+        assert_eq!(
+            attestation.verify(&Keys::generate().public_key()),
+            Err(InvalidAttestation::BadSignature)
+        );
+    }
+
+    #[test]
+    fn test_tampered_attestation_is_rejected() {
+        let service = Keys::generate();
+        let attestation = Attestation::sign(&claims(), &service);
+
+        let mut tampered_claims: AttestationClaims =
+            serde_json::from_str(&attestation.payload).unwrap();
+        tampered_claims.group_id = "peek-elsewhere".to_string();
+        let tampered = Attestation {
+            payload: serde_json::to_string(&tampered_claims).unwrap(),
+            ..attestation.clone()
+        };
+        assert_eq!(
+            tampered.verify(&service.public_key()),
+            Err(InvalidAttestation::BadSignature)
+        );
+
+        let garbled = Attestation {
+            sig: "not-a-signature".to_string(),
+            ..attestation
+        };
+        assert_eq!(
+            garbled.verify(&service.public_key()),
+            Err(InvalidAttestation::MalformedSignature)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use nostr_sdk::Timestamp;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Source of the current time, injected so time-dependent checks can be tested without sleeping
+pub trait Clock: Send + Sync {
+    /// Seconds since the unix epoch
+    fn now_unix(&self) -> u64;
+
+    fn now(&self) -> Timestamp {
+        Timestamp::from(self.now_unix())
+    }
+}
+
+/// The real wall clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix(&self) -> u64 {
+        Timestamp::now().as_u64()
+    }
+}
+
+/// When a request's time budget runs out, read from the injected clock
+/// Relay calls check it before starting, so an overrun request stops instead of waiting more
+#[derive(Clone)]
+pub struct Deadline {
+    clock: Arc<dyn Clock>,
+    expires_at: u64,
+}
+
+impl Deadline {
+    /// A budget of `secs` starting now
+    pub fn after(clock: Arc<dyn Clock>, secs: u64) -> Self {
+        let expires_at = clock.now_unix().saturating_add(secs);
+        Self { clock, expires_at }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.clock.now_unix() >= self.expires_at
+    }
+
+    /// Time left, for bounding a call already underway
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs(self.expires_at.saturating_sub(self.clock.now_unix()))
+    }
+}
@@ -0,0 +1,298 @@
+use nostr_sdk::PublicKey;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use uuid::{Uuid, Version};
+
+use crate::services::metrics;
+
+/// Why a community ID from a request was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidCommunityId {
+    #[error("not a UUID")]
+    Malformed,
+    #[error("the nil UUID is reserved")]
+    Nil,
+    #[error("community IDs must be random (v4) UUIDs")]
+    NotV4,
+}
+
+impl InvalidCommunityId {
+    /// Label value for peek_invalid_community_ids_total
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::Malformed => "malformed",
+            Self::Nil => "nil",
+            Self::NotV4 => "not_v4",
+        }
+    }
+}
+
+/// Which community UUIDs requests may carry
+///
+/// Stickers always carry random v4 UUIDs, so anything else (sequential or time-based IDs,
+/// the nil UUID) is refused by default. `allow_any` keeps any well-formed UUID working for
+/// legacy stickers printed before the check existed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommunityIdPolicy {
+    pub allow_any: bool,
+}
+
+impl CommunityIdPolicy {
+    pub fn new(allow_any: bool) -> Self {
+        Self { allow_any }
+    }
+
+    /// Parse a community ID from a request, counting rejects by reason
+    pub fn parse(&self, community_id: &str) -> Result<Uuid, InvalidCommunityId> {
+        let result = self.check(community_id);
+        if let Err(reason) = result {
+            metrics::increment(
+                "peek_invalid_community_ids_total",
+                &[("reason", reason.reason())],
+            );
+        }
+        result
+    }
+
+    fn check(&self, community_id: &str) -> Result<Uuid, InvalidCommunityId> {
+        let uuid = Uuid::parse_str(community_id).map_err(|_| InvalidCommunityId::Malformed)?;
+        if self.allow_any {
+            return Ok(uuid);
+        }
+        if uuid.is_nil() {
+            return Err(InvalidCommunityId::Nil);
+        }
+        if uuid.get_version() != Some(Version::Random) {
+            return Err(InvalidCommunityId::NotV4);
+        }
+        Ok(uuid)
+    }
+
+    /// Parse a request's community ID, which may also be a slug; UUIDs are tried first
+    pub fn parse_ref(&self, community_id: &str) -> Result<CommunityRef, InvalidCommunityId> {
+        if let (Err(InvalidCommunityId::Malformed), Ok(slug)) =
+            (self.check(community_id), CommunitySlug::parse(community_id))
+        {
+            return Ok(CommunityRef::Slug(slug));
+        }
+        self.parse(community_id).map(CommunityRef::Id)
+    }
+}
+
+/// Shortest community slug, in characters
+pub const MIN_SLUG_CHARS: usize = 3;
+
+/// Longest community slug, in characters
+pub const MAX_SLUG_CHARS: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("slugs are {MIN_SLUG_CHARS} to {MAX_SLUG_CHARS} lowercase letters, digits and hyphens")]
+pub struct InvalidSlug;
+
+/// Admin-chosen alias for a community, for shareable URLs like /c/blue-bottle-mission
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommunitySlug(String);
+
+impl CommunitySlug {
+    pub fn parse(slug: &str) -> Result<Self, InvalidSlug> {
+        let allowed = slug
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+        // A slug that parses as a UUID would be shadowed by that community
+        if !allowed
+            || !(MIN_SLUG_CHARS..=MAX_SLUG_CHARS).contains(&slug.len())
+            || Uuid::parse_str(slug).is_ok()
+        {
+            return Err(InvalidSlug);
+        }
+        Ok(Self(slug.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// What a request's community ID names: the community UUID itself, or a slug to look up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommunityRef {
+    Id(Uuid),
+    Slug(CommunitySlug),
+}
+
+/// Window over which a requester's previews of unknown communities are counted (seconds)
+pub const UNKNOWN_ID_WINDOW_SECS: u64 = 60;
+
+// Requesters tracked before idle entries are swept
+const MAX_TRACKED_REQUESTERS: usize = 10_000;
+
+/// Per-pubkey limit on previews of communities that don't exist
+///
+/// Real users scan stickers that resolve; a requester walking the ID space mostly misses,
+/// so only misses count against the limit.
+pub struct UnknownIdLimiter {
+    max_misses: usize,
+    misses: Mutex<HashMap<PublicKey, VecDeque<u64>>>,
+}
+
+impl UnknownIdLimiter {
+    /// `max_misses` per UNKNOWN_ID_WINDOW_SECS; 0 disables the limit
+    pub fn new(max_misses: usize) -> Self {
+        Self {
+            max_misses,
+            misses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `requester` has used up its misses in the window ending at `now`
+    pub fn is_limited(&self, requester: &PublicKey, now: u64) -> bool {
+        if self.max_misses == 0 {
+            return false;
+        }
+        let mut misses = self.misses.lock().unwrap();
+        let Some(recent) = misses.get_mut(requester) else {
+            return false;
+        };
+        Self::expire(recent, now);
+        recent.len() >= self.max_misses
+    }
+
+    /// Count a lookup by `requester` that found no community
+    pub fn record_miss(&self, requester: &PublicKey, now: u64) {
+        if self.max_misses == 0 {
+            return;
+        }
+        let mut misses = self.misses.lock().unwrap();
+        if misses.len() >= MAX_TRACKED_REQUESTERS && !misses.contains_key(requester) {
+            misses.retain(|_, recent| {
+                Self::expire(recent, now);
+                !recent.is_empty()
+            });
+        }
+        let recent = misses.entry(*requester).or_default();
+        Self::expire(recent, now);
+        recent.push_back(now);
+    }
+
+    fn expire(recent: &mut VecDeque<u64>, now: u64) {
+        while recent
+            .front()
+            .is_some_and(|at| now.saturating_sub(*at) >= UNKNOWN_ID_WINDOW_SECS)
+        {
+            recent.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::Keys;
+
+    const V4: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+    // Time-based, as produced by a sequential generator
+    const V1: &str = "6fa459ea-ee8a-11ca-8a3b-0002a5d5c51b";
+    const NIL: &str = "00000000-0000-0000-0000-000000000000";
+
+    #[test]
+    fn test_strict_policy_rejects_v1_and_nil() {
+        let policy = CommunityIdPolicy::default();
+        assert_eq!(policy.parse(V4), Ok(Uuid::parse_str(V4).unwrap()));
+        assert_eq!(policy.parse(V1), Err(InvalidCommunityId::NotV4));
+        assert_eq!(policy.parse(NIL), Err(InvalidCommunityId::Nil));
+        assert_eq!(
+            policy.parse("not-a-uuid"),
+            Err(InvalidCommunityId::Malformed)
+        );
+
+        let before = metrics::get("peek_invalid_community_ids_total", &[("reason", "nil")]);
+        let _ = policy.parse(NIL);
+        assert!(metrics::get("peek_invalid_community_ids_total", &[("reason", "nil")]) > before);
+    }
+
+    #[test]
+    fn test_legacy_policy_accepts_any_well_formed_uuid() {
+        let policy = CommunityIdPolicy::new(true);
+        assert!(policy.parse(V1).is_ok());
+        assert!(policy.parse(NIL).is_ok());
+        assert_eq!(
+            policy.parse("not-a-uuid"),
+            Err(InvalidCommunityId::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_community_ref_tries_uuid_then_slug() {
+        let policy = CommunityIdPolicy::default();
+        assert_eq!(
+            policy.parse_ref(V4),
+            Ok(CommunityRef::Id(Uuid::parse_str(V4).unwrap()))
+        );
+        assert_eq!(
+            policy.parse_ref("blue-bottle-mission"),
+            Ok(CommunityRef::Slug(
+                CommunitySlug::parse("blue-bottle-mission").unwrap()
+            ))
+        );
+        // A UUID refused by the policy is not retried as a slug
+        assert_eq!(policy.parse_ref(V1), Err(InvalidCommunityId::NotV4));
+        assert_eq!(
+            policy.parse_ref("Blue Bottle"),
+            Err(InvalidCommunityId::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_slug_format() {
+        for valid in [
+            "abc",
+            "blue-bottle-mission",
+            "cafe-42",
+            "a".repeat(40).as_str(),
+        ] {
+            assert!(
+                CommunitySlug::parse(valid).is_ok(),
+                "{} should parse",
+                valid
+            );
+        }
+        let too_long = "a".repeat(MAX_SLUG_CHARS + 1);
+        let invalid = [
+            "ab",
+            too_long.as_str(),
+            "Blue-Bottle",
+            "blue_bottle",
+            "blue bottle",
+            "café",
+            "",
+            V4,
+            "3a7e5c59c0a14876acf156189b86aa0d",
+        ];
+        for slug in invalid {
+            assert_eq!(CommunitySlug::parse(slug), Err(InvalidSlug), "{:?}", slug);
+        }
+    }
+
+    #[test]
+    fn test_unknown_id_limit_is_per_requester_and_windowed() {
+        let limiter = UnknownIdLimiter::new(3);
+        let prober = Keys::generate().public_key();
+        let neighbor = Keys::generate().public_key();
+
+        for second in 0..3 {
+            assert!(!limiter.is_limited(&prober, 1_000 + second));
+            limiter.record_miss(&prober, 1_000 + second);
+        }
+        assert!(limiter.is_limited(&prober, 1_003));
+        assert!(!limiter.is_limited(&neighbor, 1_003));
+
+        // The first miss ages out of the window
+        assert!(!limiter.is_limited(&prober, 1_000 + UNKNOWN_ID_WINDOW_SECS));
+
+        let unlimited = UnknownIdLimiter::new(0);
+        for _ in 0..10 {
+            unlimited.record_miss(&prober, 1_000);
+        }
+        assert!(!unlimited.is_limited(&prober, 1_000));
+    }
+}
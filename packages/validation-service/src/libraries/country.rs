@@ -0,0 +1,287 @@
+//! Country of a community's creation location, for operators bound by jurisdiction rules
+//!
+//! Only compiled with the `country-lookup` feature, and nothing is bundled: boundaries are read
+//! at startup from COUNTRY_BOUNDARIES_PATH, a GeoJSON FeatureCollection such as a simplified
+//! Natural Earth admin-0 export. Each feature needs an `ISO_A2` (or `iso_a2`) property and a
+//! Polygon or MultiPolygon geometry; features coded `-99` (disputed areas) are skipped.
+
+use geo::{Contains, Coord, LineString, MultiPolygon, Point, Polygon};
+use serde_json::Value;
+
+/// Why a boundary file could not be used
+#[derive(Debug, thiserror::Error)]
+pub enum BoundaryError {
+    #[error("Failed to read country boundaries: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Country boundaries are not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Country boundaries are not a GeoJSON FeatureCollection")]
+    NotFeatureCollection,
+    #[error("Country boundary feature {index} has no ISO_A2 code")]
+    MissingCode { index: usize },
+    #[error("Country boundary feature {index} ({code}) is not a Polygon or MultiPolygon")]
+    InvalidGeometry { index: usize, code: String },
+    #[error("COUNTRY_ALLOWLIST or COUNTRY_DENYLIST is set without COUNTRY_BOUNDARIES_PATH")]
+    NoBoundaries,
+}
+
+/// Coarse country polygons keyed by ISO 3166-1 alpha-2 code
+#[derive(Debug, Clone, Default)]
+pub struct CountryBoundaries {
+    countries: Vec<(String, MultiPolygon<f64>)>,
+}
+
+impl CountryBoundaries {
+    pub fn load(path: &str) -> Result<Self, BoundaryError> {
+        Self::from_geojson(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_geojson(json: &str) -> Result<Self, BoundaryError> {
+        let collection: Value = serde_json::from_str(json)?;
+        let features = collection
+            .get("features")
+            .and_then(Value::as_array)
+            .ok_or(BoundaryError::NotFeatureCollection)?;
+
+        let mut countries = Vec::with_capacity(features.len());
+        for (index, feature) in features.iter().enumerate() {
+            let properties = feature.get("properties");
+            let code = ["ISO_A2", "iso_a2"]
+                .iter()
+                .find_map(|key| properties?.get(*key)?.as_str())
+                .ok_or(BoundaryError::MissingCode { index })?
+                .trim()
+                .to_ascii_uppercase();
+            if code == "-99" {
+                continue;
+            }
+            let shape = feature
+                .get("geometry")
+                .and_then(parse_geometry)
+                .ok_or_else(|| BoundaryError::InvalidGeometry {
+                    index,
+                    code: code.clone(),
+                })?;
+            countries.push((code, shape));
+        }
+        Ok(Self { countries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.countries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.countries.is_empty()
+    }
+
+    /// ISO code of the first country containing the point; None at sea or in a gap in the data
+    pub fn country_at(&self, latitude: f64, longitude: f64) -> Option<&str> {
+        let point = Point::new(longitude, latitude);
+        self.countries
+            .iter()
+            .find(|(_, shape)| shape.contains(&point))
+            .map(|(code, _)| code.as_str())
+    }
+}
+
+/// GeoJSON Polygon or MultiPolygon, as longitude/latitude rings
+fn parse_geometry(geometry: &Value) -> Option<MultiPolygon<f64>> {
+    let coordinates = geometry.get("coordinates")?.as_array()?;
+    match geometry.get("type")?.as_str()? {
+        "Polygon" => Some(MultiPolygon::new(vec![parse_polygon(coordinates)?])),
+        "MultiPolygon" => coordinates
+            .iter()
+            .map(|polygon| parse_polygon(polygon.as_array()?))
+            .collect::<Option<Vec<_>>>()
+            .map(MultiPolygon::new),
+        _ => None,
+    }
+}
+
+fn parse_polygon(rings: &[Value]) -> Option<Polygon<f64>> {
+    let mut rings = rings.iter().map(|ring| {
+        ring.as_array()?
+            .iter()
+            .map(|position| {
+                let position = position.as_array()?;
+                Some(Coord {
+                    x: position.first()?.as_f64()?,
+                    y: position.get(1)?.as_f64()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(LineString::new)
+    });
+    let exterior = rings.next()??;
+    let interiors = rings.collect::<Option<Vec<_>>>()?;
+    Some(Polygon::new(exterior, interiors))
+}
+
+/// A new community may not be created where it would be hosted in an excluded country
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Communities cannot be created in this region")]
+pub struct RegionUnsupported {
+    /// None when the location is in no known country and an allowlist is set
+    pub country: Option<String>,
+}
+
+/// Creation-time country tagging and allow/deny filtering
+///
+/// A non-empty allowlist admits only its countries, so locations in no known country are
+/// refused too; the denylist refuses its countries wherever the allowlist would admit them.
+#[derive(Debug, Clone)]
+pub struct CountryGate {
+    boundaries: CountryBoundaries,
+    allowlist: Vec<String>,
+    denylist: Vec<String>,
+}
+
+impl CountryGate {
+    pub fn new(boundaries: CountryBoundaries, allowlist: &[String], denylist: &[String]) -> Self {
+        Self {
+            boundaries,
+            allowlist: normalize_codes(allowlist),
+            denylist: normalize_codes(denylist),
+        }
+    }
+
+    /// The gate COUNTRY_BOUNDARIES_PATH and the lists describe, or None when no path is set
+    pub fn load(
+        path: Option<&str>,
+        allowlist: &[String],
+        denylist: &[String],
+    ) -> Result<Option<Self>, BoundaryError> {
+        let Some(path) = path else {
+            if normalize_codes(allowlist).is_empty() && normalize_codes(denylist).is_empty() {
+                return Ok(None);
+            }
+            return Err(BoundaryError::NoBoundaries);
+        };
+        let boundaries = CountryBoundaries::load(path)?;
+        tracing::info!(
+            "Loaded {} country boundaries from {}",
+            boundaries.len(),
+            path
+        );
+        Ok(Some(Self::new(boundaries, allowlist, denylist)))
+    }
+
+    /// Country to tag a new community at this location with, if it may be created there
+    pub fn check(
+        &self,
+        latitude: f64,
+        longitude: f64,
+    ) -> Result<Option<String>, RegionUnsupported> {
+        let country = self
+            .boundaries
+            .country_at(latitude, longitude)
+            .map(str::to_string);
+        let listed = |list: &[String]| {
+            country
+                .as_ref()
+                .is_some_and(|code| list.iter().any(|listed| listed == code))
+        };
+        if listed(&self.denylist) || (!self.allowlist.is_empty() && !listed(&self.allowlist)) {
+            return Err(RegionUnsupported { country });
+        }
+        Ok(country)
+    }
+}
+
+fn normalize_codes(codes: &[String]) -> Vec<String> {
+    codes
+        .iter()
+        .map(|code| code.trim().to_ascii_uppercase())
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two fake countries side by side, XA with a lake (a hole) that belongs to neither
+    const FIXTURE: &str = include_str!("../../tests/fixtures/country_boundaries.geojson");
+
+    fn boundaries() -> CountryBoundaries {
+        CountryBoundaries::from_geojson(FIXTURE).unwrap()
+    }
+
+    fn codes(codes: &[&str]) -> Vec<String> {
+        codes.iter().map(|code| code.to_string()).collect()
+    }
+
+    #[test]
+    fn test_locations_are_tagged_with_their_country() {
+        let boundaries = boundaries();
+        assert_eq!(boundaries.len(), 2);
+        assert_eq!(boundaries.country_at(10.5, 10.5), Some("XA"));
+        assert_eq!(boundaries.country_at(10.5, 12.5), Some("XB"));
+        // The second XB island, and the lake inside XA
+        assert_eq!(boundaries.country_at(-5.5, 20.5), Some("XB"));
+        assert_eq!(boundaries.country_at(10.5, 11.5), None);
+        assert_eq!(boundaries.country_at(40.0, -40.0), None);
+
+        let gate = CountryGate::new(boundaries, &[], &[]);
+        assert_eq!(gate.check(10.5, 10.5), Ok(Some("XA".to_string())));
+        assert_eq!(gate.check(40.0, -40.0), Ok(None));
+    }
+
+    #[test]
+    fn test_allowlist_refuses_other_and_unknown_countries() {
+        let gate = CountryGate::new(boundaries(), &codes(&[" xa "]), &[]);
+        assert_eq!(gate.check(10.5, 10.5), Ok(Some("XA".to_string())));
+        assert_eq!(
+            gate.check(10.5, 12.5),
+            Err(RegionUnsupported {
+                country: Some("XB".to_string())
+            })
+        );
+        assert_eq!(
+            gate.check(40.0, -40.0),
+            Err(RegionUnsupported { country: None })
+        );
+    }
+
+    #[test]
+    fn test_denylist_refuses_only_its_countries() {
+        let gate = CountryGate::new(boundaries(), &[], &codes(&["XB"]));
+        assert_eq!(gate.check(10.5, 10.5), Ok(Some("XA".to_string())));
+        assert!(gate.check(10.5, 12.5).is_err());
+        assert!(gate.check(-5.5, 20.5).is_err());
+        assert_eq!(gate.check(40.0, -40.0), Ok(None));
+
+        // Denied wins over allowed
+        let gate = CountryGate::new(boundaries(), &codes(&["XA", "XB"]), &codes(&["XB"]));
+        assert!(gate.check(10.5, 10.5).is_ok());
+        assert!(gate.check(10.5, 12.5).is_err());
+    }
+
+    #[test]
+    fn test_lists_without_boundaries_are_a_startup_error() {
+        assert!(matches!(
+            CountryGate::load(None, &[], &codes(&["XB"])),
+            Err(BoundaryError::NoBoundaries)
+        ));
+        assert!(CountryGate::load(None, &codes(&[""]), &[])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_malformed_features_are_reported() {
+        let missing_code = r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":{},"geometry":{"type":"Point","coordinates":[0,0]}}]}"#;
+        assert!(matches!(
+            CountryBoundaries::from_geojson(missing_code),
+            Err(BoundaryError::MissingCode { index: 0 })
+        ));
+        let point = r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":{"ISO_A2":"XA"},"geometry":{"type":"Point","coordinates":[0,0]}}]}"#;
+        assert!(matches!(
+            CountryBoundaries::from_geojson(point),
+            Err(BoundaryError::InvalidGeometry { index: 0, .. })
+        ));
+        let disputed = r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":{"ISO_A2":"-99"},"geometry":null}]}"#;
+        assert_eq!(CountryBoundaries::from_geojson(disputed).unwrap().len(), 0);
+    }
+}
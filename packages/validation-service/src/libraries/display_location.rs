@@ -1,78 +1,47 @@
-use geohash::{encode, Coord};
-use rand::Rng;
-use std::f64::consts::PI;
+use peek_geo::{display_location, DisplayOffset, MAX_DISPLAY_OFFSET_METERS};
 
-/// Maximum offset distance in meters from actual location
-const MAX_OFFSET_METERS: f64 = 750.0;
+use super::rng::{RngSource, ThreadRngSource};
+use crate::models::Coordinates;
 
-/// Earth radius in meters (for distance calculations)
-const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+pub use peek_geo::DisplayLocationError;
 
 /// Generate a display location that is randomly offset from the actual location.
-/// The offset will be within MAX_OFFSET_METERS (750m) to ensure the actual location
+/// The offset will be within MAX_DISPLAY_OFFSET_METERS (750m) to ensure the actual location
 /// is always within the 1km fog circle centered on the display location.
 ///
 /// Returns a 9-character geohash for the display location.
-pub fn generate_display_location(actual_lat: f64, actual_lon: f64) -> Result<String, String> {
-    let mut rng = rand::thread_rng();
-
-    // Generate random distance (0 to 750 meters)
-    let distance_meters = rng.gen_range(0.0..MAX_OFFSET_METERS);
-
-    // Generate random bearing (0 to 360 degrees)
-    let bearing_degrees = rng.gen_range(0.0..360.0);
-    let bearing_radians = bearing_degrees * PI / 180.0;
-
-    // Calculate offset point using Haversine formula
-    let lat_rad = actual_lat * PI / 180.0;
-    let lon_rad = actual_lon * PI / 180.0;
-
-    // Angular distance
-    let angular_distance = distance_meters / EARTH_RADIUS_METERS;
-
-    // Calculate new latitude
-    let new_lat_rad = (lat_rad.sin() * angular_distance.cos()
-        + lat_rad.cos() * angular_distance.sin() * bearing_radians.cos())
-    .asin();
-
-    // Calculate new longitude
-    let new_lon_rad = lon_rad
-        + (bearing_radians.sin() * angular_distance.sin() * lat_rad.cos())
-            .atan2(angular_distance.cos() - lat_rad.sin() * new_lat_rad.sin());
-
-    // Convert back to degrees
-    let display_lat = new_lat_rad * 180.0 / PI;
-    let display_lon = new_lon_rad * 180.0 / PI;
-
-    // Encode as 9-character geohash for higher precision
-    encode(
-        Coord {
-            x: display_lon,
-            y: display_lat,
+pub fn generate_display_location(actual: Coordinates) -> Result<String, DisplayLocationError> {
+    generate_display_location_with(actual, &ThreadRngSource)
+}
+
+/// Same as `generate_display_location`, drawing the offset from `rng`
+pub fn generate_display_location_with(
+    actual: Coordinates,
+    rng: &dyn RngSource,
+) -> Result<String, DisplayLocationError> {
+    // Random distance (0 to 750 meters), then random bearing (0 to 360 degrees)
+    let distance_meters = rng.gen_range_f64(0.0, MAX_DISPLAY_OFFSET_METERS);
+    let bearing_degrees = rng.gen_range_f64(0.0, 360.0);
+    display_location(
+        actual,
+        DisplayOffset {
+            distance_meters,
+            bearing_degrees,
         },
-        9,
     )
-    .map_err(|e| format!("Failed to encode display location: {}", e))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use geohash::decode;
-
-    // Test helper function for calculating distance
-    fn calculate_distance_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-        let lat1_rad = lat1 * PI / 180.0;
-        let lat2_rad = lat2 * PI / 180.0;
-        let delta_lat = (lat2 - lat1) * PI / 180.0;
-        let delta_lon = (lon2 - lon1) * PI / 180.0;
-
-        let a = (delta_lat / 2.0).sin().powi(2)
-            + lat1_rad.cos() * lat2_rad.cos() * (delta_lon / 2.0).sin().powi(2);
-
-        let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-
-        EARTH_RADIUS_METERS * c
+    use crate::libraries::test_support::SeededRng;
+    use peek_geo::haversine_meters;
+
+    fn distance_to_display(actual: Coordinates, display_geohash: &str) -> f64 {
+        haversine_meters(
+            &actual,
+            &Coordinates::from_geohash(display_geohash).unwrap(),
+        )
     }
 
     #[test]
@@ -83,13 +52,14 @@ mod tests {
         // Generate multiple display locations to test randomness
         let mut generated = Vec::new();
         for _ in 0..10 {
-            let display_geohash = generate_display_location(lat, lon).unwrap();
+            let display_geohash =
+                generate_display_location(Coordinates::new(lat, lon).unwrap()).unwrap();
             assert_eq!(display_geohash.len(), 9);
 
             // Verify the offset is within bounds
-            let (display_coord, _, _) = decode(&display_geohash).unwrap();
-            let distance = calculate_distance_meters(lat, lon, display_coord.y, display_coord.x);
-            assert!(distance <= MAX_OFFSET_METERS);
+            let distance =
+                distance_to_display(Coordinates::new(lat, lon).unwrap(), &display_geohash);
+            assert!(distance <= MAX_DISPLAY_OFFSET_METERS);
 
             generated.push(display_geohash);
         }
@@ -103,31 +73,58 @@ mod tests {
     }
 
     #[test]
-    fn test_distance_calculation() {
-        // San Francisco to Palo Alto (approximately 48km)
-        let distance = calculate_distance_meters(37.7749, -122.4194, 37.4419, -122.1430);
-        assert!((distance - 48_000.0).abs() < 5_000.0); // Within 5km tolerance
-
-        // Very close points (100m apart)
-        let lat1 = 37.7749;
-        let lon1 = -122.4194;
-        let lat2 = 37.7758; // ~100m north
-        let lon2 = -122.4194;
-        let distance = calculate_distance_meters(lat1, lon1, lat2, lon2);
-        assert!((distance - 100.0).abs() < 20.0); // Within 20m tolerance
+    fn test_display_within_max_offset() {
+        let lat = 40.7128;
+        let lon = -74.0060;
+
+        for _ in 0..20 {
+            let display_geohash =
+                generate_display_location(Coordinates::new(lat, lon).unwrap()).unwrap();
+            let distance =
+                distance_to_display(Coordinates::new(lat, lon).unwrap(), &display_geohash);
+            assert!(distance <= MAX_DISPLAY_OFFSET_METERS);
+        }
     }
 
     #[test]
-    fn test_display_within_max_offset() {
+    fn test_seeded_display_location_is_reproducible() {
         let lat = 40.7128;
         let lon = -74.0060;
 
-        for _ in 0..20 {
-            let display_geohash = generate_display_location(lat, lon).unwrap();
-            let (display_coord, _, _) = decode(&display_geohash).unwrap();
+        let first =
+            generate_display_location_with(Coordinates::new(lat, lon).unwrap(), &SeededRng::new(7))
+                .unwrap();
+        let second =
+            generate_display_location_with(Coordinates::new(lat, lon).unwrap(), &SeededRng::new(7))
+                .unwrap();
+        assert_eq!(first, second);
+
+        let distance = distance_to_display(Coordinates::new(lat, lon).unwrap(), &first);
+        assert!(distance <= MAX_DISPLAY_OFFSET_METERS);
+    }
 
-            let distance = calculate_distance_meters(lat, lon, display_coord.y, display_coord.x);
-            assert!(distance <= MAX_OFFSET_METERS);
+    #[test]
+    fn test_display_location_near_the_antimeridian_and_poles() {
+        // Offsets from these spots regularly cross lon 180 or graze a pole
+        for (lat, lon) in [
+            (-16.5, 179.999),
+            (65.0, -179.9995),
+            (89.9999, 0.0),
+            (-90.0, 45.0),
+        ] {
+            for seed in 0..200 {
+                let actual = Coordinates::new(lat, lon).unwrap();
+                let display = generate_display_location_with(actual, &SeededRng::new(seed))
+                    .unwrap_or_else(|e| panic!("({}, {}) seed {}: {}", lat, lon, seed, e));
+                let distance = distance_to_display(actual, &display);
+                // The cell center may sit a few meters off the offset point itself
+                assert!(
+                    distance <= MAX_DISPLAY_OFFSET_METERS + 5.0,
+                    "{} is {}m away",
+                    display,
+                    distance
+                );
+            }
         }
     }
 }
@@ -0,0 +1,176 @@
+//! Circles no community marker may appear in, such as around schools or shelters
+//!
+//! Configured as EXCLUSION_ZONES, comma separated `latitude:longitude:radius_meters` entries.
+//! Display locations are re-drawn until they land outside every zone. A community may still be
+//! anchored inside one (the sticker is where it is), but the operator is told about it.
+
+use peek_geo::haversine_meters;
+
+use super::display_location::{generate_display_location_with, DisplayLocationError};
+use super::rng::RngSource;
+use crate::models::Coordinates;
+
+/// Display locations drawn for one anchor before giving up on finding one outside every zone
+pub const MAX_DISPLAY_ATTEMPTS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExclusionZone {
+    pub center: Coordinates,
+    pub radius_meters: f64,
+}
+
+impl ExclusionZone {
+    pub fn contains(&self, point: &Coordinates) -> bool {
+        haversine_meters(&self.center, point) <= self.radius_meters
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("EXCLUSION_ZONES entry {0:?} is not latitude:longitude:radius_meters")]
+pub struct InvalidExclusionZone(pub String);
+
+/// The configured zones, in EXCLUSION_ZONES order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExclusionZones {
+    zones: Vec<ExclusionZone>,
+}
+
+impl ExclusionZones {
+    pub fn new(zones: Vec<ExclusionZone>) -> Self {
+        Self { zones }
+    }
+
+    /// Parse EXCLUSION_ZONES entries; blank entries are skipped
+    pub fn parse(entries: &[String]) -> Result<Self, InvalidExclusionZone> {
+        let zones = entries
+            .iter()
+            .map(|entry| entry.trim())
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || InvalidExclusionZone(entry.to_string());
+                let fields: Vec<f64> = entry
+                    .split(':')
+                    .map(|field| field.trim().parse::<f64>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid())?;
+                let [latitude, longitude, radius_meters] = fields[..] else {
+                    return Err(invalid());
+                };
+                if !(radius_meters.is_finite() && radius_meters > 0.0) {
+                    return Err(invalid());
+                }
+                Ok(ExclusionZone {
+                    center: Coordinates::new(latitude, longitude).map_err(|_| invalid())?,
+                    radius_meters,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { zones })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    /// The first zone containing `point`, with its position in EXCLUSION_ZONES
+    pub fn containing(&self, point: &Coordinates) -> Option<(usize, &ExclusionZone)> {
+        self.zones
+            .iter()
+            .enumerate()
+            .find(|(_, zone)| zone.contains(point))
+    }
+
+    /// Whether the center of a display geohash lies in any zone; undecodable ones do not
+    pub fn contains_geohash(&self, geohash: &str) -> bool {
+        Coordinates::from_geohash(geohash).is_ok_and(|center| self.containing(&center).is_some())
+    }
+}
+
+/// A display geohash for `actual` whose center is outside every zone
+///
+/// Draws up to MAX_DISPLAY_ATTEMPTS offsets from `rng`. None means every draw landed inside a
+/// zone (one wider than the fog circle around the anchor), so the community gets no marker.
+pub fn display_location_outside(
+    actual: Coordinates,
+    zones: &ExclusionZones,
+    rng: &dyn RngSource,
+) -> Result<Option<String>, DisplayLocationError> {
+    for _ in 0..MAX_DISPLAY_ATTEMPTS {
+        let display = generate_display_location_with(actual, rng)?;
+        if !zones.contains_geohash(&display) {
+            return Ok(Some(display));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::SeededRng;
+
+    fn zones(entries: &[&str]) -> ExclusionZones {
+        let entries: Vec<String> = entries.iter().map(|entry| entry.to_string()).collect();
+        ExclusionZones::parse(&entries).unwrap()
+    }
+
+    #[test]
+    fn test_zones_parse_from_config_entries() {
+        let parsed = zones(&["37.7749:-122.4194:300", " ", "51.5:-0.12:50.5"]);
+        assert_eq!(parsed.zones.len(), 2);
+        assert_eq!(parsed.zones[1].radius_meters, 50.5);
+
+        for bad in [
+            "37.7:-122.4",
+            "37.7:-122.4:0",
+            "91:0:100",
+            "a:b:c",
+            "1:2:3:4",
+        ] {
+            assert_eq!(
+                ExclusionZones::parse(&[bad.to_string()]),
+                Err(InvalidExclusionZone(bad.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_containment_uses_the_radius() {
+        let parsed = zones(&["37.7749:-122.4194:300"]);
+        // ~100m and ~500m north of the center
+        let near = Coordinates::new(37.7758, -122.4194).unwrap();
+        let far = Coordinates::new(37.7794, -122.4194).unwrap();
+        assert_eq!(parsed.containing(&near).map(|(index, _)| index), Some(0));
+        assert!(parsed.containing(&far).is_none());
+        assert!(!parsed.contains_geohash("not-a-geohash"));
+    }
+
+    #[test]
+    fn test_display_points_are_pushed_outside_zones() {
+        // The anchor sits in the middle of a zone covering most of its fog circle
+        let anchor = Coordinates::new(37.7749, -122.4194).unwrap();
+        let parsed = zones(&["37.7749:-122.4194:400"]);
+        for seed in 0..50 {
+            let display = display_location_outside(anchor, &parsed, &SeededRng::new(seed))
+                .unwrap()
+                .unwrap();
+            assert!(!parsed.contains_geohash(&display), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_zones_wider_than_the_fog_leave_no_marker() {
+        let anchor = Coordinates::new(37.7749, -122.4194).unwrap();
+        let parsed = zones(&["37.7749:-122.4194:5000"]);
+        assert_eq!(
+            display_location_outside(anchor, &parsed, &SeededRng::new(1)).unwrap(),
+            None
+        );
+        // Without zones the first draw is used as before
+        assert_eq!(
+            display_location_outside(anchor, &ExclusionZones::default(), &SeededRng::new(1))
+                .unwrap(),
+            Some(generate_display_location_with(anchor, &SeededRng::new(1)).unwrap())
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Bounded pools of shared strings for ids that recur across requests
+//!
+//! The same group ids, pubkeys and metric labels come through the hot path over and over, and
+//! every owned copy of one is its own allocation. An `Interner` hands out one `Arc<str>` per
+//! distinct string, so caches can hold clones that cost a reference count bump. `Arc<str>`
+//! borrows as `str`, so interned keys are still looked up with a plain `&str`.
+//!
+//! The pool is bounded. When it fills up it drops the strings no one else holds any more, and
+//! while everything in it is still in use it hands out unshared copies instead of growing.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Distinct strings held by the process-wide pool
+pub const SHARED_POOL_CAPACITY: usize = 50_000;
+
+struct Pool {
+    strings: HashSet<Arc<str>>,
+    // Misses since the pool was last swept while full, so a pool of held strings is not
+    // rescanned on every miss
+    misses_while_full: usize,
+}
+
+pub struct Interner {
+    capacity: usize,
+    pool: Mutex<Pool>,
+}
+
+impl Interner {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            pool: Mutex::new(Pool {
+                strings: HashSet::new(),
+                misses_while_full: 0,
+            }),
+        }
+    }
+
+    /// The pooled copy of `value`, adding it if there is room
+    pub fn intern(&self, value: &str) -> Arc<str> {
+        let mut pool = self.pool.lock().unwrap();
+        if let Some(interned) = pool.strings.get(value) {
+            return interned.clone();
+        }
+        if pool.strings.len() >= self.capacity {
+            if pool.misses_while_full == 0 {
+                // Strings only the pool still holds are no longer shared with anything
+                pool.strings
+                    .retain(|interned| Arc::strong_count(interned) > 1);
+            }
+            pool.misses_while_full = (pool.misses_while_full + 1) % (self.capacity / 4).max(1);
+        }
+        let interned: Arc<str> = Arc::from(value);
+        if pool.strings.len() < self.capacity {
+            pool.strings.insert(interned.clone());
+            pool.misses_while_full = 0;
+        }
+        interned
+    }
+}
+
+/// Process-wide pool for group ids and pubkey hex strings
+pub fn shared() -> &'static Interner {
+    static SHARED: OnceLock<Interner> = OnceLock::new();
+    SHARED.get_or_init(|| Interner::new(SHARED_POOL_CAPACITY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equal_strings_share_one_allocation() {
+        let interner = Interner::new(10);
+        let first = interner.intern("peek-abc123");
+        let second = interner.intern(&String::from("peek-abc123"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(&*first, "peek-abc123");
+    }
+
+    #[test]
+    fn test_full_pool_drops_unused_strings_and_never_grows() {
+        let interner = Interner::new(2);
+        let held = interner.intern("peek-held");
+        drop(interner.intern("peek-dropped"));
+
+        // Full: the string nobody holds makes room
+        let third = interner.intern("peek-third");
+        assert!(Arc::ptr_eq(&third, &interner.intern("peek-third")));
+        assert!(Arc::ptr_eq(&held, &interner.intern("peek-held")));
+
+        // Everything pooled is in use, so new strings are handed out unshared
+        let extra = interner.intern("peek-extra");
+        assert_eq!(&*extra, "peek-extra");
+        assert!(!Arc::ptr_eq(&extra, &interner.intern("peek-extra")));
+    }
+}
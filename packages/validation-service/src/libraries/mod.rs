@@ -1 +1,16 @@
+pub mod attestation;
+pub mod clock;
+pub mod community_id;
+#[cfg(feature = "country-lookup")]
+pub mod country;
 pub mod display_location;
+pub mod exclusion_zones;
+pub mod intern;
+pub mod plausibility;
+pub mod relay_url;
+pub mod rng;
+pub mod sanitize;
+pub mod secret;
+
+#[cfg(test)]
+pub mod test_support;
@@ -0,0 +1,140 @@
+/// Communities are never anchored further from the equator than this
+pub const MAX_PLAUSIBLE_LATITUDE: f64 = 85.0;
+
+/// Size of one landmask cell in degrees
+const LANDMASK_CELL_DEGREES: f64 = 10.0;
+
+/// Coarse land/water raster: 18 rows of 36 cells, north to south from 90°N and west to east
+/// from 180°W. `.` marks open ocean with no land at all, `#` any cell touching a coast or an
+/// island, so only points far out at sea are ever rejected.
+const LANDMASK: &str = include_str!("landmask.txt");
+
+/// Why a creation location is not believable as a sticker spot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ImplausibleLocation {
+    #[error("Location is exactly 0,0, which is what a missing fix looks like")]
+    NullIsland,
+    #[error("Location is too close to a pole")]
+    Polar,
+    #[error("Location is in open ocean")]
+    OpenOcean,
+}
+
+impl ImplausibleLocation {
+    /// Metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NullIsland => "null_island",
+            Self::Polar => "polar",
+            Self::OpenOcean => "open_ocean",
+        }
+    }
+}
+
+/// Gate for the location a new community is anchored at
+///
+/// Spoofed or fat-fingered coordinates would otherwise pin a community somewhere nobody can
+/// scan it, and it would stay on the discovery map for good. The landmask lookup is opt-in.
+pub fn check_plausible_location(
+    latitude: f64,
+    longitude: f64,
+    use_landmask: bool,
+) -> Result<(), ImplausibleLocation> {
+    if latitude == 0.0 && longitude == 0.0 {
+        return Err(ImplausibleLocation::NullIsland);
+    }
+    if latitude.abs() > MAX_PLAUSIBLE_LATITUDE {
+        return Err(ImplausibleLocation::Polar);
+    }
+    if use_landmask && is_open_ocean(latitude, longitude) {
+        return Err(ImplausibleLocation::OpenOcean);
+    }
+    Ok(())
+}
+
+/// Whether the landmask cell containing the point has no land in it
+fn is_open_ocean(latitude: f64, longitude: f64) -> bool {
+    let row = ((90.0 - latitude) / LANDMASK_CELL_DEGREES)
+        .floor()
+        .clamp(0.0, 17.0) as usize;
+    let col = ((longitude + 180.0) / LANDMASK_CELL_DEGREES)
+        .floor()
+        .clamp(0.0, 35.0) as usize;
+    LANDMASK
+        .lines()
+        .nth(row)
+        .and_then(|cells| cells.as_bytes().get(col))
+        .is_some_and(|cell| *cell == b'.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_landmask_is_a_full_grid() {
+        let rows: Vec<&str> = LANDMASK.lines().collect();
+        assert_eq!(rows.len(), 18);
+        assert!(rows
+            .iter()
+            .all(|row| row.len() == 36 && row.bytes().all(|c| c == b'.' || c == b'#')));
+    }
+
+    #[test]
+    fn test_null_island_and_poles_are_rejected() {
+        assert_eq!(
+            check_plausible_location(0.0, 0.0, false),
+            Err(ImplausibleLocation::NullIsland)
+        );
+        assert_eq!(
+            check_plausible_location(-0.0, 0.0, true),
+            Err(ImplausibleLocation::NullIsland)
+        );
+        assert_eq!(
+            check_plausible_location(89.9, 12.0, false),
+            Err(ImplausibleLocation::Polar)
+        );
+        assert_eq!(
+            check_plausible_location(-85.5, 0.0, false),
+            Err(ImplausibleLocation::Polar)
+        );
+        // Just off the origin is a real (if unlikely) fix
+        assert_eq!(check_plausible_location(0.0001, 0.0, false), Ok(()));
+    }
+
+    #[test]
+    fn test_open_ocean_is_rejected_only_with_the_landmask() {
+        // Mid-Atlantic, halfway between Bermuda and the Azores
+        assert_eq!(
+            check_plausible_location(35.0, -45.0, true),
+            Err(ImplausibleLocation::OpenOcean)
+        );
+        assert_eq!(check_plausible_location(35.0, -45.0, false), Ok(()));
+        // Central South Pacific
+        assert_eq!(
+            check_plausible_location(-35.0, -115.0, true),
+            Err(ImplausibleLocation::OpenOcean)
+        );
+    }
+
+    #[test]
+    fn test_cities_and_islands_pass() {
+        for (latitude, longitude) in [
+            (37.7749, -122.4194), // San Francisco
+            (38.7223, -9.1393),   // Lisbon
+            (-33.8688, 151.2093), // Sydney
+            (59.9139, 10.7522),   // Oslo
+            (21.3069, -157.8583), // Honolulu
+            (-54.2806, -36.5080), // Grytviken, South Georgia
+            (64.1466, -21.9426),  // Reykjavík
+        ] {
+            assert_eq!(
+                check_plausible_location(latitude, longitude, true),
+                Ok(()),
+                "({}, {})",
+                latitude,
+                longitude
+            );
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use rand::Rng;
+
+/// Source of randomness, injected so randomized outputs (display offsets, group ids) are reproducible in tests
+pub trait RngSource: Send + Sync {
+    /// Uniform sample in [0, 1)
+    fn next_f64(&self) -> f64;
+
+    /// Uniform sample in [low, high)
+    fn gen_range_f64(&self, low: f64, high: f64) -> f64 {
+        low + self.next_f64() * (high - low)
+    }
+
+    /// Uniform index in [0, len)
+    fn gen_index(&self, len: usize) -> usize {
+        ((self.next_f64() * len as f64) as usize).min(len.saturating_sub(1))
+    }
+}
+
+/// Thread-local OS-seeded randomness
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ThreadRngSource;
+
+impl RngSource for ThreadRngSource {
+    fn next_f64(&self) -> f64 {
+        rand::thread_rng().gen::<f64>()
+    }
+}
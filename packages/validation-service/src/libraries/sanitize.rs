@@ -0,0 +1,259 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest community name published in kind 9002 metadata, in characters
+pub const MAX_NAME_CHARS: usize = 100;
+
+/// Longest community description published in kind 9002 metadata, in characters
+pub const MAX_ABOUT_CHARS: usize = 500;
+
+/// Longest welcome message for new members, in characters; long ones live in the extension event
+pub const MAX_WELCOME_CHARS: usize = 2000;
+
+/// Longest OSM opening_hours value kept for a venue, in characters (OSM's own tag limit)
+pub const MAX_OPENING_HOURS_CHARS: usize = 255;
+
+/// Who produced the text: admin input is rejected when unusable, automated text is truncated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataSource {
+    Admin,
+    Automated,
+}
+
+/// Free-text community metadata fields; None leaves a field unchanged
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataText {
+    pub name: Option<String>,
+    pub about: Option<String>,
+    // Greeting sent to new members with their join; empty removes it
+    pub welcome: Option<String>,
+}
+
+impl MetadataText {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.about.is_none() && self.welcome.is_none()
+    }
+}
+
+/// Fields of admin input that could not be salvaged by sanitizing
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid community metadata: {}", .fields.join(", "))]
+pub struct InvalidMetadata {
+    pub fields: Vec<&'static str>,
+}
+
+/// Bidi embeddings, overrides, isolates and marks, which can visually reorder surrounding text
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// NFC-normalize, drop bidi controls, turn control characters into spaces and collapse whitespace
+fn clean(text: &str) -> String {
+    let normalized: String = text
+        .nfc()
+        .filter(|c| !is_bidi_control(*c))
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    normalized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// First `max_chars` characters, without a dangling joiner or trailing space
+fn truncate(text: &str, max_chars: usize) -> String {
+    let truncated: String = text.chars().take(max_chars).collect();
+    truncated.trim_end_matches(['\u{200D}', ' ']).to_string()
+}
+
+/// Clean one field; None if it is unusable from this source
+fn sanitize_field(
+    value: &str,
+    max_chars: usize,
+    allow_empty: bool,
+    source: MetadataSource,
+) -> Option<String> {
+    let cleaned = clean(value);
+    let cleaned = if cleaned.chars().count() <= max_chars {
+        cleaned
+    } else if source == MetadataSource::Automated {
+        truncate(&cleaned, max_chars)
+    } else {
+        return None;
+    };
+    (allow_empty || !cleaned.is_empty()).then_some(cleaned)
+}
+
+/// Make name/about/welcome text safe to publish in kind 9002 tags
+///
+/// Control characters (newlines included) and bidi overrides are removed, whitespace runs
+/// collapse to single spaces and the result is NFC-normalized. Overlong admin input and empty
+/// names are rejected with every offending field listed; automated text (Overpass place
+/// names) is truncated instead.
+pub fn sanitize_metadata(
+    text: &MetadataText,
+    source: MetadataSource,
+) -> Result<MetadataText, InvalidMetadata> {
+    let mut fields = Vec::new();
+
+    let name = text
+        .name
+        .as_deref()
+        .map(|name| sanitize_field(name, MAX_NAME_CHARS, false, source).ok_or("name"));
+    let about = text
+        .about
+        .as_deref()
+        .map(|about| sanitize_field(about, MAX_ABOUT_CHARS, true, source).ok_or("about"));
+    let welcome = text
+        .welcome
+        .as_deref()
+        .map(|welcome| sanitize_field(welcome, MAX_WELCOME_CHARS, true, source).ok_or("welcome"));
+
+    let mut sanitized = MetadataText::default();
+    match name {
+        Some(Ok(name)) => sanitized.name = Some(name),
+        Some(Err(field)) => fields.push(field),
+        None => {}
+    }
+    match about {
+        Some(Ok(about)) => sanitized.about = Some(about),
+        Some(Err(field)) => fields.push(field),
+        None => {}
+    }
+    match welcome {
+        Some(Ok(welcome)) => sanitized.welcome = Some(welcome),
+        Some(Err(field)) => fields.push(field),
+        None => {}
+    }
+
+    if fields.is_empty() {
+        Ok(sanitized)
+    } else {
+        Err(InvalidMetadata { fields })
+    }
+}
+
+/// Sanitize a single automated name, e.g. from Overpass; None if nothing usable remains
+pub fn sanitize_automated_name(name: &str) -> Option<String> {
+    sanitize_field(name, MAX_NAME_CHARS, false, MetadataSource::Automated)
+}
+
+/// Sanitize an OSM opening_hours value; None if nothing is left or it is overlong, since a
+/// truncated spec would state different hours
+pub fn sanitize_opening_hours(value: &str) -> Option<String> {
+    let cleaned = clean(value);
+    (!cleaned.is_empty() && cleaned.chars().count() <= MAX_OPENING_HOURS_CHARS).then_some(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin(name: Option<&str>, about: Option<&str>) -> Result<MetadataText, InvalidMetadata> {
+        sanitize_metadata(
+            &MetadataText {
+                name: name.map(str::to_string),
+                about: about.map(str::to_string),
+                welcome: None,
+            },
+            MetadataSource::Admin,
+        )
+    }
+
+    #[test]
+    fn test_emoji_are_kept() {
+        let text = admin(Some("Café ☕ 👨‍👩‍👧 🇺🇸"), Some("Come hang out 🎉")).unwrap();
+        assert_eq!(text.name.as_deref(), Some("Café ☕ 👨‍👩‍👧 🇺🇸"));
+        assert_eq!(text.about.as_deref(), Some("Come hang out 🎉"));
+    }
+
+    #[test]
+    fn test_bidi_overrides_and_controls_are_stripped() {
+        let text = admin(
+            Some("\u{202E}gnp.exe\u{202C} Park"),
+            Some("Line one\n\nLine\ttwo\u{0007}  \u{2067}end\u{2069}"),
+        )
+        .unwrap();
+        assert_eq!(text.name.as_deref(), Some("gnp.exe Park"));
+        assert_eq!(text.about.as_deref(), Some("Line one Line two end"));
+    }
+
+    #[test]
+    fn test_nfc_normalization() {
+        let text = admin(Some("Cafe\u{0301}"), None).unwrap();
+        assert_eq!(text.name.as_deref(), Some("Caf\u{00E9}"));
+        assert_eq!(text.about, None);
+    }
+
+    #[test]
+    fn test_overlong_admin_input_lists_every_field() {
+        let name = "n".repeat(MAX_NAME_CHARS + 1);
+        let about = "a".repeat(10_000);
+        assert_eq!(
+            admin(Some(&name), Some(&about)),
+            Err(InvalidMetadata {
+                fields: vec!["name", "about"]
+            })
+        );
+
+        // Exactly at the limit (in characters, not bytes) is fine
+        let emoji_name = "☕".repeat(MAX_NAME_CHARS);
+        assert!(admin(Some(&emoji_name), Some(&"a".repeat(MAX_ABOUT_CHARS))).is_ok());
+    }
+
+    #[test]
+    fn test_welcome_is_cleaned_and_capped() {
+        let text = MetadataText {
+            welcome: Some("Welcome!\n\nHouse rules below \u{202E}👇".to_string()),
+            ..MetadataText::default()
+        };
+        let sanitized = sanitize_metadata(&text, MetadataSource::Admin).unwrap();
+        assert_eq!(
+            sanitized.welcome.as_deref(),
+            Some("Welcome! House rules below 👇")
+        );
+
+        let overlong = MetadataText {
+            welcome: Some("w".repeat(MAX_WELCOME_CHARS + 1)),
+            ..MetadataText::default()
+        };
+        assert_eq!(
+            sanitize_metadata(&overlong, MetadataSource::Admin),
+            Err(InvalidMetadata {
+                fields: vec!["welcome"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_empty_name_rejected_but_about_may_be_cleared() {
+        assert_eq!(
+            admin(Some(" \u{202E}\n "), Some("")),
+            Err(InvalidMetadata {
+                fields: vec!["name"]
+            })
+        );
+        assert_eq!(admin(None, Some("\n")).unwrap().about.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_automated_input_is_truncated() {
+        let poi = format!(
+            "{}\u{202E}{}",
+            "Very Long Place ".repeat(20),
+            "x".repeat(10_000)
+        );
+        let name = sanitize_automated_name(&poi).unwrap();
+        assert_eq!(name.chars().count(), MAX_NAME_CHARS);
+        assert!(name.starts_with("Very Long Place Very"));
+        assert!(name.ends_with("Place Very"));
+
+        // A cut landing on a space does not leave it dangling
+        let spaced = format!("{} tail", "x".repeat(MAX_NAME_CHARS - 1));
+        assert_eq!(
+            sanitize_automated_name(&spaced).unwrap(),
+            "x".repeat(MAX_NAME_CHARS - 1)
+        );
+
+        assert_eq!(sanitize_automated_name("\u{200F}\r\n"), None);
+    }
+}
@@ -0,0 +1,81 @@
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use zeroize::Zeroizing;
+
+const REDACTED: &str = "[redacted]";
+
+/// Secret loaded from the environment, such as a private key
+///
+/// Debug and Display print `[redacted]`, so a stray `{:?}` of the config or an error that
+/// embeds the value cannot leak it, and the memory is wiped when the last copy is dropped.
+/// Parse it into `Keys` once at startup and drop it.
+#[derive(Clone)]
+pub struct SecretString(secrecy::SecretString);
+
+impl SecretString {
+    /// The raw value, for parsing only; never log or format it
+    pub fn expose(&self) -> &str {
+        self.0.expose_secret()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expose().is_empty()
+    }
+}
+
+impl Default for SecretString {
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.into())
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // The intermediate String is wiped as soon as it has been copied into the secret
+        let raw = Zeroizing::new(String::deserialize(deserializer)?);
+        Ok(Self::from(raw.as_str()))
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "3f7a1b2c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f8";
+
+    #[test]
+    fn test_secret_is_redacted_in_every_format() {
+        let secret = SecretString::from(KEY);
+
+        assert_eq!(format!("{:?}", secret), "[redacted]");
+        assert_eq!(format!("{:#?}", secret), "[redacted]");
+        assert_eq!(secret.to_string(), "[redacted]");
+        assert_eq!(secret.expose(), KEY);
+    }
+
+    #[test]
+    fn test_secret_deserializes_from_a_plain_string() {
+        let secret: SecretString = serde_json::from_str(&format!("\"{}\"", KEY)).unwrap();
+        assert_eq!(secret.expose(), KEY);
+        assert!(SecretString::default().is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use super::clock::Clock;
+use super::rng::RngSource;
+
+/// Clock that only moves when told to
+pub struct ManualClock {
+    now: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(now_unix: u64) -> Self {
+        Self {
+            now: AtomicU64::new(now_unix),
+        }
+    }
+
+    pub fn set(&self, now_unix: u64) {
+        self.now.store(now_unix, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.now.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_unix(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// Deterministic randomness: the same seed always yields the same sequence
+pub struct SeededRng {
+    rng: Mutex<StdRng>,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RngSource for SeededRng {
+    fn next_f64(&self) -> f64 {
+        self.rng.lock().unwrap().gen::<f64>()
+    }
+}
+
+/// Randomness that always draws the same sample
+pub struct FixedRng(pub f64);
+
+impl RngSource for FixedRng {
+    fn next_f64(&self) -> f64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_moves_only_when_told() {
+        let clock = ManualClock::new(1_760_000_000);
+        assert_eq!(clock.now_unix(), 1_760_000_000);
+        clock.advance(90);
+        assert_eq!(clock.now().as_u64(), 1_760_000_090);
+        clock.set(5);
+        assert_eq!(clock.now_unix(), 5);
+    }
+
+    #[test]
+    fn test_seeded_rng_is_reproducible() {
+        let a = SeededRng::new(42);
+        let b = SeededRng::new(42);
+        for _ in 0..10 {
+            let x = a.gen_range_f64(0.0, 750.0);
+            assert_eq!(x, b.gen_range_f64(0.0, 750.0));
+            assert!((0.0..750.0).contains(&x));
+        }
+        assert!(a.gen_index(36) < 36);
+    }
+}
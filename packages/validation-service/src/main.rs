@@ -1,7 +1,7 @@
-use axum::{routing::get, Router};
+use axum::Router;
 use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod config;
@@ -10,17 +10,69 @@ mod libraries;
 mod models;
 mod services;
 
+// The in-process relay lives in the library; services tests reach it as crate::fake_relay
+// whichever crate compiles them
+#[cfg(test)]
+use validation_service::fake_relay;
+
 #[cfg(test)]
 mod test_gift_wrap;
 
 #[cfg(test)]
 mod test_h_tag_filter;
 
-use handlers::{health, NostrValidationHandler};
-use services::{community::CommunityService, relay::RelayService};
+#[cfg(test)]
+mod test_wire_contract;
+
+use handlers::{
+    admin, api_docs, community_events, community_preview, discovery, health_router,
+    listeners::{self, InternalServer, Routes},
+    schema, service_info, HealthState, NostrValidationHandler,
+};
+use libraries::clock::SystemClock;
+use libraries::community_id::CommunityIdPolicy;
+use libraries::exclusion_zones::ExclusionZones;
+use services::{
+    admin_audit::AdminFootprintAudit,
+    admin_jobs::{run_job_runner, AdminJobs, FINISHED_JOB_HISTORY},
+    audit_trail::{run_audit_writer, AuditTrail, AuditWriter, AUDIT_HISTORY},
+    client_pool::ClientPool,
+    community::CommunityService,
+    community_clusters::CommunityClusters,
+    creation_limit::CreationLimiter,
+    discovery_map::{DiscoveryMapSigner, DiscoveryMaps},
+    discovery_reconcile::run_discovery_reconciliation,
+    execution::ExecutionMode,
+    far_scans::FarScans,
+    group_feed::GroupFeed,
+    inactive_prune::{PruneLog, PRUNE_LOG_HISTORY},
+    itag_backfill::{parse_mapping_csv, ItagBackfill},
+    localities::refresh_discovery_localities,
+    operator_webhook::OperatorWebhook,
+    orphan_sweep::{OrphanSweep, SweepPolicy},
+    previous_refs::PreviousRefs,
+    public_relay::{verify_public_relay, NostrProbe, PublicRelayStatus},
+    relay::RelayService,
+    relay_access::{DiscoveryPublisher, GroupReader, GroupWriter},
+    relay_circuit::RelayCircuit,
+    sticker_reuse::StickerReuse,
+    subscription_watchdog::SubscriptionWatchdog,
+};
 
 #[tokio::main]
 async fn main() {
+    // `validation-service schema [PATH]` writes the protocol JSON Schema and exits, for CI and
+    // client codegen; without a path it goes to stdout
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("schema") {
+        let text = schema::protocol_schema_text();
+        match args.get(1) {
+            Some(path) => std::fs::write(path, text).expect("Failed to write schema"),
+            None => print!("{}", text),
+        }
+        return;
+    }
+
     // Initialize tracing
     tracing_subscriber::registry()
         .with(
@@ -32,60 +84,485 @@ async fn main() {
 
     // Load configuration
     dotenv::dotenv().ok();
-    let config = config::Config::from_env().expect("Failed to load configuration");
+    let mut config = config::Config::from_env().expect("Failed to load configuration");
 
     info!("Starting validation service (Nostr-only mode)");
 
+    // Parsed once; the raw secrets are wiped from the config before it is shared
+    let keys = config.take_keys().expect("Failed to parse secret keys");
+    let service_keys = keys.service.clone();
+
+    // Discovery map layout and signer (None keeps the relay key)
+    let discovery_maps = DiscoveryMaps {
+        d_tag: config.protocol.discovery_map_d_tag.clone(),
+        prefixes: config
+            .discovery_map_prefixes
+            .iter()
+            .map(|prefix| prefix.trim().to_string())
+            .filter(|prefix| !prefix.is_empty())
+            .collect(),
+        signer: match config.discovery_map_signer {
+            DiscoveryMapSigner::Relay => None,
+            DiscoveryMapSigner::Service => Some(service_keys.clone()),
+        },
+        exclusion_zones: ExclusionZones::parse(&config.exclusion_zones)
+            .expect("Invalid EXCLUSION_ZONES"),
+    };
+
     // Initialize relay service (single shared instance)
-    let relay_service =
-        RelayService::new(config.relay_url.clone(), config.relay_secret_key.clone())
-            .await
-            .expect("Failed to initialize relay service");
+    let relay_service = RelayService::new(
+        config.relay_url.clone(),
+        keys.relay.clone(),
+        config.protocol.clone(),
+        std::time::Duration::from_millis(config.discovery_geocode_budget_ms),
+        discovery_maps,
+        config.metadata_max_event_bytes,
+        RelayCircuit::new(
+            Arc::new(SystemClock),
+            config.relay_circuit_failure_threshold,
+            std::time::Duration::from_secs(config.relay_circuit_cool_down_secs),
+        ),
+        PreviousRefs::new(config.nip29_previous_refs),
+    )
+    .await
+    .expect("Failed to initialize relay service");
 
-    let relay_service_arc = Arc::new(tokio::sync::RwLock::new(relay_service));
+    // One connection and cache set, shared by role: reads take no lock and writes are
+    // serialized per group, so a slow create_group never stalls previews
+    let relay_limits = std::collections::BTreeMap::from([(
+        config.relay_url.to_string(),
+        relay_service.relay_limits().clone(),
+    )]);
+    let relay_key = relay_service.key_authorization();
+    let relay_circuit = relay_service.relay_circuit();
+    let relay_service = Arc::new(relay_service);
+
+    // Clients are told to connect to the public relay URL, so check it actually serves our groups
+    let public_relay = verify_public_relay(
+        &NostrProbe::new(keys.relay.clone()),
+        config.public_relay_url.as_str(),
+        &keys.relay.public_key(),
+        config.public_relay_sentinel_group.as_deref(),
+    )
+    .await;
+    match &public_relay {
+        PublicRelayStatus::Degraded { reason } => {
+            error!(
+                "Public relay check failed, clients may not find their groups: {}",
+                reason
+            )
+        }
+        status => info!(
+            "Public relay {} checked: {:?}",
+            config.public_relay_url, status
+        ),
+    }
+
+    // Soft launch: reads and validation run as usual, every relay mutation is only logged
+    let execution_mode = ExecutionMode::from_dry_run(config.read_only_mode);
+    if config.read_only_mode {
+        warn!("READ_ONLY_MODE is set: no group will be created or joined and nothing published");
+    }
+
+    let group_reader = GroupReader::new(relay_service.clone());
+    let group_writer = GroupWriter::new(relay_service.clone()).with_mode(execution_mode);
+    let discovery_publisher = DiscoveryPublisher::new(relay_service).with_mode(execution_mode);
+
+    // `validation-service backfill-itags [--dry-run] [--mappings CSV]` tags groups created
+    // before UUID tagging with their community's i-tag, prints the report and exits
+    if args.first().map(String::as_str) == Some("backfill-itags") {
+        if let Err(e) = backfill_itags(group_writer, &args[1..]).await {
+            error!("i-tag backfill failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     // Initialize community service with shared relay service
-    let community_service = CommunityService::new(relay_service_arc.clone());
+    let community_service = CommunityService::new(
+        group_reader.clone(),
+        group_writer.clone(),
+        CreationLimiter::new(config.max_concurrent_creations, config.creation_queue_limit),
+    );
     let community_service_arc = Arc::new(community_service);
 
+    // Shared pool for short-lived relay clients (inbox fan-out)
+    let client_pool = Arc::new(ClientPool::new(
+        config.client_pool_max,
+        std::time::Duration::from_secs(config.client_pool_idle_secs),
+    ));
+
+    // Watches the listener's relays for silently dropped subscriptions
+    let watchdog = Arc::new(SubscriptionWatchdog::new(
+        config.subscription_silence_secs,
+        config.subscription_reconnect_after,
+        Arc::new(SystemClock),
+    ));
+
     // Start Nostr validation handler in background
     let nostr_config = config.clone();
+    let nostr_keys = keys;
     let nostr_community_service = community_service_arc.clone();
-    let nostr_relay_service = relay_service_arc.clone();
+    let nostr_group_reader = group_reader.clone();
+    let nostr_group_writer = group_writer.clone();
+    let nostr_client_pool = client_pool.clone();
+    let nostr_watchdog = watchdog.clone();
+    // Inactive member prune runs, written by the handler's task and served to the admin API
+    let prune_log = Arc::new(PruneLog::new(PRUNE_LOG_HISTORY));
+    let nostr_prune_log = prune_log.clone();
+    // Joins from far outside a community, counted by the handler and listed by the admin API
+    let far_scans = Arc::new(FarScans::new(
+        config.far_scan_radius_km,
+        Arc::new(SystemClock),
+    ));
+    let nostr_far_scans = far_scans.clone();
+    // Communities whose sticker may have been copied, flagged by the handler, cleared by the API
+    let sticker_reuse = Arc::new(StickerReuse::new(
+        config.sticker_reuse_min_failures,
+        Arc::new(SystemClock),
+    ));
+    let nostr_sticker_reuse = sticker_reuse.clone();
+    // Admin actions over HTTP and gift wrap, sealed on the relay and mirrored to the webhook
+    let admin_tokens =
+        admin::AdminTokens::parse(config.admin_api_token.as_deref(), &config.admin_tokens)
+            .expect("Invalid ADMIN_TOKENS");
+    if !admin_tokens.is_empty() {
+        info!("Admin API tokens: {:?}", admin_tokens.labels());
+    }
+    let (audit_trail, audit_entries) = AuditTrail::new(Arc::new(SystemClock), AUDIT_HISTORY);
+    let audit_trail = Arc::new(audit_trail);
+    tokio::spawn(run_audit_writer(
+        AuditWriter::new(
+            discovery_publisher.clone(),
+            OperatorWebhook::new(config.operator_webhook_url.clone()),
+        ),
+        audit_entries,
+    ));
+    let nostr_audit_trail = audit_trail.clone();
+    // Exclusive community clusters, edited through the admin API and enforced on join
+    let clusters = Arc::new(CommunityClusters::new(discovery_publisher.clone()));
+    match clusters.load().await {
+        Ok(0) => {}
+        Ok(count) => info!("Loaded {} community cluster(s)", count),
+        Err(e) => error!(
+            "Failed to load community clusters, none are enforced: {}",
+            e
+        ),
+    }
+    let nostr_clusters = clusters.clone();
 
     tokio::spawn(async move {
         info!("Starting Nostr gift wrap listener");
 
-        let handler =
-            NostrValidationHandler::new(nostr_config, nostr_community_service, nostr_relay_service)
-                .await
-                .expect("Failed to initialize Nostr handler");
+        let handler = NostrValidationHandler::new(
+            nostr_config,
+            nostr_keys,
+            nostr_community_service,
+            nostr_group_reader,
+            nostr_group_writer,
+            nostr_client_pool,
+            nostr_watchdog,
+            nostr_prune_log,
+            nostr_far_scans,
+            nostr_sticker_reuse,
+            nostr_audit_trail,
+            nostr_clusters,
+        )
+        .await
+        .expect("Failed to initialize Nostr handler");
 
         if let Err(e) = handler.start().await {
             error!("Nostr handler failed: {}", e);
         }
     });
 
+    // Periodically close time-boxed communities whose deadline has passed
+    let archive_writer = group_writer.clone();
+    let archive_interval = std::time::Duration::from_secs(config.archive_sweep_interval_secs);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(archive_interval);
+        loop {
+            interval.tick().await;
+            match archive_writer.archive_expired_groups().await {
+                Ok(0) => {}
+                Ok(count) => info!("Closed {} expired communities", count),
+                Err(e) => error!("Failed to close expired communities: {}", e),
+            }
+        }
+    });
+
+    // Periodically geocode locality names for the discovery data, off the request path
+    let locality_publisher = discovery_publisher.clone();
+    let locality_interval =
+        std::time::Duration::from_secs(config.discovery_locality_interval_secs.max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(locality_interval);
+        loop {
+            interval.tick().await;
+            refresh_discovery_localities(&locality_publisher, &SystemClock).await;
+        }
+    });
+
+    // Periodically republish the discovery map if it no longer matches live group metadata
+    let reconcile_publisher = discovery_publisher.clone();
+    let reconcile_interval =
+        std::time::Duration::from_secs(config.discovery_reconcile_interval_secs.max(1));
+    let discovery_map_max_age_secs = config.discovery_map_max_age_secs;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(reconcile_interval);
+        loop {
+            interval.tick().await;
+            run_discovery_reconciliation(
+                &reconcile_publisher,
+                &SystemClock,
+                discovery_map_max_age_secs,
+            )
+            .await;
+        }
+    });
+
+    // Periodically re-resolve a few cached UUID mappings, evicting ones whose group is gone
+    let mapping_reader = group_reader.clone();
+    let mapping_interval =
+        std::time::Duration::from_secs(config.group_mapping_check_interval_secs.max(1));
+    let mapping_sample = config.group_mapping_check_sample;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(mapping_interval);
+        loop {
+            interval.tick().await;
+            match mapping_reader.verify_group_mappings(mapping_sample).await {
+                Ok(check) if check.vanished + check.remapped == 0 => {}
+                Ok(check) => warn!(
+                    "Fixed {} stale group mappings out of {} checked: {:?}",
+                    check.vanished + check.remapped,
+                    check.checked,
+                    check
+                ),
+                Err(e) => error!("Failed to verify cached group mappings: {}", e),
+            }
+        }
+    });
+
+    // Periodically find groups the relay key still administers and retry removing it
+    let admin_audit = Arc::new(AdminFootprintAudit::new(
+        group_writer.clone(),
+        Arc::new(SystemClock),
+    ));
+    let audit_task = admin_audit.clone();
+    let audit_interval = std::time::Duration::from_secs(config.admin_audit_interval_secs);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(audit_interval);
+        loop {
+            interval.tick().await;
+            match audit_task
+                .run_with(execution_mode)
+                .await
+                .map(|run| run.report)
+            {
+                Ok(report) if report.remaining.is_empty() => {}
+                Ok(report) => error!(
+                    "Relay key is still admin of {} communities: {:?}",
+                    report.remaining.len(),
+                    report.remaining
+                ),
+                Err(e) => error!("Failed to audit relay admin footprint: {}", e),
+            }
+        }
+    });
+
+    // Delete groups left by racing or partial creations; scheduled only when an interval is set
+    let orphan_sweep = Arc::new(OrphanSweep::new(
+        group_writer.clone(),
+        Arc::new(SystemClock),
+        SweepPolicy {
+            quarantine_secs: config.orphan_quarantine_secs,
+            idle_secs: config.orphan_idle_days.saturating_mul(24 * 60 * 60),
+        },
+    ));
+    if config.orphan_sweep_interval_secs > 0 {
+        let sweep_task = orphan_sweep.clone();
+        let sweep_interval = std::time::Duration::from_secs(config.orphan_sweep_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(sweep_interval);
+            loop {
+                interval.tick().await;
+                match sweep_task.run_with(execution_mode).await {
+                    Ok(run) if run.report.orphans.is_empty() => {}
+                    Ok(run) => info!(
+                        "Orphan group sweep deleted {} of {} groups ({} failed)",
+                        run.report.orphans.len() - run.report.failed.len(),
+                        run.report.scanned,
+                        run.report.failed.len()
+                    ),
+                    Err(e) => error!("Failed to sweep orphaned groups: {}", e),
+                }
+            }
+        });
+    }
+
+    // Slow admin operations requested over HTTP run here, one at a time
+    let (admin_jobs, admin_job_queue) = AdminJobs::new(Arc::new(SystemClock), FINISHED_JOB_HISTORY);
+    let admin_jobs = Arc::new(admin_jobs);
+    tokio::spawn(run_job_runner(admin_jobs.clone(), admin_job_queue));
+
+    // Periodically disconnect pooled relay clients nobody has used recently
+    let reap_interval = std::time::Duration::from_secs(config.client_pool_idle_secs.max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(reap_interval);
+        loop {
+            interval.tick().await;
+            client_pool.reap_idle().await;
+        }
+    });
+
     // Set up HTTP server for health checks
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let app = Router::new()
-        .route("/health", get(health))
-        .route("/api/health", get(health))
-        .layer(cors);
+    // Plain HTTP community previews for link unfurling bots
+    let id_policy = CommunityIdPolicy::new(config.allow_any_uuid);
+    let preview_state = Arc::new(community_preview::PreviewState::new(
+        group_reader.clone(),
+        id_policy,
+    ));
+
+    // Live community change feeds, all fed by one shared relay subscription
+    let group_feed = Arc::new(GroupFeed::new(group_reader.clone()));
+    let feed_task = group_feed.clone();
+    let feed_notifications = group_reader.notifications();
+    tokio::spawn(async move { feed_task.run(feed_notifications).await });
+    let events_state = Arc::new(community_events::EventsState::new(
+        group_feed,
+        config.event_stream_max_connections,
+        std::time::Duration::from_secs(config.event_stream_heartbeat_secs),
+        id_policy,
+    ));
+
+    // Capability descriptor mirrored from the published Nostr event
+    let service_descriptor = Arc::new(service_info::ServiceDescriptor::from_config(
+        &config,
+        &service_keys.public_key(),
+    ));
+
+    // Per-community cache refresh for support cases, instead of a restart
+    let community_caches = admin::CommunityCaches::new(group_writer.clone(), preview_state.clone());
+
+    let public_routes = Router::new()
+        .merge(health_router(Arc::new(HealthState {
+            watchdog,
+            relay_limits,
+            public_relay,
+            relay_key,
+            relay_circuit,
+            read_only: config.read_only_mode,
+        })))
+        .merge(community_preview::router(preview_state))
+        .merge(community_events::router(events_state))
+        .merge(discovery::router(Arc::new(group_reader)))
+        .merge(service_info::router(service_descriptor))
+        .merge(schema::router());
+    let public_routes = if config.enable_docs {
+        info!(
+            "API docs at {} and {}",
+            api_docs::OPENAPI_PATH,
+            api_docs::DOCS_PATH
+        );
+        public_routes.merge(api_docs::router())
+    } else {
+        public_routes
+    }
+    .layer(cors.clone());
+    let admin_routes = admin::router(Arc::new(admin::AdminState::new(
+        admin_audit,
+        discovery_publisher,
+        orphan_sweep,
+        community_caches,
+        prune_log,
+        far_scans,
+        sticker_reuse,
+        admin_jobs,
+        audit_trail,
+        clusters,
+        admin_tokens,
+    )))
+    .layer(cors);
 
     let addr: std::net::SocketAddr = format!("0.0.0.0:{}", config.port).parse().unwrap();
     info!("HTTP server listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+
+    // Metrics, and the admin API if asked, move to an operators-only address when one is set
+    let internal = match config.metrics_port {
+        Some(port) => {
+            let addr: std::net::SocketAddr = format!("{}:{}", config.metrics_bind, port)
+                .parse()
+                .expect("METRICS_BIND must be an IP address");
+            info!(
+                "Metrics{} listening on {}",
+                if config.metrics_serve_admin {
+                    " and admin API"
+                } else {
+                    ""
+                },
+                addr
+            );
+            Some(InternalServer {
+                listener: tokio::net::TcpListener::bind(addr).await.unwrap(),
+                with_admin: config.metrics_serve_admin,
+            })
+        }
+        None => None,
+    };
     info!("Validation service running. Listening for Nostr gift wrap messages and serving health endpoint.");
 
-    // Run the HTTP server
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app)
-        .await
-        .expect("Failed to start HTTP server");
+    // Run the HTTP servers until Ctrl-C or SIGTERM
+    listeners::serve(
+        listener,
+        internal,
+        Routes {
+            public: public_routes,
+            admin: admin_routes,
+        },
+        listeners::shutdown_signal(),
+    )
+    .await
+    .expect("Failed to start HTTP server");
 
     info!("Shutting down...");
 }
+
+/// Run the i-tag backfill once from the command line
+/// `--mappings` names a CSV of `group_id,uuid` lines for groups whose UUID can't be inferred.
+async fn backfill_itags(writer: GroupWriter, args: &[String]) -> anyhow::Result<()> {
+    let mut mode = ExecutionMode::Execute;
+    let mut mappings = std::collections::HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => mode = ExecutionMode::DryRun,
+            "--mappings" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--mappings needs a CSV path"))?;
+                mappings = parse_mapping_csv(&std::fs::read_to_string(path)?)?;
+            }
+            other => anyhow::bail!("Unknown backfill-itags argument {:?}", other),
+        }
+    }
+
+    let run = ItagBackfill::new(writer).run_with(mode, &mappings).await?;
+    info!(
+        "i-tag backfill ({:?}): tagged {} of {} groups, {} already tagged, {} unmapped, {} conflicts, {} failed",
+        mode,
+        run.report.tagged.len(),
+        run.report.scanned,
+        run.report.already_tagged,
+        run.report.unmapped.len(),
+        run.report.conflicts.len(),
+        run.report.failed.len()
+    );
+    println!("{}", serde_json::to_string_pretty(&run)?);
+    Ok(())
+}
@@ -1,7 +1,183 @@
-use serde::{Deserialize, Serialize};
+pub use peek_geo::{Coordinates, InvalidCoordinates, InvalidGeohash};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LocationPoint {
-    pub latitude: f64,
-    pub longitude: f64,
+/// Reported accuracy beyond this is not a usable fix for any geohash check
+pub const MAX_REPORTED_ACCURACY_METERS: f64 = 10_000.0;
+
+/// How precise the client says its fix is
+///
+/// iOS reports a horizontalAccuracy of -1 for an invalid fix, Android sometimes sends 0 for the
+/// same condition, and the web Geolocation API may leave accuracy out. All of these mean the
+/// precision is unknown: enough to join, where the geohash check decides, but not to create.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccuracyReading {
+    /// Radius of the fix in meters
+    Meters(f64),
+    Unknown,
+}
+
+impl AccuracyReading {
+    /// Classify a reported accuracy, refusing values no platform uses for "unknown"
+    pub fn parse(accuracy: Option<f64>) -> Result<Self, InvalidLocationData> {
+        match accuracy {
+            None => Ok(Self::Unknown),
+            Some(meters) if meters == -1.0 || meters == 0.0 => Ok(Self::Unknown),
+            Some(meters) if !meters.is_finite() || meters < 0.0 => {
+                Err(InvalidLocationData::Accuracy)
+            }
+            Some(meters) if meters > MAX_REPORTED_ACCURACY_METERS => {
+                Err(InvalidLocationData::AccuracyTooCoarse)
+            }
+            Some(meters) => Ok(Self::Meters(meters)),
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown)
+    }
+}
+
+/// A client location that passed `check_location_data`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocationFix {
+    pub coordinates: Coordinates,
+    pub accuracy: AccuracyReading,
+}
+
+/// Why client-reported location data was refused before any processing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidLocationData {
+    #[error("Coordinates must be finite and within latitude/longitude range")]
+    Coordinates,
+    #[error("Accuracy must be a positive, finite number of meters, or -1, 0 or absent if unknown")]
+    Accuracy,
+    #[error("Accuracy of more than 10km is not a usable location")]
+    AccuracyTooCoarse,
+}
+
+impl From<InvalidCoordinates> for InvalidLocationData {
+    fn from(_: InvalidCoordinates) -> Self {
+        Self::Coordinates
+    }
+}
+
+/// Sanity-check raw client location fields, returning the position and accuracy they describe
+///
+/// Values arrive as f64 straight from JSON, so NaN, infinities, negative accuracy and
+/// out-of-range coordinates are all representable. Every entry point taking a client
+/// location (gift-wrapped requests, location proofs, HTTP) runs this first.
+pub fn check_location_data(
+    latitude: f64,
+    longitude: f64,
+    accuracy: Option<f64>,
+) -> Result<LocationFix, InvalidLocationData> {
+    Ok(LocationFix {
+        coordinates: Coordinates::new(latitude, longitude)?,
+        accuracy: AccuracyReading::parse(accuracy)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const GARBAGE_ACCURACY: &[f64] = &[
+        -0.5,
+        -2.0,
+        -f64::MIN_POSITIVE,
+        f64::NAN,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        10_000.001,
+        f64::MAX,
+    ];
+
+    const GARBAGE_LATITUDE: &[f64] = &[f64::NAN, f64::INFINITY, -90.5, 91.0, f64::MAX];
+
+    const GARBAGE_LONGITUDE: &[f64] = &[f64::NEG_INFINITY, f64::NAN, 180.5, -1_000.0, f64::MIN];
+
+    #[test]
+    fn test_accepts_plausible_fixes() {
+        let fix = check_location_data(37.7749, -122.4194, Some(12.5)).unwrap();
+        assert_eq!(
+            (fix.coordinates.latitude(), fix.coordinates.longitude()),
+            (37.7749, -122.4194)
+        );
+        assert_eq!(fix.accuracy, AccuracyReading::Meters(12.5));
+        assert!(check_location_data(-90.0, 180.0, Some(0.1)).is_ok());
+        assert!(check_location_data(0.0, 0.0, Some(MAX_REPORTED_ACCURACY_METERS)).is_ok());
+    }
+
+    #[test]
+    fn test_platform_placeholders_read_as_unknown_accuracy() {
+        // iOS invalid fix, Android invalid fix (either sign), and a web fix without accuracy
+        for accuracy in [Some(-1.0), Some(0.0), Some(-0.0), None] {
+            let fix = check_location_data(37.7749, -122.4194, accuracy).unwrap();
+            assert_eq!(fix.accuracy, AccuracyReading::Unknown, "{:?}", accuracy);
+        }
+        assert_eq!(
+            AccuracyReading::parse(Some(5.0)),
+            Ok(AccuracyReading::Meters(5.0))
+        );
+    }
+
+    #[test]
+    fn test_rejects_every_garbage_accuracy() {
+        for accuracy in GARBAGE_ACCURACY {
+            assert!(
+                check_location_data(37.7749, -122.4194, Some(*accuracy)).is_err(),
+                "accuracy {} accepted",
+                accuracy
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_every_garbage_coordinate() {
+        for latitude in GARBAGE_LATITUDE {
+            assert_eq!(
+                check_location_data(*latitude, -122.4194, Some(12.5)),
+                Err(InvalidLocationData::Coordinates)
+            );
+        }
+        for longitude in GARBAGE_LONGITUDE {
+            assert_eq!(
+                check_location_data(37.7749, *longitude, Some(12.5)),
+                Err(InvalidLocationData::Coordinates)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fuzzed_inputs_with_any_garbage_field_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(881);
+        let pick = |rng: &mut StdRng, garbage: &[f64], valid: f64| {
+            if rng.gen_bool(0.5) {
+                (garbage[rng.gen_range(0..garbage.len())], true)
+            } else {
+                (valid, false)
+            }
+        };
+
+        for _ in 0..10_000 {
+            let valid_lat = rng.gen_range(-90.0..=90.0);
+            let valid_lon = rng.gen_range(-180.0..=180.0);
+            let valid_acc = rng.gen_range(0.5..=500.0);
+            let (latitude, bad_lat) = pick(&mut rng, GARBAGE_LATITUDE, valid_lat);
+            let (longitude, bad_lon) = pick(&mut rng, GARBAGE_LONGITUDE, valid_lon);
+            let (accuracy, bad_acc) = pick(&mut rng, GARBAGE_ACCURACY, valid_acc);
+
+            let result = check_location_data(latitude, longitude, Some(accuracy));
+            assert_eq!(
+                result.is_err(),
+                bad_lat || bad_lon || bad_acc,
+                "({}, {}, {}) -> {:?}",
+                latitude,
+                longitude,
+                accuracy,
+                result
+            );
+        }
+    }
 }
@@ -1,5 +1,10 @@
 pub mod community;
 pub mod location;
+pub mod protocol;
 
 // Re-export commonly used types
-pub use location::LocationPoint;
+pub use location::{
+    check_location_data, AccuracyReading, Coordinates, InvalidGeohash, InvalidLocationData,
+    LocationFix,
+};
+pub use protocol::ProtocolConfig;
@@ -0,0 +1,197 @@
+use nostr_sdk::Kind;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::libraries::community_id::CommunitySlug;
+use crate::libraries::rng::RngSource;
+
+/// Protocol constants that namespace a Peek deployment on shared public relays
+/// Two deployments with different values ignore each other's traffic and groups
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProtocolConfig {
+    // Rumor kind for incoming service requests (ephemeral range)
+    #[serde(default = "default_request_kind")]
+    pub request_kind: u16,
+
+    // Rumor kind for outgoing service responses (ephemeral range)
+    #[serde(default = "default_response_kind")]
+    pub response_kind: u16,
+
+    // NIP-73 identifier kind stored in the k-tag; the i-tag is "{uuid_namespace}:{uuid}"
+    #[serde(default = "default_uuid_namespace")]
+    pub uuid_namespace: String,
+
+    // Community slugs are published as an extra i-tag "{slug_namespace}:{slug}"
+    #[serde(default = "default_slug_namespace")]
+    pub slug_namespace: String,
+
+    // Prefix for generated NIP-29 group ids (h-tag)
+    #[serde(default = "default_group_id_prefix")]
+    pub group_id_prefix: String,
+
+    // d-tag of the NIP-78 discovery map event
+    #[serde(default = "default_discovery_map_d_tag")]
+    pub discovery_map_d_tag: String,
+}
+
+impl ProtocolConfig {
+    pub fn request_kind(&self) -> Kind {
+        Kind::Custom(self.request_kind)
+    }
+
+    pub fn response_kind(&self) -> Kind {
+        Kind::Custom(self.response_kind)
+    }
+
+    /// The NIP-73 i-tag value for a community UUID
+    pub fn uuid_tag(&self, community_id: &Uuid) -> String {
+        format!("{}:{}", self.uuid_namespace, community_id)
+    }
+
+    /// Parse a community UUID out of an i-tag value belonging to this namespace
+    pub fn parse_uuid_tag(&self, value: &str) -> Option<Uuid> {
+        value
+            .strip_prefix(self.uuid_namespace.as_str())
+            .and_then(|rest| rest.strip_prefix(':'))
+            .and_then(|uuid| Uuid::parse_str(uuid).ok())
+    }
+
+    /// The i-tag value marking a group as the owner of a community slug
+    pub fn slug_tag(&self, slug: &CommunitySlug) -> String {
+        format!("{}:{}", self.slug_namespace, slug.as_str())
+    }
+
+    /// Whether an i-tag value is a slug of this namespace
+    pub fn is_slug_tag(&self, value: &str) -> bool {
+        value
+            .strip_prefix(self.slug_namespace.as_str())
+            .is_some_and(|rest| rest.starts_with(':'))
+    }
+
+    /// Generate a random group identifier for the NIP-29 h-tag
+    /// Format: {group_id_prefix}{10 random alphanumeric chars}
+    pub fn generate_group_id(&self, rng: &dyn RngSource) -> String {
+        const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let id: String = (0..10)
+            .map(|_| CHARSET[rng.gen_index(CHARSET.len())] as char)
+            .collect();
+        format!("{}{}", self.group_id_prefix, id)
+    }
+
+    /// Whether a group id was generated by this deployment
+    pub fn owns_group_id(&self, group_id: &str) -> bool {
+        group_id.starts_with(&self.group_id_prefix)
+    }
+}
+
+impl Default for ProtocolConfig {
+    fn default() -> Self {
+        Self {
+            request_kind: default_request_kind(),
+            response_kind: default_response_kind(),
+            uuid_namespace: default_uuid_namespace(),
+            slug_namespace: default_slug_namespace(),
+            group_id_prefix: default_group_id_prefix(),
+            discovery_map_d_tag: default_discovery_map_d_tag(),
+        }
+    }
+}
+
+fn default_request_kind() -> u16 {
+    27492
+}
+
+fn default_response_kind() -> u16 {
+    27493
+}
+
+fn default_uuid_namespace() -> String {
+    "peek:uuid".to_string()
+}
+
+fn default_slug_namespace() -> String {
+    "peek:slug".to_string()
+}
+
+fn default_group_id_prefix() -> String {
+    "peek-".to_string()
+}
+
+fn default_discovery_map_d_tag() -> String {
+    "peek.discovery-map".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::rng::ThreadRngSource;
+    use crate::libraries::test_support::SeededRng;
+
+    fn acme() -> ProtocolConfig {
+        ProtocolConfig {
+            request_kind: 27592,
+            response_kind: 27593,
+            uuid_namespace: "acme:uuid".to_string(),
+            slug_namespace: "acme:slug".to_string(),
+            group_id_prefix: "acme-".to_string(),
+            discovery_map_d_tag: "acme.discovery-map".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_defaults_match_original_protocol() {
+        let peek = ProtocolConfig::default();
+        let id = Uuid::parse_str("3a7e5c59-c0a1-4876-acf1-56189b86aa0d").unwrap();
+
+        assert_eq!(peek.request_kind(), Kind::Custom(27492));
+        assert_eq!(peek.response_kind(), Kind::Custom(27493));
+        assert_eq!(
+            peek.uuid_tag(&id),
+            "peek:uuid:3a7e5c59-c0a1-4876-acf1-56189b86aa0d"
+        );
+        assert_eq!(
+            peek.slug_tag(&CommunitySlug::parse("blue-bottle").unwrap()),
+            "peek:slug:blue-bottle"
+        );
+        assert_eq!(peek.discovery_map_d_tag, "peek.discovery-map");
+        assert!(peek
+            .generate_group_id(&ThreadRngSource)
+            .starts_with("peek-"));
+    }
+
+    #[test]
+    fn test_namespaces_are_isolated() {
+        let peek = ProtocolConfig::default();
+        let acme = acme();
+        let id = Uuid::new_v4();
+
+        // Each deployment only recognizes its own i-tags
+        assert_eq!(peek.parse_uuid_tag(&peek.uuid_tag(&id)), Some(id));
+        assert_eq!(acme.parse_uuid_tag(&acme.uuid_tag(&id)), Some(id));
+        assert_eq!(peek.parse_uuid_tag(&acme.uuid_tag(&id)), None);
+        assert_eq!(acme.parse_uuid_tag(&peek.uuid_tag(&id)), None);
+
+        // Group ids and kinds don't overlap
+        assert!(!peek.owns_group_id(&acme.generate_group_id(&ThreadRngSource)));
+        assert!(!acme.owns_group_id(&peek.generate_group_id(&ThreadRngSource)));
+        assert_ne!(peek.request_kind(), acme.request_kind());
+        assert_ne!(peek.discovery_map_d_tag, acme.discovery_map_d_tag);
+    }
+
+    #[test]
+    fn test_group_id_is_deterministic_with_seeded_rng() {
+        let peek = ProtocolConfig::default();
+        let first = peek.generate_group_id(&SeededRng::new(1));
+        assert_eq!(first, peek.generate_group_id(&SeededRng::new(1)));
+        assert_eq!(first.len(), "peek-".len() + 10);
+    }
+
+    #[test]
+    fn test_parse_uuid_tag_rejects_prefix_collisions() {
+        let peek = ProtocolConfig::default();
+        let id = Uuid::new_v4();
+
+        assert_eq!(peek.parse_uuid_tag(&format!("peek:uuidx:{}", id)), None);
+        assert_eq!(peek.parse_uuid_tag("peek:uuid:not-a-uuid"), None);
+    }
+}
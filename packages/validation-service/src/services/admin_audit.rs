@@ -0,0 +1,290 @@
+use serde::Serialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use super::admin_jobs::ScanProgress;
+use super::execution::{ExecutionMode, MutationPlan};
+use super::metrics;
+use super::relay_access::GroupWriter;
+use crate::libraries::clock::Clock;
+
+/// Where the audit finds groups still administered by the relay key, and retries removal
+pub trait AdminFootprintSource: Send + Sync + 'static {
+    /// Ids of groups whose kind 39001 admin list still names the relay key
+    fn groups_with_relay_admin(&self) -> impl Future<Output = anyhow::Result<Vec<String>>> + Send;
+
+    /// Re-send the kind 9001 removal, recording it in `plan` (and only recording it in a dry
+    /// run); true once the admin list no longer names the relay key
+    fn remove_relay_admin(
+        &self,
+        group_id: &str,
+        plan: &mut MutationPlan,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send;
+}
+
+impl AdminFootprintSource for GroupWriter {
+    async fn groups_with_relay_admin(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self.reader().groups_with_relay_admin().await?)
+    }
+
+    async fn remove_relay_admin(
+        &self,
+        group_id: &str,
+        plan: &mut MutationPlan,
+    ) -> anyhow::Result<bool> {
+        let mut execution = self.execution(plan.mode());
+        let removed = self.remove_relay_admin(group_id, &mut execution).await;
+        plan.extend(execution.into_plan());
+        Ok(removed?)
+    }
+}
+
+/// Outcome of one audit pass, served by GET /api/admin/relay-footprint
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FootprintReport {
+    pub audited_at: u64,
+    // Groups found with the relay key still among their admins
+    pub flagged: usize,
+    // Of those, groups where a retried removal took effect
+    pub removed: usize,
+    // Groups the relay key still administers after the retry
+    pub remaining: Vec<String>,
+}
+
+/// A pass's report with the kind 9001 removals it sent, or would send in a dry run
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRun {
+    #[serde(flatten)]
+    pub plan: MutationPlan,
+    pub report: FootprintReport,
+}
+
+/// Periodic check that the relay key does not keep admin rights over communities
+///
+/// create_group removes the relay key right after creating a group, but that removal can fail
+/// (timeouts, relay restarts) without failing the creation. Each pass finds every group whose
+/// admin list still names the relay key, retries the removal, and reports what is left.
+pub struct AdminFootprintAudit<S> {
+    source: S,
+    clock: Arc<dyn Clock>,
+    last_report: Mutex<Option<FootprintReport>>,
+}
+
+impl<S: AdminFootprintSource> AdminFootprintAudit<S> {
+    pub fn new(source: S, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            source,
+            clock,
+            last_report: Mutex::new(None),
+        }
+    }
+
+    /// Run one audit pass, recording the report and the peek_relay_admin_groups gauge
+    pub async fn run(&self) -> anyhow::Result<FootprintReport> {
+        Ok(self.run_with(ExecutionMode::Execute).await?.report)
+    }
+
+    /// Run one pass in `mode`; a dry run reports what it would remove but records nothing
+    pub async fn run_with(&self, mode: ExecutionMode) -> anyhow::Result<AuditRun> {
+        self.run_reporting(mode, &|_| {}).await
+    }
+
+    /// Run one pass, reporting each flagged group handled to `progress`
+    pub async fn run_reporting(
+        &self,
+        mode: ExecutionMode,
+        progress: &(dyn Fn(ScanProgress) + Sync),
+    ) -> anyhow::Result<AuditRun> {
+        let flagged = self.source.groups_with_relay_admin().await?;
+        let mut remaining = Vec::new();
+        let mut plan = MutationPlan::new(mode);
+        let mut scan = ScanProgress {
+            total: flagged.len(),
+            ..Default::default()
+        };
+        progress(scan);
+
+        for group_id in &flagged {
+            match self.source.remove_relay_admin(group_id, &mut plan).await {
+                Ok(true) => info!("Removed lingering relay admin role from {}", group_id),
+                Ok(false) => {
+                    warn!("Relay key is still an admin of {} after retry", group_id);
+                    remaining.push(group_id.clone());
+                }
+                Err(e) => {
+                    warn!(
+                        "Retrying relay admin removal for {} failed: {}",
+                        group_id, e
+                    );
+                    remaining.push(group_id.clone());
+                }
+            }
+            scan.processed += 1;
+            scan.errors = remaining.len();
+            progress(scan);
+        }
+
+        let report = FootprintReport {
+            audited_at: self.clock.now_unix(),
+            flagged: flagged.len(),
+            removed: flagged.len() - remaining.len(),
+            remaining,
+        };
+        if mode == ExecutionMode::Execute {
+            metrics::add(
+                "peek_relay_admin_removal_retries_total",
+                &[],
+                flagged.len() as u64,
+            );
+            metrics::set_gauge(
+                "peek_relay_admin_groups",
+                &[],
+                report.remaining.len() as u64,
+            );
+            *self.last_report.lock().unwrap() = Some(report.clone());
+        }
+        Ok(AuditRun { plan, report })
+    }
+
+    /// Report of the most recent completed pass (None until the first one finishes)
+    pub fn last_report(&self) -> Option<FootprintReport> {
+        self.last_report.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+    use crate::services::execution::tests::CountingSender;
+    use crate::services::execution::Execution;
+    use nostr_sdk::{EventBuilder, Keys, Kind, Tag, TagKind};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// Relay whose "stubborn" groups ignore every removal
+    struct FakeRelay {
+        admin_of: Mutex<BTreeSet<String>>,
+        stubborn: BTreeSet<String>,
+        attempts: Mutex<BTreeMap<String, u32>>,
+        keys: Keys,
+        sender: CountingSender,
+    }
+
+    impl FakeRelay {
+        fn new(admin_of: &[&str], stubborn: &[&str]) -> Self {
+            Self {
+                admin_of: Mutex::new(admin_of.iter().map(|g| g.to_string()).collect()),
+                stubborn: stubborn.iter().map(|g| g.to_string()).collect(),
+                attempts: Mutex::new(BTreeMap::new()),
+                keys: Keys::generate(),
+                sender: CountingSender::default(),
+            }
+        }
+
+        fn attempts(&self, group_id: &str) -> u32 {
+            self.attempts
+                .lock()
+                .unwrap()
+                .get(group_id)
+                .copied()
+                .unwrap_or(0)
+        }
+    }
+
+    impl AdminFootprintSource for Arc<FakeRelay> {
+        async fn groups_with_relay_admin(&self) -> anyhow::Result<Vec<String>> {
+            Ok(self.admin_of.lock().unwrap().iter().cloned().collect())
+        }
+
+        async fn remove_relay_admin(
+            &self,
+            group_id: &str,
+            plan: &mut MutationPlan,
+        ) -> anyhow::Result<bool> {
+            let removal = EventBuilder::new(Kind::from(9001), "")
+                .tags([
+                    Tag::custom(TagKind::Custom("h".into()), [group_id]),
+                    Tag::custom(
+                        TagKind::Custom("p".into()),
+                        [self.keys.public_key().to_string()],
+                    ),
+                ])
+                .sign_with_keys(&self.keys)?;
+            let mut execution = Execution::new(&self.sender, plan.mode());
+            execution.publish(&removal).await?;
+            plan.extend(execution.into_plan());
+
+            *self
+                .attempts
+                .lock()
+                .unwrap()
+                .entry(group_id.to_string())
+                .or_default() += 1;
+            if plan.dry_run || self.stubborn.contains(group_id) {
+                return Ok(false);
+            }
+            Ok(self.admin_of.lock().unwrap().remove(group_id))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_retries_and_reports_stubborn_group() {
+        let relay = Arc::new(FakeRelay::new(
+            &["peek-clean", "peek-stubborn"],
+            &["peek-stubborn"],
+        ));
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let audit = AdminFootprintAudit::new(relay.clone(), clock.clone());
+        assert_eq!(audit.last_report(), None);
+
+        let first = audit.run().await.unwrap();
+        assert_eq!(first.flagged, 2);
+        assert_eq!(first.removed, 1);
+        assert_eq!(first.remaining, vec!["peek-stubborn".to_string()]);
+        assert_eq!(metrics::get("peek_relay_admin_groups", &[]), 1);
+
+        // The cleaned group drops out; the stubborn one is retried on every pass
+        clock.advance(3600);
+        let second = audit.run().await.unwrap();
+        assert_eq!(second.flagged, 1);
+        assert_eq!(second.remaining, vec!["peek-stubborn".to_string()]);
+        assert_eq!(relay.attempts("peek-clean"), 1);
+        assert_eq!(relay.attempts("peek-stubborn"), 2);
+        assert_eq!(audit.last_report().unwrap().audited_at, 1_760_003_600);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_plans_removals_without_sending() {
+        let relay = Arc::new(FakeRelay::new(
+            &["peek-clean", "peek-stubborn"],
+            &["peek-stubborn"],
+        ));
+        let audit = AdminFootprintAudit::new(relay.clone(), Arc::new(ManualClock::new(0)));
+
+        let run = audit.run_with(ExecutionMode::DryRun).await.unwrap();
+        assert_eq!(relay.sender.sent(), 0);
+        assert!(run.plan.dry_run);
+        let planned: Vec<(u16, Option<&str>, &[String])> = run
+            .plan
+            .events
+            .iter()
+            .map(|e| (e.kind, e.group_id.as_deref(), e.pubkeys.as_slice()))
+            .collect();
+        let relay_key = [relay.keys.public_key().to_string()];
+        assert_eq!(
+            planned,
+            vec![
+                (9001, Some("peek-clean"), &relay_key[..]),
+                (9001, Some("peek-stubborn"), &relay_key[..]),
+            ]
+        );
+
+        // Nothing changed, and the served report still waits for a real pass
+        assert_eq!(run.report.flagged, 2);
+        assert_eq!(audit.last_report(), None);
+        assert_eq!(audit.run().await.unwrap().removed, 1);
+        assert_eq!(relay.sender.sent(), 2);
+    }
+}
@@ -0,0 +1,355 @@
+//! Background jobs for admin operations too slow to answer within an HTTP request
+//!
+//! An orphan sweep or admin audit against a large relay takes minutes, well past proxy
+//! timeouts. The admin endpoints queue the operation and answer with a job id at once; a single
+//! runner works through the queue in order, recording each scan's progress as it goes, and
+//! GET /api/admin/jobs/:id reports it. Finished jobs are kept in memory, oldest dropped first.
+
+use futures::future::{BoxFuture, FutureExt};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::metrics;
+use crate::libraries::clock::Clock;
+
+/// Jobs waiting for the runner before new ones are refused
+pub const MAX_QUEUED_JOBS: usize = 16;
+
+/// Finished jobs kept for GET /api/admin/jobs/:id
+pub const FINISHED_JOB_HISTORY: usize = 100;
+
+/// How far a scan over many items has got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ScanProgress {
+    // Items the scan found to work through
+    pub total: usize,
+    pub processed: usize,
+    // Processed items that failed
+    pub errors: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+/// A job as reported by GET /api/admin/jobs/:id
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: &'static str,
+    pub status: JobStatus,
+    pub progress: ScanProgress,
+    pub queued_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    // The operation's report, once done
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+type JobWork =
+    Box<dyn FnOnce(JobProgress) -> BoxFuture<'static, anyhow::Result<serde_json::Value>> + Send>;
+
+/// A job handed to the runner
+pub struct QueuedJob {
+    id: Uuid,
+    kind: &'static str,
+    work: JobWork,
+}
+
+#[derive(Default)]
+struct JobTable {
+    jobs: HashMap<Uuid, Job>,
+    // Finished job ids, oldest first
+    finished: VecDeque<Uuid>,
+}
+
+/// Where a running job records its scan progress
+#[derive(Clone)]
+pub struct JobProgress {
+    table: Arc<Mutex<JobTable>>,
+    id: Uuid,
+}
+
+impl JobProgress {
+    pub fn report(&self, progress: ScanProgress) {
+        if let Some(job) = self.table.lock().unwrap().jobs.get_mut(&self.id) {
+            job.progress = progress;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("{0} admin jobs are already queued")]
+pub struct JobQueueFull(pub usize);
+
+/// Admin jobs by id: queued, running, and the most recent finished ones
+pub struct AdminJobs {
+    clock: Arc<dyn Clock>,
+    history: usize,
+    table: Arc<Mutex<JobTable>>,
+    tx: mpsc::Sender<QueuedJob>,
+}
+
+impl AdminJobs {
+    /// Keeps up to `history` finished jobs; the receiver goes to `run_job_runner`
+    pub fn new(clock: Arc<dyn Clock>, history: usize) -> (Self, mpsc::Receiver<QueuedJob>) {
+        let (tx, rx) = mpsc::channel(MAX_QUEUED_JOBS);
+        let jobs = Self {
+            clock,
+            history: history.max(1),
+            table: Arc::new(Mutex::new(JobTable::default())),
+            tx,
+        };
+        (jobs, rx)
+    }
+
+    /// Queue `work` for the runner and return the job as queued
+    pub fn submit<F, Fut>(&self, kind: &'static str, work: F) -> Result<Job, JobQueueFull>
+    where
+        F: FnOnce(JobProgress) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<serde_json::Value>> + Send + 'static,
+    {
+        let job = Job {
+            id: Uuid::new_v4(),
+            kind,
+            status: JobStatus::Queued,
+            progress: ScanProgress::default(),
+            queued_at: self.clock.now_unix(),
+            started_at: None,
+            finished_at: None,
+            result: None,
+            error: None,
+        };
+        // Listed before it is sent, so the runner always finds it
+        self.table.lock().unwrap().jobs.insert(job.id, job.clone());
+
+        let queued = QueuedJob {
+            id: job.id,
+            kind,
+            work: Box::new(move |progress| work(progress).boxed()),
+        };
+        if self.tx.try_send(queued).is_err() {
+            self.table.lock().unwrap().jobs.remove(&job.id);
+            return Err(JobQueueFull(MAX_QUEUED_JOBS));
+        }
+        Ok(job)
+    }
+
+    /// The job, unless it is unknown or has dropped out of the finished history
+    pub fn get(&self, id: &Uuid) -> Option<Job> {
+        self.table.lock().unwrap().jobs.get(id).cloned()
+    }
+
+    fn start(&self, id: Uuid) -> JobProgress {
+        if let Some(job) = self.table.lock().unwrap().jobs.get_mut(&id) {
+            job.status = JobStatus::Running;
+            job.started_at = Some(self.clock.now_unix());
+        }
+        JobProgress {
+            table: self.table.clone(),
+            id,
+        }
+    }
+
+    fn finish(&self, id: Uuid, outcome: anyhow::Result<serde_json::Value>) {
+        let mut table = self.table.lock().unwrap();
+        if let Some(job) = table.jobs.get_mut(&id) {
+            job.finished_at = Some(self.clock.now_unix());
+            match outcome {
+                Ok(result) => {
+                    job.status = JobStatus::Done;
+                    job.result = Some(result);
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+        }
+        table.finished.push_back(id);
+        while table.finished.len() > self.history {
+            if let Some(oldest) = table.finished.pop_front() {
+                table.jobs.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Run queued admin jobs one at a time, in the order they were submitted
+pub async fn run_job_runner(jobs: Arc<AdminJobs>, mut rx: mpsc::Receiver<QueuedJob>) {
+    while let Some(job) = rx.recv().await {
+        info!("🧰 Admin job {} ({}) started", job.id, job.kind);
+        let progress = jobs.start(job.id);
+        // A panicking job fails on its own instead of taking the runner down with it
+        let outcome = match AssertUnwindSafe((job.work)(progress)).catch_unwind().await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(anyhow::anyhow!("job panicked")),
+        };
+        let status = match &outcome {
+            Ok(_) => {
+                info!("🧰 Admin job {} ({}) done", job.id, job.kind);
+                "done"
+            }
+            Err(e) => {
+                error!("❌ Admin job {} ({}) failed: {}", job.id, job.kind, e);
+                "failed"
+            }
+        };
+        metrics::increment(
+            "peek_admin_jobs_total",
+            &[("kind", job.kind), ("status", status)],
+        );
+        jobs.finish(job.id, outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+
+    fn start_jobs(history: usize) -> Arc<AdminJobs> {
+        let (jobs, rx) = AdminJobs::new(Arc::new(ManualClock::new(1_760_000_000)), history);
+        let jobs = Arc::new(jobs);
+        tokio::spawn(run_job_runner(jobs.clone(), rx));
+        jobs
+    }
+
+    /// Let the runner go until the job reaches `status`
+    async fn wait_for(jobs: &AdminJobs, id: Uuid, status: JobStatus) -> Job {
+        for _ in 0..100 {
+            let job = jobs.get(&id).unwrap();
+            if job.status == status {
+                return job;
+            }
+            tokio::task::yield_now().await;
+        }
+        panic!("job {} never reached {:?}", id, status);
+    }
+
+    /// A scan over three items that processes one each time `step` is signalled
+    fn stepped_scan(
+        mut step: mpsc::UnboundedReceiver<()>,
+        failing_item: Option<usize>,
+    ) -> impl FnOnce(JobProgress) -> BoxFuture<'static, anyhow::Result<serde_json::Value>> + Send
+    {
+        move |progress| {
+            async move {
+                let mut scan = ScanProgress {
+                    total: 3,
+                    ..Default::default()
+                };
+                progress.report(scan);
+                for item in 0..3 {
+                    step.recv().await;
+                    scan.processed += 1;
+                    if failing_item == Some(item) {
+                        scan.errors += 1;
+                        progress.report(scan);
+                        anyhow::bail!("relay refused item {}", item);
+                    }
+                    progress.report(scan);
+                }
+                Ok(serde_json::json!({ "scanned": 3 }))
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_job_goes_from_queued_through_running_to_done() {
+        let jobs = start_jobs(10);
+        let (step, steps) = mpsc::unbounded_channel();
+        let first = jobs.submit("sweep", stepped_scan(steps, None)).unwrap();
+        assert_eq!(first.status, JobStatus::Queued);
+        let (_, idle) = mpsc::unbounded_channel();
+        let second = jobs.submit("audit", stepped_scan(idle, None)).unwrap();
+
+        let running = wait_for(&jobs, first.id, JobStatus::Running).await;
+        assert_eq!(running.started_at, Some(1_760_000_000));
+        // One job runs at a time; the next waits its turn
+        assert_eq!(jobs.get(&second.id).unwrap().status, JobStatus::Queued);
+
+        step.send(()).unwrap();
+        step.send(()).unwrap();
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        let partway = jobs.get(&first.id).unwrap();
+        assert_eq!(partway.status, JobStatus::Running);
+        assert_eq!(
+            partway.progress,
+            ScanProgress {
+                total: 3,
+                processed: 2,
+                errors: 0
+            }
+        );
+
+        step.send(()).unwrap();
+        let done = wait_for(&jobs, first.id, JobStatus::Done).await;
+        assert_eq!(done.progress.processed, 3);
+        assert_eq!(done.result, Some(serde_json::json!({ "scanned": 3 })));
+        assert_eq!(done.error, None);
+        wait_for(&jobs, second.id, JobStatus::Done).await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_job_keeps_its_progress_and_error() {
+        let jobs = start_jobs(10);
+        let (step, steps) = mpsc::unbounded_channel();
+        let job = jobs.submit("sweep", stepped_scan(steps, Some(1))).unwrap();
+        step.send(()).unwrap();
+        step.send(()).unwrap();
+
+        let failed = wait_for(&jobs, job.id, JobStatus::Failed).await;
+        assert_eq!(failed.error.as_deref(), Some("relay refused item 1"));
+        assert_eq!(failed.result, None);
+        assert_eq!(
+            failed.progress,
+            ScanProgress {
+                total: 3,
+                processed: 2,
+                errors: 1
+            }
+        );
+
+        // The runner carries on with the next job
+        let next = jobs
+            .submit("audit", |_| async { Ok(serde_json::Value::Null) })
+            .unwrap();
+        wait_for(&jobs, next.id, JobStatus::Done).await;
+    }
+
+    #[tokio::test]
+    async fn test_only_recent_finished_jobs_are_kept() {
+        let jobs = start_jobs(2);
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let job = jobs
+                .submit("refresh", |_| async { Ok(serde_json::Value::Null) })
+                .unwrap();
+            ids.push(job.id);
+        }
+        wait_for(&jobs, ids[2], JobStatus::Done).await;
+
+        assert_eq!(jobs.get(&ids[0]), None);
+        assert!(jobs.get(&ids[1]).is_some());
+    }
+}
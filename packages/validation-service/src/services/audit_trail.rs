@@ -0,0 +1,538 @@
+//! Who did what through the admin API and admin-level gift wrap requests
+//!
+//! Every mutating admin action is recorded, refused ones included: who asked (the admin API
+//! token's label, or the requester's pubkey), the action, the community it targeted, a hash of
+//! its parameters and how it ended. Recent entries are kept in memory for GET /api/admin/audit.
+//! A writer task appends each entry to a relay-authored kind 30078 event per UTC day, NIP-44
+//! encrypted to the relay key so only the operator can read it back, and mirrors it to the
+//! operator webhook. Requests that fail authentication carry no actor and are not recorded.
+
+use nostr_sdk::hashes::{sha256::Hash as Sha256Hash, Hash};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::metrics;
+use super::operator_webhook::{OperatorAlert, OperatorWebhook};
+use super::relay_access::DiscoveryPublisher;
+use crate::libraries::clock::Clock;
+
+/// Entries kept in memory for GET /api/admin/audit, oldest dropped first
+pub const AUDIT_HISTORY: usize = 10_000;
+
+/// Entries waiting for the writer before new ones are only kept in memory
+const MAX_PENDING_ENTRIES: usize = 1_024;
+
+/// Longest `detail` kept; error messages past this are cut
+const MAX_DETAIL_CHARS: usize = 200;
+
+/// Serialized entries per persisted event, well under the 64KiB NIP-44 plaintext limit
+const MAX_PART_BYTES: usize = 48_000;
+
+const AUDIT_D_TAG_PREFIX: &str = "admin-audit:";
+
+/// Who asked for an action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditActor {
+    // Label of the admin API token presented
+    Token(String),
+    // Hex pubkey of a gift-wrapped request's sender
+    Pubkey(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Ok,
+    // Refused by the service: not an admin, a bad id, nothing to do
+    Rejected,
+    // Attempted, but the relay or a job failed
+    Failed,
+}
+
+/// An action about to be attempted, recorded once its outcome is known
+#[derive(Debug, Clone)]
+pub struct AuditAction {
+    pub actor: AuditActor,
+    pub action: &'static str,
+    pub community_id: Option<String>,
+    pub group_id: Option<String>,
+    pub params: serde_json::Value,
+}
+
+impl AuditAction {
+    pub fn new(actor: AuditActor, action: &'static str, params: serde_json::Value) -> Self {
+        Self {
+            actor,
+            action,
+            community_id: None,
+            group_id: None,
+            params,
+        }
+    }
+
+    /// Target a community; UUIDs are recorded in their canonical lowercase form
+    pub fn community(self, community_id: &str) -> Self {
+        let community_id = Uuid::parse_str(community_id)
+            .map(|uuid| uuid.to_string())
+            .unwrap_or_else(|_| community_id.to_string());
+        Self {
+            community_id: Some(community_id),
+            ..self
+        }
+    }
+
+    pub fn group(self, group_id: &str) -> Self {
+        Self {
+            group_id: Some(group_id.to_string()),
+            ..self
+        }
+    }
+}
+
+/// One recorded action, as served by GET /api/admin/audit and persisted on the relay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub at: u64,
+    pub actor: AuditActor,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub community_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    // SHA-256 of the parameters as JSON, so locations and pubkeys are not kept in the clear
+    pub params_hash: String,
+    pub outcome: AuditOutcome,
+    // Why it was rejected or failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Which entries GET /api/admin/audit returns; bounds are unix times, both inclusive
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditFilter {
+    /// Only actions on this community
+    pub community_id: Option<Uuid>,
+    /// Only actions at or after this unix time
+    pub since: Option<u64>,
+    /// Only actions at or before this unix time
+    pub until: Option<u64>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.community_id
+            .is_none_or(|uuid| entry.community_id.as_deref() == Some(&uuid.to_string()))
+            && self.since.is_none_or(|since| entry.at >= since)
+            && self.until.is_none_or(|until| entry.at <= until)
+    }
+}
+
+/// Recent admin actions, handed on to the writer as they are recorded
+pub struct AuditTrail {
+    clock: Arc<dyn Clock>,
+    history: usize,
+    entries: Mutex<VecDeque<AuditEntry>>,
+    tx: mpsc::Sender<AuditEntry>,
+}
+
+impl AuditTrail {
+    /// Keeps up to `history` entries; the receiver goes to `run_audit_writer`
+    pub fn new(clock: Arc<dyn Clock>, history: usize) -> (Self, mpsc::Receiver<AuditEntry>) {
+        let (tx, rx) = mpsc::channel(MAX_PENDING_ENTRIES);
+        let trail = Self {
+            clock,
+            history: history.max(1),
+            entries: Mutex::new(VecDeque::new()),
+            tx,
+        };
+        (trail, rx)
+    }
+
+    pub fn record(&self, action: AuditAction, outcome: AuditOutcome, detail: Option<String>) {
+        let entry = AuditEntry {
+            at: self.clock.now_unix(),
+            actor: action.actor,
+            action: action.action.to_string(),
+            community_id: action.community_id,
+            group_id: action.group_id,
+            params_hash: Sha256Hash::hash(action.params.to_string().as_bytes()).to_string(),
+            outcome,
+            detail: detail.map(|detail| detail.chars().take(MAX_DETAIL_CHARS).collect()),
+        };
+        metrics::increment(
+            "peek_admin_actions_total",
+            &[("action", action.action), ("outcome", outcome.label())],
+        );
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_back(entry.clone());
+        while entries.len() > self.history {
+            entries.pop_front();
+        }
+        drop(entries);
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.tx.try_send(entry) {
+            warn!("Audit writer is behind; an admin action is only kept in memory");
+            metrics::increment("peek_audit_entries_unpersisted_total", &[]);
+        }
+    }
+
+    /// Kept entries matching `filter`, oldest first
+    pub fn entries(&self, filter: &AuditFilter) -> Vec<AuditEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| filter.matches(entry))
+            .cloned()
+            .collect()
+    }
+}
+
+impl AuditOutcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Rejected => "rejected",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// d tag of part `part` of the audit log for the UTC day of `at`, e.g. admin-audit:2026-10-17:0
+pub fn audit_d_tag(at: u64, part: u32) -> String {
+    let day = chrono::DateTime::from_timestamp(at as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d");
+    format!("{}{}:{}", AUDIT_D_TAG_PREFIX, day, part)
+}
+
+/// The part entries are currently appended to
+struct OpenPart {
+    day: String,
+    part: u32,
+    entries: Vec<AuditEntry>,
+}
+
+/// Appends entries to the day's audit events, starting a new part when one fills up
+/// Parts are sealed to the relay key; a read-only deployment logs each save instead
+pub struct AuditWriter {
+    publisher: DiscoveryPublisher,
+    webhook: OperatorWebhook,
+    open: Option<OpenPart>,
+}
+
+impl AuditWriter {
+    pub fn new(publisher: DiscoveryPublisher, webhook: OperatorWebhook) -> Self {
+        Self {
+            publisher,
+            webhook,
+            open: None,
+        }
+    }
+
+    /// Persist `entry`, then mirror it to the webhook; a failed save is logged and counted
+    pub async fn write(&mut self, entry: AuditEntry) {
+        if let Err(e) = self.append(&entry).await {
+            warn!("Failed to persist audit entry for {}: {}", entry.action, e);
+            metrics::increment("peek_audit_persist_failures_total", &[]);
+            // Reloaded on the next write, in case the relay took the save after all
+            self.open = None;
+        }
+        self.webhook
+            .notify(&OperatorAlert::AdminAction(entry))
+            .await;
+    }
+
+    async fn append(&mut self, entry: &AuditEntry) -> anyhow::Result<()> {
+        // Part 0's d tag stands for the day
+        let day = audit_d_tag(entry.at, 0);
+        let mut open = match self.open.take() {
+            Some(open) if open.day == day => open,
+            _ => self.latest_part(entry.at, day).await?,
+        };
+        open.entries.push(entry.clone());
+        if open.entries.len() > 1 && serde_json::to_vec(&open.entries)?.len() > MAX_PART_BYTES {
+            open = OpenPart {
+                day: open.day,
+                part: open.part + 1,
+                entries: vec![entry.clone()],
+            };
+        }
+        let content = serde_json::to_string(&open.entries)?;
+        self.publisher
+            .publish_sealed_app_data(&audit_d_tag(entry.at, open.part), content)
+            .await?;
+        self.open = Some(open);
+        Ok(())
+    }
+
+    /// The day's last written part, found by walking the parts from the first
+    async fn latest_part(&self, at: u64, day: String) -> anyhow::Result<OpenPart> {
+        let mut latest = OpenPart {
+            day,
+            part: 0,
+            entries: Vec::new(),
+        };
+        let mut part = 0;
+        while let Some(entries) = load_audit_part(&self.publisher, &audit_d_tag(at, part)).await? {
+            latest.part = part;
+            latest.entries = entries;
+            part += 1;
+        }
+        Ok(latest)
+    }
+}
+
+/// Entries of the part with d tag `d_tag`, or None if it was never written
+async fn load_audit_part(
+    publisher: &DiscoveryPublisher,
+    d_tag: &str,
+) -> anyhow::Result<Option<Vec<AuditEntry>>> {
+    match publisher.fetch_sealed_app_data(d_tag).await? {
+        Some(content) => Ok(Some(serde_json::from_str(&content)?)),
+        None => Ok(None),
+    }
+}
+
+/// Persist and mirror entries as the trail records them, until it is dropped
+pub async fn run_audit_writer(mut writer: AuditWriter, mut rx: mpsc::Receiver<AuditEntry>) {
+    while let Some(entry) = rx.recv().await {
+        writer.write(entry).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_relay::FakeRelay;
+    use crate::libraries::clock::SystemClock;
+    use crate::libraries::exclusion_zones::ExclusionZones;
+    use crate::libraries::relay_url::RelayUrl;
+    use crate::libraries::test_support::ManualClock;
+    use crate::models::ProtocolConfig;
+    use crate::services::discovery_map::DiscoveryMaps;
+    use crate::services::previous_refs::PreviousRefs;
+    use crate::services::relay::RelayService;
+    use crate::services::relay_circuit::RelayCircuit;
+    use nostr_sdk::Keys;
+    use std::time::Duration;
+
+    const NOW: u64 = 1_760_000_000;
+    const COMMUNITY: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+
+    /// A publisher whose sealed app data lands on an in-process relay
+    async fn publisher() -> DiscoveryPublisher {
+        let relay_keys = Keys::generate();
+        let url = FakeRelay::new(relay_keys.clone()).spawn().await.unwrap();
+        let protocol = ProtocolConfig::default();
+        let relay_service = RelayService::new(
+            RelayUrl::parse(&url).unwrap(),
+            relay_keys,
+            protocol.clone(),
+            Duration::from_millis(500),
+            DiscoveryMaps {
+                d_tag: protocol.discovery_map_d_tag.clone(),
+                prefixes: Vec::new(),
+                signer: None,
+                exclusion_zones: ExclusionZones::default(),
+            },
+            4096,
+            RelayCircuit::new(Arc::new(SystemClock), 0, Duration::from_secs(30)),
+            PreviousRefs::new(3),
+        )
+        .await
+        .unwrap();
+        DiscoveryPublisher::new(Arc::new(relay_service))
+    }
+
+    /// Entries persisted in the part with d tag `d_tag`
+    async fn stored(publisher: &DiscoveryPublisher, d_tag: &str) -> Option<usize> {
+        load_audit_part(publisher, d_tag)
+            .await
+            .unwrap()
+            .map(|entries| entries.len())
+    }
+
+    fn trail() -> (AuditTrail, mpsc::Receiver<AuditEntry>, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(NOW));
+        let (trail, rx) = AuditTrail::new(clock.clone(), 100);
+        (trail, rx, clock)
+    }
+
+    fn refresh(actor: &str) -> AuditAction {
+        AuditAction::new(
+            AuditActor::Token(actor.to_string()),
+            "community_cache_refresh",
+            serde_json::json!({ "community_id": COMMUNITY }),
+        )
+    }
+
+    #[test]
+    fn test_entries_are_filtered_by_community_and_time() {
+        let (trail, _rx, clock) = trail();
+        trail.record(
+            refresh("alice").community(&COMMUNITY.to_uppercase()),
+            AuditOutcome::Ok,
+            None,
+        );
+        clock.advance(60);
+        trail.record(
+            AuditAction::new(
+                AuditActor::Pubkey("ab".repeat(32)),
+                "archive_community",
+                serde_json::json!({}),
+            )
+            .community("not-a-uuid"),
+            AuditOutcome::Rejected,
+            Some("NOT_ADMIN".to_string()),
+        );
+        clock.advance(60);
+        trail.record(refresh("bob").community(COMMUNITY), AuditOutcome::Ok, None);
+
+        let community = AuditFilter {
+            community_id: Some(Uuid::parse_str(COMMUNITY).unwrap()),
+            ..Default::default()
+        };
+        let actors: Vec<AuditActor> = trail
+            .entries(&community)
+            .into_iter()
+            .map(|entry| entry.actor)
+            .collect();
+        assert_eq!(
+            actors,
+            vec![
+                AuditActor::Token("alice".to_string()),
+                AuditActor::Token("bob".to_string())
+            ]
+        );
+
+        let window = AuditFilter {
+            since: Some(NOW + 30),
+            until: Some(NOW + 60),
+            ..Default::default()
+        };
+        let entries = trail.entries(&window);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].outcome, AuditOutcome::Rejected);
+        assert_eq!(entries[0].detail.as_deref(), Some("NOT_ADMIN"));
+        assert_eq!(trail.entries(&AuditFilter::default()).len(), 3);
+    }
+
+    #[test]
+    fn test_parameters_are_only_kept_as_a_hash() {
+        let (trail, _rx, _) = trail();
+        let bulk_add = |pubkeys: &[&str]| {
+            AuditAction::new(
+                AuditActor::Token("alice".to_string()),
+                "bulk_add_members",
+                serde_json::json!({ "pubkeys": pubkeys }),
+            )
+        };
+        trail.record(bulk_add(&["a", "b"]), AuditOutcome::Ok, None);
+        trail.record(bulk_add(&["a", "b"]), AuditOutcome::Ok, None);
+        trail.record(bulk_add(&["a", "c"]), AuditOutcome::Ok, None);
+
+        let entries = trail.entries(&AuditFilter::default());
+        assert_eq!(entries[0].params_hash, entries[1].params_hash);
+        assert_ne!(entries[0].params_hash, entries[2].params_hash);
+        assert_eq!(entries[0].params_hash.len(), 64);
+        assert!(!serde_json::to_string(&entries[0])
+            .unwrap()
+            .contains("pubkeys"));
+    }
+
+    #[tokio::test]
+    async fn test_writer_appends_to_the_days_event_and_rolls_over_parts() {
+        let (trail, mut rx, clock) = trail();
+        let publisher = publisher().await;
+        let mut writer = AuditWriter::new(publisher.clone(), OperatorWebhook::new(None));
+
+        // Long enough details that a part fills up after about a hundred entries
+        for _ in 0..150 {
+            trail.record(
+                refresh("alice").community(COMMUNITY),
+                AuditOutcome::Failed,
+                Some("x".repeat(MAX_DETAIL_CHARS)),
+            );
+            writer.write(rx.recv().await.unwrap()).await;
+        }
+        let first = stored(&publisher, "admin-audit:2025-10-09:0")
+            .await
+            .unwrap();
+        let second = stored(&publisher, "admin-audit:2025-10-09:1")
+            .await
+            .unwrap();
+        assert_eq!(first + second, 150);
+        assert_eq!(stored(&publisher, "admin-audit:2025-10-09:2").await, None);
+
+        // A restarted writer finds the last part and keeps appending to it
+        let mut restarted = AuditWriter::new(publisher.clone(), OperatorWebhook::new(None));
+        trail.record(refresh("bob"), AuditOutcome::Ok, None);
+        restarted.write(rx.recv().await.unwrap()).await;
+        assert_eq!(
+            stored(&publisher, "admin-audit:2025-10-09:1").await,
+            Some(second + 1)
+        );
+
+        // The next UTC day starts a new event
+        clock.advance(86_400);
+        trail.record(refresh("bob"), AuditOutcome::Ok, None);
+        restarted.write(rx.recv().await.unwrap()).await;
+        assert_eq!(
+            stored(&publisher, "admin-audit:2025-10-10:0").await,
+            Some(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_mirrored_to_the_webhook() {
+        use axum::{extract::State, routing::post, Json, Router};
+
+        type Received = Arc<Mutex<Vec<serde_json::Value>>>;
+        let received = Received::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State(received): State<Received>, Json(body): Json<serde_json::Value>| async move {
+                        received.lock().unwrap().push(body);
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (trail, mut rx, _) = trail();
+        let mut writer = AuditWriter::new(publisher().await, OperatorWebhook::new(Some(url)));
+        trail.record(
+            refresh("alice").community(COMMUNITY).group("peek-3a7e5c59"),
+            AuditOutcome::Ok,
+            None,
+        );
+        let entry = rx.recv().await.unwrap();
+        writer.write(entry.clone()).await;
+
+        assert_eq!(
+            received.lock().unwrap().clone(),
+            vec![serde_json::json!({
+                "alert": "admin_action",
+                "at": NOW,
+                "actor": { "token": "alice" },
+                "action": "community_cache_refresh",
+                "community_id": COMMUNITY,
+                "group_id": "peek-3a7e5c59",
+                "params_hash": entry.params_hash,
+                "outcome": "ok",
+            })]
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! Admin import of members by pubkey, for crews moving over from another group chat
+//!
+//! Everyone else joins by scanning the community's sticker. An admin bringing an existing
+//! Telegram or WhatsApp crew along can add them by pubkey instead, once the community itself
+//! has been created by a scan. Each community may import only a configured number of members
+//! this way, and the adds are paced so a full batch does not trip the relay's rate limits.
+
+use nostr_sdk::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::metrics;
+use super::relay::{GroupSnapshot, RelayError};
+
+/// Most pubkeys one bulk_add_members request may carry
+pub const MAX_BULK_ADD: usize = 100;
+
+// Window the per-second send allowance is counted over
+const PACING_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAddStatus {
+    Added,
+    // Already in the group, or listed earlier in the same batch
+    AlreadyMember,
+    // Not a hex or npub public key
+    Invalid,
+    // The relay refused or did not confirm the add
+    Failed,
+}
+
+impl BulkAddStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::AlreadyMember => "already_member",
+            Self::Invalid => "invalid",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// What became of one requested pubkey, in request order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BulkAddOutcome {
+    // As given in the request
+    pub pubkey: String,
+    pub status: BulkAddStatus,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BulkAddError {
+    #[error("Only community admins can add members")]
+    NotAdmin,
+    #[error("At most {0} pubkeys can be added at once")]
+    TooMany(usize),
+    #[error("Members can only be imported once the community has been created by a scan")]
+    NotScanned,
+    #[error("This community can import {0} more members by pubkey")]
+    AllowanceExhausted(usize),
+    #[error("The community only has room for {0} more members")]
+    Full(usize),
+    #[error("Failed to read the community: {0}")]
+    Relay(#[from] RelayError),
+}
+
+/// Relay reads and writes a bulk add goes through
+pub trait BulkAddTarget: Send + Sync {
+    fn group_admins(
+        &self,
+        group_id: &str,
+    ) -> impl Future<Output = Result<Vec<PublicKey>, RelayError>> + Send;
+
+    fn group_snapshot(
+        &self,
+        group_id: &str,
+    ) -> impl Future<Output = Result<GroupSnapshot, RelayError>> + Send;
+
+    fn add_member(
+        &self,
+        group_id: &str,
+        pubkey: &PublicKey,
+    ) -> impl Future<Output = Result<(), RelayError>> + Send;
+}
+
+/// Adds admin-supplied pubkeys to a community within its allowance, a few per second
+pub struct BulkAdder {
+    allowance: usize,
+    per_second: usize,
+    // Members each community has imported so far
+    imported: Mutex<HashMap<Uuid, usize>>,
+}
+
+impl BulkAdder {
+    /// Up to `allowance` imported members per community, 0 disabling bulk adds, sent to the
+    /// relay at most `per_second` a second
+    pub fn new(allowance: usize, per_second: usize) -> Self {
+        Self {
+            allowance,
+            per_second: per_second.max(1),
+            imported: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Add `pubkeys` to the community on behalf of one of its admins
+    ///
+    /// The whole batch is refused when the requester is not an admin, the community was not
+    /// created by a scan, or the new members would not fit its allowance or member cap. Past
+    /// that, every pubkey gets its own outcome.
+    pub async fn add<T: BulkAddTarget>(
+        &self,
+        target: &T,
+        community_id: Uuid,
+        group_id: &str,
+        requester: &PublicKey,
+        pubkeys: &[String],
+    ) -> Result<Vec<BulkAddOutcome>, BulkAddError> {
+        if pubkeys.len() > MAX_BULK_ADD {
+            return Err(BulkAddError::TooMany(MAX_BULK_ADD));
+        }
+        let admins = target.group_admins(group_id).await?;
+        if !admins.contains(requester) {
+            return Err(BulkAddError::NotAdmin);
+        }
+        let snapshot = target.group_snapshot(group_id).await?;
+        // The scan that creates a community is what sets its first anchor
+        if snapshot.metadata.anchors.is_empty() {
+            return Err(BulkAddError::NotScanned);
+        }
+
+        let mut seen: HashSet<PublicKey> = snapshot
+            .members
+            .pubkeys
+            .iter()
+            .filter_map(|member| PublicKey::from_hex(member).ok())
+            .collect();
+        let mut outcomes = Vec::with_capacity(pubkeys.len());
+        let mut to_add = Vec::new();
+        for pubkey in pubkeys {
+            let status = match PublicKey::parse(pubkey.trim()) {
+                Err(_) => BulkAddStatus::Invalid,
+                Ok(parsed) if !seen.insert(parsed) => BulkAddStatus::AlreadyMember,
+                Ok(parsed) => {
+                    to_add.push((outcomes.len(), parsed));
+                    // Until the relay confirms the add
+                    BulkAddStatus::Failed
+                }
+            };
+            outcomes.push(BulkAddOutcome {
+                pubkey: pubkey.clone(),
+                status,
+            });
+        }
+
+        if let Some(max) = snapshot.metadata.max_members {
+            let room = (max as usize).saturating_sub(snapshot.members.count());
+            if to_add.len() > room {
+                return Err(BulkAddError::Full(room));
+            }
+        }
+        self.reserve(community_id, to_add.len())?;
+
+        // Sliding window over completed sends, so no second sees more than `per_second` adds
+        let mut recent: VecDeque<Instant> = VecDeque::with_capacity(self.per_second);
+        let mut failed = 0;
+        for (index, pubkey) in to_add {
+            if recent.len() == self.per_second {
+                if let Some(oldest) = recent.pop_front() {
+                    tokio::time::sleep_until(oldest + PACING_WINDOW).await;
+                }
+            }
+            let result = target.add_member(group_id, &pubkey).await;
+            recent.push_back(Instant::now());
+            outcomes[index].status = match result {
+                Ok(()) => BulkAddStatus::Added,
+                Err(e) => {
+                    tracing::warn!("Bulk add of {} to {} failed: {}", pubkey, group_id, e);
+                    failed += 1;
+                    BulkAddStatus::Failed
+                }
+            };
+        }
+        // Members the relay never took do not count against the allowance
+        self.release(community_id, failed);
+
+        for outcome in &outcomes {
+            metrics::increment(
+                "peek_bulk_add_members_total",
+                &[("status", outcome.status.as_str())],
+            );
+        }
+        Ok(outcomes)
+    }
+
+    fn reserve(&self, community_id: Uuid, count: usize) -> Result<(), BulkAddError> {
+        let mut imported = self.imported.lock().unwrap();
+        let used = imported.entry(community_id).or_default();
+        let remaining = self.allowance.saturating_sub(*used);
+        if count > remaining || self.allowance == 0 {
+            return Err(BulkAddError::AllowanceExhausted(remaining));
+        }
+        *used += count;
+        Ok(())
+    }
+
+    fn release(&self, community_id: Uuid, count: usize) {
+        if let Some(used) = self.imported.lock().unwrap().get_mut(&community_id) {
+            *used = used.saturating_sub(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::member_set::MemberSet;
+    use crate::services::relay::GroupMetadata;
+
+    const COMMUNITY: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+
+    struct FakeGroup {
+        admin: PublicKey,
+        members: Vec<String>,
+        anchors: Vec<&'static str>,
+        // Pubkeys the relay refuses to add
+        refused: HashSet<PublicKey>,
+        sends: Mutex<Vec<(Instant, PublicKey)>>,
+    }
+
+    impl FakeGroup {
+        fn scanned(admin: PublicKey) -> Self {
+            Self {
+                admin,
+                members: vec![admin.to_hex()],
+                anchors: vec!["9q8yyk8y"],
+                refused: HashSet::new(),
+                sends: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn sends(&self) -> Vec<(Instant, PublicKey)> {
+            self.sends.lock().unwrap().clone()
+        }
+    }
+
+    impl BulkAddTarget for FakeGroup {
+        async fn group_admins(&self, _group_id: &str) -> Result<Vec<PublicKey>, RelayError> {
+            Ok(vec![self.admin])
+        }
+
+        async fn group_snapshot(&self, _group_id: &str) -> Result<GroupSnapshot, RelayError> {
+            let mut tags = vec![
+                Tag::identifier("peek-abc123"),
+                Tag::custom(TagKind::Name, ["Blue Bottle"]),
+            ];
+            tags.extend(self.anchors.iter().map(|anchor| {
+                Tag::custom(
+                    TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::G)),
+                    [*anchor],
+                )
+            }));
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tags(tags)
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            Ok(GroupSnapshot {
+                metadata: GroupMetadata::from_event(&event, self.members.len() as u32),
+                members: MemberSet::complete(self.members.clone()),
+            })
+        }
+
+        async fn add_member(&self, _group_id: &str, pubkey: &PublicKey) -> Result<(), RelayError> {
+            self.sends.lock().unwrap().push((Instant::now(), *pubkey));
+            if self.refused.contains(pubkey) {
+                return Err(RelayError::Other("blocked: rate-limited".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    fn statuses(outcomes: &[BulkAddOutcome]) -> Vec<BulkAddStatus> {
+        outcomes.iter().map(|outcome| outcome.status).collect()
+    }
+
+    #[tokio::test]
+    async fn test_mixed_batch_reports_each_pubkey() {
+        let admin = Keys::generate().public_key();
+        let member = Keys::generate().public_key();
+        let newcomer = Keys::generate().public_key();
+        let via_npub = Keys::generate().public_key();
+        let blocked = Keys::generate().public_key();
+        let mut group = FakeGroup::scanned(admin);
+        group.members.push(member.to_hex());
+        group.refused.insert(blocked);
+
+        let adder = BulkAdder::new(10, 100);
+        let pubkeys = vec![
+            newcomer.to_hex(),
+            "not-a-pubkey".to_string(),
+            member.to_hex(),
+            via_npub.to_bech32().unwrap(),
+            newcomer.to_hex(),
+            blocked.to_hex(),
+        ];
+        let outcomes = adder
+            .add(
+                &group,
+                COMMUNITY.parse().unwrap(),
+                "peek-abc123",
+                &admin,
+                &pubkeys,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            statuses(&outcomes),
+            vec![
+                BulkAddStatus::Added,
+                BulkAddStatus::Invalid,
+                BulkAddStatus::AlreadyMember,
+                BulkAddStatus::Added,
+                BulkAddStatus::AlreadyMember,
+                BulkAddStatus::Failed,
+            ]
+        );
+        assert_eq!(outcomes[3].pubkey, pubkeys[3]);
+        let sent: Vec<PublicKey> = group.sends().into_iter().map(|(_, pk)| pk).collect();
+        assert_eq!(sent, vec![newcomer, via_npub, blocked]);
+
+        // Only the two adds the relay took count against the allowance
+        assert_eq!(
+            *adder
+                .imported
+                .lock()
+                .unwrap()
+                .get(&COMMUNITY.parse().unwrap())
+                .unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_adds_are_paced_to_the_per_second_limit() {
+        let admin = Keys::generate().public_key();
+        let group = FakeGroup::scanned(admin);
+        let pubkeys: Vec<String> = (0..5)
+            .map(|_| Keys::generate().public_key().to_hex())
+            .collect();
+
+        let adder = BulkAdder::new(100, 2);
+        let outcomes = adder
+            .add(
+                &group,
+                COMMUNITY.parse().unwrap(),
+                "peek-abc123",
+                &admin,
+                &pubkeys,
+            )
+            .await
+            .unwrap();
+        assert!(outcomes
+            .iter()
+            .all(|outcome| outcome.status == BulkAddStatus::Added));
+
+        // Any three consecutive sends span at least a second
+        let sends = group.sends();
+        assert_eq!(sends.len(), 5);
+        for window in sends.windows(3) {
+            assert!(window[2].0 - window[0].0 >= PACING_WINDOW);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_non_admins_and_unscanned_communities_are_refused() {
+        let admin = Keys::generate().public_key();
+        let outsider = Keys::generate().public_key();
+        let pubkeys = vec![Keys::generate().public_key().to_hex()];
+        let community: Uuid = COMMUNITY.parse().unwrap();
+        let adder = BulkAdder::new(10, 5);
+
+        let group = FakeGroup::scanned(admin);
+        let refused = adder
+            .add(&group, community, "peek-abc123", &outsider, &pubkeys)
+            .await;
+        assert!(matches!(refused, Err(BulkAddError::NotAdmin)));
+        assert!(group.sends().is_empty());
+
+        let mut unscanned = FakeGroup::scanned(admin);
+        unscanned.anchors.clear();
+        let refused = adder
+            .add(&unscanned, community, "peek-abc123", &admin, &pubkeys)
+            .await;
+        assert!(matches!(refused, Err(BulkAddError::NotScanned)));
+
+        let oversized = vec![pubkeys[0].clone(); MAX_BULK_ADD + 1];
+        let refused = adder
+            .add(&group, community, "peek-abc123", &admin, &oversized)
+            .await;
+        assert!(matches!(refused, Err(BulkAddError::TooMany(MAX_BULK_ADD))));
+        assert!(group.sends().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_allowance_is_per_community_and_zero_disables() {
+        let admin = Keys::generate().public_key();
+        let group = FakeGroup::scanned(admin);
+        let batch = |count: usize| -> Vec<String> {
+            (0..count)
+                .map(|_| Keys::generate().public_key().to_hex())
+                .collect()
+        };
+        let community: Uuid = COMMUNITY.parse().unwrap();
+        let adder = BulkAdder::new(3, 100);
+
+        adder
+            .add(&group, community, "peek-abc123", &admin, &batch(2))
+            .await
+            .unwrap();
+        let refused = adder
+            .add(&group, community, "peek-abc123", &admin, &batch(2))
+            .await;
+        assert!(matches!(refused, Err(BulkAddError::AllowanceExhausted(1))));
+
+        // Another community has its own allowance
+        adder
+            .add(&group, Uuid::new_v4(), "peek-def456", &admin, &batch(3))
+            .await
+            .unwrap();
+
+        let disabled = BulkAdder::new(0, 100);
+        let refused = disabled
+            .add(&group, community, "peek-abc123", &admin, &batch(1))
+            .await;
+        assert!(matches!(refused, Err(BulkAddError::AllowanceExhausted(0))));
+        assert_eq!(group.sends().len(), 5);
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::{anyhow, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OnceCell, OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+
+use super::metrics;
+
+/// Shared pool of connected clients for ad-hoc relay sets (e.g. NIP-65 inbox fan-out)
+///
+/// Borrowers of the same relay set share one underlying client instead of each opening
+/// their own sockets. A semaphore caps concurrent borrows across all relay sets, and
+/// clients unused for longer than `idle_ttl` are disconnected by `reap_idle`.
+pub struct ClientPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    semaphore: Arc<Semaphore>,
+    idle_ttl: Duration,
+    entries: Mutex<HashMap<String, PoolEntry>>,
+    created_total: AtomicU64,
+}
+
+struct PoolEntry {
+    client: Arc<OnceCell<Client>>,
+    borrowers: usize,
+    last_used: Instant,
+}
+
+/// Snapshot of pool usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    pub active: usize,
+    pub idle: usize,
+    pub created_total: u64,
+}
+
+/// A client borrowed from the pool, returned when dropped
+pub struct PooledClient {
+    client: Client,
+    key: String,
+    pool: Arc<PoolInner>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ClientPool {
+    pub fn new(max_concurrent: usize, idle_ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(PoolInner {
+                semaphore: Arc::new(Semaphore::new(max_concurrent.max(1))),
+                idle_ttl,
+                entries: Mutex::new(HashMap::new()),
+                created_total: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Borrow a connected client for `relays`, waiting if the concurrency cap is reached
+    pub async fn acquire(&self, relays: &[String]) -> Result<PooledClient> {
+        let permit = self
+            .inner
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| anyhow!("Client pool closed: {}", e))?;
+
+        let key = relay_set_key(relays);
+        let cell = {
+            let mut entries = self.inner.entries.lock().unwrap();
+            let entry = entries.entry(key.clone()).or_insert_with(|| PoolEntry {
+                client: Arc::new(OnceCell::new()),
+                borrowers: 0,
+                last_used: Instant::now(),
+            });
+            entry.borrowers += 1;
+            entry.client.clone()
+        };
+
+        let client = match cell
+            .get_or_try_init(|| connect_client(relays, &self.inner.created_total))
+            .await
+        {
+            Ok(client) => client.clone(),
+            Err(e) => {
+                self.inner.release(&key);
+                return Err(e);
+            }
+        };
+
+        self.inner.publish_stats();
+
+        Ok(PooledClient {
+            client,
+            key,
+            pool: self.inner.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Disconnect and drop clients that have had no borrowers for longer than the idle TTL
+    pub async fn reap_idle(&self) -> usize {
+        let reaped: Vec<Arc<OnceCell<Client>>> = {
+            let mut entries = self.inner.entries.lock().unwrap();
+            let idle_ttl = self.inner.idle_ttl;
+            let expired: Vec<String> = entries
+                .iter()
+                .filter(|(_, e)| e.borrowers == 0 && e.last_used.elapsed() >= idle_ttl)
+                .map(|(key, _)| key.clone())
+                .collect();
+            expired
+                .iter()
+                .filter_map(|key| entries.remove(key))
+                .map(|e| e.client)
+                .collect()
+        };
+
+        for cell in &reaped {
+            if let Some(client) = cell.get() {
+                client.disconnect().await;
+            }
+        }
+
+        if !reaped.is_empty() {
+            debug!("Reaped {} idle pooled clients", reaped.len());
+        }
+        self.inner.publish_stats();
+        reaped.len()
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.inner.stats()
+    }
+}
+
+impl PoolInner {
+    fn release(&self, key: &str) {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(entry) = entries.get_mut(key) {
+                entry.borrowers = entry.borrowers.saturating_sub(1);
+                entry.last_used = Instant::now();
+                // Never-connected entries (failed creation) are not worth keeping
+                if entry.borrowers == 0 && !entry.client.initialized() {
+                    entries.remove(key);
+                }
+            }
+        }
+        self.publish_stats();
+    }
+
+    fn stats(&self) -> PoolStats {
+        let entries = self.entries.lock().unwrap();
+        let connected = entries.values().filter(|e| e.client.initialized());
+        let (active, idle) = connected.fold((0, 0), |(active, idle), e| {
+            if e.borrowers > 0 {
+                (active + 1, idle)
+            } else {
+                (active, idle + 1)
+            }
+        });
+        PoolStats {
+            active,
+            idle,
+            created_total: self.created_total.load(Ordering::Relaxed),
+        }
+    }
+
+    fn publish_stats(&self) {
+        let stats = self.stats();
+        metrics::set_gauge("peek_client_pool_active", &[], stats.active as u64);
+        metrics::set_gauge("peek_client_pool_idle", &[], stats.idle as u64);
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        self.pool.release(&self.key);
+    }
+}
+
+/// Order-independent key for a relay set
+fn relay_set_key(relays: &[String]) -> String {
+    let mut relays: Vec<&str> = relays.iter().map(|r| r.trim_end_matches('/')).collect();
+    relays.sort_unstable();
+    relays.dedup();
+    relays.join(" ")
+}
+
+async fn connect_client(relays: &[String], created_total: &AtomicU64) -> Result<Client> {
+    let client = Client::default();
+    let mut added = 0;
+    for relay in relays {
+        match client.add_relay(relay).await {
+            Ok(_) => added += 1,
+            Err(e) => warn!("Skipping invalid relay {}: {}", relay, e),
+        }
+    }
+    if added == 0 {
+        return Err(anyhow!("No valid relays in set: {:?}", relays));
+    }
+
+    client.connect().await;
+    created_total.fetch_add(1, Ordering::Relaxed);
+    metrics::increment("peek_client_pool_created_total", &[]);
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nothing listens here; clients are created and connect in the background
+    fn relays() -> Vec<String> {
+        vec!["ws://127.0.0.1:1".to_string()]
+    }
+
+    #[test]
+    fn test_relay_set_key_is_order_independent() {
+        let a = relay_set_key(&[
+            "wss://b.example/".to_string(),
+            "wss://a.example".to_string(),
+        ]);
+        let b = relay_set_key(&["wss://a.example".to_string(), "wss://b.example".to_string()]);
+        assert_eq!(a, b);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_borrowers_share_one_client() {
+        let pool = ClientPool::new(2, Duration::from_secs(60));
+
+        let (first, second) = tokio::join!(pool.acquire(&relays()), pool.acquire(&relays()));
+        let (first, second) = (first.unwrap(), second.unwrap());
+
+        let stats = pool.stats();
+        assert_eq!(stats.created_total, 1);
+        assert_eq!(stats.active, 1);
+
+        drop(first);
+        drop(second);
+        let stats = pool.stats();
+        assert_eq!(stats.active, 0);
+        assert_eq!(stats.idle, 1);
+    }
+
+    #[tokio::test]
+    async fn test_semaphore_blocks_borrower_over_cap() {
+        let pool = ClientPool::new(2, Duration::from_secs(60));
+
+        let first = pool.acquire(&relays()).await.unwrap();
+        let _second = pool.acquire(&relays()).await.unwrap();
+
+        let third = tokio::time::timeout(Duration::from_millis(100), pool.acquire(&relays())).await;
+        assert!(third.is_err(), "third borrower should wait for a permit");
+
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_millis(100), pool.acquire(&relays())).await;
+        assert!(third.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reap_idle_disconnects_unused_clients() {
+        let pool = ClientPool::new(2, Duration::ZERO);
+
+        let client = pool.acquire(&relays()).await.unwrap();
+        assert_eq!(
+            pool.reap_idle().await,
+            0,
+            "borrowed clients are never reaped"
+        );
+
+        drop(client);
+        assert_eq!(pool.reap_idle().await, 1);
+        assert_eq!(pool.stats().idle, 0);
+    }
+}
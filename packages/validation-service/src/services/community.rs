@@ -1,220 +1,583 @@
-use geohash::{encode, Coord};
-use std::sync::Arc;
+use nostr_sdk::Timestamp;
+use peek_geo::GeohashError;
+use std::future::Future;
 use uuid::Uuid;
 
-use crate::models::LocationPoint;
-use crate::services::relay::{Location, RelayService};
+use crate::libraries::clock::Deadline;
+use crate::models::Coordinates;
+use crate::services::creation_limit::{CreationLimiter, CreationShed};
+use crate::services::join_requests::JoinMode;
+use crate::services::member_set::MemberSet;
+use crate::services::nearby_index::find_populated_duplicate;
+use crate::services::relay::{fallback_community_name, GroupMetadata, RelayError};
+use crate::services::relay_access::{GroupReader, GroupWriter};
 
 /// Information about a community
 pub struct CommunityMetadata {
-    pub geohash: String, // Level 8 geohash for location
+    pub name: String,                    // Group name shown to members
+    pub picture: Option<String>,         // Group picture URL, if one was set
+    pub geohash: String,                 // Level 8 geohash for location
+    pub anchors: Vec<String>,            // All level 8 anchor geohashes (includes geohash)
+    pub active_until: Option<Timestamp>, // Deadline for new joins on time-boxed communities
+    pub join_mode: JoinMode,             // Auto-join or admin-approved membership
+    pub max_members: Option<u32>,        // Cap on members; None is unlimited
+    pub archived: bool,                  // Archived by an admin or the expiry sweep
+    pub members: MemberSet,              // Member pubkeys from the same read as the metadata
+    pub welcome: Option<String>,         // Greeting sent to members on their first join
+    pub rules: Vec<String>,              // Community rules, sent along with the welcome
+    pub relay_url: Option<String>,       // Relay override from the group's metadata
+    pub is_public: bool,                 // Readable by non-members; created groups are private
+    pub age_restricted: bool,            // Marked 18+ by the venue
+}
+
+/// A community just created, with the group id picked for it
+pub struct CreatedCommunity {
+    pub group_id: String,
+    pub metadata: CommunityMetadata,
+}
+
+/// State of a community's group on the relay
+pub enum CommunityLookup {
+    /// No group yet, or a group nobody has joined, so the next validated user creates it
+    Absent,
+    Existing(CommunityMetadata),
+}
+
+/// Why a community could not be looked up or created
+#[derive(Debug, thiserror::Error)]
+pub enum CommunityError {
+    #[error("Group not found for community {0}")]
+    NotFound(Uuid),
+
+    /// Group has members but no location geohash - needs manual intervention
+    #[error("Community {0} exists but has no location geohash - this is a corrupted state that needs manual intervention")]
+    Corrupted(Uuid),
+
+    #[error("Failed to create community during {step}: {reason}")]
+    CreationFailed { step: &'static str, reason: String },
+
+    #[error("Failed to encode location: {0}")]
+    Geohash(#[from] GeohashError),
+
+    /// The request deadline passed before the relay call for `stage` was started
+    #[error("Request deadline passed before {stage}")]
+    Timeout { stage: &'static str },
+
+    /// Too many creations already running or queued; nothing was sent to the relay
+    #[error(transparent)]
+    Busy(#[from] CreationShed),
+
+    #[error(transparent)]
+    NearbyExists(#[from] NearbyCommunityExists),
+
+    #[error(transparent)]
+    Relay(#[from] RelayError),
+}
+
+/// Start the relay call for `stage` only if the request deadline has not passed yet
+async fn within<T>(
+    deadline: &Deadline,
+    stage: &'static str,
+    call: impl Future<Output = T>,
+) -> Result<T, CommunityError> {
+    if deadline.is_expired() {
+        tracing::warn!(
+            "[CommunityService] Request deadline passed, not starting {}",
+            stage
+        );
+        return Err(CommunityError::Timeout { stage });
+    }
+    Ok(call.await)
+}
+
+impl CommunityMetadata {
+    /// Metadata of a group found on the relay, located at `geohash`
+    fn existing(geohash: String, group_meta: GroupMetadata, members: MemberSet) -> Self {
+        Self {
+            name: group_meta.name,
+            picture: group_meta.picture.filter(|picture| !picture.is_empty()),
+            geohash,
+            anchors: group_meta.anchors,
+            active_until: group_meta.active_until,
+            join_mode: group_meta.join_mode,
+            max_members: group_meta.max_members,
+            archived: group_meta.archived,
+            members,
+            welcome: group_meta.welcome,
+            rules: group_meta.rules.unwrap_or_default(),
+            relay_url: group_meta.relay,
+            is_public: group_meta.is_public,
+            age_restricted: group_meta.age_restricted,
+        }
+    }
+
+    /// Whether `pubkey_hex` was a member when the community was looked up
+    pub fn has_member(&self, pubkey_hex: &str) -> bool {
+        self.members.contains(pubkey_hex)
+    }
+
+    /// Whether new members may still join at `now`: not archived and before any deadline
+    /// Existing members are not affected
+    pub fn accepts_new_members_at(&self, now: Timestamp) -> bool {
+        !self.archived && self.active_until.is_none_or(|until| now < until)
+    }
+
+    /// Relay clients should connect to for this community: its override, else `default`
+    pub fn relay_url_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.relay_url.as_deref().unwrap_or(default)
+    }
+
+    /// Whether the member cap was reached when the community was looked up
+    /// Concurrent joins near the cap may both pass, leaving it slightly over
+    pub fn is_full(&self) -> bool {
+        self.max_members
+            .is_some_and(|max| self.members.count() >= max as usize)
+    }
+}
+
+/// Creation declined because a community with members is already anchored at the same spot
+/// The creator can join it instead, or retry with `force` to create a separate community
+#[derive(Debug, thiserror::Error)]
+#[error("Community {group_id} already exists at this location")]
+pub struct NearbyCommunityExists {
+    pub group_id: String,
+    pub community_id: Option<Uuid>,
+    pub metadata: GroupMetadata,
 }
 
 /// Service for managing community metadata using relay as storage
 pub struct CommunityService {
-    relay_service: Arc<tokio::sync::RwLock<RelayService>>,
+    groups: GroupReader,
+    writer: GroupWriter,
+    creations: CreationLimiter,
 }
 
 impl CommunityService {
-    pub fn new(relay_service: Arc<tokio::sync::RwLock<RelayService>>) -> Self {
-        Self { relay_service }
+    pub fn new(groups: GroupReader, writer: GroupWriter, creations: CreationLimiter) -> Self {
+        Self {
+            groups,
+            writer,
+            creations,
+        }
     }
 
-    /// Get community metadata by ID
-    pub async fn get(&self, id: &Uuid) -> Option<CommunityMetadata> {
-        tracing::info!("[CommunityService::get] Looking up group for UUID {}", id);
+    /// Look up a community's group and its current state on the relay
+    ///
+    /// Metadata and members come from one concurrent snapshot read, shared by the
+    /// corruption check, the "no members yet" check and the caller's membership checks.
+    /// Relay errors are only returned for retriable states (e.g. a query that raced NIP-42
+    /// auth), so callers never mistake an existing community for a new one
+    pub async fn lookup(
+        &self,
+        id: &Uuid,
+        deadline: &Deadline,
+    ) -> Result<CommunityLookup, CommunityError> {
+        tracing::info!(
+            "[CommunityService::lookup] Looking up group for UUID {}",
+            id
+        );
 
         // Look up the group ID from UUID using NIP-73 i-tag
-        let group_id = match self.relay_service.read().await.find_group_by_uuid(id).await {
-            Ok(Some(gid)) => gid,
-            Ok(None) => {
-                tracing::info!("[CommunityService::get] No group found for UUID {}", id);
-                return None;
-            }
-            Err(e) => {
-                tracing::error!(
-                    "[CommunityService::get] Error looking up group for UUID {}: {}",
-                    id,
-                    e
-                );
-                return None;
-            }
-        };
+        let group_id =
+            match within(deadline, "group lookup", self.groups.find_group_by_uuid(id)).await? {
+                Ok(Some(gid)) => gid,
+                Ok(None) => {
+                    tracing::info!("[CommunityService::lookup] No group found for UUID {}", id);
+                    return Ok(CommunityLookup::Absent);
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "[CommunityService::lookup] Error looking up group for UUID {}: {}",
+                        id,
+                        e
+                    );
+                    return Err(CommunityError::Relay(e));
+                }
+            };
 
         tracing::info!(
-            "[CommunityService::get] Found group {} for UUID {}, fetching metadata",
+            "[CommunityService::lookup] Found group {} for UUID {}, fetching metadata",
             group_id,
             id
         );
 
-        // Try to get NIP-29 group metadata first
-        if let Ok(group_meta) = self
-            .relay_service
-            .read()
-            .await
-            .get_group_metadata(&group_id)
-            .await
+        let snapshot = match within(
+            deadline,
+            "community lookup",
+            self.groups.get_group_snapshot(&group_id),
+        )
+        .await?
         {
-            tracing::info!("[CommunityService::get] Retrieved metadata for {}: name='{}', members={}, geohash={:?}, display_geohash={:?}",
-                group_id, group_meta.name, group_meta.member_count, group_meta.geohash, group_meta.display_geohash);
-            // If group exists and has no members, it's essentially "new" for the first user
-            // Return None so the first user becomes admin
-            if group_meta.member_count == 0 {
-                tracing::info!(
-                    "[CommunityService::get] Group {} has 0 members, treating as new",
-                    group_id
+            Ok(snapshot) => snapshot,
+            Err(RelayError::QueryInconclusive(group)) => {
+                tracing::warn!(
+                    "[CommunityService::lookup] Metadata query for {} was inconclusive, not treating as new",
+                    group
                 );
-                return None;
+                return Err(RelayError::QueryInconclusive(group).into());
             }
-
-            // Group exists with members, construct metadata
-            // Get geohash from the metadata
-            if let Some(geohash) = group_meta.geohash {
+            Err(_) => {
                 tracing::info!(
-                    "[CommunityService::get] Group {} has geohash: {}",
-                    group_id,
-                    geohash
-                );
-                return Some(CommunityMetadata { geohash });
-            } else if let Some(display_geohash) = group_meta.display_geohash {
-                // Fallback to display geohash if regular geohash is missing
-                tracing::warn!("[CommunityService::get] Group {} missing regular geohash, using display_geohash: {}", group_id, display_geohash);
-                // Extract the first 8 characters as a fallback geohash
-                let geohash = display_geohash.chars().take(8).collect::<String>();
-                return Some(CommunityMetadata { geohash });
-            } else {
-                tracing::error!(
-                    "[CommunityService::get] Group {} exists with {} members but has no geohash!",
-                    group_id,
-                    group_meta.member_count
+                    "[CommunityService::lookup] Group {} not found on relay",
+                    group_id
                 );
-                return None;
+                return Ok(CommunityLookup::Absent);
             }
-        } else {
+        };
+
+        let group_meta = snapshot.metadata;
+        tracing::info!("[CommunityService::lookup] Retrieved metadata for {}: name='{}', members={}, geohash={:?}, display_geohash={:?}",
+            group_id, group_meta.name, group_meta.member_count, group_meta.geohash, group_meta.display_geohash);
+
+        // If group exists and has no members, it's essentially "new" for the first user
+        // Treat it as absent so the first user becomes admin, unless the member list could
+        // not be read in full: handing a populated group to a stranger is worse than a retry
+        if group_meta.member_count == 0 && snapshot.members.possibly_incomplete {
+            tracing::warn!(
+                "[CommunityService::lookup] Member list of {} could not be read, not treating as new",
+                group_id
+            );
+            return Err(RelayError::QueryInconclusive(group_id).into());
+        }
+        if group_meta.member_count == 0 {
             tracing::info!(
-                "[CommunityService::get] Group {} not found on relay",
+                "[CommunityService::lookup] Group {} has 0 members, treating as new",
                 group_id
             );
+            return Ok(CommunityLookup::Absent);
         }
 
-        // Group doesn't exist on relay
-        None
-    }
+        // Group exists with members but no geohash - corrupted state
+        let Some(geohash) = group_meta.geohash else {
+            tracing::error!(
+                "[CommunityService::lookup] Group {} exists with {} members but has no geohash!",
+                group_id,
+                group_meta.member_count
+            );
+            return Err(CommunityError::Corrupted(*id));
+        };
 
-    /// Create or get community
-    /// Check if group exists but has no geohash (corrupted state)
-    async fn group_exists_without_geohash(&self, community_id: &Uuid) -> bool {
         tracing::info!(
-            "[group_exists_without_geohash] Looking up group for UUID {}",
-            community_id
+            "[CommunityService::lookup] Group {} has geohash: {}",
+            group_id,
+            geohash
         );
+        Ok(CommunityLookup::Existing(CommunityMetadata::existing(
+            geohash,
+            group_meta,
+            snapshot.members,
+        )))
+    }
 
-        // Look up the group ID from UUID
-        let group_id = match self
-            .relay_service
-            .read()
-            .await
-            .find_group_by_uuid(community_id)
-            .await
-        {
-            Ok(Some(gid)) => gid,
-            Ok(None) => {
-                tracing::info!(
-                    "[group_exists_without_geohash] No group found for UUID {}",
-                    community_id
-                );
-                return false;
+    /// Create a new community at `location` with `creator_pubkey` as its admin
+    /// Declines with NearbyExists when a populated community already occupies the spot.
+    /// The deadline is only checked before each step starts: a group is never left half-made
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
+        &self,
+        community_id: Uuid,
+        location: Coordinates,
+        creator_pubkey: String,
+        active_until: Option<Timestamp>,
+        max_members: Option<u32>,
+        age_restricted: bool,
+        country: Option<String>,
+        force: bool,
+        deadline: &Deadline,
+    ) -> Result<CreatedCommunity, CommunityError> {
+        // Calculate geohash for the location
+        let geohash = location.geohash(8)?;
+
+        // A second sticker at the same spot should not fragment an active community
+        let candidates: Vec<_> = within(
+            deadline,
+            "nearby community check",
+            self.groups.nearby_communities(&geohash),
+        )
+        .await?
+        .into_iter()
+        .filter(|c| c.community_id != Some(community_id))
+        .collect();
+        let duplicate = find_populated_duplicate(candidates, force, |group_id| {
+            let groups = self.groups.clone();
+            async move { groups.get_group_metadata(&group_id).await.ok() }
+        })
+        .await;
+        if let Some((existing, metadata)) = duplicate {
+            tracing::info!(
+                "[CommunityService::get_or_create] Community {} already anchored near {}, not creating {}",
+                existing.group_id,
+                geohash,
+                community_id
+            );
+            return Err(NearbyCommunityExists {
+                group_id: existing.group_id,
+                community_id: existing.community_id,
+                metadata,
             }
+            .into());
+        }
+
+        // Create new community on relay; only other writes to this community wait on it, and
+        // only a few creations run at once so a burst of first scans cannot flood the relay
+        let create_group = self.writer.create_group(
+            community_id,
+            fallback_community_name(&community_id),
+            creator_pubkey.clone(),
+            location,
+            active_until,
+            max_members,
+            age_restricted,
+            country,
+        );
+        let created = within(
+            deadline,
+            "group creation",
+            self.creations.run(deadline.remaining(), create_group),
+        )
+        .await??;
+        let created = match created {
+            Ok(created) => created,
+            // Retriable: the relay answered before auth was confirmed
+            Err(e @ RelayError::QueryInconclusive(_)) => return Err(e.into()),
+            // Not this community's fault: the relay no longer takes the service's key
+            Err(e @ RelayError::RelayKeyUnauthorized(_)) => return Err(e.into()),
             Err(e) => {
-                tracing::error!(
-                    "[group_exists_without_geohash] Error looking up group: {}",
-                    e
-                );
-                return false;
+                return Err(CommunityError::CreationFailed {
+                    step: "group creation",
+                    reason: e.to_string(),
+                })
             }
         };
 
-        tracing::info!(
-            "[group_exists_without_geohash] Found group {}, checking metadata",
-            group_id
-        );
+        // Return the created community metadata
+        let metadata = CommunityMetadata {
+            name: created.name,
+            picture: None,
+            anchors: vec![geohash.clone()],
+            geohash,
+            active_until,
+            join_mode: JoinMode::Auto,
+            max_members: max_members.filter(|max| *max > 0),
+            archived: false,
+            members: MemberSet::complete(vec![creator_pubkey]),
+            welcome: None,
+            rules: Vec::new(),
+            relay_url: None,
+            is_public: false,
+            age_restricted,
+        };
+        Ok(CreatedCommunity {
+            group_id: created.group_id,
+            metadata,
+        })
+    }
 
-        if let Ok(group_meta) = self
-            .relay_service
-            .read()
-            .await
-            .get_group_metadata(&group_id)
+    /// Group id of a community that was just looked up or created (the lookup is cached)
+    pub async fn group_id(&self, id: &Uuid, deadline: &Deadline) -> Result<String, CommunityError> {
+        within(deadline, "group lookup", self.groups.find_group_by_uuid(id))
+            .await??
+            .ok_or(CommunityError::NotFound(*id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_no_relay_call_starts_after_the_deadline() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let deadline = Deadline::after(clock.clone(), 8);
+        let calls = AtomicUsize::new(0);
+        let relay_call = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+        };
+
+        // The lookup starts within budget, then the relay stalls past the deadline
+        within(&deadline, "group lookup", relay_call())
             .await
-        {
-            tracing::info!("[group_exists_without_geohash] Group {} metadata: members={}, geohash={:?}, display_geohash={:?}",
-                group_id, group_meta.member_count, group_meta.geohash, group_meta.display_geohash);
+            .unwrap();
+        clock.advance(8);
 
-            // Group exists with members but no geohash - corrupted state
-            let result = group_meta.member_count > 0 && group_meta.geohash.is_none();
-            tracing::info!(
-                "[group_exists_without_geohash] Group {} exists_without_geohash = {}",
-                group_id,
-                result
-            );
-            return result;
-        }
-        tracing::info!(
-            "[group_exists_without_geohash] Group {} not found on relay",
-            group_id
+        let snapshot = within(&deadline, "community lookup", relay_call()).await;
+        assert!(matches!(
+            snapshot,
+            Err(CommunityError::Timeout {
+                stage: "community lookup"
+            })
+        ));
+        let creation = within(&deadline, "group creation", relay_call()).await;
+        assert!(matches!(creation, Err(CommunityError::Timeout { .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(deadline.remaining(), std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_community_name_and_picture_come_from_the_group() {
+        use nostr_sdk::prelude::*;
+
+        let keys = Keys::generate();
+        let group = |picture: &str| {
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tag(Tag::identifier("peek-3a7e5c59"))
+                .tag(Tag::custom(TagKind::Name, ["Blue Bottle Coffee"]))
+                .tag(Tag::custom(TagKind::Custom("picture".into()), [picture]))
+                .sign_with_keys(&keys)
+                .unwrap();
+            GroupMetadata::from_event(&event, 1)
+        };
+
+        let named = CommunityMetadata::existing("9q8yyk8y".to_string(), group(""), vec![]);
+        assert_eq!(named.name, "Blue Bottle Coffee");
+        // Groups are created with an empty picture tag, which is no picture
+        assert_eq!(named.picture, None);
+
+        let pictured = CommunityMetadata::existing(
+            "9q8yyk8y".to_string(),
+            group("https://example.com/blue-bottle.png"),
+            vec![],
+        );
+        assert_eq!(
+            pictured.picture.as_deref(),
+            Some("https://example.com/blue-bottle.png")
         );
-        false
+
+        // A community created without a place name is named after its id
+        let id = Uuid::parse_str("3a7e5c59-c0a1-4876-acf1-56189b86aa0d").unwrap();
+        assert_eq!(fallback_community_name(&id), "Community 3a7e5c59");
     }
 
-    /// Returns (community_metadata, is_new)
-    pub async fn get_or_create(
-        &self,
-        community_id: Uuid,
-        _qr_id: String,
-        location: LocationPoint,
-        creator_pubkey: String,
-    ) -> Result<(CommunityMetadata, bool), Box<dyn std::error::Error>> {
-        // Check if group exists but has no geohash (corrupted state)
-        if self.group_exists_without_geohash(&community_id).await {
-            return Err(format!(
-                "Community {} exists but has no location geohash - this is a corrupted state that needs manual intervention",
-                community_id
-            ).into());
-        }
+    #[test]
+    fn test_time_boxed_community_join_window() {
+        let community = CommunityMetadata {
+            name: "Community 3a7e5c59".to_string(),
+            picture: None,
+            geohash: "9q8yyk8y".to_string(),
+            anchors: vec!["9q8yyk8y".to_string()],
+            active_until: Some(Timestamp::from(1_760_000_000)),
+            join_mode: JoinMode::Auto,
+            max_members: None,
+            archived: false,
+            members: MemberSet::default(),
+            welcome: None,
+            rules: vec![],
+            relay_url: None,
+            is_public: false,
+            age_restricted: false,
+        };
 
-        // Check if community already exists and is valid
-        if let Some(existing) = self.get(&community_id).await {
-            return Ok((existing, false));
-        }
+        // Join before the deadline is accepted
+        assert!(community.accepts_new_members_at(Timestamp::from(1_759_999_999)));
+        // Join at or after the deadline is refused
+        assert!(!community.accepts_new_members_at(Timestamp::from(1_760_000_000)));
+        assert!(!community.accepts_new_members_at(Timestamp::from(1_760_086_400)));
+    }
 
-        // Create new community on relay
-        let _group_id = self
-            .relay_service
-            .write()
-            .await
-            .create_group(
-                community_id,
-                format!("Community {}", &community_id.to_string()[..8]),
-                creator_pubkey.clone(),
-                Location {
-                    latitude: location.latitude,
-                    longitude: location.longitude,
-                },
-            )
-            .await?;
+    #[test]
+    fn test_open_ended_community_always_accepts() {
+        let community = CommunityMetadata {
+            name: "Community 3a7e5c59".to_string(),
+            picture: None,
+            geohash: "9q8yyk8y".to_string(),
+            anchors: vec!["9q8yyk8y".to_string()],
+            active_until: None,
+            join_mode: JoinMode::Auto,
+            max_members: None,
+            archived: false,
+            members: MemberSet::default(),
+            welcome: None,
+            rules: vec![],
+            relay_url: None,
+            is_public: false,
+            age_restricted: false,
+        };
+        assert!(community.accepts_new_members_at(Timestamp::from(u64::MAX)));
+    }
 
-        // Calculate geohash for the location
-        let geohash = encode(
-            Coord {
-                x: location.longitude,
-                y: location.latitude,
-            },
-            8,
-        )
-        .map_err(|e| format!("Failed to encode location: {}", e))?;
+    #[test]
+    fn test_community_is_full_exactly_at_its_cap() {
+        let mut community = CommunityMetadata {
+            name: "Community 3a7e5c59".to_string(),
+            picture: None,
+            geohash: "9q8yyk8y".to_string(),
+            anchors: vec!["9q8yyk8y".to_string()],
+            active_until: None,
+            join_mode: JoinMode::Auto,
+            max_members: Some(3),
+            archived: false,
+            members: MemberSet::complete(vec!["a".to_string(), "b".to_string()]),
+            welcome: None,
+            rules: vec![],
+            relay_url: None,
+            is_public: false,
+            age_restricted: false,
+        };
+        assert!(!community.is_full());
 
-        // Return the created community metadata
-        let metadata = CommunityMetadata { geohash };
+        community.members.pubkeys.push("c".to_string());
+        assert!(community.is_full());
+
+        community.max_members = None;
+        assert!(!community.is_full());
+    }
+
+    #[test]
+    fn test_relay_override_is_returned_instead_of_the_public_relay() {
+        let mut community = CommunityMetadata {
+            name: "Community 3a7e5c59".to_string(),
+            picture: None,
+            geohash: "9q8yyk8y".to_string(),
+            anchors: vec!["9q8yyk8y".to_string()],
+            active_until: None,
+            join_mode: JoinMode::Auto,
+            max_members: None,
+            archived: false,
+            members: MemberSet::default(),
+            welcome: None,
+            rules: vec![],
+            relay_url: Some("wss://eu.peek.example".to_string()),
+            is_public: false,
+            age_restricted: false,
+        };
+        assert_eq!(
+            community.relay_url_or("wss://peek.example"),
+            "wss://eu.peek.example"
+        );
+
+        community.relay_url = None;
+        assert_eq!(
+            community.relay_url_or("wss://peek.example"),
+            "wss://peek.example"
+        );
+    }
+
+    #[test]
+    fn test_archived_community_refuses_new_members_until_unarchived() {
+        let mut community = CommunityMetadata {
+            name: "Community 3a7e5c59".to_string(),
+            picture: None,
+            geohash: "9q8yyk8y".to_string(),
+            anchors: vec!["9q8yyk8y".to_string()],
+            active_until: None,
+            join_mode: JoinMode::Auto,
+            max_members: None,
+            archived: true,
+            members: MemberSet::complete(vec!["a".to_string()]),
+            welcome: None,
+            rules: vec![],
+            relay_url: None,
+            is_public: false,
+            age_restricted: false,
+        };
+        let now = Timestamp::from(1_760_000_000);
+        assert!(!community.accepts_new_members_at(now));
+        assert!(community.has_member("a"));
 
-        Ok((metadata, true))
+        community.archived = false;
+        assert!(community.accepts_new_members_at(now));
     }
 }
@@ -0,0 +1,661 @@
+//! Clusters of communities a user may only belong to one of
+//!
+//! Some deployments run rival communities side by side, e.g. the two teams' areas at a match,
+//! and want each fan in one of them. The operator lists such communities as a cluster through
+//! the admin API; the definitions are kept in a relay-authored kind 30078 event and loaded at
+//! startup. A location-validated join into an exclusive cluster's community is refused while
+//! the requester is a member of another community of the cluster, unless they ask to transfer,
+//! in which case they leave the old community before being added to the new one. Admins of the
+//! old community cannot transfer, as leaving would drop their role. Clusters that are not
+//! exclusive only group communities and change nothing about joining.
+
+use nostr_sdk::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::RwLock;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::metrics;
+use super::relay::RelayError;
+use super::relay_access::{DiscoveryPublisher, GroupReader, GroupWriter};
+
+/// d tag of the relay-authored app data event holding the cluster definitions
+pub const CLUSTERS_D_TAG: &str = "community-clusters";
+
+/// Most clusters one deployment may define
+pub const MAX_CLUSTERS: usize = 100;
+
+/// Most communities one cluster may list; a join reads the member list of each
+pub const MAX_CLUSTER_COMMUNITIES: usize = 20;
+
+/// A set of communities defined by the operator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CommunityCluster {
+    /// Operator's name for the cluster, unique among clusters
+    pub name: String,
+    /// Community UUIDs; they need not have been created yet
+    pub communities: Vec<Uuid>,
+    /// Whether a user may be a member of only one of the communities
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+/// Every cluster, as stored and as exchanged with the admin API
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ClusterDefinitions {
+    pub clusters: Vec<CommunityCluster>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidClusters {
+    #[error("At most {0} clusters can be defined")]
+    TooMany(usize),
+    #[error("Cluster names must not be empty")]
+    EmptyName,
+    #[error("Cluster {0} is defined more than once")]
+    DuplicateName(String),
+    #[error("Cluster {0} must list at least two communities")]
+    TooFewCommunities(String),
+    #[error("Cluster {0} lists more than {1} communities")]
+    TooManyCommunities(String, usize),
+    #[error("Cluster {0} lists community {1} more than once")]
+    DuplicateCommunity(String, Uuid),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClusterTransferError {
+    #[error("Admins of {0} cannot transfer out of it")]
+    Administrator(String),
+    #[error("{0}")]
+    Relay(#[from] RelayError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClusterUpdateError {
+    #[error("{0}")]
+    Invalid(#[from] InvalidClusters),
+    #[error("Failed to store the clusters: {0}")]
+    Relay(#[from] RelayError),
+}
+
+impl ClusterDefinitions {
+    /// Refuse definitions a join could not be checked against sensibly
+    pub fn validate(&self) -> Result<(), InvalidClusters> {
+        if self.clusters.len() > MAX_CLUSTERS {
+            return Err(InvalidClusters::TooMany(MAX_CLUSTERS));
+        }
+        let mut names = HashSet::new();
+        for cluster in &self.clusters {
+            let name = cluster.name.trim();
+            if name.is_empty() {
+                return Err(InvalidClusters::EmptyName);
+            }
+            if !names.insert(name) {
+                return Err(InvalidClusters::DuplicateName(name.to_string()));
+            }
+            if cluster.communities.len() < 2 {
+                return Err(InvalidClusters::TooFewCommunities(name.to_string()));
+            }
+            if cluster.communities.len() > MAX_CLUSTER_COMMUNITIES {
+                return Err(InvalidClusters::TooManyCommunities(
+                    name.to_string(),
+                    MAX_CLUSTER_COMMUNITIES,
+                ));
+            }
+            let mut seen = HashSet::new();
+            if let Some(duplicate) = cluster.communities.iter().find(|id| !seen.insert(*id)) {
+                return Err(InvalidClusters::DuplicateCommunity(
+                    name.to_string(),
+                    *duplicate,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A community of the same exclusive cluster the requester already belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterConflict {
+    pub cluster: String,
+    pub community_id: Uuid,
+    pub group_id: String,
+    pub community_name: String,
+}
+
+impl ClusterConflict {
+    /// Move `pubkey` out of the conflicting community, then run `join`
+    ///
+    /// Admins of the conflicting community are refused before anything changes, since leaving
+    /// would take their role with it. The member leaves first, so they are never in both
+    /// communities; each write takes its group's lock in turn, never both at once. If `join`
+    /// fails they are added back to the old community and its error is returned as the inner
+    /// result; the outer error means they could not leave and `join` never ran.
+    pub async fn transfer<T, E>(
+        &self,
+        writer: &GroupWriter,
+        pubkey: &PublicKey,
+        join: impl Future<Output = Result<T, E>>,
+    ) -> Result<Result<T, E>, ClusterTransferError>
+    where
+        E: std::fmt::Display,
+    {
+        if writer
+            .reader()
+            .is_group_admin(&self.group_id, pubkey)
+            .await?
+        {
+            metrics::increment("peek_cluster_transfers_total", &[("outcome", "refused")]);
+            return Err(ClusterTransferError::Administrator(
+                self.community_name.clone(),
+            ));
+        }
+        let pubkey = pubkey.to_hex();
+        writer.remove_group_member(&self.group_id, &pubkey).await?;
+        let joined = join.await;
+        match &joined {
+            Ok(_) => {
+                info!(
+                    "🔀 Moved {} out of {} within cluster {}",
+                    pubkey, self.group_id, self.cluster
+                );
+                metrics::increment("peek_cluster_transfers_total", &[("outcome", "moved")]);
+            }
+            Err(e) => {
+                let outcome = match writer
+                    .add_group_member(&self.group_id, &pubkey, false)
+                    .await
+                {
+                    Ok(()) => "restored",
+                    Err(restore) => {
+                        error!(
+                            "❌ Transfer of {} failed ({}) and they could not be put back in {}: {}",
+                            pubkey, e, self.group_id, restore
+                        );
+                        "lost"
+                    }
+                };
+                metrics::increment("peek_cluster_transfers_total", &[("outcome", outcome)]);
+            }
+        }
+        Ok(joined)
+    }
+}
+
+/// The operator's cluster definitions, kept in relay app data
+///
+/// A read-only deployment logs the save and only changes the definitions in memory.
+pub struct CommunityClusters {
+    publisher: DiscoveryPublisher,
+    definitions: RwLock<ClusterDefinitions>,
+}
+
+impl CommunityClusters {
+    /// No clusters until `load` reads the stored ones
+    pub fn new(publisher: DiscoveryPublisher) -> Self {
+        Self {
+            publisher,
+            definitions: RwLock::new(ClusterDefinitions::default()),
+        }
+    }
+
+    /// Replace the definitions in memory with the stored ones, returning how many there are
+    pub async fn load(&self) -> Result<usize, RelayError> {
+        let stored = match self.publisher.fetch_app_data(CLUSTERS_D_TAG).await? {
+            Some(content) => serde_json::from_str(&content)?,
+            None => ClusterDefinitions::default(),
+        };
+        let count = stored.clusters.len();
+        *self.definitions.write().unwrap() = stored;
+        Ok(count)
+    }
+
+    pub fn definitions(&self) -> ClusterDefinitions {
+        self.definitions.read().unwrap().clone()
+    }
+
+    /// Validate and store new definitions; joins are checked against them once stored
+    pub async fn replace(&self, definitions: ClusterDefinitions) -> Result<(), ClusterUpdateError> {
+        definitions.validate()?;
+        let content = serde_json::to_string(&definitions).map_err(RelayError::from)?;
+        self.publisher
+            .publish_app_data(CLUSTERS_D_TAG, content)
+            .await?;
+        *self.definitions.write().unwrap() = definitions;
+        Ok(())
+    }
+
+    /// The other communities of every exclusive cluster listing `community_id`, with the
+    /// name of the cluster; a community in several clusters may appear more than once
+    fn exclusive_siblings(&self, community_id: &Uuid) -> Vec<(String, Uuid)> {
+        self.definitions
+            .read()
+            .unwrap()
+            .clusters
+            .iter()
+            .filter(|cluster| cluster.exclusive && cluster.communities.contains(community_id))
+            .flat_map(|cluster| {
+                cluster
+                    .communities
+                    .iter()
+                    .filter(|sibling| *sibling != community_id)
+                    .map(|sibling| (cluster.name.clone(), *sibling))
+            })
+            .collect()
+    }
+
+    /// The first community sharing an exclusive cluster with `community_id` that `pubkey`
+    /// (hex) is a member of
+    ///
+    /// Communities outside exclusive clusters cost no relay reads. Siblings that have not
+    /// been created yet have no members; a member list the relay cut short is taken as it is.
+    pub async fn conflict(
+        &self,
+        groups: &GroupReader,
+        community_id: &Uuid,
+        pubkey: &str,
+    ) -> Result<Option<ClusterConflict>, RelayError> {
+        let mut checked = HashSet::new();
+        for (cluster, sibling) in self.exclusive_siblings(community_id) {
+            if !checked.insert(sibling) {
+                continue;
+            }
+            let Some(group_id) = groups.find_group_by_uuid(&sibling).await? else {
+                continue;
+            };
+            let snapshot = groups.get_group_snapshot(&group_id).await?;
+            if snapshot.members.contains(pubkey) {
+                return Ok(Some(ClusterConflict {
+                    cluster,
+                    community_id: sibling,
+                    group_id,
+                    community_name: snapshot.metadata.name,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_relay::FakeRelay;
+    use crate::libraries::clock::SystemClock;
+    use crate::libraries::exclusion_zones::ExclusionZones;
+    use crate::libraries::relay_url::RelayUrl;
+    use crate::models::ProtocolConfig;
+    use crate::services::discovery_map::DiscoveryMaps;
+    use crate::services::previous_refs::PreviousRefs;
+    use crate::services::relay::RelayService;
+    use crate::services::relay_circuit::RelayCircuit;
+    use nostr_sdk::prelude::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const RED: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+    const BLUE: &str = "9b1f0c7e-5d2a-4c8e-b6f3-1a2b3c4d5e6f";
+    const CAFE: &str = "5f2c8a41-7e3b-4d9a-a1c6-2b8e4f0d3c7a";
+
+    fn group_of(community: &str) -> String {
+        format!("peek-{}", &community[..8])
+    }
+
+    /// Communities of a stadium on an in-process relay, each founded by a steward
+    struct Stadium {
+        relay: Arc<FakeRelay>,
+        url: String,
+        relay_keys: Keys,
+        writer: GroupWriter,
+    }
+
+    impl Stadium {
+        /// `(community UUID, name, founder)` for each community to create
+        async fn open(communities: &[(&str, &str, PublicKey)]) -> Self {
+            let relay_keys = Keys::generate();
+            let relay = FakeRelay::new(relay_keys.clone());
+            let protocol = ProtocolConfig::default();
+            for (community, name, founder) in communities {
+                relay.seed_group(
+                    &group_of(community),
+                    vec![
+                        Tag::custom(TagKind::Name, [name.to_string()]),
+                        Tag::custom(
+                            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+                            [protocol.uuid_tag(&community.parse().unwrap())],
+                        ),
+                        Tag::custom(
+                            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K)),
+                            [protocol.uuid_namespace.clone()],
+                        ),
+                    ],
+                    *founder,
+                );
+            }
+            let url = relay.clone().spawn().await.unwrap();
+            let writer = GroupWriter::new(connect(&url, &relay_keys).await);
+            Self {
+                relay,
+                url,
+                relay_keys,
+                writer,
+            }
+        }
+
+        async fn join(&self, community: &str, fan: &PublicKey) {
+            self.writer
+                .add_group_member(&group_of(community), &fan.to_hex(), false)
+                .await
+                .unwrap();
+        }
+
+        fn is_member(&self, community: &str, fan: &PublicKey) -> bool {
+            self.relay
+                .members(&group_of(community))
+                .unwrap()
+                .contains(fan)
+        }
+
+        /// Clusters stored on this relay, over a connection of their own
+        async fn clusters(&self) -> CommunityClusters {
+            CommunityClusters::new(DiscoveryPublisher::new(
+                connect(&self.url, &self.relay_keys).await,
+            ))
+        }
+
+        /// Relay reads of group metadata and member lists so far
+        fn group_reads(&self) -> usize {
+            self.relay.reqs_for_kind(39000) + self.relay.reqs_for_kind(39002)
+        }
+    }
+
+    async fn connect(url: &str, relay_keys: &Keys) -> Arc<RelayService> {
+        let protocol = ProtocolConfig::default();
+        let relay_service = RelayService::new(
+            RelayUrl::parse(url).unwrap(),
+            relay_keys.clone(),
+            protocol.clone(),
+            Duration::from_millis(500),
+            DiscoveryMaps {
+                d_tag: protocol.discovery_map_d_tag.clone(),
+                prefixes: Vec::new(),
+                signer: None,
+                exclusion_zones: ExclusionZones::default(),
+            },
+            4096,
+            RelayCircuit::new(Arc::new(SystemClock), 0, Duration::from_secs(30)),
+            PreviousRefs::new(3),
+        )
+        .await
+        .unwrap();
+        Arc::new(relay_service)
+    }
+
+    fn cluster(name: &str, communities: &[&str], exclusive: bool) -> CommunityCluster {
+        CommunityCluster {
+            name: name.to_string(),
+            communities: communities.iter().map(|id| id.parse().unwrap()).collect(),
+            exclusive,
+        }
+    }
+
+    async fn clusters(stadium: &Stadium, defined: Vec<CommunityCluster>) -> CommunityClusters {
+        let clusters = stadium.clusters().await;
+        clusters
+            .replace(ClusterDefinitions { clusters: defined })
+            .await
+            .unwrap();
+        clusters
+    }
+
+    fn steward() -> PublicKey {
+        Keys::generate().public_key()
+    }
+
+    #[tokio::test]
+    async fn test_member_of_a_rival_community_is_found() {
+        let stadium = Stadium::open(&[
+            (RED, "Red Stand", steward()),
+            (BLUE, "Blue Stand", steward()),
+        ])
+        .await;
+        let fan = Keys::generate().public_key();
+        stadium.join(RED, &fan).await;
+        let clusters = clusters(&stadium, vec![cluster("derby", &[RED, BLUE], true)]).await;
+        let groups = stadium.writer.reader();
+
+        let conflict = clusters
+            .conflict(&groups, &BLUE.parse().unwrap(), &fan.to_hex())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            conflict,
+            ClusterConflict {
+                cluster: "derby".to_string(),
+                community_id: RED.parse().unwrap(),
+                group_id: group_of(RED),
+                community_name: "Red Stand".to_string(),
+            }
+        );
+
+        // Someone in neither community, and the rival community itself, are fine
+        let newcomer = Keys::generate().public_key();
+        assert!(clusters
+            .conflict(&groups, &BLUE.parse().unwrap(), &newcomer.to_hex())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(clusters
+            .conflict(&groups, &RED.parse().unwrap(), &fan.to_hex())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_communities_outside_exclusive_clusters_are_unaffected() {
+        let stadium = Stadium::open(&[
+            (RED, "Red Stand", steward()),
+            (BLUE, "Blue Stand", steward()),
+            (CAFE, "Food Court Cafe", steward()),
+        ])
+        .await;
+        let fan = Keys::generate().public_key();
+        stadium.join(RED, &fan).await;
+        let clusters = clusters(
+            &stadium,
+            vec![
+                cluster("derby", &[RED, BLUE], true),
+                cluster("food-court", &[CAFE, RED], false),
+            ],
+        )
+        .await;
+        let groups = stadium.writer.reader();
+        let reads = stadium.group_reads();
+
+        // Only grouped with RED by a cluster that is not exclusive
+        assert!(clusters
+            .conflict(&groups, &CAFE.parse().unwrap(), &fan.to_hex())
+            .await
+            .unwrap()
+            .is_none());
+        let unclustered: Uuid = "0d3c7a5f-2c8a-417e-9b3d-4d9aa1c62b8e".parse().unwrap();
+        assert!(clusters
+            .conflict(&groups, &unclustered, &fan.to_hex())
+            .await
+            .unwrap()
+            .is_none());
+        // Neither cost a relay read
+        assert_eq!(stadium.group_reads(), reads);
+    }
+
+    #[tokio::test]
+    async fn test_siblings_not_yet_created_have_no_members() {
+        let stadium = Stadium::open(&[(BLUE, "Blue Stand", steward())]).await;
+        let clusters = clusters(&stadium, vec![cluster("derby", &[RED, BLUE], true)]).await;
+        let fan = Keys::generate().public_key();
+
+        assert!(clusters
+            .conflict(
+                &stadium.writer.reader(),
+                &BLUE.parse().unwrap(),
+                &fan.to_hex()
+            )
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_leaves_the_old_community_before_joining() {
+        let stadium = Stadium::open(&[
+            (RED, "Red Stand", steward()),
+            (BLUE, "Blue Stand", steward()),
+        ])
+        .await;
+        let fan = Keys::generate().public_key();
+        stadium.join(RED, &fan).await;
+        let clusters = clusters(&stadium, vec![cluster("derby", &[RED, BLUE], true)]).await;
+        let conflict = clusters
+            .conflict(
+                &stadium.writer.reader(),
+                &BLUE.parse().unwrap(),
+                &fan.to_hex(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let joined = conflict
+            .transfer(&stadium.writer, &fan, async {
+                // Never in both communities
+                assert!(!stadium.is_member(RED, &fan));
+                stadium
+                    .writer
+                    .add_group_member(&group_of(BLUE), &fan.to_hex(), false)
+                    .await
+            })
+            .await
+            .unwrap();
+
+        assert!(joined.is_ok());
+        assert!(!stadium.is_member(RED, &fan));
+        assert!(stadium.is_member(BLUE, &fan));
+    }
+
+    #[tokio::test]
+    async fn test_failed_transfer_puts_the_member_back() {
+        let stadium = Stadium::open(&[
+            (RED, "Red Stand", steward()),
+            (BLUE, "Blue Stand", steward()),
+        ])
+        .await;
+        let fan = Keys::generate().public_key();
+        stadium.join(RED, &fan).await;
+        let clusters = clusters(&stadium, vec![cluster("derby", &[RED, BLUE], true)]).await;
+        let conflict = clusters
+            .conflict(
+                &stadium.writer.reader(),
+                &BLUE.parse().unwrap(),
+                &fan.to_hex(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let joined = conflict
+            .transfer(&stadium.writer, &fan, async {
+                Err::<(), _>("blocked: rate-limited")
+            })
+            .await
+            .unwrap();
+
+        assert!(joined.is_err());
+        assert!(stadium.is_member(RED, &fan));
+        assert!(!stadium.is_member(BLUE, &fan));
+    }
+
+    #[tokio::test]
+    async fn test_admin_cannot_transfer_out_of_their_community() {
+        let founder = Keys::generate().public_key();
+        let stadium =
+            Stadium::open(&[(RED, "Red Stand", founder), (BLUE, "Blue Stand", steward())]).await;
+        let clusters = clusters(&stadium, vec![cluster("derby", &[RED, BLUE], true)]).await;
+        let conflict = clusters
+            .conflict(
+                &stadium.writer.reader(),
+                &BLUE.parse().unwrap(),
+                &founder.to_hex(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let ran = AtomicBool::new(false);
+        let refused = conflict
+            .transfer(&stadium.writer, &founder, async {
+                ran.store(true, Ordering::SeqCst);
+                Ok::<(), RelayError>(())
+            })
+            .await;
+
+        assert!(matches!(
+            refused,
+            Err(ClusterTransferError::Administrator(name)) if name == "Red Stand"
+        ));
+        assert!(!ran.load(Ordering::SeqCst));
+        assert!(stadium.is_member(RED, &founder));
+        assert!(stadium
+            .relay
+            .admins(&group_of(RED))
+            .unwrap()
+            .contains(&founder));
+        assert!(!stadium.is_member(BLUE, &founder));
+    }
+
+    #[tokio::test]
+    async fn test_definitions_are_validated_stored_and_reloaded() {
+        let stadium = Stadium::open(&[]).await;
+        let clusters = stadium.clusters().await;
+
+        for invalid in [
+            vec![cluster(" ", &[RED, BLUE], true)],
+            vec![cluster("derby", &[RED], true)],
+            vec![cluster("derby", &[RED, BLUE, RED], true)],
+            vec![
+                cluster("derby", &[RED, BLUE], true),
+                cluster("derby", &[CAFE, BLUE], false),
+            ],
+        ] {
+            let refused = clusters
+                .replace(ClusterDefinitions { clusters: invalid })
+                .await;
+            assert!(matches!(refused, Err(ClusterUpdateError::Invalid(_))));
+        }
+        assert!(stadium.relay.events(30078).is_empty());
+
+        let defined = ClusterDefinitions {
+            clusters: vec![cluster("derby", &[RED, BLUE], true)],
+        };
+        clusters.replace(defined.clone()).await.unwrap();
+
+        let restarted = stadium.clusters().await;
+        assert_eq!(restarted.load().await.unwrap(), 1);
+        assert_eq!(restarted.definitions(), defined);
+    }
+
+    #[test]
+    fn test_exclusive_flag_defaults_to_off() {
+        let parsed: CommunityCluster = serde_json::from_str(&format!(
+            r#"{{"name":"derby","communities":["{}","{}"]}}"#,
+            RED, BLUE
+        ))
+        .unwrap();
+        assert!(!parsed.exclusive);
+    }
+}
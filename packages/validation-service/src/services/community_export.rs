@@ -0,0 +1,604 @@
+//! Admin export of a community: metadata, members and basic stats as one JSON document
+//!
+//! Admins keep these for their records or to move off Peek. The document carries the anchor
+//! geohash cells the relay already publishes, never raw coordinates. Small exports travel
+//! inline in the gift-wrapped response; larger ones would not fit a NIP-44 payload, so they
+//! are uploaded to the configured Blossom media server and only the URL is returned.
+
+use base64::Engine;
+use nostr_sdk::hashes::{sha256::Hash as Sha256Hash, Hash};
+use nostr_sdk::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::join_requests::JoinMode;
+use super::metrics;
+use super::relay::{GroupSnapshot, RelayError};
+
+/// Identifies the document, so a reader can tell it apart from other JSON
+pub const EXPORT_FORMAT: &str = "peek.community-export";
+
+/// Bump when fields are removed or change meaning
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Window over which an admin's exports are counted (seconds)
+pub const EXPORT_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+// Admins tracked before idle entries are swept
+const MAX_TRACKED_ADMINS: usize = 10_000;
+
+// Blossom upload authorizations are only valid this long
+const UPLOAD_AUTH_TTL_SECS: u64 = 300;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CommunityExport {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: u64,
+    pub community_id: Uuid,
+    pub group_id: String,
+    pub metadata: ExportedMetadata,
+    pub members: Vec<ExportedMember>,
+    pub stats: ExportStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExportedMetadata {
+    pub name: String,
+    pub about: Option<String>,
+    pub rules: Vec<String>,
+    pub picture: Option<String>,
+    pub welcome: Option<String>,
+    // Level 8 anchor cells, as published on the group; not the admins' raw fixes
+    pub geohash_cells: Vec<String>,
+    pub join_mode: JoinMode,
+    pub max_members: Option<u32>,
+    pub active_until: Option<u64>,
+    pub archived: bool,
+    // When the current metadata event was published
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportedRole {
+    Admin,
+    Member,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ExportedMember {
+    pub pubkey: String,
+    pub role: ExportedRole,
+    // From the group's kind 9000 history; None when it predates what the relay keeps
+    pub joined_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ExportStats {
+    pub member_count: usize,
+    pub admin_count: usize,
+    pub anchor_count: usize,
+    // Earliest known join, which is when the community was created unless history was pruned
+    pub first_join_at: Option<u64>,
+    // The relay's member list may have been cut short, so members may be missing
+    #[serde(default)]
+    pub members_incomplete: bool,
+}
+
+impl CommunityExport {
+    pub fn build(
+        community_id: Uuid,
+        group_id: &str,
+        snapshot: &GroupSnapshot,
+        admins: &[PublicKey],
+        join_times: &HashMap<String, Timestamp>,
+        exported_at: u64,
+    ) -> Self {
+        let admins: Vec<String> = admins.iter().map(|admin| admin.to_hex()).collect();
+        let mut members: Vec<ExportedMember> = snapshot
+            .members
+            .pubkeys
+            .iter()
+            .map(|pubkey| ExportedMember {
+                pubkey: pubkey.clone(),
+                role: if admins.contains(pubkey) {
+                    ExportedRole::Admin
+                } else {
+                    ExportedRole::Member
+                },
+                joined_at: join_times.get(pubkey).map(|at| at.as_u64()),
+            })
+            .collect();
+        // Oldest members first; those without a known date at the end
+        members.sort_by_key(|member| (member.joined_at.is_none(), member.joined_at));
+
+        let metadata = &snapshot.metadata;
+        Self {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            exported_at,
+            community_id,
+            group_id: group_id.to_string(),
+            stats: ExportStats {
+                member_count: members.len(),
+                admin_count: members
+                    .iter()
+                    .filter(|member| member.role == ExportedRole::Admin)
+                    .count(),
+                anchor_count: metadata.anchors.len(),
+                first_join_at: members.iter().filter_map(|member| member.joined_at).min(),
+                members_incomplete: snapshot.members.possibly_incomplete,
+            },
+            members,
+            metadata: ExportedMetadata {
+                name: metadata.name.clone(),
+                about: metadata.about.clone(),
+                rules: metadata.rules.clone().unwrap_or_default(),
+                picture: metadata.picture.clone(),
+                welcome: metadata.welcome.clone(),
+                geohash_cells: metadata.anchors.clone(),
+                join_mode: metadata.join_mode,
+                max_members: metadata.max_members,
+                active_until: metadata.active_until.map(|at| at.as_u64()),
+                archived: metadata.archived,
+                updated_at: metadata.created_at.as_u64(),
+            },
+        }
+    }
+}
+
+/// How an export reaches the admin
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportDelivery {
+    Inline(Box<CommunityExport>),
+    Uploaded { url: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Only community admins can export it")]
+    NotAdmin,
+    #[error("Exports are limited to {0} per admin per day")]
+    LimitReached(usize),
+    #[error("Failed to read the community: {0}")]
+    Relay(#[from] RelayError),
+    #[error(
+        "The export is {0} bytes, too large to send inline, and no media server is configured"
+    )]
+    TooLargeToSend(usize),
+    #[error("Failed to upload the export: {0}")]
+    Upload(String),
+}
+
+/// Relay reads an export is assembled from
+pub trait ExportSource: Send + Sync {
+    fn group_admins(
+        &self,
+        group_id: &str,
+    ) -> impl Future<Output = Result<Vec<PublicKey>, RelayError>> + Send;
+
+    fn group_snapshot(
+        &self,
+        group_id: &str,
+    ) -> impl Future<Output = Result<GroupSnapshot, RelayError>> + Send;
+
+    fn member_join_times(
+        &self,
+        group_id: &str,
+    ) -> impl Future<Output = Result<HashMap<String, Timestamp>, RelayError>> + Send;
+}
+
+/// Stores an export too large to send inline and returns where it can be fetched
+pub trait ExportUploader: Send + Sync {
+    fn upload(&self, document: Vec<u8>) -> impl Future<Output = Result<String, String>> + Send;
+}
+
+/// Per-admin limit on exports, counted over EXPORT_WINDOW_SECS
+pub struct ExportLimiter {
+    max_exports: usize,
+    exports: Mutex<HashMap<PublicKey, VecDeque<u64>>>,
+}
+
+impl ExportLimiter {
+    /// `max_exports` per admin per day; 0 disables the limit
+    pub fn new(max_exports: usize) -> Self {
+        Self {
+            max_exports,
+            exports: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count an export by `admin` at `now`, unless it has used up its exports for the day
+    pub fn try_acquire(&self, admin: &PublicKey, now: u64) -> Result<(), ExportError> {
+        if self.max_exports == 0 {
+            return Ok(());
+        }
+        let mut exports = self.exports.lock().unwrap();
+        if exports.len() >= MAX_TRACKED_ADMINS && !exports.contains_key(admin) {
+            exports.retain(|_, recent| {
+                Self::expire(recent, now);
+                !recent.is_empty()
+            });
+        }
+        let recent = exports.entry(*admin).or_default();
+        Self::expire(recent, now);
+        if recent.len() >= self.max_exports {
+            return Err(ExportError::LimitReached(self.max_exports));
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+
+    fn expire(recent: &mut VecDeque<u64>, now: u64) {
+        while recent
+            .front()
+            .is_some_and(|at| now.saturating_sub(*at) >= EXPORT_WINDOW_SECS)
+        {
+            recent.pop_front();
+        }
+    }
+}
+
+/// Assembles exports and delivers them inline or through the media server
+pub struct CommunityExporter<U> {
+    uploader: Option<U>,
+    limiter: ExportLimiter,
+    max_inline_bytes: usize,
+}
+
+impl<U: ExportUploader> CommunityExporter<U> {
+    /// Documents over `max_inline_bytes` go through `uploader`, or fail without one
+    pub fn new(uploader: Option<U>, limiter: ExportLimiter, max_inline_bytes: usize) -> Self {
+        Self {
+            uploader,
+            limiter,
+            max_inline_bytes,
+        }
+    }
+
+    /// Export a community for one of its admins
+    ///
+    /// The admin list is read first: anyone else is refused before the limit is touched or
+    /// the member list is read.
+    pub async fn export<S: ExportSource>(
+        &self,
+        source: &S,
+        community_id: Uuid,
+        group_id: &str,
+        requester: &PublicKey,
+        now: u64,
+    ) -> Result<ExportDelivery, ExportError> {
+        let admins = source.group_admins(group_id).await?;
+        if !admins.contains(requester) {
+            return Err(ExportError::NotAdmin);
+        }
+        self.limiter.try_acquire(requester, now)?;
+
+        let (snapshot, join_times) = futures::join!(
+            source.group_snapshot(group_id),
+            source.member_join_times(group_id)
+        );
+        let snapshot = snapshot?;
+        // Join dates are a nicety; an export without them beats no export
+        let join_times = join_times.unwrap_or_else(|e| {
+            tracing::warn!("Exporting {} without join dates: {}", group_id, e);
+            HashMap::new()
+        });
+        let export =
+            CommunityExport::build(community_id, group_id, &snapshot, &admins, &join_times, now);
+
+        let document = serde_json::to_vec(&export).expect("export serializes");
+        if document.len() <= self.max_inline_bytes {
+            metrics::increment("peek_community_exports_total", &[("delivery", "inline")]);
+            return Ok(ExportDelivery::Inline(Box::new(export)));
+        }
+        let Some(uploader) = &self.uploader else {
+            return Err(ExportError::TooLargeToSend(document.len()));
+        };
+        let url = uploader
+            .upload(document)
+            .await
+            .map_err(ExportError::Upload)?;
+        metrics::increment("peek_community_exports_total", &[("delivery", "uploaded")]);
+        Ok(ExportDelivery::Uploaded { url })
+    }
+}
+
+/// Uploads exports to a Blossom server (BUD-02), authorized by the service key
+pub struct BlossomUploader {
+    server: String,
+    keys: Keys,
+}
+
+impl BlossomUploader {
+    pub fn new(server: &str, keys: Keys) -> Self {
+        Self {
+            server: server.trim_end_matches('/').to_string(),
+            keys,
+        }
+    }
+
+    /// Kind 24242 authorization for uploading exactly this blob
+    fn authorization(&self, sha256: &str) -> Result<String, String> {
+        let expiration = Timestamp::now() + UPLOAD_AUTH_TTL_SECS;
+        let event = EventBuilder::new(Kind::from(24242), "Upload community export")
+            .tags([
+                Tag::custom(TagKind::Custom("t".into()), ["upload"]),
+                Tag::custom(TagKind::Custom("x".into()), [sha256]),
+                Tag::expiration(expiration),
+            ])
+            .sign_with_keys(&self.keys)
+            .map_err(|e| e.to_string())?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(event.as_json());
+        Ok(format!("Nostr {}", encoded))
+    }
+}
+
+#[derive(Deserialize)]
+struct BlobDescriptor {
+    url: String,
+}
+
+impl ExportUploader for BlossomUploader {
+    async fn upload(&self, document: Vec<u8>) -> Result<String, String> {
+        let sha256 = Sha256Hash::hash(&document).to_string();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let response = client
+            .put(format!("{}/upload", self.server))
+            .header(reqwest::header::AUTHORIZATION, self.authorization(&sha256)?)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(document)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("media server returned {}", response.status()));
+        }
+        let blob: BlobDescriptor = response.json().await.map_err(|e| e.to_string())?;
+        Ok(blob.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::member_set::MemberSet;
+    use crate::services::relay::GroupMetadata;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const COMMUNITY: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+    const NOW: u64 = 1_760_100_000;
+
+    struct FakeGroup {
+        admin: PublicKey,
+        members: Vec<String>,
+        snapshot_reads: AtomicUsize,
+    }
+
+    impl FakeGroup {
+        fn with_members(admin: PublicKey, extra_members: usize) -> Self {
+            let mut members = vec![admin.to_hex()];
+            members.extend((0..extra_members).map(|_| Keys::generate().public_key().to_hex()));
+            Self {
+                admin,
+                members,
+                snapshot_reads: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl ExportSource for FakeGroup {
+        async fn group_admins(&self, _group_id: &str) -> Result<Vec<PublicKey>, RelayError> {
+            Ok(vec![self.admin])
+        }
+
+        async fn group_snapshot(&self, _group_id: &str) -> Result<GroupSnapshot, RelayError> {
+            self.snapshot_reads.fetch_add(1, Ordering::SeqCst);
+            let event = EventBuilder::new(Kind::from(39000), "")
+                .tags([
+                    Tag::identifier("peek-abc123"),
+                    Tag::custom(TagKind::Name, ["Blue Bottle"]),
+                    Tag::custom(TagKind::Custom("about".into()), ["Coffee regulars"]),
+                    Tag::custom(
+                        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::G)),
+                        ["9q8yyk8y"],
+                    ),
+                ])
+                .custom_created_at(Timestamp::from(1_760_050_000))
+                .sign_with_keys(&Keys::generate())
+                .unwrap();
+            Ok(GroupSnapshot {
+                metadata: GroupMetadata::from_event(&event, self.members.len() as u32),
+                members: MemberSet::complete(self.members.clone()),
+            })
+        }
+
+        async fn member_join_times(
+            &self,
+            _group_id: &str,
+        ) -> Result<HashMap<String, Timestamp>, RelayError> {
+            // The admin created the community; later members' dates were pruned
+            Ok(HashMap::from([(
+                self.admin.to_hex(),
+                Timestamp::from(1_760_000_000),
+            )]))
+        }
+    }
+
+    /// Hands out a fixed URL and keeps what it was given
+    #[derive(Clone, Default)]
+    struct FakeUploader {
+        uploaded: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl ExportUploader for FakeUploader {
+        async fn upload(&self, document: Vec<u8>) -> Result<String, String> {
+            self.uploaded.lock().unwrap().push(document);
+            Ok("https://media.example/abc.json".to_string())
+        }
+    }
+
+    fn exporter(
+        uploader: Option<FakeUploader>,
+        max_exports: usize,
+        max_inline_bytes: usize,
+    ) -> CommunityExporter<FakeUploader> {
+        CommunityExporter::new(uploader, ExportLimiter::new(max_exports), max_inline_bytes)
+    }
+
+    async fn export(
+        exporter: &CommunityExporter<FakeUploader>,
+        group: &FakeGroup,
+        requester: &PublicKey,
+    ) -> Result<ExportDelivery, ExportError> {
+        let community_id = Uuid::parse_str(COMMUNITY).unwrap();
+        exporter
+            .export(group, community_id, "peek-abc123", requester, NOW)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_inline_export_document_schema() {
+        let admin = Keys::generate().public_key();
+        let group = FakeGroup::with_members(admin, 1);
+        let delivery = export(&exporter(None, 3, 32_768), &group, &admin)
+            .await
+            .unwrap();
+        let ExportDelivery::Inline(export) = delivery else {
+            panic!("expected an inline export, got {:?}", delivery);
+        };
+
+        let document = serde_json::to_value(&export).unwrap();
+        let keys = |value: &serde_json::Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(
+            keys(&document),
+            [
+                "community_id",
+                "exported_at",
+                "format",
+                "group_id",
+                "members",
+                "metadata",
+                "stats",
+                "version"
+            ]
+        );
+        assert_eq!(
+            keys(&document["metadata"]),
+            [
+                "about",
+                "active_until",
+                "archived",
+                "geohash_cells",
+                "join_mode",
+                "max_members",
+                "name",
+                "picture",
+                "rules",
+                "updated_at",
+                "welcome"
+            ]
+        );
+        assert_eq!(document["format"], "peek.community-export");
+        assert_eq!(document["community_id"], COMMUNITY);
+        assert_eq!(document["metadata"]["name"], "Blue Bottle");
+        assert_eq!(
+            document["metadata"]["geohash_cells"],
+            serde_json::json!(["9q8yyk8y"])
+        );
+        assert_eq!(document["metadata"]["join_mode"], "auto");
+        assert_eq!(
+            document["members"][0],
+            serde_json::json!({
+                "pubkey": admin.to_hex(),
+                "role": "admin",
+                "joined_at": 1_760_000_000u64,
+            })
+        );
+        assert_eq!(document["members"][1]["role"], "member");
+        assert_eq!(document["members"][1]["joined_at"], serde_json::Value::Null);
+        assert_eq!(
+            document["stats"],
+            serde_json::json!({
+                "member_count": 2,
+                "admin_count": 1,
+                "anchor_count": 1,
+                "first_join_at": 1_760_000_000u64,
+            })
+        );
+        // No coordinates leave the service, only the published cell
+        assert!(!document.to_string().contains("latitude"));
+
+        // The document reads back as what was sent
+        let parsed: CommunityExport = serde_json::from_value(document).unwrap();
+        assert_eq!(parsed, *export);
+    }
+
+    #[tokio::test]
+    async fn test_export_over_the_cap_is_uploaded() {
+        let admin = Keys::generate().public_key();
+        let group = FakeGroup::with_members(admin, 200);
+        let uploader = FakeUploader::default();
+
+        let delivery = export(&exporter(Some(uploader.clone()), 0, 4_096), &group, &admin)
+            .await
+            .unwrap();
+        assert_eq!(
+            delivery,
+            ExportDelivery::Uploaded {
+                url: "https://media.example/abc.json".to_string()
+            }
+        );
+        let uploaded = uploader.uploaded.lock().unwrap();
+        let document: CommunityExport = serde_json::from_slice(&uploaded[0]).unwrap();
+        assert_eq!(document.stats.member_count, 201);
+
+        // The same community fits inline under a larger cap
+        let roomy = exporter(Some(uploader.clone()), 0, 1_000_000);
+        let inline = export(&roomy, &group, &admin).await.unwrap();
+        assert!(matches!(inline, ExportDelivery::Inline(_)));
+
+        // Without a media server an oversized export fails rather than being cut short
+        let unsendable = export(&exporter(None, 0, 4_096), &group, &admin).await;
+        assert!(matches!(unsendable, Err(ExportError::TooLargeToSend(_))));
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_export_and_only_so_often() {
+        let admin = Keys::generate().public_key();
+        let group = FakeGroup::with_members(admin, 3);
+        let exporter = exporter(None, 2, 32_768);
+
+        // A member is refused before anything about the members is read
+        let member = PublicKey::from_hex(&group.members[1]).unwrap();
+        let refused = export(&exporter, &group, &member).await;
+        assert!(matches!(refused, Err(ExportError::NotAdmin)));
+        assert_eq!(group.snapshot_reads.load(Ordering::SeqCst), 0);
+
+        for _ in 0..2 {
+            assert!(export(&exporter, &group, &admin).await.is_ok());
+        }
+        let limited = export(&exporter, &group, &admin).await;
+        assert!(matches!(limited, Err(ExportError::LimitReached(2))));
+
+        // The oldest export ages out of the day
+        assert!(exporter
+            .limiter
+            .try_acquire(&admin, NOW + EXPORT_WINDOW_SECS)
+            .is_ok());
+    }
+}
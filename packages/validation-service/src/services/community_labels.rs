@@ -0,0 +1,192 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::models::Coordinates;
+
+/// Synthesizes display labels for communities whose name tag is empty
+///
+/// Labels come from a reverse-geocoded place name near the display geohash, falling
+/// back to "Community {first 6 of id}". Geocode results (including misses) are cached
+/// per display geohash for the lifetime of the service, since display locations never move.
+pub struct CommunityLabeler {
+    budget: Duration,
+    cache: RwLock<HashMap<String, Option<String>>>,
+}
+
+/// A community needing a label on the discovery map
+pub struct UnlabeledCommunity {
+    pub id: String,
+    pub display_geohash: String,
+}
+
+impl CommunityLabeler {
+    /// `budget` caps the total time spent geocoding in one `label_all` call
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Produce a label for each community, keyed by display geohash
+    /// Once the geocoding budget is spent, remaining uncached communities get the id fallback
+    pub async fn label_all<F, Fut>(
+        &self,
+        communities: &[UnlabeledCommunity],
+        mut geocode: F,
+    ) -> HashMap<String, String>
+    where
+        F: FnMut(f64, f64) -> Fut,
+        Fut: Future<Output = Result<Option<String>>>,
+    {
+        let deadline = Instant::now() + self.budget;
+        let mut labels = HashMap::new();
+
+        for community in communities {
+            let cached = self
+                .cache
+                .read()
+                .await
+                .get(&community.display_geohash)
+                .cloned();
+
+            let place = match cached {
+                Some(place) => place,
+                None => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match geocode_within(&community.display_geohash, remaining, &mut geocode).await
+                    {
+                        Some(place) => {
+                            self.cache
+                                .write()
+                                .await
+                                .insert(community.display_geohash.clone(), place.clone());
+                            place
+                        }
+                        None => None,
+                    }
+                }
+            };
+
+            let label = place.unwrap_or_else(|| fallback_label(&community.id));
+            labels.insert(community.display_geohash.clone(), label);
+        }
+
+        labels
+    }
+}
+
+/// Reverse-geocode the center of a display geohash within the remaining budget
+/// Returns None when the lookup could not complete (budget spent, timeout, error, bad geohash)
+/// and Some(None) when the provider answered that there is no named place nearby
+async fn geocode_within<F, Fut>(
+    display_geohash: &str,
+    remaining: Duration,
+    geocode: &mut F,
+) -> Option<Option<String>>
+where
+    F: FnMut(f64, f64) -> Fut,
+    Fut: Future<Output = Result<Option<String>>>,
+{
+    if remaining.is_zero() {
+        return None;
+    }
+
+    let center = Coordinates::from_geohash(display_geohash).ok()?;
+
+    match tokio::time::timeout(remaining, geocode(center.latitude(), center.longitude())).await {
+        Ok(Ok(place)) => Some(place.filter(|name| !name.trim().is_empty())),
+        Ok(Err(e)) => {
+            tracing::warn!("Reverse geocode for {} failed: {}", display_geohash, e);
+            None
+        }
+        Err(_) => {
+            tracing::warn!(
+                "Reverse geocode for {} exceeded discovery budget",
+                display_geohash
+            );
+            None
+        }
+    }
+}
+
+/// Label used when no place name is available
+pub fn fallback_label(id: &str) -> String {
+    format!("Community {}", id.chars().take(6).collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn community(id: &str, display_geohash: &str) -> UnlabeledCommunity {
+        UnlabeledCommunity {
+            id: id.to_string(),
+            display_geohash: display_geohash.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_name_uses_geocoded_place_and_caches_it() {
+        let labeler = CommunityLabeler::new(Duration::from_secs(1));
+        let calls = AtomicU32::new(0);
+        let communities = [community("3f2a9c1e-0000", "9q8yyk8yt")];
+
+        for _ in 0..2 {
+            let labels = labeler
+                .label_all(&communities, |_, _| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    std::future::ready(Ok(Some("Blue Bottle Coffee".to_string())))
+                })
+                .await;
+            assert_eq!(labels["9q8yyk8yt"], "Blue Bottle Coffee");
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_place_falls_back_to_id_prefix() {
+        let labeler = CommunityLabeler::new(Duration::from_secs(1));
+        let communities = [community("3f2a9c1e-0000", "9q8yyk8yt")];
+
+        let labels = labeler
+            .label_all(&communities, |_, _| std::future::ready(Ok(None)))
+            .await;
+
+        assert_eq!(labels["9q8yyk8yt"], "Community 3f2a9c");
+    }
+
+    #[tokio::test]
+    async fn test_budget_cutoff_falls_back_without_caching() {
+        let labeler = CommunityLabeler::new(Duration::from_millis(50));
+        let communities = [
+            community("aaaaaa11", "9q8yyk8yt"),
+            community("bbbbbb22", "dr5regw3p"),
+        ];
+
+        let started = Instant::now();
+        let labels = labeler
+            .label_all(&communities, |_, _| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(Some("Too Slow".to_string()))
+            })
+            .await;
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(labels["9q8yyk8yt"], "Community aaaaaa");
+        assert_eq!(labels["dr5regw3p"], "Community bbbbbb");
+
+        // Timeouts are not cached, so a later call with a fast provider succeeds
+        let labels = labeler
+            .label_all(&communities[..1], |_, _| {
+                std::future::ready(Ok(Some("Blue Bottle Coffee".to_string())))
+            })
+            .await;
+        assert_eq!(labels["9q8yyk8yt"], "Blue Bottle Coffee");
+    }
+}
@@ -0,0 +1,361 @@
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::relay::GroupMetadata;
+use crate::models::ProtocolConfig;
+
+/// Results returned when the client does not ask for a limit
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// Most results a single search returns
+pub const MAX_SEARCH_LIMIT: usize = 50;
+
+/// Public discovery record of a community, as served by the discovery endpoints
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CommunityDiscoveryData {
+    pub community_id: Option<Uuid>,
+    pub group_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub about: Option<String>,
+    // Level 9 display geohash, never the anchor
+    pub display_geohash: Option<String>,
+    // Reverse-geocoded "Neighborhood, City", filled in by the background locality refresh
+    pub locality: Option<String>,
+    pub member_count: u32,
+    // Marked 18+ by the venue; left out of the JSON otherwise
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub age_restricted: bool,
+}
+
+/// How well a query matched, best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchQuality {
+    ExactName,
+    NamePrefix,
+    NameWordPrefix,
+    NameSubstring,
+    About,
+}
+
+/// Lowercase with accents stripped, so "Café" and "cafe" compare equal
+pub fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug)]
+struct SearchEntry {
+    name: String,
+    about: String,
+    record: CommunityDiscoveryData,
+}
+
+impl SearchEntry {
+    /// Searchable entry for a community; archived and unnamed communities are not indexed
+    fn new(group_id: &str, community_id: Option<Uuid>, metadata: GroupMetadata) -> Option<Self> {
+        if metadata.archived || metadata.name.trim().is_empty() {
+            return None;
+        }
+        Some(Self {
+            name: fold(&metadata.name),
+            about: metadata.about.as_deref().map(fold).unwrap_or_default(),
+            record: CommunityDiscoveryData {
+                community_id,
+                group_id: group_id.to_string(),
+                member_count: metadata.member_count,
+                name: metadata.name,
+                about: metadata.about,
+                display_geohash: metadata.display_geohash,
+                locality: None,
+                age_restricted: metadata.age_restricted,
+            },
+        })
+    }
+
+    fn quality(&self, query: &str, include_about: bool) -> Option<MatchQuality> {
+        if self.name == query {
+            Some(MatchQuality::ExactName)
+        } else if self.name.starts_with(query) {
+            Some(MatchQuality::NamePrefix)
+        } else if self
+            .name
+            .match_indices(query)
+            .any(|(at, _)| self.name[..at].ends_with(' '))
+        {
+            Some(MatchQuality::NameWordPrefix)
+        } else if self.name.contains(query) {
+            Some(MatchQuality::NameSubstring)
+        } else if include_about && self.about.contains(query) {
+            Some(MatchQuality::About)
+        } else {
+            None
+        }
+    }
+}
+
+/// In-memory name search over the communities in the discovery dataset
+///
+/// Rebuilt alongside the nearby index whenever the discovery data is refreshed, so a search
+/// never queries the relay. Archived communities are left out.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    entries: Vec<SearchEntry>,
+}
+
+impl SearchIndex {
+    /// Build from kind 39000 metadata events and member counts keyed by group id
+    pub fn from_events<'a>(
+        events: impl IntoIterator<Item = &'a Event>,
+        member_counts: &HashMap<String, u32>,
+        protocol: &ProtocolConfig,
+    ) -> Self {
+        let entries = events
+            .into_iter()
+            .filter_map(|event| {
+                let group_id = event.tags.identifier()?;
+                if !protocol.owns_group_id(group_id) {
+                    return None;
+                }
+                let member_count = member_counts.get(group_id).copied().unwrap_or(0);
+                let community_id = event
+                    .tags
+                    .find(TagKind::SingleLetter(SingleLetterTag::lowercase(
+                        Alphabet::I,
+                    )))
+                    .and_then(|t| t.content())
+                    .and_then(|i| protocol.parse_uuid_tag(i));
+
+                SearchEntry::new(
+                    group_id,
+                    community_id,
+                    GroupMetadata::from_event(event, member_count),
+                )
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Drop a community's record, returning it if it was indexed
+    pub fn remove_community(&mut self, community_id: &Uuid) -> Option<CommunityDiscoveryData> {
+        let at = self
+            .entries
+            .iter()
+            .position(|entry| entry.record.community_id.as_ref() == Some(community_id))?;
+        Some(self.entries.remove(at).record)
+    }
+
+    /// Index freshly loaded metadata for one community, as the next rebuild would
+    pub fn insert(
+        &mut self,
+        group_id: &str,
+        community_id: Uuid,
+        metadata: GroupMetadata,
+        locality: Option<String>,
+    ) {
+        if let Some(mut entry) = SearchEntry::new(group_id, Some(community_id), metadata) {
+            entry.record.locality = locality;
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn community_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Display geohashes of every indexed community
+    pub fn display_geohashes(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.record.display_geohash.clone())
+            .collect()
+    }
+
+    /// Set each record's locality from `localities`, keyed by display geohash
+    pub fn set_localities(&mut self, localities: &HashMap<String, String>) {
+        for entry in &mut self.entries {
+            entry.record.locality = entry
+                .record
+                .display_geohash
+                .as_ref()
+                .and_then(|geohash| localities.get(geohash))
+                .cloned();
+        }
+    }
+
+    /// Communities whose name (or, with `include_about`, description) contains `query`,
+    /// ranked by match quality, then member count; an empty query matches nothing
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        include_about: bool,
+    ) -> Vec<CommunityDiscoveryData> {
+        let query = fold(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<(MatchQuality, &SearchEntry)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| Some((entry.quality(&query, include_about)?, entry)))
+            .collect();
+        matches.sort_by(|(qa, a), (qb, b)| {
+            qa.cmp(qb)
+                .then(b.record.member_count.cmp(&a.record.member_count))
+                .then(a.name.cmp(&b.name))
+        });
+
+        matches
+            .into_iter()
+            .take(limit.min(MAX_SEARCH_LIMIT))
+            .map(|(_, entry)| entry.record.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn community(group_id: &str, name: &str, about: &str) -> Event {
+        EventBuilder::new(Kind::from(39000), "")
+            .tags([
+                Tag::identifier(group_id),
+                Tag::custom(TagKind::Name, [name]),
+                Tag::custom(TagKind::Custom("about".into()), [about]),
+                Tag::custom(TagKind::Custom("dg".into()), ["9q8yyk8yz"]),
+            ])
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    fn index() -> SearchIndex {
+        let events = [
+            community("peek-cafe", "Café Reveille", "Espresso bar"),
+            community("peek-blue", "Blue Bottle", "Coffee regulars"),
+            community("peek-park", "Dolores Park Cafe Crew", "Picnics"),
+            community("peek-cafes", "Cafe", "Every café in town"),
+            community("other-cafe", "Cafe Elsewhere", "Another deployment"),
+        ];
+        let member_counts = HashMap::from([
+            ("peek-cafe".to_string(), 12),
+            ("peek-park".to_string(), 40),
+            ("peek-cafes".to_string(), 1),
+        ]);
+        SearchIndex::from_events(events.iter(), &member_counts, &ProtocolConfig::default())
+    }
+
+    fn names(results: &[CommunityDiscoveryData]) -> Vec<&str> {
+        results.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_accent_insensitive_ranked_matches() {
+        let index = index();
+        assert_eq!(index.community_count(), 4);
+
+        // Exact, then prefix, then a later word, regardless of accents either way
+        let results = index.search("CAFÉ", 10, false);
+        assert_eq!(
+            names(&results),
+            vec!["Cafe", "Café Reveille", "Dolores Park Cafe Crew"]
+        );
+        assert_eq!(results[1].community_id, None);
+        assert_eq!(results[1].member_count, 12);
+        assert_eq!(results[1].display_geohash.as_deref(), Some("9q8yyk8yz"));
+
+        assert_eq!(
+            names(&index.search("cafe rev", 10, false)),
+            vec!["Café Reveille"]
+        );
+        assert_eq!(names(&index.search("ottl", 10, false)), vec!["Blue Bottle"]);
+    }
+
+    #[test]
+    fn test_refreshing_one_community_leaves_the_rest_indexed() {
+        let mut index = index();
+        let (cafe, blue) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let metadata = |name: &str| GroupMetadata::from_event(&community("peek-x", name, ""), 3);
+        index.insert("peek-new-cafe", cafe, metadata("Corner Cafe"), None);
+        index.insert(
+            "peek-new-blue",
+            blue,
+            metadata("Blue Door"),
+            Some("Mission, San Francisco".to_string()),
+        );
+        assert_eq!(index.community_count(), 6);
+
+        let removed = index.remove_community(&blue).unwrap();
+        assert_eq!(removed.group_id, "peek-new-blue");
+        assert_eq!(removed.locality.as_deref(), Some("Mission, San Francisco"));
+        assert_eq!(index.community_count(), 5);
+        assert!(index.search("blue door", 10, false).is_empty());
+        assert_eq!(
+            index.search("corner", 10, false)[0].community_id,
+            Some(cafe)
+        );
+        assert!(index.remove_community(&blue).is_none());
+
+        // Archived communities are not put back
+        let mut archived = metadata("Blue Door");
+        archived.archived = true;
+        index.insert("peek-new-blue", blue, archived, None);
+        assert_eq!(index.community_count(), 5);
+    }
+
+    #[test]
+    fn test_about_matches_only_when_requested() {
+        let index = index();
+        assert!(index.search("espresso", 10, false).is_empty());
+        assert_eq!(
+            names(&index.search("coffee", 10, true)),
+            vec!["Blue Bottle"]
+        );
+    }
+
+    #[test]
+    fn test_localities_appear_on_records_and_default_to_null() {
+        let mut index = index();
+        let record = &index.search("blue bottle", 1, false)[0];
+        assert_eq!(
+            serde_json::to_value(record).unwrap()["locality"],
+            serde_json::Value::Null
+        );
+
+        assert_eq!(index.display_geohashes(), vec!["9q8yyk8yz"; 4]);
+        index.set_localities(&HashMap::from([(
+            "9q8yyk8yz".to_string(),
+            "Mission District, San Francisco".to_string(),
+        )]));
+        let record = &index.search("blue bottle", 1, false)[0];
+        assert_eq!(
+            serde_json::to_value(record).unwrap()["locality"],
+            "Mission District, San Francisco"
+        );
+    }
+
+    #[test]
+    fn test_empty_query_and_limit_cap() {
+        let index = index();
+        assert!(index.search("  ", 10, true).is_empty());
+        assert_eq!(index.search("e", 2, true).len(), 2);
+
+        let many: Vec<Event> = (0..MAX_SEARCH_LIMIT + 10)
+            .map(|n| community(&format!("peek-{}", n), &format!("Cafe {}", n), ""))
+            .collect();
+        let big =
+            SearchIndex::from_events(many.iter(), &HashMap::new(), &ProtocolConfig::default());
+        assert_eq!(big.search("cafe", 1_000, false).len(), MAX_SEARCH_LIMIT);
+    }
+}
@@ -0,0 +1,221 @@
+//! Cap on concurrent group creations, so a burst of first scans does not flood the relay
+//!
+//! Each creation publishes five or more events with the relay admin key, and relays rate-limit
+//! or ban keys that publish too fast, as happens when a sticker campaign launches. Creations
+//! over the cap wait in a bounded queue for at most the request's remaining budget; the rest
+//! are shed so the client retries instead of piling up behind the relay.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::metrics;
+
+/// Why a creation was not started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CreationShed {
+    #[error("{0} group creations are already waiting")]
+    QueueFull(usize),
+    #[error("No group creation slot freed up within the request budget")]
+    TimedOut,
+}
+
+impl CreationShed {
+    fn reason(&self) -> &'static str {
+        match self {
+            Self::QueueFull(_) => "queue_full",
+            Self::TimedOut => "timed_out",
+        }
+    }
+}
+
+/// Global limit on group creations in flight, with a bounded queue of waiting ones
+pub struct CreationLimiter {
+    permits: Semaphore,
+    max_concurrent: usize,
+    max_queued: usize,
+    queued: AtomicUsize,
+}
+
+/// A place in the wait queue, given up when the wait ends or is abandoned
+struct Queued<'a>(&'a CreationLimiter);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.queued.fetch_sub(1, Ordering::SeqCst);
+        self.0.publish();
+    }
+}
+
+/// A creation slot; the gauges are updated once the permit is back in the semaphore
+struct Slot<'a> {
+    limiter: &'a CreationLimiter,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.permit.take();
+        self.limiter.publish();
+    }
+}
+
+impl CreationLimiter {
+    pub fn new(max_concurrent: usize, max_queued: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            permits: Semaphore::new(max_concurrent),
+            max_concurrent,
+            max_queued,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Run `work` once a creation slot is free, waiting at most `wait` for one
+    pub async fn run<T>(
+        &self,
+        wait: Duration,
+        work: impl Future<Output = T>,
+    ) -> Result<T, CreationShed> {
+        let _slot = match self.acquire(wait).await {
+            Ok(slot) => slot,
+            Err(shed) => {
+                tracing::warn!("[CreationLimiter] Not starting group creation: {}", shed);
+                metrics::increment(
+                    "peek_group_creations_shed_total",
+                    &[("reason", shed.reason())],
+                );
+                return Err(shed);
+            }
+        };
+        Ok(work.await)
+    }
+
+    /// Creations currently holding a slot
+    pub fn in_flight(&self) -> usize {
+        self.max_concurrent - self.permits.available_permits()
+    }
+
+    /// Creations waiting for a slot
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    async fn acquire(&self, wait: Duration) -> Result<Slot<'_>, CreationShed> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(self.slot(permit));
+        }
+
+        self.queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                (queued < self.max_queued).then_some(queued + 1)
+            })
+            .map_err(|_| CreationShed::QueueFull(self.max_queued))?;
+        let queued = Queued(self);
+        self.publish();
+
+        let permit = tokio::time::timeout(wait, self.permits.acquire()).await;
+        drop(queued);
+        match permit {
+            Ok(Ok(permit)) => Ok(self.slot(permit)),
+            // The semaphore is never closed; a wait that ran out is the only failure
+            _ => Err(CreationShed::TimedOut),
+        }
+    }
+
+    fn slot<'a>(&'a self, permit: SemaphorePermit<'a>) -> Slot<'a> {
+        self.publish();
+        Slot {
+            limiter: self,
+            permit: Some(permit),
+        }
+    }
+
+    fn publish(&self) {
+        metrics::set_gauge(
+            "peek_group_creations_in_flight",
+            &[],
+            self.in_flight() as u64,
+        );
+        metrics::set_gauge("peek_group_creations_queued", &[], self.queued() as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+
+    /// Creation against a relay that takes a while to accept its events
+    async fn slow_creation(limiter: &CreationLimiter, running: &AtomicUsize, peak: &AtomicUsize) {
+        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now, Ordering::SeqCst);
+        assert!(limiter.in_flight() <= 3);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        running.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    #[tokio::test]
+    async fn test_creation_burst_is_capped_and_the_overflow_shed() {
+        let limiter = Arc::new(CreationLimiter::new(3, 4));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let creations: Vec<_> = (0..10)
+            .map(|_| {
+                let (limiter, running, peak) = (limiter.clone(), running.clone(), peak.clone());
+                tokio::spawn(async move {
+                    limiter
+                        .run(
+                            Duration::from_secs(5),
+                            slow_creation(&limiter, &running, &peak),
+                        )
+                        .await
+                })
+            })
+            .collect();
+        let mut results = Vec::new();
+        for creation in creations {
+            results.push(creation.await.unwrap());
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        // Three ran at once, four waited their turn, the rest found the queue full
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 7);
+        assert_eq!(
+            results
+                .iter()
+                .filter(|r| **r == Err(CreationShed::QueueFull(4)))
+                .count(),
+            3
+        );
+        assert_eq!((limiter.in_flight(), limiter.queued()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_waiting_creation_is_shed_when_the_budget_runs_out() {
+        let limiter = CreationLimiter::new(1, 4);
+        let (release, gate) = oneshot::channel::<()>();
+
+        let stuck = limiter.run(Duration::from_secs(5), async {
+            gate.await.unwrap();
+        });
+        let waiting = async {
+            tokio::task::yield_now().await;
+            assert_eq!(limiter.in_flight(), 1);
+            let waited = limiter
+                .run(Duration::from_millis(20), async { "created" })
+                .await;
+            assert_eq!(limiter.queued(), 0);
+            release.send(()).unwrap();
+            waited
+        };
+        let (stuck, waited) = tokio::join!(stuck, waiting);
+
+        assert_eq!(stuck, Ok(()));
+        assert_eq!(waited, Err(CreationShed::TimedOut));
+        assert_eq!(limiter.in_flight(), 0);
+    }
+}
@@ -0,0 +1,464 @@
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use utoipa::ToSchema;
+
+use super::metrics;
+use crate::libraries::exclusion_zones::ExclusionZones;
+
+/// NIP-78 application-specific data
+pub const DISCOVERY_MAP_KIND: u16 = 30078;
+
+/// Which key signs published discovery maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscoveryMapSigner {
+    /// The relay admin key (historical default)
+    #[default]
+    Relay,
+    /// The service's gift wrap key, keeping the relay key's footprint to group management
+    Service,
+}
+
+/// Content of one discovery map event
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DiscoveryMapContent {
+    pub geohashes: Vec<String>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    // Display geohashes of archived communities, kept out of `geohashes` so clients that
+    // only read those never show them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archived: Vec<String>,
+    // Display geohashes of communities marked 18+, whichever list they are in; clients
+    // confirm before showing them and the endpoint can leave them out on request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub age_restricted: Vec<String>,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+impl DiscoveryMapContent {
+    /// Union of several maps: geohashes in first-seen order, newest update time
+    pub fn merge(maps: impl IntoIterator<Item = DiscoveryMapContent>) -> Self {
+        let mut merged = Self::default();
+        for map in maps {
+            for geohash in map.geohashes {
+                if !merged.geohashes.contains(&geohash) {
+                    merged.geohashes.push(geohash);
+                }
+            }
+            for geohash in map.archived {
+                if !merged.archived.contains(&geohash) {
+                    merged.archived.push(geohash);
+                }
+            }
+            for geohash in map.age_restricted {
+                if !merged.age_restricted.contains(&geohash) {
+                    merged.age_restricted.push(geohash);
+                }
+            }
+            merged.labels.extend(map.labels);
+            merged.updated_at = merged.updated_at.max(map.updated_at);
+        }
+        merged
+    }
+
+    /// How many entries differ from `other`: geohashes in only one of the two, plus shared
+    /// geohashes labelled or flagged differently; order and update time are ignored
+    pub fn drift(&self, other: &Self) -> usize {
+        let ours: BTreeSet<&String> = self.geohashes.iter().collect();
+        let theirs: BTreeSet<&String> = other.geohashes.iter().collect();
+        let relabelled = ours
+            .intersection(&theirs)
+            .filter(|geohash| self.labels.get(**geohash) != other.labels.get(**geohash))
+            .count();
+        let our_archived: BTreeSet<&String> = self.archived.iter().collect();
+        let their_archived: BTreeSet<&String> = other.archived.iter().collect();
+        let our_restricted: BTreeSet<&String> = self.age_restricted.iter().collect();
+        let their_restricted: BTreeSet<&String> = other.age_restricted.iter().collect();
+        ours.symmetric_difference(&theirs).count()
+            + relabelled
+            + our_archived.symmetric_difference(&their_archived).count()
+            + our_restricted
+                .symmetric_difference(&their_restricted)
+                .count()
+    }
+
+    /// The default public view: archived communities and their labels left out entirely
+    pub fn without_archived(mut self) -> Self {
+        let archived = std::mem::take(&mut self.archived);
+        self.labels
+            .retain(|geohash, _| !archived.contains(geohash) || self.geohashes.contains(geohash));
+        self
+    }
+
+    /// Archived communities listed in `geohashes` with the active ones; `archived` still
+    /// says which they are
+    pub fn with_archived(mut self) -> Self {
+        for geohash in &self.archived {
+            if !self.geohashes.contains(geohash) {
+                self.geohashes.push(geohash.clone());
+            }
+        }
+        self
+    }
+
+    /// The map without communities marked 18+, for clients that never show them
+    pub fn without_age_restricted(mut self) -> Self {
+        let restricted = std::mem::take(&mut self.age_restricted);
+        self.geohashes.retain(|g| !restricted.contains(g));
+        self.archived.retain(|g| !restricted.contains(g));
+        self.labels.retain(|g, _| !restricted.contains(g));
+        self
+    }
+
+    /// The part of the map whose display geohashes start with `prefix`
+    fn with_prefix(&self, prefix: &str) -> Self {
+        Self {
+            geohashes: self
+                .geohashes
+                .iter()
+                .filter(|g| g.starts_with(prefix))
+                .cloned()
+                .collect(),
+            labels: self
+                .labels
+                .iter()
+                .filter(|(g, _)| g.starts_with(prefix))
+                .map(|(g, label)| (g.clone(), label.clone()))
+                .collect(),
+            archived: self
+                .archived
+                .iter()
+                .filter(|g| g.starts_with(prefix))
+                .cloned()
+                .collect(),
+            age_restricted: self
+                .age_restricted
+                .iter()
+                .filter(|g| g.starts_with(prefix))
+                .cloned()
+                .collect(),
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// The map without display geohashes whose centers lie in an exclusion zone
+    fn outside(&self, zones: &ExclusionZones) -> Self {
+        let mut map = self.clone();
+        map.geohashes.retain(|g| !zones.contains_geohash(g));
+        map.archived.retain(|g| !zones.contains_geohash(g));
+        map.age_restricted.retain(|g| !zones.contains_geohash(g));
+        map.labels.retain(|g, _| !zones.contains_geohash(g));
+        map
+    }
+}
+
+/// Which discovery map events this deployment publishes and who signs them
+///
+/// With no prefixes there is one map under `d_tag`. With prefixes (e.g. one per country's
+/// geohash cells) each gets its own map under `{d_tag}:{prefix}`, holding only the display
+/// geohashes in that prefix. Display geohashes inside an exclusion zone are never published.
+#[derive(Debug, Clone)]
+pub struct DiscoveryMaps {
+    pub d_tag: String,
+    pub prefixes: Vec<String>,
+    // None signs with the relay key
+    pub signer: Option<Keys>,
+    pub exclusion_zones: ExclusionZones,
+}
+
+impl DiscoveryMaps {
+    /// d tags of every configured map
+    pub fn d_tags(&self) -> Vec<String> {
+        if self.prefixes.is_empty() {
+            vec![self.d_tag.clone()]
+        } else {
+            self.prefixes
+                .iter()
+                .map(|prefix| format!("{}:{}", self.d_tag, prefix))
+                .collect()
+        }
+    }
+
+    /// The part of `map` the configured map events carry; with prefixes, communities
+    /// outside all of them are never published
+    pub fn published_part(&self, map: &DiscoveryMapContent) -> DiscoveryMapContent {
+        let map = self.outside_zones(map);
+        if self.prefixes.is_empty() {
+            map
+        } else {
+            DiscoveryMapContent::merge(self.prefixes.iter().map(|prefix| map.with_prefix(prefix)))
+        }
+    }
+
+    /// `map` without markers in exclusion zones, which can only get there through metadata
+    /// written before the zone was configured or by hand
+    fn outside_zones(&self, map: &DiscoveryMapContent) -> DiscoveryMapContent {
+        if self.exclusion_zones.is_empty() {
+            return map.clone();
+        }
+        let outside = map.outside(&self.exclusion_zones);
+        let dropped = (map.geohashes.len() + map.archived.len())
+            - (outside.geohashes.len() + outside.archived.len());
+        if dropped > 0 {
+            metrics::add(
+                "peek_exclusion_zone_markers_total",
+                &[("action", "withheld")],
+                dropped as u64,
+            );
+            tracing::warn!(
+                "Withholding {} discovery map marker(s) inside exclusion zones",
+                dropped
+            );
+        }
+        outside
+    }
+
+    /// Key the maps are signed with, and read back by
+    pub fn signer<'a>(&'a self, relay_keys: &'a Keys) -> &'a Keys {
+        self.signer.as_ref().unwrap_or(relay_keys)
+    }
+
+    /// One signed event per configured map, each carrying its share of `map`
+    pub fn signed_events(
+        &self,
+        map: &DiscoveryMapContent,
+        relay_keys: &Keys,
+    ) -> anyhow::Result<Vec<Event>> {
+        let map = &self.outside_zones(map);
+        let parts: Vec<(String, DiscoveryMapContent)> = if self.prefixes.is_empty() {
+            vec![(self.d_tag.clone(), map.clone())]
+        } else {
+            self.prefixes
+                .iter()
+                .zip(self.d_tags())
+                .map(|(prefix, d_tag)| (d_tag, map.with_prefix(prefix)))
+                .collect()
+        };
+
+        let signer = self.signer(relay_keys);
+        let mut events = Vec::with_capacity(parts.len());
+        for (d_tag, content) in parts {
+            let content = serde_json::to_string(&content)?;
+            events.push(
+                EventBuilder::new(Kind::from(DISCOVERY_MAP_KIND), content)
+                    .tags([Tag::identifier(d_tag)])
+                    .sign_with_keys(signer)?,
+            );
+        }
+        Ok(events)
+    }
+
+    /// Newest parsed map per configured d tag among fetched events from the signer
+    pub fn latest_maps(&self, events: &[Event], signer: &PublicKey) -> Vec<DiscoveryMapContent> {
+        let mut latest: BTreeMap<String, &Event> = BTreeMap::new();
+        let d_tags = self.d_tags();
+        for event in events {
+            if event.pubkey != *signer || event.kind != Kind::from(DISCOVERY_MAP_KIND) {
+                continue;
+            }
+            let Some(d_tag) = event.tags.identifier() else {
+                continue;
+            };
+            if !d_tags.iter().any(|d| d == d_tag) {
+                continue;
+            }
+            match latest.get(d_tag) {
+                Some(existing) if existing.created_at >= event.created_at => {}
+                _ => {
+                    latest.insert(d_tag.to_string(), event);
+                }
+            }
+        }
+
+        latest
+            .values()
+            .filter_map(|event| match serde_json::from_str(&event.content) {
+                Ok(map) => Some(map),
+                Err(e) => {
+                    tracing::warn!("Ignoring unparseable discovery map {}: {}", event.id, e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> DiscoveryMapContent {
+        DiscoveryMapContent {
+            geohashes: vec![
+                "9q8yyk8yz".to_string(),
+                "u4pruydqq".to_string(),
+                "9q9p1dhf7".to_string(),
+            ],
+            labels: BTreeMap::from([
+                ("9q8yyk8yz".to_string(), "Blue Bottle".to_string()),
+                ("u4pruydqq".to_string(), "Kaffebar".to_string()),
+            ]),
+            archived: Vec::new(),
+            age_restricted: Vec::new(),
+            updated_at: 1_760_000_000,
+        }
+    }
+
+    fn maps(prefixes: &[&str], signer: Option<Keys>) -> DiscoveryMaps {
+        DiscoveryMaps {
+            d_tag: "acme.discovery-map".to_string(),
+            prefixes: prefixes.iter().map(|p| p.to_string()).collect(),
+            signer,
+            exclusion_zones: ExclusionZones::default(),
+        }
+    }
+
+    #[test]
+    fn test_single_map_signed_by_relay_key_by_default() {
+        let relay_keys = Keys::generate();
+        let events = maps(&[], None).signed_events(&map(), &relay_keys).unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].pubkey, relay_keys.public_key());
+        assert_eq!(events[0].kind, Kind::from(DISCOVERY_MAP_KIND));
+        assert_eq!(events[0].tags.identifier(), Some("acme.discovery-map"));
+        let content: DiscoveryMapContent = serde_json::from_str(&events[0].content).unwrap();
+        assert_eq!(content, map());
+    }
+
+    #[test]
+    fn test_prefix_maps_signed_by_service_key() {
+        let relay_keys = Keys::generate();
+        let service_keys = Keys::generate();
+        let config = maps(&["9q", "u4"], Some(service_keys.clone()));
+        let events = config.signed_events(&map(), &relay_keys).unwrap();
+
+        let d_tags: Vec<&str> = events.iter().filter_map(|e| e.tags.identifier()).collect();
+        assert_eq!(
+            d_tags,
+            vec!["acme.discovery-map:9q", "acme.discovery-map:u4"]
+        );
+        assert!(events.iter().all(|e| e.pubkey == service_keys.public_key()));
+        assert!(events.iter().all(|e| e.verify().is_ok()));
+
+        let us: DiscoveryMapContent = serde_json::from_str(&events[0].content).unwrap();
+        assert_eq!(us.geohashes, vec!["9q8yyk8yz", "9q9p1dhf7"]);
+        assert_eq!(us.labels.len(), 1);
+
+        // Reading back keeps only the signer's maps, newest per d tag, and merges them whole
+        let stale = EventBuilder::new(Kind::from(DISCOVERY_MAP_KIND), "{\"geohashes\":[]}")
+            .tags([Tag::identifier("acme.discovery-map:9q")])
+            .custom_created_at(Timestamp::from(1))
+            .sign_with_keys(&service_keys)
+            .unwrap();
+        let impostor = EventBuilder::new(
+            Kind::from(DISCOVERY_MAP_KIND),
+            "{\"geohashes\":[\"9q0000000\"]}",
+        )
+        .tags([Tag::identifier("acme.discovery-map:9q")])
+        .sign_with_keys(&relay_keys)
+        .unwrap();
+        let mut fetched = events.clone();
+        fetched.extend([stale, impostor]);
+
+        let latest = config.latest_maps(&fetched, &service_keys.public_key());
+        assert_eq!(latest.len(), 2);
+        let merged = DiscoveryMapContent::merge(latest);
+        assert_eq!(merged.geohashes.len(), 3);
+        assert_eq!(merged.labels, map().labels);
+    }
+
+    #[test]
+    fn test_drift_counts_missing_extra_and_relabelled_entries() {
+        let published = map();
+        let mut live = map();
+        live.updated_at += 3600;
+        live.geohashes.reverse();
+        assert_eq!(live.drift(&published), 0);
+
+        // One community relocated, one renamed
+        live.geohashes.retain(|g| g != "9q9p1dhf7");
+        live.geohashes.push("9q8yykb00".to_string());
+        live.labels
+            .insert("u4pruydqq".to_string(), "Kaffebar Majorstuen".to_string());
+        assert_eq!(live.drift(&published), 3);
+        assert_eq!(published.drift(&live), 3);
+    }
+
+    #[test]
+    fn test_published_part_leaves_out_unconfigured_prefixes() {
+        assert_eq!(maps(&[], None).published_part(&map()), map());
+
+        let part = maps(&["9q"], None).published_part(&map());
+        assert_eq!(part.geohashes, vec!["9q8yyk8yz", "9q9p1dhf7"]);
+        assert_eq!(part.labels.len(), 1);
+    }
+
+    #[test]
+    fn test_markers_inside_exclusion_zones_are_never_published() {
+        let mut zoned = maps(&[], None);
+        zoned.exclusion_zones =
+            ExclusionZones::parse(&["37.7749:-122.4194:200".to_string()]).unwrap();
+
+        let part = zoned.published_part(&map());
+        assert_eq!(part.geohashes, vec!["u4pruydqq", "9q9p1dhf7"]);
+        assert!(!part.labels.contains_key("9q8yyk8yz"));
+
+        let events = zoned.signed_events(&map(), &Keys::generate()).unwrap();
+        let content: DiscoveryMapContent = serde_json::from_str(&events[0].content).unwrap();
+        assert_eq!(content, part);
+    }
+
+    #[test]
+    fn test_archived_communities_are_hidden_unless_asked_for() {
+        let mut full = map();
+        full.archived = vec!["u4zzzzzzz".to_string()];
+        full.labels
+            .insert("u4zzzzzzz".to_string(), "Closed Pop-up".to_string());
+
+        // Archiving or unarchiving a community is drift even when nothing else changed
+        assert_eq!(full.drift(&map()), 1);
+
+        let public = full.clone().without_archived();
+        assert_eq!(public, map());
+
+        let everything = full.clone().with_archived();
+        assert_eq!(everything.geohashes.len(), 4);
+        assert!(everything.geohashes.contains(&"u4zzzzzzz".to_string()));
+        assert_eq!(everything.archived, vec!["u4zzzzzzz"]);
+        assert_eq!(everything.labels["u4zzzzzzz"], "Closed Pop-up");
+
+        // Per-prefix maps carry only their share of archived entries
+        assert!(full.with_prefix("9q").archived.is_empty());
+        assert_eq!(
+            maps(&["9q", "u4"], None).published_part(&full).archived,
+            vec!["u4zzzzzzz"]
+        );
+    }
+
+    #[test]
+    fn test_age_restricted_communities_can_be_left_out() {
+        let mut full = map();
+        full.age_restricted = vec!["u4pruydqq".to_string()];
+
+        // Marking or unmarking a community is drift even when nothing else changed
+        assert_eq!(full.drift(&map()), 1);
+
+        let everyone = full.clone().without_archived();
+        assert_eq!(everyone.geohashes.len(), 3);
+        assert_eq!(everyone.age_restricted, vec!["u4pruydqq"]);
+
+        let filtered = full.clone().without_age_restricted();
+        assert_eq!(filtered.geohashes, vec!["9q8yyk8yz", "9q9p1dhf7"]);
+        assert!(!filtered.labels.contains_key("u4pruydqq"));
+        assert!(filtered.age_restricted.is_empty());
+
+        // Per-prefix maps carry only their share of the flags
+        assert!(full.with_prefix("9q").age_restricted.is_empty());
+        let merged = DiscoveryMapContent::merge([full.with_prefix("9q"), full.with_prefix("u4")]);
+        assert_eq!(merged.age_restricted, vec!["u4pruydqq"]);
+    }
+}
@@ -0,0 +1,281 @@
+use std::future::Future;
+
+use super::discovery_map::DiscoveryMapContent;
+use super::execution::ExecutionMode;
+use super::metrics;
+use super::relay::RelayError;
+use super::relay_access::DiscoveryPublisher;
+use crate::libraries::clock::Clock;
+
+/// Where the live and published discovery maps come from; the relay in production
+pub trait DiscoveryMapStore: Send + Sync {
+    /// The map as current kind 39000 metadata says it should be published
+    fn live_map(&self) -> impl Future<Output = Result<DiscoveryMapContent, RelayError>> + Send;
+
+    /// The last published map events, merged; None when nothing has been published
+    fn published_map(
+        &self,
+    ) -> impl Future<Output = Result<Option<DiscoveryMapContent>, RelayError>> + Send;
+
+    fn publish_map(
+        &self,
+        map: &DiscoveryMapContent,
+    ) -> impl Future<Output = Result<(), RelayError>> + Send;
+}
+
+impl DiscoveryMapStore for DiscoveryPublisher {
+    async fn live_map(&self) -> Result<DiscoveryMapContent, RelayError> {
+        self.live_discovery_map().await
+    }
+
+    async fn published_map(&self) -> Result<Option<DiscoveryMapContent>, RelayError> {
+        let maps = self.fetch_discovery_maps().await?;
+        Ok((!maps.is_empty()).then(|| DiscoveryMapContent::merge(maps)))
+    }
+
+    async fn publish_map(&self, map: &DiscoveryMapContent) -> Result<(), RelayError> {
+        let mut execution = self.execution(ExecutionMode::Execute);
+        self.publish_discovery_map_content(map, &mut execution)
+            .await
+    }
+}
+
+/// What one reconciliation pass did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconciliation {
+    /// The published map matches live metadata and is recent enough
+    InSync,
+    /// The published map had drifted by this many entries and was republished
+    Republished { drift: usize },
+    /// Nothing had drifted, but the published map was older than the forced publish interval
+    Refreshed,
+}
+
+impl Reconciliation {
+    /// Metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InSync => "in_sync",
+            Self::Republished { .. } => "republished",
+            Self::Refreshed => "refreshed",
+        }
+    }
+}
+
+/// Bring the published discovery map back in line with live group metadata
+///
+/// Publishes after community creation are best effort, and renames or relocations never
+/// republish, so the published map drifts. This rebuilds it from kind 39000 data and
+/// republishes only when it differs, or when the published map is `force_after_secs` old.
+pub async fn reconcile_discovery_map<S: DiscoveryMapStore>(
+    store: &S,
+    now: u64,
+    force_after_secs: u64,
+) -> Result<Reconciliation, RelayError> {
+    let live = store.live_map().await?;
+    let published = store.published_map().await?;
+
+    let drift = match &published {
+        Some(published) => live.drift(published),
+        None => live.geohashes.len(),
+    };
+    metrics::set_gauge("peek_discovery_map_drift", &[], drift as u64);
+
+    let outcome = if drift > 0 || published.is_none() {
+        metrics::add("peek_discovery_map_drift_entries_total", &[], drift as u64);
+        Reconciliation::Republished { drift }
+    } else if published
+        .as_ref()
+        .is_some_and(|published| now.saturating_sub(published.updated_at) >= force_after_secs)
+    {
+        Reconciliation::Refreshed
+    } else {
+        Reconciliation::InSync
+    };
+
+    if outcome != Reconciliation::InSync {
+        store.publish_map(&live).await?;
+    }
+    metrics::increment(
+        "peek_discovery_map_reconciliations_total",
+        &[("result", outcome.as_str())],
+    );
+    Ok(outcome)
+}
+
+/// One background pass, logging rather than returning failures
+pub async fn run_discovery_reconciliation(
+    discovery: &DiscoveryPublisher,
+    clock: &dyn Clock,
+    force_after_secs: u64,
+) {
+    match reconcile_discovery_map(discovery, clock.now_unix(), force_after_secs).await {
+        Ok(Reconciliation::InSync) => {}
+        Ok(Reconciliation::Republished { drift }) => tracing::warn!(
+            "Discovery map had drifted by {} entries from live metadata, republished",
+            drift
+        ),
+        Ok(Reconciliation::Refreshed) => tracing::info!("Republished unchanged discovery map"),
+        Err(e) => {
+            metrics::increment(
+                "peek_discovery_map_reconciliations_total",
+                &[("result", "error")],
+            );
+            tracing::error!("Failed to reconcile discovery map: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::discovery_map::{DiscoveryMaps, DISCOVERY_MAP_KIND};
+    use nostr_sdk::prelude::*;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    const NOW: u64 = 1_760_000_000;
+    const DAY: u64 = 24 * 60 * 60;
+
+    /// Live metadata plus the map events actually on the relay
+    struct FakeRelay {
+        maps: DiscoveryMaps,
+        keys: Keys,
+        live: Mutex<DiscoveryMapContent>,
+        events: Mutex<Vec<Event>>,
+        publishes: AtomicUsize,
+    }
+
+    impl FakeRelay {
+        fn new(live: DiscoveryMapContent) -> Self {
+            Self {
+                maps: DiscoveryMaps {
+                    d_tag: "peek.discovery-map".to_string(),
+                    prefixes: Vec::new(),
+                    signer: None,
+                    exclusion_zones: Default::default(),
+                },
+                keys: Keys::generate(),
+                live: Mutex::new(live),
+                events: Mutex::new(Vec::new()),
+                publishes: AtomicUsize::new(0),
+            }
+        }
+
+        fn published_events(&self) -> Vec<Event> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    impl DiscoveryMapStore for FakeRelay {
+        async fn live_map(&self) -> Result<DiscoveryMapContent, RelayError> {
+            Ok(self.live.lock().unwrap().clone())
+        }
+
+        async fn published_map(&self) -> Result<Option<DiscoveryMapContent>, RelayError> {
+            let maps = self
+                .maps
+                .latest_maps(&self.published_events(), &self.keys.public_key());
+            Ok((!maps.is_empty()).then(|| DiscoveryMapContent::merge(maps)))
+        }
+
+        async fn publish_map(&self, map: &DiscoveryMapContent) -> Result<(), RelayError> {
+            let events = self
+                .maps
+                .signed_events(map, &self.keys)
+                .map_err(|e| RelayError::Other(e.to_string()))?;
+            // Addressable events: each replaces the previous one with its d tag
+            let mut stored = self.events.lock().unwrap();
+            stored.retain(|old| {
+                !events
+                    .iter()
+                    .any(|new| new.tags.identifier() == old.tags.identifier())
+            });
+            stored.extend(events);
+            self.publishes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn map(entries: &[(&str, &str)], updated_at: u64) -> DiscoveryMapContent {
+        DiscoveryMapContent {
+            geohashes: entries.iter().map(|(g, _)| g.to_string()).collect(),
+            labels: entries
+                .iter()
+                .map(|(g, label)| (g.to_string(), label.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+            archived: Vec::new(),
+            age_restricted: Vec::new(),
+            updated_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missed_publish_is_corrected_on_the_next_pass() {
+        let relay = FakeRelay::new(map(&[("9q8yyk8yz", "Blue Bottle")], NOW));
+        relay
+            .publish_map(&relay.live_map().await.unwrap())
+            .await
+            .unwrap();
+
+        // A community is created but its map publish is lost, and another is renamed
+        *relay.live.lock().unwrap() = map(
+            &[
+                ("9q8yyk8yz", "Blue Bottle Coffee"),
+                ("u4pruydqq", "Kaffebar"),
+            ],
+            NOW + 60,
+        );
+
+        let outcome = reconcile_discovery_map(&relay, NOW + 120, DAY)
+            .await
+            .unwrap();
+        assert_eq!(outcome, Reconciliation::Republished { drift: 2 });
+
+        let events = relay.published_events();
+        assert_eq!(events.len(), 1);
+        let latest = &events[0];
+        assert_eq!(latest.kind, Kind::from(DISCOVERY_MAP_KIND));
+        assert_eq!(latest.tags.identifier(), Some("peek.discovery-map"));
+        let content: DiscoveryMapContent = serde_json::from_str(&latest.content).unwrap();
+        assert_eq!(content.geohashes, vec!["9q8yyk8yz", "u4pruydqq"]);
+        assert_eq!(content.labels["9q8yyk8yz"], "Blue Bottle Coffee");
+        assert_eq!(content.labels["u4pruydqq"], "Kaffebar");
+
+        // Once corrected, further passes leave the relay alone
+        assert_eq!(
+            reconcile_discovery_map(&relay, NOW + 180, DAY)
+                .await
+                .unwrap(),
+            Reconciliation::InSync
+        );
+        assert_eq!(relay.publishes.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_map_is_republished_at_the_forced_interval() {
+        let relay = FakeRelay::new(map(&[("9q8yyk8yz", "Blue Bottle")], NOW));
+
+        // Nothing published yet counts as drift
+        assert_eq!(
+            reconcile_discovery_map(&relay, NOW, DAY).await.unwrap(),
+            Reconciliation::Republished { drift: 1 }
+        );
+        assert_eq!(
+            reconcile_discovery_map(&relay, NOW + DAY - 1, DAY)
+                .await
+                .unwrap(),
+            Reconciliation::InSync
+        );
+
+        relay.live.lock().unwrap().updated_at = NOW + DAY;
+        assert_eq!(
+            reconcile_discovery_map(&relay, NOW + DAY, DAY)
+                .await
+                .unwrap(),
+            Reconciliation::Refreshed
+        );
+        assert_eq!(relay.publishes.load(Ordering::SeqCst), 2);
+    }
+}
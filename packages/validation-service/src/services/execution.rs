@@ -0,0 +1,229 @@
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::future::Future;
+use utoipa::ToSchema;
+
+/// Whether a relay mutation is sent or only planned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionMode {
+    #[default]
+    Execute,
+    /// Do every read and validation, record what would be published, send nothing
+    DryRun,
+}
+
+impl ExecutionMode {
+    pub fn from_dry_run(dry_run: bool) -> Self {
+        if dry_run {
+            Self::DryRun
+        } else {
+            Self::Execute
+        }
+    }
+
+    /// A dry run if either mode is one; a read-only deployment turns every execution into a plan
+    pub fn limited_to(self, limit: ExecutionMode) -> Self {
+        Self::from_dry_run(self == Self::DryRun || limit == Self::DryRun)
+    }
+}
+
+/// One event a mutation published, or would have published in a dry run
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PlannedEvent {
+    pub kind: u16,
+    // h tag of group management events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    // d tag of addressable events (discovery maps, app data)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub d_tag: Option<String>,
+    // p tags: members added, admins removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pubkeys: Vec<String>,
+}
+
+impl PlannedEvent {
+    pub fn from_event(event: &Event) -> Self {
+        let tag_values = |name: &str| -> Vec<String> {
+            event
+                .tags
+                .iter()
+                .filter(|tag| tag.kind().to_string() == name)
+                .filter_map(|tag| tag.content().map(str::to_string))
+                .collect()
+        };
+        Self {
+            kind: event.kind.as_u16(),
+            group_id: tag_values("h").into_iter().next(),
+            d_tag: event.tags.identifier().map(str::to_string),
+            pubkeys: tag_values("p"),
+        }
+    }
+}
+
+/// Events a mutation published (or, with dry_run, would have), in send order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct MutationPlan {
+    pub dry_run: bool,
+    pub events: Vec<PlannedEvent>,
+}
+
+impl MutationPlan {
+    pub fn new(mode: ExecutionMode) -> Self {
+        Self {
+            dry_run: mode == ExecutionMode::DryRun,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        ExecutionMode::from_dry_run(self.dry_run)
+    }
+
+    pub fn extend(&mut self, other: MutationPlan) {
+        self.events.extend(other.events);
+    }
+}
+
+/// Where executed mutations send their signed events
+pub trait EventSender: Send + Sync {
+    fn send_event(
+        &self,
+        event: &Event,
+    ) -> impl Future<Output = Result<(), nostr_sdk::client::Error>> + Send;
+}
+
+impl EventSender for Client {
+    async fn send_event(&self, event: &Event) -> Result<(), nostr_sdk::client::Error> {
+        Client::send_event(self, event).await.map(|_| ())
+    }
+}
+
+/// Publishing context threaded through relay mutation helpers
+///
+/// Every event goes through `publish`, which records it in the plan and only reaches the
+/// sender when executing, so helpers read and validate the same way in both modes.
+pub struct Execution<'a, S> {
+    sender: &'a S,
+    plan: MutationPlan,
+}
+
+impl<'a, S: EventSender> Execution<'a, S> {
+    pub fn new(sender: &'a S, mode: ExecutionMode) -> Self {
+        Self {
+            sender,
+            plan: MutationPlan::new(mode),
+        }
+    }
+
+    pub async fn publish(&mut self, event: &Event) -> Result<(), nostr_sdk::client::Error> {
+        let planned = PlannedEvent::from_event(event);
+        let mode = self.plan.mode();
+        if mode == ExecutionMode::DryRun {
+            tracing::info!("🧪 Dry run, not publishing {:?}", planned);
+        }
+        self.plan.events.push(planned);
+        match mode {
+            ExecutionMode::Execute => self.sender.send_event(event).await,
+            ExecutionMode::DryRun => Ok(()),
+        }
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.plan.mode()
+    }
+
+    pub fn into_plan(self) -> MutationPlan {
+        self.plan
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts events that actually reach the relay
+    #[derive(Default)]
+    pub(crate) struct CountingSender {
+        pub sent: AtomicUsize,
+    }
+
+    impl CountingSender {
+        pub fn sent(&self) -> usize {
+            self.sent.load(Ordering::SeqCst)
+        }
+    }
+
+    impl EventSender for CountingSender {
+        async fn send_event(&self, _event: &Event) -> Result<(), nostr_sdk::client::Error> {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn remove_admin_event(admin: &PublicKey) -> Event {
+        EventBuilder::new(Kind::from(9001), "")
+            .tags([
+                Tag::custom(TagKind::Custom("h".into()), ["peek-abc123"]),
+                Tag::custom(TagKind::Custom("p".into()), [admin.to_string()]),
+            ])
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_plans_without_sending() {
+        let sender = CountingSender::default();
+        let admin = Keys::generate().public_key();
+        let map = EventBuilder::new(Kind::from(30078), "{}")
+            .tags([Tag::identifier("peek.discovery-map")])
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+
+        let mut execution = Execution::new(&sender, ExecutionMode::DryRun);
+        execution
+            .publish(&remove_admin_event(&admin))
+            .await
+            .unwrap();
+        execution.publish(&map).await.unwrap();
+        let plan = execution.into_plan();
+
+        assert_eq!(sender.sent(), 0);
+        assert_eq!(
+            serde_json::to_value(&plan).unwrap(),
+            serde_json::json!({
+                "dry_run": true,
+                "events": [
+                    { "kind": 9001, "group_id": "peek-abc123", "pubkeys": [admin.to_string()] },
+                    { "kind": 30078, "d_tag": "peek.discovery-map" }
+                ]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_sends_and_records_the_same_plan() {
+        let sender = CountingSender::default();
+        let admin = Keys::generate().public_key();
+
+        let mut execution = Execution::new(&sender, ExecutionMode::Execute);
+        execution
+            .publish(&remove_admin_event(&admin))
+            .await
+            .unwrap();
+        let plan = execution.into_plan();
+
+        assert_eq!(sender.sent(), 1);
+        assert!(!plan.dry_run);
+        assert_eq!(plan.events[0].pubkeys, vec![admin.to_string()]);
+    }
+
+    #[test]
+    fn test_a_read_only_limit_turns_executions_into_dry_runs() {
+        use ExecutionMode::*;
+        assert_eq!(Execute.limited_to(Execute), Execute);
+        assert_eq!(Execute.limited_to(DryRun), DryRun);
+        assert_eq!(DryRun.limited_to(Execute), DryRun);
+    }
+}
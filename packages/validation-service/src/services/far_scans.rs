@@ -0,0 +1,252 @@
+//! Scans from far outside a community, rejected cheaply and counted per community
+//!
+//! Someone tens of kilometers from every anchor cannot be standing at the sticker, so there is
+//! no point running the neighbor-cell checks: the scan is rejected as very far straight away. A
+//! few such scans are people with a bad fix. Many for one community suggest its group was
+//! created with a spoofed location, or its sticker was copied and put up somewhere else, so the
+//! counts are kept for the operator to look into.
+
+use peek_geo::{haversine_meters, Coordinates};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::libraries::clock::Clock;
+
+/// Decoded anchors kept before the cache starts over
+const MAX_CACHED_ANCHORS: usize = 50_000;
+
+/// Communities whose far scans are counted; the longest quiet one makes room for a new one
+pub const MAX_TRACKED_COMMUNITIES: usize = 10_000;
+
+/// Far scans seen for one community
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FarScanCount {
+    pub community_id: Uuid,
+    pub far_scans: u64,
+    // Unix time of the latest one
+    pub last_seen: u64,
+}
+
+pub struct FarScans {
+    radius_meters: f64,
+    clock: Arc<dyn Clock>,
+    // Anchor geohash to its cell center, or None if it does not decode
+    anchors: Mutex<HashMap<String, Option<Coordinates>>>,
+    counts: Mutex<HashMap<Uuid, FarScanCount>>,
+}
+
+impl FarScans {
+    /// Scans more than `radius_km` from every anchor are far; zero turns the check off
+    pub fn new(radius_km: u64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            radius_meters: radius_km as f64 * 1_000.0,
+            clock,
+            anchors: Mutex::new(HashMap::new()),
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Distance in meters to the nearest anchor if `user` is beyond the radius of all of them,
+    /// counting the scan against the community
+    ///
+    /// None means the precise checks still have to run: the user is near an anchor, the check
+    /// is off, or no anchor decodes to a position to measure from.
+    pub fn check(&self, community_id: Uuid, user: &Coordinates, anchors: &[String]) -> Option<f64> {
+        if self.radius_meters <= 0.0 {
+            return None;
+        }
+        let nearest = self
+            .centers(anchors)
+            .iter()
+            .map(|center| haversine_meters(user, center))
+            .min_by(|a, b| a.total_cmp(b))?;
+        if nearest <= self.radius_meters {
+            return None;
+        }
+        self.record(community_id);
+        Some(nearest)
+    }
+
+    /// Far scans per community, most first
+    pub fn counts(&self) -> Vec<FarScanCount> {
+        let mut counts: Vec<FarScanCount> = self.counts.lock().unwrap().values().cloned().collect();
+        counts.sort_by(|a, b| {
+            b.far_scans
+                .cmp(&a.far_scans)
+                .then(b.last_seen.cmp(&a.last_seen))
+        });
+        counts
+    }
+
+    fn centers(&self, anchors: &[String]) -> Vec<Coordinates> {
+        let mut cache = self.anchors.lock().unwrap();
+        if cache.len() + anchors.len() > MAX_CACHED_ANCHORS {
+            cache.clear();
+        }
+        anchors
+            .iter()
+            .filter_map(|anchor| {
+                *cache
+                    .entry(anchor.clone())
+                    .or_insert_with(|| Coordinates::from_geohash(anchor).ok())
+            })
+            .collect()
+    }
+
+    fn record(&self, community_id: Uuid) {
+        let now = self.clock.now_unix();
+        let mut counts = self.counts.lock().unwrap();
+        if !counts.contains_key(&community_id) && counts.len() >= MAX_TRACKED_COMMUNITIES {
+            let quietest = counts
+                .values()
+                .min_by_key(|count| count.last_seen)
+                .map(|count| count.community_id);
+            if let Some(quietest) = quietest {
+                counts.remove(&quietest);
+            }
+        }
+        let count = counts.entry(community_id).or_insert(FarScanCount {
+            community_id,
+            far_scans: 0,
+            last_seen: now,
+        });
+        count.far_scans += 1;
+        count.last_seen = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+
+    const NOW: u64 = 1_760_000_000;
+    // Blue Bottle in San Francisco
+    const ANCHOR: &str = "9q8yyk8y";
+
+    fn point(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates::new(latitude, longitude).unwrap()
+    }
+
+    fn community(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    fn scans(far: &FarScans, community_id: Uuid) -> u64 {
+        far.counts()
+            .iter()
+            .find(|count| count.community_id == community_id)
+            .map_or(0, |count| count.far_scans)
+    }
+
+    fn far_scans(radius_km: u64) -> (FarScans, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(NOW));
+        (FarScans::new(radius_km, clock.clone()), clock)
+    }
+
+    #[test]
+    fn test_scans_far_from_every_anchor_are_short_circuited() {
+        let (far, _) = far_scans(50);
+        let anchors = vec![ANCHOR.to_string()];
+
+        // New York is about 4,100km away
+        let meters = far
+            .check(community(1), &point(40.7128, -74.0060), &anchors)
+            .unwrap();
+        assert!((4_000_000.0..4_200_000.0).contains(&meters), "{}", meters);
+
+        // Palo Alto is about 44km away, inside the radius, so the precise checks decide
+        assert_eq!(
+            far.check(community(1), &point(37.4419, -122.1430), &anchors),
+            None
+        );
+        assert_eq!(scans(&far, community(1)), 1);
+    }
+
+    #[test]
+    fn test_nearby_users_go_through_full_validation() {
+        let (far, _) = far_scans(50);
+        let anchors = vec![ANCHOR.to_string(), "dr5regw3".to_string()];
+
+        // At the anchor, a few blocks off, and near the second anchor in New York
+        for user in [
+            point(37.7749, -122.4194),
+            point(37.7793, -122.4193),
+            point(40.7128, -74.0060),
+        ] {
+            assert_eq!(far.check(community(1), &user, &anchors), None);
+        }
+        assert_eq!(scans(&far, community(1)), 0);
+        assert!(far.counts().is_empty());
+    }
+
+    #[test]
+    fn test_far_scans_are_counted_per_community() {
+        let (far, clock) = far_scans(50);
+        let anchors = vec![ANCHOR.to_string()];
+        let tokyo = point(35.6762, 139.6503);
+
+        for _ in 0..3 {
+            far.check(community(1), &tokyo, &anchors);
+            clock.advance(60);
+        }
+        far.check(community(2), &tokyo, &anchors);
+
+        assert_eq!(scans(&far, community(1)), 3);
+        assert_eq!(scans(&far, community(3)), 0);
+        assert_eq!(
+            far.counts(),
+            vec![
+                FarScanCount {
+                    community_id: community(1),
+                    far_scans: 3,
+                    last_seen: NOW + 120,
+                },
+                FarScanCount {
+                    community_id: community(2),
+                    far_scans: 1,
+                    last_seen: NOW + 180,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_is_skipped_when_off_or_unmeasurable() {
+        let tokyo = point(35.6762, 139.6503);
+
+        let (off, _) = far_scans(0);
+        assert_eq!(off.check(community(1), &tokyo, &[ANCHOR.to_string()]), None);
+
+        // Anchors that do not decode leave the decision to the precise checks
+        let (far, _) = far_scans(50);
+        assert_eq!(far.check(community(1), &tokyo, &[]), None);
+        assert_eq!(
+            far.check(community(1), &tokyo, &["9q8yyioa".to_string()]),
+            None
+        );
+        assert_eq!(scans(&far, community(1)), 0);
+    }
+
+    #[test]
+    fn test_quietest_community_makes_room() {
+        let (far, clock) = far_scans(50);
+        let anchors = vec![ANCHOR.to_string()];
+        let tokyo = point(35.6762, 139.6503);
+
+        for n in 0..MAX_TRACKED_COMMUNITIES as u128 {
+            far.check(community(n), &tokyo, &anchors);
+            clock.advance(1);
+        }
+        far.check(community(0), &tokyo, &anchors);
+        far.check(community(u128::MAX), &tokyo, &anchors);
+
+        assert_eq!(far.counts().len(), MAX_TRACKED_COMMUNITIES);
+        assert_eq!(scans(&far, community(0)), 2);
+        assert_eq!(scans(&far, community(1)), 0);
+        assert_eq!(scans(&far, community(u128::MAX)), 1);
+    }
+}
@@ -0,0 +1,234 @@
+//! Probabilistic relay faults for resilience testing
+//!
+//! Only compiled into debug builds or with the `fault-injection` feature, and only active when
+//! FAULTS is set, e.g. `FAULTS=add_member:timeout:0.2,send_metadata:error:0.1`.
+
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::execution::EventSender;
+use super::metrics;
+use super::relay::RelayError;
+use crate::libraries::rng::{RngSource, ThreadRngSource};
+
+/// How long an injected timeout stalls when FAULT_STALL_MS is unset; longer than any relay send timeout
+pub const DEFAULT_FAULT_STALL: Duration = Duration::from_secs(5);
+
+/// What goes wrong with a relay call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Nothing is sent and the call stalls, as if the relay never answered
+    Timeout,
+    /// Nothing is sent and the call fails like a dropped connection
+    Error,
+    /// The event is sent twice, so the caller sees the relay's answer to the repeat
+    Duplicate,
+}
+
+impl Fault {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Error => "error",
+            Self::Duplicate => "duplicate",
+        }
+    }
+}
+
+/// Relay operation name of an outgoing event, as used in FAULTS
+pub fn relay_op(kind: Kind) -> &'static str {
+    match kind.as_u16() {
+        9000 => "add_member",
+        9001 => "remove_member",
+        9002 => "send_metadata",
+        9007 => "create_group",
+        30078 => "app_data",
+        _ => "other",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Invalid FAULTS entry '{0}': expected op:timeout|error|duplicate:probability")]
+pub struct InvalidFaultSpec(String);
+
+#[derive(Debug, Clone, PartialEq)]
+struct FaultRule {
+    op: String,
+    fault: Fault,
+    probability: f64,
+}
+
+/// Parsed FAULTS rules and the randomness that decides when they fire
+pub struct FaultInjector {
+    rules: Vec<FaultRule>,
+    stall: Duration,
+    rng: Arc<dyn RngSource>,
+}
+
+impl FaultInjector {
+    /// Parse comma-separated `op:fault:probability` rules; `*` as op matches every operation
+    pub fn parse(
+        spec: &str,
+        stall: Duration,
+        rng: Arc<dyn RngSource>,
+    ) -> Result<Self, InvalidFaultSpec> {
+        let rules = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || InvalidFaultSpec(entry.to_string());
+                let mut parts = entry.split(':');
+                let (Some(op), Some(fault), Some(probability), None) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(invalid());
+                };
+                let fault = match fault {
+                    "timeout" => Fault::Timeout,
+                    "error" => Fault::Error,
+                    "duplicate" => Fault::Duplicate,
+                    _ => return Err(invalid()),
+                };
+                let probability: f64 = probability.parse().map_err(|_| invalid())?;
+                if op.is_empty() || !(0.0..=1.0).contains(&probability) {
+                    return Err(invalid());
+                }
+                Ok(FaultRule {
+                    op: op.to_string(),
+                    fault,
+                    probability,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { rules, stall, rng })
+    }
+
+    /// Injector configured by FAULTS and FAULT_STALL_MS, or None when FAULTS is unset or empty
+    pub fn from_env() -> Result<Option<Self>, InvalidFaultSpec> {
+        let Ok(spec) = std::env::var("FAULTS") else {
+            return Ok(None);
+        };
+        let stall = std::env::var("FAULT_STALL_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map_or(DEFAULT_FAULT_STALL, Duration::from_millis);
+        let injector = Self::parse(&spec, stall, Arc::new(ThreadRngSource))?;
+        Ok((!injector.rules.is_empty()).then_some(injector))
+    }
+
+    /// Fault to inject into this call of `op`, if any; each matching rule rolls independently
+    pub fn roll(&self, op: &str) -> Option<Fault> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.op == op || rule.op == "*")
+            .find(|rule| self.rng.next_f64() < rule.probability)
+            .map(|rule| rule.fault)
+    }
+
+    /// Send `event` through `sender`, unless a fault fires for its operation
+    pub async fn send_event<S: EventSender>(
+        &self,
+        sender: &S,
+        event: &Event,
+    ) -> Result<(), RelayError> {
+        let op = relay_op(event.kind);
+        let Some(fault) = self.roll(op) else {
+            return Ok(sender.send_event(event).await?);
+        };
+
+        tracing::warn!("💥 Injecting {} into {} ({})", fault.as_str(), op, event.id);
+        metrics::increment(
+            "peek_injected_faults_total",
+            &[("op", op), ("fault", fault.as_str())],
+        );
+        match fault {
+            Fault::Timeout => {
+                tokio::time::sleep(self.stall).await;
+                Err(RelayError::Other(format!(
+                    "fault injected: {} timed out",
+                    op
+                )))
+            }
+            Fault::Error => Err(RelayError::Other(format!(
+                "fault injected: connection reset during {}",
+                op
+            ))),
+            Fault::Duplicate => {
+                sender.send_event(event).await?;
+                Ok(sender.send_event(event).await?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::FixedRng;
+    use crate::services::execution::tests::CountingSender;
+
+    fn injector(spec: &str, roll: f64) -> FaultInjector {
+        FaultInjector::parse(spec, Duration::from_millis(10), Arc::new(FixedRng(roll))).unwrap()
+    }
+
+    fn event(kind: u16) -> Event {
+        EventBuilder::new(Kind::from(kind), "")
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_rules_and_reject_malformed_entries() {
+        let faults = injector("add_member:timeout:0.2, send_metadata:error:0.1,", 0.15);
+        assert_eq!(faults.roll("add_member"), Some(Fault::Timeout));
+        assert_eq!(faults.roll("send_metadata"), None);
+        assert_eq!(faults.roll("create_group"), None);
+        assert_eq!(
+            injector("*:duplicate:1", 0.99).roll("other"),
+            Some(Fault::Duplicate)
+        );
+
+        for spec in [
+            "add_member:timeout",
+            "add_member:explode:0.5",
+            "add_member:error:1.5",
+            ":error:0.5",
+            "add_member:error:0.5:extra",
+        ] {
+            assert!(
+                FaultInjector::parse(spec, DEFAULT_FAULT_STALL, Arc::new(ThreadRngSource)).is_err(),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injected_faults_shape_what_reaches_the_relay() {
+        let sender = CountingSender::default();
+
+        let timeout = injector("add_member:timeout:1", 0.0);
+        let err = timeout.send_event(&sender, &event(9000)).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        assert_eq!(sender.sent(), 0);
+
+        let error = injector("send_metadata:error:1", 0.0);
+        assert!(error.send_event(&sender, &event(9002)).await.is_err());
+        // Other operations pass through untouched
+        error.send_event(&sender, &event(9000)).await.unwrap();
+        assert_eq!(sender.sent(), 1);
+
+        let duplicate = injector("create_group:duplicate:1", 0.0);
+        duplicate.send_event(&sender, &event(9007)).await.unwrap();
+        assert_eq!(sender.sent(), 3);
+        assert_eq!(
+            metrics::get(
+                "peek_injected_faults_total",
+                &[("op", "create_group"), ("fault", "duplicate")]
+            ),
+            1
+        );
+    }
+}
@@ -1,39 +1,58 @@
 use nostr_sdk::prelude::*;
 use std::error::Error;
-use tracing::info;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use super::client_pool::ClientPool;
+use super::metrics;
+
+/// Tag kinds a response rumor may carry: the request correlation and the DM recipient
+/// Anything else, including tags echoed from a request, is stripped before wrapping
+pub const ECHOABLE_TAG_KINDS: [&str; 2] = ["e", "p"];
+/// Longest tag value kept in a response rumor; event ids and pubkeys are 64 hex chars
+pub const MAX_RESPONSE_TAG_VALUE_CHARS: usize = 128;
+/// Most tags a response rumor keeps; later ones are dropped
+pub const MAX_RESPONSE_TAGS: usize = 8;
 
 /// Service for handling NIP-59 gift wrap communication
 pub struct GiftWrapService {
     keys: Keys, // Ephemeral keys for signing, separate from relay keys
+    client_pool: Arc<ClientPool>,
 }
 
 impl GiftWrapService {
     /// Create a new gift wrap service with given keys
-    pub fn new(keys: Keys) -> Self {
-        Self { keys }
+    /// Inbox relay deliveries borrow clients from `client_pool`
+    pub fn new(keys: Keys, client_pool: Arc<ClientPool>) -> Self {
+        Self { keys, client_pool }
     }
 
     /// Create and send a gift-wrapped message to a recipient
+    /// Responses, notifications and welcomes all pass through here, so the rumor's tags are
+    /// limited by `response_tags` whatever the caller put on them.
+    /// The gift wrap carries a NIP-40 expiration tag set to `expiration`.
+    /// If `inbox_relays` is non-empty the gift wrap is also published to those relays
     pub async fn create_and_send_gift_wrap(
         &self,
         client: &Client,
         recipient: &PublicKey,
-        content: String,
-        kind: Kind,
-        tags: Vec<Tag>,
+        rumor: EventBuilder,
+        expiration: Timestamp,
+        inbox_relays: &[String],
     ) -> Result<EventId, Box<dyn Error>> {
         info!(
             "Creating gift wrap for recipient: {}",
             recipient.to_bech32()?
         );
 
-        // Create the rumor (unsigned event)
-        let rumor = EventBuilder::new(kind, content)
-            .tags(tags)
+        // Create the rumor (unsigned event), keeping only tags the response policy allows
+        let rumor = rumor.build(self.keys.public_key());
+        let rumor = EventBuilder::new(rumor.kind, rumor.content)
+            .tags(response_tags(rumor.tags.iter().cloned()))
+            .custom_created_at(rumor.created_at)
             .build(self.keys.public_key());
 
         // Create gift wrap with expiration
-        let expiration = Timestamp::now() + 7 * 24 * 60 * 60; // 7 days in seconds
         let expiration_tag = Tag::expiration(expiration);
         let event =
             EventBuilder::gift_wrap(&self.keys, recipient, rumor, Some(expiration_tag)).await?;
@@ -48,6 +67,162 @@ impl GiftWrapService {
             recipient.to_bech32()?
         );
 
+        if !inbox_relays.is_empty() {
+            self.publish_to_inbox_relays(&event, inbox_relays).await;
+        }
+
         Ok(event_id)
     }
+
+    /// Publish an already-signed gift wrap to a recipient's NIP-65 inbox relays
+    /// Borrows a pooled client so these relays don't join the main relay client.
+    /// Returns the per-relay outcome; failures here never fail the response.
+    pub async fn publish_to_inbox_relays(
+        &self,
+        event: &Event,
+        relays: &[String],
+    ) -> Vec<(String, bool)> {
+        let client = match self.client_pool.acquire(relays).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("📭 No client for inbox relays {:?}: {}", relays, e);
+                return relays.iter().map(|r| (r.clone(), false)).collect();
+            }
+        };
+
+        let outcomes = match client.send_event(event).await {
+            Ok(output) => {
+                let mut outcomes = Vec::new();
+                for url in &output.success {
+                    info!("📬 Gift wrap {} delivered to inbox relay {}", event.id, url);
+                    outcomes.push((url.to_string(), true));
+                }
+                for (url, reason) in &output.failed {
+                    warn!(
+                        "📭 Gift wrap {} rejected by inbox relay {}: {}",
+                        event.id, url, reason
+                    );
+                    outcomes.push((url.to_string(), false));
+                }
+                outcomes
+            }
+            Err(e) => {
+                warn!("📭 Failed to publish gift wrap to inbox relays: {}", e);
+                relays.iter().map(|r| (r.clone(), false)).collect()
+            }
+        };
+
+        outcomes
+    }
+}
+
+/// Apply the response tag policy: whitelisted kinds only, values truncated, count capped
+/// Each stripped or truncated tag is counted in `peek_response_tags_sanitized_total`.
+pub fn response_tags(tags: impl IntoIterator<Item = Tag>) -> Vec<Tag> {
+    let mut kept = Vec::new();
+    let (mut disallowed, mut over_limit, mut truncated) = (0u64, 0u64, 0u64);
+
+    for tag in tags {
+        let values = tag.as_slice();
+        if !ECHOABLE_TAG_KINDS.contains(&values[0].as_str()) {
+            disallowed += 1;
+            continue;
+        }
+        if kept.len() == MAX_RESPONSE_TAGS {
+            over_limit += 1;
+            continue;
+        }
+
+        if values
+            .iter()
+            .any(|v| v.chars().count() > MAX_RESPONSE_TAG_VALUE_CHARS)
+        {
+            truncated += 1;
+            let shortened = values.iter().map(|v| {
+                v.chars()
+                    .take(MAX_RESPONSE_TAG_VALUE_CHARS)
+                    .collect::<String>()
+            });
+            if let Ok(tag) = Tag::parse(shortened) {
+                kept.push(tag);
+            }
+        } else {
+            kept.push(tag);
+        }
+    }
+
+    for (reason, count) in [
+        ("kind", disallowed),
+        ("count", over_limit),
+        ("truncated", truncated),
+    ] {
+        if count > 0 {
+            metrics::add(
+                "peek_response_tags_sanitized_total",
+                &[("reason", reason)],
+                count,
+            );
+        }
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bogus_request_tags_are_reduced_to_the_whitelisted_truncated_set() {
+        let long = "f".repeat(1000);
+        let mut tags = Vec::new();
+        for i in 0..200 {
+            let tag = match i % 4 {
+                0 => Tag::parse(["client", long.as_str()]).unwrap(),
+                1 => Tag::parse(["e", long.as_str(), "wss://relay.example"]).unwrap(),
+                2 => Tag::parse([format!("x{}", i), "junk".to_string()]).unwrap(),
+                _ => Tag::parse(["p", "0".repeat(64).as_str()]).unwrap(),
+            };
+            tags.push(tag);
+        }
+
+        let kept = response_tags(tags);
+
+        assert_eq!(kept.len(), MAX_RESPONSE_TAGS);
+        for tag in &kept {
+            let values = tag.as_slice();
+            assert!(ECHOABLE_TAG_KINDS.contains(&values[0].as_str()));
+            assert!(values
+                .iter()
+                .all(|v| v.chars().count() <= MAX_RESPONSE_TAG_VALUE_CHARS));
+        }
+        assert_eq!(
+            kept[0].as_slice(),
+            vec![
+                "e".to_string(),
+                "f".repeat(MAX_RESPONSE_TAG_VALUE_CHARS),
+                "wss://relay.example".to_string()
+            ]
+        );
+        assert_eq!(kept[1].as_slice(), vec!["p".to_string(), "0".repeat(64)]);
+    }
+
+    #[test]
+    fn test_well_formed_response_tags_pass_unchanged() {
+        let recipient = Keys::generate().public_key();
+        let tags = vec![Tag::event(EventId::all_zeros()), Tag::public_key(recipient)];
+
+        assert_eq!(response_tags(tags.clone()), tags);
+    }
+
+    #[test]
+    fn test_truncation_keeps_multibyte_characters_whole() {
+        let value = "é".repeat(MAX_RESPONSE_TAG_VALUE_CHARS + 10);
+        let kept = response_tags([Tag::parse(["e", value.as_str()]).unwrap()]);
+
+        assert_eq!(
+            kept[0].as_slice()[1],
+            "é".repeat(MAX_RESPONSE_TAG_VALUE_CHARS)
+        );
+    }
 }
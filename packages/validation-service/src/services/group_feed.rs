@@ -0,0 +1,334 @@
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::relay::{member_pubkeys, GroupMetadata};
+use super::relay_access::GroupReader;
+
+// The single relay subscription carrying updates for every watched group
+const GROUP_FEED_SUBSCRIPTION: &str = "peek-group-feed";
+
+// Deltas buffered per group for connections that fall behind
+const DELTA_BUFFER: usize = 32;
+
+/// A change to a watched group, streamed to connected clients as JSON
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GroupDelta {
+    NameChanged { name: String },
+    MemberCountChanged { member_count: u32 },
+    Archived,
+}
+
+/// Relay operations the feed needs
+pub trait GroupFeedRelay: Send + Sync + 'static {
+    /// Group id for a community UUID, or None if no such community exists
+    fn find_group(
+        &self,
+        community_id: Uuid,
+    ) -> impl Future<Output = anyhow::Result<Option<String>>> + Send;
+
+    /// Point the feed's relay subscription at exactly these groups, replacing the previous filter
+    fn subscribe(&self, group_ids: Vec<String>) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+impl GroupFeedRelay for GroupReader {
+    async fn find_group(&self, community_id: Uuid) -> anyhow::Result<Option<String>> {
+        Ok(self.find_group_by_uuid(&community_id).await?)
+    }
+
+    async fn subscribe(&self, group_ids: Vec<String>) -> anyhow::Result<()> {
+        let subscription_id = SubscriptionId::new(GROUP_FEED_SUBSCRIPTION);
+        Ok(self
+            .subscribe_group_updates(subscription_id, group_ids)
+            .await?)
+    }
+}
+
+#[derive(Default)]
+struct WatchedGroup {
+    watchers: usize,
+    sender: Option<broadcast::Sender<GroupDelta>>,
+    // Last seen kind 39000 state: (created_at, name, archived)
+    metadata: Option<(Timestamp, String, bool)>,
+    // Last seen kind 39002 state: (created_at, member count)
+    members: Option<(Timestamp, u32)>,
+}
+
+/// Watches groups that have connected clients and fans their changes out as deltas
+///
+/// All watched groups share one relay subscription for kinds 39000/39002, refreshed whenever a
+/// group gains its first watcher. The first event seen for a group only sets the baseline;
+/// later, newer events are compared against it.
+pub struct GroupFeed<R> {
+    relay: R,
+    groups: Mutex<HashMap<String, WatchedGroup>>,
+}
+
+/// A connection's view of one group; dropping it stops watching
+pub struct GroupWatch<R: GroupFeedRelay> {
+    feed: Arc<GroupFeed<R>>,
+    group_id: String,
+    pub deltas: broadcast::Receiver<GroupDelta>,
+}
+
+impl<R: GroupFeedRelay> Drop for GroupWatch<R> {
+    fn drop(&mut self) {
+        self.feed.release(&self.group_id);
+    }
+}
+
+impl<R: GroupFeedRelay> GroupFeed<R> {
+    pub fn new(relay: R) -> Self {
+        Self {
+            relay,
+            groups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn find_group(&self, community_id: Uuid) -> anyhow::Result<Option<String>> {
+        self.relay.find_group(community_id).await
+    }
+
+    /// Start watching a group, extending the relay subscription if it is newly watched
+    pub async fn watch(self: &Arc<Self>, group_id: &str) -> GroupWatch<R> {
+        let (deltas, subscribe_to) = {
+            let mut groups = self.groups.lock().unwrap();
+            let group = groups.entry(group_id.to_string()).or_default();
+            group.watchers += 1;
+            let sender = group
+                .sender
+                .get_or_insert_with(|| broadcast::channel(DELTA_BUFFER).0);
+            let deltas = sender.subscribe();
+            let newly_watched = group.watchers == 1;
+            let subscribe_to = newly_watched.then(|| groups.keys().cloned().collect());
+            (deltas, subscribe_to)
+        };
+
+        if let Some(group_ids) = subscribe_to {
+            if let Err(e) = self.relay.subscribe(group_ids).await {
+                warn!("Failed to refresh group feed subscription: {}", e);
+            }
+        }
+
+        GroupWatch {
+            feed: self.clone(),
+            group_id: group_id.to_string(),
+            deltas,
+        }
+    }
+
+    fn release(&self, group_id: &str) {
+        let mut groups = self.groups.lock().unwrap();
+        if let Some(group) = groups.get_mut(group_id) {
+            group.watchers = group.watchers.saturating_sub(1);
+            if group.watchers == 0 {
+                // Left in the relay filter until the next refresh; its events are ignored
+                groups.remove(group_id);
+            }
+        }
+    }
+
+    /// Number of groups with at least one connected watcher
+    pub fn watched_groups(&self) -> usize {
+        self.groups.lock().unwrap().len()
+    }
+
+    /// Apply a kind 39000/39002 event, broadcasting any deltas to the group's watchers
+    pub fn handle_event(&self, event: &Event) {
+        let Some(group_id) = event.tags.identifier() else {
+            return;
+        };
+        let mut groups = self.groups.lock().unwrap();
+        let Some(group) = groups.get_mut(group_id) else {
+            return;
+        };
+
+        let deltas = match event.kind.as_u16() {
+            39000 => metadata_deltas(group, event),
+            39002 => member_deltas(group, event),
+            _ => Vec::new(),
+        };
+
+        if let Some(sender) = &group.sender {
+            for delta in deltas {
+                debug!("Group {} changed: {:?}", group_id, delta);
+                // No receivers just means every watcher disconnected in the meantime
+                let _ = sender.send(delta);
+            }
+        }
+    }
+
+    /// Feed relay notifications from the shared subscription into `handle_event`
+    pub async fn run(&self, mut notifications: broadcast::Receiver<RelayPoolNotification>) {
+        let feed_subscription = SubscriptionId::new(GROUP_FEED_SUBSCRIPTION);
+        loop {
+            match notifications.recv().await {
+                Ok(RelayPoolNotification::Event {
+                    subscription_id,
+                    event,
+                    ..
+                }) if subscription_id == feed_subscription => self.handle_event(&event),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Group feed skipped {} relay notifications", skipped)
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+fn metadata_deltas(group: &mut WatchedGroup, event: &Event) -> Vec<GroupDelta> {
+    let metadata = GroupMetadata::from_event(event, 0);
+    let mut deltas = Vec::new();
+
+    match &group.metadata {
+        Some((seen_at, _, _)) if event.created_at <= *seen_at => return deltas,
+        Some((_, name, archived)) => {
+            if *name != metadata.name {
+                deltas.push(GroupDelta::NameChanged {
+                    name: metadata.name.clone(),
+                });
+            }
+            if metadata.archived && !archived {
+                deltas.push(GroupDelta::Archived);
+            }
+        }
+        None => {}
+    }
+
+    group.metadata = Some((event.created_at, metadata.name, metadata.archived));
+    deltas
+}
+
+fn member_deltas(group: &mut WatchedGroup, event: &Event) -> Vec<GroupDelta> {
+    let member_count = member_pubkeys(event).len() as u32;
+    let mut deltas = Vec::new();
+
+    match group.members {
+        Some((seen_at, _)) if event.created_at <= seen_at => return deltas,
+        Some((_, count)) if count != member_count => {
+            deltas.push(GroupDelta::MemberCountChanged { member_count })
+        }
+        _ => {}
+    }
+
+    group.members = Some((event.created_at, member_count));
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Relay that only records how the shared subscription was set up
+    #[derive(Default)]
+    struct FakeRelay {
+        subscriptions: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl GroupFeedRelay for Arc<FakeRelay> {
+        async fn find_group(&self, _community_id: Uuid) -> anyhow::Result<Option<String>> {
+            Ok(None)
+        }
+
+        async fn subscribe(&self, mut group_ids: Vec<String>) -> anyhow::Result<()> {
+            group_ids.sort();
+            self.subscriptions.lock().unwrap().push(group_ids);
+            Ok(())
+        }
+    }
+
+    fn metadata(group_id: &str, name: &str, archived: bool, created_at: u64) -> Event {
+        let mut tags = vec![
+            Tag::identifier(group_id),
+            Tag::custom(TagKind::Name, [name]),
+        ];
+        if archived {
+            tags.push(Tag::custom(
+                TagKind::Custom("archived".into()),
+                Vec::<String>::new(),
+            ));
+        }
+        EventBuilder::new(Kind::from(39000), "")
+            .tags(tags)
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    fn members(group_id: &str, count: usize, created_at: u64) -> Event {
+        let mut tags = vec![Tag::identifier(group_id)];
+        tags.extend((0..count).map(|_| Tag::public_key(Keys::generate().public_key())));
+        EventBuilder::new(Kind::from(39002), "")
+            .tags(tags)
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_one_subscription_covers_all_watched_groups() {
+        let relay = Arc::new(FakeRelay::default());
+        let feed = Arc::new(GroupFeed::new(relay.clone()));
+
+        let first = feed.watch("peek-a").await;
+        let second = feed.watch("peek-a").await;
+        let other = feed.watch("peek-b").await;
+
+        // A second watcher of the same group reuses the existing filter
+        assert_eq!(
+            *relay.subscriptions.lock().unwrap(),
+            vec![
+                vec!["peek-a".to_string()],
+                vec!["peek-a".to_string(), "peek-b".to_string()],
+            ]
+        );
+
+        drop(first);
+        drop(other);
+        assert_eq!(feed.watched_groups(), 1);
+        drop(second);
+        assert_eq!(feed.watched_groups(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_deltas_after_baseline() {
+        let feed = Arc::new(GroupFeed::new(Arc::new(FakeRelay::default())));
+        let mut watch = feed.watch("peek-a").await;
+
+        // Stored events only establish the baseline
+        feed.handle_event(&metadata("peek-a", "Cafe", false, 100));
+        feed.handle_event(&members("peek-a", 2, 100));
+        assert!(watch.deltas.try_recv().is_err());
+
+        feed.handle_event(&members("peek-a", 3, 110));
+        feed.handle_event(&metadata("peek-a", "Blue Cafe", true, 120));
+        // Stale replaceable events are ignored
+        feed.handle_event(&metadata("peek-a", "Cafe", false, 105));
+        // Other groups are not this watcher's concern
+        feed.handle_event(&metadata("peek-b", "Elsewhere", false, 130));
+
+        let mut received = Vec::new();
+        while let Ok(delta) = watch.deltas.try_recv() {
+            received.push(delta);
+        }
+        assert_eq!(
+            received,
+            vec![
+                GroupDelta::MemberCountChanged { member_count: 3 },
+                GroupDelta::NameChanged {
+                    name: "Blue Cafe".to_string()
+                },
+                GroupDelta::Archived,
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Coalescing of duplicate requests that arrive while the first one is still being processed
+//!
+//! Mobile clients retry aggressively: a user re-tapping "join" sends a second gift wrap while
+//! the first is mid-processing. Requests sharing a key join the in-flight one and get a copy of
+//! its result instead of running their own relay mutations; each is still answered under its
+//! own correlation id.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+/// Requests currently being processed, by key, with a channel their result is published on
+pub struct InFlight<K, V> {
+    pending: Mutex<HashMap<K, watch::Receiver<Option<V>>>>,
+}
+
+/// How a request was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// First request for its key; ran the work itself
+    Led,
+    /// Arrived while an identical request was in flight and shared its result
+    Joined,
+}
+
+/// Removes the leader's entry when it finishes, fails or is dropped mid-flight
+struct Lead<'a, K: Eq + Hash, V> {
+    in_flight: &'a InFlight<K, V>,
+    key: K,
+    sender: watch::Sender<Option<V>>,
+}
+
+impl<K: Eq + Hash, V> Drop for Lead<'_, K, V> {
+    fn drop(&mut self) {
+        let mut pending = self.in_flight.pending.lock().unwrap();
+        if pending
+            .get(&self.key)
+            .is_some_and(|receiver| self.sender.same_channel(receiver))
+        {
+            pending.remove(&self.key);
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> InFlight<K, V> {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `work` for `key`, or share the result of the request already running for it
+    ///
+    /// Only successful results are shared: when the leader fails, requests that joined it run
+    /// their own `work`, so an error for one request never becomes the answer to another.
+    pub async fn run<Fut, E>(&self, key: K, work: Fut) -> (Result<V, E>, Outcome)
+    where
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let joined = {
+            let mut pending = self.pending.lock().unwrap();
+            match pending.get(&key) {
+                Some(receiver) => Err(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    pending.insert(key.clone(), receiver);
+                    Ok(Lead {
+                        in_flight: self,
+                        key,
+                        sender,
+                    })
+                }
+            }
+        };
+
+        match joined {
+            Ok(lead) => {
+                let result = work.await;
+                if let Ok(value) = &result {
+                    lead.sender.send_replace(Some(value.clone()));
+                }
+                (result, Outcome::Led)
+            }
+            Err(mut receiver) => {
+                let shared = receiver
+                    .wait_for(Option::is_some)
+                    .await
+                    .ok()
+                    .and_then(|value| value.clone());
+                match shared {
+                    Some(value) => (Ok(value), Outcome::Joined),
+                    None => (work.await, Outcome::Led),
+                }
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for InFlight<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::oneshot;
+
+    type Key = (&'static str, &'static str, &'static str);
+
+    const JOIN: Key = ("npub1alice", "3a7e5c59", "location_validation");
+
+    /// One request from the client: its correlation id and the shared membership result
+    async fn join_request(
+        in_flight: &InFlight<Key, String>,
+        mutations: &AtomicUsize,
+        correlation_id: &'static str,
+        gate: Option<oneshot::Receiver<()>>,
+    ) -> (&'static str, String, Outcome) {
+        let (result, outcome) = in_flight
+            .run(JOIN, async {
+                if let Some(gate) = gate {
+                    gate.await.unwrap();
+                }
+                mutations.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, ()>("peek-3a7e5c59 member".to_string())
+            })
+            .await;
+        (correlation_id, result.unwrap(), outcome)
+    }
+
+    #[tokio::test]
+    async fn test_retapped_join_runs_one_mutation_and_answers_both() {
+        let in_flight = InFlight::new();
+        let mutations = AtomicUsize::new(0);
+        let (release, gate) = oneshot::channel();
+
+        let first = join_request(&in_flight, &mutations, "rumor-1", Some(gate));
+        let second = async {
+            // The retry arrives while the first request is still mid-mutation
+            tokio::task::yield_now().await;
+            let second = join_request(&in_flight, &mutations, "rumor-2", None);
+            tokio::pin!(second);
+            tokio::select! {
+                biased;
+                _ = &mut second => unreachable!("joined request finished before the leader"),
+                _ = tokio::task::yield_now() => {}
+            }
+            release.send(()).unwrap();
+            second.await
+        };
+        let (first, second) = tokio::join!(first, second);
+
+        assert_eq!(mutations.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            first,
+            ("rumor-1", "peek-3a7e5c59 member".to_string(), Outcome::Led)
+        );
+        assert_eq!(
+            second,
+            (
+                "rumor-2",
+                "peek-3a7e5c59 member".to_string(),
+                Outcome::Joined
+            )
+        );
+
+        // Once answered, the next request for the key runs again
+        let third = join_request(&in_flight, &mutations, "rumor-3", None).await;
+        assert_eq!(third.2, Outcome::Led);
+        assert_eq!(mutations.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_leader_lets_joined_requests_run_their_own_work() {
+        let in_flight: InFlight<Key, String> = InFlight::new();
+        let (release, gate) = oneshot::channel::<()>();
+
+        let failing = in_flight.run(JOIN, async {
+            gate.await.unwrap();
+            Err("relay timeout")
+        });
+        let retry = async {
+            tokio::task::yield_now().await;
+            let retry = in_flight.run(JOIN, async { Ok::<_, &str>("member".to_string()) });
+            tokio::pin!(retry);
+            tokio::select! {
+                biased;
+                _ = &mut retry => unreachable!("joined request finished before the leader"),
+                _ = tokio::task::yield_now() => {}
+            }
+            release.send(()).unwrap();
+            retry.await
+        };
+        let ((failed, _), (retried, outcome)) = tokio::join!(failing, retry);
+
+        assert_eq!(failed, Err("relay timeout"));
+        assert_eq!(retried, Ok("member".to_string()));
+        assert_eq!(outcome, Outcome::Led);
+        assert!(in_flight.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_do_not_coalesce() {
+        let in_flight: InFlight<Key, u32> = InFlight::new();
+        let (release, gate) = oneshot::channel::<()>();
+
+        let join = in_flight.run(JOIN, async {
+            gate.await.unwrap();
+            Ok::<_, ()>(1)
+        });
+        let cancel = async {
+            tokio::task::yield_now().await;
+            let cancel = in_flight
+                .run(("npub1alice", "3a7e5c59", "cancel_join_request"), async {
+                    Ok::<_, ()>(2)
+                })
+                .await;
+            release.send(()).unwrap();
+            cancel
+        };
+        let ((join, _), (cancel, outcome)) = tokio::join!(join, cancel);
+
+        assert_eq!(join, Ok(1));
+        assert_eq!(cancel, Ok(2));
+        assert_eq!(outcome, Outcome::Led);
+    }
+}
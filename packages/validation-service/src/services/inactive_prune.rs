@@ -0,0 +1,757 @@
+//! Removal of members who stopped coming around, for communities whose admins opt in
+//!
+//! An admin sets "inactive_prune_days" on their community. Each run reads who has posted in the
+//! group (events carrying its h tag) or been added to it within that many days. A member quiet
+//! for all but the last week of the threshold is warned; once the whole threshold has passed and
+//! the warning is a week old, they are removed with a kind 9001. Anyone who shows up in between
+//! is forgotten. Admins and the relay key are never candidates.
+//!
+//! Warnings are only remembered in memory. After a restart a quiet member is warned again and
+//! gets a fresh week, so nobody is ever removed with less notice.
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::execution::{ExecutionMode, MutationPlan, PlannedEvent};
+use super::metrics;
+use super::relay::{member_pubkeys, GroupMetadata};
+use super::relay_access::GroupWriter;
+use crate::libraries::clock::Clock;
+use crate::models::ProtocolConfig;
+
+/// Group metadata tag holding the inactivity threshold in days; absent means nobody is pruned
+pub const INACTIVE_PRUNE_DAYS_TAG: &str = "inactive_prune_days";
+
+/// Shortest threshold an admin can set, leaving room for the week of warning inside it
+pub const INACTIVE_PRUNE_MIN_DAYS: u32 = 14;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// How long before removal a quiet member is warned
+pub const WARNING_LEAD_SECS: u64 = 7 * DAY_SECS;
+
+/// Prune runs kept for GET /api/admin/inactive-prune
+pub const PRUNE_LOG_HISTORY: usize = 50;
+
+/// One opted-in group as pruning sees it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PruneGroup {
+    pub group_id: String,
+    pub community_id: Uuid,
+    pub name: String,
+    pub prune_days: u32,
+    // Hex pubkeys from the kind 39002 and 39001 lists
+    pub members: Vec<String>,
+    pub admins: Vec<String>,
+}
+
+/// Build prune groups from relay-signed metadata (39000), member lists (39002) and admin
+/// lists (39001)
+///
+/// A group without an admin list is left out: nobody in it can be told apart from its admins.
+pub fn prune_groups(
+    metadata: &[Event],
+    member_lists: &[Event],
+    admin_lists: &[Event],
+    protocol: &ProtocolConfig,
+) -> Vec<PruneGroup> {
+    let by_group = |lists: &[Event]| -> HashMap<String, Vec<String>> {
+        lists
+            .iter()
+            .filter_map(|list| Some((list.tags.identifier()?.to_string(), member_pubkeys(list))))
+            .collect()
+    };
+    let members = by_group(member_lists);
+    let admins = by_group(admin_lists);
+
+    metadata
+        .iter()
+        .filter_map(|event| {
+            let group_id = event
+                .tags
+                .identifier()
+                .filter(|group_id| protocol.owns_group_id(group_id))?;
+            let community_id = event
+                .tags
+                .find(TagKind::SingleLetter(SingleLetterTag::lowercase(
+                    Alphabet::I,
+                )))
+                .and_then(|tag| tag.content())
+                .and_then(|i| protocol.parse_uuid_tag(i))?;
+            let group = GroupMetadata::from_event(event, 0);
+            Some(PruneGroup {
+                group_id: group_id.to_string(),
+                community_id,
+                name: group.name,
+                prune_days: group.inactive_prune_days?,
+                members: members.get(group_id).cloned().unwrap_or_default(),
+                admins: admins.get(group_id)?.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Latest activity per hex pubkey: events they authored, and kind 9000 put-users adding them
+pub fn latest_activity<'a>(events: impl Iterator<Item = &'a Event>) -> HashMap<String, u64> {
+    let mut latest: HashMap<String, u64> = HashMap::new();
+    let mut saw = |pubkey: String, at: u64| {
+        let seen = latest.entry(pubkey).or_insert(at);
+        *seen = (*seen).max(at);
+    };
+    for event in events {
+        let at = event.created_at.as_u64();
+        saw(event.pubkey.to_hex(), at);
+        // Someone just added has not had the chance to say anything yet
+        if event.kind == Kind::from(9000) {
+            for pubkey in event.tags.public_keys() {
+                saw(pubkey.to_hex(), at);
+            }
+        }
+    }
+    latest
+}
+
+/// A member warned in this run, to be told how long they have
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct InactivityWarning {
+    pub group_id: String,
+    pub community_id: Uuid,
+    pub community_name: String,
+    pub pubkey: String,
+    pub inactive_days: u64,
+    // Unix time from which they are removed unless they show up
+    pub remove_after: u64,
+}
+
+/// A member removed (or, in a dry run, due for removal) in this run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PrunedMember {
+    pub group_id: String,
+    pub community_id: Uuid,
+    pub pubkey: String,
+    pub inactive_days: u64,
+}
+
+/// Outcome of one prune run
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PruneReport {
+    pub ran_at: u64,
+    // Groups with an inactivity threshold
+    pub groups: usize,
+    pub warned: Vec<InactivityWarning>,
+    pub removed: Vec<PrunedMember>,
+    // Removals that could not be sent; they are retried next run
+    pub failed: Vec<PrunedMember>,
+    // Groups whose activity could not be read, so nobody in them was considered
+    pub skipped_groups: Vec<String>,
+}
+
+/// A run's report with the kind 9001 removals it sent, or would send in a dry run
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PruneRun {
+    #[serde(flatten)]
+    pub plan: MutationPlan,
+    pub report: PruneReport,
+}
+
+/// The most recent prune runs, dry runs included, oldest dropped first
+pub struct PruneLog {
+    history: usize,
+    runs: Mutex<VecDeque<PruneRun>>,
+}
+
+impl PruneLog {
+    pub fn new(history: usize) -> Self {
+        Self {
+            history: history.max(1),
+            runs: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn record(&self, run: PruneRun) {
+        let mut runs = self.runs.lock().unwrap();
+        runs.push_back(run);
+        while runs.len() > self.history {
+            runs.pop_front();
+        }
+    }
+
+    /// Kept runs, oldest first
+    pub fn recent(&self) -> Vec<PruneRun> {
+        self.runs.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Warns, then removes, members of opted-in communities who have gone quiet
+pub struct InactivePrune {
+    writer: GroupWriter,
+    clock: Arc<dyn Clock>,
+    // Hex; listed in every group but never a member to prune
+    relay_pubkey: String,
+    // When each (group, member) was warned; dropped once they are active again or removed
+    warned: Mutex<HashMap<(String, String), u64>>,
+    log: Arc<PruneLog>,
+}
+
+impl InactivePrune {
+    pub fn new(
+        writer: GroupWriter,
+        clock: Arc<dyn Clock>,
+        relay_pubkey: &PublicKey,
+        log: Arc<PruneLog>,
+    ) -> Self {
+        Self {
+            writer,
+            clock,
+            relay_pubkey: relay_pubkey.to_hex(),
+            warned: Mutex::new(HashMap::new()),
+            log,
+        }
+    }
+
+    /// Run once in `mode` and record the run in the log
+    ///
+    /// A dry run reports who would be warned and removed but remembers no warning, so it never
+    /// brings a removal closer. Warnings in the report of an executed run are for the caller to
+    /// deliver.
+    pub async fn run_with(&self, mode: ExecutionMode) -> anyhow::Result<PruneRun> {
+        let reader = self.writer.reader();
+        let groups = reader.prune_candidates().await?;
+        let now = self.clock.now_unix();
+        let execute = mode == ExecutionMode::Execute;
+        let mut plan = MutationPlan::new(mode);
+        let mut report = PruneReport {
+            ran_at: now,
+            groups: groups.len(),
+            warned: Vec::new(),
+            removed: Vec::new(),
+            failed: Vec::new(),
+            skipped_groups: Vec::new(),
+        };
+
+        for group in &groups {
+            let threshold = u64::from(group.prune_days) * DAY_SECS;
+            let warn_after = threshold.saturating_sub(WARNING_LEAD_SECS);
+            let activity = match reader
+                .member_activity(&group.group_id, now.saturating_sub(threshold))
+                .await
+            {
+                Ok(activity) => activity,
+                Err(e) => {
+                    warn!(
+                        "Not pruning {}, its activity could not be read: {}",
+                        group.group_id, e
+                    );
+                    report.skipped_groups.push(group.group_id.clone());
+                    continue;
+                }
+            };
+
+            for pubkey in &group.members {
+                if *pubkey == self.relay_pubkey || group.admins.contains(pubkey) {
+                    continue;
+                }
+                // Nothing within the window means quiet for at least the whole threshold
+                let idle = activity
+                    .get(pubkey)
+                    .map_or(threshold, |at| now.saturating_sub(*at));
+                let key = (group.group_id.clone(), pubkey.clone());
+                let warned_at = self.warned.lock().unwrap().get(&key).copied();
+
+                if idle < warn_after {
+                    if execute && warned_at.is_some() {
+                        self.warned.lock().unwrap().remove(&key);
+                    }
+                    continue;
+                }
+
+                let member = PrunedMember {
+                    group_id: group.group_id.clone(),
+                    community_id: group.community_id,
+                    pubkey: pubkey.clone(),
+                    inactive_days: idle / DAY_SECS,
+                };
+                match warned_at {
+                    None => {
+                        info!(
+                            "{} {} of {} after {} quiet days",
+                            if execute { "Warning" } else { "Would warn" },
+                            pubkey,
+                            group.group_id,
+                            member.inactive_days
+                        );
+                        if execute {
+                            self.warned.lock().unwrap().insert(key, now);
+                            metrics::increment(
+                                "peek_inactive_members_total",
+                                &[("action", "warned")],
+                            );
+                        }
+                        report.warned.push(InactivityWarning {
+                            group_id: member.group_id,
+                            community_id: member.community_id,
+                            community_name: group.name.clone(),
+                            pubkey: member.pubkey,
+                            inactive_days: member.inactive_days,
+                            remove_after: now + WARNING_LEAD_SECS,
+                        });
+                    }
+                    Some(at) if idle >= threshold && now >= at + WARNING_LEAD_SECS => {
+                        match self.remove_member(&group.group_id, pubkey, &mut plan).await {
+                            Ok(()) => {
+                                info!(
+                                    "{} {} from {} after {} quiet days",
+                                    if execute { "Removed" } else { "Would remove" },
+                                    pubkey,
+                                    group.group_id,
+                                    member.inactive_days
+                                );
+                                if execute {
+                                    self.warned.lock().unwrap().remove(&key);
+                                    metrics::increment(
+                                        "peek_inactive_members_total",
+                                        &[("action", "removed")],
+                                    );
+                                }
+                                report.removed.push(member);
+                            }
+                            Err(e) => {
+                                warn!(
+                                    "Removing inactive member {} from {} failed: {}",
+                                    pubkey, group.group_id, e
+                                );
+                                report.failed.push(member);
+                            }
+                        }
+                    }
+                    // Warned, and still within the week they were given
+                    Some(_) => {}
+                }
+            }
+        }
+
+        let run = PruneRun { plan, report };
+        self.log.record(run.clone());
+        Ok(run)
+    }
+
+    /// Send a kind 9001 removal, recording it in `plan` (and only recording it in a dry run)
+    async fn remove_member(
+        &self,
+        group_id: &str,
+        pubkey: &str,
+        plan: &mut MutationPlan,
+    ) -> anyhow::Result<()> {
+        plan.events.push(PlannedEvent {
+            kind: 9001,
+            group_id: Some(group_id.to_string()),
+            d_tag: None,
+            pubkeys: vec![pubkey.to_string()],
+        });
+        if plan.dry_run {
+            return Ok(());
+        }
+        Ok(self.writer.remove_group_member(group_id, pubkey).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_relay::FakeRelay;
+    use crate::libraries::clock::SystemClock;
+    use crate::libraries::exclusion_zones::ExclusionZones;
+    use crate::libraries::relay_url::RelayUrl;
+    use crate::libraries::test_support::ManualClock;
+    use crate::services::discovery_map::DiscoveryMaps;
+    use crate::services::previous_refs::PreviousRefs;
+    use crate::services::relay::RelayService;
+    use crate::services::relay_circuit::RelayCircuit;
+    use std::time::Duration;
+
+    const NOW: u64 = 1_760_000_000;
+
+    const LONG_AGO: u64 = NOW - 400 * DAY_SECS;
+
+    /// The first seed community, opted in at 30 days, on an in-process relay
+    struct Cafe {
+        relay: Arc<FakeRelay>,
+        relay_keys: Keys,
+        relay_service: Arc<RelayService>,
+        writer: GroupWriter,
+        group_id: String,
+        admin: Keys,
+    }
+
+    impl Cafe {
+        async fn open() -> Self {
+            let relay_keys = Keys::generate();
+            let admin = Keys::generate();
+            let relay = FakeRelay::new(relay_keys.clone());
+            let protocol = ProtocolConfig::default();
+            // The relay seeds with its own crate's protocol type, the same defaults
+            let group_id = relay.seed(&Default::default(), admin.public_key())[0]
+                .group_id
+                .clone();
+            relay.add_metadata_tag(
+                &group_id,
+                Tag::custom(TagKind::Custom(INACTIVE_PRUNE_DAYS_TAG.into()), ["30"]),
+            );
+            let url = relay.clone().spawn().await.unwrap();
+            let relay_service = Arc::new(
+                RelayService::new(
+                    RelayUrl::parse(&url).unwrap(),
+                    relay_keys.clone(),
+                    protocol.clone(),
+                    Duration::from_millis(500),
+                    DiscoveryMaps {
+                        d_tag: protocol.discovery_map_d_tag.clone(),
+                        prefixes: Vec::new(),
+                        signer: None,
+                        exclusion_zones: ExclusionZones::default(),
+                    },
+                    4096,
+                    RelayCircuit::new(Arc::new(SystemClock), 1, Duration::from_secs(30)),
+                    PreviousRefs::new(3),
+                )
+                .await
+                .unwrap(),
+            );
+            Self {
+                relay,
+                relay_keys,
+                writer: GroupWriter::new(relay_service.clone()),
+                relay_service,
+                group_id,
+                admin,
+            }
+        }
+
+        fn pruner(&self) -> (InactivePrune, Arc<ManualClock>, Arc<PruneLog>) {
+            let clock = Arc::new(ManualClock::new(NOW));
+            let log = Arc::new(PruneLog::new(10));
+            let prune = InactivePrune::new(
+                self.writer.clone(),
+                clock.clone(),
+                &self.relay_keys.public_key(),
+                log.clone(),
+            );
+            (prune, clock, log)
+        }
+
+        /// Publish a group event dated `at`, signed by `signer`
+        async fn publish(&self, signer: &Keys, kind: u16, tags: Vec<Tag>, at: u64) {
+            let event = EventBuilder::new(Kind::from(kind), "")
+                .tag(Tag::custom(TagKind::Custom("h".into()), [&self.group_id]))
+                .tags(tags)
+                .custom_created_at(Timestamp::from(at))
+                .sign_with_keys(signer)
+                .unwrap();
+            self.writer.client().send_event(&event).await.unwrap();
+        }
+
+        /// Add `pubkey` with a kind 9000 from the relay key dated `at`
+        async fn join(&self, pubkey: PublicKey, at: u64) {
+            let relay_keys = self.relay_keys.clone();
+            self.publish(&relay_keys, 9000, vec![Tag::public_key(pubkey)], at)
+                .await;
+        }
+
+        /// A chat message from `member` dated `at`
+        async fn post(&self, member: &Keys, at: u64) {
+            self.publish(member, 9, Vec::new(), at).await;
+        }
+
+        fn is_member(&self, pubkey: &PublicKey) -> bool {
+            self.relay.members(&self.group_id).unwrap().contains(pubkey)
+        }
+    }
+
+    fn warned(run: &PruneRun) -> Vec<&str> {
+        run.report
+            .warned
+            .iter()
+            .map(|w| w.pubkey.as_str())
+            .collect()
+    }
+
+    fn removed(run: &PruneRun) -> Vec<&str> {
+        run.report
+            .removed
+            .iter()
+            .map(|m| m.pubkey.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_quiet_member_is_warned_then_removed_a_week_later() {
+        let cafe = Cafe::open().await;
+        let (prune, clock, _) = cafe.pruner();
+        let (quiet, regular) = (Keys::generate(), Keys::generate());
+        cafe.join(quiet.public_key(), LONG_AGO).await;
+        cafe.join(regular.public_key(), LONG_AGO).await;
+        cafe.post(&quiet, NOW - 20 * DAY_SECS).await;
+        cafe.post(&regular, NOW - DAY_SECS).await;
+        let quiet_hex = quiet.public_key().to_hex();
+
+        // 20 of 30 days: not yet within the last week
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert!(warned(&run).is_empty());
+
+        clock.advance(3 * DAY_SECS);
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert_eq!(warned(&run), vec![quiet_hex.as_str()]);
+        assert_eq!(run.report.warned[0].inactive_days, 23);
+        assert_eq!(run.report.warned[0].community_name, "Dev Coffee");
+        assert_eq!(
+            run.report.warned[0].remove_after,
+            NOW + 3 * DAY_SECS + WARNING_LEAD_SECS
+        );
+
+        // Warned once; the grace period runs without repeating it or removing anyone
+        clock.advance(6 * DAY_SECS);
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert!(warned(&run).is_empty());
+        assert!(removed(&run).is_empty());
+
+        clock.advance(DAY_SECS);
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert_eq!(removed(&run), vec![quiet_hex.as_str()]);
+        assert_eq!(run.plan.events[0].kind, 9001);
+        assert!(!cafe.is_member(&quiet.public_key()));
+        assert!(cafe.is_member(&regular.public_key()));
+    }
+
+    #[tokio::test]
+    async fn test_a_member_quiet_past_the_threshold_still_gets_a_week() {
+        // Turning pruning on in a community with long-gone members warns them first
+        let cafe = Cafe::open().await;
+        let (prune, clock, _) = cafe.pruner();
+        let gone = Keys::generate().public_key();
+        cafe.join(gone, LONG_AGO).await;
+        let gone_hex = gone.to_hex();
+
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert_eq!(warned(&run), vec![gone_hex.as_str()]);
+        assert!(removed(&run).is_empty());
+
+        clock.advance(WARNING_LEAD_SECS - 1);
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert!(removed(&run).is_empty());
+
+        clock.advance(1);
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert_eq!(removed(&run), vec![gone_hex.as_str()]);
+        assert!(!cafe.is_member(&gone));
+    }
+
+    #[tokio::test]
+    async fn test_posting_after_the_warning_cancels_the_removal() {
+        let cafe = Cafe::open().await;
+        let (prune, clock, _) = cafe.pruner();
+        let back = Keys::generate();
+        cafe.join(back.public_key(), LONG_AGO).await;
+
+        prune.run_with(ExecutionMode::Execute).await.unwrap();
+        clock.advance(3 * DAY_SECS);
+        cafe.post(&back, NOW + 3 * DAY_SECS).await;
+        clock.advance(5 * DAY_SECS);
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert!(removed(&run).is_empty());
+        assert!(cafe.is_member(&back.public_key()));
+
+        // Going quiet again starts over with a new warning
+        clock.advance(23 * DAY_SECS);
+        let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+        assert_eq!(warned(&run), vec![back.public_key().to_hex().as_str()]);
+        assert!(removed(&run).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_admins_and_the_relay_key_are_never_pruned() {
+        let cafe = Cafe::open().await;
+        let (prune, clock, _) = cafe.pruner();
+        let guest = Keys::generate().public_key();
+        cafe.join(cafe.relay_keys.public_key(), LONG_AGO).await;
+        cafe.join(guest, LONG_AGO).await;
+        let guest_hex = guest.to_hex();
+
+        for _ in 0..3 {
+            let run = prune.run_with(ExecutionMode::Execute).await.unwrap();
+            assert!(warned(&run).iter().all(|pubkey| *pubkey == guest_hex));
+            clock.advance(WARNING_LEAD_SECS);
+        }
+        assert!(!cafe.is_member(&guest));
+        assert!(cafe.is_member(&cafe.admin.public_key()));
+        assert!(cafe.is_member(&cafe.relay_keys.public_key()));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_is_logged_but_warns_and_removes_nobody() {
+        let cafe = Cafe::open().await;
+        let (prune, clock, log) = cafe.pruner();
+        let quiet = Keys::generate().public_key();
+        cafe.join(quiet, LONG_AGO).await;
+        let quiet_hex = quiet.to_hex();
+
+        let dry = prune.run_with(ExecutionMode::DryRun).await.unwrap();
+        assert_eq!(warned(&dry), vec![quiet_hex.as_str()]);
+        clock.advance(WARNING_LEAD_SECS);
+        // No warning was remembered, so the dry run never reaches a removal
+        let dry = prune.run_with(ExecutionMode::DryRun).await.unwrap();
+        assert_eq!(warned(&dry), vec![quiet_hex.as_str()]);
+        assert!(removed(&dry).is_empty());
+
+        prune.run_with(ExecutionMode::Execute).await.unwrap();
+        clock.advance(WARNING_LEAD_SECS);
+        let dry = prune.run_with(ExecutionMode::DryRun).await.unwrap();
+        assert!(dry.plan.dry_run);
+        assert_eq!(removed(&dry), vec![quiet_hex.as_str()]);
+        assert_eq!(dry.plan.events[0].pubkeys, vec![quiet_hex.clone()]);
+        assert!(cafe.is_member(&quiet));
+
+        let runs = log.recent();
+        assert_eq!(runs.len(), 4);
+        assert_eq!(
+            runs.iter().map(|run| run.plan.dry_run).collect::<Vec<_>>(),
+            vec![true, true, false, true]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_relay_fails_the_run_and_removes_nobody() {
+        let cafe = Cafe::open().await;
+        let (prune, clock, log) = cafe.pruner();
+        let quiet = Keys::generate().public_key();
+        cafe.join(quiet, LONG_AGO).await;
+        prune.run_with(ExecutionMode::Execute).await.unwrap();
+        clock.advance(WARNING_LEAD_SECS);
+
+        // Nothing is read while the circuit is open, so nobody is considered
+        cafe.relay_service.relay_circuit().record(false);
+        assert!(prune.run_with(ExecutionMode::Execute).await.is_err());
+        assert!(cafe.is_member(&quiet));
+        assert_eq!(log.recent().len(), 1);
+    }
+
+    #[test]
+    fn test_prune_log_keeps_the_latest_runs() {
+        let log = PruneLog::new(2);
+        for ran_at in 1..=3 {
+            log.record(PruneRun {
+                plan: MutationPlan::new(ExecutionMode::Execute),
+                report: PruneReport {
+                    ran_at,
+                    groups: 0,
+                    warned: Vec::new(),
+                    removed: Vec::new(),
+                    failed: Vec::new(),
+                    skipped_groups: Vec::new(),
+                },
+            });
+        }
+        let kept: Vec<u64> = log.recent().iter().map(|run| run.report.ran_at).collect();
+        assert_eq!(kept, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_activity_counts_posts_and_being_added() {
+        let relay = Keys::generate();
+        let member = Keys::generate();
+        let newcomer = Keys::generate().public_key();
+        let h = |group_id: &str| Tag::custom(TagKind::Custom("h".into()), [group_id]);
+
+        let post = |at: u64| {
+            EventBuilder::new(Kind::from(9), "hi")
+                .tags([h("peek-cafe")])
+                .custom_created_at(Timestamp::from(at))
+                .sign_with_keys(&member)
+                .unwrap()
+        };
+        let added = EventBuilder::new(Kind::from(9000), "")
+            .tags([h("peek-cafe"), Tag::public_key(newcomer)])
+            .custom_created_at(Timestamp::from(NOW - 60))
+            .sign_with_keys(&relay)
+            .unwrap();
+        let events = [post(NOW - 3 * DAY_SECS), post(NOW - DAY_SECS), added];
+
+        let activity = latest_activity(events.iter());
+        assert_eq!(
+            activity.get(&member.public_key().to_hex()),
+            Some(&(NOW - DAY_SECS))
+        );
+        assert_eq!(activity.get(&newcomer.to_hex()), Some(&(NOW - 60)));
+    }
+
+    #[test]
+    fn test_groups_come_from_opted_in_metadata_with_an_admin_list() {
+        let relay = Keys::generate();
+        let protocol = ProtocolConfig::default();
+        let member = Keys::generate().public_key();
+        let admin = Keys::generate().public_key();
+
+        let metadata = |group_id: &str, community: u128, days: Option<&str>| {
+            let mut tags = vec![
+                Tag::identifier(group_id),
+                Tag::custom(TagKind::Name, ["Corner Cafe"]),
+                Tag::custom(
+                    TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+                    [protocol.uuid_tag(&Uuid::from_u128(community))],
+                ),
+            ];
+            if let Some(days) = days {
+                tags.push(Tag::custom(
+                    TagKind::Custom(INACTIVE_PRUNE_DAYS_TAG.into()),
+                    [days],
+                ));
+            }
+            EventBuilder::new(Kind::from(39000), "")
+                .tags(tags)
+                .sign_with_keys(&relay)
+                .unwrap()
+        };
+        let list = |kind: u16, group_id: &str, pubkeys: &[PublicKey]| {
+            EventBuilder::new(Kind::from(kind), "")
+                .tags(
+                    std::iter::once(Tag::identifier(group_id))
+                        .chain(pubkeys.iter().map(|pk| Tag::public_key(*pk))),
+                )
+                .sign_with_keys(&relay)
+                .unwrap()
+        };
+
+        let metadata = vec![
+            metadata("peek-a", 1, Some("30")),
+            metadata("peek-b", 2, None),
+            // Too short to fit the week of warning
+            metadata("peek-c", 3, Some("5")),
+            // Opted in, but its admin list is missing
+            metadata("peek-d", 4, Some("30")),
+        ];
+        let members = vec![
+            list(39002, "peek-a", &[member, admin]),
+            list(39002, "peek-d", &[member]),
+        ];
+        let admins = vec![
+            list(39001, "peek-a", &[admin]),
+            list(39001, "peek-c", &[admin]),
+        ];
+
+        assert_eq!(
+            prune_groups(&metadata, &members, &admins, &protocol),
+            vec![PruneGroup {
+                group_id: "peek-a".to_string(),
+                community_id: Uuid::from_u128(1),
+                name: "Corner Cafe".to_string(),
+                prune_days: 30,
+                members: vec![member.to_hex(), admin.to_hex()],
+                admins: vec![admin.to_hex()],
+            }]
+        );
+    }
+}
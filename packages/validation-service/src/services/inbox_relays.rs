@@ -0,0 +1,258 @@
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+
+use super::client_pool::ClientPool;
+use crate::libraries::relay_url::RelayUrl;
+
+/// How long a fetched NIP-65 relay list is reused before refetching
+const INBOX_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How long to wait for a kind 10002 relay list before giving up
+const INBOX_FETCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Resolves and caches users' NIP-65 read (inbox) relays
+/// Used to deliver gift-wrapped responses to clients that only read DMs from their inbox relays
+pub struct InboxRelayResolver {
+    max_fanout: usize,
+    // Relays that index kind 10002 lists; the groups relay rarely holds them
+    index_relays: Vec<String>,
+    // Also keep ws:// relays on this machine, for a service running against a local relay
+    allow_local: bool,
+    client_pool: Arc<ClientPool>,
+    cache: RwLock<HashMap<PublicKey, (Instant, Vec<String>)>>,
+}
+
+impl InboxRelayResolver {
+    pub fn new(
+        max_fanout: usize,
+        index_relays: Vec<String>,
+        allow_local: bool,
+        client_pool: Arc<ClientPool>,
+    ) -> Self {
+        Self {
+            max_fanout,
+            index_relays,
+            allow_local,
+            client_pool,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Get up to `max_fanout` wss:// read relays for a user, looked up on the index relays
+    /// Returns an empty list when the user has no kind 10002 event or the fetch fails,
+    /// in which case responses go only to the relays we're already connected to
+    pub async fn read_relays(&self, pubkey: &PublicKey) -> Vec<String> {
+        if self.max_fanout == 0 || self.index_relays.is_empty() {
+            return Vec::new();
+        }
+
+        if let Some((fetched_at, relays)) = self.cache.read().await.get(pubkey) {
+            if fetched_at.elapsed() < INBOX_CACHE_TTL {
+                return relays.clone();
+            }
+        }
+
+        let filter = Filter::new().kind(Kind::RelayList).author(*pubkey).limit(1);
+
+        let relays = match self.fetch_relay_list(filter).await {
+            Ok(events) => events
+                .first()
+                .map(|event| parse_read_relays(event, self.max_fanout, self.allow_local))
+                .unwrap_or_default(),
+            Err(e) => {
+                debug!("Failed to fetch NIP-65 relay list for {}: {}", pubkey, e);
+                Vec::new()
+            }
+        };
+
+        info!(
+            "📬 Resolved {} inbox relays for {}",
+            relays.len(),
+            pubkey.to_hex()
+        );
+
+        self.cache
+            .write()
+            .await
+            .insert(*pubkey, (Instant::now(), relays.clone()));
+
+        relays
+    }
+
+    /// Fetch from the index relays with a pooled client, newest relay list first
+    async fn fetch_relay_list(&self, filter: Filter) -> anyhow::Result<Events> {
+        let client = self.client_pool.acquire(&self.index_relays).await?;
+        Ok(client.fetch_events(filter, INBOX_FETCH_TIMEOUT).await?)
+    }
+}
+
+/// Extract read relays from a NIP-65 kind 10002 event
+/// An r tag without a marker counts as both read and write. Only wss:// URLs are kept, plus
+/// ws:// ones on this machine when `allow_local` is set.
+pub fn parse_read_relays(event: &Event, max: usize, allow_local: bool) -> Vec<String> {
+    let mut relays: Vec<RelayUrl> = Vec::new();
+
+    for tag in event.tags.iter() {
+        let values = tag.as_slice();
+        if values.first().map(String::as_str) != Some("r") {
+            continue;
+        }
+
+        let Some(url) = values.get(1) else {
+            continue;
+        };
+        let is_read = match values.get(2).map(String::as_str) {
+            None | Some("read") => true,
+            Some(_) => false,
+        };
+
+        let Ok(url) = RelayUrl::parse(url) else {
+            continue;
+        };
+        let reachable = url.is_secure() || (allow_local && url.is_local());
+        if is_read && reachable && !relays.contains(&url) {
+            relays.push(url);
+        }
+
+        if relays.len() >= max {
+            break;
+        }
+    }
+
+    relays.iter().map(RelayUrl::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_relay::FakeRelay;
+    use crate::services::gift_wrap::GiftWrapService;
+
+    fn relay_list(tags: Vec<Vec<&str>>) -> Event {
+        let tags = tags
+            .into_iter()
+            .map(|t| Tag::parse(t.into_iter().map(String::from).collect::<Vec<_>>()).unwrap());
+        EventBuilder::new(Kind::RelayList, "")
+            .tags(tags)
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_read_relays_respect_markers() {
+        let event = relay_list(vec![
+            vec!["r", "wss://inbox.example.com"],
+            vec!["r", "wss://read.example.com", "read"],
+            vec!["r", "wss://write.example.com", "write"],
+        ]);
+
+        assert_eq!(
+            parse_read_relays(&event, 5, false),
+            vec!["wss://inbox.example.com", "wss://read.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_read_relays_only_wss_and_deduplicated() {
+        let event = relay_list(vec![
+            vec!["r", "ws://insecure.example.com"],
+            vec!["r", "https://not-a-relay.example.com"],
+            vec!["r", "wss://inbox.example.com/"],
+            vec!["r", "wss://inbox.example.com"],
+            vec!["r", "WSS://Inbox.example.com:443"],
+            vec!["r", "not a relay"],
+        ]);
+
+        assert_eq!(
+            parse_read_relays(&event, 5, false),
+            vec!["wss://inbox.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_read_relays_capped_at_max_fanout() {
+        let event = relay_list(vec![
+            vec!["r", "wss://one.example.com"],
+            vec!["r", "wss://two.example.com"],
+            vec!["r", "wss://three.example.com"],
+        ]);
+
+        assert_eq!(parse_read_relays(&event, 2, false).len(), 2);
+    }
+
+    #[test]
+    fn test_missing_relay_list_falls_back_to_no_extra_relays() {
+        let event = relay_list(vec![]);
+        assert!(parse_read_relays(&event, 3, false).is_empty());
+    }
+
+    #[test]
+    fn test_local_relays_only_kept_when_allowed() {
+        let event = relay_list(vec![
+            vec!["r", "ws://localhost:7777"],
+            vec!["r", "ws://relay.example.com"],
+        ]);
+
+        assert!(parse_read_relays(&event, 5, false).is_empty());
+        assert_eq!(
+            parse_read_relays(&event, 5, true),
+            vec!["ws://localhost:7777"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gift_wrap_reaches_the_inbox_relay_listed_on_the_index_relay() {
+        let index = FakeRelay::new(Keys::generate());
+        let index_url = index.clone().spawn().await.unwrap();
+        let inbox = FakeRelay::new(Keys::generate());
+        let inbox_url = inbox.clone().spawn().await.unwrap();
+        let groups = FakeRelay::new(Keys::generate());
+        let groups_url = groups.clone().spawn().await.unwrap();
+
+        // The user publishes their relay list to the index relay only, not the groups relay
+        let user = Keys::generate();
+        let user_client = Client::new(user.clone());
+        user_client.add_relay(&index_url).await.unwrap();
+        user_client.connect().await;
+        let list = EventBuilder::new(Kind::RelayList, "")
+            .tag(Tag::parse(["r", inbox_url.as_str(), "read"]).unwrap())
+            .sign_with_keys(&user)
+            .unwrap();
+        user_client.send_event(&list).await.unwrap();
+
+        let pool = Arc::new(ClientPool::new(4, Duration::from_secs(30)));
+        let resolver = InboxRelayResolver::new(3, vec![index_url], true, pool.clone());
+        let inbox_relays = resolver.read_relays(&user.public_key()).await;
+        assert_eq!(inbox_relays, vec![inbox_url.clone()]);
+
+        let service_client = Client::new(Keys::generate());
+        service_client.add_relay(&groups_url).await.unwrap();
+        service_client.connect().await;
+        let wrap_id = GiftWrapService::new(Keys::generate(), pool)
+            .create_and_send_gift_wrap(
+                &service_client,
+                &user.public_key(),
+                EventBuilder::new(Kind::from(14), "{}"),
+                Timestamp::now() + 3600,
+                &inbox_relays,
+            )
+            .await
+            .unwrap();
+
+        let reader = Client::new(user);
+        reader.add_relay(&inbox_url).await.unwrap();
+        reader.connect().await;
+        let delivered = reader
+            .fetch_events(
+                Filter::new().kind(Kind::GiftWrap).id(wrap_id),
+                Duration::from_secs(2),
+            )
+            .await
+            .unwrap();
+        assert_eq!(delivered.len(), 1);
+    }
+}
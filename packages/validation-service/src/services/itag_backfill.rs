@@ -0,0 +1,631 @@
+//! Backfill of community UUID i-tags onto groups created before UUID tagging
+//!
+//! Early groups (the `peek_{uuid}` underscore era, and `peek-{uuid}` groups created before the
+//! NIP-73 i-tag was added) carry no `i` tag, so find_group_by_uuid never finds them and their
+//! stickers fail to preview or join. The backfill scans every peek-prefixed kind 39000 event,
+//! infers the UUID from the legacy d tag, and republishes the metadata with the i-tag. Groups
+//! whose d tag embeds no UUID are reported for mapping by hand through a CSV of
+//! `group_id,uuid` lines. Groups already carrying an i-tag are left alone, so reruns are safe.
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::admin_jobs::ScanProgress;
+use super::execution::{ExecutionMode, MutationPlan};
+use super::metrics;
+use super::relay_access::GroupWriter;
+use crate::models::ProtocolConfig;
+
+/// Group id prefixes of the formats that embedded the community UUID in the d tag
+pub const LEGACY_GROUP_ID_PREFIXES: [&str; 2] = ["peek_", "peek-"];
+
+/// One group as the backfill sees it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackfillCandidate {
+    pub group_id: String,
+    // The UUID of the i-tag already on the metadata, if any
+    pub tagged_with: Option<Uuid>,
+}
+
+/// Build backfill candidates from relay-signed metadata (39000), newest event per group
+/// Groups outside the peek prefixes, current or legacy, belong to someone else and are ignored.
+pub fn backfill_candidates(
+    metadata: &[Event],
+    protocol: &ProtocolConfig,
+) -> Vec<BackfillCandidate> {
+    let mut newest: BTreeMap<&str, &Event> = BTreeMap::new();
+    for event in metadata {
+        let Some(group_id) = event.tags.identifier().filter(|group_id| {
+            protocol.owns_group_id(group_id)
+                || LEGACY_GROUP_ID_PREFIXES
+                    .iter()
+                    .any(|prefix| group_id.starts_with(prefix))
+        }) else {
+            continue;
+        };
+        let slot = newest.entry(group_id).or_insert(event);
+        if event.created_at > slot.created_at {
+            *slot = event;
+        }
+    }
+
+    newest
+        .into_iter()
+        .map(|(group_id, event)| {
+            BackfillCandidate {
+            group_id: group_id.to_string(),
+            tagged_with: event
+                .tags
+                .iter()
+                .filter(|tag| {
+                    matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::I)
+                })
+                .filter_map(|tag| tag.content())
+                .find_map(|value| protocol.parse_uuid_tag(value)),
+        }
+        })
+        .collect()
+}
+
+/// The community UUID a legacy d tag embeds, in either the underscore or dash format
+pub fn infer_community_id(group_id: &str) -> Option<Uuid> {
+    LEGACY_GROUP_ID_PREFIXES
+        .iter()
+        .find_map(|prefix| group_id.strip_prefix(prefix))
+        .and_then(|rest| Uuid::parse_str(rest).ok())
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum MappingCsvError {
+    #[error("line {line}: expected group_id,uuid")]
+    MissingColumn { line: usize },
+
+    #[error("line {line}: invalid UUID {value:?}")]
+    InvalidUuid { line: usize, value: String },
+
+    #[error("line {line}: group {group_id} is mapped twice")]
+    Duplicate { line: usize, group_id: String },
+}
+
+/// Parse manual mappings, one `group_id,uuid` per line
+/// Blank lines, `#` comments and a `group_id,uuid` header are skipped.
+pub fn parse_mapping_csv(text: &str) -> Result<HashMap<String, Uuid>, MappingCsvError> {
+    let mut mappings = HashMap::new();
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let raw = raw.trim();
+        if raw.is_empty() || raw.starts_with('#') || raw.eq_ignore_ascii_case("group_id,uuid") {
+            continue;
+        }
+        let (group_id, value) = raw
+            .split_once(',')
+            .map(|(group_id, value)| (group_id.trim(), value.trim()))
+            .filter(|(group_id, _)| !group_id.is_empty())
+            .ok_or(MappingCsvError::MissingColumn { line })?;
+        let community_id = Uuid::parse_str(value).map_err(|_| MappingCsvError::InvalidUuid {
+            line,
+            value: value.to_string(),
+        })?;
+        if mappings
+            .insert(group_id.to_string(), community_id)
+            .is_some()
+        {
+            return Err(MappingCsvError::Duplicate {
+                line,
+                group_id: group_id.to_string(),
+            });
+        }
+    }
+    Ok(mappings)
+}
+
+/// Where a backfilled group's UUID came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UuidSource {
+    /// Read from the legacy d tag
+    Inferred,
+    /// Given in the mapping CSV
+    Manual,
+}
+
+impl UuidSource {
+    /// Metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Inferred => "inferred",
+            Self::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackfilledGroup {
+    pub group_id: String,
+    pub community_id: Uuid,
+    pub source: UuidSource,
+}
+
+/// A group left untagged because another group already answers for its UUID
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackfillConflict {
+    pub group_id: String,
+    pub community_id: Uuid,
+    pub tagged_group: String,
+}
+
+/// What one backfill pass would do, before anything is sent
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackfillSelection {
+    pub to_tag: Vec<BackfilledGroup>,
+    pub already_tagged: usize,
+    pub conflicts: Vec<BackfillConflict>,
+    pub unmapped: Vec<String>,
+}
+
+/// Decide which groups to tag and with which UUID
+///
+/// Manual mappings win over inference. Groups with an i-tag are skipped, and a UUID another
+/// group already carries is reported as a conflict rather than tagged twice, since lookups
+/// would then pick one of the two arbitrarily.
+pub fn select_backfill(
+    candidates: &[BackfillCandidate],
+    manual: &HashMap<String, Uuid>,
+) -> BackfillSelection {
+    let mut selection = BackfillSelection::default();
+    let mut owners: BTreeMap<Uuid, &str> = candidates
+        .iter()
+        .filter_map(|c| c.tagged_with.map(|uuid| (uuid, c.group_id.as_str())))
+        .collect();
+
+    for candidate in candidates {
+        if candidate.tagged_with.is_some() {
+            selection.already_tagged += 1;
+            continue;
+        }
+        let found = match manual.get(&candidate.group_id) {
+            Some(uuid) => Some((*uuid, UuidSource::Manual)),
+            None => {
+                infer_community_id(&candidate.group_id).map(|uuid| (uuid, UuidSource::Inferred))
+            }
+        };
+        let Some((community_id, source)) = found else {
+            selection.unmapped.push(candidate.group_id.clone());
+            continue;
+        };
+        if let Some(owner) = owners.get(&community_id) {
+            selection.conflicts.push(BackfillConflict {
+                group_id: candidate.group_id.clone(),
+                community_id,
+                tagged_group: owner.to_string(),
+            });
+            continue;
+        }
+        owners.insert(community_id, &candidate.group_id);
+        selection.to_tag.push(BackfilledGroup {
+            group_id: candidate.group_id.clone(),
+            community_id,
+            source,
+        });
+    }
+    selection
+}
+
+/// Outcome of one backfill pass
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BackfillReport {
+    // Peek-prefixed groups found on the relay
+    pub scanned: usize,
+    pub already_tagged: usize,
+    pub tagged: Vec<BackfilledGroup>,
+    pub conflicts: Vec<BackfillConflict>,
+    // Groups whose UUID could not be inferred; map them in the CSV and rerun
+    pub unmapped: Vec<String>,
+    // Groups whose metadata edit could not be sent
+    pub failed: Vec<String>,
+}
+
+/// A backfill's report with the kind 9002 edits it sent, or would send in a dry run
+#[derive(Debug, Clone, Serialize)]
+pub struct BackfillRun {
+    #[serde(flatten)]
+    pub plan: MutationPlan,
+    pub report: BackfillReport,
+}
+
+/// Tags legacy groups with their community UUID
+pub struct ItagBackfill {
+    writer: GroupWriter,
+}
+
+impl ItagBackfill {
+    pub fn new(writer: GroupWriter) -> Self {
+        Self { writer }
+    }
+
+    /// Run one pass in `mode`; a dry run reports what would be tagged but sends nothing
+    pub async fn run_with(
+        &self,
+        mode: ExecutionMode,
+        manual: &HashMap<String, Uuid>,
+    ) -> anyhow::Result<BackfillRun> {
+        self.run_reporting(mode, manual, &|_| {}).await
+    }
+
+    /// Run one pass, reporting each group handled to `progress`
+    pub async fn run_reporting(
+        &self,
+        mode: ExecutionMode,
+        manual: &HashMap<String, Uuid>,
+        progress: &(dyn Fn(ScanProgress) + Sync),
+    ) -> anyhow::Result<BackfillRun> {
+        let candidates = self.writer.reader().backfill_candidates().await?;
+        let selection = select_backfill(&candidates, manual);
+        let mut plan = MutationPlan::new(mode);
+        let mut tagged = Vec::new();
+        let mut failed = Vec::new();
+        let mut scan = ScanProgress {
+            total: selection.to_tag.len(),
+            ..Default::default()
+        };
+        progress(scan);
+
+        for group in selection.to_tag {
+            match self
+                .tag_group(&group.group_id, group.community_id, &mut plan)
+                .await
+            {
+                Ok(()) => {
+                    info!(
+                        "{} group {} with community {} ({:?})",
+                        if plan.dry_run { "Would tag" } else { "Tagged" },
+                        group.group_id,
+                        group.community_id,
+                        group.source
+                    );
+                    if mode == ExecutionMode::Execute {
+                        metrics::increment(
+                            "peek_itag_backfills_total",
+                            &[("source", group.source.as_str())],
+                        );
+                    }
+                    tagged.push(group);
+                }
+                Err(e) => {
+                    warn!("Tagging group {} failed: {}", group.group_id, e);
+                    failed.push(group.group_id);
+                    scan.errors += 1;
+                }
+            }
+            scan.processed += 1;
+            progress(scan);
+        }
+
+        Ok(BackfillRun {
+            plan,
+            report: BackfillReport {
+                scanned: candidates.len(),
+                already_tagged: selection.already_tagged,
+                tagged,
+                conflicts: selection.conflicts,
+                unmapped: selection.unmapped,
+                failed,
+            },
+        })
+    }
+
+    /// Send a kind 9002 adding the community's i-tag, recording it in `plan` (and only
+    /// recording it in a dry run)
+    async fn tag_group(
+        &self,
+        group_id: &str,
+        community_id: Uuid,
+        plan: &mut MutationPlan,
+    ) -> anyhow::Result<()> {
+        let mut execution = self.writer.execution(plan.mode());
+        let tagged = self
+            .writer
+            .add_uuid_tag(group_id, community_id, &mut execution)
+            .await;
+        plan.extend(execution.into_plan());
+        Ok(tagged?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_relay::FakeRelay;
+    use crate::libraries::clock::SystemClock;
+    use crate::libraries::exclusion_zones::ExclusionZones;
+    use crate::libraries::relay_url::RelayUrl;
+    use crate::services::discovery_map::DiscoveryMaps;
+    use crate::services::previous_refs::PreviousRefs;
+    use crate::services::relay::{GroupMetadata, RelayService};
+    use crate::services::relay_circuit::RelayCircuit;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const COMMUNITY: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+
+    fn community() -> Uuid {
+        Uuid::parse_str(COMMUNITY).unwrap()
+    }
+
+    fn untagged(group_id: &str) -> BackfillCandidate {
+        BackfillCandidate {
+            group_id: group_id.to_string(),
+            tagged_with: None,
+        }
+    }
+
+    /// Legacy groups, named but without i-tags, on an in-process relay
+    async fn legacy_relay(group_ids: &[&str]) -> (Arc<FakeRelay>, ItagBackfill) {
+        let relay_keys = Keys::generate();
+        let relay = FakeRelay::new(relay_keys.clone());
+        let admin = Keys::generate().public_key();
+        for group_id in group_ids {
+            relay.seed_group(
+                group_id,
+                vec![Tag::custom(TagKind::Name, ["Corner Cafe"])],
+                admin,
+            );
+        }
+        let url = relay.clone().spawn().await.unwrap();
+        let protocol = ProtocolConfig::default();
+        let relay_service = RelayService::new(
+            RelayUrl::parse(&url).unwrap(),
+            relay_keys,
+            protocol.clone(),
+            Duration::from_millis(500),
+            DiscoveryMaps {
+                d_tag: protocol.discovery_map_d_tag.clone(),
+                prefixes: Vec::new(),
+                signer: None,
+                exclusion_zones: ExclusionZones::default(),
+            },
+            4096,
+            RelayCircuit::new(Arc::new(SystemClock), 0, Duration::from_secs(30)),
+            PreviousRefs::new(3),
+        )
+        .await
+        .unwrap();
+        let writer = GroupWriter::new(Arc::new(relay_service));
+        (relay, ItagBackfill::new(writer))
+    }
+
+    /// Group id and i-tag of each kind 9002 edit the relay received, in order
+    fn uuid_edits(relay: &FakeRelay) -> Vec<(String, String)> {
+        relay
+            .events(9002)
+            .iter()
+            .map(|edit| {
+                let value = |name: &str| {
+                    edit.tags
+                        .iter()
+                        .find_map(|tag| match tag.as_slice() {
+                            [tag_name, value, ..] if tag_name == name => Some(value.clone()),
+                            _ => None,
+                        })
+                        .unwrap_or_default()
+                };
+                (value("h"), value("i"))
+            })
+            .collect()
+    }
+
+    fn metadata(group_id: &str, created_at: u64, extra_tags: Vec<Tag>) -> Event {
+        EventBuilder::new(Kind::from(39000), "")
+            .tags([vec![Tag::identifier(group_id)], extra_tags].concat())
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_candidates_cover_peek_groups_with_their_latest_i_tag() {
+        let protocol = ProtocolConfig::default();
+        let i_tag = Tag::custom(
+            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+            [protocol.uuid_tag(&community())],
+        );
+        let events = vec![
+            metadata(&format!("peek_{}", COMMUNITY), 100, vec![]),
+            // Tagged by an earlier backfill run
+            metadata(&format!("peek_{}", COMMUNITY), 200, vec![i_tag]),
+            metadata(&format!("peek-{}", COMMUNITY), 100, vec![]),
+            metadata("somebody-elses-group", 100, vec![]),
+        ];
+
+        let candidates = backfill_candidates(&events, &protocol);
+
+        assert_eq!(
+            candidates,
+            vec![
+                untagged(&format!("peek-{}", COMMUNITY)),
+                BackfillCandidate {
+                    group_id: format!("peek_{}", COMMUNITY),
+                    tagged_with: Some(community()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_both_legacy_d_tag_formats_embed_the_uuid() {
+        assert_eq!(
+            infer_community_id(&format!("peek_{}", COMMUNITY)),
+            Some(community())
+        );
+        assert_eq!(
+            infer_community_id(&format!("peek-{}", COMMUNITY)),
+            Some(community())
+        );
+        // The underscore era also wrote UUIDs without hyphens
+        assert_eq!(
+            infer_community_id(&format!("peek_{}", community().simple())),
+            Some(community())
+        );
+        // Random ids from the current format embed nothing
+        assert_eq!(infer_community_id("peek-x7k2m9q4ab"), None);
+        assert_eq!(infer_community_id(COMMUNITY), None);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_groups_are_tagged_and_tagged_ones_skipped() {
+        let protocol = ProtocolConfig::default();
+        let other = Uuid::new_v4();
+        let (underscore, dash) = (format!("peek_{}", COMMUNITY), format!("peek-{}", other));
+        let (relay, backfill) = legacy_relay(&[&underscore, &dash]).await;
+        // A current group, created with its i-tag
+        relay.seed_group(
+            "peek-x7k2m9q4ab",
+            vec![Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+                [protocol.uuid_tag(&Uuid::new_v4())],
+            )],
+            Keys::generate().public_key(),
+        );
+
+        let run = backfill
+            .run_with(ExecutionMode::Execute, &HashMap::new())
+            .await
+            .unwrap();
+
+        assert_eq!(run.report.scanned, 3);
+        assert_eq!(run.report.already_tagged, 1);
+        assert_eq!(
+            uuid_edits(&relay),
+            vec![
+                (dash.clone(), protocol.uuid_tag(&other)),
+                (underscore.clone(), protocol.uuid_tag(&community())),
+            ]
+        );
+        assert!(run
+            .report
+            .tagged
+            .iter()
+            .all(|group| group.source == UuidSource::Inferred));
+        assert!(run.report.unmapped.is_empty());
+
+        // The edit kept the name and the relay's metadata now carries the i-tag
+        let metadata = relay
+            .events(39000)
+            .into_iter()
+            .find(|event| event.tags.identifier() == Some(underscore.as_str()))
+            .unwrap();
+        assert_eq!(GroupMetadata::from_event(&metadata, 0).name, "Corner Cafe");
+        assert_eq!(
+            backfill_candidates(&[metadata], &protocol)[0].tagged_with,
+            Some(community())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_rerun_after_tagging_changes_nothing() {
+        let (relay, backfill) = legacy_relay(&[&format!("peek_{}", COMMUNITY)]).await;
+        backfill
+            .run_with(ExecutionMode::Execute, &HashMap::new())
+            .await
+            .unwrap();
+
+        let run = backfill
+            .run_with(ExecutionMode::Execute, &HashMap::new())
+            .await
+            .unwrap();
+
+        assert_eq!(run.report.already_tagged, 1);
+        assert!(run.report.tagged.is_empty());
+        assert!(run.plan.events.is_empty());
+        assert_eq!(uuid_edits(&relay).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_plans_edits_without_sending() {
+        let (relay, backfill) = legacy_relay(&[&format!("peek-{}", COMMUNITY)]).await;
+
+        let run = backfill
+            .run_with(ExecutionMode::DryRun, &HashMap::new())
+            .await
+            .unwrap();
+
+        assert!(run.plan.dry_run);
+        assert_eq!(run.plan.events.len(), 1);
+        assert_eq!(run.plan.events[0].kind, 9002);
+        assert_eq!(run.report.tagged.len(), 1);
+        assert!(uuid_edits(&relay).is_empty());
+    }
+
+    #[test]
+    fn test_unmapped_groups_are_reported_and_manual_mappings_fill_them() {
+        let candidates = vec![untagged("peek_cafe"), untagged("peek-park")];
+
+        let selection = select_backfill(&candidates, &HashMap::new());
+        assert_eq!(selection.unmapped, vec!["peek_cafe", "peek-park"]);
+        assert!(selection.to_tag.is_empty());
+
+        let manual = parse_mapping_csv(&format!(
+            "group_id,uuid\n# by hand\npeek_cafe, {}\n",
+            COMMUNITY
+        ))
+        .unwrap();
+        let selection = select_backfill(&candidates, &manual);
+        assert_eq!(
+            selection.to_tag,
+            vec![BackfilledGroup {
+                group_id: "peek_cafe".to_string(),
+                community_id: community(),
+                source: UuidSource::Manual,
+            }]
+        );
+        assert_eq!(selection.unmapped, vec!["peek-park"]);
+    }
+
+    #[test]
+    fn test_a_uuid_another_group_carries_is_a_conflict() {
+        let candidates = vec![
+            BackfillCandidate {
+                group_id: "peek-x7k2m9q4ab".to_string(),
+                tagged_with: Some(community()),
+            },
+            untagged(&format!("peek_{}", COMMUNITY)),
+        ];
+
+        let selection = select_backfill(&candidates, &HashMap::new());
+
+        assert!(selection.to_tag.is_empty());
+        assert_eq!(
+            selection.conflicts,
+            vec![BackfillConflict {
+                group_id: format!("peek_{}", COMMUNITY),
+                community_id: community(),
+                tagged_group: "peek-x7k2m9q4ab".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_malformed_mapping_lines_are_rejected() {
+        assert_eq!(
+            parse_mapping_csv("peek_cafe\n"),
+            Err(MappingCsvError::MissingColumn { line: 1 })
+        );
+        assert_eq!(
+            parse_mapping_csv("\npeek_cafe,not-a-uuid"),
+            Err(MappingCsvError::InvalidUuid {
+                line: 2,
+                value: "not-a-uuid".to_string()
+            })
+        );
+        assert_eq!(
+            parse_mapping_csv(&format!("peek_cafe,{}\npeek_cafe,{}", COMMUNITY, COMMUNITY)),
+            Err(MappingCsvError::Duplicate {
+                line: 2,
+                group_id: "peek_cafe".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Admin notifications for new members, collapsed per community
+//!
+//! The first join in a quiet community opens a batch; joins arriving within the window are
+//! added to it, and the whole batch goes out as one notification once the window has passed.
+//! A crowd arriving at a meetup is one ping for each admin rather than dozens.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::libraries::clock::Clock;
+
+/// Group metadata tag holding an admin's choice about join notifications; absent means on
+pub const JOIN_NOTIFICATIONS_TAG: &str = "join_notifications";
+
+/// Joins to one community waiting to be announced to its admins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinBatch {
+    pub group_id: String,
+    pub community_id: String,
+    // Joiner pubkeys in join order, without repeats
+    pub pubkeys: Vec<String>,
+    opened_at: u64,
+}
+
+pub struct JoinBatcher {
+    window_secs: u64,
+    clock: Arc<dyn Clock>,
+    pending: Mutex<BTreeMap<String, JoinBatch>>,
+}
+
+impl JoinBatcher {
+    pub fn new(window_secs: u64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            window_secs,
+            clock,
+            pending: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Add a member's join to its community's open batch, opening one if needed
+    pub fn record(&self, group_id: &str, community_id: &str, pubkey: &str) {
+        let now = self.clock.now_unix();
+        let mut pending = self.pending.lock().unwrap();
+        let batch = pending
+            .entry(group_id.to_string())
+            .or_insert_with(|| JoinBatch {
+                group_id: group_id.to_string(),
+                community_id: community_id.to_string(),
+                pubkeys: Vec::new(),
+                opened_at: now,
+            });
+        if !batch.pubkeys.iter().any(|joined| joined == pubkey) {
+            batch.pubkeys.push(pubkey.to_string());
+        }
+    }
+
+    /// Remove and return the batches whose window has closed
+    pub fn take_due(&self) -> Vec<JoinBatch> {
+        let now = self.clock.now_unix();
+        let mut pending = self.pending.lock().unwrap();
+        let due: Vec<String> = pending
+            .values()
+            .filter(|batch| now >= batch.opened_at.saturating_add(self.window_secs))
+            .map(|batch| batch.group_id.clone())
+            .collect();
+        due.iter()
+            .filter_map(|group_id| pending.remove(group_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+
+    const NOW: u64 = 1_760_000_000;
+
+    fn batcher() -> (JoinBatcher, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(NOW));
+        (JoinBatcher::new(60, clock.clone()), clock)
+    }
+
+    #[test]
+    fn test_joins_within_a_minute_collapse_into_one_batch() {
+        let (batcher, clock) = batcher();
+        batcher.record("peek-a", "uuid-a", "alice");
+        clock.advance(20);
+        batcher.record("peek-a", "uuid-a", "bob");
+        // Re-recorded joins (a retried request) are not listed twice
+        batcher.record("peek-a", "uuid-a", "alice");
+        clock.advance(39);
+        assert!(batcher.take_due().is_empty());
+
+        clock.advance(1);
+        let due = batcher.take_due();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].community_id, "uuid-a");
+        assert_eq!(due[0].pubkeys, vec!["alice", "bob"]);
+        assert!(batcher.take_due().is_empty());
+    }
+
+    #[test]
+    fn test_a_join_after_the_window_starts_a_new_batch() {
+        let (batcher, clock) = batcher();
+        batcher.record("peek-a", "uuid-a", "alice");
+        clock.advance(60);
+        assert_eq!(batcher.take_due()[0].pubkeys, vec!["alice"]);
+
+        batcher.record("peek-a", "uuid-a", "bob");
+        assert!(batcher.take_due().is_empty());
+        clock.advance(60);
+        assert_eq!(batcher.take_due()[0].pubkeys, vec!["bob"]);
+    }
+
+    #[test]
+    fn test_communities_are_batched_separately() {
+        let (batcher, clock) = batcher();
+        batcher.record("peek-a", "uuid-a", "alice");
+        clock.advance(30);
+        batcher.record("peek-b", "uuid-b", "bob");
+        clock.advance(30);
+
+        let due = batcher.take_due();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].group_id, "peek-a");
+        clock.advance(30);
+        assert_eq!(batcher.take_due()[0].group_id, "peek-b");
+    }
+}
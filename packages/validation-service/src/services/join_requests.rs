@@ -0,0 +1,494 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+use super::relay::{RelayError, RelayService};
+
+/// Group metadata tag selecting how location-validated users become members
+pub const JOIN_MODE_TAG: &str = "join_mode";
+
+/// How a user who passes location validation becomes a member
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinMode {
+    /// Added immediately (the default)
+    #[default]
+    Auto,
+    /// Queued until a community admin approves
+    Approval,
+}
+
+impl JoinMode {
+    /// Parse a join_mode tag value; unknown values fall back to auto
+    pub fn from_tag_value(value: &str) -> Self {
+        match value {
+            "approval" => Self::Approval,
+            _ => Self::Auto,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Approval => "approval",
+        }
+    }
+}
+
+/// Where a join request stands, as reported to applicants and admins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+    /// Not decided within the TTL and removed by the sweeper
+    Expired,
+    /// Withdrawn by the applicant
+    Cancelled,
+}
+
+/// A location-validated user waiting for admin approval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingJoin {
+    pub pubkey: String,
+    pub requested_at: u64,
+}
+
+/// Pending join requests for one group, stored as a kind 30078 event per group
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinQueue {
+    /// Community UUID, so the sweeper can address notifications without a metadata lookup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub community_id: Option<String>,
+    pub entries: Vec<PendingJoin>,
+}
+
+impl JoinQueue {
+    /// Queue `pubkey`, keeping the original request time if already queued
+    /// Returns false if it was already pending
+    pub fn enqueue(&mut self, pubkey: &str, now: u64) -> bool {
+        if self.contains(pubkey) {
+            return false;
+        }
+        self.entries.push(PendingJoin {
+            pubkey: pubkey.to_string(),
+            requested_at: now,
+        });
+        true
+    }
+
+    pub fn contains(&self, pubkey: &str) -> bool {
+        self.entries.iter().any(|e| e.pubkey == pubkey)
+    }
+
+    /// Remove and return the pending entry for `pubkey`
+    pub fn take(&mut self, pubkey: &str) -> Option<PendingJoin> {
+        let index = self.entries.iter().position(|e| e.pubkey == pubkey)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// Remove and return entries requested more than `ttl_secs` before `now`
+    pub fn take_expired(&mut self, now: u64, ttl_secs: u64) -> Vec<PendingJoin> {
+        let (expired, pending) = self
+            .entries
+            .drain(..)
+            .partition(|e| now.saturating_sub(e.requested_at) >= ttl_secs);
+        self.entries = pending;
+        expired
+    }
+}
+
+/// A request removed by the sweeper, with what is needed to notify the applicant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiredJoin {
+    pub group_id: String,
+    pub community_id: Option<String>,
+    pub pubkey: String,
+}
+
+/// d tag of the kind 30078 event holding a group's join queue
+pub fn join_queue_d_tag(group_id: &str) -> String {
+    format!("{}{}", JOIN_QUEUE_D_TAG_PREFIX, group_id)
+}
+
+const JOIN_QUEUE_D_TAG_PREFIX: &str = "join-requests:";
+
+/// Persistence for join queues; the relay in production, in-memory in tests
+pub trait JoinQueueStore: Send + Sync {
+    fn load_join_queue(
+        &self,
+        group_id: &str,
+    ) -> impl Future<Output = Result<JoinQueue, RelayError>> + Send;
+
+    fn save_join_queue(
+        &self,
+        group_id: &str,
+        queue: &JoinQueue,
+    ) -> impl Future<Output = Result<(), RelayError>> + Send;
+
+    /// Every stored join queue, keyed by group id
+    fn list_join_queues(
+        &self,
+    ) -> impl Future<Output = Result<Vec<(String, JoinQueue)>, RelayError>> + Send;
+}
+
+impl JoinQueueStore for RelayService {
+    async fn load_join_queue(&self, group_id: &str) -> Result<JoinQueue, RelayError> {
+        match self.fetch_app_data(&join_queue_d_tag(group_id)).await? {
+            Some(content) => Ok(serde_json::from_str(&content)?),
+            None => Ok(JoinQueue::default()),
+        }
+    }
+
+    async fn save_join_queue(&self, group_id: &str, queue: &JoinQueue) -> Result<(), RelayError> {
+        let content = serde_json::to_string(queue)?;
+        self.publish_app_data(&join_queue_d_tag(group_id), content)
+            .await
+    }
+
+    async fn list_join_queues(&self) -> Result<Vec<(String, JoinQueue)>, RelayError> {
+        let mut queues = Vec::new();
+        for (d_tag, content) in self.fetch_all_app_data().await? {
+            let Some(group_id) = d_tag.strip_prefix(JOIN_QUEUE_D_TAG_PREFIX) else {
+                continue;
+            };
+            match serde_json::from_str(&content) {
+                Ok(queue) => queues.push((group_id.to_string(), queue)),
+                Err(e) => tracing::warn!("Skipping unreadable join queue for {}: {}", group_id, e),
+            }
+        }
+        Ok(queues)
+    }
+}
+
+/// Queue a validated user for admin approval
+/// Returns false if they were already pending, in which case admins are not re-notified
+pub async fn request_join<S: JoinQueueStore>(
+    store: &S,
+    group_id: &str,
+    community_id: &str,
+    pubkey: &str,
+    now: u64,
+) -> Result<bool, RelayError> {
+    let mut queue = store.load_join_queue(group_id).await?;
+    queue.community_id = Some(community_id.to_string());
+    if !queue.enqueue(pubkey, now) {
+        return Ok(false);
+    }
+    store.save_join_queue(group_id, &queue).await?;
+    Ok(true)
+}
+
+/// Consume a pending request, adding the member first when approved
+/// Returns None if `pubkey` had no pending request; the entry is kept if adding fails
+pub async fn resolve_join<S, F, Fut>(
+    store: &S,
+    group_id: &str,
+    pubkey: &str,
+    approve: bool,
+    add_member: F,
+) -> Result<Option<JoinRequestStatus>, RelayError>
+where
+    S: JoinQueueStore,
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<(), RelayError>>,
+{
+    let mut queue = store.load_join_queue(group_id).await?;
+    if !queue.contains(pubkey) {
+        return Ok(None);
+    }
+
+    if approve {
+        add_member(pubkey.to_string()).await?;
+    }
+
+    queue.take(pubkey);
+    store.save_join_queue(group_id, &queue).await?;
+
+    Ok(Some(if approve {
+        JoinRequestStatus::Approved
+    } else {
+        JoinRequestStatus::Rejected
+    }))
+}
+
+/// Withdraw the applicant's own pending request
+/// Returns false if nothing was pending (never requested, already decided or expired)
+pub async fn cancel_join<S: JoinQueueStore>(
+    store: &S,
+    group_id: &str,
+    pubkey: &str,
+) -> Result<bool, RelayError> {
+    let mut queue = store.load_join_queue(group_id).await?;
+    if queue.take(pubkey).is_none() {
+        return Ok(false);
+    }
+    store.save_join_queue(group_id, &queue).await?;
+    Ok(true)
+}
+
+/// Remove every pending request older than `ttl_secs` from all stored queues, calling
+/// `notify` for each one. Returns the number of requests expired.
+///
+/// Each queue is saved before its applicants are notified, so a crash or restart
+/// mid-sweep never expires (or notifies about) the same request twice; the next sweep
+/// simply picks up whatever the previous one did not reach.
+pub async fn sweep_expired_requests<S, F, Fut>(
+    store: &S,
+    now: u64,
+    ttl_secs: u64,
+    mut notify: F,
+) -> Result<usize, RelayError>
+where
+    S: JoinQueueStore,
+    F: FnMut(ExpiredJoin) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut expired_count = 0;
+    for (group_id, mut queue) in store.list_join_queues().await? {
+        let stale = queue.take_expired(now, ttl_secs);
+        if stale.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = store.save_join_queue(&group_id, &queue).await {
+            tracing::warn!("Failed to expire join requests for {}: {}", group_id, e);
+            continue;
+        }
+
+        for entry in stale {
+            expired_count += 1;
+            notify(ExpiredJoin {
+                group_id: group_id.clone(),
+                community_id: queue.community_id.clone(),
+                pubkey: entry.pubkey,
+            })
+            .await;
+        }
+    }
+    Ok(expired_count)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::libraries::clock::Clock;
+    use crate::libraries::test_support::ManualClock;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    const GROUP: &str = "peek-cafe";
+    const COMMUNITY: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+    const APPLICANT: &str = "applicant-pubkey";
+
+    /// In-memory JoinQueueStore
+    #[derive(Default)]
+    pub(crate) struct MemoryStore {
+        pub queues: Mutex<HashMap<String, JoinQueue>>,
+    }
+
+    impl JoinQueueStore for MemoryStore {
+        async fn load_join_queue(&self, group_id: &str) -> Result<JoinQueue, RelayError> {
+            Ok(self
+                .queues
+                .lock()
+                .unwrap()
+                .get(group_id)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        async fn save_join_queue(
+            &self,
+            group_id: &str,
+            queue: &JoinQueue,
+        ) -> Result<(), RelayError> {
+            self.queues
+                .lock()
+                .unwrap()
+                .insert(group_id.to_string(), queue.clone());
+            Ok(())
+        }
+
+        async fn list_join_queues(&self) -> Result<Vec<(String, JoinQueue)>, RelayError> {
+            let mut queues: Vec<_> = self
+                .queues
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(group_id, queue)| (group_id.clone(), queue.clone()))
+                .collect();
+            queues.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(queues)
+        }
+    }
+
+    fn pending(store: &MemoryStore) -> Vec<String> {
+        store
+            .queues
+            .lock()
+            .unwrap()
+            .get(GROUP)
+            .map(|q| q.entries.iter().map(|e| e.pubkey.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_auto_mode_is_default_and_unknown_values_fall_back() {
+        assert_eq!(JoinMode::default(), JoinMode::Auto);
+        assert_eq!(JoinMode::from_tag_value("approval"), JoinMode::Approval);
+        assert_eq!(JoinMode::from_tag_value("auto"), JoinMode::Auto);
+        assert_eq!(JoinMode::from_tag_value("moderated"), JoinMode::Auto);
+    }
+
+    #[tokio::test]
+    async fn test_request_join_creates_single_pending_entry() {
+        let store = MemoryStore::default();
+
+        assert!(
+            request_join(&store, GROUP, COMMUNITY, APPLICANT, 1_760_000_000)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !request_join(&store, GROUP, COMMUNITY, APPLICANT, 1_760_000_500)
+                .await
+                .unwrap()
+        );
+
+        let queue = store.load_join_queue(GROUP).await.unwrap();
+        assert_eq!(
+            queue.entries,
+            vec![PendingJoin {
+                pubkey: APPLICANT.to_string(),
+                requested_at: 1_760_000_000,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approval_adds_member_and_consumes_entry() {
+        let store = MemoryStore::default();
+        request_join(&store, GROUP, COMMUNITY, APPLICANT, 1_760_000_000)
+            .await
+            .unwrap();
+
+        let added = Mutex::new(Vec::new());
+        let status = resolve_join(&store, GROUP, APPLICANT, true, |pubkey| {
+            added.lock().unwrap().push(pubkey);
+            async { Ok(()) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(status, Some(JoinRequestStatus::Approved));
+        assert_eq!(*added.lock().unwrap(), vec![APPLICANT.to_string()]);
+        assert!(pending(&store).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejection_consumes_entry_without_adding() {
+        let store = MemoryStore::default();
+        request_join(&store, GROUP, COMMUNITY, APPLICANT, 1_760_000_000)
+            .await
+            .unwrap();
+
+        let status = resolve_join(&store, GROUP, APPLICANT, false, |_| async {
+            Err(RelayError::Other(
+                "rejected applicants must not be added".to_string(),
+            ))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(status, Some(JoinRequestStatus::Rejected));
+        assert!(pending(&store).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_add_keeps_pending_entry() {
+        let store = MemoryStore::default();
+        request_join(&store, GROUP, COMMUNITY, APPLICANT, 1_760_000_000)
+            .await
+            .unwrap();
+
+        let result = resolve_join(&store, GROUP, APPLICANT, true, |_| async {
+            Err(RelayError::Other("relay unavailable".to_string()))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(pending(&store), vec![APPLICANT.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_resolving_unknown_request_is_none() {
+        let store = MemoryStore::default();
+        let status = resolve_join(&store, GROUP, APPLICANT, true, |_| async { Ok(()) })
+            .await
+            .unwrap();
+        assert_eq!(status, None);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_removes_only_own_request() {
+        let store = MemoryStore::default();
+        request_join(&store, GROUP, COMMUNITY, APPLICANT, 1_760_000_000)
+            .await
+            .unwrap();
+        request_join(&store, GROUP, COMMUNITY, "other-pubkey", 1_760_000_000)
+            .await
+            .unwrap();
+
+        assert!(cancel_join(&store, GROUP, APPLICANT).await.unwrap());
+        assert!(!cancel_join(&store, GROUP, APPLICANT).await.unwrap());
+        assert_eq!(pending(&store), vec!["other-pubkey".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_expires_requests_past_ttl_once() {
+        const TTL: u64 = 72 * 60 * 60;
+        let clock = ManualClock::new(1_760_000_000);
+        let store = MemoryStore::default();
+
+        request_join(&store, GROUP, COMMUNITY, APPLICANT, clock.now_unix())
+            .await
+            .unwrap();
+        clock.advance(TTL / 2);
+        request_join(&store, GROUP, COMMUNITY, "later-pubkey", clock.now_unix())
+            .await
+            .unwrap();
+
+        let notified = Mutex::new(Vec::new());
+        let (store_ref, notified_ref) = (&store, &notified);
+        let sweep = move |now| {
+            sweep_expired_requests(store_ref, now, TTL, move |expired| {
+                notified_ref.lock().unwrap().push(expired);
+                async {}
+            })
+        };
+
+        // Nothing has reached the TTL yet
+        clock.advance(TTL / 2 - 1);
+        assert_eq!(sweep(clock.now_unix()).await.unwrap(), 0);
+        assert!(notified.lock().unwrap().is_empty());
+
+        // The first request expires and its applicant is notified; the later one stays pending
+        clock.advance(1);
+        assert_eq!(sweep(clock.now_unix()).await.unwrap(), 1);
+        assert_eq!(
+            *notified.lock().unwrap(),
+            vec![ExpiredJoin {
+                group_id: GROUP.to_string(),
+                community_id: Some(COMMUNITY.to_string()),
+                pubkey: APPLICANT.to_string(),
+            }]
+        );
+        assert_eq!(pending(&store), vec!["later-pubkey".to_string()]);
+
+        // A repeated sweep (e.g. after a restart) does not expire or notify again
+        assert_eq!(sweep(clock.now_unix()).await.unwrap(), 0);
+        assert_eq!(notified.lock().unwrap().len(), 1);
+    }
+}
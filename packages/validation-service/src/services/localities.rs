@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use super::metrics;
+use super::overpass;
+use super::relay::RelayError;
+use super::relay_access::DiscoveryPublisher;
+use crate::libraries::clock::Clock;
+use crate::models::Coordinates;
+
+/// A display geohash is geocoded again once its locality is this old
+pub const LOCALITY_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// d tag of the relay-authored app data event persisting geocoded localities
+pub const LOCALITY_CACHE_D_TAG: &str = "discovery-localities";
+
+/// Pause between two reverse geocode lookups, to stay within the provider's usage policy
+pub const LOCALITY_LOOKUP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most lookups one refresh pass makes; the rest wait for the next pass
+pub const MAX_LOCALITY_LOOKUPS_PER_REFRESH: usize = 100;
+
+/// Geocoded locality of one display geohash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalityEntry {
+    // None when the provider knows no locality there
+    pub locality: Option<String>,
+    pub geocoded_at: u64,
+}
+
+/// Localities keyed by display geohash, as persisted
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LocalityCache {
+    pub entries: HashMap<String, LocalityEntry>,
+}
+
+/// Persistence for the locality cache; the relay in production, in-memory in tests
+pub trait LocalityStore: Send + Sync {
+    fn load_localities(&self) -> impl Future<Output = Result<LocalityCache, RelayError>> + Send;
+
+    fn save_localities(
+        &self,
+        cache: &LocalityCache,
+    ) -> impl Future<Output = Result<(), RelayError>> + Send;
+}
+
+impl LocalityStore for DiscoveryPublisher {
+    async fn load_localities(&self) -> Result<LocalityCache, RelayError> {
+        match self.fetch_app_data(LOCALITY_CACHE_D_TAG).await? {
+            Some(content) => Ok(serde_json::from_str(&content)?),
+            None => Ok(LocalityCache::default()),
+        }
+    }
+
+    async fn save_localities(&self, cache: &LocalityCache) -> Result<(), RelayError> {
+        let content = serde_json::to_string(cache)?;
+        self.publish_app_data(LOCALITY_CACHE_D_TAG, content).await
+    }
+}
+
+/// One background pass: geocode communities in the discovery data that are due, then
+/// put the results on their discovery records
+pub async fn refresh_discovery_localities(discovery: &DiscoveryPublisher, clock: &dyn Clock) {
+    let geohashes = discovery.discovery_geohashes().await;
+    let lookups = discovery
+        .locality_resolver()
+        .refresh(
+            discovery,
+            &geohashes,
+            clock.now_unix(),
+            overpass::get_locality,
+        )
+        .await;
+    if lookups > 0 {
+        tracing::info!("Geocoded {} community localities", lookups);
+        discovery.apply_localities().await;
+    }
+}
+
+/// Coarse locality names ("Mission District, San Francisco") for discovery records
+///
+/// Lookups only happen in `refresh`, from the background task; readers get whatever is
+/// already cached. Each display geohash is looked up at most once per LOCALITY_TTL_SECS,
+/// across restarts, since the cache is persisted through a `LocalityStore`.
+pub struct LocalityResolver {
+    lookup_interval: Duration,
+    // None until loaded from the store
+    cache: RwLock<Option<LocalityCache>>,
+}
+
+impl LocalityResolver {
+    pub fn new(lookup_interval: Duration) -> Self {
+        Self {
+            lookup_interval,
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// Known localities by display geohash, stale ones included until they are refreshed
+    pub async fn localities(&self) -> HashMap<String, String> {
+        self.cache
+            .read()
+            .await
+            .iter()
+            .flat_map(|cache| &cache.entries)
+            .filter_map(|(geohash, entry)| Some((geohash.clone(), entry.locality.clone()?)))
+            .collect()
+    }
+
+    /// Geocode display geohashes with no locality younger than LOCALITY_TTL_SECS
+    /// A failed lookup leaves the geohash without one until a later pass; returns lookups made
+    pub async fn refresh<S, F, Fut>(
+        &self,
+        store: &S,
+        display_geohashes: &[String],
+        now: u64,
+        mut geocode: F,
+    ) -> usize
+    where
+        S: LocalityStore,
+        F: FnMut(f64, f64) -> Fut,
+        Fut: Future<Output = anyhow::Result<Option<String>>>,
+    {
+        if self.cache.read().await.is_none() {
+            match store.load_localities().await {
+                Ok(loaded) => {
+                    self.cache.write().await.get_or_insert(loaded);
+                }
+                Err(e) => {
+                    // Without the persisted cache every community would be geocoded again
+                    tracing::warn!("Locality cache unavailable, skipping refresh: {}", e);
+                    return 0;
+                }
+            }
+        }
+
+        let due: Vec<String> = {
+            let guard = self.cache.read().await;
+            let Some(cache) = guard.as_ref() else {
+                return 0;
+            };
+            display_geohashes
+                .iter()
+                .filter(|geohash| {
+                    cache.entries.get(*geohash).is_none_or(|entry| {
+                        now.saturating_sub(entry.geocoded_at) >= LOCALITY_TTL_SECS
+                    })
+                })
+                .take(MAX_LOCALITY_LOOKUPS_PER_REFRESH)
+                .cloned()
+                .collect()
+        };
+
+        // Lookups run without the cache lock, so readers never wait on the geocoder
+        let mut lookups = 0;
+        let mut geocoded = Vec::new();
+        for geohash in due {
+            if lookups > 0 {
+                tokio::time::sleep(self.lookup_interval).await;
+            }
+            lookups += 1;
+
+            let Ok(center) = Coordinates::from_geohash(&geohash) else {
+                continue;
+            };
+            match geocode(center.latitude(), center.longitude()).await {
+                Ok(locality) => {
+                    metrics::increment("peek_locality_lookups_total", &[("result", "ok")]);
+                    let entry = LocalityEntry {
+                        locality: locality.filter(|name| !name.trim().is_empty()),
+                        geocoded_at: now,
+                    };
+                    geocoded.push((geohash, entry));
+                }
+                Err(e) => {
+                    metrics::increment("peek_locality_lookups_total", &[("result", "error")]);
+                    tracing::warn!("Locality lookup for {} failed: {}", geohash, e);
+                }
+            }
+        }
+
+        if !geocoded.is_empty() {
+            let snapshot = {
+                let mut guard = self.cache.write().await;
+                let cache = guard.get_or_insert_with(LocalityCache::default);
+                cache.entries.extend(geocoded);
+                cache.clone()
+            };
+            if let Err(e) = store.save_localities(&snapshot).await {
+                tracing::warn!("Failed to persist locality cache: {}", e);
+            }
+        }
+        lookups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    const NOW: u64 = 1_760_000_000;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        saved: Mutex<Option<LocalityCache>>,
+    }
+
+    impl LocalityStore for MemoryStore {
+        async fn load_localities(&self) -> Result<LocalityCache, RelayError> {
+            Ok(self.saved.lock().unwrap().clone().unwrap_or_default())
+        }
+
+        async fn save_localities(&self, cache: &LocalityCache) -> Result<(), RelayError> {
+            *self.saved.lock().unwrap() = Some(cache.clone());
+            Ok(())
+        }
+    }
+
+    fn geohashes() -> Vec<String> {
+        vec!["9q8yyk8yz".to_string(), "u4pruydqq".to_string()]
+    }
+
+    #[tokio::test]
+    async fn test_localities_are_looked_up_once_and_persisted() {
+        let store = MemoryStore::default();
+        let calls = AtomicU32::new(0);
+        let geocode = |lat: f64, _lon: f64| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let locality = (lat > 50.0).then(|| "Frogner, Oslo".to_string());
+            std::future::ready(Ok(locality))
+        };
+
+        let resolver = LocalityResolver::new(Duration::ZERO);
+        assert_eq!(
+            resolver.refresh(&store, &geohashes(), NOW, geocode).await,
+            2
+        );
+        assert_eq!(
+            resolver
+                .refresh(&store, &geohashes(), NOW + 60, geocode)
+                .await,
+            0
+        );
+        assert_eq!(
+            resolver.localities().await,
+            HashMap::from([("u4pruydqq".to_string(), "Frogner, Oslo".to_string())])
+        );
+
+        // A restarted service picks up the persisted cache instead of geocoding again
+        let restarted = LocalityResolver::new(Duration::ZERO);
+        assert_eq!(
+            restarted
+                .refresh(&store, &geohashes(), NOW + 120, geocode)
+                .await,
+            0
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // A week later each is looked up again
+        let later = NOW + LOCALITY_TTL_SECS;
+        assert_eq!(
+            restarted
+                .refresh(&store, &geohashes(), later, geocode)
+                .await,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_lookups_leave_locality_empty_and_retry_later() {
+        let store = MemoryStore::default();
+        let resolver = LocalityResolver::new(Duration::ZERO);
+
+        let failing = |_, _| std::future::ready(Err(anyhow::anyhow!("geocoder unavailable")));
+        assert_eq!(
+            resolver.refresh(&store, &geohashes(), NOW, failing).await,
+            2
+        );
+        assert!(resolver.localities().await.is_empty());
+        assert!(store.saved.lock().unwrap().is_none());
+
+        let working = |_, _| std::future::ready(Ok(Some("Mission District, San Francisco".into())));
+        assert_eq!(
+            resolver
+                .refresh(&store, &geohashes(), NOW + 60, working)
+                .await,
+            2
+        );
+        assert_eq!(resolver.localities().await.len(), 2);
+    }
+}
@@ -0,0 +1,257 @@
+//! Spot requesters binary-searching for a community's hidden anchor
+//!
+//! Each LOCATION_INVALID answer tells the requester which side of the boundary a guess fell
+//! on, and its distance bucket narrows it further. Someone submitting location after location
+//! just outside the area is probing for the anchor. Once one pubkey collects enough rejections
+//! at one community within the window, that pair gets a degraded mode: rejections lose their
+//! distance detail, answers are delayed by a random amount, and an optional cool-down refuses
+//! further attempts outright. A location that passes clears the pair.
+
+use nostr_sdk::PublicKey;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::metrics;
+use crate::libraries::rng::RngSource;
+
+// (pubkey, community) pairs tracked before idle entries are swept
+const MAX_TRACKED_PAIRS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbePolicy {
+    // Rejections within the window that mark a pair as probing; 0 disables detection
+    pub threshold: usize,
+    pub window_secs: u64,
+    // Attempts refused after each rejection of a probing pair; 0 only degrades responses
+    pub cool_down_secs: u64,
+    // Upper bound of the random delay added to a probing pair's answers
+    pub max_jitter: Duration,
+}
+
+/// How a location validation from a (pubkey, community) pair is to be answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeStatus {
+    Normal,
+    /// Answer without distance detail, after a random delay
+    Degraded,
+    /// Refuse without checking the location
+    CoolingDown {
+        retry_after_secs: u64,
+    },
+}
+
+pub struct LocationProbeGuard {
+    policy: ProbePolicy,
+    rng: Arc<dyn RngSource>,
+    // Rejection times since the pair's last accepted location, oldest first
+    rejections: Mutex<HashMap<(PublicKey, Uuid), VecDeque<u64>>>,
+}
+
+impl LocationProbeGuard {
+    pub fn new(policy: ProbePolicy, rng: Arc<dyn RngSource>) -> Self {
+        Self {
+            policy,
+            rng,
+            rejections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn status(&self, requester: &PublicKey, community: &Uuid, now: u64) -> ProbeStatus {
+        if self.policy.threshold == 0 {
+            return ProbeStatus::Normal;
+        }
+        let mut rejections = self.rejections.lock().unwrap();
+        let Some(recent) = rejections.get_mut(&(*requester, *community)) else {
+            return ProbeStatus::Normal;
+        };
+        self.expire(recent, now);
+        if recent.len() < self.policy.threshold {
+            return ProbeStatus::Normal;
+        }
+        let cooled_at = recent.back().copied().unwrap_or(now) + self.policy.cool_down_secs;
+        if cooled_at > now {
+            ProbeStatus::CoolingDown {
+                retry_after_secs: cooled_at - now,
+            }
+        } else {
+            ProbeStatus::Degraded
+        }
+    }
+
+    /// Count a LOCATION_INVALID answer to the pair
+    pub fn record_rejection(&self, requester: &PublicKey, community: &Uuid, now: u64) {
+        if self.policy.threshold == 0 {
+            return;
+        }
+        let mut rejections = self.rejections.lock().unwrap();
+        let key = (*requester, *community);
+        if rejections.len() >= MAX_TRACKED_PAIRS && !rejections.contains_key(&key) {
+            rejections.retain(|_, recent| {
+                self.expire(recent, now);
+                !recent.is_empty()
+            });
+        }
+        let recent = rejections.entry(key).or_default();
+        self.expire(recent, now);
+        recent.push_back(now);
+        if recent.len() == self.policy.threshold {
+            tracing::warn!(
+                "🕵️ {} has had {} locations rejected at community {} within {}s, degrading its responses",
+                requester,
+                recent.len(),
+                community,
+                self.policy.window_secs
+            );
+            metrics::increment("peek_location_probing_escalations_total", &[]);
+        }
+        self.publish(&rejections);
+    }
+
+    /// An accepted location ends any probing by the pair
+    pub fn record_accepted(&self, requester: &PublicKey, community: &Uuid) {
+        let mut rejections = self.rejections.lock().unwrap();
+        if rejections.remove(&(*requester, *community)).is_some() {
+            self.publish(&rejections);
+        }
+    }
+
+    /// Random delay for a degraded answer, so response timing carries no signal either
+    pub fn jitter(&self) -> Duration {
+        self.policy.max_jitter.mul_f64(self.rng.next_f64())
+    }
+
+    fn expire(&self, recent: &mut VecDeque<u64>, now: u64) {
+        while recent
+            .front()
+            .is_some_and(|at| now.saturating_sub(*at) >= self.policy.window_secs)
+        {
+            recent.pop_front();
+        }
+    }
+
+    fn publish(&self, rejections: &HashMap<(PublicKey, Uuid), VecDeque<u64>>) {
+        let probing = rejections
+            .values()
+            .filter(|recent| recent.len() >= self.policy.threshold)
+            .count();
+        metrics::set_gauge("peek_location_probing_pairs", &[], probing as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::FixedRng;
+    use nostr_sdk::Keys;
+
+    fn guard(cool_down_secs: u64) -> LocationProbeGuard {
+        LocationProbeGuard::new(
+            ProbePolicy {
+                threshold: 3,
+                window_secs: 3_600,
+                cool_down_secs,
+                max_jitter: Duration::from_millis(2_000),
+            },
+            Arc::new(FixedRng(0.25)),
+        )
+    }
+
+    #[test]
+    fn test_rejections_past_the_threshold_degrade_the_pair_only() {
+        let guard = guard(0);
+        let prober = Keys::generate().public_key();
+        let neighbor = Keys::generate().public_key();
+        let community = Uuid::new_v4();
+        let other = Uuid::new_v4();
+
+        for second in 0..3 {
+            assert_eq!(
+                guard.status(&prober, &community, 1_000 + second),
+                ProbeStatus::Normal
+            );
+            guard.record_rejection(&prober, &community, 1_000 + second);
+        }
+        assert_eq!(
+            guard.status(&prober, &community, 1_010),
+            ProbeStatus::Degraded
+        );
+        assert_eq!(guard.jitter(), Duration::from_millis(500));
+
+        // Other requesters and other communities are unaffected
+        assert_eq!(
+            guard.status(&neighbor, &community, 1_010),
+            ProbeStatus::Normal
+        );
+        assert_eq!(guard.status(&prober, &other, 1_010), ProbeStatus::Normal);
+
+        // The first rejection ages out of the window
+        assert_eq!(
+            guard.status(&prober, &community, 4_600),
+            ProbeStatus::Normal
+        );
+    }
+
+    #[test]
+    fn test_cool_down_follows_each_probing_rejection() {
+        let guard = guard(300);
+        let prober = Keys::generate().public_key();
+        let community = Uuid::new_v4();
+        for second in 0..3 {
+            guard.record_rejection(&prober, &community, 1_000 + second);
+        }
+
+        assert_eq!(
+            guard.status(&prober, &community, 1_102),
+            ProbeStatus::CoolingDown {
+                retry_after_secs: 200
+            }
+        );
+        assert_eq!(
+            guard.status(&prober, &community, 1_302),
+            ProbeStatus::Degraded
+        );
+
+        // Another miss after the cool-down starts it over
+        guard.record_rejection(&prober, &community, 1_310);
+        assert_eq!(
+            guard.status(&prober, &community, 1_310),
+            ProbeStatus::CoolingDown {
+                retry_after_secs: 300
+            }
+        );
+    }
+
+    #[test]
+    fn test_accepted_location_resets_and_zero_disables() {
+        let guard = guard(300);
+        let prober = Keys::generate().public_key();
+        let community = Uuid::new_v4();
+        for second in 0..3 {
+            guard.record_rejection(&prober, &community, 1_000 + second);
+        }
+        guard.record_accepted(&prober, &community);
+        assert_eq!(
+            guard.status(&prober, &community, 1_003),
+            ProbeStatus::Normal
+        );
+
+        let disabled = LocationProbeGuard::new(
+            ProbePolicy {
+                threshold: 0,
+                window_secs: 3_600,
+                cool_down_secs: 300,
+                max_jitter: Duration::ZERO,
+            },
+            Arc::new(FixedRng(0.5)),
+        );
+        for _ in 0..20 {
+            disabled.record_rejection(&prober, &community, 1_000);
+        }
+        assert_eq!(
+            disabled.status(&prober, &community, 1_000),
+            ProbeStatus::Normal
+        );
+    }
+}
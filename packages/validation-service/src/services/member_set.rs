@@ -0,0 +1,274 @@
+//! Group membership merged across kind 39002 revisions
+//!
+//! Relays usually keep one 39002 per group, but near their tag limits some split the list into
+//! chunks published together, publish delta revisions on top of a full list, or cut the list
+//! short. Reading a single event then undercounts without saying so. The member list is read
+//! page by page back to the newest full revision and merged here; when members may still be
+//! missing the set says so, and callers treat it conservatively.
+
+use nostr_sdk::prelude::*;
+use std::collections::HashSet;
+
+/// Marks a 39002 revision that lists changes rather than the whole membership
+pub const MEMBER_DELTA_TAG: &str = "delta";
+/// Member removed by a delta revision; `p` tags of a delta are additions
+pub const MEMBER_REMOVE_TAG: &str = "remove";
+/// Full size of the member list, advertised by relays that split or truncate it
+pub const MEMBER_COUNT_TAG: &str = "member_count";
+
+/// A group's member pubkeys (hex) as far as the relay let us read them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemberSet {
+    pub pubkeys: Vec<String>,
+    // Size the relay advertises for the whole list, if it does
+    pub advertised: Option<usize>,
+    // Members may be missing: the list was truncated, or history ran out before a full revision
+    pub possibly_incomplete: bool,
+}
+
+impl MemberSet {
+    /// A list known to be whole, e.g. a single untruncated 39002
+    pub fn complete(pubkeys: Vec<String>) -> Self {
+        Self {
+            pubkeys,
+            ..Default::default()
+        }
+    }
+
+    /// A list that could not be read at all
+    pub fn unknown() -> Self {
+        Self {
+            possibly_incomplete: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn contains(&self, pubkey_hex: &str) -> bool {
+        self.pubkeys.iter().any(|member| member == pubkey_hex)
+    }
+
+    /// Best estimate of the member count: what was read, or what the relay advertises if more
+    pub fn count(&self) -> usize {
+        self.pubkeys.len().max(self.advertised.unwrap_or(0))
+    }
+}
+
+fn has_tag(event: &Event, name: &str) -> bool {
+    event.tags.iter().any(|tag| tag.kind().to_string() == name)
+}
+
+fn tag_values<'a>(event: &'a Event, name: &'a str) -> impl Iterator<Item = &'a str> {
+    event
+        .tags
+        .iter()
+        .filter(move |tag| tag.kind().to_string() == name)
+        .filter_map(|tag| tag.content())
+}
+
+/// Whether a revision lists changes rather than the whole membership
+pub fn is_delta(event: &Event) -> bool {
+    has_tag(event, MEMBER_DELTA_TAG)
+}
+
+/// Merge 39002 revisions, in any order, into the current membership
+///
+/// Revisions apply oldest first. A full revision replaces the set, except that full revisions
+/// sharing a timestamp are chunks of one list and are unioned; a delta adds its `p` tags and
+/// drops its `remove` tags. `history_complete` says whether the revisions reach back to the
+/// newest full revision (or the start of the group); deltas with no full revision before them
+/// are incomplete otherwise. A full revision filling `max_event_tags` was probably truncated.
+pub fn merge_member_revisions(
+    revisions: &[Event],
+    history_complete: bool,
+    max_event_tags: Option<usize>,
+) -> MemberSet {
+    let mut ordered: Vec<&Event> = revisions.iter().collect();
+    ordered.sort_by(|a, b| (a.created_at, a.id).cmp(&(b.created_at, b.id)));
+
+    let mut pubkeys: Vec<String> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut base_at: Option<Timestamp> = None;
+    let mut missing_base = false;
+    let mut truncated = false;
+    for revision in &ordered {
+        let added = tag_values(revision, "p");
+        if is_delta(revision) {
+            missing_base |= base_at.is_none();
+            let removed: HashSet<&str> = tag_values(revision, MEMBER_REMOVE_TAG).collect();
+            if !removed.is_empty() {
+                pubkeys.retain(|member| !removed.contains(member.as_str()));
+                seen.retain(|member| !removed.contains(member.as_str()));
+            }
+        } else {
+            if base_at != Some(revision.created_at) {
+                pubkeys.clear();
+                seen.clear();
+                base_at = Some(revision.created_at);
+                missing_base = false;
+            }
+            truncated |= max_event_tags.is_some_and(|max| revision.tags.len() >= max);
+        }
+        for member in added {
+            if seen.insert(member.to_string()) {
+                pubkeys.push(member.to_string());
+            }
+        }
+    }
+
+    let advertised = ordered.iter().rev().find_map(|revision| {
+        tag_values(revision, MEMBER_COUNT_TAG)
+            .next()
+            .and_then(|count| count.parse().ok())
+    });
+    let possibly_incomplete = truncated
+        || (missing_base && !history_complete)
+        || advertised.is_some_and(|count| count > pubkeys.len());
+    MemberSet {
+        pubkeys,
+        advertised,
+        possibly_incomplete,
+    }
+}
+
+/// Whether revisions read so far, newest pages first, reach back far enough to merge
+/// True once a full revision is strictly newer than the oldest revision read, so every chunk
+/// sharing its timestamp has been seen too.
+pub fn reaches_full_revision(revisions: &[Event]) -> bool {
+    let Some(oldest) = revisions.iter().map(|revision| revision.created_at).min() else {
+        return false;
+    };
+    revisions
+        .iter()
+        .any(|revision| !is_delta(revision) && revision.created_at > oldest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision(created_at: u64, members: &[&str], extra: Vec<Tag>) -> Event {
+        let mut tags = vec![Tag::identifier("peek-abc123")];
+        tags.extend(
+            members
+                .iter()
+                .map(|member| Tag::custom(TagKind::Custom("p".into()), [*member])),
+        );
+        tags.extend(extra);
+        EventBuilder::new(Kind::from(39002), "")
+            .tags(tags)
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    fn delta(created_at: u64, added: &[&str], removed: &[&str]) -> Event {
+        let mut extra = vec![Tag::custom(
+            TagKind::Custom(MEMBER_DELTA_TAG.into()),
+            Vec::<String>::new(),
+        )];
+        extra.extend(
+            removed
+                .iter()
+                .map(|member| Tag::custom(TagKind::Custom(MEMBER_REMOVE_TAG.into()), [*member])),
+        );
+        revision(created_at, added, extra)
+    }
+
+    fn count_tag(count: usize) -> Tag {
+        Tag::custom(
+            TagKind::Custom(MEMBER_COUNT_TAG.into()),
+            [count.to_string()],
+        )
+    }
+
+    #[test]
+    fn test_latest_full_revision_replaces_older_ones() {
+        let revisions = vec![
+            revision(200, &["alice", "carol"], vec![]),
+            revision(100, &["alice", "bob"], vec![]),
+        ];
+
+        let members = merge_member_revisions(&revisions, true, None);
+
+        assert_eq!(
+            members,
+            MemberSet::complete(vec!["alice".into(), "carol".into()])
+        );
+    }
+
+    #[test]
+    fn test_chunks_of_one_list_are_unioned() {
+        let revisions = vec![
+            revision(100, &["alice", "bob"], vec![count_tag(4)]),
+            revision(100, &["bob", "carol", "dave"], vec![count_tag(4)]),
+        ];
+
+        let members = merge_member_revisions(&revisions, true, None);
+
+        let mut pubkeys = members.pubkeys.clone();
+        pubkeys.sort();
+        assert_eq!(pubkeys, vec!["alice", "bob", "carol", "dave"]);
+        assert!(!members.possibly_incomplete);
+        assert_eq!(members.count(), 4);
+    }
+
+    #[test]
+    fn test_deltas_add_and_remove_on_top_of_a_full_list() {
+        let revisions = vec![
+            delta(300, &["erin"], &["alice"]),
+            revision(100, &["alice", "bob"], vec![]),
+            delta(200, &["carol"], &[]),
+            // Older than the full list, so already part of it
+            delta(50, &["zed"], &[]),
+        ];
+
+        let members = merge_member_revisions(&revisions, true, None);
+
+        assert_eq!(members.pubkeys, vec!["bob", "carol", "erin"]);
+        assert!(!members.possibly_incomplete);
+    }
+
+    #[test]
+    fn test_deltas_without_their_base_are_incomplete() {
+        let revisions = vec![delta(300, &["erin"], &["alice"])];
+
+        let cut_short = merge_member_revisions(&revisions, false, None);
+        assert_eq!(cut_short.pubkeys, vec!["erin"]);
+        assert!(cut_short.possibly_incomplete);
+
+        // The whole history was read: the group started with deltas
+        assert!(!merge_member_revisions(&revisions, true, None).possibly_incomplete);
+    }
+
+    #[test]
+    fn test_truncation_is_flagged_from_the_advertised_count_or_the_tag_limit() {
+        let advertised = merge_member_revisions(
+            &[revision(100, &["alice", "bob"], vec![count_tag(5)])],
+            true,
+            None,
+        );
+        assert!(advertised.possibly_incomplete);
+        assert_eq!(advertised.count(), 5);
+
+        // d tag plus two members fills a three-tag limit
+        let full = revision(100, &["alice", "bob"], vec![]);
+        assert!(
+            merge_member_revisions(std::slice::from_ref(&full), true, Some(3)).possibly_incomplete
+        );
+        assert!(!merge_member_revisions(&[full], true, Some(4)).possibly_incomplete);
+    }
+
+    #[test]
+    fn test_paging_stops_once_a_full_revision_is_behind_the_window() {
+        let newest = delta(300, &["erin"], &[]);
+        let full = revision(200, &["alice"], vec![]);
+        assert!(!reaches_full_revision(&[newest.clone()]));
+        // Another chunk of the full list could still be on the next page
+        assert!(!reaches_full_revision(&[newest.clone(), full.clone()]));
+        assert!(reaches_full_revision(&[
+            newest,
+            full,
+            revision(100, &["bob"], vec![])
+        ]));
+    }
+}
@@ -0,0 +1,398 @@
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::relay::GroupMetadata;
+
+/// Metadata tag holding the NIP-01 address of the group's extension event
+pub const EXT_TAG: &str = "ext";
+
+/// One metadata tag per community rule, in display order
+pub const RULE_TAG: &str = "rule";
+
+/// Metadata tag holding the greeting shown to members on their first join
+pub const WELCOME_TAG: &str = "welcome";
+
+/// Rules kept in the metadata event when it overflows; later ones move to the extension
+pub const INLINE_RULES: usize = 3;
+
+/// Characters of an overflowing about text kept inline for clients that ignore the extension
+pub const ABOUT_PREVIEW_CHARS: usize = 140;
+
+// id, pubkey, sig, created_at, kind and the JSON punctuation around tags and content
+const EVENT_ENVELOPE_BYTES: usize = 320;
+
+/// Estimated size in bytes of the signed event carrying these tags and content
+pub fn estimated_event_size(tags: &[Tag], content: &str) -> usize {
+    let tags_bytes = serde_json::to_string(tags).map_or(usize::MAX / 2, |json| json.len());
+    let content_bytes = serde_json::to_string(content).map_or(usize::MAX / 2, |json| json.len());
+    EVENT_ENVELOPE_BYTES + tags_bytes + content_bytes
+}
+
+/// Metadata that did not fit in the group's kind 9002/39000 event
+/// Published as relay-authored kind 30078 app data under `extension_d_tag`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetadataExtension {
+    // Full about text; the metadata event keeps a preview
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub about: Option<String>,
+    // Rules after the first INLINE_RULES
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<String>,
+    // Welcome message, moved out whole; no preview stays inline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub welcome: Option<String>,
+}
+
+impl MetadataExtension {
+    pub fn is_empty(&self) -> bool {
+        self.about.is_none() && self.rules.is_empty() && self.welcome.is_none()
+    }
+
+    /// Put the overflow back onto metadata parsed from the core event
+    pub fn merge_into(self, metadata: &mut GroupMetadata) {
+        if let Some(about) = self.about {
+            metadata.about = Some(about);
+        }
+        if !self.rules.is_empty() {
+            metadata
+                .rules
+                .get_or_insert_with(Vec::new)
+                .extend(self.rules);
+        }
+        if let Some(welcome) = self.welcome {
+            metadata.welcome = Some(welcome);
+        }
+    }
+}
+
+/// d tag of a group's extension event
+pub fn extension_d_tag(group_id: &str) -> String {
+    format!("{}:metadata-ext", group_id)
+}
+
+/// NIP-01 address of a group's extension event, used as the ext tag value
+pub fn extension_address(author: &PublicKey, group_id: &str) -> String {
+    format!("30078:{}:{}", author, extension_d_tag(group_id))
+}
+
+/// d tag named by an ext tag value, if it addresses a kind 30078 event
+pub fn extension_d_tag_from_address(address: &str) -> Option<&str> {
+    let mut parts = address.splitn(3, ':');
+    if parts.next()? != "30078" {
+        return None;
+    }
+    parts.next()?;
+    parts.next().filter(|d_tag| !d_tag.is_empty())
+}
+
+fn is_custom(tag: &Tag, name: &str) -> bool {
+    matches!(tag.kind(), TagKind::Custom(ref k) if k == name)
+}
+
+/// A metadata edit after moving overflow out of it
+#[derive(Debug, Clone)]
+pub struct MetadataSplit {
+    pub tags: Vec<Tag>,
+    pub extension: Option<MetadataExtension>,
+}
+
+impl MetadataSplit {
+    /// Tags to publish when the extension could not be: the core metadata without the ext reference
+    pub fn core_only(self) -> Vec<Tag> {
+        self.tags
+            .into_iter()
+            .filter(|tag| !is_custom(tag, EXT_TAG))
+            .collect()
+    }
+}
+
+/// Fit a kind 9002 metadata edit under `max_bytes` and the relay's `max_tags`, if it has one
+///
+/// Edits that already fit are left alone (minus any stale ext tag). Otherwise rules beyond
+/// the first INLINE_RULES move into the extension, then, if still too large, the welcome
+/// message and finally the about text, leaving a preview inline; an ext tag pointing at
+/// `ext_address` is added. Names, anchors and
+/// flags always stay in the core event, so an edit with nothing movable is returned as is.
+pub fn split_metadata_tags(
+    tags: Vec<Tag>,
+    max_bytes: usize,
+    max_tags: Option<usize>,
+    ext_address: &str,
+) -> MetadataSplit {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !is_custom(tag, EXT_TAG))
+        .collect();
+    let within_tag_limit = |tags: &[Tag]| max_tags.is_none_or(|max| tags.len() <= max);
+    if estimated_event_size(&tags, "") <= max_bytes && within_tag_limit(&tags) {
+        return MetadataSplit {
+            tags,
+            extension: None,
+        };
+    }
+
+    // Reserve room for the reference up front
+    tags.push(Tag::custom(
+        TagKind::Custom(EXT_TAG.into()),
+        [ext_address.to_string()],
+    ));
+    let mut extension = MetadataExtension::default();
+
+    let mut rules_seen = 0;
+    tags.retain(|tag| {
+        if !is_custom(tag, RULE_TAG) {
+            return true;
+        }
+        rules_seen += 1;
+        if rules_seen <= INLINE_RULES {
+            return true;
+        }
+        extension
+            .rules
+            .push(tag.content().unwrap_or_default().to_string());
+        false
+    });
+
+    if estimated_event_size(&tags, "") > max_bytes {
+        if let Some(index) = tags.iter().position(|tag| is_custom(tag, WELCOME_TAG)) {
+            let tag = tags.remove(index);
+            extension.welcome = tag.content().map(str::to_string);
+        }
+    }
+
+    if estimated_event_size(&tags, "") > max_bytes {
+        if let Some(tag) = tags.iter_mut().find(|tag| is_custom(tag, "about")) {
+            let about = tag.content().unwrap_or_default().to_string();
+            if about.chars().count() > ABOUT_PREVIEW_CHARS {
+                let preview: String = about.chars().take(ABOUT_PREVIEW_CHARS).collect();
+                *tag = Tag::custom(
+                    TagKind::Custom("about".into()),
+                    [format!("{}…", preview.trim_end())],
+                );
+                extension.about = Some(about);
+            }
+        }
+    }
+
+    if extension.is_empty() {
+        tags.retain(|tag| !is_custom(tag, EXT_TAG));
+        return MetadataSplit {
+            tags,
+            extension: None,
+        };
+    }
+    MetadataSplit {
+        tags,
+        extension: Some(extension),
+    }
+}
+
+/// Re-inflate edit tags built from a split metadata event so the edit republishes everything
+///
+/// `stored_about` is the about tag of the stored event: an about tag still equal to it is the
+/// preview and gets the full text back, a different one is a new value from the edit and wins.
+/// A welcome message that moved out is put back as its own tag.
+pub fn restore_overflow(
+    tags: Vec<Tag>,
+    extension: MetadataExtension,
+    stored_about: Option<&str>,
+) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !is_custom(tag, EXT_TAG))
+        .map(|tag| match &extension.about {
+            Some(about) if is_custom(&tag, "about") && tag.content() == stored_about => {
+                Tag::custom(TagKind::Custom("about".into()), [about.clone()])
+            }
+            _ => tag,
+        })
+        .collect();
+    tags.extend(
+        extension
+            .rules
+            .into_iter()
+            .map(|rule| Tag::custom(TagKind::Custom(RULE_TAG.into()), [rule])),
+    );
+    if let Some(welcome) = extension.welcome {
+        if !tags.iter().any(|tag| is_custom(tag, WELCOME_TAG)) {
+            tags.push(Tag::custom(TagKind::Custom(WELCOME_TAG.into()), [welcome]));
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_BYTES: usize = 1024;
+
+    fn oversized_edit() -> (Vec<Tag>, Vec<String>, String) {
+        let rules: Vec<String> = (1..=12)
+            .map(|n| format!("Rule {}: {}", n, "be kind to your neighbors ".repeat(2)))
+            .collect();
+        let about = "Weekly meetups at the corner café, bring a friend. ".repeat(9);
+
+        let mut tags = vec![
+            Tag::custom(TagKind::Custom("h".into()), ["peek-abc123"]),
+            Tag::custom(TagKind::Name, ["Blue Bottle"]),
+            Tag::custom(TagKind::Custom("about".into()), [about.clone()]),
+            Tag::custom(TagKind::Custom("private".into()), Vec::<String>::new()),
+            Tag::custom(TagKind::Custom("closed".into()), Vec::<String>::new()),
+            Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::G)),
+                ["9q8yyk8y"],
+            ),
+        ];
+        tags.extend(
+            rules
+                .iter()
+                .map(|rule| Tag::custom(TagKind::Custom(RULE_TAG.into()), [rule.clone()])),
+        );
+        (tags, rules, about)
+    }
+
+    /// The kind 39000 event the relay would generate from a kind 9002 edit
+    fn relay_metadata_event(edit_tags: &[Tag]) -> Event {
+        let mut tags = vec![Tag::identifier("peek-abc123")];
+        tags.extend(
+            edit_tags
+                .iter()
+                .filter(|tag| tag.kind().to_string() != "h")
+                .cloned(),
+        );
+        EventBuilder::new(Kind::from(39000), "")
+            .tags(tags)
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_oversized_metadata_split_and_merge_round_trip() {
+        let relay = Keys::generate();
+        let address = extension_address(&relay.public_key(), "peek-abc123");
+        let (tags, rules, about) = oversized_edit();
+        assert!(estimated_event_size(&tags, "") > MAX_BYTES);
+
+        let split = split_metadata_tags(tags, MAX_BYTES, None, &address);
+        assert!(estimated_event_size(&split.tags, "") <= MAX_BYTES);
+        let extension = split.extension.clone().unwrap();
+        assert_eq!(extension.rules, rules[INLINE_RULES..].to_vec());
+        assert_eq!(extension.about.as_deref(), Some(about.as_str()));
+
+        // The extension travels as JSON content of the kind 30078 event
+        let content = serde_json::to_string(&extension).unwrap();
+        let extension: MetadataExtension = serde_json::from_str(&content).unwrap();
+
+        let stored = relay_metadata_event(&split.tags);
+        let mut metadata = GroupMetadata::from_event(&stored, 1);
+        assert_eq!(metadata.name, "Blue Bottle");
+        assert_eq!(metadata.rules.as_ref().unwrap().len(), INLINE_RULES);
+        assert!(metadata.about.as_ref().unwrap().ends_with('…'));
+        assert_eq!(
+            metadata.extension.as_deref(),
+            Some(extension_d_tag("peek-abc123").as_str())
+        );
+
+        extension.clone().merge_into(&mut metadata);
+        assert_eq!(metadata.rules, Some(rules.clone()));
+        assert_eq!(metadata.about, Some(about.clone()));
+
+        // A later edit of the stored event republishes the full content, then splits again
+        let stored_about = stored
+            .tags
+            .iter()
+            .find(|tag| is_custom(tag, "about"))
+            .and_then(|tag| tag.content());
+        let restored = restore_overflow(
+            stored.tags.iter().cloned().collect(),
+            extension.clone(),
+            stored_about,
+        );
+        let resplit = split_metadata_tags(restored, MAX_BYTES, None, &address);
+        assert_eq!(resplit.extension, Some(extension));
+    }
+
+    #[test]
+    fn test_long_welcome_moves_to_the_extension_whole() {
+        let address = extension_address(&Keys::generate().public_key(), "peek-abc123");
+        let welcome = "Welcome! Grab a coffee and say hi to the regulars. ".repeat(30);
+        let tags = vec![
+            Tag::custom(TagKind::Name, ["Blue Bottle"]),
+            Tag::custom(TagKind::Custom("about".into()), ["Coffee nerds"]),
+            Tag::custom(TagKind::Custom(WELCOME_TAG.into()), [welcome.clone()]),
+        ];
+
+        let split = split_metadata_tags(tags, MAX_BYTES, None, &address);
+        let extension = split.extension.clone().unwrap();
+        assert_eq!(extension.welcome.as_deref(), Some(welcome.as_str()));
+        // Moving the welcome was enough, so the short about stays inline in full
+        assert_eq!(extension.about, None);
+
+        let stored = relay_metadata_event(&split.tags);
+        let mut metadata = GroupMetadata::from_event(&stored, 1);
+        assert_eq!(metadata.welcome, None);
+        assert_eq!(metadata.about.as_deref(), Some("Coffee nerds"));
+        extension.clone().merge_into(&mut metadata);
+        assert_eq!(metadata.welcome, Some(welcome));
+
+        let restored = restore_overflow(
+            stored.tags.iter().cloned().collect(),
+            extension.clone(),
+            Some("Coffee nerds"),
+        );
+        let resplit = split_metadata_tags(restored, MAX_BYTES, None, &address);
+        assert_eq!(resplit.extension, Some(extension));
+    }
+
+    #[test]
+    fn test_core_metadata_survives_without_extension() {
+        let address = extension_address(&Keys::generate().public_key(), "peek-abc123");
+        let (tags, _, _) = oversized_edit();
+
+        let core = split_metadata_tags(tags, MAX_BYTES, None, &address).core_only();
+        let metadata = GroupMetadata::from_event(&relay_metadata_event(&core), 1);
+        assert_eq!(metadata.name, "Blue Bottle");
+        assert_eq!(metadata.anchors, vec!["9q8yyk8y"]);
+        assert_eq!(metadata.rules.unwrap().len(), INLINE_RULES);
+        assert_eq!(metadata.extension, None);
+    }
+
+    #[test]
+    fn test_rules_move_out_when_the_relay_caps_tag_count() {
+        let address = extension_address(&Keys::generate().public_key(), "peek-abc123");
+        let mut tags = vec![Tag::custom(TagKind::Name, ["Blue Bottle"])];
+        tags.extend(
+            (1..=8).map(|n| Tag::custom(TagKind::Custom(RULE_TAG.into()), [format!("Rule {}", n)])),
+        );
+        assert!(estimated_event_size(&tags, "") <= MAX_BYTES);
+
+        let split = split_metadata_tags(tags.clone(), MAX_BYTES, Some(6), &address);
+        // Name, the inline rules and the ext reference
+        assert_eq!(split.tags.len(), 1 + INLINE_RULES + 1);
+        assert_eq!(split.extension.unwrap().rules.len(), 8 - INLINE_RULES);
+
+        let roomy = split_metadata_tags(tags, MAX_BYTES, Some(9), &address);
+        assert_eq!(roomy.extension, None);
+    }
+
+    #[test]
+    fn test_small_metadata_is_untouched() {
+        let address = extension_address(&Keys::generate().public_key(), "peek-abc123");
+        let tags = vec![
+            Tag::custom(TagKind::Name, ["Blue Bottle"]),
+            Tag::custom(TagKind::Custom(RULE_TAG.into()), ["Be kind"]),
+            // Left over from an earlier split that no longer applies
+            Tag::custom(TagKind::Custom(EXT_TAG.into()), [address.clone()]),
+        ];
+
+        let split = split_metadata_tags(tags, MAX_BYTES, None, &address);
+        assert_eq!(split.extension, None);
+        assert_eq!(split.tags.len(), 2);
+        assert_eq!(
+            extension_d_tag_from_address(&address),
+            Some("peek-abc123:metadata-ext")
+        );
+        assert_eq!(extension_d_tag_from_address("39000:abc:peek-abc123"), None);
+    }
+}
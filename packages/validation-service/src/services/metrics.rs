@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::libraries::intern::Interner;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MetricType {
+    Counter,
+    Gauge,
+}
+
+/// Upper bounds (seconds) of the histogram buckets, sized for relay round trips
+const DURATION_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 3.0, 5.0, 8.0, 13.0];
+
+// Metric names and label keys and values are a small fixed set, reused on every call
+const MAX_INTERNED_LABELS: usize = 4096;
+
+// Some label values come from requesters (e.g. client names); past this many series a metric
+// records new label sets under OVERFLOW_LABEL instead, so they can't grow the registry forever
+const MAX_SERIES_PER_METRIC: usize = 256;
+
+/// Value every label of a series takes once its metric has MAX_SERIES_PER_METRIC series
+pub const OVERFLOW_LABEL: &str = "other";
+
+type SeriesKey = (Arc<str>, Vec<(Arc<str>, Arc<str>)>);
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+struct Registry {
+    strings: Interner,
+    types: BTreeMap<Arc<str>, MetricType>,
+    series: BTreeMap<SeriesKey, u64>,
+    histograms: BTreeMap<SeriesKey, Histogram>,
+    // Labelled series admitted per metric name, counters, gauges and histograms alike
+    series_counts: BTreeMap<Arc<str>, usize>,
+}
+
+/// Process-wide counters, gauges and duration histograms, rendered in the Prometheus text format
+/// Labels distinguish series of the same metric, e.g. rejection reasons
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        Mutex::new(Registry {
+            strings: Interner::new(MAX_INTERNED_LABELS),
+            types: BTreeMap::new(),
+            series: BTreeMap::new(),
+            histograms: BTreeMap::new(),
+            series_counts: BTreeMap::new(),
+        })
+    })
+}
+
+impl Registry {
+    fn series_key(&self, name: &str, labels: &[(&str, &str)]) -> SeriesKey {
+        let mut labels: Vec<(Arc<str>, Arc<str>)> = labels
+            .iter()
+            .map(|(k, v)| (self.strings.intern(k), self.strings.intern(v)))
+            .collect();
+        labels.sort();
+        (self.strings.intern(name), labels)
+    }
+
+    /// The key a new observation is recorded under: its own while the metric has room for
+    /// another series, else the metric's overflow series with every label set to "other"
+    fn admit(&mut self, key: SeriesKey, known: bool) -> SeriesKey {
+        if known || key.1.is_empty() {
+            return key;
+        }
+        let count = self.series_counts.entry(key.0.clone()).or_insert(0);
+        if *count < MAX_SERIES_PER_METRIC {
+            *count += 1;
+            return key;
+        }
+        let other = self.strings.intern(OVERFLOW_LABEL);
+        let (name, labels) = key;
+        let labels = labels
+            .into_iter()
+            .map(|(label, _)| (label, other.clone()))
+            .collect();
+        (name, labels)
+    }
+
+    fn declare(&mut self, name: &str, metric_type: MetricType) {
+        if !self.types.contains_key(name) {
+            let name = self.strings.intern(name);
+            self.types.insert(name, metric_type);
+        }
+    }
+}
+
+/// Increment a counter by one
+pub fn increment(name: &str, labels: &[(&str, &str)]) {
+    add(name, labels, 1);
+}
+
+/// Increment a counter by `value`
+pub fn add(name: &str, labels: &[(&str, &str)], value: u64) {
+    let mut registry = registry().lock().unwrap();
+    registry.declare(name, MetricType::Counter);
+    let key = registry.series_key(name, labels);
+    let known = registry.series.contains_key(&key);
+    let key = registry.admit(key, known);
+    *registry.series.entry(key).or_insert(0) += value;
+}
+
+/// Set a gauge to an absolute value
+pub fn set_gauge(name: &str, labels: &[(&str, &str)], value: u64) {
+    let mut registry = registry().lock().unwrap();
+    registry.declare(name, MetricType::Gauge);
+    let key = registry.series_key(name, labels);
+    let known = registry.series.contains_key(&key);
+    let key = registry.admit(key, known);
+    registry.series.insert(key, value);
+}
+
+/// Record one observation (in seconds) in a duration histogram
+pub fn observe(name: &str, labels: &[(&str, &str)], seconds: f64) {
+    let mut registry = registry().lock().unwrap();
+    let key = registry.series_key(name, labels);
+    let known = registry.histograms.contains_key(&key);
+    let key = registry.admit(key, known);
+    let histogram = registry.histograms.entry(key).or_default();
+    if let Some(index) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+        histogram.buckets[index] += 1;
+    }
+    histogram.sum += seconds;
+    histogram.count += 1;
+}
+
+/// Number of observations recorded in a histogram series (0 if never recorded)
+pub fn observation_count(name: &str, labels: &[(&str, &str)]) -> u64 {
+    let registry = registry().lock().unwrap();
+    registry
+        .histograms
+        .get(&registry.series_key(name, labels))
+        .map(|histogram| histogram.count)
+        .unwrap_or(0)
+}
+
+fn render_labels(labels: &[(Arc<str>, Arc<str>)], extra: Option<(&str, &str)>) -> String {
+    let rendered = labels
+        .iter()
+        .map(|(k, v)| (&**k, &**v))
+        .chain(extra)
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(",");
+    if rendered.is_empty() {
+        rendered
+    } else {
+        format!("{{{}}}", rendered)
+    }
+}
+
+/// Current value of a counter or gauge series (0 if never recorded)
+pub fn get(name: &str, labels: &[(&str, &str)]) -> u64 {
+    let registry = registry().lock().unwrap();
+    registry
+        .series
+        .get(&registry.series_key(name, labels))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Render every series in the Prometheus text exposition format
+pub fn render() -> String {
+    let registry = registry().lock().unwrap();
+    let mut out = String::new();
+    let mut current_name: Option<&str> = None;
+
+    for ((name, labels), value) in &registry.series {
+        if current_name != Some(&**name) {
+            let metric_type = match registry.types.get(name) {
+                Some(MetricType::Gauge) => "gauge",
+                _ => "counter",
+            };
+            let _ = writeln!(out, "# TYPE {} {}", name, metric_type);
+            current_name = Some(&**name);
+        }
+
+        let _ = writeln!(out, "{}{} {}", name, render_labels(labels, None), value);
+    }
+
+    current_name = None;
+    for ((name, labels), histogram) in &registry.histograms {
+        if current_name != Some(&**name) {
+            let _ = writeln!(out, "# TYPE {} histogram", name);
+            current_name = Some(&**name);
+        }
+
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            let le = bound.to_string();
+            let labels = render_labels(labels, Some(("le", &le)));
+            let _ = writeln!(out, "{}_bucket{} {}", name, labels, cumulative);
+        }
+        let labels_inf = render_labels(labels, Some(("le", "+Inf")));
+        let _ = writeln!(out, "{}_bucket{} {}", name, labels_inf, histogram.count);
+        let labels = render_labels(labels, None);
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, histogram.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, histogram.count);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_accumulate_per_label_set() {
+        increment("test_counter_total", &[("reason", "a")]);
+        increment("test_counter_total", &[("reason", "a")]);
+        add("test_counter_total", &[("reason", "b")], 5);
+
+        assert_eq!(get("test_counter_total", &[("reason", "a")]), 2);
+        assert_eq!(get("test_counter_total", &[("reason", "b")]), 5);
+        assert_eq!(get("test_counter_total", &[("reason", "c")]), 0);
+    }
+
+    #[test]
+    fn test_render_prometheus_text() {
+        set_gauge("test_render_gauge", &[], 3);
+        increment("test_render_total", &[("kind", "x\"y")]);
+
+        let text = render();
+        assert!(text.contains("# TYPE test_render_gauge gauge\ntest_render_gauge 3\n"));
+        assert!(text.contains("# TYPE test_render_total counter\n"));
+        assert!(text.contains("test_render_total{kind=\"x\\\"y\"} 1\n"));
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        observe("test_duration_seconds", &[("op", "read")], 0.07);
+        observe("test_duration_seconds", &[("op", "read")], 0.3);
+        observe("test_duration_seconds", &[("op", "read")], 60.0);
+
+        assert_eq!(
+            observation_count("test_duration_seconds", &[("op", "read")]),
+            3
+        );
+        let text = render();
+        assert!(text.contains("# TYPE test_duration_seconds histogram\n"));
+        assert!(text.contains("test_duration_seconds_bucket{op=\"read\",le=\"0.05\"} 0\n"));
+        assert!(text.contains("test_duration_seconds_bucket{op=\"read\",le=\"0.1\"} 1\n"));
+        assert!(text.contains("test_duration_seconds_bucket{op=\"read\",le=\"0.5\"} 2\n"));
+        assert!(text.contains("test_duration_seconds_bucket{op=\"read\",le=\"13\"} 2\n"));
+        assert!(text.contains("test_duration_seconds_bucket{op=\"read\",le=\"+Inf\"} 3\n"));
+        assert!(text.contains("test_duration_seconds_count{op=\"read\"} 3\n"));
+    }
+
+    #[test]
+    fn test_series_past_the_cap_collapse_into_other() {
+        for i in 0..MAX_SERIES_PER_METRIC {
+            increment("test_capped_total", &[("client", &format!("app-{}", i))]);
+        }
+        increment("test_capped_total", &[("client", "one-too-many")]);
+        increment("test_capped_total", &[("client", "another")]);
+        // Series admitted before the cap keep counting under their own labels
+        increment("test_capped_total", &[("client", "app-0")]);
+
+        assert_eq!(get("test_capped_total", &[("client", "one-too-many")]), 0);
+        assert_eq!(get("test_capped_total", &[("client", OVERFLOW_LABEL)]), 2);
+        assert_eq!(get("test_capped_total", &[("client", "app-0")]), 2);
+        let rendered = render()
+            .lines()
+            .filter(|line| line.starts_with("test_capped_total{"))
+            .count();
+        assert_eq!(rendered, MAX_SERIES_PER_METRIC + 1);
+    }
+}
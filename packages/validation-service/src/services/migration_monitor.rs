@@ -5,7 +5,7 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{error, info};
 
-use super::relay::RelayService;
+use super::relay_access::{GroupReader, GroupWriter};
 
 const MIGRATION_KIND: u16 = 1776;
 #[allow(dead_code)]
@@ -13,14 +13,16 @@ const MAX_MIGRATION_DEPTH: usize = 10;
 
 /// Service for monitoring and processing identity migrations (NIP-XX/kind 1776)
 pub struct MigrationMonitor {
-    relay_service: Arc<RwLock<RelayService>>,
+    groups: GroupReader,
+    writer: GroupWriter,
     migration_cache: Arc<RwLock<HashMap<String, String>>>, // old_pubkey -> new_pubkey
 }
 
 impl MigrationMonitor {
-    pub fn new(relay_service: Arc<RwLock<RelayService>>) -> Self {
+    pub fn new(groups: GroupReader, writer: GroupWriter) -> Self {
         Self {
-            relay_service,
+            groups,
+            writer,
             migration_cache: Arc::new(RwLock::new(HashMap::new())),
         }
     }
@@ -28,19 +30,22 @@ impl MigrationMonitor {
     /// Start monitoring for migration events
     /// NOTE: This creates the subscription, but events are handled by the
     /// NostrValidationHandler's notification handler
-    pub async fn start_monitoring(&self, client: &Client) -> AnyResult<()> {
+    pub async fn start_monitoring(&self, client: &Client) -> AnyResult<SubscriptionId> {
         info!(
             "Starting migration monitor for kind {} events",
             MIGRATION_KIND
         );
 
         // Subscribe to all migration events using the shared client
-        let filter = Filter::new().kind(Kind::Custom(MIGRATION_KIND)).limit(0);
-
-        client.subscribe(filter, None).await?;
+        let output = client.subscribe(Self::subscription_filter(), None).await?;
 
         info!("Subscribed to migration events (kind {})", MIGRATION_KIND);
-        Ok(())
+        Ok(output.val)
+    }
+
+    /// Filter for all migration events, re-sent by the subscription watchdog
+    pub fn subscription_filter() -> Filter {
+        Filter::new().kind(Kind::Custom(MIGRATION_KIND)).limit(0)
     }
 
     /// Handle a migration event
@@ -153,7 +158,7 @@ impl MigrationMonitor {
 
         info!("Found {} groups to update", groups.len());
 
-        // Check admin status for all groups BEFORE acquiring write lock to avoid deadlock
+        // Check admin status for all groups before changing any of them
         let mut group_admin_status = Vec::new();
         for group_id in &groups {
             let is_admin = self
@@ -167,9 +172,7 @@ impl MigrationMonitor {
             group_admin_status.push((group_id.clone(), is_admin));
         }
 
-        // Now acquire write lock to perform updates
-        let relay_service = self.relay_service.write().await;
-
+        // Each group's add and remove are serialized with other writes to that group
         for (group_id, is_admin) in group_admin_status {
             info!(
                 "Updating group {}: replacing {} with {} (admin: {})",
@@ -178,7 +181,8 @@ impl MigrationMonitor {
 
             // Add new member first with same admin status as old member
             // This ensures groups always have at least one admin
-            match relay_service
+            match self
+                .writer
                 .add_group_member(&group_id, new_pubkey, is_admin)
                 .await
             {
@@ -193,10 +197,7 @@ impl MigrationMonitor {
             }
 
             // Only remove old member after successful add
-            match relay_service
-                .remove_group_member(&group_id, old_pubkey)
-                .await
-            {
+            match self.writer.remove_group_member(&group_id, old_pubkey).await {
                 Ok(_) => info!("Removed {} from group {}", old_pubkey, group_id),
                 Err(e) => error!(
                     "Failed to remove {} from group {}: {}",
@@ -216,8 +217,8 @@ impl MigrationMonitor {
             .custom_tag(SingleLetterTag::lowercase(Alphabet::P), pubkey.to_string());
 
         use std::time::Duration;
-        let relay_service = self.relay_service.read().await;
-        let events = relay_service
+        let events = self
+            .groups
             .client()
             .fetch_events(filter, Duration::from_secs(5))
             .await?;
@@ -249,8 +250,8 @@ impl MigrationMonitor {
             .limit(1);
 
         use std::time::Duration;
-        let relay_service = self.relay_service.read().await;
-        let events = relay_service
+        let events = self
+            .groups
             .client()
             .fetch_events(filter, Duration::from_secs(5))
             .await?;
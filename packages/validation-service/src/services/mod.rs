@@ -1,5 +1,47 @@
+pub mod admin_audit;
+pub mod admin_jobs;
+pub mod audit_trail;
+pub mod bulk_members;
+pub mod client_pool;
 pub mod community;
+pub mod community_clusters;
+pub mod community_export;
+pub mod community_labels;
+pub mod community_search;
+pub mod creation_limit;
+pub mod discovery_map;
+pub mod discovery_reconcile;
+pub mod execution;
+pub mod far_scans;
+#[cfg(any(debug_assertions, feature = "fault-injection"))]
+pub mod fault_injection;
 pub mod gift_wrap;
+pub mod group_feed;
+pub mod in_flight;
+pub mod inactive_prune;
+pub mod inbox_relays;
+pub mod itag_backfill;
+pub mod join_notifications;
+pub mod join_requests;
+pub mod localities;
+pub mod location_probing;
+pub mod member_set;
+pub mod metadata_extension;
+pub mod metrics;
 pub mod migration_monitor;
+pub mod nearby_index;
+pub mod operator_webhook;
+pub mod orphan_sweep;
 pub mod overpass;
+pub mod previous_refs;
+pub mod public_relay;
+pub mod quiet_hours;
 pub mod relay;
+pub mod relay_access;
+pub mod relay_authorization;
+pub mod relay_circuit;
+pub mod relay_limits;
+pub mod response_retry;
+pub mod sticker_reuse;
+pub mod subscription_watchdog;
+pub mod venue_hours;
@@ -0,0 +1,241 @@
+use nostr_sdk::prelude::*;
+use peek_geo::neighbor_cells;
+use std::collections::HashMap;
+use std::future::Future;
+use uuid::Uuid;
+
+use super::relay::GroupMetadata;
+use crate::models::ProtocolConfig;
+
+/// A community known to be anchored in a level 8 geohash cell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedCommunity {
+    pub group_id: String,
+    pub community_id: Option<Uuid>,
+}
+
+/// In-memory index of community anchors by level 8 geohash
+///
+/// Rebuilt from the kind 39000 events fetched for the discovery map and extended as
+/// groups are created, so duplicate detection never needs a per-request relay scan.
+#[derive(Debug, Default)]
+pub struct NearbyIndex {
+    by_cell: HashMap<String, Vec<IndexedCommunity>>,
+}
+
+impl NearbyIndex {
+    /// Build the index from group metadata events, one entry per g tag (anchor)
+    pub fn from_events<'a>(
+        events: impl IntoIterator<Item = &'a Event>,
+        protocol: &ProtocolConfig,
+    ) -> Self {
+        let mut index = Self::default();
+        for event in events {
+            let Some(group_id) = event.tags.identifier() else {
+                continue;
+            };
+            let community_id = event
+                .tags
+                .find(TagKind::SingleLetter(SingleLetterTag::lowercase(
+                    Alphabet::I,
+                )))
+                .and_then(|t| t.content())
+                .and_then(|i| protocol.parse_uuid_tag(i));
+
+            for tag in event.tags.iter() {
+                if let [name, cell, ..] = tag.as_slice() {
+                    if name == "g" && cell.len() == 8 {
+                        index.insert(
+                            cell,
+                            IndexedCommunity {
+                                group_id: group_id.to_string(),
+                                community_id,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    pub fn insert(&mut self, cell: &str, community: IndexedCommunity) {
+        let entries = self.by_cell.entry(cell.to_string()).or_default();
+        if !entries.iter().any(|e| e.group_id == community.group_id) {
+            entries.push(community);
+        }
+    }
+
+    /// Drop every anchor of a community, returning the group ids that were indexed for it
+    pub fn remove_community(&mut self, community_id: &Uuid) -> Vec<String> {
+        let mut removed: Vec<String> = Vec::new();
+        self.by_cell.retain(|_, entries| {
+            entries.retain(|entry| {
+                if entry.community_id.as_ref() != Some(community_id) {
+                    return true;
+                }
+                if !removed.contains(&entry.group_id) {
+                    removed.push(entry.group_id.clone());
+                }
+                false
+            });
+            !entries.is_empty()
+        });
+        removed
+    }
+
+    /// Communities anchored in `cell` or any of its eight neighbors
+    pub fn nearby(&self, cell: &str) -> Vec<IndexedCommunity> {
+        let mut cells = vec![cell.to_string()];
+        if let Ok(around) = neighbor_cells(cell) {
+            cells.extend(around);
+        }
+
+        let mut found: Vec<IndexedCommunity> = Vec::new();
+        for cell in cells {
+            for community in self.by_cell.get(&cell).into_iter().flatten() {
+                if !found.iter().any(|f| f.group_id == community.group_id) {
+                    found.push(community.clone());
+                }
+            }
+        }
+        found
+    }
+}
+
+/// First nearby community that already has members, unless the creator forces creation
+/// Empty groups (e.g. abandoned creations) never block a new community
+pub async fn find_populated_duplicate<F, Fut>(
+    candidates: Vec<IndexedCommunity>,
+    force: bool,
+    mut load_metadata: F,
+) -> Option<(IndexedCommunity, GroupMetadata)>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Option<GroupMetadata>>,
+{
+    if force {
+        return None;
+    }
+
+    for candidate in candidates {
+        if let Some(metadata) = load_metadata(candidate.group_id.clone()).await {
+            if metadata.member_count > 0 {
+                return Some((candidate, metadata));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: &str = "9q8yyk8y";
+
+    fn group_event(group_id: &str, cells: &[&str]) -> Event {
+        let protocol = ProtocolConfig::default();
+        let uuid = Uuid::new_v4();
+        let mut tags = vec![
+            Tag::identifier(group_id),
+            Tag::custom(TagKind::Custom("name".into()), ["Café"]),
+            Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+                [protocol.uuid_tag(&uuid)],
+            ),
+        ];
+        for cell in cells {
+            tags.push(Tag::custom(TagKind::Custom("g".into()), [*cell]));
+        }
+        EventBuilder::new(Kind::from(39000), "")
+            .tags(tags)
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    fn metadata(group_id: &str, member_count: u32) -> GroupMetadata {
+        GroupMetadata::from_event(&group_event(group_id, &[CELL]), member_count)
+    }
+
+    fn index_with(group_id: &str, cell: &str) -> NearbyIndex {
+        let event = group_event(group_id, &[cell]);
+        NearbyIndex::from_events([&event], &ProtocolConfig::default())
+    }
+
+    #[test]
+    fn test_index_matches_same_and_neighbor_cells() {
+        let [_, _, east, ..] = neighbor_cells(CELL).unwrap();
+        let index = index_with("peek-cafe", &east);
+
+        assert_eq!(index.nearby(CELL).len(), 1);
+        assert_eq!(index.nearby(&east)[0].group_id, "peek-cafe");
+        assert!(index.nearby("u4pruydq").is_empty());
+    }
+
+    #[test]
+    fn test_removing_a_community_keeps_its_neighbors() {
+        let [_, _, east, ..] = neighbor_cells(CELL).unwrap();
+        let (cafe, park) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let mut index = NearbyIndex::default();
+        for (cell, group_id, community_id) in [
+            (CELL, "peek-cafe", cafe),
+            (east.as_str(), "peek-cafe", cafe),
+            (CELL, "peek-park", park),
+        ] {
+            index.insert(
+                cell,
+                IndexedCommunity {
+                    group_id: group_id.to_string(),
+                    community_id: Some(community_id),
+                },
+            );
+        }
+
+        assert_eq!(index.remove_community(&cafe), vec!["peek-cafe"]);
+        assert_eq!(index.nearby(CELL).len(), 1);
+        assert_eq!(index.nearby(CELL)[0].group_id, "peek-park");
+        assert!(index.remove_community(&cafe).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_existing_community_with_members_is_reported() {
+        let candidates = index_with("peek-cafe", CELL).nearby(CELL);
+
+        let duplicate = find_populated_duplicate(candidates, false, |group_id| async move {
+            Some(metadata(&group_id, 4))
+        })
+        .await;
+
+        let (community, metadata) = duplicate.expect("populated community should block creation");
+        assert_eq!(community.group_id, "peek-cafe");
+        assert_eq!(metadata.member_count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_existing_empty_community_does_not_block() {
+        let candidates = index_with("peek-cafe", CELL).nearby(CELL);
+
+        let duplicate = find_populated_duplicate(candidates, false, |group_id| async move {
+            Some(metadata(&group_id, 0))
+        })
+        .await;
+
+        assert!(duplicate.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_force_skips_duplicate_check() {
+        let candidates = index_with("peek-cafe", CELL).nearby(CELL);
+        let mut lookups = 0;
+
+        let duplicate = find_populated_duplicate(candidates, true, |group_id| {
+            lookups += 1;
+            async move { Some(metadata(&group_id, 4)) }
+        })
+        .await;
+
+        assert!(duplicate.is_none());
+        assert_eq!(lookups, 0);
+    }
+}
@@ -0,0 +1,183 @@
+//! Best-effort alerts for the operator, POSTed as JSON to OPERATOR_WEBHOOK_URL
+//!
+//! Nothing waits on delivery: a failed POST is logged and counted, never retried, so alerting
+//! can't hold up or fail the request that raised it.
+
+use serde::Serialize;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::audit_trail::AuditEntry;
+use super::metrics;
+use crate::libraries::exclusion_zones::ExclusionZones;
+use crate::models::Coordinates;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "alert", rename_all = "snake_case")]
+pub enum OperatorAlert {
+    /// A community was created with its anchor inside an exclusion zone. Its map marker is
+    /// kept outside the zone, but the sticker itself may need to come down.
+    AnchorInExclusionZone {
+        community_id: Uuid,
+        group_id: String,
+        // Position of the zone in EXCLUSION_ZONES
+        zone: usize,
+        anchor_geohash: String,
+    },
+    /// An admin acted through the admin API or an admin-level request, or was refused
+    AdminAction(AuditEntry),
+}
+
+impl OperatorAlert {
+    /// The alert for a community anchored at `anchor`, if that is inside a zone
+    pub fn anchor_in_zone(
+        zones: &ExclusionZones,
+        community_id: Uuid,
+        group_id: &str,
+        anchor: &Coordinates,
+    ) -> Option<Self> {
+        let (zone, _) = zones.containing(anchor)?;
+        Some(Self::AnchorInExclusionZone {
+            community_id,
+            group_id: group_id.to_string(),
+            zone,
+            anchor_geohash: anchor.geohash(8).unwrap_or_default(),
+        })
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::AnchorInExclusionZone { .. } => "anchor_in_exclusion_zone",
+            Self::AdminAction(_) => "admin_action",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OperatorWebhook {
+    url: Option<String>,
+    client: reqwest::Client,
+}
+
+impl OperatorWebhook {
+    pub fn new(url: Option<String>) -> Self {
+        Self {
+            url: url.filter(|url| !url.trim().is_empty()),
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Log `alert` and POST it to the webhook if one is configured; true if it was delivered
+    pub async fn notify(&self, alert: &OperatorAlert) -> bool {
+        tracing::warn!("Operator alert: {:?}", alert);
+        let Some(url) = &self.url else {
+            return false;
+        };
+        let delivered = match self.client.post(url).json(alert).send().await {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                tracing::warn!("Operator webhook returned {}", response.status());
+                false
+            }
+            Err(e) => {
+                tracing::warn!("Operator webhook failed: {}", e);
+                false
+            }
+        };
+        metrics::increment(
+            "peek_operator_alerts_total",
+            &[
+                ("alert", alert.kind()),
+                ("delivered", if delivered { "true" } else { "false" }),
+            ],
+        );
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<serde_json::Value>>>;
+
+    async fn webhook_server() -> (String, Received) {
+        let received = Received::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State(received): State<Received>, Json(body): Json<serde_json::Value>| async move {
+                        received.lock().unwrap().push(body);
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, received)
+    }
+
+    fn zones() -> ExclusionZones {
+        ExclusionZones::parse(&[
+            "51.5007:-0.1246:100".to_string(),
+            "37.7749:-122.4194:300".to_string(),
+        ])
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_fires_for_anchor_inside_a_zone() {
+        let (url, received) = webhook_server().await;
+        let webhook = OperatorWebhook::new(Some(url));
+        let community_id = Uuid::new_v4();
+
+        let inside = Coordinates::new(37.7752, -122.4190).unwrap();
+        let alert = OperatorAlert::anchor_in_zone(&zones(), community_id, "peek-abc", &inside)
+            .expect("anchor is inside the second zone");
+        assert!(webhook.notify(&alert).await);
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(
+            received,
+            vec![serde_json::json!({
+                "alert": "anchor_in_exclusion_zone",
+                "community_id": community_id,
+                "group_id": "peek-abc",
+                "zone": 1,
+                "anchor_geohash": inside.geohash(8).unwrap(),
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anchors_outside_zones_raise_nothing() {
+        let outside = Coordinates::new(37.7849, -122.4094).unwrap();
+        assert_eq!(
+            OperatorAlert::anchor_in_zone(&zones(), Uuid::new_v4(), "peek-abc", &outside),
+            None
+        );
+        // Unconfigured or unreachable webhooks only log
+        let alert = OperatorAlert::anchor_in_zone(
+            &zones(),
+            Uuid::new_v4(),
+            "peek-abc",
+            &Coordinates::new(51.5007, -0.1246).unwrap(),
+        )
+        .unwrap();
+        assert!(!OperatorWebhook::new(None).notify(&alert).await);
+        assert!(
+            !OperatorWebhook::new(Some("http://127.0.0.1:9/hook".to_string()))
+                .notify(&alert)
+                .await
+        );
+    }
+}
@@ -0,0 +1,532 @@
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::admin_jobs::ScanProgress;
+use super::execution::{ExecutionMode, MutationPlan};
+use super::metrics;
+use super::relay::member_pubkeys;
+use super::relay_access::GroupWriter;
+use crate::libraries::clock::Clock;
+use crate::models::ProtocolConfig;
+
+/// Where the sweep finds this deployment's groups, and deletes the orphans
+pub trait OrphanSource: Send + Sync + 'static {
+    /// Every group whose kind 39000 metadata carries a community UUID i-tag
+    fn sweep_candidates(&self) -> impl Future<Output = anyhow::Result<Vec<SweepCandidate>>> + Send;
+
+    /// Send a kind 9008 delete-group, recording it in `plan` (and only recording it in a dry run)
+    fn delete_group(
+        &self,
+        group_id: &str,
+        plan: &mut MutationPlan,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+impl OrphanSource for GroupWriter {
+    async fn sweep_candidates(&self) -> anyhow::Result<Vec<SweepCandidate>> {
+        Ok(self.reader().orphan_sweep_candidates().await?)
+    }
+
+    async fn delete_group(&self, group_id: &str, plan: &mut MutationPlan) -> anyhow::Result<()> {
+        let mut execution = self.execution(plan.mode());
+        let deleted = self.delete_group(group_id, &mut execution).await;
+        plan.extend(execution.into_plan());
+        Ok(deleted?)
+    }
+}
+
+/// One group as the sweep sees it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepCandidate {
+    pub group_id: String,
+    pub community_id: Uuid,
+    // The kind 9007 creation, or the metadata's timestamp if the relay no longer serves it
+    pub created_at: u64,
+    // Latest metadata or member list update
+    pub last_activity: u64,
+    // Members other than the relay key
+    pub members: usize,
+}
+
+/// Build sweep candidates from relay-signed metadata (39000), member lists (39002) and
+/// group creations (9007)
+pub fn sweep_candidates(
+    metadata: &[Event],
+    member_lists: &[Event],
+    creations: &[Event],
+    relay_pubkey: &PublicKey,
+    protocol: &ProtocolConfig,
+) -> Vec<SweepCandidate> {
+    let relay_hex = relay_pubkey.to_hex();
+    let mut members: HashMap<&str, (usize, u64)> = HashMap::new();
+    for list in member_lists {
+        let Some(group_id) = list.tags.identifier() else {
+            continue;
+        };
+        let count = member_pubkeys(list)
+            .iter()
+            .filter(|member| **member != relay_hex)
+            .count();
+        members.insert(group_id, (count, list.created_at.as_u64()));
+    }
+
+    let mut created: HashMap<&str, u64> = HashMap::new();
+    for creation in creations {
+        let Some(group_id) = creation
+            .tags
+            .find(TagKind::Custom("h".into()))
+            .and_then(|tag| tag.content())
+        else {
+            continue;
+        };
+        let at = created.entry(group_id).or_insert(u64::MAX);
+        *at = (*at).min(creation.created_at.as_u64());
+    }
+
+    metadata
+        .iter()
+        .filter_map(|event| {
+            let group_id = event
+                .tags
+                .identifier()
+                .filter(|group_id| protocol.owns_group_id(group_id))?;
+            let community_id = event
+                .tags
+                .find(TagKind::SingleLetter(SingleLetterTag::lowercase(
+                    Alphabet::I,
+                )))
+                .and_then(|tag| tag.content())
+                .and_then(|i| protocol.parse_uuid_tag(i))?;
+            let updated_at = event.created_at.as_u64();
+            let (member_count, members_at) = members.get(group_id).copied().unwrap_or((0, 0));
+            Some(SweepCandidate {
+                group_id: group_id.to_string(),
+                community_id,
+                created_at: created.get(group_id).copied().unwrap_or(updated_at),
+                last_activity: updated_at.max(members_at),
+                members: member_count,
+            })
+        })
+        .collect()
+}
+
+/// When a group becomes an orphan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepPolicy {
+    // Duplicates younger than this may still be mid-creation
+    pub quarantine_secs: u64,
+    // Memberless groups idle this long were never finished
+    pub idle_secs: u64,
+}
+
+/// Why a group is deleted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum OrphanReason {
+    /// Another group carries the same community UUID and is the one kept
+    Duplicate { survivor: String },
+    /// Nobody but the relay key ever joined, and nothing has changed for the idle period
+    Abandoned,
+}
+
+impl OrphanReason {
+    /// Metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Duplicate { .. } => "duplicate",
+            Self::Abandoned => "abandoned",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Orphan {
+    pub group_id: String,
+    pub community_id: Uuid,
+    pub members: usize,
+    #[serde(flatten)]
+    pub reason: OrphanReason,
+}
+
+/// Pick the groups to delete
+///
+/// Groups sharing a community UUID keep the oldest one that has members (the oldest overall
+/// when none do); the rest are deleted once past the quarantine. Any kept group with no
+/// members is deleted once it has been idle for the policy's idle period.
+pub fn select_orphans(candidates: &[SweepCandidate], now: u64, policy: SweepPolicy) -> Vec<Orphan> {
+    let mut by_community: BTreeMap<Uuid, Vec<&SweepCandidate>> = BTreeMap::new();
+    for candidate in candidates {
+        by_community
+            .entry(candidate.community_id)
+            .or_default()
+            .push(candidate);
+    }
+
+    let mut orphans = Vec::new();
+    for (community_id, mut groups) in by_community {
+        groups.sort_by(|a, b| (a.created_at, &a.group_id).cmp(&(b.created_at, &b.group_id)));
+        let survivor = groups
+            .iter()
+            .find(|group| group.members > 0)
+            .unwrap_or(&groups[0]);
+
+        for group in &groups {
+            let reason = if group.group_id != survivor.group_id {
+                (now.saturating_sub(group.created_at) >= policy.quarantine_secs).then(|| {
+                    OrphanReason::Duplicate {
+                        survivor: survivor.group_id.clone(),
+                    }
+                })
+            } else {
+                (group.members == 0 && now.saturating_sub(group.last_activity) >= policy.idle_secs)
+                    .then_some(OrphanReason::Abandoned)
+            };
+            if let Some(reason) = reason {
+                orphans.push(Orphan {
+                    group_id: group.group_id.clone(),
+                    community_id,
+                    members: group.members,
+                    reason,
+                });
+            }
+        }
+    }
+    orphans
+}
+
+/// Outcome of one sweep
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SweepReport {
+    pub swept_at: u64,
+    // Groups with a community UUID that were considered
+    pub scanned: usize,
+    pub orphans: Vec<Orphan>,
+    // Orphans whose delete-group could not be sent
+    pub failed: Vec<String>,
+}
+
+/// A sweep's report with the kind 9008 deletions it sent, or would send in a dry run
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepRun {
+    #[serde(flatten)]
+    pub plan: MutationPlan,
+    pub report: SweepReport,
+}
+
+/// Deletes groups left behind by racing or partial community creations
+///
+/// Two validations racing for the same sticker can both create a group with its UUID, and a
+/// creation that fails after the kind 9007 leaves a group nobody ever joins. Lookups by UUID
+/// pick one of the duplicates arbitrarily, so the extras are deleted.
+pub struct OrphanSweep<S> {
+    source: S,
+    clock: Arc<dyn Clock>,
+    policy: SweepPolicy,
+}
+
+impl<S: OrphanSource> OrphanSweep<S> {
+    pub fn new(source: S, clock: Arc<dyn Clock>, policy: SweepPolicy) -> Self {
+        Self {
+            source,
+            clock,
+            policy,
+        }
+    }
+
+    /// Run one sweep in `mode`; a dry run reports the orphans but deletes nothing
+    pub async fn run_with(&self, mode: ExecutionMode) -> anyhow::Result<SweepRun> {
+        self.run_reporting(mode, &|_| {}).await
+    }
+
+    /// Run one sweep, reporting each orphan handled to `progress`
+    pub async fn run_reporting(
+        &self,
+        mode: ExecutionMode,
+        progress: &(dyn Fn(ScanProgress) + Sync),
+    ) -> anyhow::Result<SweepRun> {
+        let candidates = self.source.sweep_candidates().await?;
+        let now = self.clock.now_unix();
+        let orphans = select_orphans(&candidates, now, self.policy);
+        let mut plan = MutationPlan::new(mode);
+        let mut failed = Vec::new();
+        let mut scan = ScanProgress {
+            total: orphans.len(),
+            ..Default::default()
+        };
+        progress(scan);
+
+        for orphan in &orphans {
+            match self.source.delete_group(&orphan.group_id, &mut plan).await {
+                Ok(()) => {
+                    info!(
+                        "{} orphaned group {} of community {} ({:?}, {} members)",
+                        if plan.dry_run {
+                            "Would delete"
+                        } else {
+                            "Deleted"
+                        },
+                        orphan.group_id,
+                        orphan.community_id,
+                        orphan.reason,
+                        orphan.members
+                    );
+                    if mode == ExecutionMode::Execute {
+                        metrics::increment(
+                            "peek_orphan_groups_deleted_total",
+                            &[("reason", orphan.reason.as_str())],
+                        );
+                    }
+                }
+                Err(e) => {
+                    warn!("Deleting orphaned group {} failed: {}", orphan.group_id, e);
+                    failed.push(orphan.group_id.clone());
+                    scan.errors += 1;
+                }
+            }
+            scan.processed += 1;
+            progress(scan);
+        }
+
+        Ok(SweepRun {
+            plan,
+            report: SweepReport {
+                swept_at: now,
+                scanned: candidates.len(),
+                orphans,
+                failed,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+    use crate::services::execution::PlannedEvent;
+    use std::sync::Mutex;
+
+    const NOW: u64 = 1_760_000_000;
+    const DAY: u64 = 24 * 60 * 60;
+    const POLICY: SweepPolicy = SweepPolicy {
+        quarantine_secs: DAY,
+        idle_secs: 30 * DAY,
+    };
+
+    fn community(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    fn candidate(group_id: &str, community_id: Uuid, age: u64, members: usize) -> SweepCandidate {
+        SweepCandidate {
+            group_id: group_id.to_string(),
+            community_id,
+            created_at: NOW - age,
+            last_activity: NOW - age,
+            members,
+        }
+    }
+
+    fn selected(orphans: &[Orphan]) -> Vec<(&str, &OrphanReason)> {
+        orphans
+            .iter()
+            .map(|orphan| (orphan.group_id.as_str(), &orphan.reason))
+            .collect()
+    }
+
+    #[test]
+    fn test_duplicates_keep_the_oldest_group_with_members() {
+        let sticker = community(1);
+        let candidates = vec![
+            // The race's loser was created first but never got its creator added
+            candidate("peek-empty", sticker, 3 * DAY, 0),
+            candidate("peek-kept", sticker, 2 * DAY, 4),
+            candidate("peek-late", sticker, 2 * DAY - 60, 1),
+            candidate("peek-solo", community(2), 90 * DAY, 12),
+        ];
+
+        let survivor = OrphanReason::Duplicate {
+            survivor: "peek-kept".to_string(),
+        };
+        assert_eq!(
+            selected(&select_orphans(&candidates, NOW, POLICY)),
+            vec![("peek-empty", &survivor), ("peek-late", &survivor)]
+        );
+    }
+
+    #[test]
+    fn test_duplicates_inside_the_quarantine_are_left_alone() {
+        let sticker = community(1);
+        let candidates = vec![
+            candidate("peek-first", sticker, DAY, 0),
+            candidate("peek-second", sticker, 60, 0),
+        ];
+
+        // With nobody in either, the oldest is kept; the other may still be mid-creation
+        assert!(select_orphans(&candidates, NOW, POLICY).is_empty());
+        assert_eq!(
+            selected(&select_orphans(&candidates, NOW + DAY, POLICY)),
+            vec![(
+                "peek-second",
+                &OrphanReason::Duplicate {
+                    survivor: "peek-first".to_string()
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_memberless_groups_are_reaped_after_the_idle_period() {
+        let mut stale = candidate("peek-stale", community(1), 40 * DAY, 0);
+        stale.last_activity = NOW - 30 * DAY;
+        let mut touched = candidate("peek-touched", community(2), 40 * DAY, 0);
+        touched.last_activity = NOW - DAY;
+        let candidates = vec![
+            stale,
+            touched,
+            candidate("peek-quiet", community(3), 400 * DAY, 1),
+        ];
+
+        assert_eq!(
+            selected(&select_orphans(&candidates, NOW, POLICY)),
+            vec![("peek-stale", &OrphanReason::Abandoned)]
+        );
+    }
+
+    #[test]
+    fn test_candidates_come_from_relay_signed_events() {
+        let relay = Keys::generate();
+        let member = Keys::generate().public_key();
+        let protocol = ProtocolConfig::default();
+        let sticker = community(7);
+        let at = |secs: u64| Timestamp::from(secs);
+
+        let group_metadata = |group_id: &str, created_at: u64, uuid_tag: &str| {
+            EventBuilder::new(Kind::from(39000), "")
+                .tags([
+                    Tag::identifier(group_id),
+                    Tag::custom(
+                        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+                        [uuid_tag],
+                    ),
+                ])
+                .custom_created_at(at(created_at))
+                .sign_with_keys(&relay)
+                .unwrap()
+        };
+        let uuid_tag = protocol.uuid_tag(&sticker);
+        let metadata = vec![
+            group_metadata("peek-a", NOW - DAY, &uuid_tag),
+            group_metadata("peek-b", NOW - 2 * DAY, &uuid_tag),
+            group_metadata("other-c", NOW, &uuid_tag),
+            group_metadata("peek-d", NOW, "elsewhere:uuid:1"),
+        ];
+        let members = vec![EventBuilder::new(Kind::from(39002), "")
+            .tags([
+                Tag::identifier("peek-a"),
+                Tag::public_key(relay.public_key()),
+                Tag::public_key(member),
+            ])
+            .custom_created_at(at(NOW - 60))
+            .sign_with_keys(&relay)
+            .unwrap()];
+        let creations = vec![EventBuilder::new(Kind::from(9007), "")
+            .tags([Tag::custom(TagKind::Custom("h".into()), ["peek-a"])])
+            .custom_created_at(at(NOW - 5 * DAY))
+            .sign_with_keys(&relay)
+            .unwrap()];
+
+        let candidates = sweep_candidates(
+            &metadata,
+            &members,
+            &creations,
+            &relay.public_key(),
+            &protocol,
+        );
+        assert_eq!(
+            candidates,
+            vec![
+                SweepCandidate {
+                    group_id: "peek-a".to_string(),
+                    community_id: sticker,
+                    created_at: NOW - 5 * DAY,
+                    last_activity: NOW - 60,
+                    members: 1,
+                },
+                SweepCandidate {
+                    group_id: "peek-b".to_string(),
+                    community_id: sticker,
+                    created_at: NOW - 2 * DAY,
+                    last_activity: NOW - 2 * DAY,
+                    members: 0,
+                },
+            ]
+        );
+    }
+
+    struct FakeRelay {
+        candidates: Vec<SweepCandidate>,
+        deleted: Mutex<Vec<String>>,
+    }
+
+    impl OrphanSource for Arc<FakeRelay> {
+        async fn sweep_candidates(&self) -> anyhow::Result<Vec<SweepCandidate>> {
+            Ok(self.candidates.clone())
+        }
+
+        async fn delete_group(
+            &self,
+            group_id: &str,
+            plan: &mut MutationPlan,
+        ) -> anyhow::Result<()> {
+            plan.events.push(PlannedEvent {
+                kind: 9008,
+                group_id: Some(group_id.to_string()),
+                d_tag: None,
+                pubkeys: Vec::new(),
+            });
+            if !plan.dry_run {
+                self.deleted.lock().unwrap().push(group_id.to_string());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_plans_deletions_without_sending() {
+        let relay = Arc::new(FakeRelay {
+            candidates: vec![
+                candidate("peek-kept", community(1), 3 * DAY, 2),
+                candidate("peek-dup", community(1), 2 * DAY, 0),
+            ],
+            deleted: Mutex::new(Vec::new()),
+        });
+        let sweep = OrphanSweep::new(relay.clone(), Arc::new(ManualClock::new(NOW)), POLICY);
+
+        let dry = sweep.run_with(ExecutionMode::DryRun).await.unwrap();
+        assert!(dry.plan.dry_run);
+        assert_eq!(dry.plan.events[0].kind, 9008);
+        assert_eq!(dry.report.scanned, 2);
+        assert_eq!(selected(&dry.report.orphans)[0].0, "peek-dup");
+        assert!(relay.deleted.lock().unwrap().is_empty());
+
+        let before = metrics::get(
+            "peek_orphan_groups_deleted_total",
+            &[("reason", "duplicate")],
+        );
+        sweep.run_with(ExecutionMode::Execute).await.unwrap();
+        assert_eq!(*relay.deleted.lock().unwrap(), vec!["peek-dup".to_string()]);
+        assert_eq!(
+            metrics::get(
+                "peek_orphan_groups_deleted_total",
+                &[("reason", "duplicate")]
+            ),
+            before + 1
+        );
+    }
+}
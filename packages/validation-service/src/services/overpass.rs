@@ -1,7 +1,10 @@
 use anyhow::{anyhow, Result};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 
+use crate::libraries::sanitize::sanitize_automated_name;
+
 /// Overpass API response structure
 #[derive(Debug, Deserialize, Serialize)]
 struct OverpassResponse {
@@ -25,11 +28,27 @@ struct OverpassTags {
     shop: Option<String>,
     #[serde(rename = "building")]
     building_type: Option<String>,
+    opening_hours: Option<String>,
+}
+
+/// The closest named place to a point
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Place {
+    pub name: String,
+    /// The place's raw OSM opening_hours value, if tagged
+    pub opening_hours: Option<String>,
 }
 
 /// Query Overpass API for the nearest named place
 /// Returns the name of the closest POI/amenity within 25m radius
 pub async fn get_place_name(latitude: f64, longitude: f64) -> Result<Option<String>> {
+    Ok(get_place(latitude, longitude)
+        .await?
+        .map(|place| place.name))
+}
+
+/// Query Overpass API for the closest POI/amenity within 25m radius, with its opening hours
+pub async fn get_place(latitude: f64, longitude: f64) -> Result<Option<Place>> {
     // Overpass query: find amenities, shops, or buildings with names within 25m
     let query = format!(
         r#"[out:json][timeout:15];
@@ -81,10 +100,19 @@ out center 1;"#,
         .map_err(|e| anyhow!("Failed to parse Overpass response: {}", e))?;
 
     // Find the closest element with a name
-    if let Some(element) = data.elements.first() {
-        if let Some(name) = &element.tags.name {
+    // POI names are third-party text: strip control/bidi characters and truncate
+    if let Some(element) = data.elements.into_iter().next() {
+        if let Some(name) = element
+            .tags
+            .name
+            .as_deref()
+            .and_then(sanitize_automated_name)
+        {
             tracing::info!("✅ Found place name from Overpass: {}", name);
-            return Ok(Some(name.clone()));
+            return Ok(Some(Place {
+                name,
+                opening_hours: element.tags.opening_hours,
+            }));
         }
     }
 
@@ -93,10 +121,194 @@ out center 1;"#,
     Ok(None)
 }
 
+#[derive(Debug, Deserialize)]
+struct AreaResponse {
+    elements: Vec<AreaElement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AreaElement {
+    #[serde(default)]
+    tags: AreaTags,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct AreaTags {
+    name: Option<String>,
+    place: Option<String>,
+    admin_level: Option<String>,
+    timezone: Option<String>,
+}
+
+/// Query Overpass API for the neighborhood and city containing a point
+/// Returns e.g. "Mission District, San Francisco", or just the city when there is no neighborhood
+pub async fn get_locality(latitude: f64, longitude: f64) -> Result<Option<String>> {
+    let query = format!(
+        r#"[out:json][timeout:15];
+is_in({},{})->.a;
+(
+  area.a["place"~"^(neighbourhood|quarter|suburb|village|town|city)$"];
+  area.a["boundary"="administrative"]["admin_level"="8"];
+);
+out tags;"#,
+        latitude, longitude
+    );
+
+    tracing::info!(
+        "🌍 Querying Overpass API for locality at ({}, {})",
+        latitude,
+        longitude
+    );
+
+    let client = reqwest::Client::builder()
+        .user_agent("Peek/0.1.0 (https://github.com/verse-pbc/peek; noreply@verse.app)")
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .map_err(|e| anyhow!("Failed to build HTTP client: {}", e))?;
+
+    let response = client
+        .post("https://overpass-api.de/api/interpreter")
+        .body(query)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Overpass API request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Overpass API returned error: {}",
+            response.status()
+        ));
+    }
+
+    let data: AreaResponse = response
+        .json()
+        .await
+        .map_err(|e| anyhow!("Failed to parse Overpass response: {}", e))?;
+
+    let areas: Vec<AreaTags> = data.elements.into_iter().map(|e| e.tags).collect();
+    Ok(format_locality(&areas))
+}
+
+/// Query Overpass API for the IANA timezone of the areas containing a point
+pub async fn get_timezone(latitude: f64, longitude: f64) -> Result<Option<Tz>> {
+    let query = format!(
+        r#"[out:json][timeout:15];
+is_in({},{})->.a;
+area.a["timezone"];
+out tags;"#,
+        latitude, longitude
+    );
+
+    let client = reqwest::Client::builder()
+        .user_agent("Peek/0.1.0 (https://github.com/verse-pbc/peek; noreply@verse.app)")
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .map_err(|e| anyhow!("Failed to build HTTP client: {}", e))?;
+
+    let response = client
+        .post("https://overpass-api.de/api/interpreter")
+        .body(query)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Overpass API request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Overpass API returned error: {}",
+            response.status()
+        ));
+    }
+
+    let data: AreaResponse = response
+        .json()
+        .await
+        .map_err(|e| anyhow!("Failed to parse Overpass response: {}", e))?;
+
+    let areas: Vec<AreaTags> = data.elements.into_iter().map(|e| e.tags).collect();
+    Ok(area_timezone(&areas))
+}
+
+/// The first area timezone that names an IANA zone
+fn area_timezone(areas: &[AreaTags]) -> Option<Tz> {
+    areas
+        .iter()
+        .find_map(|area| area.timezone.as_deref()?.parse().ok())
+}
+
+/// Sanitized name of the first area whose place tag is one of `kinds`
+fn place_named(areas: &[AreaTags], kinds: &[&str]) -> Option<String> {
+    areas
+        .iter()
+        .filter(|area| area.place.as_deref().is_some_and(|p| kinds.contains(&p)))
+        .find_map(|area| area.name.as_deref().and_then(sanitize_automated_name))
+}
+
+/// "Neighborhood, City" from the areas containing a point, most specific neighborhood first
+fn format_locality(areas: &[AreaTags]) -> Option<String> {
+    let neighborhood = place_named(areas, &["neighbourhood"])
+        .or_else(|| place_named(areas, &["quarter"]))
+        .or_else(|| place_named(areas, &["suburb"]));
+    let city = place_named(areas, &["city", "town", "village"]).or_else(|| {
+        areas
+            .iter()
+            .filter(|area| area.admin_level.as_deref() == Some("8"))
+            .find_map(|area| area.name.as_deref().and_then(sanitize_automated_name))
+    });
+
+    match (neighborhood, city) {
+        (Some(neighborhood), Some(city)) if neighborhood != city => {
+            Some(format!("{}, {}", neighborhood, city))
+        }
+        (neighborhood, city) => city.or(neighborhood),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn area(name: &str, place: Option<&str>, admin_level: Option<&str>) -> AreaTags {
+        AreaTags {
+            name: Some(name.to_string()),
+            place: place.map(str::to_string),
+            admin_level: admin_level.map(str::to_string),
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn test_locality_combines_neighborhood_and_city() {
+        let areas = [
+            area("California", None, Some("4")),
+            area("San Francisco", Some("city"), Some("8")),
+            area("Mission District", Some("suburb"), None),
+        ];
+        assert_eq!(
+            format_locality(&areas).as_deref(),
+            Some("Mission District, San Francisco")
+        );
+
+        // Falls back to the municipality, and to nothing without one
+        let rural = [area("Hemsedal", None, Some("8"))];
+        assert_eq!(format_locality(&rural).as_deref(), Some("Hemsedal"));
+        assert_eq!(format_locality(&[]), None);
+    }
+
+    #[test]
+    fn test_timezone_comes_from_an_area_naming_a_known_zone() {
+        let zoned = |timezone: &str| AreaTags {
+            timezone: Some(timezone.to_string()),
+            ..area("Zone", None, None)
+        };
+        let areas = [
+            area("Berlin", Some("city"), Some("4")),
+            zoned("Europe/Atlantis"),
+            zoned("Europe/Berlin"),
+        ];
+        assert_eq!(area_timezone(&areas), Some(chrono_tz::Europe::Berlin));
+        assert_eq!(area_timezone(&areas[..2]), None);
+    }
+
     #[tokio::test]
     #[ignore] // Ignore by default as it requires network
     async fn test_get_place_name() {
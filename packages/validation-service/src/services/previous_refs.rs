@@ -0,0 +1,151 @@
+//! NIP-29 "previous" references on the group management events the service signs
+//!
+//! Relays following later NIP-29 revisions expect each event sent to a group to reference a
+//! few of the group's recent events by the first 8 hex characters of their ids, so events
+//! replayed out of order or into another group stand out. The ids come from h-tagged events
+//! the service already reads from or sends to the relay. A brand-new group has no history
+//! yet, so its first events go out without the tag.
+
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::libraries::intern;
+
+pub const PREVIOUS_TAG: &str = "previous";
+
+// Leading hex characters of an event id carried in a previous tag
+const PREVIOUS_ID_CHARS: usize = 8;
+
+// Groups tracked before the one with the stalest history is dropped
+const MAX_TRACKED_GROUPS: usize = 10_000;
+
+pub struct PreviousRefs {
+    max_refs: usize,
+    // Most recent event ids per interned group id, newest first
+    recent: Mutex<HashMap<Arc<str>, Vec<(Timestamp, EventId)>>>,
+}
+
+impl PreviousRefs {
+    /// Reference up to `max_refs` recent events on each management event; 0 sends none
+    pub fn new(max_refs: usize) -> Self {
+        Self {
+            max_refs,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remember a group event read from or accepted by the relay; events without an h tag
+    /// belong to no group and are ignored
+    pub fn observe(&self, event: &Event) {
+        if self.max_refs == 0 {
+            return;
+        }
+        let Some(group_id) = group_of(event) else {
+            return;
+        };
+        let mut recent = self.recent.lock().unwrap();
+        if !recent.contains_key(group_id) {
+            if recent.len() >= MAX_TRACKED_GROUPS {
+                let stalest = recent
+                    .iter()
+                    .min_by_key(|(_, ids)| ids.first().map(|(at, _)| *at))
+                    .map(|(group_id, _)| group_id.clone());
+                if let Some(stalest) = stalest {
+                    recent.remove(&stalest);
+                }
+            }
+            recent.insert(intern::shared().intern(group_id), Vec::new());
+        }
+        let Some(ids) = recent.get_mut(group_id) else {
+            return;
+        };
+        if ids.iter().any(|(_, id)| *id == event.id) {
+            return;
+        }
+        ids.push((event.created_at, event.id));
+        ids.sort_by(|a, b| b.0.cmp(&a.0));
+        ids.truncate(self.max_refs);
+    }
+
+    /// The previous tag for the next event sent to the group, or None while it has no history
+    pub fn tag(&self, group_id: &str) -> Option<Tag> {
+        let recent = self.recent.lock().unwrap();
+        let ids = recent.get(group_id).filter(|ids| !ids.is_empty())?;
+        Some(Tag::custom(
+            TagKind::Custom(PREVIOUS_TAG.into()),
+            ids.iter()
+                .map(|(_, id)| id.to_hex()[..PREVIOUS_ID_CHARS].to_string()),
+        ))
+    }
+
+    /// `builder` with the group's previous tag added, when there is one
+    pub fn attach(&self, group_id: &str, builder: EventBuilder) -> EventBuilder {
+        match self.tag(group_id) {
+            Some(tag) => builder.tag(tag),
+            None => builder,
+        }
+    }
+}
+
+fn group_of(event: &Event) -> Option<&str> {
+    event
+        .tags
+        .iter()
+        .find(|tag| matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::H))
+        .and_then(|tag| tag.content())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group_event(group_id: &str, at: u64, keys: &Keys) -> Event {
+        EventBuilder::new(Kind::from(9), "hi")
+            .tags([Tag::custom(TagKind::Custom("h".into()), [group_id])])
+            .custom_created_at(Timestamp::from(at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    fn previous_values(refs: &PreviousRefs, group_id: &str) -> Option<Vec<String>> {
+        refs.tag(group_id).map(|tag| tag.as_slice()[1..].to_vec())
+    }
+
+    #[test]
+    fn test_newest_ids_are_kept_per_group_and_truncated() {
+        let refs = PreviousRefs::new(2);
+        let keys = Keys::generate();
+        let events: Vec<Event> = [1_760_000_100, 1_760_000_300, 1_760_000_200]
+            .into_iter()
+            .map(|at| group_event("peek-abc123", at, &keys))
+            .collect();
+        for event in &events {
+            refs.observe(event);
+        }
+        // Seen twice, still referenced once
+        refs.observe(&events[1]);
+
+        let short = |event: &Event| event.id.to_hex()[..8].to_string();
+        assert_eq!(
+            previous_values(&refs, "peek-abc123"),
+            Some(vec![short(&events[1]), short(&events[2])])
+        );
+        assert_eq!(previous_values(&refs, "peek-other"), None);
+    }
+
+    #[test]
+    fn test_zero_disables_and_groupless_events_are_ignored() {
+        let keys = Keys::generate();
+        let disabled = PreviousRefs::new(0);
+        disabled.observe(&group_event("peek-abc123", 1_760_000_000, &keys));
+        assert!(disabled.tag("peek-abc123").is_none());
+
+        let refs = PreviousRefs::new(3);
+        let note = EventBuilder::text_note("no group")
+            .sign_with_keys(&keys)
+            .unwrap();
+        refs.observe(&note);
+        assert!(refs.recent.lock().unwrap().is_empty());
+    }
+}
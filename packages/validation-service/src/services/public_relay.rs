@@ -0,0 +1,253 @@
+//! Startup check that the relay URL handed to clients actually serves the service's groups
+//!
+//! Location validation tells clients to connect to `public_relay_url`. A typo there, or a
+//! proxy pointing at the wrong relay, sends every new member somewhere their group does not
+//! exist, so the URL is probed once at startup and the outcome is reported on /health.
+//! Groups living on another relay name it in a `relay` metadata tag, which wins over the URL.
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use super::metrics;
+
+/// Metadata tag naming the relay clients should use for this group instead of the global one
+pub const RELAY_TAG: &str = "relay";
+
+/// How long the startup probe waits for the public relay
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A usable relay override from a `relay` tag value; anything but a ws(s) URL is ignored
+pub fn relay_override(value: &str) -> Option<String> {
+    let value = value.trim();
+    let host = value
+        .strip_prefix("wss://")
+        .or_else(|| value.strip_prefix("ws://"))?;
+    (!host.is_empty() && !host.contains(char::is_whitespace)).then(|| value.to_string())
+}
+
+/// Fetches events through a relay URL the way a client would; a fresh connection in production
+pub trait PublicRelayProbe: Send + Sync {
+    fn fetch(
+        &self,
+        relay_url: &str,
+        filter: Filter,
+    ) -> impl Future<Output = Result<Vec<Event>, String>> + Send;
+}
+
+/// Probe connecting with the relay admin keys, so private group metadata is readable after AUTH
+pub struct NostrProbe {
+    keys: Keys,
+}
+
+impl NostrProbe {
+    pub fn new(keys: Keys) -> Self {
+        Self { keys }
+    }
+}
+
+impl PublicRelayProbe for NostrProbe {
+    async fn fetch(&self, relay_url: &str, filter: Filter) -> Result<Vec<Event>, String> {
+        let client = Client::new(self.keys.clone());
+        client.automatic_authentication(true);
+        client
+            .add_relay(relay_url)
+            .await
+            .map_err(|e| e.to_string())?;
+        client.connect().await;
+
+        let events = client.fetch_events(filter, PROBE_TIMEOUT).await;
+        // An unreachable relay answers an empty result rather than an error
+        let connected = match client.relay(relay_url).await {
+            Ok(relay) => relay.is_connected(),
+            Err(_) => false,
+        };
+        client.disconnect().await;
+
+        if !connected {
+            return Err("could not connect".to_string());
+        }
+        events
+            .map(|events| events.into_iter().collect())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Outcome of the startup probe of the public relay URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum PublicRelayStatus {
+    /// The sentinel group's metadata came back through the public URL
+    Verified { sentinel_group: String },
+    /// The relay answered, but no sentinel group is configured to prove it serves ours
+    Reachable,
+    /// Clients are likely being sent somewhere their group does not exist
+    Degraded { reason: String },
+}
+
+impl PublicRelayStatus {
+    pub fn is_degraded(&self) -> bool {
+        matches!(self, Self::Degraded { .. })
+    }
+}
+
+/// Check that `relay_url` is reachable and, if a sentinel group is named, that it serves it
+pub async fn verify_public_relay<P: PublicRelayProbe>(
+    probe: &P,
+    relay_url: &str,
+    relay_pubkey: &PublicKey,
+    sentinel_group: Option<&str>,
+) -> PublicRelayStatus {
+    let mut filter = Filter::new()
+        .kind(Kind::from(39000))
+        .author(*relay_pubkey)
+        .limit(1);
+    if let Some(group_id) = sentinel_group {
+        filter = filter.identifier(group_id);
+    }
+
+    let status = match (probe.fetch(relay_url, filter).await, sentinel_group) {
+        (Err(e), _) => PublicRelayStatus::Degraded {
+            reason: format!("{} is unreachable: {}", relay_url, e),
+        },
+        (Ok(_), None) => PublicRelayStatus::Reachable,
+        (Ok(events), Some(group_id))
+            if events.iter().any(|event| {
+                event.pubkey == *relay_pubkey && event.tags.identifier() == Some(group_id)
+            }) =>
+        {
+            PublicRelayStatus::Verified {
+                sentinel_group: group_id.to_string(),
+            }
+        }
+        (Ok(_), Some(group_id)) => PublicRelayStatus::Degraded {
+            reason: format!(
+                "{} does not serve the metadata of sentinel group {}",
+                relay_url, group_id
+            ),
+        },
+    };
+    metrics::set_gauge(
+        "peek_public_relay_degraded",
+        &[],
+        u64::from(status.is_degraded()),
+    );
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::relay::GroupMetadata;
+
+    /// Relay serving fixed group metadata whatever the filter, or refusing connections
+    struct FakeRelay {
+        groups: Vec<Event>,
+        reachable: bool,
+    }
+
+    impl PublicRelayProbe for FakeRelay {
+        async fn fetch(&self, _relay_url: &str, _filter: Filter) -> Result<Vec<Event>, String> {
+            if !self.reachable {
+                return Err("could not connect".to_string());
+            }
+            Ok(self.groups.clone())
+        }
+    }
+
+    fn group_event(keys: &Keys, group_id: &str, extra: Vec<Tag>) -> Event {
+        EventBuilder::new(Kind::from(39000), "")
+            .tag(Tag::identifier(group_id))
+            .tags(extra)
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_or_foreign_public_relay_marks_degraded() {
+        let relay = Keys::generate();
+        let url = "wss://peek.example";
+
+        let unreachable = FakeRelay {
+            groups: vec![group_event(&relay, "peek-sentinel", vec![])],
+            reachable: false,
+        };
+        let status = verify_public_relay(
+            &unreachable,
+            url,
+            &relay.public_key(),
+            Some("peek-sentinel"),
+        )
+        .await;
+        assert!(status.is_degraded());
+
+        // Reachable, but it is some other relay without our groups
+        let foreign = FakeRelay {
+            groups: vec![group_event(&Keys::generate(), "peek-sentinel", vec![])],
+            reachable: true,
+        };
+        let status =
+            verify_public_relay(&foreign, url, &relay.public_key(), Some("peek-sentinel")).await;
+        assert_eq!(
+            status,
+            PublicRelayStatus::Degraded {
+                reason:
+                    "wss://peek.example does not serve the metadata of sentinel group peek-sentinel"
+                        .to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sentinel_group_served_verifies_public_relay() {
+        let relay = Keys::generate();
+        let probe = FakeRelay {
+            groups: vec![group_event(&relay, "peek-sentinel", vec![])],
+            reachable: true,
+        };
+
+        let status = verify_public_relay(
+            &probe,
+            "wss://peek.example",
+            &relay.public_key(),
+            Some("peek-sentinel"),
+        )
+        .await;
+        assert_eq!(
+            status,
+            PublicRelayStatus::Verified {
+                sentinel_group: "peek-sentinel".to_string()
+            }
+        );
+
+        let status =
+            verify_public_relay(&probe, "wss://peek.example", &relay.public_key(), None).await;
+        assert_eq!(status, PublicRelayStatus::Reachable);
+    }
+
+    #[test]
+    fn test_relay_tag_overrides_only_with_a_websocket_url() {
+        let keys = Keys::generate();
+        let metadata = |value: &str| {
+            let event = group_event(
+                &keys,
+                "peek-abc123",
+                vec![Tag::custom(TagKind::Custom(RELAY_TAG.into()), [value])],
+            );
+            GroupMetadata::from_event(&event, 1).relay
+        };
+
+        assert_eq!(
+            metadata("wss://eu.peek.example"),
+            Some("wss://eu.peek.example".to_string())
+        );
+        assert_eq!(metadata("https://eu.peek.example"), None);
+        assert_eq!(metadata("not a url"), None);
+        assert_eq!(
+            GroupMetadata::from_event(&group_event(&keys, "peek-abc123", vec![]), 1).relay,
+            None
+        );
+    }
+}
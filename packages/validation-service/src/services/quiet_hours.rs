@@ -0,0 +1,435 @@
+//! Per-community quiet hours for notifications
+//!
+//! Admins may set a nightly window, with start and end in local time plus an IANA timezone.
+//! Routine pushes generated inside it are held instead of sent, and go out together when the
+//! window ends, so a venue's members and admins are not woken at 3am by join announcements.
+//! Mentions and moderation alerts are never held, since someone may need to act on them.
+//!
+//! Windows are evaluated in the community's timezone with chrono-tz, so they follow DST. A
+//! 22:00-07:00 window ends at 07:00 local time on both sides of a clock change. If a
+//! spring-forward gap skips the end time, the window ends at the first local time after the gap.
+
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use utoipa::{openapi, PartialSchema, ToSchema};
+
+use crate::libraries::clock::Clock;
+
+/// Group metadata tag holding a community's quiet hours: start, end and timezone
+pub const QUIET_HOURS_TAG: &str = "quiet_hours";
+
+/// Most pushes held at once across all communities; beyond this they are sent right away
+pub const MAX_HELD_PUSHES: usize = 10_000;
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// A daily window, in a community's local time, during which routine pushes wait
+///
+/// On the wire and in metadata it is `{"start": "22:00", "end": "07:00", "timezone":
+/// "Europe/Berlin"}`. A window whose start equals its end is empty; admins send one to turn
+/// quiet hours off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "QuietHoursFields", into = "QuietHoursFields")]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+    timezone: Tz,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, ToSchema)]
+#[schemars(rename = "QuietHours")]
+struct QuietHoursFields {
+    /// Local time the window opens, as HH:MM
+    start: String,
+    /// Local time the window closes, as HH:MM; equal to start to turn quiet hours off
+    end: String,
+    /// IANA timezone name, such as Europe/Berlin
+    timezone: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidQuietHours {
+    #[error("{0:?} is not a HH:MM time")]
+    Time(String),
+    #[error("{0:?} is not an IANA timezone")]
+    Timezone(String),
+}
+
+impl QuietHours {
+    pub fn parse(start: &str, end: &str, timezone: &str) -> Result<Self, InvalidQuietHours> {
+        let time = |raw: &str| {
+            NaiveTime::parse_from_str(raw.trim(), TIME_FORMAT)
+                .map_err(|_| InvalidQuietHours::Time(raw.to_string()))
+        };
+        Ok(Self {
+            start: time(start)?,
+            end: time(end)?,
+            timezone: timezone
+                .trim()
+                .parse()
+                .map_err(|_| InvalidQuietHours::Timezone(timezone.to_string()))?,
+        })
+    }
+
+    /// The values of a quiet_hours metadata tag, after the tag name
+    pub fn tag_values(&self) -> [String; 3] {
+        [
+            self.start.format(TIME_FORMAT).to_string(),
+            self.end.format(TIME_FORMAT).to_string(),
+            self.timezone.name().to_string(),
+        ]
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    /// Whether the window is empty, which is how quiet hours are turned off
+    pub fn is_off(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Whether the unix time `now` falls inside the window
+    pub fn contains(&self, now: u64) -> bool {
+        let local = self.local(now).time();
+        if self.start < self.end {
+            self.start <= local && local < self.end
+        } else if self.start > self.end {
+            // The window runs past midnight
+            local >= self.start || local < self.end
+        } else {
+            false
+        }
+    }
+
+    /// Unix time of the first end of the window after `now`
+    pub fn ends_after(&self, now: u64) -> u64 {
+        let local = self.local(now).naive_local();
+        let mut date = local.date();
+        if local.time() >= self.end {
+            date = date.succ_opt().unwrap_or(date);
+        }
+        let end = self.first_instant_at_or_after(date.and_time(self.end));
+        end.timestamp().max(0) as u64
+    }
+
+    fn local(&self, now: u64) -> DateTime<Tz> {
+        let utc = DateTime::<Utc>::from_timestamp(now as i64, 0).unwrap_or_default();
+        utc.with_timezone(&self.timezone)
+    }
+
+    /// The instant the local clock reads `local`, or first reads a later time if a
+    /// spring-forward gap skips it; an hour read twice at fall-back counts the first time
+    fn first_instant_at_or_after(&self, local: NaiveDateTime) -> DateTime<Tz> {
+        (0..=24 * 60)
+            .find_map(|minutes| {
+                match self
+                    .timezone
+                    .from_local_datetime(&(local + Duration::minutes(minutes)))
+                {
+                    LocalResult::Single(instant) | LocalResult::Ambiguous(instant, _) => {
+                        Some(instant)
+                    }
+                    LocalResult::None => None,
+                }
+            })
+            .unwrap_or_else(|| self.timezone.from_utc_datetime(&local))
+    }
+}
+
+impl TryFrom<QuietHoursFields> for QuietHours {
+    type Error = InvalidQuietHours;
+
+    fn try_from(fields: QuietHoursFields) -> Result<Self, Self::Error> {
+        Self::parse(&fields.start, &fields.end, &fields.timezone)
+    }
+}
+
+// The wire form is the fields struct, which the derive cannot see through try_from/into
+impl JsonSchema for QuietHours {
+    fn schema_name() -> String {
+        QuietHoursFields::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        QuietHoursFields::json_schema(gen)
+    }
+}
+
+// Likewise for the OpenAPI description of the HTTP API
+impl PartialSchema for QuietHours {
+    fn schema() -> openapi::RefOr<openapi::schema::Schema> {
+        QuietHoursFields::schema()
+    }
+}
+
+impl ToSchema for QuietHours {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("QuietHours")
+    }
+}
+
+impl From<QuietHours> for QuietHoursFields {
+    fn from(quiet_hours: QuietHours) -> Self {
+        let [start, end, timezone] = quiet_hours.tag_values();
+        Self {
+            start,
+            end,
+            timezone,
+        }
+    }
+}
+
+/// What a push is about, which decides whether quiet hours may hold it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushKind {
+    /// Announcements and reminders, such as new members or inactivity warnings
+    Routine,
+    /// The recipient was mentioned; no push mentions anyone yet, but one that does is not held
+    #[allow(dead_code)]
+    Mention,
+    /// Something an admin may need to act on, such as a join request awaiting approval
+    Moderation,
+}
+
+impl PushKind {
+    pub fn bypasses_quiet_hours(&self) -> bool {
+        !matches!(self, Self::Routine)
+    }
+}
+
+struct HeldPush<T> {
+    release_at: u64,
+    push: T,
+}
+
+/// Pushes generated during their community's quiet hours, waiting for the window to end
+pub struct QuietHoursQueue<T> {
+    capacity: usize,
+    clock: Arc<dyn Clock>,
+    held: Mutex<Vec<HeldPush<T>>>,
+}
+
+impl<T> QuietHoursQueue<T> {
+    pub fn new(capacity: usize, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            capacity,
+            clock,
+            held: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Hold a routine `push` generated inside `quiet_hours` until the window ends
+    ///
+    /// Anything else is handed back to be sent now: pushes that bypass quiet hours, pushes
+    /// outside the window, and pushes arriving while the queue is full.
+    pub fn hold(&self, quiet_hours: Option<&QuietHours>, kind: PushKind, push: T) -> Option<T> {
+        let now = self.clock.now_unix();
+        let Some(quiet_hours) = quiet_hours.filter(|quiet_hours| quiet_hours.contains(now)) else {
+            return Some(push);
+        };
+        if kind.bypasses_quiet_hours() {
+            return Some(push);
+        }
+        let mut held = self.held.lock().unwrap();
+        if held.len() >= self.capacity {
+            return Some(push);
+        }
+        held.push(HeldPush {
+            release_at: quiet_hours.ends_after(now),
+            push,
+        });
+        None
+    }
+
+    /// Remove and return the pushes whose window has ended, in the order they were held
+    pub fn take_due(&self) -> Vec<T> {
+        let now = self.clock.now_unix();
+        let mut held = self.held.lock().unwrap();
+        let (due, waiting) = std::mem::take(&mut *held)
+            .into_iter()
+            .partition::<Vec<_>, _>(|held| now >= held.release_at);
+        *held = waiting;
+        due.into_iter().map(|held| held.push).collect()
+    }
+
+    pub fn held_count(&self) -> usize {
+        self.held.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+
+    // 2026-07-15 23:00 UTC, 01:00 in Berlin (CEST)
+    const SUMMER_NIGHT: u64 = 1_784_156_400;
+    // 2026-07-16 05:00 UTC, 07:00 in Berlin
+    const SUMMER_MORNING: u64 = 1_784_178_000;
+
+    fn berlin(start: &str, end: &str) -> QuietHours {
+        QuietHours::parse(start, end, "Europe/Berlin").unwrap()
+    }
+
+    fn queue(now: u64) -> (QuietHoursQueue<&'static str>, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(now));
+        (QuietHoursQueue::new(MAX_HELD_PUSHES, clock.clone()), clock)
+    }
+
+    #[test]
+    fn test_routine_pushes_inside_the_window_are_held() {
+        let quiet = berlin("22:00", "07:00");
+        let (queue, _) = queue(SUMMER_NIGHT);
+
+        assert_eq!(queue.hold(Some(&quiet), PushKind::Routine, "joined"), None);
+        assert_eq!(queue.hold(Some(&quiet), PushKind::Routine, "warning"), None);
+        assert_eq!(queue.held_count(), 2);
+        assert!(queue.take_due().is_empty());
+
+        // Communities without quiet hours are never held
+        assert_eq!(
+            queue.hold(None, PushKind::Routine, "elsewhere"),
+            Some("elsewhere")
+        );
+        assert_eq!(queue.held_count(), 2);
+    }
+
+    #[test]
+    fn test_held_pushes_are_flushed_when_the_window_ends() {
+        let quiet = berlin("22:00", "07:00");
+        let (queue, clock) = queue(SUMMER_NIGHT);
+        queue.hold(Some(&quiet), PushKind::Routine, "first");
+        clock.advance(3_600);
+        queue.hold(Some(&quiet), PushKind::Routine, "second");
+
+        clock.set(SUMMER_MORNING - 1);
+        assert!(queue.take_due().is_empty());
+        clock.set(SUMMER_MORNING);
+        assert_eq!(queue.take_due(), vec!["first", "second"]);
+        assert_eq!(queue.held_count(), 0);
+
+        // After the window, pushes go straight out again
+        assert_eq!(
+            queue.hold(Some(&quiet), PushKind::Routine, "morning"),
+            Some("morning")
+        );
+    }
+
+    #[test]
+    fn test_mentions_and_moderation_alerts_bypass_the_window() {
+        let quiet = berlin("22:00", "07:00");
+        let (queue, _) = queue(SUMMER_NIGHT);
+
+        assert_eq!(
+            queue.hold(Some(&quiet), PushKind::Mention, "mention"),
+            Some("mention")
+        );
+        assert_eq!(
+            queue.hold(Some(&quiet), PushKind::Moderation, "join request"),
+            Some("join request")
+        );
+        assert_eq!(queue.held_count(), 0);
+    }
+
+    #[test]
+    fn test_full_queue_sends_instead_of_dropping() {
+        let quiet = berlin("22:00", "07:00");
+        let clock = Arc::new(ManualClock::new(SUMMER_NIGHT));
+        let queue = QuietHoursQueue::new(1, clock);
+        assert_eq!(queue.hold(Some(&quiet), PushKind::Routine, "held"), None);
+        assert_eq!(
+            queue.hold(Some(&quiet), PushKind::Routine, "overflow"),
+            Some("overflow")
+        );
+    }
+
+    #[test]
+    fn test_window_follows_the_spring_forward_transition() {
+        let quiet = berlin("22:00", "07:00");
+
+        // Friday night ends at 07:00 CET, 06:00 UTC
+        let friday = 1_774_647_000; // 2026-03-27 21:30 UTC, 22:30 CET
+        assert!(quiet.contains(friday));
+        assert_eq!(quiet.ends_after(friday), 1_774_677_600);
+
+        // Clocks go forward at 02:00 on Sunday 29 March, so that night ends at 07:00 CEST,
+        // 05:00 UTC, an hour earlier in UTC than a fixed offset would give
+        let saturday = 1_774_733_400; // 2026-03-28 21:30 UTC, 22:30 CET
+        assert!(quiet.contains(saturday));
+        let end = quiet.ends_after(saturday);
+        assert_eq!(end, 1_774_760_400);
+        assert!(quiet.contains(end - 1));
+        assert!(!quiet.contains(end));
+
+        let (queue, clock) = queue(saturday);
+        assert_eq!(queue.hold(Some(&quiet), PushKind::Routine, "joined"), None);
+        clock.set(end - 1);
+        assert!(queue.take_due().is_empty());
+        clock.set(end);
+        assert_eq!(queue.take_due(), vec!["joined"]);
+    }
+
+    #[test]
+    fn test_end_times_skipped_or_repeated_by_dst() {
+        // 02:30 does not exist on 29 March; the window ends when clocks jump to 03:00 CEST
+        let skipped = berlin("23:00", "02:30");
+        let before_gap = 1_774_737_000; // 2026-03-28 22:30 UTC, 23:30 CET
+        assert!(skipped.contains(before_gap));
+        assert_eq!(skipped.ends_after(before_gap), 1_774_746_000); // 01:00 UTC
+
+        // 02:30 happens twice on 25 October; the first one ends the window
+        let repeated = berlin("22:00", "02:30");
+        let before_fall_back = 1_792_881_000; // 2026-10-24 22:30 UTC, 00:30 CEST
+        assert!(repeated.contains(before_fall_back));
+        assert_eq!(repeated.ends_after(before_fall_back), 1_792_888_200); // 00:30 UTC
+    }
+
+    #[test]
+    fn test_daytime_and_empty_windows() {
+        let lunch = QuietHours::parse("12:00", "13:30", "UTC").unwrap();
+        let noon = 1_784_116_800; // 2026-07-15 12:00 UTC
+        assert!(lunch.contains(noon));
+        assert!(!lunch.contains(noon - 1));
+        assert!(!lunch.contains(noon + 90 * 60));
+        assert_eq!(lunch.ends_after(noon), noon + 90 * 60);
+
+        let off = QuietHours::parse("22:00", "22:00", "UTC").unwrap();
+        assert!(off.is_off());
+        assert!(!off.contains(noon));
+        assert!(!berlin("22:00", "07:00").is_off());
+    }
+
+    #[test]
+    fn test_parsing_and_wire_form() {
+        let quiet = berlin("22:00", "07:00");
+        assert_eq!(quiet.tag_values(), ["22:00", "07:00", "Europe/Berlin"]);
+        let json = serde_json::to_string(&quiet).unwrap();
+        assert_eq!(
+            json,
+            r#"{"start":"22:00","end":"07:00","timezone":"Europe/Berlin"}"#
+        );
+        assert_eq!(serde_json::from_str::<QuietHours>(&json).unwrap(), quiet);
+
+        assert_eq!(
+            QuietHours::parse("25:00", "07:00", "UTC"),
+            Err(InvalidQuietHours::Time("25:00".to_string()))
+        );
+        assert_eq!(
+            QuietHours::parse("22:00", "7pm", "UTC"),
+            Err(InvalidQuietHours::Time("7pm".to_string()))
+        );
+        assert_eq!(
+            QuietHours::parse("22:00", "07:00", "Mars/Olympus"),
+            Err(InvalidQuietHours::Timezone("Mars/Olympus".to_string()))
+        );
+        assert!(serde_json::from_str::<QuietHours>(
+            r#"{"start":"22:00","end":"07:00","timezone":"CEST"}"#
+        )
+        .is_err());
+    }
+}
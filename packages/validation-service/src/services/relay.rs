@@ -1,28 +1,75 @@
-use geohash::{encode, Coord};
 use nostr_sdk::prelude::*;
-use rand::Rng;
-use serde::{Deserialize, Serialize};
+use peek_geo::GeohashError;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use uuid::Uuid;
 
-use crate::libraries::display_location::generate_display_location;
+use super::community_labels::{CommunityLabeler, UnlabeledCommunity};
+use super::community_search::{CommunityDiscoveryData, SearchIndex};
+use super::discovery_map::{DiscoveryMapContent, DiscoveryMaps, DISCOVERY_MAP_KIND};
+use super::execution::{EventSender, Execution, ExecutionMode};
+#[cfg(any(debug_assertions, feature = "fault-injection"))]
+use super::fault_injection::FaultInjector;
+use super::in_flight::{InFlight, Outcome};
+use super::inactive_prune::{
+    latest_activity, prune_groups, PruneGroup, INACTIVE_PRUNE_DAYS_TAG, INACTIVE_PRUNE_MIN_DAYS,
+};
+use super::itag_backfill::{backfill_candidates, BackfillCandidate};
+use super::join_notifications::JOIN_NOTIFICATIONS_TAG;
+use super::join_requests::{JoinMode, JOIN_MODE_TAG};
+use super::localities::{LocalityResolver, LOCALITY_LOOKUP_INTERVAL};
+use super::member_set::{merge_member_revisions, reaches_full_revision, MemberSet};
+use super::metadata_extension::{
+    extension_address, extension_d_tag_from_address, restore_overflow, split_metadata_tags,
+    MetadataExtension, EXT_TAG, RULE_TAG, WELCOME_TAG,
+};
+use super::metrics;
+use super::nearby_index::{IndexedCommunity, NearbyIndex};
+use super::orphan_sweep::{sweep_candidates, SweepCandidate};
+use super::previous_refs::PreviousRefs;
+use super::public_relay::{relay_override, RELAY_TAG};
+use super::quiet_hours::{QuietHours, QUIET_HOURS_TAG};
+use super::relay_authorization::RelayKeyAuthorization;
+use super::relay_circuit::RelayCircuit;
+use super::relay_limits::{fetch_paginated, fetch_relay_limits, RelayLimits};
+use super::venue_hours::{venue_timezone, VenueHours, VENUE_HOURS_TAG};
+use crate::libraries::clock::{Clock, SystemClock};
+use crate::libraries::community_id::CommunitySlug;
+use crate::libraries::display_location::DisplayLocationError;
+use crate::libraries::exclusion_zones::display_location_outside;
+use crate::libraries::intern;
+use crate::libraries::relay_url::RelayUrl;
+use crate::libraries::rng::{RngSource, ThreadRngSource};
+use crate::libraries::sanitize::{
+    sanitize_automated_name, sanitize_metadata, InvalidMetadata, MetadataSource, MetadataText,
+};
+use crate::models::{Coordinates, ProtocolConfig};
 
-/// Generate a random group identifier for NIP-29 h-tag
-/// Format: peek-{10 random alphanumeric chars}
-fn generate_random_group_id() -> String {
-    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
-    let mut rng = rand::thread_rng();
-    let id: String = (0..10)
-        .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
-        .collect();
-    format!("peek-{}", id)
-}
+/// How long to wait before re-querying when an empty result may be NIP-42 auth lag
+const AUTH_RETRY_DELAY: Duration = Duration::from_millis(750);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Location {
-    pub latitude: f64,
-    pub longitude: f64,
-}
+/// How long to wait for the relay's OK to a kind 9000 before the outcome is ambiguous
+const MEMBER_ADD_SEND_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Member list polls after an ambiguous kind 9000 send (3 × 650ms ≈ 2s)
+const MEMBERSHIP_POLL_ATTEMPTS: u32 = 3;
+const MEMBERSHIP_POLL_INTERVAL: Duration = Duration::from_millis(650);
+
+/// Kind 39002 revisions asked for per page, and pages read before giving up on older history
+const MEMBER_REVISIONS_PAGE: usize = 20;
+const MEMBER_REVISION_PAGES: usize = 5;
+
+/// How long a group's admin list is reused for display before it is read again
+const ADMIN_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Metadata tag capping how many members a community takes; absent means unlimited
+pub const MAX_MEMBERS_TAG: &str = "max_members";
+
+/// Metadata tag marking a community 18+ so clients can ask for confirmation; absent means not
+/// The service never checks anyone's age, it only carries the venue's choice
+pub const AGE_RESTRICTED_TAG: &str = "age_restricted";
 
 /// NIP-29 Group metadata fetched from relay
 #[derive(Debug, Clone)]
@@ -35,20 +82,377 @@ pub struct GroupMetadata {
     pub is_public: bool,
     pub is_open: bool,
     pub created_at: Timestamp,
-    pub geohash: Option<String>, // Level 8 geohash for actual location
+    pub geohash: Option<String>, // Level 8 geohash for actual location (first anchor)
+    pub anchors: Vec<String>,    // All level 8 anchor geohashes (repeated g tags)
     #[allow(dead_code)]
     pub display_geohash: Option<String>, // Level 9 geohash for display location
+    pub active_until: Option<Timestamp>, // Time-boxed communities stop accepting joins after this
+    pub archived: bool,          // Set once an expired community has been archived
+    pub join_mode: JoinMode,     // Whether validated users join directly or await admin approval
+    pub max_members: Option<u32>, // New joins are refused once member_count reaches this
+    pub extension: Option<String>, // d tag of the kind 30078 event holding overflow metadata
+    pub welcome: Option<String>, // Greeting for new members; long ones come from the extension
+    pub relay: Option<String>, // Relay clients should use for this group instead of the global one
+    pub join_notifications: bool, // Whether admins are told about new members
+    pub age_restricted: bool,  // Marked 18+ by the venue; clients confirm before showing it
+    pub inactive_prune_days: Option<u32>, // Members quiet this long are warned, then removed
+    pub quiet_hours: Option<QuietHours>, // Nightly window when routine pushes are held
+    pub venue_hours: Option<VenueHours>, // The venue's opening hours from OSM, if it has any
+}
+
+impl GroupMetadata {
+    /// Parse NIP-29 group metadata from a kind 39000 event
+    /// The member count comes from the separate kind 39002 event
+    pub fn from_event(event: &Event, member_count: u32) -> Self {
+        let mut name = String::new();
+        let mut picture = None;
+        let mut about = None;
+        let mut is_public = false;
+        let mut is_open = false;
+        let mut anchors: Vec<String> = Vec::new();
+        let mut display_geohash = None;
+        let mut active_until = None;
+        let mut archived = false;
+        let mut join_mode = JoinMode::default();
+        let mut rules: Vec<String> = Vec::new();
+        let mut extension = None;
+        let mut max_members = None;
+        let mut welcome = None;
+        let mut relay = None;
+        let mut join_notifications = true;
+        let mut age_restricted = false;
+        let mut inactive_prune_days = None;
+        let mut quiet_hours = None;
+        let mut venue_hours = None;
+
+        for tag in event.tags.iter() {
+            tracing::debug!(
+                "[get_group_metadata] Processing tag: {:?}, kind: {:?}",
+                tag,
+                tag.kind()
+            );
+
+            // Handle each tag based on its kind
+            match tag.kind() {
+                // Handle single-letter tags (like "g")
+                TagKind::SingleLetter(single_letter) => {
+                    if single_letter.character == Alphabet::G {
+                        // Parse geohash location tag
+                        if let Some(content) = tag.content() {
+                            tracing::info!("[get_group_metadata] Found 'g' tag (SingleLetter) with content: '{}' (len={})", content, content.len());
+                            // Validate it's a level 8 geohash
+                            if content.len() == 8 {
+                                if !anchors.iter().any(|a| a == content) {
+                                    anchors.push(content.to_string());
+                                }
+                                tracing::info!(
+                                    "[get_group_metadata] Added anchor geohash: {}",
+                                    content
+                                );
+                            } else {
+                                tracing::warn!("[get_group_metadata] Geohash '{}' has invalid length {} (expected 8)", content, content.len());
+                            }
+                        } else {
+                            tracing::warn!("[get_group_metadata] 'g' tag has no content");
+                        }
+                    }
+                }
+                // Handle the special "name" tag kind
+                TagKind::Name => {
+                    if let Some(content) = tag.content() {
+                        name = content.to_string();
+                        tracing::info!("[get_group_metadata] Found name tag: '{}'", name);
+                    }
+                }
+                // Handle custom tags (like "dg", "about", "picture", etc.)
+                TagKind::Custom(tag_name) => {
+                    match tag_name.as_ref() {
+                        "about" => {
+                            about = tag.content().map(|s| s.to_string());
+                        }
+                        "picture" => {
+                            picture = tag.content().map(|s| s.to_string());
+                        }
+                        "dg" => {
+                            // Parse display geohash location tag
+                            if let Some(content) = tag.content() {
+                                tracing::info!("[get_group_metadata] Found 'dg' tag with content: '{}' (len={})", content, content.len());
+                                // Validate it's a level 9 geohash
+                                if content.len() == 9 {
+                                    display_geohash = Some(content.to_string());
+                                    tracing::info!(
+                                        "[get_group_metadata] Set display_geohash to: {:?}",
+                                        display_geohash
+                                    );
+                                } else {
+                                    tracing::warn!("[get_group_metadata] Display geohash '{}' has invalid length {} (expected 9)", content, content.len());
+                                }
+                            } else {
+                                tracing::warn!("[get_group_metadata] 'dg' tag has no content");
+                            }
+                        }
+                        "active_until" => {
+                            // Unix timestamp after which new joins are refused
+                            match tag.content().map(|s| s.parse::<u64>()) {
+                                Some(Ok(ts)) => active_until = Some(Timestamp::from(ts)),
+                                _ => tracing::warn!(
+                                    "[get_group_metadata] Ignoring invalid 'active_until' tag: {:?}",
+                                    tag
+                                ),
+                            }
+                        }
+                        "archived" => archived = true,
+                        RULE_TAG => {
+                            if let Some(rule) = tag.content() {
+                                rules.push(rule.to_string());
+                            }
+                        }
+                        WELCOME_TAG => {
+                            welcome = tag
+                                .content()
+                                .filter(|text| !text.is_empty())
+                                .map(str::to_string);
+                        }
+                        RELAY_TAG => {
+                            relay = tag.content().and_then(relay_override);
+                            if relay.is_none() {
+                                tracing::warn!(
+                                    "[get_group_metadata] Ignoring invalid 'relay' tag: {:?}",
+                                    tag
+                                );
+                            }
+                        }
+                        EXT_TAG => {
+                            extension = tag
+                                .content()
+                                .and_then(extension_d_tag_from_address)
+                                .map(str::to_string);
+                        }
+                        JOIN_NOTIFICATIONS_TAG => {
+                            join_notifications = tag.content() != Some("off");
+                        }
+                        AGE_RESTRICTED_TAG => {
+                            age_restricted = tag.content() == Some("true");
+                        }
+                        INACTIVE_PRUNE_DAYS_TAG => match tag.content().map(|s| s.parse::<u32>()) {
+                            Some(Ok(days)) if days >= INACTIVE_PRUNE_MIN_DAYS => {
+                                inactive_prune_days = Some(days)
+                            }
+                            _ => tracing::warn!(
+                                "[get_group_metadata] Ignoring invalid 'inactive_prune_days' tag: {:?}",
+                                tag
+                            ),
+                        },
+                        QUIET_HOURS_TAG => match tag.as_slice() {
+                            [_, start, end, timezone, ..] => {
+                                match QuietHours::parse(start, end, timezone) {
+                                    Ok(hours) if !hours.is_off() => quiet_hours = Some(hours),
+                                    _ => tracing::warn!(
+                                        "[get_group_metadata] Ignoring invalid 'quiet_hours' tag: {:?}",
+                                        tag
+                                    ),
+                                }
+                            }
+                            _ => tracing::warn!(
+                                "[get_group_metadata] Ignoring invalid 'quiet_hours' tag: {:?}",
+                                tag
+                            ),
+                        },
+                        VENUE_HOURS_TAG => {
+                            venue_hours = VenueHours::from_tag_values(&tag.as_slice()[1..]);
+                            if venue_hours.is_none() {
+                                tracing::warn!(
+                                    "[get_group_metadata] Ignoring invalid 'venue_hours' tag: {:?}",
+                                    tag
+                                );
+                            }
+                        }
+                        JOIN_MODE_TAG => {
+                            join_mode = tag
+                                .content()
+                                .map(JoinMode::from_tag_value)
+                                .unwrap_or_default();
+                        }
+                        MAX_MEMBERS_TAG => match tag.content().map(|s| s.parse::<u32>()) {
+                            Some(Ok(max)) if max > 0 => max_members = Some(max),
+                            _ => tracing::warn!(
+                                "[get_group_metadata] Ignoring invalid 'max_members' tag: {:?}",
+                                tag
+                            ),
+                        },
+                        "public" => is_public = true,
+                        "private" => is_public = false,
+                        "open" => is_open = true,
+                        "closed" => is_open = false,
+                        _ => {}
+                    }
+                }
+                _ => {
+                    // Other tag kinds we don't need to handle
+                }
+            }
+        }
+
+        Self {
+            name,
+            picture,
+            about,
+            rules: (!rules.is_empty()).then_some(rules),
+            member_count,
+            is_public,
+            is_open,
+            created_at: event.created_at,
+            geohash: anchors.first().cloned(),
+            anchors,
+            display_geohash,
+            active_until,
+            archived,
+            join_mode,
+            max_members,
+            extension,
+            welcome,
+            relay,
+            join_notifications,
+            age_restricted,
+            inactive_prune_days,
+            quiet_hours,
+            venue_hours,
+        }
+    }
+
+    /// Whether a time-boxed community's deadline has passed at `now`
+    pub fn is_expired_at(&self, now: Timestamp) -> bool {
+        self.active_until.is_some_and(|until| now >= until)
+    }
+
+    /// Whether the member cap has been reached
+    pub fn is_full(&self) -> bool {
+        self.max_members.is_some_and(|max| self.member_count >= max)
+    }
+}
+
+/// A group's metadata together with the member list its member count was taken from
+#[derive(Debug, Clone)]
+pub struct GroupSnapshot {
+    pub metadata: GroupMetadata,
+    pub members: MemberSet,
+}
+
+/// A group made by `create_group`, with the name it was given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedGroup {
+    pub group_id: String,
+    pub name: String,
+}
+
+/// Name of a community no place name was found for
+pub fn fallback_community_name(community_id: &Uuid) -> String {
+    format!("Community {}", &community_id.to_string()[..8])
+}
+
+/// What `refresh_community` dropped, and the community as reloaded from the relay
+#[derive(Debug, Clone)]
+pub struct CommunityRefresh {
+    /// Cache layers that held an entry for the community
+    pub purged: Vec<&'static str>,
+    /// Group id and fresh metadata; None when the relay has no group for the UUID
+    pub reloaded: Option<(String, GroupMetadata)>,
+}
+
+/// What one pass of `verify_group_mappings` found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MappingCheck {
+    /// Cached mappings looked up on the relay
+    pub checked: usize,
+    /// Mappings whose group no longer exists, now evicted
+    pub vanished: usize,
+    /// Mappings the relay resolves to a different group, now updated
+    pub remapped: usize,
+}
+
+/// Kind 39001 admin lists by group, reused until `ttl` old
+/// Only for display; authorization always reads the current list
+pub struct AdminListCache {
+    ttl: Duration,
+    // Keyed by interned group id, still looked up by &str
+    entries: std::sync::Mutex<
+        std::collections::HashMap<std::sync::Arc<str>, (Vec<PublicKey>, std::time::Instant)>,
+    >,
+}
+
+impl AdminListCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// The group's admins if read less than `ttl` ago
+    pub fn get(&self, group_id: &str) -> Option<Vec<PublicKey>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(group_id)
+            .filter(|(_, read_at)| read_at.elapsed() < self.ttl)
+            .map(|(admins, _)| admins.clone())
+    }
+
+    pub fn insert(&self, group_id: &str, admins: Vec<PublicKey>) {
+        self.entries.lock().unwrap().insert(
+            intern::shared().intern(group_id),
+            (admins, std::time::Instant::now()),
+        );
+    }
+
+    /// Forget a group whose roles just changed; true if an entry was dropped
+    pub fn invalidate(&self, group_id: &str) -> bool {
+        self.entries.lock().unwrap().remove(group_id).is_some()
+    }
 }
 
 /// Service for managing NIP-29 groups on a Nostr relay
 pub struct RelayService {
     client: Client,
     relay_keys: Keys,
+    protocol: ProtocolConfig,
     uuid_to_group_cache:
         std::sync::Arc<tokio::sync::RwLock<std::collections::HashMap<Uuid, String>>>,
     // Cache of community names for uniqueness checking
     // Maps: name -> Vec<community_id>
     name_cache: std::sync::Arc<tokio::sync::RwLock<std::collections::HashMap<String, Vec<Uuid>>>>,
+    // Set once the relay accepts our NIP-42 AUTH or an authenticated query has returned
+    // private group data, after which empty results are treated as authoritative
+    auth_confirmed: std::sync::Arc<AtomicBool>,
+    // Fallback labels for discovery map entries whose name tag is empty
+    labeler: CommunityLabeler,
+    // Anchor geohash -> communities, for spotting duplicates at the same physical spot
+    nearby_index: std::sync::Arc<tokio::sync::RwLock<NearbyIndex>>,
+    // Folded community names for discovery search, rebuilt with the nearby index
+    search_index: std::sync::Arc<tokio::sync::RwLock<SearchIndex>>,
+    // Geocoded localities for discovery records, filled in by a background task
+    localities: std::sync::Arc<LocalityResolver>,
+    clock: std::sync::Arc<dyn Clock>,
+    rng: std::sync::Arc<dyn RngSource>,
+    // Which discovery map events are published, and with which key
+    discovery: DiscoveryMaps,
+    // Metadata edits above this estimated size move overflow into an extension event
+    metadata_max_event_bytes: usize,
+    // Limits from the relay's NIP-11 document, fetched at connect time
+    limits: RelayLimits,
+    // Admin lists from recent reads, so previews can show the requester's role
+    admin_cache: AdminListCache,
+    // Whether the relay still accepts management events from the relay key, shared with /health
+    authorization: std::sync::Arc<RelayKeyAuthorization>,
+    // Refuses relay calls at once while the relay is known to be unreachable
+    circuit: std::sync::Arc<RelayCircuit>,
+    // Recent event ids per group, referenced by the management events we sign
+    previous_refs: PreviousRefs,
+    // Snapshot and member list reads under way, so a crowd previewing one group shares them
+    snapshot_reads: InFlight<String, GroupSnapshot>,
+    member_reads: InFlight<String, MemberSet>,
+    // Relay faults injected into sends when FAULTS is set
+    #[cfg(any(debug_assertions, feature = "fault-injection"))]
+    faults: Option<std::sync::Arc<FaultInjector>>,
 }
 
 impl RelayService {
@@ -57,19 +461,36 @@ impl RelayService {
         &self.client
     }
 
-    pub async fn new(relay_url: String, relay_secret_key: String) -> Result<Self> {
-        // Parse the relay's secret key
-        let secret_key = SecretKey::from_bech32(&relay_secret_key)
-            .or_else(|_| SecretKey::from_hex(&relay_secret_key))?;
-        let relay_keys = Keys::new(secret_key);
+    /// Protocol constants this deployment uses for tags and group ids
+    pub fn protocol(&self) -> &ProtocolConfig {
+        &self.protocol
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new(
+        relay_url: RelayUrl,
+        relay_keys: Keys,
+        protocol: ProtocolConfig,
+        discovery_geocode_budget: Duration,
+        discovery: DiscoveryMaps,
+        metadata_max_event_bytes: usize,
+        circuit: RelayCircuit,
+        previous_refs: PreviousRefs,
+    ) -> Result<Self> {
         // Create client with relay's keys
         // Note: nostr-sdk has automatic authentication enabled by default
         let client = Client::new(relay_keys.clone());
 
         // Add and connect to relay
         tracing::info!("Connecting to relay: {}", relay_url);
-        client.add_relay(&relay_url).await?;
+        client.add_relay(relay_url.as_str()).await?;
+        // Watch for the AUTH acknowledgement from the start; on a relay with no groups yet
+        // no read ever returns data to confirm authentication otherwise
+        let auth_confirmed = std::sync::Arc::new(AtomicBool::new(false));
+        tokio::spawn(watch_authentication(
+            client.relay(relay_url.as_str()).await?.notifications(),
+            auth_confirmed.clone(),
+        ));
         client.connect().await;
 
         // Wait a moment for connection to establish
@@ -83,7 +504,7 @@ impl RelayService {
         tokio::time::sleep(Duration::from_millis(1000)).await;
 
         // Verify connection
-        let relay = client.relay(&relay_url).await?;
+        let relay = client.relay(relay_url.as_str()).await?;
         if relay.is_connected() {
             tracing::info!(
                 "✅ Successfully connected and authenticated to relay: {}",
@@ -93,15 +514,55 @@ impl RelayService {
             tracing::warn!("⚠️ Relay connection might not be fully established");
         }
 
+        let limits = match fetch_relay_limits(relay_url.as_str()).await {
+            Ok(limits) => {
+                tracing::info!("Relay {} advertises limits {:?}", relay_url, limits);
+                limits
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Could not fetch NIP-11 limits of {}: {}; assuming none",
+                    relay_url,
+                    e
+                );
+                RelayLimits::default()
+            }
+        };
+
+        let clock: std::sync::Arc<dyn Clock> = std::sync::Arc::new(SystemClock);
         let service = Self {
             client,
             relay_keys,
+            protocol,
             uuid_to_group_cache: std::sync::Arc::new(tokio::sync::RwLock::new(
                 std::collections::HashMap::new(),
             )),
             name_cache: std::sync::Arc::new(tokio::sync::RwLock::new(
                 std::collections::HashMap::new(),
             )),
+            auth_confirmed,
+            labeler: CommunityLabeler::new(discovery_geocode_budget),
+            nearby_index: std::sync::Arc::new(tokio::sync::RwLock::new(NearbyIndex::default())),
+            search_index: std::sync::Arc::new(tokio::sync::RwLock::new(SearchIndex::default())),
+            localities: std::sync::Arc::new(LocalityResolver::new(LOCALITY_LOOKUP_INTERVAL)),
+            authorization: std::sync::Arc::new(RelayKeyAuthorization::new(clock.clone())),
+            circuit: std::sync::Arc::new(circuit),
+            previous_refs,
+            snapshot_reads: InFlight::new(),
+            member_reads: InFlight::new(),
+            clock,
+            rng: std::sync::Arc::new(ThreadRngSource),
+            discovery,
+            metadata_max_event_bytes,
+            limits,
+            admin_cache: AdminListCache::new(ADMIN_CACHE_TTL),
+            #[cfg(any(debug_assertions, feature = "fault-injection"))]
+            faults: FaultInjector::from_env()
+                .map_err(|e| RelayError::Other(e.to_string()))?
+                .map(|faults| {
+                    tracing::warn!("💥 Relay fault injection enabled by FAULTS");
+                    std::sync::Arc::new(faults)
+                }),
         };
 
         // Load existing community names into cache
@@ -110,6 +571,98 @@ impl RelayService {
         Ok(service)
     }
 
+    /// Limits the relay advertised in its NIP-11 document
+    pub fn relay_limits(&self) -> &RelayLimits {
+        &self.limits
+    }
+
+    /// Whether the relay currently accepts group management events from the relay key
+    pub fn key_authorization(&self) -> std::sync::Arc<RelayKeyAuthorization> {
+        self.authorization.clone()
+    }
+
+    /// The circuit breaker guarding calls to the relay
+    pub fn relay_circuit(&self) -> std::sync::Arc<RelayCircuit> {
+        self.circuit.clone()
+    }
+
+    /// Whether the client holds a live connection to any of its relays
+    async fn is_connected(&self) -> bool {
+        self.client
+            .relays()
+            .await
+            .values()
+            .any(|relay| relay.is_connected())
+    }
+
+    /// Fetch events through the circuit breaker; a call ending disconnected counts as a failure
+    async fn fetch_events(&self, filter: Filter, timeout: Duration) -> Result<Events> {
+        self.circuit.admit()?;
+        let fetched = self.client.fetch_events(filter, timeout).await;
+        self.circuit.record(self.is_connected().await);
+        let events = fetched?;
+        for event in events.iter() {
+            self.previous_refs.observe(event);
+        }
+        Ok(events)
+    }
+
+    /// Up to `desired` events matching `filter`, paged to stay within the relay's max_limit
+    async fn fetch_up_to(
+        &self,
+        filter: Filter,
+        desired: usize,
+        timeout: Duration,
+    ) -> Result<Vec<Event>> {
+        Ok(
+            fetch_paginated(filter, desired, &self.limits, |filter| async move {
+                self.fetch_events(filter, timeout)
+                    .await
+                    .map(|events| events.into_iter().collect())
+            })
+            .await?,
+        )
+    }
+
+    /// Send a signed event to the relay, through the fault injector when one is configured
+    /// Events with more tags than the relay accepts fail here instead of being rejected, and
+    /// management events fail fast while the relay is refusing the relay key
+    async fn send_event(&self, event: &Event) -> Result<()> {
+        if let Err(e) = self.limits.check_tags(event) {
+            metrics::increment("peek_events_over_tag_limit_total", &[]);
+            return Err(RelayError::Other(e.to_string()));
+        }
+        self.circuit.admit()?;
+        let sent = self
+            .authorization
+            .send(event.kind, move || async move {
+                #[cfg(any(debug_assertions, feature = "fault-injection"))]
+                if let Some(faults) = &self.faults {
+                    return faults.send_event(&self.client, event).await;
+                }
+                EventSender::send_event(&self.client, event).await?;
+                Ok(())
+            })
+            .await;
+        self.circuit.record(self.is_connected().await);
+        match &sent {
+            Ok(()) => self.previous_refs.observe(event),
+            Err(e) if is_group_gone(e) => {
+                if let Some(group_id) = h_tag(event) {
+                    self.forget_vanished_group(group_id).await;
+                }
+            }
+            Err(_) => {}
+        }
+        sent
+    }
+
+    /// Sign a management event for the group, referencing its recent events
+    async fn sign_group_event(&self, group_id: &str, builder: EventBuilder) -> Result<Event> {
+        let builder = self.previous_refs.attach(group_id, builder);
+        Ok(self.client.sign_event_builder(builder).await?)
+    }
+
     /// Load existing community names into cache for uniqueness checking
     async fn load_name_cache(&self) -> Result<()> {
         tracing::info!("Loading existing community names into cache...");
@@ -117,17 +670,20 @@ impl RelayService {
         // Query all peek communities using k-tag
         let filter = Filter::new().kind(Kind::from(39000)).custom_tag(
             SingleLetterTag::lowercase(Alphabet::K),
-            "peek:uuid".to_string(),
+            self.protocol.uuid_namespace.clone(),
         );
 
         let events = self
-            .client
-            .fetch_events(filter, std::time::Duration::from_secs(10))
+            .fetch_up_to(filter, 1000, Duration::from_secs(10)) // Same cap as the discovery map
             .await?;
 
+        // The same fetch seeds the nearby and search indexes until the next discovery map refresh
+        *self.nearby_index.write().await = NearbyIndex::from_events(events.iter(), &self.protocol);
+        self.refresh_search_index(&events).await;
+
         let mut cache = self.name_cache.write().await;
 
-        for event in events {
+        for event in events.iter() {
             // Extract name from tags
             let name = event
                 .tags
@@ -144,13 +700,11 @@ impl RelayService {
                     .find(|t| matches!(t.kind(), TagKind::SingleLetter(ref s) if s.character == Alphabet::I))
                 {
                     if let Some(i_content) = i_tag.content() {
-                        if let Some(uuid_str) = i_content.strip_prefix("peek:uuid:") {
-                            if let Ok(community_id) = Uuid::parse_str(uuid_str) {
-                                cache
-                                    .entry(name.clone())
-                                    .or_insert_with(Vec::new)
-                                    .push(community_id);
-                            }
+                        if let Some(community_id) = self.protocol.parse_uuid_tag(i_content) {
+                            cache
+                                .entry(name.clone())
+                                .or_insert_with(Vec::new)
+                                .push(community_id);
                         }
                     }
                 }
@@ -161,6 +715,70 @@ impl RelayService {
         Ok(())
     }
 
+    /// Member count per group from the relay's kind 39002 lists; empty if the query fails
+    async fn fetch_member_counts(&self) -> std::collections::HashMap<String, u32> {
+        let filter = Filter::new()
+            .kind(Kind::from(39002))
+            .author(self.relay_keys.public_key());
+
+        match self
+            .fetch_up_to(filter, 1000, Duration::from_secs(10)) // Safety limit
+            .await
+        {
+            Ok(events) => events
+                .iter()
+                .filter_map(|event| {
+                    let group_id = event.tags.identifier()?;
+                    Some((group_id.to_string(), member_pubkeys(event).len() as u32))
+                })
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Member counts unavailable for community search: {}", e);
+                std::collections::HashMap::new()
+            }
+        }
+    }
+
+    /// Rebuild the community search index from freshly fetched kind 39000 events
+    async fn refresh_search_index(&self, events: &[Event]) {
+        let member_counts = self.fetch_member_counts().await;
+        let mut index = SearchIndex::from_events(events.iter(), &member_counts, &self.protocol);
+        index.set_localities(&self.localities.localities().await);
+        tracing::info!(
+            "Indexed {} communities for discovery search",
+            index.community_count()
+        );
+        *self.search_index.write().await = index;
+    }
+
+    pub fn locality_resolver(&self) -> std::sync::Arc<LocalityResolver> {
+        self.localities.clone()
+    }
+
+    /// Display geohashes of the communities in the discovery data
+    pub async fn discovery_geohashes(&self) -> Vec<String> {
+        self.search_index.read().await.display_geohashes()
+    }
+
+    /// Put the currently known localities on the discovery records
+    pub async fn apply_localities(&self) {
+        let localities = self.localities.localities().await;
+        self.search_index.write().await.set_localities(&localities);
+    }
+
+    /// Search cached community names (and optionally descriptions) without touching the relay
+    pub async fn search_communities(
+        &self,
+        query: &str,
+        limit: usize,
+        include_about: bool,
+    ) -> Vec<CommunityDiscoveryData> {
+        self.search_index
+            .read()
+            .await
+            .search(query, limit, include_about)
+    }
+
     /// Ensure a community name is unique by appending numbers if needed
     async fn ensure_unique_name(&self, base_name: String, community_id: Uuid) -> String {
         let cache = self.name_cache.read().await;
@@ -213,21 +831,31 @@ impl RelayService {
             .push(community_id);
     }
 
+    /// A fresh random group id, as create_group picks one
+    pub fn new_group_id(&self) -> String {
+        self.protocol.generate_group_id(self.rng.as_ref())
+    }
+
     /// Create a new NIP-29 group for a community
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_group(
         &self,
         community_id: Uuid,
         _name: String, // Name is now fetched from Overpass API, not used directly
         creator_pubkey: String,
-        location: Location,
-    ) -> Result<String> {
+        location: Coordinates,
+        active_until: Option<Timestamp>,
+        max_members: Option<u32>,
+        age_restricted: bool,
+        country: Option<String>,
+    ) -> Result<CreatedGroup> {
         // Generate random group ID (h-tag for NIP-29)
         // UUID is stored separately in i-tag per NIP-73
-        let group_id = generate_random_group_id();
+        let group_id = self.new_group_id();
 
         // Check if group already exists by trying to fetch its metadata
         // This avoids the 10-second timeout when relay returns "Group already exists"
-        if let Ok(_metadata) = self.get_group_metadata(&group_id).await {
+        if let Ok(metadata) = self.get_group_metadata(&group_id).await {
             tracing::info!("Group {} already exists, skipping creation", group_id);
             // Group already exists, just add the creator as a member
             self.add_group_member(&group_id, &creator_pubkey, false)
@@ -235,7 +863,10 @@ impl RelayService {
 
             // Just add creator as member, location is already in group metadata
 
-            return Ok(group_id);
+            return Ok(CreatedGroup {
+                group_id,
+                name: metadata.name,
+            });
         }
 
         // Parse creator's public key
@@ -252,7 +883,7 @@ impl RelayService {
         // Send the group creation event with a shorter timeout
         let start = std::time::Instant::now();
         tracing::info!("⏱️ Signing group creation event...");
-        let event = self.client.sign_event_builder(group_creation).await?;
+        let event = self.sign_group_event(&group_id, group_creation).await?;
         tracing::info!("⏱️ Signed in {:?}ms", start.elapsed().as_millis());
 
         let send_start = std::time::Instant::now();
@@ -261,7 +892,7 @@ impl RelayService {
         // Try to send the event but handle timeout/error gracefully
         match tokio::time::timeout(
             Duration::from_secs(2), // 2 second timeout instead of 10
-            self.client.send_event(&event),
+            self.send_event(&event),
         )
         .await
         {
@@ -271,6 +902,8 @@ impl RelayService {
                     send_start.elapsed().as_millis()
                 );
             }
+            // Nothing was created, and the remaining steps would be refused the same way
+            Ok(Err(e @ RelayError::RelayKeyUnauthorized(_))) => return Err(e),
             Ok(Err(e)) => {
                 tracing::warn!(
                     "⏱️ Kind 9007 send failed after {:?}ms: {}",
@@ -291,28 +924,18 @@ impl RelayService {
 
         // Step 2: Add creator as admin (kind 9000 with admin role)
         // Per NIP-29, roles are added as additional values in the p tag
-        let add_admin = EventBuilder::new(
-            Kind::from(9000), // put-user event
-            "",               // Empty content per NIP-29
-        )
-        .tags([
-            Tag::custom(TagKind::Custom("h".into()), [group_id.clone()]),
-            Tag::custom(
-                TagKind::Custom("p".into()),
-                [creator_pk.to_string(), "admin".to_string()],
-            ),
-        ]);
+        let add_admin = put_user_builder(&group_id, &creator_pk, "admin");
 
         let admin_start = std::time::Instant::now();
         tracing::info!("⏱️ Signing add admin event...");
-        let event = self.client.sign_event_builder(add_admin).await?;
+        let event = self.sign_group_event(&group_id, add_admin).await?;
         tracing::info!("⏱️ Signed in {:?}ms", admin_start.elapsed().as_millis());
 
         let send_start = std::time::Instant::now();
         tracing::info!("⏱️ Sending kind 9000 (put-user with admin role)...");
 
         // Send with timeout
-        match tokio::time::timeout(Duration::from_secs(2), self.client.send_event(&event)).await {
+        match tokio::time::timeout(Duration::from_secs(2), self.send_event(&event)).await {
             Ok(Ok(_)) => {
                 tracing::info!(
                     "⏱️ Kind 9000 sent successfully in {:?}ms",
@@ -330,35 +953,28 @@ impl RelayService {
         // Step 3: Remove relay key from admin (kind 9001)
         // The relay key automatically becomes admin when creating the group,
         // but we want the creator to be the sole admin
-        let remove_relay = EventBuilder::new(
-            Kind::from(9001), // remove-user event
-            "",               // Empty content per NIP-29
-        )
-        .allow_self_tagging() // Allow removing ourselves from the group
-        .tags([
-            Tag::custom(TagKind::Custom("h".into()), [group_id.clone()]),
-            Tag::custom(
-                TagKind::Custom("p".into()),
-                [self.relay_keys.public_key().to_string()],
-            ),
-        ]);
-
         let remove_start = std::time::Instant::now();
         tracing::info!("⏱️ Removing relay key from group admins...");
-        let event = self.client.sign_event_builder(remove_relay).await?;
-
-        match tokio::time::timeout(Duration::from_secs(2), self.client.send_event(&event)).await {
-            Ok(Ok(_)) => {
+        let mut execution = Execution::new(&self.client, ExecutionMode::Execute);
+        match self.remove_relay_admin(&group_id, &mut execution).await {
+            Ok(true) => {
                 tracing::info!(
-                    "⏱️ Kind 9001 sent successfully in {:?}ms",
+                    "⏱️ Relay key removed from admins in {:?}ms",
                     remove_start.elapsed().as_millis()
                 );
             }
-            Ok(Err(e)) => {
-                tracing::warn!("⏱️ Kind 9001 send failed: {}", e);
+            Ok(false) => {
+                tracing::warn!(
+                    "⏱️ Relay key still listed as admin of {} after kind 9001; the admin audit will retry",
+                    group_id
+                );
             }
-            Err(_) => {
-                tracing::warn!("⏱️ Kind 9001 send timed out after 2 seconds");
+            Err(e) => {
+                tracing::warn!(
+                    "⏱️ Removing relay key from {} failed: {}; the admin audit will retry",
+                    group_id,
+                    e
+                );
             }
         }
 
@@ -373,47 +989,60 @@ impl RelayService {
         );
 
         // Step 5: Set group metadata with location (kind 9002)
-        // Generate the display geohash for the discovery map
-        let display_geohash = generate_display_location(location.latitude, location.longitude)
-            .map_err(|e| {
-                RelayError::Other(format!("Failed to generate display location: {}", e))
-            })?;
+        // Generate the display geohash for the discovery map, outside every exclusion zone
+        let display_geohash =
+            display_location_outside(location, &self.discovery.exclusion_zones, self.rng.as_ref())?;
+        if display_geohash.is_none() {
+            metrics::increment(
+                "peek_exclusion_zone_markers_total",
+                &[("action", "unplaced")],
+            );
+            tracing::warn!(
+                "No display location outside exclusion zones for {}; it gets no map marker",
+                community_id
+            );
+        }
 
         // Query Overpass API for real place name
         tracing::info!(
             "Querying Overpass API for place name at ({}, {})",
-            location.latitude,
-            location.longitude
-        );
-        let place_name =
-            match super::overpass::get_place_name(location.latitude, location.longitude).await {
-                Ok(Some(name)) => {
-                    tracing::info!("Found place name from Overpass: {}", name);
-                    name
+            location.latitude(),
+            location.longitude()
+        );
+        let (place_name, opening_hours) =
+            match super::overpass::get_place(location.latitude(), location.longitude()).await {
+                Ok(Some(place)) => {
+                    tracing::info!("Found place name from Overpass: {}", place.name);
+                    (place.name, place.opening_hours)
                 }
                 Ok(None) => {
                     tracing::info!("No place name found from Overpass, using default");
-                    format!("Community {}", &community_id.to_string()[..8])
+                    (fallback_community_name(&community_id), None)
                 }
                 Err(e) => {
                     tracing::warn!("Overpass API error: {}, using default name", e);
-                    format!("Community {}", &community_id.to_string()[..8])
+                    (fallback_community_name(&community_id), None)
                 }
             };
+        // Venue hours are read in the venue's timezone, only looked up when there are hours
+        let venue_hours = match opening_hours {
+            Some(opening_hours) => VenueHours::new(&opening_hours, venue_timezone(&location).await),
+            None => None,
+        };
 
         // Ensure name is unique (append number if needed)
         let unique_name = self.ensure_unique_name(place_name, community_id).await;
+        let unique_name = sanitize_automated_name(&unique_name)
+            .unwrap_or_else(|| fallback_community_name(&community_id));
         tracing::info!("Using unique community name: {}", unique_name);
 
         // Update cache with new name
         self.update_name_cache(None, unique_name.clone(), community_id)
             .await;
 
-        let metadata_event = EventBuilder::new(
-            Kind::from(9002),
-            "", // Empty content per NIP-29
-        )
-        .tags([
+        let anchor_geohash = location.geohash(8)?;
+
+        let mut metadata_tags = vec![
             Tag::custom(TagKind::Custom("h".into()), [group_id.clone()]),
             Tag::custom(TagKind::Custom("name".into()), [unique_name.clone()]),
             Tag::custom(
@@ -424,36 +1053,75 @@ impl RelayService {
             Tag::custom(TagKind::Custom("private".into()), Vec::<String>::new()), // Private group
             Tag::custom(TagKind::Custom("closed".into()), Vec::<String>::new()), // Closed - requires location validation
             // Store location as geohash for privacy and efficient matching
-            Tag::custom(
-                TagKind::Custom("g".into()),
-                [encode(
-                    Coord {
-                        x: location.longitude,
-                        y: location.latitude,
-                    },
-                    8,
-                )
-                .map_err(|e| RelayError::Other(format!("Failed to encode location: {}", e)))?],
-            ),
-            // Store display location as 9-character geohash for public discovery
-            Tag::custom(TagKind::Custom("dg".into()), [display_geohash.clone()]),
+            Tag::custom(TagKind::Custom("g".into()), [anchor_geohash.clone()]),
             // Store UUID as i-tag per NIP-73 for efficient UUID-based lookups
             Tag::custom(
                 TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
-                [format!("peek:uuid:{}", community_id)],
+                [self.protocol.uuid_tag(&community_id)],
             ),
             // Store identifier kind as k-tag per NIP-73 for queryable filtering
             Tag::custom(
                 TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K)),
-                ["peek:uuid"],
+                [self.protocol.uuid_namespace.clone()],
             ),
-        ]);
+        ];
+
+        // Store display location as 9-character geohash for public discovery
+        if let Some(display_geohash) = &display_geohash {
+            metadata_tags.push(Tag::custom(
+                TagKind::Custom("dg".into()),
+                [display_geohash.clone()],
+            ));
+        }
+
+        // Time-boxed communities (pop-up events) stop accepting joins after this timestamp
+        if let Some(until) = active_until {
+            metadata_tags.push(Tag::custom(
+                TagKind::Custom("active_until".into()),
+                [until.as_u64().to_string()],
+            ));
+        }
+
+        // Busy spots (airports) can cap membership; zero means no cap
+        if let Some(max) = max_members.filter(|max| *max > 0) {
+            metadata_tags.push(Tag::custom(
+                TagKind::Custom(MAX_MEMBERS_TAG.into()),
+                [max.to_string()],
+            ));
+        }
+
+        // Bars and clubs mark their community 18+ on the sticker
+        if age_restricted {
+            metadata_tags.push(Tag::custom(
+                TagKind::Custom(AGE_RESTRICTED_TAG.into()),
+                ["true"],
+            ));
+        }
+
+        // ISO code of the creation location, for operators filtering by jurisdiction
+        if let Some(country) = country {
+            metadata_tags.push(Tag::custom(TagKind::Custom("country".into()), [country]));
+        }
+
+        // Opening hours of the venue the community is named after, for previews
+        if let Some(hours) = &venue_hours {
+            metadata_tags.push(Tag::custom(
+                TagKind::Custom(VENUE_HOURS_TAG.into()),
+                hours.tag_values(),
+            ));
+        }
+
+        let metadata_event = EventBuilder::new(
+            Kind::from(9002),
+            "", // Empty content per NIP-29
+        )
+        .tags(metadata_tags);
 
         let metadata_start = std::time::Instant::now();
         tracing::info!("⏱️ Setting group metadata with location...");
-        let event = self.client.sign_event_builder(metadata_event).await?;
+        let event = self.sign_group_event(&group_id, metadata_event).await?;
 
-        match tokio::time::timeout(Duration::from_secs(2), self.client.send_event(&event)).await {
+        match tokio::time::timeout(Duration::from_secs(2), self.send_event(&event)).await {
             Ok(Ok(_)) => {
                 tracing::info!(
                     "⏱️ Kind 9002 (metadata) sent successfully in {:?}ms",
@@ -478,7 +1146,11 @@ impl RelayService {
         tracing::info!("Cached UUID {} → group {}", community_id, group_id);
 
         // Publish updated discovery map with new community's display geohash
-        if let Err(e) = self.publish_discovery_map(Some(display_geohash)).await {
+        let mut execution = Execution::new(&self.client, ExecutionMode::Execute);
+        if let Err(e) = self
+            .publish_discovery_map(display_geohash, &mut execution)
+            .await
+        {
             tracing::warn!(
                 "Failed to publish discovery map after creating group: {}",
                 e
@@ -486,7 +1158,19 @@ impl RelayService {
             // Don't fail the group creation if discovery map publishing fails
         }
 
-        Ok(group_id)
+        // Index after the refresh, which may not have seen the new metadata yet
+        self.nearby_index.write().await.insert(
+            &anchor_geohash,
+            IndexedCommunity {
+                group_id: group_id.clone(),
+                community_id: Some(community_id),
+            },
+        );
+
+        Ok(CreatedGroup {
+            group_id,
+            name: unique_name,
+        })
     }
 
     /// Add a user to a group (wrapper for add_group_member)
@@ -511,51 +1195,70 @@ impl RelayService {
             PublicKey::from_bech32(user_pubkey).or_else(|_| PublicKey::from_hex(user_pubkey))?;
 
         // Create NIP-29 add user event (kind 9000)
-        // Per NIP-29, roles are added as additional values in the p tag
         let role = if is_admin { "admin" } else { "member" };
+        let add_user = put_user_builder(group_id, &pubkey, role);
 
-        let add_user = EventBuilder::new(
-            Kind::from(9000),
-            "", // Empty content per NIP-29
-        )
-        .tags([
-            Tag::custom(TagKind::Custom("h".into()), [group_id.to_string()]),
-            Tag::custom(
-                TagKind::Custom("p".into()),
-                [pubkey.to_string(), role.to_string()],
-            ),
-        ]);
-
-        let event = self.client.sign_event_builder(add_user).await?;
+        let event = self.sign_group_event(group_id, add_user).await?;
+        if is_admin {
+            self.admin_cache.invalidate(group_id);
+        }
 
         // Send the event and check for duplicate member error
-        match self.client.send_event(&event).await {
-            Ok(_) => {
-                tracing::info!(
-                    "Successfully added user {} to group {}",
-                    pubkey.to_string(),
-                    group_id
-                );
-                Ok(())
-            }
-            Err(e) => {
-                let error_msg = e.to_string();
-                // Check if this is a duplicate member error (per NIP-29)
-                if error_msg.contains("duplicate:") || error_msg.contains("already a member") {
+        let ambiguity =
+            match tokio::time::timeout(MEMBER_ADD_SEND_TIMEOUT, self.send_event(&event)).await {
+                Ok(Ok(_)) => {
                     tracing::info!(
-                        "User {} is already a member of group {} (relay returned: {})",
+                        "Successfully added user {} to group {}",
                         pubkey.to_string(),
-                        group_id,
-                        error_msg
+                        group_id
                     );
-                    // This is not an error - user is already a member
-                    Ok(())
-                } else {
-                    // Real error
-                    Err(e.into())
+                    return Ok(());
                 }
-            }
-        }
+                Ok(Err(e @ RelayError::RelayKeyUnauthorized(_))) => return Err(e),
+                Ok(Err(e)) => {
+                    // The relay's own message, without our error wrapper's prefix
+                    let error_msg = match &e {
+                        RelayError::NostrSdk(inner) => inner.to_string(),
+                        other => other.to_string(),
+                    };
+                    // Check if this is a duplicate member error (per NIP-29)
+                    if error_msg.contains("duplicate:") || error_msg.contains("already a member") {
+                        tracing::info!(
+                            "User {} is already a member of group {} (relay returned: {})",
+                            pubkey.to_string(),
+                            group_id,
+                            error_msg
+                        );
+                        // This is not an error - user is already a member
+                        return Ok(());
+                    }
+                    if is_relay_rejection(&error_msg) {
+                        // The relay answered and refused: nothing was applied
+                        return Err(e);
+                    }
+                    error_msg
+                }
+                Err(_) => format!(
+                    "send timed out after {}s",
+                    MEMBER_ADD_SEND_TIMEOUT.as_secs()
+                ),
+            };
+
+        // The relay may or may not have applied the add, so believe the member list instead
+        tracing::warn!(
+            "Adding user {} to group {} was ambiguous ({}); checking the member list",
+            pubkey.to_string(),
+            group_id,
+            ambiguity
+        );
+        let pubkey_hex = &pubkey.to_hex();
+        confirm_membership(
+            group_id,
+            move || async move { Ok(self.fetch_members(group_id).await?.contains(pubkey_hex)) },
+            MEMBERSHIP_POLL_ATTEMPTS,
+            MEMBERSHIP_POLL_INTERVAL,
+        )
+        .await
     }
 
     /// Remove a member from a NIP-29 group
@@ -574,10 +1277,11 @@ impl RelayService {
             Tag::custom(TagKind::Custom("p".into()), [pubkey.to_string()]),
         ]);
 
-        let event = self.client.sign_event_builder(remove_user).await?;
+        let event = self.sign_group_event(group_id, remove_user).await?;
 
         // Send the event
-        self.client.send_event(&event).await?;
+        self.send_event(&event).await?;
+        self.admin_cache.invalidate(group_id);
 
         tracing::info!(
             "Successfully removed user {} from group {}",
@@ -587,101 +1291,91 @@ impl RelayService {
         Ok(())
     }
 
-    /// Get the member list for a NIP-29 group
-    /// Returns a vector of member pubkeys
-    pub async fn get_group_members(&self, group_id: &str) -> Result<Vec<String>> {
-        // Fetch kind 39002 (group members) event using d-tag
-        let members_filter = Filter::new()
-            .kind(Kind::from(39002))
-            .identifier(group_id)
-            .limit(1);
+    /// Fetch a group's members from its relay-generated kind 39002 member list
+    /// Concurrent fetches for the same group share one relay query
+    async fn fetch_members(&self, group_id: &str) -> Result<MemberSet> {
+        let read = self.read_members(group_id);
+        let (members, outcome) = self.member_reads.run(group_id.to_string(), read).await;
+        record_coalesced_read("group_members", outcome);
+        members
+    }
 
-        let members_events = self
-            .client
-            .fetch_events(members_filter, Duration::from_secs(5))
-            .await?;
-
-        if let Some(event) = members_events.into_iter().next() {
-            // Extract all p-tags (member pubkeys)
-            let members: Vec<String> = event
-                .tags
-                .iter()
-                .filter_map(|tag| {
-                    if let TagKind::SingleLetter(single_letter) = tag.kind() {
-                        if single_letter.character == Alphabet::P {
-                            tag.content().map(|s| s.to_string())
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            tracing::info!(
-                "Found {} members in group {} from kind 39002",
-                members.len(),
-                group_id
-            );
-            return Ok(members);
-        }
-
-        tracing::info!(
-            "No kind 39002 event found for group {}, returning empty list",
-            group_id
-        );
-        Ok(Vec::new())
-    }
-
-    /// Get the member count for a NIP-29 group
-    pub async fn get_group_member_count(&self, group_id: &str) -> Result<u32> {
-        // Fetch kind 39002 (group members) event using d-tag
-        // This is the relay-generated list of all group members
-        let members_filter = Filter::new()
-            .kind(Kind::from(39002))
-            .identifier(group_id)
-            .limit(1);
+    /// Page back through 39002 revisions until the newest full list is covered
+    ///
+    /// Relays that replace the list answer the first page with a single event. Pages end
+    /// (`until`) at the oldest revision seen, so revisions sharing that second are read again
+    /// and deduplicated; after MEMBER_REVISION_PAGES pages the merge is marked incomplete.
+    async fn read_members(&self, group_id: &str) -> Result<MemberSet> {
+        let page_size = self.limits.clamp_limit(MEMBER_REVISIONS_PAGE);
+        let mut seen = std::collections::HashSet::new();
+        let mut revisions = Vec::new();
+        let mut until = None;
+        let mut history_complete = false;
 
-        let members_events = self
-            .client
-            .fetch_events(members_filter, Duration::from_secs(5))
-            .await?;
+        for _ in 0..MEMBER_REVISION_PAGES {
+            let mut filter = Filter::new()
+                .kind(Kind::from(39002))
+                .identifier(group_id)
+                .limit(page_size);
+            if let Some(until) = until {
+                filter = filter.until(until);
+            }
+            let page = self.fetch_events(filter, Duration::from_secs(5)).await?;
+            let full = page.len() >= page_size;
+            let oldest = page.iter().map(|event| event.created_at).min();
 
-        // Get the first (and should be only) kind 39002 event
-        if let Some(event) = members_events.into_iter().next() {
-            // Count p-tags in the kind 39002 event
-            let mut member_count = 0u32;
-            for tag in event.tags.iter() {
-                if let TagKind::SingleLetter(single_letter) = tag.kind() {
-                    if single_letter.character == Alphabet::P {
-                        member_count += 1;
-                    }
-                }
+            let before = revisions.len();
+            revisions.extend(page.into_iter().filter(|event| seen.insert(event.id)));
+            if !full || reaches_full_revision(&revisions) {
+                history_complete = true;
+                break;
             }
+            if revisions.len() == before {
+                break;
+            }
+            until = oldest;
+        }
 
-            tracing::info!(
-                "Found {} members in group {} from kind 39002",
-                member_count,
-                group_id
+        let members =
+            merge_member_revisions(&revisions, history_complete, self.limits.max_event_tags);
+        if members.possibly_incomplete {
+            tracing::warn!(
+                "Member list of {} may be incomplete: read {} members from {} revisions, relay advertises {:?}",
+                group_id,
+                members.pubkeys.len(),
+                revisions.len(),
+                members.advertised
             );
-            return Ok(member_count);
+            metrics::increment("peek_member_lists_incomplete_total", &[]);
         }
-
-        // No kind 39002 event found
-        tracing::info!(
-            "No kind 39002 event found for group {}, returning 0 members",
-            group_id
-        );
-        Ok(0)
+        Ok(members)
     }
 
     /// Get NIP-29 group metadata from relay
     pub async fn get_group_metadata(&self, group_id: &str) -> Result<GroupMetadata> {
+        Ok(self.get_group_snapshot(group_id).await?.metadata)
+    }
+
+    /// Get a group's metadata and member list in one pass
+    ///
+    /// The kind 39000 and kind 39002 queries are independent relay round trips, so they
+    /// run concurrently; the member count and the member list come from the same 39002
+    /// event instead of fetching it twice. Callers asking for a group whose snapshot is
+    /// already being read wait for that read instead of starting their own; a failed read
+    /// is not shared, so each of its waiters tries again.
+    pub async fn get_group_snapshot(&self, group_id: &str) -> Result<GroupSnapshot> {
+        let read = self.read_group_snapshot(group_id);
+        let (snapshot, outcome) = self.snapshot_reads.run(group_id.to_string(), read).await;
+        record_coalesced_read("group_snapshot", outcome);
+        snapshot
+    }
+
+    async fn read_group_snapshot(&self, group_id: &str) -> Result<GroupSnapshot> {
         tracing::info!(
             "[get_group_metadata] Fetching metadata for group: {}",
             group_id
         );
+        let started = std::time::Instant::now();
 
         // Fetch kind 39000 (group metadata) events using d-tag
         // These are relay-generated events that contain the group metadata
@@ -693,133 +1387,153 @@ impl RelayService {
         // Debug: Log the filter to see what it generates
         tracing::debug!("Filter JSON: {:?}", serde_json::to_string(&metadata_filter));
 
-        let metadata_events = self
-            .client
-            .fetch_events(metadata_filter, Duration::from_secs(5))
-            .await?;
+        // An empty result right after connecting may just mean NIP-42 auth hasn't completed
+        let known_group = self.is_cached_group(group_id).await;
+        let metadata = fetch_with_auth_retry(
+            group_id,
+            known_group,
+            &self.auth_confirmed,
+            AUTH_RETRY_DELAY,
+            || {
+                let filter = metadata_filter.clone();
+                async move {
+                    let events = self.fetch_events(filter, Duration::from_secs(5)).await?;
+                    tracing::info!(
+                        "[get_group_metadata] Found {} events for group {}",
+                        events.len(),
+                        group_id
+                    );
+                    Ok(events.first().cloned())
+                }
+            },
+        );
 
-        tracing::info!(
-            "[get_group_metadata] Found {} events for group {}",
-            metadata_events.len(),
-            group_id
+        let snapshot = fetch_group_snapshot(metadata, self.fetch_members(group_id)).await;
+        metrics::observe(
+            "peek_relay_read_duration_seconds",
+            &[("query", "group_snapshot")],
+            started.elapsed().as_secs_f64(),
         );
+        let mut snapshot = snapshot?;
 
-        if let Some(event) = metadata_events.first() {
-            tracing::info!("[get_group_metadata] Raw kind 39000 event for {}: id={}, created_at={}, tags count={}",
-                group_id, event.id, event.created_at, event.tags.len());
-            tracing::debug!("[get_group_metadata] Full event: {:?}", event);
-            // Parse tags for metadata fields
-            let mut name = String::new();
-            let mut picture = None;
-            let mut about = None;
-            let mut is_public = false;
-            let mut is_open = false;
-            let mut geohash = None;
-            let mut display_geohash = None;
+        // Rules and about text that did not fit in the metadata event
+        if let Some(d_tag) = snapshot.metadata.extension.clone() {
+            match self.fetch_metadata_extension(&d_tag).await {
+                Ok(Some(extension)) => extension.merge_into(&mut snapshot.metadata),
+                Ok(None) => {
+                    tracing::warn!(
+                        "[get_group_metadata] Extension {} of {} not found, serving core metadata",
+                        d_tag,
+                        group_id
+                    );
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "[get_group_metadata] Failed to read extension {} of {}: {}",
+                        d_tag,
+                        group_id,
+                        e
+                    );
+                }
+            }
+        }
 
-            for tag in event.tags.iter() {
-                tracing::debug!(
-                    "[get_group_metadata] Processing tag: {:?}, kind: {:?}",
-                    tag,
-                    tag.kind()
-                );
+        tracing::info!("[get_group_metadata] Final metadata for {}: name='{}', members={}, geohash={:?}, display_geohash={:?}",
+            group_id, snapshot.metadata.name, snapshot.metadata.member_count, snapshot.metadata.geohash, snapshot.metadata.display_geohash);
 
-                // Handle each tag based on its kind
-                match tag.kind() {
-                    // Handle single-letter tags (like "g")
-                    TagKind::SingleLetter(single_letter) => {
-                        if single_letter.character == Alphabet::G {
-                            // Parse geohash location tag
-                            if let Some(content) = tag.content() {
-                                tracing::info!("[get_group_metadata] Found 'g' tag (SingleLetter) with content: '{}' (len={})", content, content.len());
-                                // Validate it's a level 8 geohash
-                                if content.len() == 8 {
-                                    geohash = Some(content.to_string());
-                                    tracing::info!(
-                                        "[get_group_metadata] Set geohash to: {:?}",
-                                        geohash
-                                    );
-                                } else {
-                                    tracing::warn!("[get_group_metadata] Geohash '{}' has invalid length {} (expected 8)", content, content.len());
-                                }
-                            } else {
-                                tracing::warn!("[get_group_metadata] 'g' tag has no content");
-                            }
-                        }
-                    }
-                    // Handle the special "name" tag kind
-                    TagKind::Name => {
-                        if let Some(content) = tag.content() {
-                            name = content.to_string();
-                            tracing::info!("[get_group_metadata] Found name tag: '{}'", name);
-                        }
-                    }
-                    // Handle custom tags (like "dg", "about", "picture", etc.)
-                    TagKind::Custom(tag_name) => {
-                        match tag_name.as_ref() {
-                            "about" => {
-                                about = tag.content().map(|s| s.to_string());
-                            }
-                            "picture" => {
-                                picture = tag.content().map(|s| s.to_string());
-                            }
-                            "dg" => {
-                                // Parse display geohash location tag
-                                if let Some(content) = tag.content() {
-                                    tracing::info!("[get_group_metadata] Found 'dg' tag with content: '{}' (len={})", content, content.len());
-                                    // Validate it's a level 9 geohash
-                                    if content.len() == 9 {
-                                        display_geohash = Some(content.to_string());
-                                        tracing::info!(
-                                            "[get_group_metadata] Set display_geohash to: {:?}",
-                                            display_geohash
-                                        );
-                                    } else {
-                                        tracing::warn!("[get_group_metadata] Display geohash '{}' has invalid length {} (expected 9)", content, content.len());
-                                    }
-                                } else {
-                                    tracing::warn!("[get_group_metadata] 'dg' tag has no content");
-                                }
-                            }
-                            "public" => is_public = true,
-                            "private" => is_public = false,
-                            "open" => is_open = true,
-                            "closed" => is_open = false,
-                            _ => {}
-                        }
-                    }
-                    _ => {
-                        // Other tag kinds we don't need to handle
-                    }
-                }
+        Ok(snapshot)
+    }
+
+    /// Drop everything cached about one community, then reload its metadata from the relay
+    ///
+    /// Only entries for this community are touched. The nearby and search indexes get the
+    /// reloaded entry back right away instead of waiting for the next discovery rebuild.
+    pub async fn refresh_community(&self, community_id: &Uuid) -> Result<CommunityRefresh> {
+        let mut purged = Vec::new();
+        let mut group_ids = Vec::new();
+        if let Some(group_id) = self.uuid_to_group_cache.write().await.remove(community_id) {
+            purged.push("group_mapping");
+            group_ids.push(group_id);
+        }
+        let indexed = self
+            .nearby_index
+            .write()
+            .await
+            .remove_community(community_id);
+        if !indexed.is_empty() {
+            purged.push("nearby_index");
+        }
+        for group_id in indexed {
+            if !group_ids.contains(&group_id) {
+                group_ids.push(group_id);
             }
+        }
+        let search_record = self
+            .search_index
+            .write()
+            .await
+            .remove_community(community_id);
+        if search_record.is_some() {
+            purged.push("search_index");
+        }
+        let mut admins_dropped = false;
+        for group_id in &group_ids {
+            admins_dropped |= self.admin_cache.invalidate(group_id);
+        }
+        if admins_dropped {
+            purged.push("admin_list");
+        }
+        tracing::info!("Purged cached {:?} for community {}", purged, community_id);
 
-            // Fetch the member count from kind 39002 (group members list)
-            let member_count = self.get_group_member_count(group_id).await.unwrap_or(0);
-            let rules = None;
-
-            tracing::info!("[get_group_metadata] Final metadata for {}: name='{}', members={}, geohash={:?}, display_geohash={:?}",
-                group_id, name, member_count, geohash, display_geohash);
-
-            Ok(GroupMetadata {
-                name,
-                picture,
-                about,
-                rules,
-                member_count,
-                is_public,
-                is_open,
-                created_at: event.created_at,
-                geohash,
-                display_geohash,
-            })
-        } else {
-            tracing::warn!(
-                "[get_group_metadata] No kind 39000 event found for group {}",
-                group_id
-            );
-            Err(RelayError::GroupNotFound(group_id.to_string()))
+        let Some(group_id) = self.find_group_by_uuid(community_id).await? else {
+            return Ok(CommunityRefresh {
+                purged,
+                reloaded: None,
+            });
+        };
+        let metadata = self.get_group_metadata(&group_id).await?;
+
+        {
+            let mut nearby = self.nearby_index.write().await;
+            for anchor in &metadata.anchors {
+                nearby.insert(
+                    anchor,
+                    IndexedCommunity {
+                        group_id: group_id.clone(),
+                        community_id: Some(*community_id),
+                    },
+                );
+            }
         }
+        // The locality still applies while the display geohash is unchanged
+        let locality = search_record
+            .filter(|record| record.display_geohash == metadata.display_geohash)
+            .and_then(|record| record.locality);
+        self.search_index.write().await.insert(
+            &group_id,
+            *community_id,
+            metadata.clone(),
+            locality,
+        );
+
+        Ok(CommunityRefresh {
+            purged,
+            reloaded: Some((group_id, metadata)),
+        })
+    }
+
+    /// Communities anchored in `anchor_geohash` or a neighboring cell, from the in-memory index
+    pub async fn nearby_communities(&self, anchor_geohash: &str) -> Vec<IndexedCommunity> {
+        self.nearby_index.read().await.nearby(anchor_geohash)
+    }
+
+    /// Whether a group id is present in the UUID → group cache (i.e. known to exist)
+    async fn is_cached_group(&self, group_id: &str) -> bool {
+        self.uuid_to_group_cache
+            .read()
+            .await
+            .values()
+            .any(|cached| cached == group_id)
     }
 
     /// Find a group's h-tag by its UUID using NIP-73 i-tag
@@ -837,141 +1551,2594 @@ impl RelayService {
             return Ok(Some(group_id.clone()));
         }
 
+        let group_id = self.query_group_by_uuid(uuid).await?;
+        if let Some(group_id) = &group_id {
+            // Cache for future lookups
+            self.uuid_to_group_cache
+                .write()
+                .await
+                .insert(*uuid, group_id.clone());
+        }
+        Ok(group_id)
+    }
+
+    /// Resolve a UUID on the relay itself, bypassing the cache
+    /// An empty result before NIP-42 auth is confirmed is QueryInconclusive, not "no group"
+    async fn query_group_by_uuid(&self, uuid: &Uuid) -> Result<Option<String>> {
         // Query for kind 39000 (group metadata) with i-tag containing the UUID
         let filter = Filter::new()
             .kind(Kind::from(39000))
             .custom_tag(
                 SingleLetterTag::lowercase(Alphabet::I),
-                format!("peek:uuid:{}", uuid),
+                self.protocol.uuid_tag(uuid),
             )
             .limit(1);
 
-        let events = self
-            .client
-            .fetch_events(filter, Duration::from_secs(5))
-            .await?;
+        resolve_uuid_query(uuid, &self.auth_confirmed, AUTH_RETRY_DELAY, || {
+            let filter = filter.clone();
+            async move {
+                let events = self.fetch_events(filter, Duration::from_secs(5)).await?;
+                Ok(events.first().cloned())
+            }
+        })
+        .await
+    }
+
+    /// Re-resolve up to `sample` random cached UUID mappings on the relay, fixing stale ones
+    ///
+    /// Groups can be deleted by operators using other tools, leaving mappings that send
+    /// joins to a group that is gone. Skipped until authentication is confirmed, since an
+    /// empty result before then does not mean the group is gone; a failed query ends the pass.
+    pub async fn verify_group_mappings(&self, sample: usize) -> Result<MappingCheck> {
+        let mut check = MappingCheck::default();
+        if !self.auth_confirmed.load(Ordering::Relaxed) {
+            tracing::debug!("Skipping group mapping check until authentication is confirmed");
+            return Ok(check);
+        }
+        let mut cached: Vec<(Uuid, String)> = self
+            .uuid_to_group_cache
+            .read()
+            .await
+            .iter()
+            .map(|(uuid, group_id)| (*uuid, group_id.clone()))
+            .collect();
+        // Partial shuffle: the first `sample` entries end up a uniform random pick
+        let sample = sample.min(cached.len());
+        for i in 0..sample {
+            let j = i + self.rng.gen_index(cached.len() - i);
+            cached.swap(i, j);
+        }
+
+        for (uuid, group_id) in cached.into_iter().take(sample) {
+            let resolved = self.query_group_by_uuid(&uuid).await?;
+            check.checked += 1;
+            if resolved.as_deref() == Some(group_id.as_str()) {
+                continue;
+            }
+            let mut cache = self.uuid_to_group_cache.write().await;
+            // Replaced while we were asking the relay; the newer mapping wins
+            if cache.get(&uuid) != Some(&group_id) {
+                continue;
+            }
+            match resolved {
+                Some(current) => {
+                    tracing::warn!(
+                        "UUID {} was cached as group {} but the relay has group {}",
+                        uuid,
+                        group_id,
+                        current
+                    );
+                    cache.insert(uuid, current);
+                    check.remapped += 1;
+                    metrics::increment(
+                        "peek_group_mappings_evicted_total",
+                        &[("reason", "remapped")],
+                    );
+                }
+                None => {
+                    tracing::warn!(
+                        "UUID {} was cached as group {} which no longer exists on the relay",
+                        uuid,
+                        group_id
+                    );
+                    cache.remove(&uuid);
+                    drop(cache);
+                    self.admin_cache.invalidate(&group_id);
+                    check.vanished += 1;
+                    metrics::increment(
+                        "peek_group_mappings_evicted_total",
+                        &[("reason", "vanished")],
+                    );
+                }
+            }
+        }
+        Ok(check)
+    }
+
+    /// Forget every UUID mapping to a group the relay says does not exist
+    async fn forget_vanished_group(&self, group_id: &str) {
+        let mut cache = self.uuid_to_group_cache.write().await;
+        let stale: Vec<Uuid> = cache
+            .iter()
+            .filter(|(_, cached)| cached.as_str() == group_id)
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in &stale {
+            cache.remove(uuid);
+            tracing::warn!(
+                "Evicted UUID {} → group {}: the relay no longer has the group",
+                uuid,
+                group_id
+            );
+        }
+        drop(cache);
+        self.admin_cache.invalidate(group_id);
+        if !stale.is_empty() {
+            metrics::add(
+                "peek_group_mappings_evicted_total",
+                &[("reason", "mutation")],
+                stale.len() as u64,
+            );
+        }
+    }
+
+    /// The group using `slug` as its alias, from the slug i-tag of its kind 39000 metadata
+    pub async fn find_group_by_slug(&self, slug: &CommunitySlug) -> Result<Option<SlugOwner>> {
+        let filter = Filter::new().kind(Kind::from(39000)).custom_tag(
+            SingleLetterTag::lowercase(Alphabet::I),
+            self.protocol.slug_tag(slug),
+        );
 
-        if let Some(event) = events.first() {
-            // Extract the d-tag (identifier) which contains the group h-tag
-            if let Some(group_id) = event.tags.identifier() {
-                let group_id_string = group_id.to_string();
+        let events = self.fetch_events(filter, Duration::from_secs(5)).await?;
+        let owner = slug_owner(events.iter(), &self.protocol);
+
+        match &owner {
+            Some(owner) => {
                 tracing::info!(
-                    "[find_group_by_uuid] Found group {} for UUID {}",
-                    group_id_string,
-                    uuid
+                    "[find_group_by_slug] Found group {} for slug {}",
+                    owner.group_id,
+                    slug.as_str()
                 );
-                // Cache for future lookups
                 self.uuid_to_group_cache
                     .write()
                     .await
-                    .insert(*uuid, group_id_string.clone());
-                return Ok(Some(group_id_string));
+                    .insert(owner.community_id, owner.group_id.clone());
             }
-
-            tracing::warn!(
-                "[find_group_by_uuid] Found event but no d-tag for UUID {}",
-                uuid
-            );
-            Ok(None)
-        } else {
-            tracing::info!("[find_group_by_uuid] No group found for UUID {}", uuid);
-            Ok(None)
+            None => tracing::info!(
+                "[find_group_by_slug] No group found for slug {}",
+                slug.as_str()
+            ),
         }
+        Ok(owner)
     }
 
-    /// Publish a NIP-78 discovery map event with all communities' display locations
-    /// If current_display_geohash is provided, it will be included in the map
-    pub async fn publish_discovery_map(
-        &self,
-        current_display_geohash: Option<String>,
-    ) -> Result<()> {
-        tracing::info!("Publishing discovery map...");
-
-        // Fetch all kind 39000 (group metadata) events created by this relay
+    /// Fetch the raw kind 39000 metadata event for a group
+    pub async fn get_group_metadata_event(&self, group_id: &str) -> Result<Event> {
         let filter = Filter::new()
             .kind(Kind::from(39000))
-            .author(self.relay_keys.public_key())
-            .limit(1000); // Safety limit
+            .identifier(group_id)
+            .limit(1);
 
-        let events = self
-            .client
-            .fetch_events(filter, Duration::from_secs(5))
-            .await?;
+        let events = self.fetch_events(filter, Duration::from_secs(5)).await?;
 
-        let mut geohashes = Vec::new();
+        events
+            .first()
+            .cloned()
+            .ok_or_else(|| RelayError::GroupNotFound(group_id.to_string()))
+    }
 
-        // Add the current group's display geohash if provided
-        if let Some(dg) = current_display_geohash {
-            if dg.len() == 9 {
-                geohashes.push(dg);
-            }
-        }
+    /// Pubkeys holding a role in the group's kind 39001 admin list
+    pub async fn get_group_admins(&self, group_id: &str) -> Result<Vec<PublicKey>> {
+        let filter = Filter::new()
+            .kind(Kind::from(39001))
+            .identifier(group_id)
+            .limit(1);
 
-        for event in events {
-            let mut display_geohash = None;
+        let events = self.fetch_events(filter, Duration::from_secs(5)).await?;
 
-            for tag in event.tags.iter() {
-                if let TagKind::Custom(tag_name) = tag.kind() {
-                    if tag_name.as_ref() == "dg" {
-                        // Display geohash (9 characters)
-                        if let Some(content) = tag.content() {
-                            if content.len() == 9 {
-                                display_geohash = Some(content.to_string());
-                                break; // Found dg, no need to check more tags
-                            }
-                        }
-                    }
-                }
-            }
+        let admins = events.first().map(admin_pubkeys).unwrap_or_default();
+        self.admin_cache.insert(group_id, admins.clone());
+        Ok(admins)
+    }
 
-            // Add any valid display geohash found
-            if let Some(dg_hash) = display_geohash {
-                if !geohashes.contains(&dg_hash) {
-                    geohashes.push(dg_hash);
-                }
-            }
+    /// When each member was first added, from the group's kind 9000 put-user history
+    /// Members added before the relay's history begins are missing from the map
+    pub async fn member_join_times(&self, group_id: &str) -> Result<HashMap<String, Timestamp>> {
+        let filter = Filter::new()
+            .kind(Kind::from(9000))
+            .custom_tag(SingleLetterTag::lowercase(Alphabet::H), group_id);
+        let events = self
+            .fetch_up_to(filter, 5000, Duration::from_secs(10))
+            .await?;
+        Ok(first_put_user_times(events.iter()))
+    }
+
+    /// The group's admins for display, from the last read if it is recent enough
+    /// Authorization must use get_group_admins, which always asks the relay
+    pub async fn cached_group_admins(&self, group_id: &str) -> Result<Vec<PublicKey>> {
+        if let Some(admins) = self.admin_cache.get(group_id) {
+            metrics::increment("peek_admin_cache_total", &[("result", "hit")]);
+            return Ok(admins);
         }
+        metrics::increment("peek_admin_cache_total", &[("result", "miss")]);
+        self.get_group_admins(group_id).await
+    }
 
-        // Create NIP-78 event with discovery map containing only geohashes
-        let content = serde_json::json!({
-            "geohashes": geohashes,
-            "updated_at": Timestamp::now().as_u64(),
-        })
-        .to_string();
+    /// Kind 39000/39002 updates for these groups on one long-lived subscription
+    /// Re-subscribing with the same id replaces the previous filter
+    pub async fn subscribe_group_updates(
+        &self,
+        subscription_id: SubscriptionId,
+        group_ids: Vec<String>,
+    ) -> Result<()> {
+        let filter = Filter::new()
+            .kinds([Kind::from(39000), Kind::from(39002)])
+            .identifiers(group_ids);
+        self.client
+            .subscribe_with_id(subscription_id, filter, None)
+            .await?;
+        Ok(())
+    }
 
-        let event = EventBuilder::new(Kind::from(30078), content).tags([Tag::custom(
-            TagKind::Custom("d".into()),
-            ["peek.discovery-map"],
-        )]);
+    /// Notifications from the relay client, including events for our subscriptions
+    pub fn notifications(&self) -> tokio::sync::broadcast::Receiver<RelayPoolNotification> {
+        self.client.notifications()
+    }
 
-        // Sign and publish
-        let signed_event = self.client.sign_event_builder(event).await?;
-        self.client.send_event(&signed_event).await?;
+    /// Send a kind 9001 removing the relay key from the group's admins, then re-fetch the
+    /// admin list once; true if the relay key is no longer listed (never, in a dry run)
+    pub async fn remove_relay_admin(
+        &self,
+        group_id: &str,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<bool> {
+        let relay_pubkey = self.relay_keys.public_key();
+        let remove_relay = EventBuilder::new(Kind::from(9001), "")
+            .allow_self_tagging() // Allow removing ourselves from the group
+            .tags([
+                Tag::custom(TagKind::Custom("h".into()), [group_id.to_string()]),
+                Tag::custom(TagKind::Custom("p".into()), [relay_pubkey.to_string()]),
+            ]);
+        let event = self.sign_group_event(group_id, remove_relay).await?;
 
-        tracing::info!("Published discovery map with {} geohashes", geohashes.len());
-        Ok(())
-    }
-}
+        tokio::time::timeout(Duration::from_secs(2), execution.publish(&event))
+            .await
+            .map_err(|_| RelayError::Other("Kind 9001 send timed out after 2 seconds".into()))??;
 
-#[derive(Debug, thiserror::Error)]
-pub enum RelayError {
-    #[error("Nostr SDK error: {0}")]
-    NostrSdk(#[from] nostr_sdk::client::Error),
+        Ok(!self
+            .get_group_admins(group_id)
+            .await?
+            .contains(&relay_pubkey))
+    }
 
-    #[error("Key error: {0}")]
-    Key(#[from] nostr_sdk::key::Error),
+    /// Groups of this deployment whose relay-signed kind 39001 admin list still names the relay key
+    pub async fn groups_with_relay_admin(&self) -> Result<Vec<String>> {
+        let relay_pubkey = self.relay_keys.public_key();
+        let filter = Filter::new().kind(Kind::from(39001)).author(relay_pubkey);
 
-    #[error("NIP-44 encryption error: {0}")]
-    Nip44(#[from] nip44::Error),
+        let events = self.fetch_events(filter, Duration::from_secs(10)).await?;
 
-    #[error("JSON error: {0}")]
-    Json(#[from] serde_json::Error),
+        Ok(groups_listing_admin(
+            events.iter(),
+            &relay_pubkey,
+            &self.protocol,
+        ))
+    }
 
-    #[error("Group not found: {0}")]
-    GroupNotFound(String),
+    /// This deployment's groups with a community UUID, with their creation time, last
+    /// activity and member count, for the orphan sweep
+    pub async fn orphan_sweep_candidates(&self) -> Result<Vec<SweepCandidate>> {
+        let relay_pubkey = self.relay_keys.public_key();
+        let metadata_filter = Filter::new()
+            .kind(Kind::from(39000))
+            .author(relay_pubkey)
+            .custom_tag(
+                SingleLetterTag::lowercase(Alphabet::K),
+                self.protocol.uuid_namespace.clone(),
+            );
+        let metadata: Vec<Event> = self
+            .fetch_events(metadata_filter, Duration::from_secs(10))
+            .await?
+            .into_iter()
+            .collect();
 
-    #[error("{0}")]
-    Other(String),
-}
+        let group_ids: Vec<String> = metadata
+            .iter()
+            .filter_map(|event| event.tags.identifier())
+            .map(str::to_string)
+            .collect();
+        if group_ids.is_empty() {
+            return Ok(Vec::new());
+        }
 
-type Result<T> = std::result::Result<T, RelayError>;
+        let members_filter = Filter::new()
+            .kind(Kind::from(39002))
+            .author(relay_pubkey)
+            .identifiers(group_ids.clone());
+        let creations_filter = Filter::new()
+            .kind(Kind::from(9007))
+            .author(relay_pubkey)
+            .custom_tags(SingleLetterTag::lowercase(Alphabet::H), group_ids);
+        let (members, creations) = tokio::join!(
+            self.fetch_events(members_filter, Duration::from_secs(10)),
+            self.fetch_events(creations_filter, Duration::from_secs(10)),
+        );
+        let members: Vec<Event> = members?.into_iter().collect();
+        // Relays may prune moderation events; creation then falls back to the metadata time
+        let creations: Vec<Event> = creations
+            .map(|events| events.into_iter().collect())
+            .unwrap_or_else(|e| {
+                tracing::warn!(
+                    "Fetching group creations for the orphan sweep failed: {}",
+                    e
+                );
+                Vec::new()
+            });
+
+        Ok(sweep_candidates(
+            &metadata,
+            &members,
+            &creations,
+            &relay_pubkey,
+            &self.protocol,
+        ))
+    }
+
+    /// Every peek-prefixed group with the UUID of its i-tag, if it has one, for the i-tag
+    /// backfill; legacy groups lack the k-tag too, so the filter can't narrow by namespace
+    pub async fn backfill_candidates(&self) -> Result<Vec<BackfillCandidate>> {
+        let filter = Filter::new()
+            .kind(Kind::from(39000))
+            .author(self.relay_keys.public_key());
+        let metadata: Vec<Event> = self
+            .fetch_events(filter, Duration::from_secs(30))
+            .await?
+            .into_iter()
+            .collect();
+        Ok(backfill_candidates(&metadata, &self.protocol))
+    }
+
+    /// This deployment's groups whose admins turned on inactive member pruning, with their
+    /// members and admins
+    pub async fn prune_candidates(&self) -> Result<Vec<PruneGroup>> {
+        let relay_pubkey = self.relay_keys.public_key();
+        let metadata_filter = Filter::new()
+            .kind(Kind::from(39000))
+            .author(relay_pubkey)
+            .custom_tag(
+                SingleLetterTag::lowercase(Alphabet::K),
+                self.protocol.uuid_namespace.clone(),
+            );
+        let metadata: Vec<Event> = self
+            .fetch_events(metadata_filter, Duration::from_secs(10))
+            .await?
+            .into_iter()
+            .filter(|event| {
+                GroupMetadata::from_event(event, 0)
+                    .inactive_prune_days
+                    .is_some()
+            })
+            .collect();
+
+        let group_ids: Vec<String> = metadata
+            .iter()
+            .filter_map(|event| event.tags.identifier())
+            .map(str::to_string)
+            .collect();
+        if group_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let members_filter = Filter::new()
+            .kind(Kind::from(39002))
+            .author(relay_pubkey)
+            .identifiers(group_ids.clone());
+        let admins_filter = Filter::new()
+            .kind(Kind::from(39001))
+            .author(relay_pubkey)
+            .identifiers(group_ids);
+        let (members, admins) = tokio::join!(
+            self.fetch_events(members_filter, Duration::from_secs(10)),
+            self.fetch_events(admins_filter, Duration::from_secs(10)),
+        );
+        let members: Vec<Event> = members?.into_iter().collect();
+        // Without its admin list a group is skipped rather than risk pruning an admin
+        let admins: Vec<Event> = admins?.into_iter().collect();
+
+        Ok(prune_groups(&metadata, &members, &admins, &self.protocol))
+    }
+
+    /// Latest time each pubkey posted in the group or was added to it, since `since`
+    pub async fn member_activity(
+        &self,
+        group_id: &str,
+        since: u64,
+    ) -> Result<HashMap<String, u64>> {
+        let filter = Filter::new()
+            .custom_tag(SingleLetterTag::lowercase(Alphabet::H), group_id)
+            .since(Timestamp::from(since));
+        let events = self
+            .fetch_up_to(filter, 5000, Duration::from_secs(10))
+            .await?;
+        Ok(latest_activity(events.iter()))
+    }
+
+    /// Send a kind 9008 delete-group and forget the group's cached UUID mapping
+    pub async fn delete_group(
+        &self,
+        group_id: &str,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<()> {
+        let delete = EventBuilder::new(Kind::from(9008), "").tags([Tag::custom(
+            TagKind::Custom("h".into()),
+            [group_id.to_string()],
+        )]);
+        let event = self.sign_group_event(group_id, delete).await?;
+
+        tokio::time::timeout(Duration::from_secs(2), execution.publish(&event))
+            .await
+            .map_err(|_| RelayError::Other("Kind 9008 send timed out after 2 seconds".into()))??;
+
+        if execution.mode() == ExecutionMode::Execute {
+            self.uuid_to_group_cache
+                .write()
+                .await
+                .retain(|_, cached| cached != group_id);
+            self.admin_cache.invalidate(group_id);
+        }
+        Ok(())
+    }
+
+    /// Add the community UUID i-tag and namespace k-tag to a legacy group's metadata with a
+    /// kind 9002, keeping every other tag as published
+    pub async fn add_uuid_tag(
+        &self,
+        group_id: &str,
+        community_id: Uuid,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<()> {
+        let event = self.get_group_metadata_event(group_id).await?;
+        let archived = GroupMetadata::from_event(&event, 0).archived;
+        let tags = uuid_edit_tags(
+            archived_edit_tags(&event, group_id, archived),
+            &self.protocol,
+            &community_id,
+        );
+        let edit = EventBuilder::new(Kind::from(9002), "").tags(tags);
+        let event = self.sign_group_event(group_id, edit).await?;
+
+        tokio::time::timeout(Duration::from_secs(2), execution.publish(&event))
+            .await
+            .map_err(|_| RelayError::Other("Kind 9002 send timed out after 2 seconds".into()))??;
+
+        if execution.mode() == ExecutionMode::Execute {
+            self.uuid_to_group_cache
+                .write()
+                .await
+                .insert(community_id, group_id.to_string());
+        }
+        Ok(())
+    }
+
+    /// Check whether a pubkey holds a role in the group's kind 39001 admin list
+    pub async fn is_group_admin(&self, group_id: &str, pubkey: &PublicKey) -> Result<bool> {
+        Ok(self.get_group_admins(group_id).await?.contains(pubkey))
+    }
+
+    /// Apply admin changes to join mode, member cap, name, about, slug and flags in a single kind 9002
+    /// metadata edit; a `max_members` of zero removes the cap and an empty slug removes the slug
+    /// Text is sanitized first; unsalvageable input fails with InvalidMetadata before any publish,
+    /// and a slug another group already uses fails with SlugTaken
+    /// An `inactive_prune_days` of zero turns pruning off; other values below the minimum are
+    /// InvalidMetadata, since members must get their week of warning inside the threshold
+    /// Quiet hours whose start equals their end turn quiet hours off
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_group_metadata(
+        &self,
+        group_id: &str,
+        join_mode: Option<JoinMode>,
+        max_members: Option<u32>,
+        text: &MetadataText,
+        slug: Option<&str>,
+        join_notifications: Option<bool>,
+        age_restricted: Option<bool>,
+        inactive_prune_days: Option<u32>,
+        quiet_hours: Option<QuietHours>,
+    ) -> Result<()> {
+        let slug = slug
+            .map(|slug| match slug {
+                "" => Ok(None),
+                slug => CommunitySlug::parse(slug).map(Some),
+            })
+            .transpose();
+        let prune_days_valid =
+            inactive_prune_days.is_none_or(|days| days == 0 || days >= INACTIVE_PRUNE_MIN_DAYS);
+        let (text, slug) = match (sanitize_metadata(text, MetadataSource::Admin), slug) {
+            (Ok(text), Ok(slug)) if prune_days_valid => (text, slug),
+            (text, slug) => {
+                let mut fields = text.err().map(|e| e.fields).unwrap_or_default();
+                if slug.is_err() {
+                    fields.push("slug");
+                }
+                if !prune_days_valid {
+                    fields.push("inactive_prune_days");
+                }
+                return Err(InvalidMetadata { fields }.into());
+            }
+        };
+        if let Some(Some(slug)) = &slug {
+            claim_slug(slug, self.find_group_by_slug(slug).await?, group_id)?;
+        }
+        let event = self.get_group_metadata_event(group_id).await?;
+        let join_mode = join_mode.unwrap_or(GroupMetadata::from_event(&event, 0).join_mode);
+
+        let tags = self
+            .restore_metadata_overflow(&event, join_mode_edit_tags(&event, group_id, join_mode))
+            .await?;
+        let tags = text_edit_tags(tags, &text);
+        let tags = match max_members {
+            Some(max) => max_members_edit_tags(tags, max),
+            None => tags,
+        };
+        let tags = match &slug {
+            Some(slug) => slug_edit_tags(tags, &self.protocol, slug.as_ref()),
+            None => tags,
+        };
+        let tags = match join_notifications {
+            Some(enabled) => join_notifications_edit_tags(tags, enabled),
+            None => tags,
+        };
+        let tags = match age_restricted {
+            Some(restricted) => age_restricted_edit_tags(tags, restricted),
+            None => tags,
+        };
+        let tags = match inactive_prune_days {
+            Some(days) => inactive_prune_edit_tags(tags, days),
+            None => tags,
+        };
+        let tags = match &quiet_hours {
+            Some(hours) => quiet_hours_edit_tags(tags, hours),
+            None => tags,
+        };
+        self.publish_metadata_edit(group_id, tags).await?;
+
+        tracing::info!(
+            "Updated metadata of group {} (join mode {}, max members {:?}, name changed: {}, about changed: {}, welcome changed: {}, slug: {:?}, join notifications: {:?}, age restricted: {:?}, inactive prune days: {:?}, quiet hours: {:?})",
+            group_id,
+            join_mode.as_str(),
+            max_members,
+            text.name.is_some(),
+            text.about.is_some(),
+            text.welcome.is_some(),
+            slug.map(|slug| slug.map(|slug| slug.as_str().to_string())),
+            join_notifications,
+            age_restricted,
+            inactive_prune_days,
+            quiet_hours.map(|hours| hours.tag_values())
+        );
+        Ok(())
+    }
+
+    /// Archive or unarchive a group with a kind 9002 metadata edit toggling the "archived" marker
+    /// Other flags are left as they are, so unarchiving restores the previous join behavior
+    pub async fn set_group_archived(&self, group_id: &str, archived: bool) -> Result<()> {
+        let event = self.get_group_metadata_event(group_id).await?;
+        let tags = self
+            .restore_metadata_overflow(&event, archived_edit_tags(&event, group_id, archived))
+            .await?;
+        self.publish_metadata_edit(group_id, tags).await?;
+
+        tracing::info!(
+            "{} group {}",
+            if archived { "Archived" } else { "Unarchived" },
+            group_id
+        );
+        Ok(())
+    }
+
+    /// Replace a group's venue hours with a kind 9002 metadata edit; None removes them
+    pub async fn set_venue_hours(&self, group_id: &str, hours: Option<&VenueHours>) -> Result<()> {
+        let event = self.get_group_metadata_event(group_id).await?;
+        let archived = GroupMetadata::from_event(&event, 0).archived;
+        let tags = self
+            .restore_metadata_overflow(&event, archived_edit_tags(&event, group_id, archived))
+            .await?;
+        self.publish_metadata_edit(group_id, venue_hours_edit_tags(tags, hours))
+            .await?;
+
+        tracing::info!(
+            "Set venue hours of group {} to {:?}",
+            group_id,
+            hours.map(VenueHours::opening_hours)
+        );
+        Ok(())
+    }
+
+    /// Overflow metadata stored in the extension event with d tag `d_tag`, if published
+    async fn fetch_metadata_extension(&self, d_tag: &str) -> Result<Option<MetadataExtension>> {
+        match self.fetch_app_data(d_tag).await? {
+            Some(content) => Ok(Some(serde_json::from_str(&content)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Bring overflow back into edit tags built from `stored`, so the edit republishes it
+    /// Fails rather than publishing an edit that would silently drop the overflow
+    async fn restore_metadata_overflow(&self, stored: &Event, tags: Vec<Tag>) -> Result<Vec<Tag>> {
+        let metadata = GroupMetadata::from_event(stored, 0);
+        let Some(d_tag) = metadata.extension else {
+            return Ok(tags);
+        };
+        let extension = self
+            .fetch_metadata_extension(&d_tag)
+            .await?
+            .ok_or_else(|| RelayError::Other(format!("Metadata extension {} not found", d_tag)))?;
+        Ok(restore_overflow(tags, extension, metadata.about.as_deref()))
+    }
+
+    /// Send a kind 9002 metadata edit, moving overflow into the group's extension event
+    /// when the edit would exceed the size ceiling
+    ///
+    /// The extension is published first so the core never points at missing data; if that
+    /// fails the core metadata is still sent, without the overflow or the ext reference.
+    async fn publish_metadata_edit(&self, group_id: &str, tags: Vec<Tag>) -> Result<()> {
+        let address = extension_address(&self.relay_keys.public_key(), group_id);
+        let split = split_metadata_tags(
+            tags,
+            self.metadata_max_event_bytes,
+            self.limits.max_event_tags,
+            &address,
+        );
+
+        let tags = match &split.extension {
+            Some(extension) => {
+                let d_tag = extension_d_tag_from_address(&address).unwrap_or_default();
+                let published = match serde_json::to_string(extension) {
+                    Ok(content) => self.publish_app_data(d_tag, content).await,
+                    Err(e) => Err(e.into()),
+                };
+                match published {
+                    Ok(()) => {
+                        tracing::info!(
+                            "Moved {} rules{} of group {} into extension {}",
+                            extension.rules.len(),
+                            if extension.about.is_some() {
+                                " and the about text"
+                            } else {
+                                ""
+                            },
+                            group_id,
+                            d_tag
+                        );
+                        split.tags
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to publish metadata extension for {}: {}; sending core metadata only",
+                            group_id,
+                            e
+                        );
+                        metrics::increment("peek_metadata_extension_failures_total", &[]);
+                        split.core_only()
+                    }
+                }
+            }
+            None => split.tags,
+        };
+
+        let edit = EventBuilder::new(Kind::from(9002), "").tags(tags);
+        let signed = self.sign_group_event(group_id, edit).await?;
+        self.send_event(&signed).await?;
+        Ok(())
+    }
+
+    /// Content of the relay-authored kind 30078 app data event with d tag `d_tag`, if any
+    pub async fn fetch_app_data(&self, d_tag: &str) -> Result<Option<String>> {
+        let filter = Filter::new()
+            .kind(Kind::from(30078))
+            .author(self.relay_keys.public_key())
+            .identifier(d_tag)
+            .limit(1);
+
+        let events = self.fetch_events(filter, Duration::from_secs(5)).await?;
+
+        Ok(events
+            .into_iter()
+            .max_by_key(|e| e.created_at)
+            .map(|e| e.content))
+    }
+
+    /// d tag and content of every relay-authored kind 30078 app data event, newest per d tag
+    pub async fn fetch_all_app_data(&self) -> Result<Vec<(String, String)>> {
+        let filter = Filter::new()
+            .kind(Kind::from(30078))
+            .author(self.relay_keys.public_key());
+
+        let events = self
+            .fetch_up_to(filter, 1000, Duration::from_secs(10)) // Safety limit
+            .await?;
+
+        let mut latest: std::collections::HashMap<String, Event> = std::collections::HashMap::new();
+        for event in events {
+            let Some(d_tag) = event.tags.identifier().map(|d| d.to_string()) else {
+                continue;
+            };
+            if latest
+                .get(&d_tag)
+                .is_none_or(|existing| existing.created_at < event.created_at)
+            {
+                latest.insert(d_tag, event);
+            }
+        }
+
+        Ok(latest
+            .into_iter()
+            .map(|(d_tag, event)| (d_tag, event.content))
+            .collect())
+    }
+
+    /// Replace the relay-authored kind 30078 app data event with d tag `d_tag`
+    pub async fn publish_app_data(&self, d_tag: &str, content: String) -> Result<()> {
+        let event = EventBuilder::new(Kind::from(30078), content).tags([Tag::custom(
+            TagKind::Custom("d".into()),
+            [d_tag.to_string()],
+        )]);
+        let signed = self.client.sign_event_builder(event).await?;
+        self.send_event(&signed).await?;
+        Ok(())
+    }
+
+    /// Content of an app data event written by publish_sealed_app_data, decrypted
+    pub async fn fetch_sealed_app_data(&self, d_tag: &str) -> Result<Option<String>> {
+        match self.fetch_app_data(d_tag).await? {
+            Some(sealed) => Ok(Some(nip44::decrypt(
+                self.relay_keys.secret_key(),
+                &self.relay_keys.public_key(),
+                sealed,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Replace an app data event with `content` NIP-44 encrypted to the relay key itself, so
+    /// only the operator holding that key can read it
+    pub async fn publish_sealed_app_data(&self, d_tag: &str, content: String) -> Result<()> {
+        let sealed = nip44::encrypt(
+            self.relay_keys.secret_key(),
+            &self.relay_keys.public_key(),
+            content,
+            nip44::Version::V2,
+        )?;
+        self.publish_app_data(d_tag, sealed).await
+    }
+
+    /// Add an extra anchor location to a multi-anchor community
+    /// Appends a g tag to the group metadata via kind 9002, capped at `max_anchors`
+    /// Returns the resulting number of anchors
+    pub async fn add_group_anchor(
+        &self,
+        group_id: &str,
+        location: Coordinates,
+        max_anchors: usize,
+    ) -> Result<usize> {
+        let anchor = location.geohash(8)?;
+
+        let event = self.get_group_metadata_event(group_id).await?;
+        let (edit_tags, anchor_count) = anchor_edit_tags(&event, group_id, &anchor, max_anchors)?;
+        let edit_tags = self.restore_metadata_overflow(&event, edit_tags).await?;
+        self.publish_metadata_edit(group_id, edit_tags).await?;
+
+        tracing::info!(
+            "Added anchor {} to group {} ({} anchors total)",
+            anchor,
+            group_id,
+            anchor_count
+        );
+        Ok(anchor_count)
+    }
+
+    /// Close time-boxed communities whose active_until deadline has passed
+    /// Publishes a kind 9002 metadata edit replacing "open" with the "closed" marker; the
+    /// service creates groups closed, so only groups opened since then need one. Joins check
+    /// the deadline themselves, and discovery lists expired communities as archived
+    /// Returns the number of groups closed in this pass
+    pub async fn archive_expired_groups(&self) -> Result<usize> {
+        let filter = Filter::new().kind(Kind::from(39000)).custom_tag(
+            SingleLetterTag::lowercase(Alphabet::K),
+            self.protocol.uuid_namespace.clone(),
+        );
+
+        let events: Vec<Event> = self
+            .fetch_events(filter, Duration::from_secs(10))
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut closed_count = 0;
+        for (event, group_id) in expired_still_open(&events, self.clock.now()) {
+            let tags = match self
+                .restore_metadata_overflow(event, closed_edit_tags(event, &group_id))
+                .await
+            {
+                Ok(tags) => tags,
+                Err(e) => {
+                    tracing::warn!("Failed to close expired community {}: {}", group_id, e);
+                    continue;
+                }
+            };
+            let edit = self.publish_metadata_edit(&group_id, tags);
+            match tokio::time::timeout(Duration::from_secs(2), edit).await {
+                Ok(Ok(())) => {
+                    tracing::info!("Closed expired community {}", group_id);
+                    closed_count += 1;
+                }
+                Ok(Err(e)) => {
+                    tracing::warn!("Failed to close expired community {}: {}", group_id, e);
+                }
+                Err(_) => {
+                    tracing::warn!(
+                        "Closing expired community {} timed out after 2 seconds",
+                        group_id
+                    );
+                }
+            }
+        }
+
+        Ok(closed_count)
+    }
+
+    /// Publish a NIP-78 discovery map event with all communities' display locations
+    /// If current_display_geohash is provided, it will be included in the map
+    pub async fn publish_discovery_map(
+        &self,
+        current_display_geohash: Option<String>,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<()> {
+        tracing::info!("Publishing discovery map...");
+        let map = self.build_discovery_map(current_display_geohash).await?;
+        self.publish_discovery_map_content(&map, execution).await
+    }
+
+    /// The discovery map as current group metadata says it should be, without publishing it
+    /// Refreshes the nearby and search indexes from the same fetch
+    pub async fn build_discovery_map(
+        &self,
+        current_display_geohash: Option<String>,
+    ) -> Result<DiscoveryMapContent> {
+        // Fetch all kind 39000 (group metadata) events created by this relay
+        let filter = Filter::new()
+            .kind(Kind::from(39000))
+            .author(self.relay_keys.public_key());
+
+        let events = self
+            .fetch_up_to(filter, 1000, Duration::from_secs(5)) // Safety limit
+            .await?;
+
+        // Refresh the duplicate-detection and search indexes from the same fetch
+        *self.nearby_index.write().await = NearbyIndex::from_events(events.iter(), &self.protocol);
+        self.refresh_search_index(&events).await;
+
+        let now = self.clock.now();
+        let mut geohashes = Vec::new();
+        let mut archived = Vec::new();
+        let mut age_restricted = Vec::new();
+        let mut labels = std::collections::BTreeMap::new();
+        let mut unlabeled = Vec::new();
+
+        // Add the current group's display geohash if provided
+        if let Some(dg) = current_display_geohash {
+            if dg.len() == 9 {
+                geohashes.push(dg);
+            }
+        }
+
+        for event in events {
+            let mut display_geohash = None;
+
+            for tag in event.tags.iter() {
+                if let TagKind::Custom(tag_name) = tag.kind() {
+                    if tag_name.as_ref() == "dg" {
+                        // Display geohash (9 characters)
+                        if let Some(content) = tag.content() {
+                            if content.len() == 9 {
+                                display_geohash = Some(content.to_string());
+                                break; // Found dg, no need to check more tags
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Add any valid display geohash found
+            if let Some(dg_hash) = display_geohash {
+                let name = event
+                    .tags
+                    .find(TagKind::Name)
+                    .and_then(|t| t.content())
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_default();
+
+                if name.is_empty() {
+                    // Prefer the community UUID for the fallback label, else the group id
+                    let id = event
+                        .tags
+                        .find(TagKind::SingleLetter(SingleLetterTag::lowercase(
+                            Alphabet::I,
+                        )))
+                        .and_then(|t| t.content())
+                        .and_then(|i| self.protocol.parse_uuid_tag(i))
+                        .map(|uuid| uuid.to_string())
+                        .or_else(|| event.tags.identifier().map(|d| d.to_string()))
+                        .unwrap_or_default();
+                    unlabeled.push(UnlabeledCommunity {
+                        id,
+                        display_geohash: dg_hash.clone(),
+                    });
+                } else {
+                    labels.insert(dg_hash.clone(), name);
+                }
+
+                let metadata = GroupMetadata::from_event(&event, 0);
+                if metadata.age_restricted && !age_restricted.contains(&dg_hash) {
+                    age_restricted.push(dg_hash.clone());
+                }
+                // Archived and expired communities go in their own list so the default map
+                // hides them
+                let list = if metadata.archived || metadata.is_expired_at(now) {
+                    &mut archived
+                } else {
+                    &mut geohashes
+                };
+                if !list.contains(&dg_hash) {
+                    list.push(dg_hash);
+                }
+            }
+        }
+
+        // Synthesize labels for communities created with an empty name tag
+        if !unlabeled.is_empty() {
+            tracing::info!(
+                "Deriving discovery labels for {} unnamed communities",
+                unlabeled.len()
+            );
+            let derived = self
+                .labeler
+                .label_all(&unlabeled, super::overpass::get_place_name)
+                .await;
+            labels.extend(derived);
+        }
+
+        Ok(DiscoveryMapContent {
+            geohashes,
+            labels,
+            archived,
+            age_restricted,
+            updated_at: self.clock.now_unix(),
+        })
+    }
+
+    /// The part of the live discovery map that the configured map events carry
+    pub async fn live_discovery_map(&self) -> Result<DiscoveryMapContent> {
+        let map = self.build_discovery_map(None).await?;
+        Ok(self.discovery.published_part(&map))
+    }
+
+    /// Sign and publish `map` as the NIP-78 event(s), split per configured prefix
+    pub async fn publish_discovery_map_content(
+        &self,
+        map: &DiscoveryMapContent,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<()> {
+        let events = self
+            .discovery
+            .signed_events(map, &self.relay_keys)
+            .map_err(|e| RelayError::Other(format!("Failed to sign discovery map: {}", e)))?;
+
+        for event in &events {
+            execution.publish(event).await?;
+        }
+
+        tracing::info!(
+            "Published {} discovery map(s) with {} geohashes",
+            events.len(),
+            map.geohashes.len()
+        );
+        Ok(())
+    }
+
+    /// Newest content of every configured discovery map, one entry per d tag found
+    pub async fn fetch_discovery_maps(&self) -> Result<Vec<DiscoveryMapContent>> {
+        let signer = self.discovery.signer(&self.relay_keys).public_key();
+        let filter = Filter::new()
+            .kind(Kind::from(DISCOVERY_MAP_KIND))
+            .author(signer)
+            .identifiers(self.discovery.d_tags());
+
+        let events = self.fetch_events(filter, Duration::from_secs(5)).await?;
+        let events: Vec<Event> = events.into_iter().collect();
+
+        Ok(self.discovery.latest_maps(&events, &signer))
+    }
+}
+
+/// Count a read that was answered by one already in flight
+fn record_coalesced_read(query: &'static str, outcome: Outcome) {
+    if outcome == Outcome::Joined {
+        metrics::increment("peek_relay_reads_coalesced_total", &[("query", query)]);
+    }
+}
+
+/// Earliest kind 9000 naming each pubkey, keyed by hex pubkey
+pub fn first_put_user_times<'a>(
+    events: impl Iterator<Item = &'a Event>,
+) -> HashMap<String, Timestamp> {
+    let mut joined: HashMap<String, Timestamp> = HashMap::new();
+    for event in events {
+        for pubkey in event.tags.public_keys() {
+            joined
+                .entry(pubkey.to_hex())
+                .and_modify(|at| *at = (*at).min(event.created_at))
+                .or_insert(event.created_at);
+        }
+    }
+    joined
+}
+
+/// NIP-29 put-user (kind 9000); the role goes in the p tag after the pubkey
+fn put_user_builder(group_id: &str, pubkey: &PublicKey, role: &str) -> EventBuilder {
+    EventBuilder::new(
+        Kind::from(9000),
+        "", // Empty content per NIP-29
+    )
+    .tags([
+        Tag::custom(TagKind::Custom("h".into()), [group_id.to_string()]),
+        Tag::custom(
+            TagKind::Custom("p".into()),
+            [pubkey.to_string(), role.to_string()],
+        ),
+    ])
+}
+
+/// Member pubkeys (p-tags) of a kind 39002 member list event
+pub fn member_pubkeys(event: &Event) -> Vec<String> {
+    event
+        .tags
+        .iter()
+        .filter(|tag| matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::P))
+        .filter_map(|tag| tag.content().map(|s| s.to_string()))
+        .collect()
+}
+
+/// Admin pubkeys (p-tags) of a kind 39001 admin list event
+fn admin_pubkeys(event: &Event) -> Vec<PublicKey> {
+    event
+        .tags
+        .iter()
+        .filter(|tag| matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::P))
+        .filter_map(|tag| tag.content())
+        .filter_map(|pk| PublicKey::from_hex(pk).ok())
+        .collect()
+}
+
+/// Ids of this deployment's groups whose admin list names `admin`
+fn groups_listing_admin<'a>(
+    admin_lists: impl Iterator<Item = &'a Event>,
+    admin: &PublicKey,
+    protocol: &ProtocolConfig,
+) -> Vec<String> {
+    admin_lists
+        .filter(|event| admin_pubkeys(event).contains(admin))
+        .filter_map(|event| event.tags.identifier())
+        .filter(|group_id| protocol.owns_group_id(group_id))
+        .map(|group_id| group_id.to_string())
+        .collect()
+}
+
+/// Whether a send error is the relay explicitly refusing the event (NIP-01 OK prefixes),
+/// as opposed to a transport failure that leaves the outcome unknown
+fn is_relay_rejection(error_msg: &str) -> bool {
+    [
+        "blocked:",
+        "rate-limited:",
+        "invalid:",
+        "pow:",
+        "restricted:",
+        "auth-required:",
+        "error:",
+    ]
+    .iter()
+    .any(|prefix| error_msg.contains(prefix))
+}
+
+/// Whether a management event failed because its group does not exist on the relay
+fn is_group_gone(error: &RelayError) -> bool {
+    let message = match error {
+        RelayError::GroupNotFound(_) => return true,
+        RelayError::NostrSdk(inner) => inner.to_string(),
+        other => other.to_string(),
+    }
+    .to_lowercase();
+    [
+        "group not found",
+        "unknown group",
+        "group does not exist",
+        "group doesn't exist",
+    ]
+    .iter()
+    .any(|phrase| message.contains(phrase))
+}
+
+/// The group a management event targets, from its h-tag
+fn h_tag(event: &Event) -> Option<&str> {
+    event.tags.iter().find_map(|tag| match tag.as_slice() {
+        [name, group_id, ..] if name == "h" => Some(group_id.as_str()),
+        _ => None,
+    })
+}
+
+/// Poll membership after an ambiguous add until it shows up, or fail with MembershipUnconfirmed
+/// A failed poll counts as "not yet visible" rather than aborting the remaining attempts
+async fn confirm_membership<F, Fut>(
+    group_id: &str,
+    mut is_member: F,
+    attempts: u32,
+    interval: Duration,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    for attempt in 1..=attempts {
+        tokio::time::sleep(interval).await;
+        match is_member().await {
+            Ok(true) => {
+                tracing::info!(
+                    "Membership in {} confirmed on poll {}/{}",
+                    group_id,
+                    attempt,
+                    attempts
+                );
+                return Ok(());
+            }
+            Ok(false) => {}
+            Err(e) => {
+                tracing::warn!(
+                    "Membership poll {}/{} for {} failed: {}",
+                    attempt,
+                    attempts,
+                    group_id,
+                    e
+                );
+            }
+        }
+    }
+    metrics::increment("peek_membership_unconfirmed_total", &[]);
+    Err(RelayError::MembershipUnconfirmed(group_id.to_string()))
+}
+
+/// Await the kind 39000 metadata and kind 39002 member queries together
+/// A failed member query degrades to an empty list marked incomplete
+async fn fetch_group_snapshot<M, L>(metadata: M, members: L) -> Result<GroupSnapshot>
+where
+    M: Future<Output = Result<Event>>,
+    L: Future<Output = Result<MemberSet>>,
+{
+    let (metadata, members) = tokio::join!(metadata, members);
+    let metadata_event = metadata?;
+
+    let members = members.unwrap_or_else(|e| {
+        tracing::warn!("[get_group_metadata] Member list query failed: {}", e);
+        MemberSet::unknown()
+    });
+
+    Ok(GroupSnapshot {
+        metadata: GroupMetadata::from_event(&metadata_event, members.count() as u32),
+        members,
+    })
+}
+
+/// Mark authentication confirmed once the relay accepts our NIP-42 AUTH
+async fn watch_authentication(
+    mut notifications: tokio::sync::broadcast::Receiver<RelayNotification>,
+    auth_confirmed: std::sync::Arc<AtomicBool>,
+) {
+    loop {
+        match notifications.recv().await {
+            Ok(RelayNotification::Authenticated) => {
+                tracing::info!("Relay accepted NIP-42 authentication");
+                auth_confirmed.store(true, Ordering::Relaxed);
+            }
+            Ok(RelayNotification::Shutdown) => return,
+            Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Run a group metadata query, retrying once when an empty result may be NIP-42 auth lag
+///
+/// An empty result is only authoritative (GroupNotFound) once authentication has been
+/// confirmed by an earlier successful private read. Groups we know exist (cached) are
+/// always retried once. Empty results before auth is confirmed yield QueryInconclusive
+/// so callers can fail with a retriable error instead of treating the group as missing.
+async fn fetch_with_auth_retry<F, Fut>(
+    group_id: &str,
+    known_group: bool,
+    auth_confirmed: &AtomicBool,
+    retry_delay: Duration,
+    mut fetch: F,
+) -> Result<Event>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<Event>>>,
+{
+    if let Some(event) = fetch().await? {
+        auth_confirmed.store(true, Ordering::Relaxed);
+        return Ok(event);
+    }
+
+    if !known_group && auth_confirmed.load(Ordering::Relaxed) {
+        tracing::warn!(
+            "[get_group_metadata] No kind 39000 event found for group {}",
+            group_id
+        );
+        return Err(RelayError::GroupNotFound(group_id.to_string()));
+    }
+
+    tracing::warn!(
+        "[get_group_metadata] Empty result for group {} (known={}, auth_confirmed={}), retrying in {:?}",
+        group_id,
+        known_group,
+        auth_confirmed.load(Ordering::Relaxed),
+        retry_delay
+    );
+    tokio::time::sleep(retry_delay).await;
+
+    if let Some(event) = fetch().await? {
+        auth_confirmed.store(true, Ordering::Relaxed);
+        return Ok(event);
+    }
+
+    if auth_confirmed.load(Ordering::Relaxed) {
+        Err(RelayError::GroupNotFound(group_id.to_string()))
+    } else {
+        Err(RelayError::QueryInconclusive(group_id.to_string()))
+    }
+}
+
+/// Group id (d-tag) of the kind 39000 event a UUID query found, through fetch_with_auth_retry
+/// Only an authoritative empty result means the UUID has no group
+async fn resolve_uuid_query<F, Fut>(
+    uuid: &Uuid,
+    auth_confirmed: &AtomicBool,
+    retry_delay: Duration,
+    fetch: F,
+) -> Result<Option<String>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<Event>>>,
+{
+    let uuid_label = uuid.to_string();
+    match fetch_with_auth_retry(&uuid_label, false, auth_confirmed, retry_delay, fetch).await {
+        Ok(event) => match event.tags.identifier() {
+            Some(group_id) => {
+                tracing::info!(
+                    "[find_group_by_uuid] Found group {} for UUID {}",
+                    group_id,
+                    uuid
+                );
+                Ok(Some(group_id.to_string()))
+            }
+            None => {
+                tracing::warn!(
+                    "[find_group_by_uuid] Found event but no d-tag for UUID {}",
+                    uuid
+                );
+                Ok(None)
+            }
+        },
+        Err(RelayError::GroupNotFound(_)) => {
+            tracing::info!("[find_group_by_uuid] No group found for UUID {}", uuid);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Groups among kind 39000 `events` whose active_until has passed at `now` and that are
+/// still open, with their group ids
+fn expired_still_open(events: &[Event], now: Timestamp) -> Vec<(&Event, String)> {
+    events
+        .iter()
+        .filter(|event| {
+            let metadata = GroupMetadata::from_event(event, 0);
+            metadata.is_open && metadata.is_expired_at(now)
+        })
+        .filter_map(|event| Some((event, event.tags.identifier()?.to_string())))
+        .collect()
+}
+
+/// Build the kind 9002 tags for adding `anchor` to a group's existing metadata
+/// Returns the tags and the resulting anchor count, or AnchorLimitReached at the cap
+fn anchor_edit_tags(
+    event: &Event,
+    group_id: &str,
+    anchor: &str,
+    max_anchors: usize,
+) -> Result<(Vec<Tag>, usize)> {
+    let existing = GroupMetadata::from_event(event, 0).anchors;
+    if existing.iter().any(|a| a == anchor) {
+        return Err(RelayError::Other(format!(
+            "Anchor {} already exists for group {}",
+            anchor, group_id
+        )));
+    }
+    if existing.len() >= max_anchors {
+        return Err(RelayError::AnchorLimitReached(max_anchors));
+    }
+
+    let mut tags = vec![Tag::custom(
+        TagKind::Custom("h".into()),
+        [group_id.to_string()],
+    )];
+    tags.extend(
+        event
+            .tags
+            .iter()
+            .filter(
+                |tag| !matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::D),
+            )
+            .cloned(),
+    );
+    tags.push(Tag::custom(
+        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::G)),
+        [anchor.to_string()],
+    ));
+
+    Ok((tags, existing.len() + 1))
+}
+
+/// Build the kind 9002 tags setting a group's join mode, preserving its other metadata
+fn join_mode_edit_tags(event: &Event, group_id: &str, join_mode: JoinMode) -> Vec<Tag> {
+    let mut tags = vec![Tag::custom(
+        TagKind::Custom("h".into()),
+        [group_id.to_string()],
+    )];
+    tags.extend(
+        event
+            .tags
+            .iter()
+            .filter(|tag| {
+                !matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::D)
+                    && !matches!(tag.kind(), TagKind::Custom(ref k) if k == JOIN_MODE_TAG)
+            })
+            .cloned(),
+    );
+    tags.push(Tag::custom(
+        TagKind::Custom(JOIN_MODE_TAG.into()),
+        [join_mode.as_str()],
+    ));
+    tags
+}
+
+/// Build the kind 9002 tags re-sending a group's metadata with "closed" in place of "open"
+fn closed_edit_tags(event: &Event, group_id: &str) -> Vec<Tag> {
+    let mut tags = vec![Tag::custom(
+        TagKind::Custom("h".into()),
+        [group_id.to_string()],
+    )];
+    tags.extend(
+        event
+            .tags
+            .iter()
+            .filter(|tag| {
+                !matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::D)
+                    && !matches!(tag.kind(), TagKind::Custom(ref k) if k == "open" || k == "closed")
+            })
+            .cloned(),
+    );
+    tags.push(Tag::custom(
+        TagKind::Custom("closed".into()),
+        Vec::<String>::new(),
+    ));
+    tags
+}
+
+/// Build the kind 9002 tags re-sending a group's metadata with the archived marker set or cleared
+fn archived_edit_tags(event: &Event, group_id: &str, archived: bool) -> Vec<Tag> {
+    let mut tags = vec![Tag::custom(
+        TagKind::Custom("h".into()),
+        [group_id.to_string()],
+    )];
+    tags.extend(
+        event
+            .tags
+            .iter()
+            .filter(|tag| {
+                !matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::D)
+                    && !matches!(tag.kind(), TagKind::Custom(ref k) if k == "archived")
+            })
+            .cloned(),
+    );
+    if archived {
+        tags.push(Tag::custom(
+            TagKind::Custom("archived".into()),
+            Vec::<String>::new(),
+        ));
+    }
+    tags
+}
+
+/// Replace the name and about tags of a metadata edit with the given (sanitized) text
+fn text_edit_tags(tags: Vec<Tag>, text: &MetadataText) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| match tag.kind() {
+            TagKind::Name => text.name.is_none(),
+            TagKind::Custom(ref k) if k == "about" => text.about.is_none(),
+            TagKind::Custom(ref k) if k == WELCOME_TAG => text.welcome.is_none(),
+            _ => true,
+        })
+        .collect();
+    if let Some(name) = &text.name {
+        tags.push(Tag::custom(TagKind::Name, [name.clone()]));
+    }
+    if let Some(about) = &text.about {
+        tags.push(Tag::custom(
+            TagKind::Custom("about".into()),
+            [about.clone()],
+        ));
+    }
+    // An empty welcome removes it rather than publishing an empty greeting
+    if let Some(welcome) = text.welcome.as_ref().filter(|welcome| !welcome.is_empty()) {
+        tags.push(Tag::custom(
+            TagKind::Custom(WELCOME_TAG.into()),
+            [welcome.clone()],
+        ));
+    }
+    tags
+}
+
+/// Add the UUID i-tag to a metadata edit, and the namespace k-tag unless already there
+fn uuid_edit_tags(mut tags: Vec<Tag>, protocol: &ProtocolConfig, community_id: &Uuid) -> Vec<Tag> {
+    let k = TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K));
+    let has_namespace = tags
+        .iter()
+        .any(|tag| tag.kind() == k && tag.content() == Some(protocol.uuid_namespace.as_str()));
+    tags.push(Tag::custom(
+        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+        [protocol.uuid_tag(community_id)],
+    ));
+    if !has_namespace {
+        tags.push(Tag::custom(k, [protocol.uuid_namespace.clone()]));
+    }
+    tags
+}
+
+/// Replace the slug i-tag of a metadata edit; None removes it
+fn slug_edit_tags(
+    tags: Vec<Tag>,
+    protocol: &ProtocolConfig,
+    slug: Option<&CommunitySlug>,
+) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| {
+            !(matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::I)
+                && tag
+                    .content()
+                    .is_some_and(|value| protocol.is_slug_tag(value)))
+        })
+        .collect();
+    if let Some(slug) = slug {
+        tags.push(Tag::custom(
+            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+            [protocol.slug_tag(slug)],
+        ));
+    }
+    tags
+}
+
+/// Group id and community UUID of the group holding a slug
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlugOwner {
+    pub group_id: String,
+    pub community_id: Uuid,
+}
+
+/// The holder of a slug among the metadata events carrying its i-tag
+/// Should two groups ever carry it, the oldest metadata wins so lookups stay deterministic
+fn slug_owner<'a>(
+    events: impl Iterator<Item = &'a Event>,
+    protocol: &ProtocolConfig,
+) -> Option<SlugOwner> {
+    events
+        .filter_map(|event| {
+            let group_id = event.tags.identifier()?.to_string();
+            let community_id = event
+                .tags
+                .iter()
+                .filter(|tag| {
+                    matches!(tag.kind(), TagKind::SingleLetter(s) if s.character == Alphabet::I)
+                })
+                .filter_map(|tag| tag.content())
+                .find_map(|value| protocol.parse_uuid_tag(value))?;
+            Some((event.created_at, group_id, community_id))
+        })
+        .min_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)))
+        .map(|(_, group_id, community_id)| SlugOwner {
+            group_id,
+            community_id,
+        })
+}
+
+/// Refuse a slug held by another group; re-sending a group's own slug is not a collision
+fn claim_slug(slug: &CommunitySlug, owner: Option<SlugOwner>, group_id: &str) -> Result<()> {
+    match owner {
+        Some(owner) if owner.group_id != group_id => {
+            Err(RelayError::SlugTaken(slug.as_str().to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replace the member cap of a metadata edit; zero removes it
+fn max_members_edit_tags(tags: Vec<Tag>, max_members: u32) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !matches!(tag.kind(), TagKind::Custom(ref k) if k == MAX_MEMBERS_TAG))
+        .collect();
+    if max_members > 0 {
+        tags.push(Tag::custom(
+            TagKind::Custom(MAX_MEMBERS_TAG.into()),
+            [max_members.to_string()],
+        ));
+    }
+    tags
+}
+
+/// Replace a group's join notification choice; only opting out is stored, as "off"
+fn join_notifications_edit_tags(tags: Vec<Tag>, enabled: bool) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !matches!(tag.kind(), TagKind::Custom(ref k) if k == JOIN_NOTIFICATIONS_TAG))
+        .collect();
+    if !enabled {
+        tags.push(Tag::custom(
+            TagKind::Custom(JOIN_NOTIFICATIONS_TAG.into()),
+            ["off"],
+        ));
+    }
+    tags
+}
+
+/// Replace a group's 18+ marker; only a restricted community carries the tag
+fn age_restricted_edit_tags(tags: Vec<Tag>, restricted: bool) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !matches!(tag.kind(), TagKind::Custom(ref k) if k == AGE_RESTRICTED_TAG))
+        .collect();
+    if restricted {
+        tags.push(Tag::custom(
+            TagKind::Custom(AGE_RESTRICTED_TAG.into()),
+            ["true"],
+        ));
+    }
+    tags
+}
+
+/// Replace a group's inactivity threshold; zero removes it and with it the pruning
+fn inactive_prune_edit_tags(tags: Vec<Tag>, days: u32) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !matches!(tag.kind(), TagKind::Custom(ref k) if k == INACTIVE_PRUNE_DAYS_TAG))
+        .collect();
+    if days > 0 {
+        tags.push(Tag::custom(
+            TagKind::Custom(INACTIVE_PRUNE_DAYS_TAG.into()),
+            [days.to_string()],
+        ));
+    }
+    tags
+}
+
+/// Replace a group's quiet hours; an empty window removes them
+fn quiet_hours_edit_tags(tags: Vec<Tag>, quiet_hours: &QuietHours) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !matches!(tag.kind(), TagKind::Custom(ref k) if k == QUIET_HOURS_TAG))
+        .collect();
+    if !quiet_hours.is_off() {
+        tags.push(Tag::custom(
+            TagKind::Custom(QUIET_HOURS_TAG.into()),
+            quiet_hours.tag_values(),
+        ));
+    }
+    tags
+}
+
+/// Replace a group's venue hours; None removes them
+fn venue_hours_edit_tags(tags: Vec<Tag>, hours: Option<&VenueHours>) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = tags
+        .into_iter()
+        .filter(|tag| !matches!(tag.kind(), TagKind::Custom(ref k) if k == VENUE_HOURS_TAG))
+        .collect();
+    if let Some(hours) = hours {
+        tags.push(Tag::custom(
+            TagKind::Custom(VENUE_HOURS_TAG.into()),
+            hours.tag_values(),
+        ));
+    }
+    tags
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RelayError {
+    #[error("Nostr SDK error: {0}")]
+    NostrSdk(#[from] nostr_sdk::client::Error),
+
+    #[error("Key error: {0}")]
+    Key(#[from] nostr_sdk::key::Error),
+
+    #[error("NIP-44 encryption error: {0}")]
+    Nip44(#[from] nip44::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Group not found: {0}")]
+    GroupNotFound(String),
+
+    #[error("{0}")]
+    InvalidMetadata(#[from] InvalidMetadata),
+
+    #[error("Query inconclusive for group {0}: relay returned nothing before authentication was confirmed")]
+    QueryInconclusive(String),
+
+    #[error("Anchor limit reached: communities may have at most {0} anchors")]
+    AnchorLimitReached(usize),
+
+    #[error("Membership in {0} could not be confirmed after an ambiguous relay response")]
+    MembershipUnconfirmed(String),
+
+    #[error("The relay refuses group management events from the relay key: {0}")]
+    RelayKeyUnauthorized(String),
+
+    #[error("Slug {0} is already used by another community")]
+    SlugTaken(String),
+
+    #[error("Relay unavailable, retry in {retry_after_secs}s")]
+    CircuitOpen { retry_after_secs: u64 },
+
+    #[error("Failed to encode location: {0}")]
+    Geohash(#[from] GeohashError),
+
+    #[error(transparent)]
+    DisplayLocation(#[from] DisplayLocationError),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+type Result<T> = std::result::Result<T, RelayError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata_event(extra_tags: Vec<Tag>) -> Event {
+        let mut tags = vec![
+            Tag::identifier("peek-abc123"),
+            Tag::custom(TagKind::Name, ["Pop-up Festival"]),
+            Tag::custom(TagKind::Custom("private".into()), Vec::<String>::new()),
+            Tag::custom(TagKind::Custom("closed".into()), Vec::<String>::new()),
+            Tag::custom(
+                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::G)),
+                ["9q8yyk8y"],
+            ),
+        ];
+        tags.extend(extra_tags);
+        EventBuilder::new(Kind::from(39000), "")
+            .tags(tags)
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_members_of_created_groups_must_auth_and_of_public_groups_need_not() {
+        use crate::services::relay_limits::{read_auth_scope, AuthScope};
+
+        // Tagged private and closed, like every group create_group makes
+        let created = GroupMetadata::from_event(&metadata_event(vec![]), 1);
+        assert!(!created.is_public);
+        assert_eq!(
+            read_auth_scope(created.is_public, &RelayLimits::default()),
+            Some(AuthScope::Group)
+        );
+
+        // No Peek group is public today; the later tag wins
+        let public = GroupMetadata::from_event(
+            &metadata_event(vec![Tag::custom(
+                TagKind::Custom("public".into()),
+                Vec::<String>::new(),
+            )]),
+            1,
+        );
+        assert!(public.is_public);
+        assert_eq!(
+            read_auth_scope(public.is_public, &RelayLimits::default()),
+            None
+        );
+
+        let auth_only_relay = RelayLimits {
+            auth_required: Some(true),
+            ..RelayLimits::default()
+        };
+        assert_eq!(
+            read_auth_scope(public.is_public, &auth_only_relay),
+            Some(AuthScope::Relay)
+        );
+    }
+
+    #[test]
+    fn test_parse_active_until() {
+        let event = metadata_event(vec![Tag::custom(
+            TagKind::Custom("active_until".into()),
+            ["1760000000"],
+        )]);
+        let metadata = GroupMetadata::from_event(&event, 3);
+
+        assert_eq!(metadata.name, "Pop-up Festival");
+        assert_eq!(metadata.geohash.as_deref(), Some("9q8yyk8y"));
+        assert_eq!(metadata.active_until, Some(Timestamp::from(1_760_000_000)));
+        assert!(!metadata.archived);
+        assert!(!metadata.is_expired_at(Timestamp::from(1_759_999_999)));
+        assert!(metadata.is_expired_at(Timestamp::from(1_760_000_000)));
+    }
+
+    #[test]
+    fn test_missing_or_invalid_active_until_never_expires() {
+        let metadata = GroupMetadata::from_event(&metadata_event(vec![]), 1);
+        assert_eq!(metadata.active_until, None);
+        assert!(!metadata.is_expired_at(Timestamp::from(u64::MAX)));
+
+        let event = metadata_event(vec![Tag::custom(
+            TagKind::Custom("active_until".into()),
+            ["next tuesday"],
+        )]);
+        assert_eq!(GroupMetadata::from_event(&event, 1).active_until, None);
+    }
+
+    #[test]
+    fn test_repeated_g_tags_collected_as_anchors() {
+        let event = metadata_event(vec![Tag::custom(
+            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::G)),
+            ["9q8yyk9b"],
+        )]);
+        let metadata = GroupMetadata::from_event(&event, 2);
+
+        assert_eq!(metadata.anchors, vec!["9q8yyk8y", "9q8yyk9b"]);
+        assert_eq!(metadata.geohash.as_deref(), Some("9q8yyk8y"));
+    }
+
+    #[test]
+    fn test_anchor_edit_tags_appends_and_enforces_cap() {
+        let event = metadata_event(vec![]);
+
+        let (tags, count) = anchor_edit_tags(&event, "peek-abc123", "9q8yyk9b", 5).unwrap();
+        assert_eq!(count, 2);
+        let anchors: Vec<&str> = tags
+            .iter()
+            .filter(|t| t.kind().to_string() == "g")
+            .filter_map(|t| t.content())
+            .collect();
+        assert_eq!(anchors, vec!["9q8yyk8y", "9q8yyk9b"]);
+        assert!(!tags.iter().any(|t| t.kind().to_string() == "d"));
+
+        // Duplicate anchors are refused
+        assert!(anchor_edit_tags(&event, "peek-abc123", "9q8yyk8y", 5).is_err());
+
+        // At the cap no further anchors are accepted
+        assert!(matches!(
+            anchor_edit_tags(&event, "peek-abc123", "9q8yyk9b", 1),
+            Err(RelayError::AnchorLimitReached(1))
+        ));
+    }
+
+    #[test]
+    fn test_join_mode_defaults_to_auto_and_edit_replaces_it() {
+        let event = metadata_event(vec![]);
+        assert_eq!(
+            GroupMetadata::from_event(&event, 1).join_mode,
+            JoinMode::Auto
+        );
+
+        let tags = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Approval);
+        let edited = metadata_event(
+            tags.into_iter()
+                .filter(|t| t.kind().to_string() == JOIN_MODE_TAG)
+                .collect(),
+        );
+        assert_eq!(
+            GroupMetadata::from_event(&edited, 1).join_mode,
+            JoinMode::Approval
+        );
+
+        // Switching back replaces the old tag rather than adding a second one
+        let tags = join_mode_edit_tags(&edited, "peek-abc123", JoinMode::Auto);
+        let modes: Vec<&str> = tags
+            .iter()
+            .filter(|t| t.kind().to_string() == JOIN_MODE_TAG)
+            .filter_map(|t| t.content())
+            .collect();
+        assert_eq!(modes, vec!["auto"]);
+        assert!(!tags.iter().any(|t| t.kind().to_string() == "d"));
+    }
+
+    #[test]
+    fn test_member_cap_is_parsed_and_full_at_exactly_the_cap() {
+        let uncapped = GroupMetadata::from_event(&metadata_event(vec![]), 10_000);
+        assert_eq!(uncapped.max_members, None);
+        assert!(!uncapped.is_full());
+
+        let event = metadata_event(vec![Tag::custom(
+            TagKind::Custom(MAX_MEMBERS_TAG.into()),
+            ["50"],
+        )]);
+        assert!(!GroupMetadata::from_event(&event, 49).is_full());
+        assert!(GroupMetadata::from_event(&event, 50).is_full());
+        // Boundary races may leave a few extra members; still full, never an error
+        assert!(GroupMetadata::from_event(&event, 52).is_full());
+
+        // Malformed or zero caps are ignored rather than locking the community
+        for bad in ["0", "lots", "-5"] {
+            let event = metadata_event(vec![Tag::custom(
+                TagKind::Custom(MAX_MEMBERS_TAG.into()),
+                [bad],
+            )]);
+            assert_eq!(GroupMetadata::from_event(&event, 1).max_members, None);
+        }
+    }
+
+    #[test]
+    fn test_max_members_edit_replaces_or_removes_the_cap() {
+        let event = metadata_event(vec![Tag::custom(
+            TagKind::Custom(MAX_MEMBERS_TAG.into()),
+            ["50"],
+        )]);
+        let base = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Auto);
+
+        let raised = max_members_edit_tags(base.clone(), 200);
+        let caps: Vec<&str> = raised
+            .iter()
+            .filter(|t| t.kind().to_string() == MAX_MEMBERS_TAG)
+            .filter_map(|t| t.content())
+            .collect();
+        assert_eq!(caps, vec!["200"]);
+
+        let removed = max_members_edit_tags(base, 0);
+        assert!(!removed
+            .iter()
+            .any(|t| t.kind().to_string() == MAX_MEMBERS_TAG));
+    }
+
+    #[test]
+    fn test_join_notifications_default_on_and_opt_out_is_stored() {
+        let event = metadata_event(vec![]);
+        assert!(GroupMetadata::from_event(&event, 1).join_notifications);
+
+        let base = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Auto);
+        let opted_out = metadata_event(join_notifications_edit_tags(base, false));
+        assert!(!GroupMetadata::from_event(&opted_out, 1).join_notifications);
+
+        let base = join_mode_edit_tags(&opted_out, "peek-abc123", JoinMode::Auto);
+        let opted_in = join_notifications_edit_tags(base, true);
+        assert!(!opted_in
+            .iter()
+            .any(|t| t.kind().to_string() == JOIN_NOTIFICATIONS_TAG));
+    }
+
+    #[test]
+    fn test_age_restriction_is_set_and_cleared_by_metadata_edits() {
+        let event = metadata_event(vec![]);
+        assert!(!GroupMetadata::from_event(&event, 1).age_restricted);
+
+        let base = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Auto);
+        let restricted = metadata_event(age_restricted_edit_tags(base, true));
+        assert!(GroupMetadata::from_event(&restricted, 1).age_restricted);
+
+        // Setting it twice keeps a single tag
+        let base = join_mode_edit_tags(&restricted, "peek-abc123", JoinMode::Auto);
+        let again = age_restricted_edit_tags(base, true);
+        assert_eq!(
+            again
+                .iter()
+                .filter(|t| t.kind().to_string() == AGE_RESTRICTED_TAG)
+                .count(),
+            1
+        );
+
+        let base = join_mode_edit_tags(&restricted, "peek-abc123", JoinMode::Auto);
+        let cleared = metadata_event(age_restricted_edit_tags(base, false));
+        assert!(!GroupMetadata::from_event(&cleared, 1).age_restricted);
+
+        // Only an explicit "true" restricts; a malformed value is not a restriction
+        let odd = metadata_event(vec![Tag::custom(
+            TagKind::Custom(AGE_RESTRICTED_TAG.into()),
+            ["yes"],
+        )]);
+        assert!(!GroupMetadata::from_event(&odd, 1).age_restricted);
+    }
+
+    #[test]
+    fn test_inactive_prune_threshold_is_set_and_cleared() {
+        let event = metadata_event(vec![]);
+        assert_eq!(
+            GroupMetadata::from_event(&event, 1).inactive_prune_days,
+            None
+        );
+
+        let base = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Auto);
+        let pruned = metadata_event(inactive_prune_edit_tags(base, 30));
+        assert_eq!(
+            GroupMetadata::from_event(&pruned, 1).inactive_prune_days,
+            Some(30)
+        );
+
+        let base = join_mode_edit_tags(&pruned, "peek-abc123", JoinMode::Auto);
+        let cleared = metadata_event(inactive_prune_edit_tags(base, 0));
+        assert_eq!(
+            GroupMetadata::from_event(&cleared, 1).inactive_prune_days,
+            None
+        );
+
+        // A threshold too short for the week of warning is not acted on
+        let short = metadata_event(vec![Tag::custom(
+            TagKind::Custom(INACTIVE_PRUNE_DAYS_TAG.into()),
+            ["6"],
+        )]);
+        assert_eq!(
+            GroupMetadata::from_event(&short, 1).inactive_prune_days,
+            None
+        );
+    }
+
+    #[test]
+    fn test_quiet_hours_are_set_and_cleared() {
+        let event = metadata_event(vec![]);
+        assert_eq!(GroupMetadata::from_event(&event, 1).quiet_hours, None);
+
+        let hours = QuietHours::parse("22:00", "07:00", "Europe/Berlin").unwrap();
+        let base = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Auto);
+        let quiet = metadata_event(quiet_hours_edit_tags(base, &hours));
+        assert_eq!(
+            GroupMetadata::from_event(&quiet, 1).quiet_hours,
+            Some(hours)
+        );
+
+        let off = QuietHours::parse("00:00", "00:00", "UTC").unwrap();
+        let base = join_mode_edit_tags(&quiet, "peek-abc123", JoinMode::Auto);
+        let cleared = metadata_event(quiet_hours_edit_tags(base, &off));
+        assert_eq!(GroupMetadata::from_event(&cleared, 1).quiet_hours, None);
+
+        // Tags with a bad timezone or missing values are ignored rather than guessed at
+        for values in [
+            vec!["22:00", "07:00", "Mars/Olympus"],
+            vec!["22:00", "07:00"],
+        ] {
+            let odd = metadata_event(vec![Tag::custom(
+                TagKind::Custom(QUIET_HOURS_TAG.into()),
+                values,
+            )]);
+            assert_eq!(GroupMetadata::from_event(&odd, 1).quiet_hours, None);
+        }
+    }
+
+    #[test]
+    fn test_venue_hours_survive_edits_until_replaced() {
+        let hours = VenueHours::new("Mo-Fr 09:00-17:00", Some(chrono_tz::Europe::Berlin));
+        let event = metadata_event(venue_hours_edit_tags(vec![], hours.as_ref()));
+        assert_eq!(GroupMetadata::from_event(&event, 1).venue_hours, hours);
+
+        // Other edits carry the tag over; a refresh that finds no hours removes it
+        let edited = metadata_event(join_mode_edit_tags(
+            &event,
+            "peek-abc123",
+            JoinMode::Approval,
+        ));
+        assert_eq!(GroupMetadata::from_event(&edited, 1).venue_hours, hours);
+        let base = archived_edit_tags(&edited, "peek-abc123", false);
+        let cleared = metadata_event(venue_hours_edit_tags(base, None));
+        assert_eq!(GroupMetadata::from_event(&cleared, 1).venue_hours, None);
+    }
+
+    #[test]
+    fn test_text_edit_replaces_only_given_fields() {
+        let event = metadata_event(vec![Tag::custom(
+            TagKind::Custom("about".into()),
+            ["Old about"],
+        )]);
+        let base = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Auto);
+
+        let renamed = text_edit_tags(
+            base.clone(),
+            &MetadataText {
+                name: Some("Blue Bottle".to_string()),
+                about: None,
+                welcome: Some("Say hi in the chat!".to_string()),
+            },
+        );
+        let values = |tags: &[Tag], kind: &str| -> Vec<String> {
+            tags.iter()
+                .filter(|t| t.kind().to_string() == kind)
+                .filter_map(|t| t.content().map(str::to_string))
+                .collect()
+        };
+        assert_eq!(values(&renamed, "name"), vec!["Blue Bottle"]);
+        assert_eq!(values(&renamed, "about"), vec!["Old about"]);
+        assert_eq!(values(&renamed, "g"), vec!["9q8yyk8y"]);
+        assert_eq!(values(&renamed, "welcome"), vec!["Say hi in the chat!"]);
+
+        let cleared = text_edit_tags(
+            renamed,
+            &MetadataText {
+                name: None,
+                about: Some(String::new()),
+                welcome: Some(String::new()),
+            },
+        );
+        assert_eq!(values(&cleared, "name"), vec!["Blue Bottle"]);
+        assert_eq!(values(&cleared, "about"), vec![""]);
+        assert!(values(&cleared, "welcome").is_empty());
+    }
+
+    fn i_tag(value: &str) -> Tag {
+        Tag::custom(
+            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::I)),
+            [value],
+        )
+    }
+
+    #[test]
+    fn test_slug_edit_sets_replaces_and_removes_the_slug_tag() {
+        let protocol = ProtocolConfig::default();
+        let community_id = Uuid::new_v4();
+        let event = metadata_event(vec![
+            i_tag(&protocol.uuid_tag(&community_id)),
+            i_tag("peek:slug:old-name"),
+        ]);
+        let base = join_mode_edit_tags(&event, "peek-abc123", JoinMode::Auto);
+        let i_values = |tags: &[Tag]| -> Vec<String> {
+            tags.iter()
+                .filter(|t| t.kind().to_string() == "i")
+                .filter_map(|t| t.content().map(str::to_string))
+                .collect()
+        };
+
+        let slug = CommunitySlug::parse("blue-bottle-mission").unwrap();
+        let renamed = slug_edit_tags(base.clone(), &protocol, Some(&slug));
+        assert_eq!(
+            i_values(&renamed),
+            vec![
+                protocol.uuid_tag(&community_id),
+                "peek:slug:blue-bottle-mission".to_string()
+            ]
+        );
+
+        // Removing the slug keeps the community's UUID tag
+        let removed = slug_edit_tags(renamed, &protocol, None);
+        assert_eq!(i_values(&removed), vec![protocol.uuid_tag(&community_id)]);
+    }
+
+    #[test]
+    fn test_uuid_edit_adds_the_tags_and_keeps_the_rest() {
+        let protocol = ProtocolConfig::default();
+        let community_id = Uuid::new_v4();
+        let event = metadata_event(vec![]);
+        let base = archived_edit_tags(&event, "peek-abc123", false);
+
+        let k_tag = Tag::custom(
+            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::K)),
+            [protocol.uuid_namespace.clone()],
+        );
+
+        let tags = uuid_edit_tags(base.clone(), &protocol, &community_id);
+        assert_eq!(tags[..base.len()], base[..]);
+        assert_eq!(
+            tags[base.len()..],
+            [i_tag(&protocol.uuid_tag(&community_id)), k_tag.clone()]
+        );
+
+        // A k-tag already naming the namespace is not repeated
+        let namespaced = [base, vec![k_tag]].concat();
+        let tags = uuid_edit_tags(namespaced, &protocol, &community_id);
+        assert_eq!(
+            tags.iter().filter(|t| t.kind().to_string() == "k").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_slug_resolves_to_its_community_and_refuses_other_groups() {
+        let protocol = ProtocolConfig::default();
+        let slug = CommunitySlug::parse("blue-bottle-mission").unwrap();
+        let community_id = Uuid::new_v4();
+        let event = metadata_event(vec![
+            i_tag(&protocol.uuid_tag(&community_id)),
+            i_tag(&protocol.slug_tag(&slug)),
+        ]);
+
+        let owner = slug_owner(std::iter::once(&event), &protocol);
+        assert_eq!(
+            owner,
+            Some(SlugOwner {
+                group_id: "peek-abc123".to_string(),
+                community_id,
+            })
+        );
+        assert_eq!(slug_owner(std::iter::empty(), &protocol), None);
+
+        assert!(claim_slug(&slug, None, "peek-xyz789").is_ok());
+        assert!(claim_slug(&slug, owner.clone(), "peek-abc123").is_ok());
+        assert!(matches!(
+            claim_slug(&slug, owner, "peek-xyz789"),
+            Err(RelayError::SlugTaken(taken)) if taken == "blue-bottle-mission"
+        ));
+    }
+
+    fn counting_fetch(
+        calls: &std::sync::Arc<std::sync::atomic::AtomicUsize>,
+        results: Vec<Option<Event>>,
+    ) -> impl FnMut() -> std::future::Ready<Result<Option<Event>>> {
+        let calls = calls.clone();
+        move || {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(Ok(results.get(n).cloned().flatten()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pre_auth_empty_result_retries_and_succeeds() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let auth_confirmed = AtomicBool::new(false);
+        let event = metadata_event(vec![]);
+
+        let result = fetch_with_auth_retry(
+            "peek-abc123",
+            true,
+            &auth_confirmed,
+            Duration::from_millis(1),
+            counting_fetch(&calls, vec![None, Some(event.clone())]),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.id, event.id);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(auth_confirmed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_pre_auth_empty_results_are_inconclusive() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let auth_confirmed = AtomicBool::new(false);
+
+        let result = fetch_with_auth_retry(
+            "peek-abc123",
+            false,
+            &auth_confirmed,
+            Duration::from_millis(1),
+            counting_fetch(&calls, vec![None, None]),
+        )
+        .await;
+
+        assert!(matches!(result, Err(RelayError::QueryInconclusive(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pre_auth_empty_uuid_query_retries_and_finds_the_group() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let auth_confirmed = AtomicBool::new(false);
+        let uuid = Uuid::new_v4();
+
+        let found = resolve_uuid_query(
+            &uuid,
+            &auth_confirmed,
+            Duration::from_millis(1),
+            counting_fetch(&calls, vec![None, Some(metadata_event(vec![]))]),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(found.as_deref(), Some("peek-abc123"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(auth_confirmed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_uuid_query_is_only_absent_once_auth_is_confirmed() {
+        let uuid = Uuid::new_v4();
+
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let before_auth = resolve_uuid_query(
+            &uuid,
+            &AtomicBool::new(false),
+            Duration::from_millis(1),
+            counting_fetch(&calls, vec![None, None]),
+        )
+        .await;
+        assert!(matches!(before_auth, Err(RelayError::QueryInconclusive(_))));
+
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let after_auth = resolve_uuid_query(
+            &uuid,
+            &AtomicBool::new(true),
+            Duration::from_millis(1),
+            counting_fetch(&calls, vec![None]),
+        )
+        .await;
+        assert!(matches!(after_auth, Ok(None)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_post_auth_unknown_group_is_authoritative() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let auth_confirmed = AtomicBool::new(true);
+
+        let result = fetch_with_auth_retry(
+            "peek-missing",
+            false,
+            &auth_confirmed,
+            Duration::from_millis(1),
+            counting_fetch(&calls, vec![None]),
+        )
+        .await;
+
+        assert!(matches!(result, Err(RelayError::GroupNotFound(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    /// Member list polls answering `visible` in turn (false once exhausted)
+    fn membership_polls(
+        calls: &std::sync::Arc<std::sync::atomic::AtomicUsize>,
+        visible: Vec<bool>,
+    ) -> impl FnMut() -> std::future::Ready<Result<bool>> {
+        let calls = calls.clone();
+        move || {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(Ok(visible.get(n).copied().unwrap_or(false)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_add_confirmed_once_member_is_visible() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        // The relay applied the add but its OK was lost; the 39002 update lags one poll
+        let result = confirm_membership(
+            "peek-abc123",
+            membership_polls(&calls, vec![false, true]),
+            MEMBERSHIP_POLL_ATTEMPTS,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_add_never_visible_is_unconfirmed() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let result = confirm_membership(
+            "peek-abc123",
+            membership_polls(&calls, vec![]),
+            MEMBERSHIP_POLL_ATTEMPTS,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert!(matches!(result, Err(RelayError::MembershipUnconfirmed(_))));
+        assert_eq!(
+            calls.load(Ordering::SeqCst),
+            MEMBERSHIP_POLL_ATTEMPTS as usize
+        );
+    }
+
+    #[test]
+    fn test_only_transport_failures_are_ambiguous() {
+        assert!(is_relay_rejection(
+            "event not published: restricted: not a group admin"
+        ));
+        assert!(is_relay_rejection("rate-limited: slow down"));
+        assert!(!is_relay_rejection("relay not connected"));
+        assert!(!is_relay_rejection("timeout"));
+    }
+
+    #[test]
+    fn test_missing_group_errors_are_recognized() {
+        assert!(is_group_gone(&RelayError::GroupNotFound(
+            "peek-abc123".into()
+        )));
+        assert!(is_group_gone(&RelayError::Other(
+            "event not published: invalid: group not found".into()
+        )));
+        assert!(is_group_gone(&RelayError::Other(
+            "error: Group does not exist".into()
+        )));
+        assert!(!is_group_gone(&RelayError::Other(
+            "restricted: not a group admin".into()
+        )));
+    }
+
+    fn members_event(members: &[&str]) -> Event {
+        EventBuilder::new(Kind::from(39002), "")
+            .tags(
+                std::iter::once(Tag::identifier("peek-abc123")).chain(members.iter().map(|m| {
+                    Tag::custom(
+                        TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::P)),
+                        [*m],
+                    )
+                })),
+            )
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_reads_run_concurrently_and_share_member_list() {
+        let slow = Duration::from_millis(300);
+        let metadata = async {
+            tokio::time::sleep(slow).await;
+            Ok(metadata_event(vec![]))
+        };
+        let members = async {
+            tokio::time::sleep(slow).await;
+            Ok(merge_member_revisions(
+                &[members_event(&["alice", "bob"])],
+                true,
+                None,
+            ))
+        };
+
+        let started = std::time::Instant::now();
+        let snapshot = fetch_group_snapshot(metadata, members).await.unwrap();
+        let elapsed = started.elapsed();
+
+        // Close to the slowest single read, well short of the serial sum
+        assert!(elapsed >= slow);
+        assert!(
+            elapsed < slow * 2 - Duration::from_millis(100),
+            "{:?}",
+            elapsed
+        );
+        assert_eq!(snapshot.metadata.member_count, 2);
+        assert_eq!(snapshot.members.pubkeys, vec!["alice", "bob"]);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_survives_member_query_failure() {
+        let snapshot = fetch_group_snapshot(
+            std::future::ready(Ok(metadata_event(vec![]))),
+            std::future::ready(Err(RelayError::Other("timeout".into()))),
+        )
+        .await
+        .unwrap();
+        assert_eq!(snapshot.metadata.member_count, 0);
+        assert!(snapshot.members.pubkeys.is_empty());
+        assert!(snapshot.members.possibly_incomplete);
+
+        let missing = fetch_group_snapshot(
+            std::future::ready(Err(RelayError::GroupNotFound("peek-abc123".into()))),
+            std::future::ready(Ok(MemberSet::default())),
+        )
+        .await;
+        assert!(matches!(missing, Err(RelayError::GroupNotFound(_))));
+    }
+
+    #[test]
+    fn test_admin_list_cache_serves_recent_reads_only() {
+        let admin = Keys::generate().public_key();
+        let cache = AdminListCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get("peek-abc123"), None);
+
+        cache.insert("peek-abc123", vec![admin]);
+        cache.insert("peek-def456", vec![admin]);
+        assert_eq!(cache.get("peek-abc123"), Some(vec![admin]));
+
+        assert!(cache.invalidate("peek-abc123"));
+        assert!(!cache.invalidate("peek-abc123"));
+        assert_eq!(cache.get("peek-abc123"), None);
+        assert_eq!(cache.get("peek-def456"), Some(vec![admin]));
+
+        let expired = AdminListCache::new(Duration::ZERO);
+        expired.insert("peek-abc123", vec![admin]);
+        assert_eq!(expired.get("peek-abc123"), None);
+    }
+
+    #[test]
+    fn test_admin_list_cache_matches_interned_and_owned_group_ids() {
+        let admin = Keys::generate().public_key();
+        let cache = AdminListCache::new(Duration::from_secs(60));
+        let owned = String::from("peek-intern1");
+        let interned = intern::shared().intern("peek-intern1");
+
+        cache.insert(&owned, vec![admin]);
+        assert_eq!(cache.get(&interned), Some(vec![admin]));
+        cache.insert(&interned, vec![]);
+        assert_eq!(cache.get(&owned), Some(vec![]));
+        assert!(cache.invalidate(&owned));
+        assert_eq!(cache.get(&interned), None);
+    }
+
+    #[test]
+    fn test_groups_listing_admin_flags_only_our_groups_naming_the_key() {
+        let relay = Keys::generate().public_key();
+        let creator = Keys::generate().public_key();
+        let admin_list = |group_id: &str, admins: &[PublicKey]| {
+            let mut tags = vec![Tag::identifier(group_id)];
+            tags.extend(admins.iter().map(|pk| Tag::public_key(*pk)));
+            EventBuilder::new(Kind::from(39001), "")
+                .tags(tags)
+                .sign_with_keys(&Keys::generate())
+                .unwrap()
+        };
+
+        let lists = [
+            admin_list("peek-stubborn", &[creator, relay]),
+            admin_list("peek-clean", &[creator]),
+            admin_list("other-app-group", &[relay]),
+        ];
+
+        let flagged = groups_listing_admin(lists.iter(), &relay, &ProtocolConfig::default());
+        assert_eq!(flagged, vec!["peek-stubborn".to_string()]);
+    }
+
+    #[test]
+    fn test_expired_still_open_picks_only_past_deadline_open_groups_at_now() {
+        let active_until =
+            |until: u64| Tag::custom(TagKind::Custom("active_until".into()), [until.to_string()]);
+        let open = || Tag::custom(TagKind::Custom("open".into()), Vec::<String>::new());
+        // metadata_event is closed unless a later "open" tag reopens it
+        let events = [
+            metadata_event(vec![open(), active_until(1_760_000_000)]),
+            metadata_event(vec![active_until(1_760_000_000)]),
+            metadata_event(vec![open()]),
+        ];
+
+        assert!(expired_still_open(&events, Timestamp::from(1_759_999_999)).is_empty());
+
+        let due = expired_still_open(&events, Timestamp::from(1_760_000_000));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.id, events[0].id);
+        assert_eq!(due[0].1, "peek-abc123");
+    }
+
+    #[test]
+    fn test_sweep_edit_replaces_open_with_the_closed_marker() {
+        let event = metadata_event(vec![
+            Tag::custom(TagKind::Custom("open".into()), Vec::<String>::new()),
+            Tag::custom(TagKind::Custom("active_until".into()), ["1760000000"]),
+        ]);
+        let tags = closed_edit_tags(&event, "peek-abc123");
+        let kinds: Vec<String> = tags.iter().map(|t| t.kind().to_string()).collect();
+
+        assert_eq!(kinds.iter().filter(|k| *k == "closed").count(), 1);
+        assert!(!kinds.contains(&"open".to_string()));
+        assert!(!kinds.contains(&"archived".to_string()));
+        assert!(!kinds.contains(&"d".to_string()));
+        assert!(kinds.contains(&"active_until".to_string()));
+        assert_eq!(tags[0].content(), Some("peek-abc123"));
+    }
+
+    #[test]
+    fn test_archive_edit_round_trips_and_keeps_other_flags() {
+        // Stand-in for the relay applying a 9002 edit to the group's 39000 metadata
+        let apply = |edit: Vec<Tag>| {
+            let tags = edit.into_iter().map(|tag| match tag.kind() {
+                TagKind::Custom(ref k) if k == "h" => Tag::identifier("peek-abc123"),
+                _ => tag,
+            });
+            EventBuilder::new(Kind::from(39000), "")
+                .tags(tags)
+                .sign_with_keys(&Keys::generate())
+                .unwrap()
+        };
+
+        let archived = apply(archived_edit_tags(
+            &metadata_event(vec![]),
+            "peek-abc123",
+            true,
+        ));
+        let metadata = GroupMetadata::from_event(&archived, 0);
+        assert!(metadata.archived);
+        assert_eq!(metadata.name, "Pop-up Festival");
+        assert!(!metadata.is_open);
+        // Archiving twice does not stack markers
+        assert_eq!(
+            archived_edit_tags(&archived, "peek-abc123", true)
+                .iter()
+                .filter(|t| t.kind() == TagKind::Custom("archived".into()))
+                .count(),
+            1
+        );
+
+        let restored = apply(archived_edit_tags(&archived, "peek-abc123", false));
+        let metadata = GroupMetadata::from_event(&restored, 0);
+        assert!(!metadata.archived);
+        assert!(!metadata.is_open);
+        assert_eq!(metadata.anchors, vec!["9q8yyk8y".to_string()]);
+    }
+
+    #[test]
+    fn test_join_time_is_the_first_put_user() {
+        let relay = Keys::generate();
+        let (alice, bob) = (Keys::generate().public_key(), Keys::generate().public_key());
+        let put_user = |pubkey: PublicKey, at: u64| {
+            EventBuilder::new(Kind::from(9000), "")
+                .tags([
+                    Tag::custom(TagKind::Custom("h".into()), ["peek-abc123"]),
+                    Tag::public_key(pubkey),
+                ])
+                .custom_created_at(Timestamp::from(at))
+                .sign_with_keys(&relay)
+                .unwrap()
+        };
+        // Re-adds (role changes, rejoins after removal) come later and do not move the date
+        let events = [
+            put_user(alice, 1_760_000_500),
+            put_user(alice, 1_760_000_000),
+            put_user(bob, 1_760_000_200),
+        ];
+
+        let joined = first_put_user_times(events.iter());
+        assert_eq!(joined.len(), 2);
+        assert_eq!(joined[&alice.to_hex()], Timestamp::from(1_760_000_000));
+        assert_eq!(joined[&bob.to_hex()], Timestamp::from(1_760_000_200));
+    }
+
+    #[test]
+    fn test_put_user_references_previous_events_once_the_group_has_history() {
+        let relay = Keys::generate();
+        let refs = PreviousRefs::new(3);
+        let previous = |event: &Event| {
+            event
+                .tags
+                .iter()
+                .find(|tag| tag.as_slice()[0] == "previous")
+                .map(|tag| tag.as_slice()[1..].to_vec())
+        };
+
+        // The first event of a brand-new group has nothing to reference
+        let creator = Keys::generate().public_key();
+        let first = refs
+            .attach(
+                "peek-abc123",
+                put_user_builder("peek-abc123", &creator, "admin"),
+            )
+            .sign_with_keys(&relay)
+            .unwrap();
+        assert_eq!(previous(&first), None);
+        refs.observe(&first);
+
+        let member = Keys::generate().public_key();
+        let add_member = refs
+            .attach(
+                "peek-abc123",
+                put_user_builder("peek-abc123", &member, "member"),
+            )
+            .sign_with_keys(&relay)
+            .unwrap();
+        assert_eq!(
+            previous(&add_member),
+            Some(vec![first.id.to_hex()[..8].to_string()])
+        );
+        assert_eq!(
+            add_member.tags.public_keys().copied().collect::<Vec<_>>(),
+            vec![member]
+        );
+    }
+}
@@ -0,0 +1,658 @@
+//! Relay access split by role, so a slow write never stalls unrelated work
+//!
+//! One `RelayService` (client and caches) is shared through `Arc`. `GroupReader` reads
+//! without taking any lock, `GroupWriter` serializes mutations per group, and
+//! `DiscoveryPublisher` owns the relay-wide discovery data. A create_group stuck on relay
+//! sends therefore only holds up other writes to that same community.
+
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use uuid::Uuid;
+
+use super::bulk_members::BulkAddTarget;
+use super::community_export::ExportSource;
+use super::community_search::CommunityDiscoveryData;
+use super::discovery_map::DiscoveryMapContent;
+use super::execution::{Execution, ExecutionMode};
+use super::inactive_prune::PruneGroup;
+use super::itag_backfill::BackfillCandidate;
+use super::join_requests::{JoinMode, JoinQueue, JoinQueueStore};
+use super::localities::LocalityResolver;
+use super::metrics;
+use super::nearby_index::IndexedCommunity;
+use super::orphan_sweep::SweepCandidate;
+use super::quiet_hours::QuietHours;
+use super::relay::{
+    fallback_community_name, CommunityRefresh, CreatedGroup, GroupMetadata, GroupSnapshot,
+    MappingCheck, RelayError, RelayService, SlugOwner,
+};
+use super::relay_circuit::RelayCircuit;
+use super::relay_limits::RelayLimits;
+use super::venue_hours::VenueHours;
+use crate::libraries::community_id::CommunitySlug;
+use crate::libraries::intern;
+use crate::libraries::sanitize::MetadataText;
+use crate::models::Coordinates;
+
+type Result<T> = std::result::Result<T, RelayError>;
+
+/// One async lock per key, created on first use and forgotten once nobody holds or awaits it
+#[derive(Default)]
+pub struct GroupLocks {
+    locks: std::sync::Mutex<HashMap<Arc<str>, Weak<Mutex<()>>>>,
+}
+
+impl GroupLocks {
+    /// Wait for exclusive use of `key`; released when the guard drops
+    pub async fn lock(&self, key: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            match locks.get(key).and_then(Weak::upgrade) {
+                Some(lock) => lock,
+                None => {
+                    locks.retain(|_, lock| lock.strong_count() > 0);
+                    let lock = Arc::new(Mutex::new(()));
+                    locks.insert(intern::shared().intern(key), Arc::downgrade(&lock));
+                    lock
+                }
+            }
+        };
+        lock.lock_owned().await
+    }
+}
+
+/// Read-only relay queries; cheap to clone and never waits on a writer
+#[derive(Clone)]
+pub struct GroupReader {
+    relay: Arc<RelayService>,
+}
+
+impl GroupReader {
+    pub fn new(relay: Arc<RelayService>) -> Self {
+        Self { relay }
+    }
+
+    /// The authenticated client, for queries not covered here
+    pub fn client(&self) -> &Client {
+        self.relay.client()
+    }
+
+    /// The circuit breaker guarding relay calls, to refuse requests while the relay is down
+    pub fn relay_circuit(&self) -> Arc<RelayCircuit> {
+        self.relay.relay_circuit()
+    }
+
+    /// Limits the relay advertised in its NIP-11 document
+    pub fn relay_limits(&self) -> &RelayLimits {
+        self.relay.relay_limits()
+    }
+
+    pub async fn find_group_by_uuid(&self, uuid: &Uuid) -> Result<Option<String>> {
+        self.relay.find_group_by_uuid(uuid).await
+    }
+
+    pub async fn find_group_by_slug(&self, slug: &CommunitySlug) -> Result<Option<SlugOwner>> {
+        self.relay.find_group_by_slug(slug).await
+    }
+
+    pub async fn get_group_metadata(&self, group_id: &str) -> Result<GroupMetadata> {
+        self.relay.get_group_metadata(group_id).await
+    }
+
+    pub async fn get_group_snapshot(&self, group_id: &str) -> Result<GroupSnapshot> {
+        self.relay.get_group_snapshot(group_id).await
+    }
+
+    pub async fn get_group_admins(&self, group_id: &str) -> Result<Vec<PublicKey>> {
+        self.relay.get_group_admins(group_id).await
+    }
+
+    pub async fn is_group_admin(&self, group_id: &str, pubkey: &PublicKey) -> Result<bool> {
+        self.relay.is_group_admin(group_id, pubkey).await
+    }
+
+    pub async fn cached_group_admins(&self, group_id: &str) -> Result<Vec<PublicKey>> {
+        self.relay.cached_group_admins(group_id).await
+    }
+
+    pub async fn refresh_community(&self, community_id: &Uuid) -> Result<CommunityRefresh> {
+        self.relay.refresh_community(community_id).await
+    }
+
+    pub async fn verify_group_mappings(&self, sample: usize) -> Result<MappingCheck> {
+        self.relay.verify_group_mappings(sample).await
+    }
+
+    pub async fn groups_with_relay_admin(&self) -> Result<Vec<String>> {
+        self.relay.groups_with_relay_admin().await
+    }
+
+    pub async fn orphan_sweep_candidates(&self) -> Result<Vec<SweepCandidate>> {
+        self.relay.orphan_sweep_candidates().await
+    }
+
+    pub async fn backfill_candidates(&self) -> Result<Vec<BackfillCandidate>> {
+        self.relay.backfill_candidates().await
+    }
+
+    pub async fn prune_candidates(&self) -> Result<Vec<PruneGroup>> {
+        self.relay.prune_candidates().await
+    }
+
+    pub async fn member_activity(
+        &self,
+        group_id: &str,
+        since: u64,
+    ) -> Result<HashMap<String, u64>> {
+        self.relay.member_activity(group_id, since).await
+    }
+
+    pub async fn nearby_communities(&self, anchor_geohash: &str) -> Vec<IndexedCommunity> {
+        self.relay.nearby_communities(anchor_geohash).await
+    }
+
+    pub async fn search_communities(
+        &self,
+        query: &str,
+        limit: usize,
+        include_about: bool,
+    ) -> Vec<CommunityDiscoveryData> {
+        self.relay
+            .search_communities(query, limit, include_about)
+            .await
+    }
+
+    pub async fn fetch_discovery_maps(&self) -> Result<Vec<DiscoveryMapContent>> {
+        self.relay.fetch_discovery_maps().await
+    }
+
+    pub async fn subscribe_group_updates(
+        &self,
+        subscription_id: SubscriptionId,
+        group_ids: Vec<String>,
+    ) -> Result<()> {
+        self.relay
+            .subscribe_group_updates(subscription_id, group_ids)
+            .await
+    }
+
+    pub fn notifications(&self) -> tokio::sync::broadcast::Receiver<RelayPoolNotification> {
+        self.relay.notifications()
+    }
+}
+
+/// Whether a mutation is skipped because the writer only dry-runs, logging it if so
+fn skipped(mode: ExecutionMode, mutation: &'static str, target: &str) -> bool {
+    if mode == ExecutionMode::Execute {
+        return false;
+    }
+    tracing::info!("🧪 Read-only mode, not running {} for {}", mutation, target);
+    metrics::increment("peek_read_only_skipped_total", &[("mutation", mutation)]);
+    true
+}
+
+/// Exclusive write access to one group, held until dropped
+pub struct GroupWriteGuard<'a> {
+    relay: &'a RelayService,
+    mode: ExecutionMode,
+    _lock: OwnedMutexGuard<()>,
+}
+
+impl GroupWriteGuard<'_> {
+    /// Add a member as part of a locked read-modify-write, unless the writer only dry-runs
+    pub async fn add_member(&self, group_id: &str, user_pubkey: &str) -> Result<()> {
+        if skipped(self.mode, "add_group_member", group_id) {
+            return Ok(());
+        }
+        self.relay
+            .add_user_to_group(group_id, user_pubkey, false)
+            .await
+    }
+}
+
+// Join queue edits made while holding the group; a dry-running writer reads but never saves
+impl JoinQueueStore for GroupWriteGuard<'_> {
+    async fn load_join_queue(&self, group_id: &str) -> Result<JoinQueue> {
+        self.relay.load_join_queue(group_id).await
+    }
+
+    async fn save_join_queue(&self, group_id: &str, queue: &JoinQueue) -> Result<()> {
+        if skipped(self.mode, "save_join_queue", group_id) {
+            return Ok(());
+        }
+        self.relay.save_join_queue(group_id, queue).await
+    }
+
+    async fn list_join_queues(&self) -> Result<Vec<(String, JoinQueue)>> {
+        self.relay.list_join_queues().await
+    }
+}
+
+impl Deref for GroupWriteGuard<'_> {
+    type Target = RelayService;
+
+    fn deref(&self) -> &RelayService {
+        self.relay
+    }
+}
+
+/// Group mutations, serialized per group id rather than globally
+///
+/// Creation is keyed by community UUID, since the group id is only picked during creation;
+/// two validations racing to create the same community run one after the other.
+///
+/// A writer in `ExecutionMode::DryRun` (READ_ONLY_MODE) does every read but skips every
+/// mutation, answering as if it had succeeded, so a new deployment can be checked end to end
+/// before it changes anything on the relay.
+#[derive(Clone)]
+pub struct GroupWriter {
+    relay: Arc<RelayService>,
+    locks: Arc<GroupLocks>,
+    mode: ExecutionMode,
+}
+
+impl GroupWriter {
+    pub fn new(relay: Arc<RelayService>) -> Self {
+        Self {
+            relay,
+            locks: Arc::new(GroupLocks::default()),
+            mode: ExecutionMode::Execute,
+        }
+    }
+
+    pub fn with_mode(self, mode: ExecutionMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    /// An execution in `mode`, or a dry run when the writer only dry-runs
+    pub fn execution(&self, mode: ExecutionMode) -> Execution<'_, Client> {
+        Execution::new(self.client(), mode.limited_to(self.mode))
+    }
+
+    /// A reader over the same relay connection and caches
+    pub fn reader(&self) -> GroupReader {
+        GroupReader::new(self.relay.clone())
+    }
+
+    /// The authenticated client, for recording mutations in an `Execution`
+    pub fn client(&self) -> &Client {
+        self.relay.client()
+    }
+
+    /// Hold `group_id` for a read-modify-write sequence, such as a join queue edit
+    /// Calls made through the guard must not go back through the writer for the same group
+    pub async fn lock_group(&self, group_id: &str) -> GroupWriteGuard<'_> {
+        GroupWriteGuard {
+            relay: &self.relay,
+            mode: self.mode,
+            _lock: self.locks.lock(group_id).await,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_group(
+        &self,
+        community_id: Uuid,
+        name: String,
+        creator_pubkey: String,
+        location: Coordinates,
+        active_until: Option<Timestamp>,
+        max_members: Option<u32>,
+        age_restricted: bool,
+        country: Option<String>,
+    ) -> Result<CreatedGroup> {
+        if skipped(self.mode, "create_group", &community_id.to_string()) {
+            return Ok(CreatedGroup {
+                group_id: self.relay.new_group_id(),
+                name: fallback_community_name(&community_id),
+            });
+        }
+        self.lock_group(&community_id.to_string())
+            .await
+            .create_group(
+                community_id,
+                name,
+                creator_pubkey,
+                location,
+                active_until,
+                max_members,
+                age_restricted,
+                country,
+            )
+            .await
+    }
+
+    pub async fn add_group_member(
+        &self,
+        group_id: &str,
+        user_pubkey: &str,
+        is_admin: bool,
+    ) -> Result<()> {
+        if skipped(self.mode, "add_group_member", group_id) {
+            return Ok(());
+        }
+        self.lock_group(group_id)
+            .await
+            .add_group_member(group_id, user_pubkey, is_admin)
+            .await
+    }
+
+    pub async fn remove_group_member(&self, group_id: &str, user_pubkey: &str) -> Result<()> {
+        if skipped(self.mode, "remove_group_member", group_id) {
+            return Ok(());
+        }
+        self.lock_group(group_id)
+            .await
+            .remove_group_member(group_id, user_pubkey)
+            .await
+    }
+
+    /// Claims of the same slug are serialized too, so two groups racing for it are checked in turn
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_group_metadata(
+        &self,
+        group_id: &str,
+        join_mode: Option<JoinMode>,
+        max_members: Option<u32>,
+        text: &MetadataText,
+        slug: Option<&str>,
+        join_notifications: Option<bool>,
+        age_restricted: Option<bool>,
+        inactive_prune_days: Option<u32>,
+        quiet_hours: Option<QuietHours>,
+    ) -> Result<()> {
+        if skipped(self.mode, "update_group_metadata", group_id) {
+            return Ok(());
+        }
+        let _slug_lock = match slug.filter(|slug| !slug.is_empty()) {
+            Some(slug) => Some(self.locks.lock(&format!("slug:{}", slug)).await),
+            None => None,
+        };
+        self.lock_group(group_id)
+            .await
+            .update_group_metadata(
+                group_id,
+                join_mode,
+                max_members,
+                text,
+                slug,
+                join_notifications,
+                age_restricted,
+                inactive_prune_days,
+                quiet_hours,
+            )
+            .await
+    }
+
+    pub async fn set_group_archived(&self, group_id: &str, archived: bool) -> Result<()> {
+        if skipped(self.mode, "set_group_archived", group_id) {
+            return Ok(());
+        }
+        self.lock_group(group_id)
+            .await
+            .set_group_archived(group_id, archived)
+            .await
+    }
+
+    pub async fn set_venue_hours(&self, group_id: &str, hours: Option<&VenueHours>) -> Result<()> {
+        if skipped(self.mode, "set_venue_hours", group_id) {
+            return Ok(());
+        }
+        self.lock_group(group_id)
+            .await
+            .set_venue_hours(group_id, hours)
+            .await
+    }
+
+    pub async fn delete_group(
+        &self,
+        group_id: &str,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<()> {
+        self.lock_group(group_id)
+            .await
+            .delete_group(group_id, execution)
+            .await
+    }
+
+    pub async fn add_uuid_tag(
+        &self,
+        group_id: &str,
+        community_id: Uuid,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<()> {
+        self.lock_group(group_id)
+            .await
+            .add_uuid_tag(group_id, community_id, execution)
+            .await
+    }
+
+    pub async fn add_group_anchor(
+        &self,
+        group_id: &str,
+        location: Coordinates,
+        max_anchors: usize,
+    ) -> Result<usize> {
+        if skipped(self.mode, "add_group_anchor", group_id) {
+            return Ok(self.relay.get_group_metadata(group_id).await?.anchors.len());
+        }
+        self.lock_group(group_id)
+            .await
+            .add_group_anchor(group_id, location, max_anchors)
+            .await
+    }
+
+    pub async fn remove_relay_admin(
+        &self,
+        group_id: &str,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<bool> {
+        self.lock_group(group_id)
+            .await
+            .remove_relay_admin(group_id, execution)
+            .await
+    }
+
+    /// Expired communities take no new members, so the sweep runs without per-group locks
+    pub async fn archive_expired_groups(&self) -> Result<usize> {
+        if skipped(self.mode, "archive_expired_groups", "expired groups") {
+            return Ok(0);
+        }
+        self.relay.archive_expired_groups().await
+    }
+}
+
+// The expiry sweeper's saves wait for in-flight join queue edits to the same group
+impl JoinQueueStore for GroupWriter {
+    async fn load_join_queue(&self, group_id: &str) -> Result<JoinQueue> {
+        self.relay.load_join_queue(group_id).await
+    }
+
+    async fn save_join_queue(&self, group_id: &str, queue: &JoinQueue) -> Result<()> {
+        JoinQueueStore::save_join_queue(&self.lock_group(group_id).await, group_id, queue).await
+    }
+
+    async fn list_join_queues(&self) -> Result<Vec<(String, JoinQueue)>> {
+        self.relay.list_join_queues().await
+    }
+}
+
+// Each add takes the group's lock, like a location-validated join
+impl BulkAddTarget for GroupWriter {
+    async fn group_admins(&self, group_id: &str) -> Result<Vec<PublicKey>> {
+        self.relay.get_group_admins(group_id).await
+    }
+
+    async fn group_snapshot(&self, group_id: &str) -> Result<GroupSnapshot> {
+        self.relay.get_group_snapshot(group_id).await
+    }
+
+    async fn add_member(&self, group_id: &str, pubkey: &PublicKey) -> Result<()> {
+        self.add_group_member(group_id, &pubkey.to_hex(), false)
+            .await
+    }
+}
+
+impl ExportSource for GroupReader {
+    async fn group_admins(&self, group_id: &str) -> Result<Vec<PublicKey>> {
+        self.relay.get_group_admins(group_id).await
+    }
+
+    async fn group_snapshot(&self, group_id: &str) -> Result<GroupSnapshot> {
+        self.relay.get_group_snapshot(group_id).await
+    }
+
+    async fn member_join_times(&self, group_id: &str) -> Result<HashMap<String, Timestamp>> {
+        self.relay.member_join_times(group_id).await
+    }
+}
+
+/// Discovery map publishing and the relay-authored data behind discovery records
+/// In `ExecutionMode::DryRun` it only logs what it would publish
+#[derive(Clone)]
+pub struct DiscoveryPublisher {
+    relay: Arc<RelayService>,
+    mode: ExecutionMode,
+}
+
+impl DiscoveryPublisher {
+    pub fn new(relay: Arc<RelayService>) -> Self {
+        Self {
+            relay,
+            mode: ExecutionMode::Execute,
+        }
+    }
+
+    pub fn with_mode(self, mode: ExecutionMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    /// The authenticated client, for recording publishes in an `Execution`
+    pub fn client(&self) -> &Client {
+        self.relay.client()
+    }
+
+    /// An execution in `mode`, or a dry run when the publisher only dry-runs
+    pub fn execution(&self, mode: ExecutionMode) -> Execution<'_, Client> {
+        Execution::new(self.client(), mode.limited_to(self.mode))
+    }
+
+    /// Rebuild every discovery map from current group metadata
+    pub async fn publish_discovery_map(&self, execution: &mut Execution<'_, Client>) -> Result<()> {
+        self.relay.publish_discovery_map(None, execution).await
+    }
+
+    /// The discovery map as current group metadata says it should be published
+    pub async fn live_discovery_map(&self) -> Result<DiscoveryMapContent> {
+        self.relay.live_discovery_map().await
+    }
+
+    /// What was last published, per configured map
+    pub async fn fetch_discovery_maps(&self) -> Result<Vec<DiscoveryMapContent>> {
+        self.relay.fetch_discovery_maps().await
+    }
+
+    pub async fn publish_discovery_map_content(
+        &self,
+        map: &DiscoveryMapContent,
+        execution: &mut Execution<'_, Client>,
+    ) -> Result<()> {
+        self.relay
+            .publish_discovery_map_content(map, execution)
+            .await
+    }
+
+    pub fn locality_resolver(&self) -> Arc<LocalityResolver> {
+        self.relay.locality_resolver()
+    }
+
+    pub async fn discovery_geohashes(&self) -> Vec<String> {
+        self.relay.discovery_geohashes().await
+    }
+
+    pub async fn apply_localities(&self) {
+        self.relay.apply_localities().await
+    }
+
+    pub async fn fetch_app_data(&self, d_tag: &str) -> Result<Option<String>> {
+        self.relay.fetch_app_data(d_tag).await
+    }
+
+    pub async fn publish_app_data(&self, d_tag: &str, content: String) -> Result<()> {
+        if skipped(self.mode, "publish_app_data", d_tag) {
+            return Ok(());
+        }
+        self.relay.publish_app_data(d_tag, content).await
+    }
+
+    pub async fn fetch_sealed_app_data(&self, d_tag: &str) -> Result<Option<String>> {
+        self.relay.fetch_sealed_app_data(d_tag).await
+    }
+
+    pub async fn publish_sealed_app_data(&self, d_tag: &str, content: String) -> Result<()> {
+        if skipped(self.mode, "publish_sealed_app_data", d_tag) {
+            return Ok(());
+        }
+        self.relay.publish_sealed_app_data(d_tag, content).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    const PROMPT: Duration = Duration::from_millis(200);
+
+    #[tokio::test]
+    async fn test_slow_write_only_holds_up_its_own_group() {
+        let locks = Arc::new(GroupLocks::default());
+
+        // A create_group stuck on relay sends, holding its community's lock
+        let (held_tx, held_rx) = oneshot::channel();
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let slow_create = tokio::spawn({
+            let locks = locks.clone();
+            async move {
+                let _guard = locks.lock("community-a").await;
+                held_tx.send(()).unwrap();
+                let _ = release_rx.await;
+            }
+        });
+        held_rx.await.unwrap();
+
+        // Writes to other groups go ahead, writes to the same one wait their turn
+        assert!(tokio::time::timeout(PROMPT, locks.lock("peek-b"))
+            .await
+            .is_ok());
+        assert!(tokio::time::timeout(PROMPT, locks.lock("community-a"))
+            .await
+            .is_err());
+
+        release_tx.send(()).unwrap();
+        slow_create.await.unwrap();
+        assert!(tokio::time::timeout(PROMPT, locks.lock("community-a"))
+            .await
+            .is_ok());
+        assert!(locks.locks.lock().unwrap().len() <= 1);
+    }
+
+    #[tokio::test]
+    async fn test_interned_and_owned_keys_share_a_lock() {
+        let locks = GroupLocks::default();
+        let owned = String::from("peek-shared");
+        let _guard = locks.lock(&owned).await;
+        let interned = intern::shared().intern("peek-shared");
+        assert!(tokio::time::timeout(PROMPT, locks.lock(&interned))
+            .await
+            .is_err());
+    }
+}
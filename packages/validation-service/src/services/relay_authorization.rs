@@ -0,0 +1,254 @@
+//! Whether the groups relay still accepts management events signed with the relay key
+//!
+//! When the relay's admin list is rotated without the service's key, every create, add and
+//! remove comes back "restricted:" and joins fail one by one with a generic error. The first
+//! such refusal marks the key unauthorized: /health reports it, later mutations fail fast with
+//! SERVICE_MISCONFIGURED, and one mutation is let through now and then to notice a fix.
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+use super::metrics;
+use super::relay::RelayError;
+use crate::libraries::clock::Clock;
+
+/// While unauthorized, one mutation per interval still reaches the relay to detect a fix
+pub const RECHECK_INTERVAL_SECS: u64 = 60;
+
+/// NIP-29 moderation events, which only a group or relay admin may publish
+pub fn is_group_management(kind: Kind) -> bool {
+    (9000..=9020).contains(&kind.as_u16())
+}
+
+/// Whether a relay's refusal is about who signed the event rather than what it contains
+pub fn is_authorization_rejection(error_msg: &str) -> bool {
+    ["restricted:", "blocked:"]
+        .iter()
+        .any(|prefix| error_msg.contains(prefix))
+}
+
+/// Relay key status as reported on /health
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RelayKeyStatus {
+    Authorized,
+    /// The relay refused a management event from the relay key
+    Unauthorized {
+        reason: String,
+        since: u64,
+    },
+}
+
+impl RelayKeyStatus {
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, Self::Unauthorized { .. })
+    }
+}
+
+struct Refusal {
+    reason: String,
+    since: u64,
+    // Last time a mutation was let through to the relay while unauthorized
+    last_attempt: u64,
+}
+
+/// Shared flag flipped by relay refusals and cleared by the next accepted mutation
+pub struct RelayKeyAuthorization {
+    clock: Arc<dyn Clock>,
+    refusal: Mutex<Option<Refusal>>,
+}
+
+impl RelayKeyAuthorization {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            refusal: Mutex::new(None),
+        }
+    }
+
+    pub fn status(&self) -> RelayKeyStatus {
+        match &*self.refusal.lock().unwrap() {
+            None => RelayKeyStatus::Authorized,
+            Some(refusal) => RelayKeyStatus::Unauthorized {
+                reason: refusal.reason.clone(),
+                since: refusal.since,
+            },
+        }
+    }
+
+    /// Send a management event through `send`, or refuse it without sending while the relay
+    /// is known to reject the key; other events go straight through
+    pub async fn send<F, Fut>(&self, kind: Kind, send: F) -> Result<(), RelayError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(), RelayError>>,
+    {
+        if !is_group_management(kind) {
+            return send().await;
+        }
+        if let Some(reason) = self.short_circuit() {
+            metrics::increment("peek_relay_key_short_circuits_total", &[]);
+            return Err(RelayError::RelayKeyUnauthorized(reason));
+        }
+
+        match send().await {
+            Ok(()) => {
+                self.record_accepted();
+                Ok(())
+            }
+            Err(e) => {
+                // The relay's own message, without our error wrapper's prefix
+                let error_msg = match &e {
+                    RelayError::NostrSdk(inner) => inner.to_string(),
+                    other => other.to_string(),
+                };
+                if !is_authorization_rejection(&error_msg) {
+                    return Err(e);
+                }
+                self.record_refusal(&error_msg);
+                Err(RelayError::RelayKeyUnauthorized(error_msg))
+            }
+        }
+    }
+
+    /// The refusal reason, unless the key is authorized or a recheck is due
+    fn short_circuit(&self) -> Option<String> {
+        let now = self.clock.now_unix();
+        let mut refusal = self.refusal.lock().unwrap();
+        let refusal = refusal.as_mut()?;
+        if now.saturating_sub(refusal.last_attempt) >= RECHECK_INTERVAL_SECS {
+            refusal.last_attempt = now;
+            return None;
+        }
+        Some(refusal.reason.clone())
+    }
+
+    fn record_refusal(&self, reason: &str) {
+        let now = self.clock.now_unix();
+        let mut refusal = self.refusal.lock().unwrap();
+        match refusal.as_mut() {
+            Some(refusal) => {
+                refusal.reason = reason.to_string();
+                refusal.last_attempt = now;
+            }
+            None => {
+                tracing::error!(
+                    "🚫 The relay refused a group management event from the relay key ({}); \
+                     failing mutations with SERVICE_MISCONFIGURED until it is accepted again",
+                    reason
+                );
+                *refusal = Some(Refusal {
+                    reason: reason.to_string(),
+                    since: now,
+                    last_attempt: now,
+                });
+            }
+        }
+        metrics::set_gauge("peek_relay_key_unauthorized", &[], 1);
+    }
+
+    fn record_accepted(&self) {
+        if self.refusal.lock().unwrap().take().is_some() {
+            tracing::info!("✅ The relay accepts group management events from the relay key again");
+        }
+        metrics::set_gauge("peek_relay_key_unauthorized", &[], 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const PUT_USER: u16 = 9000;
+
+    fn restricted() -> RelayError {
+        RelayError::Other("event not published: restricted: not a relay admin".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_restricted_refusal_flags_the_key_and_short_circuits_mutations() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let authorization = RelayKeyAuthorization::new(clock.clone());
+        let sent = &AtomicUsize::new(0);
+        let refusing = move || async move {
+            sent.fetch_add(1, Ordering::SeqCst);
+            Err(restricted())
+        };
+
+        let first = authorization.send(Kind::from(PUT_USER), refusing).await;
+        assert!(matches!(first, Err(RelayError::RelayKeyUnauthorized(_))));
+        assert_eq!(
+            authorization.status(),
+            RelayKeyStatus::Unauthorized {
+                reason: "event not published: restricted: not a relay admin".to_string(),
+                since: 1_760_000_000,
+            }
+        );
+
+        // Later mutations fail without reaching the relay
+        clock.advance(10);
+        let second = authorization.send(Kind::from(PUT_USER), refusing).await;
+        assert!(matches!(second, Err(RelayError::RelayKeyUnauthorized(_))));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+
+        // Events the relay accepts from anyone are not held back
+        let app_data = authorization
+            .send(Kind::from(30078), || async { Ok(()) })
+            .await;
+        assert!(app_data.is_ok());
+        assert!(authorization.status().is_unauthorized());
+    }
+
+    #[tokio::test]
+    async fn test_accepted_recheck_clears_the_flag() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let authorization = RelayKeyAuthorization::new(clock.clone());
+        let _ = authorization
+            .send(Kind::from(PUT_USER), || async { Err(restricted()) })
+            .await;
+
+        // A recheck that is refused again keeps the flag and restarts the interval
+        clock.advance(RECHECK_INTERVAL_SECS);
+        let _ = authorization
+            .send(Kind::from(PUT_USER), || async {
+                Err(RelayError::Other("blocked: key revoked".to_string()))
+            })
+            .await;
+        assert!(authorization.status().is_unauthorized());
+        clock.advance(1);
+        let held = authorization
+            .send(Kind::from(PUT_USER), || async { Ok(()) })
+            .await;
+        assert!(held.is_err());
+
+        clock.advance(RECHECK_INTERVAL_SECS);
+        let recheck = authorization
+            .send(Kind::from(PUT_USER), || async { Ok(()) })
+            .await;
+        assert!(recheck.is_ok());
+        assert_eq!(authorization.status(), RelayKeyStatus::Authorized);
+    }
+
+    #[tokio::test]
+    async fn test_other_failures_leave_the_key_authorized() {
+        let authorization = RelayKeyAuthorization::new(Arc::new(ManualClock::new(0)));
+        for message in [
+            "rate-limited: slow down",
+            "relay not connected",
+            "invalid: bad tag",
+        ] {
+            let result = authorization
+                .send(Kind::from(PUT_USER), move || async move {
+                    Err(RelayError::Other(message.to_string()))
+                })
+                .await;
+            assert!(matches!(result, Err(RelayError::Other(_))));
+        }
+        assert_eq!(authorization.status(), RelayKeyStatus::Authorized);
+    }
+}
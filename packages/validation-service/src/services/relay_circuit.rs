@@ -0,0 +1,269 @@
+//! Circuit breaker around the groups relay, so an outage is answered at once
+//!
+//! With the relay unreachable every read waits out its timeout and every request burns its
+//! whole deadline, while gift wraps keep arriving. After enough consecutive relay calls end
+//! with the relay disconnected, the circuit opens: requests are refused immediately with a
+//! retry hint until the cool-down passes, then a single probe call decides whether to close.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use super::metrics;
+use super::relay::RelayError;
+use crate::libraries::clock::Clock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed { consecutive_failures: u32 },
+    Open { until: u64 },
+    // One probe call is out; the circuit closes or reopens on its outcome
+    HalfOpen { probe_started: u64 },
+}
+
+/// Circuit state as reported on /health
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum CircuitStatus {
+    Closed { consecutive_failures: u32 },
+    Open { retry_after_secs: u64 },
+    HalfOpen,
+}
+
+impl CircuitStatus {
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Self::Closed { .. })
+    }
+}
+
+pub struct RelayCircuit {
+    clock: Arc<dyn Clock>,
+    failure_threshold: u32,
+    cool_down_secs: u64,
+    state: Mutex<State>,
+}
+
+impl RelayCircuit {
+    /// Opens after `failure_threshold` consecutive unreachable calls; 0 disables the breaker
+    pub fn new(clock: Arc<dyn Clock>, failure_threshold: u32, cool_down: Duration) -> Self {
+        Self {
+            clock,
+            failure_threshold,
+            cool_down_secs: cool_down.as_secs().max(1),
+            state: Mutex::new(State::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    /// Seconds until a refused request is worth retrying, or None if it may go ahead
+    pub fn retry_after(&self) -> Option<u64> {
+        let now = self.clock.now_unix();
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => None,
+            State::Open { until } => (until > now).then(|| until - now),
+            State::HalfOpen { probe_started } => self.probe_pending(probe_started, now),
+        }
+    }
+
+    /// Let a relay call through, or refuse it while the circuit is open
+    /// Once the cool-down has passed, the first call through becomes the half-open probe
+    pub fn admit(&self) -> Result<(), RelayError> {
+        let now = self.clock.now_unix();
+        let mut state = self.state.lock().unwrap();
+        let refused = match *state {
+            State::Closed { .. } => None,
+            State::Open { until } if until > now => Some(until - now),
+            // A probe that never reported back does not keep the circuit half-open forever
+            State::HalfOpen { probe_started } => self.probe_pending(probe_started, now),
+            State::Open { .. } => None,
+        };
+        if let Some(retry_after_secs) = refused {
+            metrics::increment("peek_relay_circuit_rejections_total", &[]);
+            return Err(RelayError::CircuitOpen { retry_after_secs });
+        }
+        if !matches!(*state, State::Closed { .. }) {
+            tracing::info!("🔌 Relay circuit half-open, probing the relay");
+            *state = State::HalfOpen { probe_started: now };
+            self.publish(&state);
+        }
+        Ok(())
+    }
+
+    /// Record how an admitted relay call ended: whether the relay was reachable
+    pub fn record(&self, reachable: bool) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+        let now = self.clock.now_unix();
+        let mut state = self.state.lock().unwrap();
+        let next = match (*state, reachable) {
+            (State::Closed { .. }, true) => State::Closed {
+                consecutive_failures: 0,
+            },
+            (
+                State::Closed {
+                    consecutive_failures,
+                },
+                false,
+            ) if consecutive_failures + 1 < self.failure_threshold => State::Closed {
+                consecutive_failures: consecutive_failures + 1,
+            },
+            (State::Closed { .. }, false) | (State::HalfOpen { .. }, false) => {
+                tracing::warn!(
+                    "🔌 Relay unreachable, opening the circuit for {}s",
+                    self.cool_down_secs
+                );
+                metrics::increment("peek_relay_circuit_opened_total", &[]);
+                State::Open {
+                    until: now + self.cool_down_secs,
+                }
+            }
+            (State::HalfOpen { .. }, true) => {
+                tracing::info!("🔌 Relay reachable again, closing the circuit");
+                State::Closed {
+                    consecutive_failures: 0,
+                }
+            }
+            // Calls admitted before the circuit opened do not decide anything
+            (State::Open { until }, _) => State::Open { until },
+        };
+        *state = next;
+        self.publish(&state);
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        let now = self.clock.now_unix();
+        match *self.state.lock().unwrap() {
+            State::Closed {
+                consecutive_failures,
+            } => CircuitStatus::Closed {
+                consecutive_failures,
+            },
+            State::Open { until } => CircuitStatus::Open {
+                retry_after_secs: until.saturating_sub(now),
+            },
+            State::HalfOpen { .. } => CircuitStatus::HalfOpen,
+        }
+    }
+
+    /// Seconds left for an outstanding probe to report, if it is still expected to
+    fn probe_pending(&self, probe_started: u64, now: u64) -> Option<u64> {
+        let deadline = probe_started + self.cool_down_secs;
+        (deadline > now).then(|| deadline - now)
+    }
+
+    fn publish(&self, state: &State) {
+        let value = match state {
+            State::Closed { .. } => 0,
+            State::HalfOpen { .. } => 1,
+            State::Open { .. } => 2,
+        };
+        metrics::set_gauge("peek_relay_circuit_state", &[], value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Groups relay that is either up or down; each call reports back to the circuit
+    struct FakeRelay {
+        up: AtomicBool,
+    }
+
+    impl FakeRelay {
+        fn call(&self, circuit: &RelayCircuit) -> Result<(), RelayError> {
+            circuit.admit()?;
+            let up = self.up.load(Ordering::SeqCst);
+            circuit.record(up);
+            if up {
+                Ok(())
+            } else {
+                Err(RelayError::Other("relay not connected".to_string()))
+            }
+        }
+    }
+
+    #[test]
+    fn test_circuit_opens_probes_and_closes() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let circuit = RelayCircuit::new(clock.clone(), 3, Duration::from_secs(30));
+        let relay = FakeRelay {
+            up: AtomicBool::new(false),
+        };
+
+        // Closed: failures are counted until the threshold
+        for failures in 1..3 {
+            assert!(matches!(relay.call(&circuit), Err(RelayError::Other(_))));
+            assert_eq!(
+                circuit.status(),
+                CircuitStatus::Closed {
+                    consecutive_failures: failures
+                }
+            );
+        }
+        assert!(relay.call(&circuit).is_err());
+        assert_eq!(
+            circuit.status(),
+            CircuitStatus::Open {
+                retry_after_secs: 30
+            }
+        );
+
+        // Open: refused at once with a retry hint, without reaching the relay
+        clock.advance(10);
+        assert_eq!(circuit.retry_after(), Some(20));
+        assert!(matches!(
+            relay.call(&circuit),
+            Err(RelayError::CircuitOpen {
+                retry_after_secs: 20
+            })
+        ));
+
+        // Half-open: the first call after the cool-down probes, and a failed probe reopens
+        clock.advance(20);
+        assert_eq!(circuit.retry_after(), None);
+        assert!(matches!(relay.call(&circuit), Err(RelayError::Other(_))));
+        assert_eq!(circuit.retry_after(), Some(30));
+
+        // A successful probe closes the circuit
+        clock.advance(30);
+        relay.up.store(true, Ordering::SeqCst);
+        circuit.admit().unwrap();
+        assert_eq!(circuit.status(), CircuitStatus::HalfOpen);
+        // Only one probe is out at a time
+        assert!(matches!(
+            circuit.admit(),
+            Err(RelayError::CircuitOpen { .. })
+        ));
+        circuit.record(true);
+        assert_eq!(
+            circuit.status(),
+            CircuitStatus::Closed {
+                consecutive_failures: 0
+            }
+        );
+        assert!(relay.call(&circuit).is_ok());
+    }
+
+    #[test]
+    fn test_success_resets_the_count_and_zero_disables() {
+        let clock = Arc::new(ManualClock::new(0));
+        let circuit = RelayCircuit::new(clock.clone(), 2, Duration::from_secs(30));
+        circuit.record(false);
+        circuit.record(true);
+        circuit.record(false);
+        assert!(circuit.status().is_closed());
+
+        let disabled = RelayCircuit::new(clock, 0, Duration::from_secs(30));
+        for _ in 0..10 {
+            disabled.admit().unwrap();
+            disabled.record(false);
+        }
+        assert!(disabled.status().is_closed());
+    }
+}
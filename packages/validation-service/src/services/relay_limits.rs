@@ -0,0 +1,299 @@
+//! Relay limits advertised in the NIP-11 information document
+//!
+//! Relays with a lower `max_limit` than a filter asks for silently truncate the result, and
+//! events with more tags than `max_event_tags` are rejected. The document is fetched once at
+//! connect time; fetches page through `until` windows and sends check tag counts up front.
+
+use nostr_sdk::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// How long to wait for a relay's NIP-11 document
+const NIP11_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The `limitation` object of a NIP-11 document; absent fields mean the relay did not say
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RelayLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_event_tags: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_message_length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_content_length: Option<usize>,
+    // NIP-42 AUTH before any REQ or EVENT, whatever the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_required: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Nip11Document {
+    #[serde(default)]
+    limitation: RelayLimits,
+}
+
+/// Why a new member must NIP-42 AUTH before reading a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScope {
+    /// The group is private, so the relay only serves it to authenticated members
+    Group,
+    /// The relay requires AUTH for every subscription
+    Relay,
+}
+
+/// The AUTH a member needs before subscribing to a group, or None if they can read it as is
+pub fn read_auth_scope(group_is_public: bool, limits: &RelayLimits) -> Option<AuthScope> {
+    if limits.auth_required == Some(true) {
+        Some(AuthScope::Relay)
+    } else if !group_is_public {
+        Some(AuthScope::Group)
+    } else {
+        None
+    }
+}
+
+/// An event with more tags than the relay accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Event has {tags} tags, relay accepts at most {max}")]
+pub struct TooManyTags {
+    pub tags: usize,
+    pub max: usize,
+}
+
+impl RelayLimits {
+    /// Largest filter limit the relay serves in full, capped at `desired`
+    pub fn clamp_limit(&self, desired: usize) -> usize {
+        match self.max_limit {
+            Some(max) if max > 0 => desired.min(max),
+            _ => desired,
+        }
+    }
+
+    /// Reject an event the relay would refuse for its tag count
+    pub fn check_tags(&self, event: &Event) -> Result<(), TooManyTags> {
+        match self.max_event_tags {
+            Some(max) if event.tags.len() > max => Err(TooManyTags {
+                tags: event.tags.len(),
+                max,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// HTTP URL serving a relay's NIP-11 document
+pub fn nip11_url(relay_url: &str) -> String {
+    if let Some(rest) = relay_url.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else if let Some(rest) = relay_url.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else {
+        relay_url.to_string()
+    }
+}
+
+/// Fetch the limits a relay advertises
+pub async fn fetch_relay_limits(relay_url: &str) -> anyhow::Result<RelayLimits> {
+    let document: Nip11Document = reqwest::Client::new()
+        .get(nip11_url(relay_url))
+        .header(reqwest::header::ACCEPT, "application/nostr+json")
+        .timeout(NIP11_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(document.limitation)
+}
+
+/// Up to `desired` events matching `filter`, newest first, in pages the relay serves in full
+///
+/// Each page asks for at most the relay's `max_limit` and the next one ends (`until`) at the
+/// oldest event seen so far. Events sharing that second are fetched again and deduplicated; a
+/// page that brings nothing new ends the walk, so a relay returning less than it was asked for
+/// cannot loop it.
+pub async fn fetch_paginated<F, Fut, E>(
+    filter: Filter,
+    desired: usize,
+    limits: &RelayLimits,
+    mut fetch: F,
+) -> Result<Vec<Event>, E>
+where
+    F: FnMut(Filter) -> Fut,
+    Fut: Future<Output = Result<Vec<Event>, E>>,
+{
+    let page_size = limits.clamp_limit(desired);
+    let mut seen = HashSet::new();
+    let mut events = Vec::new();
+    let mut until = filter.until;
+
+    while events.len() < desired {
+        let mut page_filter = filter.clone().limit(page_size);
+        if let Some(until) = until {
+            page_filter = page_filter.until(until);
+        }
+        let page = fetch(page_filter).await?;
+        let full = page.len() >= page_size;
+        let oldest = page.iter().map(|event| event.created_at).min();
+
+        let before = events.len();
+        for event in page {
+            if events.len() < desired && seen.insert(event.id) {
+                events.push(event);
+            }
+        }
+        if !full || events.len() == before || page_size == desired {
+            break;
+        }
+        until = oldest;
+    }
+
+    events.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Relay holding `count` events one second apart that serves at most `max_limit` per query
+    struct TruncatingRelay {
+        events: Vec<Event>,
+        max_limit: usize,
+        queries: Mutex<Vec<Filter>>,
+    }
+
+    impl TruncatingRelay {
+        fn new(count: u64, max_limit: usize) -> Self {
+            let keys = Keys::generate();
+            let events = (0..count)
+                .map(|n| {
+                    EventBuilder::new(Kind::from(39000), "")
+                        .tag(Tag::identifier(format!("peek-{}", n)))
+                        .custom_created_at(Timestamp::from(1_760_000_000 + n))
+                        .sign_with_keys(&keys)
+                        .unwrap()
+                })
+                .collect();
+            Self {
+                events,
+                max_limit,
+                queries: Mutex::new(Vec::new()),
+            }
+        }
+
+        async fn fetch(&self, filter: Filter) -> Result<Vec<Event>, std::convert::Infallible> {
+            self.queries.lock().unwrap().push(filter.clone());
+            let mut matching: Vec<Event> = self
+                .events
+                .iter()
+                .filter(|event| filter.until.is_none_or(|until| event.created_at <= until))
+                .cloned()
+                .collect();
+            matching.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            let limit = filter.limit.unwrap_or(usize::MAX).min(self.max_limit);
+            matching.truncate(limit);
+            Ok(matching)
+        }
+    }
+
+    /// Serve one NIP-11 document on a local port, returning its ws:// URL
+    async fn serve_nip11(document: serde_json::Value) -> String {
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::get(move || async move { axum::Json(document) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("ws://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_discovery_fetch_paginates_under_a_low_max_limit() {
+        let relay_url = serve_nip11(serde_json::json!({
+            "name": "tiny relay",
+            "limitation": { "max_limit": 100, "max_event_tags": 40, "auth_required": true }
+        }))
+        .await;
+        let limits = fetch_relay_limits(&relay_url).await.unwrap();
+        assert_eq!(limits.max_limit, Some(100));
+        assert_eq!(limits.max_event_tags, Some(40));
+        assert_eq!(limits.auth_required, Some(true));
+        assert_eq!(limits.clamp_limit(1000), 100);
+
+        let relay = TruncatingRelay::new(250, 100);
+        let discovery_filter = Filter::new().kind(Kind::from(39000));
+        let events = fetch_paginated(discovery_filter, 1000, &limits, |filter| {
+            relay.fetch(filter)
+        })
+        .await
+        .unwrap();
+
+        // A single limit(1000) query would have come back with only the newest 100
+        assert_eq!(events.len(), 250);
+        let queries = relay.queries.lock().unwrap();
+        assert_eq!(queries.len(), 3);
+        assert!(queries.iter().all(|filter| filter.limit == Some(100)));
+        assert_eq!(queries[0].until, None);
+        assert_eq!(queries[1].until, Some(Timestamp::from(1_760_000_150)));
+        assert_eq!(queries[2].until, Some(Timestamp::from(1_760_000_051)));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_without_advertised_limits_is_a_single_query() {
+        let relay = TruncatingRelay::new(30, usize::MAX);
+        let events = fetch_paginated(
+            Filter::new().kind(Kind::from(39000)),
+            1000,
+            &RelayLimits::default(),
+            |filter| relay.fetch(filter),
+        )
+        .await
+        .unwrap();
+        assert_eq!(events.len(), 30);
+        assert_eq!(relay.queries.lock().unwrap().len(), 1);
+
+        // Never more than asked for, even when pages are smaller
+        let capped = RelayLimits {
+            max_limit: Some(10),
+            ..RelayLimits::default()
+        };
+        let events = fetch_paginated(
+            Filter::new().kind(Kind::from(39000)),
+            25,
+            &capped,
+            |filter| relay.fetch(filter),
+        )
+        .await
+        .unwrap();
+        assert_eq!(events.len(), 25);
+    }
+
+    #[test]
+    fn test_tag_count_is_checked_against_max_event_tags() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new(Kind::from(9002), "")
+            .tags((0..5).map(|n| Tag::custom(TagKind::Custom("rule".into()), [n.to_string()])))
+            .sign_with_keys(&keys)
+            .unwrap();
+        let limits = RelayLimits {
+            max_event_tags: Some(4),
+            ..RelayLimits::default()
+        };
+
+        assert_eq!(
+            limits.check_tags(&event),
+            Err(TooManyTags { tags: 5, max: 4 })
+        );
+        assert_eq!(RelayLimits::default().check_tags(&event), Ok(()));
+        assert_eq!(nip11_url("wss://relay.example/"), "https://relay.example/");
+    }
+}
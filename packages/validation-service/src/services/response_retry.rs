@@ -0,0 +1,324 @@
+use nostr_sdk::prelude::*;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use super::gift_wrap::GiftWrapService;
+use super::metrics;
+
+/// A response whose first delivery failed, kept until it is delivered or gives up
+#[derive(Debug, Clone)]
+pub struct QueuedResponse {
+    pub recipient: PublicKey,
+    pub kind: Kind,
+    pub content: String,
+    pub tags: Vec<Tag>,
+    pub correlation_id: String,
+    pub inbox_relays: Vec<String>,
+    pub expiration: Timestamp,
+    /// Delivery attempts made so far (the failed initial send counts as one)
+    pub attempts: u32,
+    first_failed_at: Instant,
+}
+
+impl QueuedResponse {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        recipient: PublicKey,
+        kind: Kind,
+        content: String,
+        tags: Vec<Tag>,
+        correlation_id: String,
+        inbox_relays: Vec<String>,
+        expiration: Timestamp,
+    ) -> Self {
+        Self {
+            recipient,
+            kind,
+            content,
+            tags,
+            correlation_id,
+            inbox_relays,
+            expiration,
+            attempts: 1,
+            first_failed_at: Instant::now(),
+        }
+    }
+}
+
+/// Backoff schedule for queued responses
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Give up once this long has passed since the first failure
+    pub max_elapsed: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before the next attempt, doubling per attempt already made
+    fn delay_after(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Delivers a queued response; implemented over GiftWrapService in production
+pub trait ResponseSender: Send + Sync + 'static {
+    fn send(&self, response: &QueuedResponse) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Sends queued responses as gift wraps through the shared client
+pub struct GiftWrapResponseSender {
+    pub gift_wrap_service: Arc<GiftWrapService>,
+    pub client: Client,
+}
+
+impl ResponseSender for GiftWrapResponseSender {
+    async fn send(&self, response: &QueuedResponse) -> Result<(), String> {
+        let rumor =
+            EventBuilder::new(response.kind, response.content.clone()).tags(response.tags.clone());
+        self.gift_wrap_service
+            .create_and_send_gift_wrap(
+                &self.client,
+                &response.recipient,
+                rumor,
+                response.expiration,
+                &response.inbox_relays,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Handle for enqueueing failed responses; cheap to clone
+#[derive(Clone)]
+pub struct ResponseRetryQueue {
+    tx: mpsc::Sender<QueuedResponse>,
+}
+
+/// Outcome counts for a retry worker run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetryStats {
+    pub delivered: usize,
+    pub dropped: usize,
+}
+
+impl ResponseRetryQueue {
+    /// Create a queue holding at most `capacity` responses awaiting their first retry
+    /// Returns the handle and the receiver to pass to `run_retry_worker`
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<QueuedResponse>) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (Self { tx }, rx)
+    }
+
+    /// Enqueue a response for retry; drops it (with a metric) if the queue is full
+    pub fn enqueue(&self, response: QueuedResponse) -> bool {
+        let correlation_id = response.correlation_id.clone();
+        match self.tx.try_send(response) {
+            Ok(()) => {
+                metrics::increment("peek_response_retries_enqueued_total", &[]);
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "📭 Dropping response for request {}: retry queue unavailable ({})",
+                    correlation_id, e
+                );
+                metrics::increment("peek_responses_dropped_total", &[("reason", "queue_full")]);
+                false
+            }
+        }
+    }
+}
+
+/// Retry queued responses with exponential backoff until delivered or `max_elapsed` passes
+/// Runs until the queue handle is dropped and every pending response is resolved
+pub async fn run_retry_worker<S: ResponseSender>(
+    mut rx: mpsc::Receiver<QueuedResponse>,
+    sender: S,
+    policy: RetryPolicy,
+) -> RetryStats {
+    let mut stats = RetryStats::default();
+    let mut pending: Vec<(Instant, QueuedResponse)> = Vec::new();
+    let mut closed = false;
+
+    loop {
+        if closed && pending.is_empty() {
+            return stats;
+        }
+
+        let next_due = pending.iter().map(|(due, _)| *due).min();
+
+        tokio::select! {
+            received = rx.recv(), if !closed => match received {
+                Some(response) => {
+                    let due = Instant::now() + policy.delay_after(response.attempts);
+                    pending.push((due, response));
+                }
+                None => closed = true,
+            },
+            _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                let now = Instant::now();
+                let (due, waiting): (Vec<_>, Vec<_>) =
+                    pending.drain(..).partition(|(due, _)| *due <= now);
+                pending = waiting;
+
+                for (_, mut response) in due {
+                    response.attempts += 1;
+                    match sender.send(&response).await {
+                        Ok(()) => {
+                            info!(
+                                "✅ Delivered response for request {} on attempt {}",
+                                response.correlation_id, response.attempts
+                            );
+                            metrics::increment("peek_response_retries_delivered_total", &[]);
+                            stats.delivered += 1;
+                        }
+                        Err(e) if response.first_failed_at.elapsed() >= policy.max_elapsed => {
+                            warn!(
+                                "📭 Giving up on response for request {} after {} attempts: {}",
+                                response.correlation_id, response.attempts, e
+                            );
+                            metrics::increment(
+                                "peek_responses_dropped_total",
+                                &[("reason", "retries_exhausted")],
+                            );
+                            stats.dropped += 1;
+                        }
+                        Err(e) => {
+                            warn!(
+                                "Retry {} for request {} failed: {}",
+                                response.attempts, response.correlation_id, e
+                            );
+                            let due = Instant::now() + policy.delay_after(response.attempts);
+                            pending.push((due, response));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Fails the first `failures` sends, recording every attempt number it sees
+    struct FlakySender {
+        failures: u32,
+        attempts_seen: Arc<Mutex<Vec<u32>>>,
+        delivered: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ResponseSender for FlakySender {
+        async fn send(&self, response: &QueuedResponse) -> Result<(), String> {
+            let mut seen = self.attempts_seen.lock().unwrap();
+            seen.push(response.attempts);
+            if seen.len() as u32 <= self.failures {
+                return Err("relay unavailable".to_string());
+            }
+            self.delivered
+                .lock()
+                .unwrap()
+                .push(response.correlation_id.clone());
+            Ok(())
+        }
+    }
+
+    fn policy(max_elapsed: Duration) -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            max_elapsed,
+        }
+    }
+
+    fn queued(correlation_id: &str) -> QueuedResponse {
+        QueuedResponse::new(
+            Keys::generate().public_key(),
+            Kind::Custom(27493),
+            "{}".to_string(),
+            vec![],
+            correlation_id.to_string(),
+            vec![],
+            Timestamp::from(u64::MAX),
+        )
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+            max_elapsed: Duration::from_secs(600),
+        };
+        assert_eq!(policy.delay_after(1), Duration::from_secs(2));
+        assert_eq!(policy.delay_after(2), Duration::from_secs(4));
+        assert_eq!(policy.delay_after(3), Duration::from_secs(8));
+        assert_eq!(policy.delay_after(20), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_two_failed_sends_then_success_delivers_once() {
+        let attempts_seen = Arc::new(Mutex::new(Vec::new()));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        // The initial send already failed; the first retry fails too
+        let sender = FlakySender {
+            failures: 1,
+            attempts_seen: attempts_seen.clone(),
+            delivered: delivered.clone(),
+        };
+
+        let (queue, rx) = ResponseRetryQueue::new(8);
+        assert!(queue.enqueue(queued("request-1")));
+        drop(queue);
+
+        let stats = run_retry_worker(rx, sender, policy(Duration::from_secs(5))).await;
+
+        assert_eq!(
+            stats,
+            RetryStats {
+                delivered: 1,
+                dropped: 0
+            }
+        );
+        assert_eq!(*delivered.lock().unwrap(), vec!["request-1".to_string()]);
+        assert_eq!(*attempts_seen.lock().unwrap(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_elapsed() {
+        let sender = FlakySender {
+            failures: u32::MAX,
+            attempts_seen: Arc::new(Mutex::new(Vec::new())),
+            delivered: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let (queue, rx) = ResponseRetryQueue::new(8);
+        queue.enqueue(queued("request-1"));
+        drop(queue);
+
+        let stats = run_retry_worker(rx, sender, policy(Duration::ZERO)).await;
+        assert_eq!(
+            stats,
+            RetryStats {
+                delivered: 0,
+                dropped: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_full_queue_rejects() {
+        let (queue, _rx) = ResponseRetryQueue::new(1);
+        assert!(queue.enqueue(queued("request-1")));
+        assert!(!queue.enqueue(queued("request-2")));
+    }
+}
@@ -0,0 +1,334 @@
+//! Failed joins clustering away from a community's anchors, a sign its sticker was copied
+//!
+//! A sticker photographed and reprinted somewhere else keeps working at the real venue, while
+//! people at the copy keep failing validation. Failed joins are bucketed into geohash-5 cells,
+//! about 5km across. A cell away from every anchor that keeps collecting failures from several
+//! people for more than a day flags the community as a possible sticker reuse. Flags are sent to
+//! the community's admins and listed for the operator; membership is never changed.
+
+use peek_geo::Coordinates;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::libraries::clock::Clock;
+
+/// Failed joins are clustered by geohash cells of this many characters
+pub const CLUSTER_GEOHASH_PRECISION: usize = 5;
+
+/// Failures nearer an anchor than this are bad fixes at the venue, not a second location
+pub const MIN_CLUSTER_DISTANCE_METERS: f64 = 10_000.0;
+
+/// Different people failing in one cell before it counts; one person retrying is no copy
+pub const MIN_CLUSTER_USERS: usize = 3;
+
+/// How long a cell has to keep collecting failures; a group trying once does not count
+pub const MIN_CLUSTER_SPAN_SECS: u64 = 24 * 60 * 60;
+
+/// A cell without failures for this long starts over
+pub const CLUSTER_IDLE_SECS: u64 = 7 * 24 * 60 * 60;
+
+// Cells tracked per community before the longest quiet one makes room
+const MAX_CELLS_PER_COMMUNITY: usize = 32;
+
+/// Communities whose failures are tracked; the longest quiet one makes room for a new one
+pub const MAX_TRACKED_COMMUNITIES: usize = 10_000;
+
+/// A community whose failed joins cluster somewhere else
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ReuseFlag {
+    pub community_id: Uuid,
+    // Geohash-5 cell of the cluster
+    pub cluster: String,
+    pub failures: u32,
+    pub distance_km: u64,
+    // Unix times of the cluster's first failure and of the flag
+    pub first_failure: u64,
+    pub flagged_at: u64,
+}
+
+struct Cell {
+    failures: u32,
+    // Only counted up to MIN_CLUSTER_USERS
+    users: HashSet<String>,
+    nearest_meters: f64,
+    first_seen: u64,
+    last_seen: u64,
+}
+
+#[derive(Default)]
+struct CommunityCells {
+    cells: HashMap<String, Cell>,
+    last_seen: u64,
+}
+
+#[derive(Default)]
+struct State {
+    communities: HashMap<Uuid, CommunityCells>,
+    flags: HashMap<Uuid, ReuseFlag>,
+    // Raised but not yet sent to the community's admins
+    unannounced: Vec<ReuseFlag>,
+}
+
+pub struct StickerReuse {
+    min_failures: u32,
+    clock: Arc<dyn Clock>,
+    state: Mutex<State>,
+}
+
+impl StickerReuse {
+    /// A cell is a cluster after `min_failures` failed joins; zero turns detection off
+    pub fn new(min_failures: u32, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            min_failures,
+            clock,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Count a failed join by `pubkey` at `user`, `nearest_meters` from the closest anchor
+    ///
+    /// Returns the flag if this failure is the one that raised it. A flagged community stays
+    /// flagged, and is not flagged again, until the operator clears it.
+    pub fn record_failure(
+        &self,
+        community_id: Uuid,
+        pubkey: &str,
+        user: &Coordinates,
+        nearest_meters: Option<f64>,
+    ) -> Option<ReuseFlag> {
+        if self.min_failures == 0 {
+            return None;
+        }
+        let nearest_meters = nearest_meters.filter(|m| *m >= MIN_CLUSTER_DISTANCE_METERS)?;
+        let cluster = user.geohash(CLUSTER_GEOHASH_PRECISION).ok()?;
+        let now = self.clock.now_unix();
+
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        if !state.communities.contains_key(&community_id)
+            && state.communities.len() >= MAX_TRACKED_COMMUNITIES
+        {
+            let quietest = state
+                .communities
+                .iter()
+                .min_by_key(|(_, community)| community.last_seen)
+                .map(|(id, _)| *id);
+            if let Some(quietest) = quietest {
+                state.communities.remove(&quietest);
+            }
+        }
+        let community = state.communities.entry(community_id).or_default();
+        community.last_seen = now;
+        community
+            .cells
+            .retain(|_, cell| now.saturating_sub(cell.last_seen) < CLUSTER_IDLE_SECS);
+        if !community.cells.contains_key(&cluster)
+            && community.cells.len() >= MAX_CELLS_PER_COMMUNITY
+        {
+            let quietest = community
+                .cells
+                .iter()
+                .min_by_key(|(_, cell)| cell.last_seen)
+                .map(|(cell, _)| cell.clone());
+            if let Some(quietest) = quietest {
+                community.cells.remove(&quietest);
+            }
+        }
+
+        let cell = community.cells.entry(cluster.clone()).or_insert(Cell {
+            failures: 0,
+            users: HashSet::new(),
+            nearest_meters,
+            first_seen: now,
+            last_seen: now,
+        });
+        cell.failures += 1;
+        cell.last_seen = now;
+        cell.nearest_meters = cell.nearest_meters.min(nearest_meters);
+        if cell.users.len() < MIN_CLUSTER_USERS {
+            cell.users.insert(pubkey.to_string());
+        }
+
+        let is_cluster = cell.failures >= self.min_failures
+            && cell.users.len() >= MIN_CLUSTER_USERS
+            && cell.last_seen.saturating_sub(cell.first_seen) >= MIN_CLUSTER_SPAN_SECS;
+        if !is_cluster || state.flags.contains_key(&community_id) {
+            return None;
+        }
+        let flag = ReuseFlag {
+            community_id,
+            cluster,
+            failures: cell.failures,
+            distance_km: (cell.nearest_meters / 1_000.0).round() as u64,
+            first_failure: cell.first_seen,
+            flagged_at: now,
+        };
+        state.flags.insert(community_id, flag.clone());
+        state.unannounced.push(flag.clone());
+        Some(flag)
+    }
+
+    pub fn is_flagged(&self, community_id: &Uuid) -> bool {
+        self.state.lock().unwrap().flags.contains_key(community_id)
+    }
+
+    /// Flagged communities, most recently flagged first
+    pub fn flags(&self) -> Vec<ReuseFlag> {
+        let mut flags: Vec<ReuseFlag> =
+            self.state.lock().unwrap().flags.values().cloned().collect();
+        flags.sort_by_key(|flag| std::cmp::Reverse(flag.flagged_at));
+        flags
+    }
+
+    /// Flags raised since the last call, for the admin notifier
+    pub fn take_unannounced(&self) -> Vec<ReuseFlag> {
+        std::mem::take(&mut self.state.lock().unwrap().unannounced)
+    }
+
+    /// Drop a community's flag and the failures behind it, once the operator has looked
+    pub fn clear(&self, community_id: &Uuid) -> Option<ReuseFlag> {
+        let mut state = self.state.lock().unwrap();
+        state.communities.remove(community_id);
+        state
+            .unannounced
+            .retain(|flag| flag.community_id != *community_id);
+        state.flags.remove(community_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+
+    const NOW: u64 = 1_760_000_000;
+    const HOUR: u64 = 60 * 60;
+    // Oakland is about 13km from the Blue Bottle anchor in San Francisco
+    const OAKLAND: (f64, f64) = (37.8044, -122.2712);
+    const OAKLAND_METERS: f64 = 13_000.0;
+
+    fn point((latitude, longitude): (f64, f64)) -> Coordinates {
+        Coordinates::new(latitude, longitude).unwrap()
+    }
+
+    fn community(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    fn detector(min_failures: u32) -> (StickerReuse, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(NOW));
+        (StickerReuse::new(min_failures, clock.clone()), clock)
+    }
+
+    /// Fail `count` joins at `at`, cycling through `users` people, one every `every_secs`
+    fn fail(
+        reuse: &StickerReuse,
+        clock: &ManualClock,
+        at: (f64, f64),
+        meters: f64,
+        users: usize,
+        count: usize,
+        every_secs: u64,
+    ) -> Vec<ReuseFlag> {
+        let mut raised = Vec::new();
+        for n in 0..count {
+            let pubkey = format!("user{}", n % users);
+            raised.extend(reuse.record_failure(community(1), &pubkey, &point(at), Some(meters)));
+            clock.advance(every_secs);
+        }
+        raised
+    }
+
+    #[test]
+    fn test_persistent_cluster_far_from_the_anchor_is_flagged() {
+        let (reuse, clock) = detector(10);
+
+        // Five people failing twice a day for two days
+        let raised = fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 4, 12 * HOUR);
+        assert!(raised.is_empty(), "flagged before the threshold");
+        let raised = fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 6, 12 * HOUR);
+
+        assert_eq!(
+            raised,
+            vec![ReuseFlag {
+                community_id: community(1),
+                cluster: point(OAKLAND).geohash(CLUSTER_GEOHASH_PRECISION).unwrap(),
+                failures: 10,
+                distance_km: 13,
+                first_failure: NOW,
+                flagged_at: NOW + 9 * 12 * HOUR,
+            }]
+        );
+        assert!(reuse.is_flagged(&community(1)));
+        assert!(!reuse.is_flagged(&community(2)));
+        assert_eq!(reuse.flags(), raised);
+
+        // Announced once; further failures do not flag it again
+        assert_eq!(reuse.take_unannounced(), raised);
+        assert!(fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 10, HOUR).is_empty());
+        assert!(reuse.take_unannounced().is_empty());
+    }
+
+    #[test]
+    fn test_failures_that_do_not_form_a_cluster_are_ignored() {
+        // Bad fixes around the venue itself
+        let (reuse, clock) = detector(10);
+        fail(&reuse, &clock, OAKLAND, 2_500.0, 5, 40, 6 * HOUR);
+        assert!(reuse.flags().is_empty());
+
+        // One person retrying for days
+        let (reuse, clock) = detector(10);
+        fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 1, 40, 6 * HOUR);
+        assert!(reuse.flags().is_empty());
+
+        // A crowd trying all at once within an hour, then never again
+        let (reuse, clock) = detector(10);
+        fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 8, 40, 60);
+        assert!(reuse.flags().is_empty());
+
+        // Failures spread over many cells, none reaching the threshold
+        let (reuse, clock) = detector(10);
+        for step in 0..40 {
+            let spot = (37.0 + step as f64 * 0.1, -121.0);
+            fail(&reuse, &clock, spot, 50_000.0, 3, 3, 12 * HOUR);
+        }
+        assert!(reuse.flags().is_empty());
+    }
+
+    #[test]
+    fn test_idle_cells_start_over() {
+        let (reuse, clock) = detector(10);
+        fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 9, 12 * HOUR);
+        clock.advance(CLUSTER_IDLE_SECS);
+
+        // Nine failures that went quiet a week ago no longer count towards a cluster
+        assert!(fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 1, HOUR).is_empty());
+        assert!(reuse.flags().is_empty());
+    }
+
+    #[test]
+    fn test_detection_can_be_turned_off() {
+        let (reuse, clock) = detector(0);
+        fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 40, 12 * HOUR);
+        assert!(reuse.flags().is_empty());
+        assert!(!reuse.is_flagged(&community(1)));
+    }
+
+    #[test]
+    fn test_clearing_drops_the_flag_and_its_failures() {
+        let (reuse, clock) = detector(10);
+        fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 10, 12 * HOUR);
+        assert!(reuse.is_flagged(&community(1)));
+
+        assert_eq!(reuse.clear(&community(1)).unwrap().failures, 10);
+        assert!(!reuse.is_flagged(&community(1)));
+        assert!(reuse.take_unannounced().is_empty());
+        assert_eq!(reuse.clear(&community(1)), None);
+
+        // The count starts from scratch
+        assert!(fail(&reuse, &clock, OAKLAND, OAKLAND_METERS, 5, 9, 12 * HOUR).is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::libraries::clock::Clock;
+use crate::libraries::relay_url::RelayUrl;
+
+/// What the listener should do about a relay that has gone quiet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchdogAction {
+    /// Send fresh REQs for our subscriptions on this relay
+    Resubscribe(String),
+    /// Repeated silence: drop and re-establish the connection
+    Reconnect(String),
+}
+
+struct RelayActivity {
+    // Last event actually received, reported by the health endpoint
+    last_event_at: Option<u64>,
+    // Start of the current silence window (last event, registration or last action)
+    quiet_since: u64,
+    // Consecutive silence windows without any traffic
+    silent_windows: u32,
+}
+
+/// Tracks time since the last event per relay, for relays that drop subscriptions silently
+///
+/// Some relays forget subscriptions after inactivity or restart without sending a close
+/// frame, so the notification loop keeps running with nothing arriving. Every `threshold_secs`
+/// of silence yields a re-subscription, and every `reconnect_after`-th one a full reconnect.
+pub struct SubscriptionWatchdog {
+    relays: Mutex<BTreeMap<String, RelayActivity>>,
+    threshold_secs: u64,
+    reconnect_after: u32,
+    clock: Arc<dyn Clock>,
+}
+
+impl SubscriptionWatchdog {
+    pub fn new(threshold_secs: u64, reconnect_after: u32, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            relays: Mutex::new(BTreeMap::new()),
+            threshold_secs: threshold_secs.max(1),
+            reconnect_after: reconnect_after.max(1),
+            clock,
+        }
+    }
+
+    /// Start watching a relay we hold subscriptions on
+    pub fn watch(&self, relay_url: &str) {
+        let now = self.clock.now_unix();
+        self.relays
+            .lock()
+            .unwrap()
+            .entry(relay_key(relay_url))
+            .or_insert(RelayActivity {
+                last_event_at: None,
+                quiet_since: now,
+                silent_windows: 0,
+            });
+    }
+
+    /// Record traffic from a relay, resetting its silence window
+    pub fn record_event(&self, relay_url: &str) {
+        let now = self.clock.now_unix();
+        let mut relays = self.relays.lock().unwrap();
+        let activity = relays.entry(relay_key(relay_url)).or_insert(RelayActivity {
+            last_event_at: None,
+            quiet_since: now,
+            silent_windows: 0,
+        });
+        activity.last_event_at = Some(now);
+        activity.quiet_since = now;
+        activity.silent_windows = 0;
+    }
+
+    /// Actions due for relays silent for at least the threshold
+    pub fn check(&self) -> Vec<WatchdogAction> {
+        let now = self.clock.now_unix();
+        let mut actions = Vec::new();
+
+        for (relay, activity) in self.relays.lock().unwrap().iter_mut() {
+            if now.saturating_sub(activity.quiet_since) < self.threshold_secs {
+                continue;
+            }
+
+            activity.quiet_since = now;
+            activity.silent_windows += 1;
+            if activity.silent_windows >= self.reconnect_after {
+                activity.silent_windows = 0;
+                actions.push(WatchdogAction::Reconnect(relay.clone()));
+            } else {
+                actions.push(WatchdogAction::Resubscribe(relay.clone()));
+            }
+        }
+
+        actions
+    }
+
+    /// Seconds since the last received event per relay (None if nothing has arrived yet)
+    pub fn last_event_ages(&self) -> BTreeMap<String, Option<u64>> {
+        let now = self.clock.now_unix();
+        self.relays
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(relay, activity)| {
+                (
+                    relay.clone(),
+                    activity.last_event_at.map(|at| now.saturating_sub(at)),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Configured URLs and notification URLs may be spelled differently for the same relay
+fn relay_key(relay_url: &str) -> String {
+    RelayUrl::parse(relay_url)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| relay_url.trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::test_support::ManualClock;
+
+    const RELAY: &str = "wss://relay.example";
+
+    fn watchdog(clock: Arc<ManualClock>) -> SubscriptionWatchdog {
+        let watchdog = SubscriptionWatchdog::new(60, 3, clock);
+        watchdog.watch(RELAY);
+        watchdog
+    }
+
+    /// Replay a fake notification stream: (seconds since start, relay) pairs,
+    /// checking once per simulated second like the listener's timer
+    fn replay(
+        clock: &ManualClock,
+        watchdog: &SubscriptionWatchdog,
+        events: &[(u64, &str)],
+        until: u64,
+    ) -> Vec<(u64, WatchdogAction)> {
+        let start = clock.now_unix();
+        let mut actions = Vec::new();
+        for t in 0..=until {
+            clock.set(start + t);
+            for (_, relay) in events.iter().filter(|(at, _)| *at == t) {
+                watchdog.record_event(relay);
+            }
+            actions.extend(watchdog.check().into_iter().map(|a| (t, a)));
+        }
+        actions
+    }
+
+    #[test]
+    fn test_resubscribes_at_threshold() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let watchdog = watchdog(clock.clone());
+
+        let actions = replay(&clock, &watchdog, &[], 59);
+        assert!(actions.is_empty(), "nothing fires before the threshold");
+
+        let actions = replay(&clock, &watchdog, &[], 1);
+        assert_eq!(
+            actions,
+            vec![(1, WatchdogAction::Resubscribe(RELAY.to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_traffic_resets_the_silence_window() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let watchdog = watchdog(clock.clone());
+
+        // An event every 45 seconds keeps the subscription healthy
+        let events = [(45, RELAY), (90, RELAY), (135, RELAY)];
+        let actions = replay(&clock, &watchdog, &events, 190);
+        assert!(actions.is_empty());
+
+        // Silence after the last event fires 60 seconds later
+        let actions = replay(&clock, &watchdog, &[], 5);
+        assert_eq!(
+            actions,
+            vec![(5, WatchdogAction::Resubscribe(RELAY.to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_repeated_silence_escalates_to_reconnect() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let watchdog = watchdog(clock.clone());
+
+        let actions: Vec<WatchdogAction> = replay(&clock, &watchdog, &[], 180)
+            .into_iter()
+            .map(|(_, a)| a)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                WatchdogAction::Resubscribe(RELAY.to_string()),
+                WatchdogAction::Resubscribe(RELAY.to_string()),
+                WatchdogAction::Reconnect(RELAY.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_last_event_age_per_relay() {
+        let clock = Arc::new(ManualClock::new(1_760_000_000));
+        let watchdog = watchdog(clock.clone());
+        watchdog.watch("wss://quiet.example/");
+
+        watchdog.record_event("wss://relay.example/");
+        clock.advance(42);
+
+        let ages = watchdog.last_event_ages();
+        assert_eq!(ages.get(RELAY), Some(&Some(42)));
+        assert_eq!(ages.get("wss://quiet.example"), Some(&None));
+    }
+}
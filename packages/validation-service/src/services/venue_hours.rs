@@ -0,0 +1,381 @@
+//! Approximate opening hours of a community's venue, from OpenStreetMap
+//!
+//! The opening_hours tag of the place Overpass names the community after is stored in the
+//! group metadata when the community is created, or when an admin refreshes it. Previews
+//! return the raw value plus whether the venue is open right now, so users can tell a café
+//! is closed before walking over.
+//!
+//! Only the common part of the OSM syntax is understood: weekday lists and ranges, time
+//! ranges (also past midnight), `off` and `24/7`. Public holiday rules are skipped, so the
+//! answer is approximate on holidays. Anything else makes the spec unparseable, and the
+//! preview then carries only the raw string.
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::overpass;
+use crate::libraries::sanitize::sanitize_opening_hours;
+use crate::models::Coordinates;
+
+/// Group metadata tag holding the venue's OSM opening_hours value, then its timezone if known
+pub const VENUE_HOURS_TAG: &str = "venue_hours";
+
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// The venue's opening hours as stored in metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VenueHours {
+    opening_hours: String,
+    timezone: Option<Tz>,
+}
+
+/// Venue hours as shown on previews
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct VenueHoursStatus {
+    /// The OSM opening_hours value, such as "Mo-Fr 09:00-17:00; Sa 10:00-14:00"
+    pub opening_hours: String,
+    /// Whether the venue is open now; absent when the hours or its timezone are not understood
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_now: Option<bool>,
+}
+
+impl VenueHours {
+    /// Hours from OSM after sanitizing, or None when nothing usable remains
+    pub fn new(opening_hours: &str, timezone: Option<Tz>) -> Option<Self> {
+        Some(Self {
+            opening_hours: sanitize_opening_hours(opening_hours)?,
+            timezone,
+        })
+    }
+
+    /// Parse the values of a venue_hours tag, after the tag name; a bad timezone is dropped
+    pub fn from_tag_values(values: &[String]) -> Option<Self> {
+        let (opening_hours, rest) = values.split_first()?;
+        let timezone = rest.first().and_then(|timezone| timezone.parse().ok());
+        Self::new(opening_hours, timezone)
+    }
+
+    /// The values of a venue_hours metadata tag, after the tag name
+    pub fn tag_values(&self) -> Vec<String> {
+        let mut values = vec![self.opening_hours.clone()];
+        values.extend(self.timezone.map(|timezone| timezone.name().to_string()));
+        values
+    }
+
+    pub fn opening_hours(&self) -> &str {
+        &self.opening_hours
+    }
+
+    /// The hours for a preview at unix time `now`
+    ///
+    /// `fallback_timezone` (the community's quiet hours timezone) is used when the venue's
+    /// own timezone was not found.
+    pub fn status(&self, now: u64, fallback_timezone: Option<Tz>) -> VenueHoursStatus {
+        let open_now = self.timezone.or(fallback_timezone).and_then(|timezone| {
+            let hours = OpeningHours::parse(&self.opening_hours).ok()?;
+            let local = DateTime::<Utc>::from_timestamp(now as i64, 0)?.with_timezone(&timezone);
+            Some(hours.is_open_at(
+                local.weekday().num_days_from_monday() as usize,
+                local.hour() * 60 + local.minute(),
+            ))
+        });
+        VenueHoursStatus {
+            opening_hours: self.opening_hours.clone(),
+            open_now,
+        }
+    }
+}
+
+/// Look up the opening hours of the named place at `location`, with the timezone there
+/// None when Overpass finds no place or the place has no usable opening_hours tag
+pub async fn lookup_venue_hours(location: &Coordinates) -> anyhow::Result<Option<VenueHours>> {
+    let Some(place) = overpass::get_place(location.latitude(), location.longitude()).await? else {
+        return Ok(None);
+    };
+    let Some(opening_hours) = place.opening_hours else {
+        return Ok(None);
+    };
+    Ok(VenueHours::new(
+        &opening_hours,
+        venue_timezone(location).await,
+    ))
+}
+
+/// The timezone at `location`, or None if Overpass does not know it or could not be asked
+pub async fn venue_timezone(location: &Coordinates) -> Option<Tz> {
+    match overpass::get_timezone(location.latitude(), location.longitude()).await {
+        Ok(timezone) => timezone,
+        Err(e) => {
+            tracing::warn!("Overpass timezone lookup failed: {}", e);
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidOpeningHours {
+    #[error("unsupported opening_hours rule {0:?}")]
+    Rule(String),
+    #[error("{0:?} is not a HH:MM-HH:MM time range")]
+    TimeRange(String),
+}
+
+/// Weekly opening hours parsed from an OSM opening_hours value
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OpeningHours {
+    // Per weekday from Monday: open ranges in minutes since midnight; an end past
+    // MINUTES_PER_DAY runs into the next day
+    days: [Vec<(u32, u32)>; 7],
+}
+
+impl OpeningHours {
+    fn parse(spec: &str) -> Result<Self, InvalidOpeningHours> {
+        let spec = spec.trim();
+        if spec == "24/7" {
+            return Ok(Self {
+                days: std::array::from_fn(|_| vec![(0, MINUTES_PER_DAY)]),
+            });
+        }
+
+        let mut days: [Vec<(u32, u32)>; 7] = Default::default();
+        for rule in spec
+            .split(';')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let (selector, times) = match rule.split_once(' ') {
+                Some((selector, times)) if !starts_with_digit(selector) => {
+                    (Some(selector), times.trim())
+                }
+                _ => (None, rule),
+            };
+            let weekdays = match selector {
+                Some(selector) => match weekdays(selector)? {
+                    Some(weekdays) => weekdays,
+                    // Holidays only; we cannot tell when they are
+                    None => continue,
+                },
+                None => [true; 7],
+            };
+            let ranges = match times {
+                "off" | "closed" => Vec::new(),
+                times => times
+                    .split(',')
+                    .map(|range| time_range(range.trim()))
+                    .collect::<Result<_, _>>()?,
+            };
+            // A later rule replaces earlier ones for the days it names
+            for (day, selected) in weekdays.into_iter().enumerate() {
+                if selected {
+                    days[day] = ranges.clone();
+                }
+            }
+        }
+        Ok(Self { days })
+    }
+
+    /// Whether the venue is open on `weekday` (0 for Monday) at `minute` past midnight
+    fn is_open_at(&self, weekday: usize, minute: u32) -> bool {
+        let today = self.days[weekday % 7]
+            .iter()
+            .any(|(start, end)| *start <= minute && minute < *end);
+        let since_yesterday = self.days[(weekday + 6) % 7]
+            .iter()
+            .any(|(_, end)| minute + MINUTES_PER_DAY < *end);
+        today || since_yesterday
+    }
+}
+
+fn starts_with_digit(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// The weekdays named by a selector such as "Mo-Fr,Su"; None if it names only holidays
+fn weekdays(selector: &str) -> Result<Option<[bool; 7]>, InvalidOpeningHours> {
+    let unsupported = || InvalidOpeningHours::Rule(selector.to_string());
+    let day = |name: &str| {
+        WEEKDAYS
+            .iter()
+            .position(|day| *day == name)
+            .ok_or_else(unsupported)
+    };
+
+    let mut selected = [false; 7];
+    let mut any_weekday = false;
+    for item in selector.split(',') {
+        match item {
+            "PH" | "SH" => continue,
+            item => match item.split_once('-') {
+                Some((from, to)) => {
+                    let (from, to) = (day(from)?, day(to)?);
+                    // Ranges may wrap past Sunday, as in Fr-Mo
+                    let mut current = from;
+                    loop {
+                        selected[current] = true;
+                        if current == to {
+                            break;
+                        }
+                        current = (current + 1) % 7;
+                    }
+                }
+                None => selected[day(item)?] = true,
+            },
+        }
+        any_weekday = true;
+    }
+    Ok(any_weekday.then_some(selected))
+}
+
+/// Minutes since midnight for a "HH:MM-HH:MM" range; an end at or before the start runs past midnight
+fn time_range(range: &str) -> Result<(u32, u32), InvalidOpeningHours> {
+    let invalid = || InvalidOpeningHours::TimeRange(range.to_string());
+    let minutes = |time: &str| -> Result<u32, InvalidOpeningHours> {
+        let (hours, minutes) = time.trim().split_once(':').ok_or_else(invalid)?;
+        if hours.len() != 2 || minutes.len() != 2 {
+            return Err(invalid());
+        }
+        let hours: u32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 48 || minutes > 59 {
+            return Err(invalid());
+        }
+        Ok(hours * 60 + minutes)
+    };
+    let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+    let (start, mut end) = (minutes(start)?, minutes(end)?);
+    if start >= MINUTES_PER_DAY {
+        return Err(invalid());
+    }
+    if end <= start {
+        end += MINUTES_PER_DAY;
+    }
+    Ok((start, end.min(2 * MINUTES_PER_DAY)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Thursday 2025-10-09 12:00 UTC
+    const THURSDAY_NOON_UTC: u64 = 1_760_011_200;
+    const HOUR: u64 = 60 * 60;
+    const DAY: u64 = 24 * HOUR;
+
+    fn office() -> VenueHours {
+        VenueHours::new("Mo-Fr 09:00-17:00", Some(chrono_tz::Europe::Berlin)).unwrap()
+    }
+
+    #[test]
+    fn test_weekday_hours_at_different_times() {
+        let hours = office();
+        // 14:00 in Berlin on a Thursday
+        assert_eq!(hours.status(THURSDAY_NOON_UTC, None).open_now, Some(true));
+        // 08:00 and 17:00 local: just before opening and at closing
+        assert_eq!(
+            hours.status(THURSDAY_NOON_UTC - 6 * HOUR, None).open_now,
+            Some(false)
+        );
+        assert_eq!(
+            hours.status(THURSDAY_NOON_UTC + 3 * HOUR, None).open_now,
+            Some(false)
+        );
+        // Saturday afternoon
+        assert_eq!(
+            hours.status(THURSDAY_NOON_UTC + 2 * DAY, None).open_now,
+            Some(false)
+        );
+        assert_eq!(
+            hours.status(THURSDAY_NOON_UTC, None).opening_hours,
+            "Mo-Fr 09:00-17:00"
+        );
+    }
+
+    #[test]
+    fn test_hours_are_read_in_the_venues_timezone() {
+        // 12:00 UTC is 08:00 in New York, before opening, unless the venue's zone is unknown
+        let new_york = VenueHours::new("Mo-Fr 09:00-17:00", Some(chrono_tz::America::New_York));
+        assert_eq!(
+            new_york.unwrap().status(THURSDAY_NOON_UTC, None).open_now,
+            Some(false)
+        );
+
+        let unzoned = VenueHours::new("Mo-Fr 09:00-17:00", None).unwrap();
+        assert_eq!(unzoned.status(THURSDAY_NOON_UTC, None).open_now, None);
+        assert_eq!(
+            unzoned
+                .status(THURSDAY_NOON_UTC, Some(chrono_tz::Europe::Berlin))
+                .open_now,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_later_rules_override_and_late_hours_run_past_midnight() {
+        let bar = OpeningHours::parse("Mo-Su 18:00-02:00; Su off; PH off").unwrap();
+        // Saturday 01:00 is still Friday night
+        assert!(bar.is_open_at(5, 60));
+        assert!(!bar.is_open_at(5, 3 * 60));
+        assert!(!bar.is_open_at(6, 20 * 60));
+        // Saturday night spills into Sunday, even though Sunday itself is off
+        assert!(bar.is_open_at(6, 60));
+
+        let split = OpeningHours::parse("Mo-Fr 08:00-12:00,13:00-17:30; Sa 10:00-14:00").unwrap();
+        assert!(split.is_open_at(0, 11 * 60));
+        assert!(!split.is_open_at(0, 12 * 60 + 30));
+        assert!(split.is_open_at(5, 10 * 60));
+
+        assert!(OpeningHours::parse("24/7").unwrap().is_open_at(2, 3 * 60));
+        assert!(OpeningHours::parse("Fr-Mo 10:00-16:00")
+            .unwrap()
+            .is_open_at(6, 11 * 60));
+    }
+
+    #[test]
+    fn test_malformed_hours_fall_back_to_the_raw_string() {
+        for spec in [
+            "Mo-Fr 9am-5pm",
+            "Jan-Mar Mo-Fr 09:00-17:00",
+            "Mo-Fr 17:00+",
+            "by appointment",
+        ] {
+            assert!(OpeningHours::parse(spec).is_err(), "{}", spec);
+            let hours = VenueHours::new(spec, Some(chrono_tz::Europe::Berlin)).unwrap();
+            assert_eq!(
+                hours.status(THURSDAY_NOON_UTC, None),
+                VenueHoursStatus {
+                    opening_hours: spec.to_string(),
+                    open_now: None,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_tag_values_round_trip_and_junk_is_cleaned() {
+        let hours = office();
+        assert_eq!(
+            hours.tag_values(),
+            vec!["Mo-Fr 09:00-17:00".to_string(), "Europe/Berlin".to_string()]
+        );
+        assert_eq!(
+            VenueHours::from_tag_values(&hours.tag_values()),
+            Some(hours)
+        );
+
+        let unknown_zone = ["Mo-Fr 09:00-17:00".to_string(), "Mars/Olympus".to_string()];
+        assert_eq!(
+            VenueHours::from_tag_values(&unknown_zone),
+            VenueHours::new("Mo-Fr 09:00-17:00", None)
+        );
+        assert_eq!(VenueHours::new("  ", None), None);
+        assert_eq!(
+            VenueHours::new("Mo-Fr\u{202e} 09:00-17:00\n", None),
+            VenueHours::new("Mo-Fr 09:00-17:00", None)
+        );
+        assert_eq!(VenueHours::new(&"Mo ".repeat(100), None), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Soak test: synthetic joins against a real relay with relay faults injected
+//!
+//! Creates one community, then pushes SOAK_RUNS joins (default 300, SOAK_CONCURRENCY at a time)
+//! through the same GroupWriter join path the validation handler uses, with FAULTS active
+//! (default `add_member:timeout:0.2,add_member:error:0.1,add_member:duplicate:0.1`). Each
+//! outcome is answered through a response retry queue whose first delivery fails at random.
+//!
+//! Invariants checked:
+//! - no join reports success unless the member list shows the new member
+//! - no join task panics
+//! - the response retry queue drains, every queued response delivered or dropped
+//!
+//! Needs RELAY_URL and RELAY_SECRET_KEY, like the service. Run with
+//! `cargo run --features fault-injection --bin soak_faults`.
+
+use futures::stream::{self, StreamExt};
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+use validation_service::libraries::clock::SystemClock;
+use validation_service::libraries::exclusion_zones::ExclusionZones;
+use validation_service::libraries::relay_url::RelayUrl;
+use validation_service::libraries::rng::{RngSource, ThreadRngSource};
+use validation_service::models::{Coordinates, ProtocolConfig};
+use validation_service::services::discovery_map::DiscoveryMaps;
+use validation_service::services::previous_refs::PreviousRefs;
+use validation_service::services::relay::RelayService;
+use validation_service::services::relay_access::GroupWriter;
+use validation_service::services::relay_circuit::RelayCircuit;
+use validation_service::services::response_retry::{
+    run_retry_worker, QueuedResponse, ResponseRetryQueue, ResponseSender, RetryPolicy,
+};
+
+const DEFAULT_FAULTS: &str = "add_member:timeout:0.2,add_member:error:0.1,add_member:duplicate:0.1";
+
+/// Fails a share of deliveries, like an unreachable inbox relay
+struct FlakyResponses {
+    failure_rate: f64,
+}
+
+impl ResponseSender for FlakyResponses {
+    async fn send(&self, _response: &QueuedResponse) -> Result<(), String> {
+        if ThreadRngSource.next_f64() < self.failure_rate {
+            return Err("injected delivery failure".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "validation_service=warn".into()),
+        )
+        .init();
+
+    if std::env::var("FAULTS").is_err() {
+        std::env::set_var("FAULTS", DEFAULT_FAULTS);
+    }
+    let runs: usize = env_or("SOAK_RUNS", 300);
+    let concurrency: usize = env_or("SOAK_CONCURRENCY", 8);
+    let relay_url = RelayUrl::parse(&std::env::var("RELAY_URL")?)?;
+    let relay_keys = Keys::parse(&zeroize::Zeroizing::new(std::env::var("RELAY_SECRET_KEY")?))?;
+    let protocol: ProtocolConfig = envy::from_env()?;
+
+    println!(
+        "Soaking {} joins against {} with FAULTS={}",
+        runs,
+        relay_url,
+        std::env::var("FAULTS")?
+    );
+
+    let writer = GroupWriter::new(Arc::new(
+        RelayService::new(
+            relay_url,
+            relay_keys,
+            protocol.clone(),
+            Duration::from_millis(500),
+            DiscoveryMaps {
+                d_tag: protocol.discovery_map_d_tag.clone(),
+                prefixes: Vec::new(),
+                signer: None,
+                exclusion_zones: ExclusionZones::default(),
+            },
+            4096,
+            // Injected faults are the point here; a tripped breaker would hide them
+            RelayCircuit::new(Arc::new(SystemClock), 0, Duration::from_secs(30)),
+            PreviousRefs::new(3),
+        )
+        .await?,
+    ));
+    let reader = writer.reader();
+
+    let creator = Keys::generate();
+    let group_id = writer
+        .create_group(
+            Uuid::new_v4(),
+            "Soak test".to_string(),
+            creator.public_key().to_hex(),
+            Coordinates::new(37.7749, -122.4194)?,
+            None,
+            None,
+            false,
+            None,
+        )
+        .await?
+        .group_id;
+    println!("Created {}", group_id);
+
+    let (queue, retry_rx) = ResponseRetryQueue::new(runs);
+    let responses = FlakyResponses { failure_rate: 0.3 };
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(50),
+        max_delay: Duration::from_millis(500),
+        max_elapsed: Duration::from_secs(5),
+    };
+    let worker = tokio::spawn(run_retry_worker(retry_rx, responses, policy));
+
+    let outcomes: Vec<_> = stream::iter(0..runs)
+        .map(|_| {
+            let writer = writer.clone();
+            let reader = reader.clone();
+            let group_id = group_id.clone();
+            tokio::spawn(async move {
+                let member = Keys::generate().public_key().to_hex();
+                let joined = writer.add_group_member(&group_id, &member, false).await;
+                let visible = reader
+                    .get_group_snapshot(&group_id)
+                    .await
+                    .map(|snapshot| snapshot.members.contains(&member))
+                    .unwrap_or(false);
+                (member, joined.map_err(|e| e.to_string()), visible)
+            })
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    let mut panics = 0;
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut violations = Vec::new();
+    let mut enqueued = 0;
+    for outcome in outcomes {
+        let Ok((member, joined, visible)) = outcome else {
+            panics += 1;
+            continue;
+        };
+        match joined {
+            Ok(()) if !visible => violations.push(member.clone()),
+            Ok(()) => succeeded += 1,
+            Err(_) => failed += 1,
+        }
+        // Every outcome gets a response; pretend the first delivery failed
+        let response = QueuedResponse::new(
+            PublicKey::from_hex(&member)?,
+            protocol.response_kind(),
+            String::new(),
+            Vec::new(),
+            member,
+            Vec::new(),
+            Timestamp::from(Timestamp::now().as_u64() + 3600),
+        );
+        if queue.enqueue(response) {
+            enqueued += 1;
+        }
+    }
+
+    drop(queue);
+    let stats = tokio::time::timeout(Duration::from_secs(30), worker).await??;
+
+    println!(
+        "joins: {} succeeded, {} failed, {} panicked; responses: {} queued, {} delivered, {} dropped",
+        succeeded, failed, panics, enqueued, stats.delivered, stats.dropped
+    );
+
+    assert!(
+        violations.is_empty(),
+        "{} join(s) reported success without visible membership: {:?}",
+        violations.len(),
+        violations
+    );
+    assert_eq!(panics, 0, "join tasks panicked");
+    assert_eq!(
+        stats.delivered + stats.dropped,
+        enqueued,
+        "response retry queue did not drain"
+    );
+    println!("✓ Invariants held");
+    Ok(())
+}
@@ -1,23 +1,143 @@
-#[allow(dead_code)]
-fn main() {
-    println!("This is a test binary. Run with `cargo test --bin test_gift_wrap`");
-}
+//! Cross-language gift wrap conformance suite
+//!
+//! Vectors live in `tests/fixtures/gift_wrap_*.json` and are shared with the TypeScript client:
+//! - `gift_wrap_rust*.json` are written by `regenerate_rust_vectors` below
+//!   (`cargo test --bin validation-service regenerate_rust_vectors -- --ignored`)
+//! - `gift_wrap_js*.json` are written by `packages/pwa-client/scripts/generate-gift-wrap-fixtures.mjs`
+//!
+//! Every vector is built from fixed keys and fixed timestamps, so the rumor (and its id) is
+//! stable across regenerations; only NIP-44 nonces and signatures may change. The `variants`
+//! sets carry every request (client → service) and response (service → client) payload from
+//! the wire contract fixtures.
 
 #[cfg(test)]
 mod tests {
+    use crate::handlers::nostr_validation::{ServiceRequest, ServiceResponse};
+    use crate::test_wire_contract::tests::{
+        all_requests, all_responses, request_type, response_type, REQUEST_TYPES,
+    };
+    use nostr_sdk::nips::nip44;
     use nostr_sdk::prelude::*;
+    use serde::{Deserialize, Serialize};
     use serde_json::json;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    /// Service identity in every vector
+    const SERVICE_SECRET: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+    /// Client identity in every vector
+    const CLIENT_SECRET: &str = "0000000000000000000000000000000000000000000000000000000000000002";
+    /// Gift wrap signer, normally a fresh random key per wrap
+    const EPHEMERAL_SECRET: &str =
+        "0000000000000000000000000000000000000000000000000000000000000003";
+
+    const RUMOR_CREATED_AT: u64 = 1_700_000_000;
+    /// Seals and wraps are normally backdated by a random amount; vectors use a fixed offset
+    const WRAP_CREATED_AT: u64 = 1_699_900_000;
+    const EXPIRATION: u64 = 1_700_259_200;
+
+    const REQUEST_KIND: u16 = 27492;
+    const RESPONSE_KIND: u16 = 27493;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct VectorFile {
+        description: String,
+        vectors: Vec<GiftWrapVector>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct GiftWrapVector {
+        name: String,
+        sender_secret: String,
+        sender_pubkey: String,
+        recipient_secret: String,
+        recipient_pubkey: String,
+        rumor: UnsignedEvent,
+        gift_wrap: Event,
+    }
 
-    #[tokio::test]
-    async fn test_gift_wrap_roundtrip() {
-        // Create sender and recipient keys
-        let sender_keys = Keys::generate();
-        let recipient_keys = Keys::generate();
+    fn keys(secret: &str) -> Keys {
+        Keys::parse(secret).unwrap()
+    }
 
-        println!("Sender pubkey: {}", sender_keys.public_key().to_hex());
-        println!("Recipient pubkey: {}", recipient_keys.public_key().to_hex());
+    fn fixtures_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    }
 
-        // Create a rumor (unsigned event)
+    fn load(file: &str) -> Option<VectorFile> {
+        let path = fixtures_dir().join(file);
+        let json = std::fs::read_to_string(&path).ok()?;
+        Some(serde_json::from_str(&json).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)))
+    }
+
+    fn tag_values(tags: &Tags) -> Vec<Vec<String>> {
+        tags.iter().map(|tag| tag.as_slice().to_vec()).collect()
+    }
+
+    /// Seal and gift wrap `rumor` exactly as NIP-59 describes, with fixed keys and timestamps
+    fn wrap(sender: &Keys, recipient: &PublicKey, rumor: &UnsignedEvent) -> Event {
+        let encrypted_rumor = nip44::encrypt(
+            sender.secret_key(),
+            recipient,
+            serde_json::to_string(rumor).unwrap(),
+            nip44::Version::V2,
+        )
+        .unwrap();
+        let seal = EventBuilder::new(Kind::Seal, encrypted_rumor)
+            .custom_created_at(Timestamp::from(WRAP_CREATED_AT))
+            .sign_with_keys(sender)
+            .unwrap();
+
+        let ephemeral = keys(EPHEMERAL_SECRET);
+        let encrypted_seal = nip44::encrypt(
+            ephemeral.secret_key(),
+            recipient,
+            serde_json::to_string(&seal).unwrap(),
+            nip44::Version::V2,
+        )
+        .unwrap();
+        EventBuilder::new(Kind::GiftWrap, encrypted_seal)
+            .tags([
+                Tag::public_key(*recipient),
+                Tag::expiration(Timestamp::from(EXPIRATION)),
+            ])
+            .custom_created_at(Timestamp::from(WRAP_CREATED_AT))
+            .sign_with_keys(&ephemeral)
+            .unwrap()
+    }
+
+    fn vector(
+        name: String,
+        sender_secret: &str,
+        recipient_secret: &str,
+        kind: u16,
+        content: String,
+        tags: Vec<Tag>,
+    ) -> GiftWrapVector {
+        let sender = keys(sender_secret);
+        let recipient = keys(recipient_secret);
+        let mut rumor = UnsignedEvent::new(
+            sender.public_key(),
+            Timestamp::from(RUMOR_CREATED_AT),
+            Kind::from(kind),
+            tags,
+            content,
+        );
+        rumor.ensure_id();
+
+        GiftWrapVector {
+            name,
+            sender_secret: sender_secret.to_string(),
+            sender_pubkey: sender.public_key().to_hex(),
+            recipient_secret: recipient_secret.to_string(),
+            recipient_pubkey: recipient.public_key().to_hex(),
+            gift_wrap: wrap(&sender, &recipient.public_key(), &rumor),
+            rumor,
+        }
+    }
+
+    /// The original single-vector export: a preview response to a request id
+    fn basic_vectors() -> Vec<GiftWrapVector> {
         let content = json!({
             "type": "preview_response",
             "success": true,
@@ -25,185 +145,212 @@ mod tests {
             "member_count": 42
         })
         .to_string();
-
-        let rumor = UnsignedEvent::new(
-            sender_keys.public_key(),
-            Timestamp::now(),
-            Kind::from(27493), // Response kind
+        vec![vector(
+            "preview_response".to_string(),
+            SERVICE_SECRET,
+            CLIENT_SECRET,
+            RESPONSE_KIND,
+            content,
             vec![Tag::custom(
                 TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::E)),
-                vec!["test-request-id".to_string()],
+                ["test-request-id"],
             )],
-            content.clone(),
-        );
-
-        println!("Rumor created with content: {}", content);
-
-        // Method 1: Using EventBuilder::seal and gift_wrap_from_seal
-        println!("\n=== Testing Method 1: EventBuilder::seal + gift_wrap_from_seal ===");
-
-        // Create seal
-        let seal = EventBuilder::seal(&sender_keys, &recipient_keys.public_key(), rumor.clone())
-            .await
-            .expect("Failed to create seal")
-            .sign_with_keys(&sender_keys)
-            .expect("Failed to sign seal");
-
-        println!("Seal created: {:?}", seal.id);
+        )]
+    }
 
-        // Create gift wrap from seal
-        let gift_wrap =
-            EventBuilder::gift_wrap_from_seal(&recipient_keys.public_key(), &seal, vec![])
-                .expect("Failed to create gift wrap from seal");
+    /// Every response fixture from the wire contract, as the service sends it to the client
+    fn variant_vectors() -> Vec<GiftWrapVector> {
+        all_responses()
+            .iter()
+            .enumerate()
+            .map(|(i, response)| {
+                vector(
+                    format!("{:02}_{}", i, response_type(response)),
+                    SERVICE_SECRET,
+                    CLIENT_SECRET,
+                    RESPONSE_KIND,
+                    serde_json::to_string(response).unwrap(),
+                    // Responses reference the request rumor they answer
+                    vec![Tag::event(EventId::all_zeros())],
+                )
+            })
+            .collect()
+    }
 
-        println!("Gift wrap created: {:?}", gift_wrap.id);
-        println!(
-            "Gift wrap pubkey (ephemeral): {}",
-            gift_wrap.pubkey.to_hex()
+    /// Open a vector's gift wrap with the recipient key, checking every NIP-59 layer
+    /// Returns the rumor found inside the seal
+    fn unwrap(vector: &GiftWrapVector) -> UnsignedEvent {
+        let recipient = keys(&vector.recipient_secret);
+        assert_eq!(recipient.public_key().to_hex(), vector.recipient_pubkey);
+        assert_eq!(
+            keys(&vector.sender_secret).public_key().to_hex(),
+            vector.sender_pubkey
         );
 
-        // Try to unwrap it
-        println!("\nAttempting to unwrap gift wrap...");
-
-        // First decrypt the outer layer (gift wrap)
-        use nostr_sdk::nips::nip44;
+        let gift_wrap = &vector.gift_wrap;
+        gift_wrap.verify().expect("gift wrap signature");
+        assert_eq!(gift_wrap.kind, Kind::GiftWrap);
+        assert_ne!(gift_wrap.pubkey.to_hex(), vector.sender_pubkey);
+        let wrap_tags = tag_values(&gift_wrap.tags);
+        assert_eq!(
+            wrap_tags[0],
+            vec!["p".to_string(), vector.recipient_pubkey.clone()]
+        );
+        assert!(
+            wrap_tags[1..].iter().all(|tag| tag[0] == "expiration"),
+            "unexpected gift wrap tags {:?}",
+            wrap_tags
+        );
 
-        let decrypted_seal_json = nip44::decrypt(
-            recipient_keys.secret_key(),
+        let seal_json = nip44::decrypt(
+            recipient.secret_key(),
             &gift_wrap.pubkey,
             &gift_wrap.content,
         )
-        .expect("Failed to decrypt gift wrap");
-
-        println!("Successfully decrypted outer layer");
-
-        let seal_from_wrap: Event = serde_json::from_str(&decrypted_seal_json)
-            .expect("Failed to parse seal from gift wrap");
-
-        println!("Extracted seal from gift wrap: {:?}", seal_from_wrap.id);
-
-        // Now decrypt the seal to get the rumor
-        let decrypted_rumor_json = nip44::decrypt(
-            recipient_keys.secret_key(),
-            &seal_from_wrap.pubkey,
-            &seal_from_wrap.content,
-        )
-        .expect("Failed to decrypt seal");
-
-        println!("Successfully decrypted seal");
-
-        let rumor_from_seal: UnsignedEvent =
-            serde_json::from_str(&decrypted_rumor_json).expect("Failed to parse rumor from seal");
-
-        println!("Extracted rumor content: {}", rumor_from_seal.content);
-
-        assert_eq!(rumor_from_seal.content, content);
-        println!("✅ Content matches!");
+        .expect("decrypt gift wrap");
+        let seal: Event = serde_json::from_str(&seal_json).unwrap();
+        seal.verify().expect("seal signature");
+        assert_eq!(seal.kind, Kind::Seal);
+        assert!(seal.tags.is_empty(), "seal must not carry tags");
+        assert_eq!(seal.pubkey.to_hex(), vector.sender_pubkey);
+
+        let rumor_json = nip44::decrypt(recipient.secret_key(), &seal.pubkey, &seal.content)
+            .expect("decrypt seal");
+        let rumor: UnsignedEvent = serde_json::from_str(&rumor_json).unwrap();
+        assert_eq!(
+            rumor.pubkey, seal.pubkey,
+            "rumor author must match seal signer"
+        );
+        let computed_id = EventId::new(
+            &rumor.pubkey,
+            &rumor.created_at,
+            &rumor.kind,
+            rumor.tags.as_slice(),
+            &rumor.content,
+        );
+        assert_eq!(rumor.id, Some(computed_id), "rumor id must hash its fields");
+        rumor
+    }
 
-        // Method 2: Using Client gift_wrap (if we have a client)
-        println!("\n=== Testing Method 2: Client::gift_wrap ===");
+    /// Unwrap every vector and compare the rumor with the one recorded next to it
+    fn assert_vectors_open(vectors: &[GiftWrapVector]) {
+        for vector in vectors {
+            let rumor = unwrap(vector);
+            assert_eq!(rumor.id, vector.rumor.id, "{}", vector.name);
+            assert_eq!(rumor.kind, vector.rumor.kind, "{}", vector.name);
+            assert_eq!(rumor.created_at, vector.rumor.created_at, "{}", vector.name);
+            assert_eq!(rumor.content, vector.rumor.content, "{}", vector.name);
+            assert_eq!(
+                tag_values(&rumor.tags),
+                tag_values(&vector.rumor.tags),
+                "{}",
+                vector.name
+            );
+        }
+    }
 
-        let client = Client::new(sender_keys.clone());
+    #[test]
+    fn test_rust_vectors_round_trip() {
+        let vectors: Vec<_> = basic_vectors()
+            .into_iter()
+            .chain(variant_vectors())
+            .collect();
+        assert_vectors_open(&vectors);
+
+        // Rumors are fully determined by the fixed keys and timestamps
+        let again = basic_vectors().into_iter().chain(variant_vectors());
+        for (first, second) in vectors.iter().zip(again) {
+            assert_eq!(first.rumor.id, second.rumor.id, "{}", first.name);
+        }
+    }
 
-        // Try the client's gift_wrap method
-        match client
-            .gift_wrap(&recipient_keys.public_key(), rumor.clone(), vec![])
-            .await
-        {
-            Ok(output) => {
-                println!("✅ Client gift_wrap succeeded: {:?}", output.id());
-            }
-            Err(e) => {
-                println!("❌ Client gift_wrap failed: {:?}", e);
+    #[test]
+    fn test_checked_in_rust_vectors_still_open() {
+        let basic = load("gift_wrap_rust.json").expect("gift_wrap_rust.json is checked in");
+        assert_vectors_open(&basic.vectors);
+
+        // Once exported, the variant set must keep matching today's response fixtures
+        if let Some(variants) = load("gift_wrap_rust_variants.json") {
+            assert_vectors_open(&variants.vectors);
+            let expected: Vec<String> = variant_vectors().into_iter().map(|v| v.name).collect();
+            let recorded: Vec<String> = variants.vectors.iter().map(|v| v.name.clone()).collect();
+            assert_eq!(
+                recorded, expected,
+                "response fixtures changed; regenerate gift_wrap_rust_variants.json"
+            );
+            for (vector, response) in variants.vectors.iter().zip(all_responses()) {
+                let parsed: ServiceResponse = serde_json::from_str(&vector.rumor.content).unwrap();
+                assert_eq!(parsed, response, "{}", vector.name);
             }
         }
     }
 
-    #[tokio::test]
-    async fn test_manual_gift_wrap() {
-        // Test manual gift wrap creation like in our service
-        let sender_keys = Keys::generate();
-        let recipient_keys = Keys::generate();
-
-        println!("Testing manual gift wrap creation...");
-        println!("Sender: {}", sender_keys.public_key().to_hex());
-        println!("Recipient: {}", recipient_keys.public_key().to_hex());
-
-        let content = json!({"test": "data"}).to_string();
-        let rumor = UnsignedEvent::new(
-            sender_keys.public_key(),
-            Timestamp::now(),
-            Kind::from(27493),
-            vec![],
-            content.clone(),
-        );
-
-        // Manually create seal
-        use nostr_sdk::nips::nip44;
-
-        let rumor_json = serde_json::to_string(&rumor).unwrap();
-
-        // Encrypt rumor for recipient
-        let encrypted_rumor = nip44::encrypt(
-            sender_keys.secret_key(),
-            &recipient_keys.public_key(),
-            &rumor_json,
-            nip44::Version::default(),
-        )
-        .expect("Failed to encrypt rumor");
-
-        // Create seal event
-        let seal = EventBuilder::new(Kind::Seal, encrypted_rumor)
-            .custom_created_at(Timestamp::now())
-            .sign_with_keys(&sender_keys)
-            .expect("Failed to create seal");
-
-        println!("Seal created manually: {:?}", seal.id);
-
-        // Create gift wrap with ephemeral keys
-        let ephemeral_keys = Keys::generate();
-        println!("Ephemeral pubkey: {}", ephemeral_keys.public_key().to_hex());
-
-        let seal_json = serde_json::to_string(&seal).unwrap();
-
-        // Encrypt seal for recipient using ephemeral keys
-        let encrypted_seal = nip44::encrypt(
-            ephemeral_keys.secret_key(),
-            &recipient_keys.public_key(),
-            &seal_json,
-            nip44::Version::default(),
-        )
-        .expect("Failed to encrypt seal");
-
-        // Create gift wrap event
-        let gift_wrap = EventBuilder::new(Kind::GiftWrap, encrypted_seal)
-            .tag(Tag::public_key(recipient_keys.public_key()))
-            .custom_created_at(Timestamp::tweaked(0..2 * 24 * 60 * 60))
-            .sign_with_keys(&ephemeral_keys)
-            .expect("Failed to create gift wrap");
-
-        println!("Gift wrap created manually: {:?}", gift_wrap.id);
-
-        // Verify we can decrypt it
-        let decrypted_seal_json = nip44::decrypt(
-            recipient_keys.secret_key(),
-            &gift_wrap.pubkey,
-            &gift_wrap.content,
-        )
-        .expect("Failed to decrypt gift wrap");
-
-        let seal_from_wrap: Event = serde_json::from_str(&decrypted_seal_json).unwrap();
-
-        let decrypted_rumor_json = nip44::decrypt(
-            recipient_keys.secret_key(),
-            &seal_from_wrap.pubkey,
-            &seal_from_wrap.content,
-        )
-        .expect("Failed to decrypt seal");
+    #[test]
+    fn test_checked_in_js_vectors_open() {
+        let basic = load("gift_wrap_js.json").expect("gift_wrap_js.json is checked in");
+        assert_vectors_open(&basic.vectors);
+        for vector in &basic.vectors {
+            assert_eq!(vector.rumor.kind, Kind::from(REQUEST_KIND));
+            assert!(serde_json::from_str::<ServiceRequest>(&vector.rumor.content).is_ok());
+        }
+    }
 
-        let rumor_from_seal: UnsignedEvent = serde_json::from_str(&decrypted_rumor_json).unwrap();
+    #[test]
+    fn test_js_variant_vectors_cover_every_request() {
+        let variants =
+            load("gift_wrap_js_variants.json").expect("gift_wrap_js_variants.json is checked in");
+        assert_vectors_open(&variants.vectors);
+
+        let known = all_requests();
+        let mut covered = HashSet::new();
+        for vector in &variants.vectors {
+            assert_eq!(
+                vector.rumor.kind,
+                Kind::from(REQUEST_KIND),
+                "{}",
+                vector.name
+            );
+            let request: ServiceRequest = serde_json::from_str(&vector.rumor.content)
+                .unwrap_or_else(|e| panic!("{}: {}", vector.name, e));
+            assert!(
+                known.contains(&request),
+                "{} no longer matches a wire contract fixture",
+                vector.name
+            );
+            covered.insert(request_type(&request));
+        }
+        for ty in REQUEST_TYPES {
+            assert!(
+                covered.contains(ty),
+                "no JS vector for request {}; rerun the fixture generator",
+                ty
+            );
+        }
+    }
 
-        assert_eq!(rumor_from_seal.content, content);
-        println!("✅ Manual gift wrap roundtrip successful!");
+    /// Rewrite the Rust-generated vector files consumed by the TypeScript client
+    #[test]
+    #[ignore]
+    fn regenerate_rust_vectors() {
+        let files = [
+            (
+                "gift_wrap_rust.json",
+                "Rust (nostr-sdk) gift wraps from the service to a client",
+                basic_vectors(),
+            ),
+            (
+                "gift_wrap_rust_variants.json",
+                "Rust (nostr-sdk) gift wraps of every service response variant",
+                variant_vectors(),
+            ),
+        ];
+        for (file, description, vectors) in files {
+            let file_contents = VectorFile {
+                description: description.to_string(),
+                vectors,
+            };
+            let json = serde_json::to_string_pretty(&file_contents).unwrap();
+            std::fs::write(fixtures_dir().join(file), json + "\n").unwrap();
+        }
     }
 }
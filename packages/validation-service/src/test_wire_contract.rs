@@ -0,0 +1,1815 @@
+//! Wire contract for the gift-wrapped request/response payloads shared with the TypeScript client
+//!
+//! Every canonical fixture is an inline insta snapshot of our serialization, and is parsed back
+//! to check the other direction. Non-canonical forms (absent optional fields, legacy untyped
+//! payloads) are checked for deserialization only.
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::handlers::error_codes::{DistanceBucket, ValidationErrorCode};
+    use crate::handlers::nostr_validation::{
+        ExistingCommunity, LocationData, LocationValidationRequest, LocationValidationResponse,
+        MemberRole, PreviewResult, ServiceRequest, ServiceResponse, Welcome,
+        SUPPORTED_REQUEST_TYPES,
+    };
+    use crate::handlers::schema::protocol_schema;
+    use crate::libraries::attestation::Attestation;
+    use crate::services::bulk_members::{BulkAddOutcome, BulkAddStatus};
+    use crate::services::community_export::{
+        CommunityExport, ExportStats, ExportedMember, ExportedMetadata, ExportedRole,
+    };
+    use crate::services::join_requests::{JoinMode, JoinRequestStatus};
+    use crate::services::quiet_hours::QuietHours;
+    use crate::services::relay_limits::AuthScope;
+    use crate::services::venue_hours::VenueHoursStatus;
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::collections::{BTreeMap, HashSet};
+    use std::fmt::Debug;
+
+    const COMMUNITY_ID: &str = "3a7e5c59-c0a1-4876-acf1-56189b86aa0d";
+    const APPLICANT: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    /// Every ServiceRequest "type" tag, as advertised in the service descriptor.
+    /// Keep in sync with `request_type`.
+    pub(crate) const REQUEST_TYPES: &[&str] = SUPPORTED_REQUEST_TYPES;
+
+    /// Every ServiceResponse "type" tag. Keep in sync with `response_type`.
+    const RESPONSE_TYPES: &[&str] = &[
+        "location_validation_response",
+        "preview_response",
+        "preview_batch_response",
+        "add_anchor_response",
+        "update_metadata_response",
+        "approve_join_response",
+        "join_request_update",
+        "cancel_join_request_response",
+        "archive_community_response",
+        "export_community_response",
+        "bulk_add_members_response",
+        "member_joined",
+        "inactivity_warning",
+        "sticker_reuse_suspected",
+    ];
+
+    // Exhaustive on purpose: a new variant fails to compile here until it is listed
+    // in REQUEST_TYPES, and then fails `test_every_variant_has_a_fixture` until it has a fixture
+    pub(crate) fn request_type(request: &ServiceRequest) -> &'static str {
+        match request {
+            ServiceRequest::LocationValidation { .. } => "location_validation",
+            ServiceRequest::PreviewRequest { .. } => "preview_request",
+            ServiceRequest::PreviewBatch { .. } => "preview_batch",
+            ServiceRequest::AddAnchor { .. } => "add_anchor",
+            ServiceRequest::UpdateMetadata { .. } => "update_metadata",
+            ServiceRequest::ApproveJoin { .. } => "approve_join",
+            ServiceRequest::CancelJoinRequest { .. } => "cancel_join_request",
+            ServiceRequest::ArchiveCommunity { .. } => "archive_community",
+            ServiceRequest::UnarchiveCommunity { .. } => "unarchive_community",
+            ServiceRequest::ExportCommunity { .. } => "export_community",
+            ServiceRequest::BulkAddMembers { .. } => "bulk_add_members",
+        }
+    }
+
+    pub(crate) fn response_type(response: &ServiceResponse) -> &'static str {
+        match response {
+            ServiceResponse::LocationValidation { .. } => "location_validation_response",
+            ServiceResponse::Preview(_) => "preview_response",
+            ServiceResponse::PreviewBatch { .. } => "preview_batch_response",
+            ServiceResponse::AddAnchor { .. } => "add_anchor_response",
+            ServiceResponse::UpdateMetadata { .. } => "update_metadata_response",
+            ServiceResponse::ApproveJoin { .. } => "approve_join_response",
+            ServiceResponse::JoinRequestUpdate { .. } => "join_request_update",
+            ServiceResponse::MemberJoined { .. } => "member_joined",
+            ServiceResponse::InactivityWarning { .. } => "inactivity_warning",
+            ServiceResponse::StickerReuseSuspected { .. } => "sticker_reuse_suspected",
+            ServiceResponse::CancelJoinRequest { .. } => "cancel_join_request_response",
+            ServiceResponse::ArchiveCommunity { .. } => "archive_community_response",
+            ServiceResponse::ExportCommunity { .. } => "export_community_response",
+            ServiceResponse::BulkAddMembers { .. } => "bulk_add_members_response",
+        }
+    }
+
+    fn location() -> LocationData {
+        LocationData {
+            latitude: 37.7749,
+            longitude: -122.4194,
+            accuracy: Some(12.5),
+            timestamp: 1760000000,
+        }
+    }
+
+    fn location_validation_request(active_until: Option<u64>) -> ServiceRequest {
+        ServiceRequest::LocationValidation {
+            community_id: COMMUNITY_ID.to_string(),
+            location: location(),
+            active_until,
+            force: false,
+            remote_venue: false,
+            max_members: None,
+            age_restricted: false,
+            transfer: false,
+        }
+    }
+
+    fn forced_location_validation_request() -> ServiceRequest {
+        ServiceRequest::LocationValidation {
+            community_id: COMMUNITY_ID.to_string(),
+            location: location(),
+            active_until: None,
+            force: true,
+            remote_venue: false,
+            max_members: None,
+            age_restricted: false,
+            transfer: false,
+        }
+    }
+
+    fn remote_venue_location_validation_request() -> ServiceRequest {
+        ServiceRequest::LocationValidation {
+            community_id: COMMUNITY_ID.to_string(),
+            location: location(),
+            active_until: None,
+            force: false,
+            remote_venue: true,
+            max_members: None,
+            age_restricted: false,
+            transfer: false,
+        }
+    }
+
+    fn preview_request() -> ServiceRequest {
+        ServiceRequest::PreviewRequest {
+            community_id: COMMUNITY_ID.to_string(),
+        }
+    }
+
+    fn add_anchor_request() -> ServiceRequest {
+        ServiceRequest::AddAnchor {
+            community_id: COMMUNITY_ID.to_string(),
+            location: location(),
+        }
+    }
+
+    fn update_metadata_request() -> ServiceRequest {
+        ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: Some(JoinMode::Approval),
+            max_members: None,
+            name: None,
+            about: None,
+            welcome: None,
+            slug: None,
+            join_notifications: None,
+            age_restricted: None,
+            inactive_prune_days: None,
+            quiet_hours: None,
+        }
+    }
+
+    fn approve_join_request() -> ServiceRequest {
+        ServiceRequest::ApproveJoin {
+            community_id: COMMUNITY_ID.to_string(),
+            pubkey: APPLICANT.to_string(),
+            approve: true,
+        }
+    }
+
+    fn cancel_join_request() -> ServiceRequest {
+        ServiceRequest::CancelJoinRequest {
+            community_id: COMMUNITY_ID.to_string(),
+        }
+    }
+
+    fn archive_community_request() -> ServiceRequest {
+        ServiceRequest::ArchiveCommunity {
+            community_id: COMMUNITY_ID.to_string(),
+        }
+    }
+
+    fn unarchive_community_request() -> ServiceRequest {
+        ServiceRequest::UnarchiveCommunity {
+            community_id: COMMUNITY_ID.to_string(),
+        }
+    }
+
+    fn export_community_request() -> ServiceRequest {
+        ServiceRequest::ExportCommunity {
+            community_id: COMMUNITY_ID.to_string(),
+        }
+    }
+
+    fn location_validation_response() -> ServiceResponse {
+        ServiceResponse::LocationValidation {
+            success: true,
+            group_id: Some("peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d".to_string()),
+            relay_url: Some("wss://communities2.nos.social".to_string()),
+            is_admin: Some(true),
+            is_member: Some(true),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        }
+    }
+
+    fn pending_join_response() -> ServiceResponse {
+        ServiceResponse::LocationValidation {
+            success: true,
+            group_id: Some("peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d".to_string()),
+            relay_url: Some("wss://communities2.nos.social".to_string()),
+            is_admin: Some(false),
+            is_member: Some(false),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+            existing_community: None,
+            status: Some(JoinRequestStatus::Pending),
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        }
+    }
+
+    fn nearby_community_exists_response() -> ServiceResponse {
+        ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("A community already exists at this location".to_string()),
+            error_code: Some("NEARBY_COMMUNITY_EXISTS".to_string()),
+            message_key: Some("error.nearby_community_exists".to_string()),
+            params: Some(BTreeMap::new()),
+            existing_community: Some(ExistingCommunity {
+                community_id: Some(COMMUNITY_ID.to_string()),
+                group_id: "peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d".to_string(),
+                preview: PreviewResult {
+                    success: true,
+                    name: Some("Blue Bottle Coffee".to_string()),
+                    member_count: Some(3),
+                    is_public: Some(false),
+                    is_open: Some(false),
+                    created_at: Some(1759163304),
+                    archived: Some(false),
+                    ..Default::default()
+                },
+            }),
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        }
+    }
+
+    fn location_invalid_response() -> ServiceResponse {
+        ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Location outside community area".to_string()),
+            error_code: Some("LOCATION_INVALID".to_string()),
+            message_key: Some("error.location_invalid".to_string()),
+            params: Some(BTreeMap::from([(
+                "distance_bucket".to_string(),
+                "100m_to_1km".to_string(),
+            )])),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        }
+    }
+
+    fn preview_response(archived: Option<bool>) -> ServiceResponse {
+        ServiceResponse::Preview(PreviewResult {
+            success: true,
+            name: Some("Blue Bottle Coffee".to_string()),
+            about: Some("Location-based community".to_string()),
+            member_count: Some(3),
+            is_public: Some(false),
+            is_open: Some(false),
+            created_at: Some(1759163304),
+            archived,
+            ..Default::default()
+        })
+    }
+
+    fn add_anchor_response() -> ServiceResponse {
+        ServiceResponse::AddAnchor {
+            success: true,
+            anchor_count: Some(2),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn update_metadata_response() -> ServiceResponse {
+        ServiceResponse::UpdateMetadata {
+            success: true,
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn approve_join_response() -> ServiceResponse {
+        ServiceResponse::ApproveJoin {
+            success: true,
+            status: Some(JoinRequestStatus::Approved),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn join_request_update(status: JoinRequestStatus) -> ServiceResponse {
+        ServiceResponse::join_request_update(
+            COMMUNITY_ID.to_string(),
+            APPLICANT.to_string(),
+            status,
+        )
+    }
+
+    fn member_joined() -> ServiceResponse {
+        ServiceResponse::MemberJoined {
+            community_id: COMMUNITY_ID.to_string(),
+            community_name: "Blue Bottle Coffee".to_string(),
+            member_count: 14,
+            pubkeys: vec![APPLICANT.to_string()],
+        }
+    }
+
+    fn inactivity_warning() -> ServiceResponse {
+        ServiceResponse::InactivityWarning {
+            community_id: COMMUNITY_ID.to_string(),
+            community_name: "Blue Bottle Coffee".to_string(),
+            inactive_days: 23,
+            remove_after: 1760604800,
+        }
+    }
+
+    fn sticker_reuse_suspected() -> ServiceResponse {
+        ServiceResponse::StickerReuseSuspected {
+            community_id: COMMUNITY_ID.to_string(),
+            community_name: "Blue Bottle Coffee".to_string(),
+            cluster: "9q9p1".to_string(),
+            distance_km: 13,
+            failures: 12,
+        }
+    }
+
+    fn preview_batch_request() -> ServiceRequest {
+        ServiceRequest::PreviewBatch {
+            community_ids: vec![
+                COMMUNITY_ID.to_string(),
+                "9c1f0e2b-7d4a-4b8e-a5f3-2e6d8c0b1a47".to_string(),
+            ],
+        }
+    }
+
+    fn preview_batch_response() -> ServiceResponse {
+        let ServiceResponse::Preview(found) = preview_response(None) else {
+            unreachable!()
+        };
+        ServiceResponse::PreviewBatch {
+            success: true,
+            results: vec![
+                found,
+                PreviewResult {
+                    success: false,
+                    error: Some("Community not found".to_string()),
+                    ..Default::default()
+                },
+            ],
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn cancel_join_request_response() -> ServiceResponse {
+        ServiceResponse::CancelJoinRequest {
+            success: true,
+            status: Some(JoinRequestStatus::Cancelled),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn archive_community_response(archived: bool) -> ServiceResponse {
+        ServiceResponse::ArchiveCommunity {
+            success: true,
+            archived: Some(archived),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn community_export() -> CommunityExport {
+        CommunityExport {
+            format: "peek.community-export".to_string(),
+            version: 1,
+            exported_at: 1760000000,
+            community_id: COMMUNITY_ID.parse().unwrap(),
+            group_id: "peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d".to_string(),
+            metadata: ExportedMetadata {
+                name: "Blue Bottle Coffee".to_string(),
+                about: None,
+                rules: Vec::new(),
+                picture: None,
+                welcome: None,
+                geohash_cells: vec!["9q8yyk8y".to_string()],
+                join_mode: JoinMode::Auto,
+                max_members: None,
+                active_until: None,
+                archived: false,
+                updated_at: 1759163304,
+            },
+            members: vec![ExportedMember {
+                pubkey: APPLICANT.to_string(),
+                role: ExportedRole::Admin,
+                joined_at: Some(1759163304),
+            }],
+            stats: ExportStats {
+                member_count: 1,
+                admin_count: 1,
+                anchor_count: 1,
+                first_join_at: Some(1759163304),
+                members_incomplete: false,
+            },
+        }
+    }
+
+    fn export_community_response() -> ServiceResponse {
+        ServiceResponse::ExportCommunity {
+            success: true,
+            export: Some(Box::new(community_export())),
+            export_url: None,
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn uploaded_export_community_response() -> ServiceResponse {
+        ServiceResponse::ExportCommunity {
+            success: true,
+            export: None,
+            export_url: Some("https://media.peek.hol.is/5f2b1c.json".to_string()),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    fn bulk_add_members_request() -> ServiceRequest {
+        ServiceRequest::BulkAddMembers {
+            community_id: COMMUNITY_ID.to_string(),
+            pubkeys: vec![APPLICANT.to_string(), "not-a-pubkey".to_string()],
+        }
+    }
+
+    fn bulk_add_members_response() -> ServiceResponse {
+        ServiceResponse::BulkAddMembers {
+            success: true,
+            outcomes: vec![
+                BulkAddOutcome {
+                    pubkey: APPLICANT.to_string(),
+                    status: BulkAddStatus::Added,
+                },
+                BulkAddOutcome {
+                    pubkey: "not-a-pubkey".to_string(),
+                    status: BulkAddStatus::Invalid,
+                },
+            ],
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+        }
+    }
+
+    pub(crate) fn all_requests() -> Vec<ServiceRequest> {
+        vec![
+            location_validation_request(Some(1760086400)),
+            location_validation_request(None),
+            forced_location_validation_request(),
+            preview_request(),
+            preview_batch_request(),
+            add_anchor_request(),
+            update_metadata_request(),
+            approve_join_request(),
+            cancel_join_request(),
+            archive_community_request(),
+            unarchive_community_request(),
+            export_community_request(),
+            bulk_add_members_request(),
+        ]
+    }
+
+    pub(crate) fn all_responses() -> Vec<ServiceResponse> {
+        vec![
+            location_validation_response(),
+            nearby_community_exists_response(),
+            location_invalid_response(),
+            preview_response(None),
+            preview_response(Some(true)),
+            preview_batch_response(),
+            add_anchor_response(),
+            pending_join_response(),
+            update_metadata_response(),
+            approve_join_response(),
+            join_request_update(JoinRequestStatus::Pending),
+            join_request_update(JoinRequestStatus::Expired),
+            cancel_join_request_response(),
+            archive_community_response(true),
+            archive_community_response(false),
+            export_community_response(),
+            uploaded_export_community_response(),
+            bulk_add_members_response(),
+            member_joined(),
+            inactivity_warning(),
+            sticker_reuse_suspected(),
+        ]
+    }
+
+    fn to_json<T: Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    /// Parse a snapshot-verified fixture back and compare with the value it came from
+    fn assert_parses_to<T: DeserializeOwned + PartialEq + Debug>(fixture: &str, expected: &T) {
+        let parsed: T = serde_json::from_str(fixture).unwrap();
+        assert_eq!(&parsed, expected);
+    }
+
+    #[test]
+    fn test_every_variant_has_a_fixture() {
+        let request_types: HashSet<_> = all_requests().iter().map(request_type).collect();
+        for ty in REQUEST_TYPES {
+            assert!(request_types.contains(ty), "no fixture for request {}", ty);
+        }
+        assert_eq!(request_types.len(), REQUEST_TYPES.len());
+
+        let response_types: HashSet<_> = all_responses().iter().map(response_type).collect();
+        for ty in RESPONSE_TYPES {
+            assert!(
+                response_types.contains(ty),
+                "no fixture for response {}",
+                ty
+            );
+        }
+        assert_eq!(response_types.len(), RESPONSE_TYPES.len());
+    }
+
+    /// The "type" tags the schema's `definition` enum accepts, one per variant
+    fn schema_type_tags(schema: &serde_json::Value, definition: &str) -> HashSet<String> {
+        schema["definitions"][definition]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| {
+                variant["properties"]["type"]["enum"][0]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_every_variant_is_in_the_protocol_schema() {
+        let schema = protocol_schema();
+
+        let request_tags = schema_type_tags(&schema, "ServiceRequest");
+        for ty in REQUEST_TYPES {
+            assert!(request_tags.contains(*ty), "no schema for request {}", ty);
+        }
+        assert_eq!(request_tags.len(), REQUEST_TYPES.len());
+
+        let response_tags = schema_type_tags(&schema, "ServiceResponse");
+        for ty in RESPONSE_TYPES {
+            assert!(response_tags.contains(*ty), "no schema for response {}", ty);
+        }
+        assert_eq!(response_tags.len(), RESPONSE_TYPES.len());
+    }
+
+    #[test]
+    fn test_type_tags_match_serialized_type_field() {
+        for request in all_requests() {
+            let json: serde_json::Value = serde_json::to_value(&request).unwrap();
+            assert_eq!(json["type"], request_type(&request));
+        }
+        for response in all_responses() {
+            let json: serde_json::Value = serde_json::to_value(&response).unwrap();
+            assert_eq!(json["type"], response_type(&response));
+        }
+    }
+
+    #[test]
+    fn test_location_validation_request_contract() {
+        let request = location_validation_request(Some(1760086400));
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000},"active_until":1760086400}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_location_validation_request_without_active_until_contract() {
+        let request = location_validation_request(None);
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000}}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_forced_location_validation_request_contract() {
+        let request = forced_location_validation_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000},"force":true}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_remote_venue_location_validation_request_contract() {
+        let request = remote_venue_location_validation_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000},"remote_venue":true}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_age_restricted_location_validation_request_contract() {
+        let mut request = location_validation_request(None);
+        let ServiceRequest::LocationValidation { age_restricted, .. } = &mut request else {
+            unreachable!()
+        };
+        *age_restricted = true;
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000},"age_restricted":true}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_transfer_location_validation_request_contract() {
+        let mut request = location_validation_request(None);
+        let ServiceRequest::LocationValidation { transfer, .. } = &mut request else {
+            unreachable!()
+        };
+        *transfer = true;
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000},"transfer":true}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_location_validation_request_null_active_until_is_absent() {
+        assert_parses_to(
+            r#"{"type":"location_validation","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000},"active_until":null}"#,
+            &location_validation_request(None),
+        );
+    }
+
+    #[test]
+    fn test_location_without_accuracy_parses_as_unknown() {
+        let json = r#"{"latitude":37.7749,"longitude":-122.4194,"timestamp":1760000000}"#;
+        let location: LocationData = serde_json::from_str(json).unwrap();
+        assert_eq!(location.accuracy, None);
+        assert!(location.check().unwrap().accuracy.is_unknown());
+        assert_eq!(to_json(&location), json);
+    }
+
+    /// Every time on the wire is whole unix seconds as a JSON number, never an RFC3339 string
+    /// or milliseconds; the TypeScript client parses them all the same way
+    fn assert_times_are_unix_seconds(path: &str, value: &serde_json::Value) {
+        match value {
+            serde_json::Value::Object(fields) => {
+                for (key, field) in fields {
+                    let path = format!("{}.{}", path, key);
+                    let is_time =
+                        key == "timestamp" || key.ends_with("_at") || key.ends_with("_until");
+                    if is_time && !field.is_null() {
+                        let seconds = field
+                            .as_u64()
+                            .unwrap_or_else(|| panic!("{} is {}, not unix seconds", path, field));
+                        assert!(
+                            (1_000_000_000..10_000_000_000).contains(&seconds),
+                            "{} = {} is not in seconds",
+                            path,
+                            seconds
+                        );
+                    }
+                    assert_times_are_unix_seconds(&path, field);
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    assert_times_are_unix_seconds(&format!("{}[{}]", path, index), item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_wire_time_is_unix_seconds() {
+        for request in all_requests() {
+            let json = serde_json::to_value(&request).unwrap();
+            assert_times_are_unix_seconds(request_type(&request), &json);
+        }
+        for response in all_responses() {
+            let json = serde_json::to_value(&response).unwrap();
+            assert_times_are_unix_seconds(response_type(&response), &json);
+        }
+    }
+
+    #[test]
+    fn test_location_timestamp_must_be_whole_seconds() {
+        for timestamp in [
+            r#""2025-10-09T08:53:20Z""#,
+            "1760000000.5",
+            r#""1760000000""#,
+        ] {
+            let json = format!(
+                r#"{{"latitude":37.7749,"longitude":-122.4194,"timestamp":{}}}"#,
+                timestamp
+            );
+            assert!(
+                serde_json::from_str::<LocationData>(&json).is_err(),
+                "timestamp {} was accepted",
+                timestamp
+            );
+        }
+    }
+
+    #[test]
+    fn test_preview_request_contract() {
+        let request = preview_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_request","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d"}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_preview_batch_request_contract() {
+        let request = preview_batch_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_batch","community_ids":["3a7e5c59-c0a1-4876-acf1-56189b86aa0d","9c1f0e2b-7d4a-4b8e-a5f3-2e6d8c0b1a47"]}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_add_anchor_request_contract() {
+        let request = add_anchor_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"add_anchor","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000}}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_legacy_untyped_request_contract() {
+        let legacy = LocationValidationRequest {
+            request_type: None,
+            community_id: COMMUNITY_ID.to_string(),
+            location: location(),
+        };
+        let json = to_json(&legacy);
+        insta::assert_snapshot!(json, @r#"{"community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","location":{"latitude":37.7749,"longitude":-122.4194,"accuracy":12.5,"timestamp":1760000000}}"#);
+        assert_parses_to(&json, &legacy);
+
+        // Untyped payloads must not be mistaken for a unified request
+        assert!(serde_json::from_str::<ServiceRequest>(&json).is_err());
+    }
+
+    #[test]
+    fn test_location_validation_response_contract() {
+        let response = location_validation_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":true,"is_member":true,"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_attested_location_validation_response_contract() {
+        let mut response = location_validation_response();
+        let ServiceResponse::LocationValidation { attestation, .. } = &mut response else {
+            unreachable!()
+        };
+        *attestation = Some(Attestation {
+            payload: format!(
+                r#"{{"community_id":"{}","pubkey":"{}","group_id":"peek-{}","granted_at":1760000000}}"#,
+                COMMUNITY_ID, APPLICANT, COMMUNITY_ID
+            ),
+            sig: "ab".repeat(64),
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":true,"is_member":true,"error":null,"error_code":null,"attestation":{"payload":"{\"community_id\":\"3a7e5c59-c0a1-4876-acf1-56189b86aa0d\",\"pubkey\":\"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\",\"group_id\":\"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d\",\"granted_at\":1760000000}","sig":"abababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababab"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_welcomed_location_validation_response_contract() {
+        let mut response = location_validation_response();
+        let ServiceResponse::LocationValidation {
+            is_admin, welcome, ..
+        } = &mut response
+        else {
+            unreachable!()
+        };
+        *is_admin = Some(false);
+        *welcome = Some(Welcome {
+            text: "Welcome to Blue Bottle! Say hi in the chat.".to_string(),
+            rules: vec!["Be kind".to_string(), "No spam".to_string()],
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":false,"is_member":true,"error":null,"error_code":null,"welcome":{"text":"Welcome to Blue Bottle! Say hi in the chat.","rules":["Be kind","No spam"]}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_named_location_validation_response_contract() {
+        let mut response = location_validation_response();
+        let ServiceResponse::LocationValidation {
+            community_name,
+            picture,
+            ..
+        } = &mut response
+        else {
+            unreachable!()
+        };
+        *community_name = Some("Blue Bottle Coffee".to_string());
+        *picture = Some("https://example.com/blue-bottle.png".to_string());
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":true,"is_member":true,"error":null,"error_code":null,"community_name":"Blue Bottle Coffee","picture":"https://example.com/blue-bottle.png"}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_age_restricted_location_validation_response_contract() {
+        let mut response = location_validation_response();
+        let ServiceResponse::LocationValidation {
+            community_name,
+            age_restricted,
+            ..
+        } = &mut response
+        else {
+            unreachable!()
+        };
+        *community_name = Some("The Night Owl".to_string());
+        *age_restricted = Some(true);
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":true,"is_member":true,"error":null,"error_code":null,"community_name":"The Night Owl","age_restricted":true}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_auth_required_location_validation_response_contract() {
+        let mut response = location_validation_response();
+        let ServiceResponse::LocationValidation {
+            auth_required,
+            auth_scope,
+            ..
+        } = &mut response
+        else {
+            unreachable!()
+        };
+        *auth_required = Some(true);
+        *auth_scope = Some(AuthScope::Group);
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":true,"is_member":true,"error":null,"error_code":null,"auth_required":true,"auth_scope":"group"}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_dry_run_location_validation_response_contract() {
+        let mut response = location_validation_response();
+        let ServiceResponse::LocationValidation { dry_run, .. } = &mut response else {
+            unreachable!()
+        };
+        *dry_run = Some(true);
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":true,"is_member":true,"error":null,"error_code":null,"dry_run":true}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_location_validation_response_absent_optionals() {
+        assert_parses_to(
+            r#"{"type":"location_validation_response","success":false,"error":"Community is closed","error_code":"COMMUNITY_CLOSED"}"#,
+            &ServiceResponse::LocationValidation {
+                success: false,
+                group_id: None,
+                relay_url: None,
+                is_admin: None,
+                is_member: None,
+                error: Some("Community is closed".to_string()),
+                error_code: Some("COMMUNITY_CLOSED".to_string()),
+                message_key: None,
+                params: None,
+                existing_community: None,
+                status: None,
+                attestation: None,
+                welcome: None,
+                community_name: None,
+                picture: None,
+                age_restricted: None,
+                auth_required: None,
+                auth_scope: None,
+                dry_run: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_legacy_response_matches_unified_response() {
+        let legacy = LocationValidationResponse {
+            response_type: Some("location_validation_response".to_string()),
+            success: true,
+            group_id: Some("peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d".to_string()),
+            relay_url: Some("wss://communities2.nos.social".to_string()),
+            is_admin: Some(true),
+            is_member: Some(true),
+            error: None,
+            error_code: None,
+            message_key: None,
+            params: None,
+            existing_community: None,
+            status: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        assert_eq!(to_json(&legacy), to_json(&location_validation_response()));
+
+        // Older clients may omit the type field entirely
+        let untyped = LocationValidationResponse {
+            response_type: None,
+            ..legacy
+        };
+        let json = to_json(&untyped);
+        insta::assert_snapshot!(json, @r#"{"success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":true,"is_member":true,"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &untyped);
+    }
+
+    #[test]
+    fn test_localizable_error_response_contract() {
+        let response = location_invalid_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Location outside community area","error_code":"LOCATION_INVALID","message_key":"error.location_invalid","params":{"distance_bucket":"100m_to_1km"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_very_far_location_response_contract() {
+        let code = ValidationErrorCode::LocationInvalid {
+            distance_bucket: DistanceBucket::VeryFar,
+        };
+        let response = ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Location outside community area".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Location outside community area","error_code":"LOCATION_INVALID","message_key":"error.location_invalid","params":{"distance_bucket":"very_far"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_membership_unconfirmed_response_contract() {
+        let code = ValidationErrorCode::MembershipUnconfirmed;
+        let response = ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Your membership could not be confirmed yet, please try again".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Your membership could not be confirmed yet, please try again","error_code":"MEMBERSHIP_UNCONFIRMED","message_key":"error.membership_unconfirmed","params":{}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_implausible_location_response_contract() {
+        let code = ValidationErrorCode::ImplausibleLocation;
+        let response = ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Location is in open ocean".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Location is in open ocean","error_code":"IMPLAUSIBLE_LOCATION","message_key":"error.implausible_location","params":{}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_region_unsupported_response_contract() {
+        let code = ValidationErrorCode::RegionUnsupported;
+        let response = ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Communities cannot be created in this region".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Communities cannot be created in this region","error_code":"REGION_UNSUPPORTED","message_key":"error.region_unsupported","params":{}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_nearby_community_exists_response_contract() {
+        let response = nearby_community_exists_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"A community already exists at this location","error_code":"NEARBY_COMMUNITY_EXISTS","message_key":"error.nearby_community_exists","params":{},"existing_community":{"community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","preview":{"success":true,"name":"Blue Bottle Coffee","picture":null,"about":null,"rules":null,"member_count":3,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"archived":false,"error":null}}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_preview_response_contract() {
+        let response = preview_response(None);
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":true,"name":"Blue Bottle Coffee","picture":null,"about":"Location-based community","rules":null,"member_count":3,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"error":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_archived_preview_response_contract() {
+        let response = preview_response(Some(true));
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":true,"name":"Blue Bottle Coffee","picture":null,"about":"Location-based community","rules":null,"member_count":3,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"archived":true,"error":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_preview_batch_response_contract() {
+        let response = preview_batch_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_batch_response","success":true,"results":[{"success":true,"name":"Blue Bottle Coffee","picture":null,"about":"Location-based community","rules":null,"member_count":3,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"error":null},{"success":false,"name":null,"picture":null,"about":null,"rules":null,"member_count":null,"members":null,"is_public":null,"is_open":null,"created_at":null,"error":"Community not found"}],"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_batch_too_large_response_contract() {
+        let code = ValidationErrorCode::BatchTooLarge { max_batch: 20 };
+        let response = ServiceResponse::PreviewBatch {
+            success: false,
+            results: Vec::new(),
+            error: Some("At most 20 communities can be previewed at once".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_batch_response","success":false,"results":[],"error":"At most 20 communities can be previewed at once","error_code":"BATCH_TOO_LARGE","message_key":"error.batch_too_large","params":{"max_batch":"20"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_full_preview_response_contract() {
+        let response = ServiceResponse::Preview(PreviewResult {
+            success: true,
+            name: Some("Gate B Lounge".to_string()),
+            member_count: Some(500),
+            is_public: Some(false),
+            is_open: Some(false),
+            created_at: Some(1759163304),
+            archived: Some(false),
+            max_members: Some(500),
+            is_full: Some(true),
+            ..Default::default()
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":true,"name":"Gate B Lounge","picture":null,"about":null,"rules":null,"member_count":500,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"archived":false,"max_members":500,"is_full":true,"error":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_member_preview_response_contract() {
+        let response = ServiceResponse::Preview(PreviewResult {
+            success: true,
+            name: Some("Gate B Lounge".to_string()),
+            member_count: Some(12),
+            is_public: Some(true),
+            is_open: Some(true),
+            created_at: Some(1759163304),
+            archived: Some(false),
+            is_full: Some(false),
+            is_member: Some(true),
+            role: Some(MemberRole::Admin),
+            ..Default::default()
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":true,"name":"Gate B Lounge","picture":null,"about":null,"rules":null,"member_count":12,"members":null,"is_public":true,"is_open":true,"created_at":1759163304,"archived":false,"is_full":false,"is_member":true,"role":"admin","error":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_quiet_hours_preview_response_contract() {
+        let response = ServiceResponse::Preview(PreviewResult {
+            success: true,
+            name: Some("The Night Owl".to_string()),
+            member_count: Some(40),
+            is_public: Some(false),
+            is_open: Some(false),
+            created_at: Some(1759163304),
+            archived: Some(false),
+            is_full: Some(false),
+            quiet_hours: Some(QuietHours::parse("23:30", "08:00", "America/New_York").unwrap()),
+            ..Default::default()
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":true,"name":"The Night Owl","picture":null,"about":null,"rules":null,"member_count":40,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"archived":false,"is_full":false,"quiet_hours":{"start":"23:30","end":"08:00","timezone":"America/New_York"},"error":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_venue_hours_preview_response_contract() {
+        let response = ServiceResponse::Preview(PreviewResult {
+            success: true,
+            name: Some("Blue Bottle".to_string()),
+            member_count: Some(8),
+            is_public: Some(false),
+            is_open: Some(false),
+            created_at: Some(1759163304),
+            archived: Some(false),
+            is_full: Some(false),
+            venue_hours: Some(VenueHoursStatus {
+                opening_hours: "Mo-Fr 07:00-18:00; Sa,Su 08:00-17:00".to_string(),
+                open_now: Some(true),
+            }),
+            ..Default::default()
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":true,"name":"Blue Bottle","picture":null,"about":null,"rules":null,"member_count":8,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"archived":false,"is_full":false,"venue_hours":{"opening_hours":"Mo-Fr 07:00-18:00; Sa,Su 08:00-17:00","open_now":true},"error":null}"#);
+        assert_parses_to(&json, &response);
+
+        // Hours that could not be evaluated come without open_now
+        let raw_only = r#"{"type":"preview_response","success":true,"name":"Blue Bottle","picture":null,"about":null,"rules":null,"member_count":8,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"venue_hours":{"opening_hours":"by appointment"},"error":null}"#;
+        let ServiceResponse::Preview(preview) = serde_json::from_str(raw_only).unwrap() else {
+            panic!("not a preview");
+        };
+        assert_eq!(
+            preview.venue_hours,
+            Some(VenueHoursStatus {
+                opening_hours: "by appointment".to_string(),
+                open_now: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_age_restricted_preview_response_contract() {
+        let response = ServiceResponse::Preview(PreviewResult {
+            success: true,
+            name: Some("The Night Owl".to_string()),
+            member_count: Some(40),
+            is_public: Some(false),
+            is_open: Some(false),
+            created_at: Some(1759163304),
+            archived: Some(false),
+            is_full: Some(false),
+            age_restricted: Some(true),
+            ..Default::default()
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":true,"name":"The Night Owl","picture":null,"about":null,"rules":null,"member_count":40,"members":null,"is_public":false,"is_open":false,"created_at":1759163304,"archived":false,"is_full":false,"age_restricted":true,"error":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_community_full_response_contract() {
+        let code = ValidationErrorCode::CommunityFull;
+        let response = ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Community is full".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Community is full","error_code":"COMMUNITY_FULL","message_key":"error.community_full","params":{}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_already_in_cluster_response_contract() {
+        let code = ValidationErrorCode::AlreadyInCluster {
+            community_id: "9b1f0c7e-5d2a-4c8e-b6f3-1a2b3c4d5e6f".to_string(),
+        };
+        let response = ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Already a member of Red Stand in the same exclusive cluster".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Already a member of Red Stand in the same exclusive cluster","error_code":"ALREADY_IN_CLUSTER","message_key":"error.already_in_cluster","params":{"community_id":"9b1f0c7e-5d2a-4c8e-b6f3-1a2b3c4d5e6f"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_preview_response_absent_optionals() {
+        assert_parses_to(
+            r#"{"type":"preview_response","success":false,"error":"Community not found"}"#,
+            &ServiceResponse::Preview(PreviewResult {
+                success: false,
+                error: Some("Community not found".to_string()),
+                ..Default::default()
+            }),
+        );
+    }
+
+    #[test]
+    fn test_preview_retry_later_contract() {
+        let response = ServiceResponse::Preview(PreviewResult {
+            success: false,
+            error: Some("Community preview timed out, please retry".to_string()),
+            error_code: Some("RETRY_LATER".to_string()),
+            ..Default::default()
+        });
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_response","success":false,"name":null,"picture":null,"about":null,"rules":null,"member_count":null,"members":null,"is_public":null,"is_open":null,"created_at":null,"error":"Community preview timed out, please retry","error_code":"RETRY_LATER"}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_add_anchor_response_contract() {
+        let response = add_anchor_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"add_anchor_response","success":true,"anchor_count":2,"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_add_anchor_response_absent_optionals() {
+        assert_parses_to(
+            r#"{"type":"add_anchor_response","success":false,"error":"Only community admins can add anchors","error_code":"NOT_ADMIN"}"#,
+            &ServiceResponse::AddAnchor {
+                success: false,
+                anchor_count: None,
+                error: Some("Only community admins can add anchors".to_string()),
+                error_code: Some("NOT_ADMIN".to_string()),
+                message_key: None,
+                params: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_update_metadata_request_contract() {
+        let request = update_metadata_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","join_mode":"approval"}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_update_metadata_join_notifications_request_contract() {
+        let request = ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: None,
+            max_members: None,
+            name: None,
+            about: None,
+            welcome: None,
+            slug: None,
+            join_notifications: Some(false),
+            age_restricted: None,
+            inactive_prune_days: None,
+            quiet_hours: None,
+        };
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","join_notifications":false}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_update_metadata_age_restricted_request_contract() {
+        let request = ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: None,
+            max_members: None,
+            name: None,
+            about: None,
+            welcome: None,
+            slug: None,
+            join_notifications: None,
+            age_restricted: Some(true),
+            inactive_prune_days: None,
+            quiet_hours: None,
+        };
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","age_restricted":true}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_update_metadata_inactive_prune_request_contract() {
+        let request = ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: None,
+            max_members: None,
+            name: None,
+            about: None,
+            welcome: None,
+            slug: None,
+            join_notifications: None,
+            age_restricted: None,
+            inactive_prune_days: Some(30),
+            quiet_hours: None,
+        };
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","inactive_prune_days":30}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_update_metadata_quiet_hours_request_contract() {
+        let request = ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: None,
+            max_members: None,
+            name: None,
+            about: None,
+            welcome: None,
+            slug: None,
+            join_notifications: None,
+            age_restricted: None,
+            inactive_prune_days: None,
+            quiet_hours: Some(QuietHours::parse("22:00", "07:00", "Europe/Berlin").unwrap()),
+        };
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","quiet_hours":{"start":"22:00","end":"07:00","timezone":"Europe/Berlin"}}"#);
+        assert_parses_to(&json, &request);
+
+        // A timezone that is not IANA is rejected when the request is parsed
+        let bad = json.replace("Europe/Berlin", "CEST");
+        assert!(serde_json::from_str::<ServiceRequest>(&bad).is_err());
+    }
+
+    #[test]
+    fn test_update_metadata_text_request_contract() {
+        let request = ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: None,
+            max_members: None,
+            name: Some("Blue Bottle".to_string()),
+            about: Some("Coffee regulars".to_string()),
+            welcome: Some("Say hi in the chat!".to_string()),
+            slug: None,
+            join_notifications: None,
+            age_restricted: None,
+            inactive_prune_days: None,
+            quiet_hours: None,
+        };
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","name":"Blue Bottle","about":"Coffee regulars","welcome":"Say hi in the chat!"}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_update_metadata_max_members_request_contract() {
+        let request = ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: None,
+            max_members: Some(200),
+            name: None,
+            about: None,
+            welcome: None,
+            slug: None,
+            join_notifications: None,
+            age_restricted: None,
+            inactive_prune_days: None,
+            quiet_hours: None,
+        };
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","max_members":200}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_invalid_metadata_response_contract() {
+        let code = ValidationErrorCode::InvalidMetadata {
+            fields: "name, about".to_string(),
+        };
+        let response = ServiceResponse::UpdateMetadata {
+            success: false,
+            error: Some("Invalid community metadata: name, about".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata_response","success":false,"error":"Invalid community metadata: name, about","error_code":"INVALID_METADATA","message_key":"error.invalid_metadata","params":{"fields":"name, about"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_update_metadata_slug_request_contract() {
+        let request = ServiceRequest::UpdateMetadata {
+            community_id: COMMUNITY_ID.to_string(),
+            join_mode: None,
+            max_members: None,
+            name: None,
+            about: None,
+            welcome: None,
+            slug: Some("blue-bottle-mission".to_string()),
+            join_notifications: None,
+            age_restricted: None,
+            inactive_prune_days: None,
+            quiet_hours: None,
+        };
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","slug":"blue-bottle-mission"}"#);
+        assert_parses_to(&json, &request);
+
+        // Shared links put the slug where the UUID usually goes
+        let preview = ServiceRequest::PreviewRequest {
+            community_id: "blue-bottle-mission".to_string(),
+        };
+        let json = to_json(&preview);
+        insta::assert_snapshot!(json, @r#"{"type":"preview_request","community_id":"blue-bottle-mission"}"#);
+        assert_parses_to(&json, &preview);
+    }
+
+    #[test]
+    fn test_slug_taken_response_contract() {
+        let code = ValidationErrorCode::SlugTaken {
+            slug: "blue-bottle-mission".to_string(),
+        };
+        let response = ServiceResponse::UpdateMetadata {
+            success: false,
+            error: Some(
+                "Slug blue-bottle-mission is already used by another community".to_string(),
+            ),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata_response","success":false,"error":"Slug blue-bottle-mission is already used by another community","error_code":"SLUG_TAKEN","message_key":"error.slug_taken","params":{"slug":"blue-bottle-mission"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_service_unavailable_response_contract() {
+        let code = ValidationErrorCode::ServiceUnavailable {
+            retry_after_secs: 25,
+        };
+        let error = "The relay is unreachable, please retry later";
+        for request in all_requests() {
+            let response = request.failure_response(error.to_string(), code.clone());
+            let expected = match request_type(&request) {
+                "preview_request" => "preview_response".to_string(),
+                "unarchive_community" => "archive_community_response".to_string(),
+                other => format!("{}_response", other),
+            };
+            assert_eq!(response_type(&response), expected);
+            assert!(to_json(&response).contains(r#""error_code":"SERVICE_UNAVAILABLE""#));
+        }
+
+        let response = add_anchor_request().failure_response(error.to_string(), code);
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"add_anchor_response","success":false,"anchor_count":null,"error":"The relay is unreachable, please retry later","error_code":"SERVICE_UNAVAILABLE","message_key":"error.service_unavailable","params":{"retry_after_secs":"25"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_approve_join_request_contract() {
+        let request = approve_join_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"approve_join","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","pubkey":"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","approve":true}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_pending_join_response_contract() {
+        let response = pending_join_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":true,"group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","relay_url":"wss://communities2.nos.social","is_admin":false,"is_member":false,"error":null,"error_code":null,"status":"pending"}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_update_metadata_response_contract() {
+        let response = update_metadata_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"update_metadata_response","success":true,"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_approve_join_response_contract() {
+        let response = approve_join_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"approve_join_response","success":true,"status":"approved","error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_join_request_update_contract() {
+        let update = join_request_update(JoinRequestStatus::Pending);
+        let json = to_json(&update);
+        insta::assert_snapshot!(json, @r#"{"type":"join_request_update","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","pubkey":"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","status":"pending"}"#);
+        assert_parses_to(&json, &update);
+    }
+
+    #[test]
+    fn test_member_joined_contract() {
+        let notification = member_joined();
+        let json = to_json(&notification);
+        insta::assert_snapshot!(json, @r#"{"type":"member_joined","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","community_name":"Blue Bottle Coffee","member_count":14,"pubkeys":["79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"]}"#);
+        assert_parses_to(&json, &notification);
+    }
+
+    #[test]
+    fn test_inactivity_warning_contract() {
+        let warning = inactivity_warning();
+        let json = to_json(&warning);
+        insta::assert_snapshot!(json, @r#"{"type":"inactivity_warning","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","community_name":"Blue Bottle Coffee","inactive_days":23,"remove_after":1760604800}"#);
+        assert_parses_to(&json, &warning);
+    }
+
+    #[test]
+    fn test_sticker_reuse_suspected_contract() {
+        let alert = sticker_reuse_suspected();
+        let json = to_json(&alert);
+        insta::assert_snapshot!(json, @r#"{"type":"sticker_reuse_suspected","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","community_name":"Blue Bottle Coffee","cluster":"9q9p1","distance_km":13,"failures":12}"#);
+        assert_parses_to(&json, &alert);
+    }
+
+    #[test]
+    fn test_expired_join_request_update_contract() {
+        let update = join_request_update(JoinRequestStatus::Expired);
+        let json = to_json(&update);
+        insta::assert_snapshot!(json, @r#"{"type":"join_request_update","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","pubkey":"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","status":"expired","error_code":"JOIN_REQUEST_EXPIRED","message_key":"error.join_request_expired","params":{}}"#);
+        assert_parses_to(&json, &update);
+    }
+
+    #[test]
+    fn test_cancel_join_request_contract() {
+        let request = cancel_join_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"cancel_join_request","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d"}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_cancel_join_request_response_contract() {
+        let response = cancel_join_request_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"cancel_join_request_response","success":true,"status":"cancelled","error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_archive_community_request_contract() {
+        let request = archive_community_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"archive_community","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d"}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_unarchive_community_request_contract() {
+        let request = unarchive_community_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"unarchive_community","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d"}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_archive_community_response_contract() {
+        let response = archive_community_response(true);
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"archive_community_response","success":true,"archived":true,"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_community_archived_response_contract() {
+        let code = ValidationErrorCode::CommunityArchived;
+        let response = ServiceResponse::LocationValidation {
+            success: false,
+            group_id: None,
+            relay_url: None,
+            is_admin: None,
+            is_member: None,
+            error: Some("Community has been archived".to_string()),
+            error_code: Some(code.code().to_string()),
+            message_key: Some(code.message_key().to_string()),
+            params: Some(code.params()),
+            existing_community: None,
+            status: None,
+            attestation: None,
+            welcome: None,
+            community_name: None,
+            picture: None,
+            age_restricted: None,
+            auth_required: None,
+            auth_scope: None,
+            dry_run: None,
+        };
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"location_validation_response","success":false,"group_id":null,"relay_url":null,"is_admin":null,"is_member":null,"error":"Community has been archived","error_code":"COMMUNITY_ARCHIVED","message_key":"error.community_archived","params":{}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_export_community_request_contract() {
+        let request = export_community_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"export_community","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d"}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_export_community_response_contract() {
+        let response = export_community_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"export_community_response","success":true,"export":{"format":"peek.community-export","version":1,"exported_at":1760000000,"community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","group_id":"peek-3a7e5c59-c0a1-4876-acf1-56189b86aa0d","metadata":{"name":"Blue Bottle Coffee","about":null,"rules":[],"picture":null,"welcome":null,"geohash_cells":["9q8yyk8y"],"join_mode":"auto","max_members":null,"active_until":null,"archived":false,"updated_at":1759163304},"members":[{"pubkey":"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","role":"admin","joined_at":1759163304}],"stats":{"member_count":1,"admin_count":1,"anchor_count":1,"first_join_at":1759163304,"members_incomplete":false}},"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_uploaded_export_community_response_contract() {
+        let response = uploaded_export_community_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"export_community_response","success":true,"export_url":"https://media.peek.hol.is/5f2b1c.json","error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_export_limit_reached_response_contract() {
+        let code = ValidationErrorCode::ExportLimitReached { max_exports: 3 };
+        let response = export_community_request().failure_response(
+            "Exports are limited to 3 per admin per day".to_string(),
+            code,
+        );
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"export_community_response","success":false,"error":"Exports are limited to 3 per admin per day","error_code":"EXPORT_LIMIT_REACHED","message_key":"error.export_limit_reached","params":{"max_exports":"3"}}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_bulk_add_members_request_contract() {
+        let request = bulk_add_members_request();
+        let json = to_json(&request);
+        insta::assert_snapshot!(json, @r#"{"type":"bulk_add_members","community_id":"3a7e5c59-c0a1-4876-acf1-56189b86aa0d","pubkeys":["79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","not-a-pubkey"]}"#);
+        assert_parses_to(&json, &request);
+    }
+
+    #[test]
+    fn test_bulk_add_members_response_contract() {
+        let response = bulk_add_members_response();
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"bulk_add_members_response","success":true,"outcomes":[{"pubkey":"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","status":"added"},{"pubkey":"not-a-pubkey","status":"invalid"}],"error":null,"error_code":null}"#);
+        assert_parses_to(&json, &response);
+    }
+
+    #[test]
+    fn test_bulk_add_limit_reached_response_contract() {
+        let code = ValidationErrorCode::BulkAddLimitReached { remaining: 12 };
+        let response = bulk_add_members_request().failure_response(
+            "This community can import 12 more members by pubkey".to_string(),
+            code,
+        );
+        let json = to_json(&response);
+        insta::assert_snapshot!(json, @r#"{"type":"bulk_add_members_response","success":false,"outcomes":[],"error":"This community can import 12 more members by pubkey","error_code":"BULK_ADD_LIMIT_REACHED","message_key":"error.bulk_add_limit_reached","params":{"remaining":"12"}}"#);
+        assert_parses_to(&json, &response);
+    }
+}
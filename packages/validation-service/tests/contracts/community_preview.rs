@@ -1,185 +0,0 @@
-use axum::http::StatusCode;
-use serde_json::json;
-use validation_service::models::community::Community;
-
-#[cfg(test)]
-mod community_preview_tests {
-    use super::*;
-
-    #[test]
-    fn test_returns_community_info_without_auth() {
-        // Given: A valid community ID
-        let community_id = "test-community-123";
-
-        // When: Preview endpoint is called without authentication
-        // GET /api/community/preview?id={community_id}
-        
-        // Then: Should return 200 with community information
-        // Expected response:
-        // {
-        //   "id": "test-community-123",
-        //   "name": "SF Coffee House",
-        //   "description": "A cozy community at SF Coffee House",
-        //   "member_count": 42,
-        //   "location": {
-        //     "name": "SF Coffee House",
-        //     "latitude": 37.7749,
-        //     "longitude": -122.4194
-        //   },
-        //   "created_at": "2024-01-01T00:00:00Z"
-        // }
-    }
-
-    #[test]
-    fn test_handles_non_existent_community() {
-        // Given: A non-existent community ID
-        let community_id = "non-existent-community-999";
-
-        // When: Preview endpoint is called
-        // GET /api/community/preview?id={community_id}
-        
-        // Then: Should return 404 with error
-        // Expected response:
-        // {
-        //   "error": "Community not found"
-        // }
-    }
-
-    #[test]
-    fn test_response_matches_openapi_schema() {
-        // Given: A valid community ID
-        let community_id = "test-community-456";
-
-        // When: Preview endpoint is called
-        // Then: Response should match the OpenAPI schema exactly
-        // Required fields:
-        // - id: string
-        // - name: string
-        // - description: string
-        // - member_count: integer
-        // - location: object
-        //   - name: string
-        //   - latitude: number
-        //   - longitude: number
-        // - created_at: string (ISO 8601)
-    }
-
-    #[test]
-    fn test_missing_community_id_parameter() {
-        // Given: No community ID parameter
-        // When: Preview endpoint is called without ID
-        // GET /api/community/preview
-        
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "error": "Community ID parameter is required"
-        // }
-    }
-
-    #[test]
-    fn test_invalid_community_id_format() {
-        // Given: Invalid community ID formats
-        let invalid_ids = vec![
-            "",  // Empty string
-            " ",  // Whitespace
-            "../etc/passwd",  // Path traversal attempt
-            "<script>alert('xss')</script>",  // XSS attempt
-            "'; DROP TABLE communities; --",  // SQL injection attempt
-        ];
-
-        // When: Preview endpoint is called with each invalid ID
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "error": "Invalid community ID format"
-        // }
-    }
-
-    #[test]
-    fn test_caching_behavior() {
-        // Given: A valid community ID
-        let community_id = "test-community-789";
-
-        // When: Preview endpoint is called multiple times within 60 seconds
-        // First call: Fetches from relay
-        // Second call within 60s: Returns cached data
-        // Third call after 60s: Fetches fresh data from relay
-        
-        // Then: 
-        // - First call should have higher latency
-        // - Second call should return identical data with lower latency
-        // - Third call might have different member_count if changed
-    }
-
-    #[test]
-    fn test_handles_relay_connection_error() {
-        // Given: Relay is unavailable or connection fails
-        let community_id = "test-community-321";
-
-        // When: Preview endpoint is called
-        // Then: Should return 503 with error
-        // Expected response:
-        // {
-        //   "error": "Unable to connect to relay service"
-        // }
-    }
-
-    #[test]
-    fn test_member_count_accuracy() {
-        // Given: A community with known member count
-        let community_id = "test-community-count";
-
-        // When: Preview endpoint is called
-        // Then: member_count should match actual NIP-29 group member list
-        // This ensures we're counting active members correctly
-    }
-
-    #[test]
-    fn test_location_data_consistency() {
-        // Given: A community with location data
-        let community_id = "test-community-location";
-
-        // When: Preview endpoint returns location
-        // Then: Location coordinates should match the QR code coordinates
-        // And location name should be human-readable
-    }
-
-    #[test]
-    fn test_concurrent_preview_requests() {
-        // Given: Multiple simultaneous preview requests for different communities
-        let community_ids = vec![
-            "community-1",
-            "community-2", 
-            "community-3",
-        ];
-
-        // When: All requests are made concurrently
-        // Then: Each should return correct data without interference
-    }
-
-    #[test]
-    fn test_preview_excludes_sensitive_data() {
-        // Given: A community with sensitive data (private keys, member details)
-        let community_id = "test-community-private";
-
-        // When: Preview endpoint is called
-        // Then: Response should NOT include:
-        // - Member email addresses or npubs
-        // - Admin private keys
-        // - Invite codes
-        // - Internal relay metadata
-    }
-
-    #[test]
-    fn test_preview_with_special_characters() {
-        // Given: Community with special characters in name/description
-        let community_id = "test-community-special";
-        // Community name: "Café ☕ & 🍰"
-        // Description: "München's finest Bürgerbräu"
-
-        // When: Preview endpoint is called
-        // Then: Should properly handle UTF-8 characters
-        // Response should preserve special characters correctly
-    }
-}
\ No newline at end of file
@@ -1,2 +0,0 @@
-pub mod validate_location;
-pub mod community_preview;
\ No newline at end of file
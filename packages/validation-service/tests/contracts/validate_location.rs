@@ -1,240 +0,0 @@
-use axum::http::StatusCode;
-use serde_json::json;
-use validation_service::models::location::{LocationProof, LocationPoint};
-
-#[cfg(test)]
-mod validate_location_tests {
-    use super::*;
-
-    fn create_valid_location_proof(lat: f64, lng: f64, accuracy: f64) -> LocationProof {
-        LocationProof {
-            coordinates: LocationPoint {
-                latitude: lat,
-                longitude: lng,
-            },
-            accuracy,
-            timestamp: chrono::Utc::now().timestamp(),
-        }
-    }
-
-    fn create_community_location() -> LocationPoint {
-        LocationPoint {
-            latitude: 37.7749,
-            longitude: -122.4194,
-        }
-    }
-
-    #[test]
-    fn test_successful_validation_returns_invite_code() {
-        // Given: A valid location proof within 25m and accuracy <= 20m
-        let community_location = create_community_location();
-        let proof = create_valid_location_proof(
-            community_location.latitude + 0.0001, // ~11m away
-            community_location.longitude,
-            15.0, // 15m accuracy
-        );
-
-        // When: Validation is performed
-        // Then: Should return 200 with invite code
-        // Expected response:
-        // {
-        //   "success": true,
-        //   "invite_code": "naddr1...",
-        //   "relay_url": "wss://peek.hol.is",
-        //   "expires_at": 1234567890
-        // }
-    }
-
-    #[test]
-    fn test_accuracy_over_20m_rejection() {
-        // Given: Location proof with accuracy > 20m
-        let community_location = create_community_location();
-        let proof = create_valid_location_proof(
-            community_location.latitude,
-            community_location.longitude,
-            25.0, // 25m accuracy - too high
-        );
-
-        // When: Validation is performed
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "success": false,
-        //   "error": "GPS accuracy too low. Please enable precise location."
-        // }
-    }
-
-    #[test]
-    fn test_distance_over_25m_rejection() {
-        // Given: Location proof > 25m from community location
-        let community_location = create_community_location();
-        let proof = create_valid_location_proof(
-            community_location.latitude + 0.0003, // ~33m away
-            community_location.longitude,
-            10.0, // Good accuracy
-        );
-
-        // When: Validation is performed
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "success": false,
-        //   "error": "You must be at the location to join this community."
-        // }
-    }
-
-    #[test]
-    fn test_expired_timestamp_rejection() {
-        // Given: Location proof with timestamp > 30 seconds old
-        let community_location = create_community_location();
-        let mut proof = create_valid_location_proof(
-            community_location.latitude,
-            community_location.longitude,
-            10.0,
-        );
-        proof.timestamp = chrono::Utc::now().timestamp() - 35; // 35 seconds old
-
-        // When: Validation is performed
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "success": false,
-        //   "error": "Location proof expired. Please try again."
-        // }
-    }
-
-    #[test]
-    fn test_invalid_coordinates_rejection() {
-        // Given: Invalid coordinates (out of range)
-        let invalid_proofs = vec![
-            LocationProof {
-                coordinates: LocationPoint {
-                    latitude: 91.0, // Invalid: > 90
-                    longitude: 0.0,
-                },
-                accuracy: 10.0,
-                timestamp: chrono::Utc::now().timestamp(),
-            },
-            LocationProof {
-                coordinates: LocationPoint {
-                    latitude: 0.0,
-                    longitude: 181.0, // Invalid: > 180
-                },
-                accuracy: 10.0,
-                timestamp: chrono::Utc::now().timestamp(),
-            },
-            LocationProof {
-                coordinates: LocationPoint {
-                    latitude: -91.0, // Invalid: < -90
-                    longitude: 0.0,
-                },
-                accuracy: 10.0,
-                timestamp: chrono::Utc::now().timestamp(),
-            },
-            LocationProof {
-                coordinates: LocationPoint {
-                    latitude: 0.0,
-                    longitude: -181.0, // Invalid: < -180
-                },
-                accuracy: 10.0,
-                timestamp: chrono::Utc::now().timestamp(),
-            },
-        ];
-
-        // When: Validation is performed for each invalid proof
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "success": false,
-        //   "error": "Invalid coordinates provided."
-        // }
-    }
-
-    #[test]
-    fn test_missing_community_id() {
-        // Given: Valid location proof but missing community_id in request
-        let proof = create_valid_location_proof(37.7749, -122.4194, 10.0);
-
-        // When: Request is made without community_id
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "success": false,
-        //   "error": "Community ID is required."
-        // }
-    }
-
-    #[test]
-    fn test_boundary_case_exactly_25m() {
-        // Given: Location exactly 25m from community (boundary case)
-        let community_location = create_community_location();
-        // Calculate point exactly 25m away
-        let meters_to_degrees = 0.000225; // Approximate conversion at this latitude
-        let proof = create_valid_location_proof(
-            community_location.latitude + meters_to_degrees,
-            community_location.longitude,
-            10.0,
-        );
-
-        // When: Validation is performed
-        // Then: Should return 200 (inclusive boundary)
-        // Expected response:
-        // {
-        //   "success": true,
-        //   "invite_code": "naddr1...",
-        //   "relay_url": "wss://peek.hol.is",
-        //   "expires_at": 1234567890
-        // }
-    }
-
-    #[test]
-    fn test_boundary_case_exactly_20m_accuracy() {
-        // Given: Location with exactly 20m accuracy (boundary case)
-        let community_location = create_community_location();
-        let proof = create_valid_location_proof(
-            community_location.latitude,
-            community_location.longitude,
-            20.0, // Exactly 20m accuracy
-        );
-
-        // When: Validation is performed
-        // Then: Should return 200 (inclusive boundary)
-        // Expected response:
-        // {
-        //   "success": true,
-        //   "invite_code": "naddr1...",
-        //   "relay_url": "wss://peek.hol.is",
-        //   "expires_at": 1234567890
-        // }
-    }
-
-    #[test]
-    fn test_concurrent_validation_requests() {
-        // Given: Multiple simultaneous validation requests for same community
-        // This tests that the service can handle concurrent requests without
-        // race conditions or state corruption
-
-        // When: 10 concurrent requests are made
-        // Then: Each should receive a unique invite code
-    }
-
-    #[test]
-    fn test_validation_with_future_timestamp() {
-        // Given: Location proof with timestamp in the future
-        let community_location = create_community_location();
-        let mut proof = create_valid_location_proof(
-            community_location.latitude,
-            community_location.longitude,
-            10.0,
-        );
-        proof.timestamp = chrono::Utc::now().timestamp() + 60; // 60 seconds in future
-
-        // When: Validation is performed
-        // Then: Should return 400 with error
-        // Expected response:
-        // {
-        //   "success": false,
-        //   "error": "Invalid timestamp."
-        // }
-    }
-}
\ No newline at end of file
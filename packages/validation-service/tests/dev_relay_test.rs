@@ -0,0 +1,372 @@
+//! Create-and-join cycle against the in-process dev relay, with no external relay or keys
+//!
+//! Community names come from Overpass; without network access creation falls back to the
+//! default name, so the cycle still completes.
+
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+use validation_service::fake_relay::FakeRelay;
+use validation_service::libraries::clock::{Deadline, SystemClock};
+use validation_service::libraries::exclusion_zones::ExclusionZones;
+use validation_service::libraries::relay_url::RelayUrl;
+use validation_service::libraries::sanitize::MetadataText;
+use validation_service::models::{Coordinates, ProtocolConfig};
+use validation_service::services::community::CommunityError;
+use validation_service::services::community::{CommunityLookup, CommunityService};
+use validation_service::services::creation_limit::CreationLimiter;
+use validation_service::services::discovery_map::DiscoveryMaps;
+use validation_service::services::execution::ExecutionMode;
+use validation_service::services::previous_refs::PreviousRefs;
+use validation_service::services::relay::RelayService;
+use validation_service::services::relay_access::{GroupReader, GroupWriter};
+use validation_service::services::relay_circuit::RelayCircuit;
+
+async fn service_against(
+    relay: Arc<FakeRelay>,
+    keys: Keys,
+    mode: ExecutionMode,
+) -> (CommunityService, GroupWriter) {
+    let url = relay.spawn().await.unwrap();
+    let protocol = ProtocolConfig::default();
+    let relay_service = Arc::new(
+        RelayService::new(
+            RelayUrl::parse(&url).unwrap(),
+            keys,
+            protocol.clone(),
+            Duration::from_millis(500),
+            DiscoveryMaps {
+                d_tag: protocol.discovery_map_d_tag.clone(),
+                prefixes: Vec::new(),
+                signer: None,
+                exclusion_zones: ExclusionZones::default(),
+            },
+            4096,
+            RelayCircuit::new(Arc::new(SystemClock), 0, Duration::from_secs(30)),
+            PreviousRefs::new(3),
+        )
+        .await
+        .unwrap(),
+    );
+    let writer = GroupWriter::new(relay_service.clone()).with_mode(mode);
+    let service = CommunityService::new(
+        GroupReader::new(relay_service),
+        writer.clone(),
+        CreationLimiter::new(1, 1),
+    );
+    (service, writer)
+}
+
+#[tokio::test]
+async fn test_create_and_join_cycle() {
+    let relay_keys = Keys::generate();
+    let relay = FakeRelay::new(relay_keys.clone());
+    let (service, writer) =
+        service_against(relay.clone(), relay_keys.clone(), ExecutionMode::Execute).await;
+    let deadline = Deadline::after(Arc::new(SystemClock), 60);
+
+    let community_id = Uuid::new_v4();
+    let creator = Keys::generate().public_key();
+    let location = Coordinates::new(37.7749, -122.4194).unwrap();
+    assert!(matches!(
+        service.lookup(&community_id, &deadline).await.unwrap(),
+        CommunityLookup::Absent
+    ));
+    service
+        .create(
+            community_id,
+            location,
+            creator.to_hex(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            &deadline,
+        )
+        .await
+        .unwrap();
+
+    let group_id = service.group_id(&community_id, &deadline).await.unwrap();
+    let joiner = Keys::generate().public_key();
+    writer
+        .add_group_member(&group_id, &joiner.to_hex(), false)
+        .await
+        .unwrap();
+
+    // The creator is the only admin; the relay key handed its admin role back
+    assert_eq!(
+        relay
+            .admins(&group_id)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![creator]
+    );
+    let CommunityLookup::Existing(community) =
+        service.lookup(&community_id, &deadline).await.unwrap()
+    else {
+        panic!("created community was not found");
+    };
+    assert!(community.has_member(&creator.to_hex()));
+    assert!(community.has_member(&joiner.to_hex()));
+}
+
+#[tokio::test]
+async fn test_age_restriction_set_at_creation_and_cleared_by_the_admin() {
+    let relay_keys = Keys::generate();
+    let relay = FakeRelay::new(relay_keys.clone());
+    let (service, writer) =
+        service_against(relay.clone(), relay_keys, ExecutionMode::Execute).await;
+    let deadline = Deadline::after(Arc::new(SystemClock), 60);
+
+    let community_id = Uuid::new_v4();
+    let created = service
+        .create(
+            community_id,
+            Coordinates::new(37.7749, -122.4194).unwrap(),
+            Keys::generate().public_key().to_hex(),
+            None,
+            None,
+            true,
+            None,
+            false,
+            &deadline,
+        )
+        .await
+        .unwrap();
+    assert!(created.metadata.age_restricted);
+
+    let restricted = |lookup: CommunityLookup| match lookup {
+        CommunityLookup::Existing(community) => community.age_restricted,
+        CommunityLookup::Absent => panic!("created community was not found"),
+    };
+    assert!(restricted(
+        service.lookup(&community_id, &deadline).await.unwrap()
+    ));
+
+    writer
+        .update_group_metadata(
+            &created.group_id,
+            None,
+            None,
+            &MetadataText::default(),
+            None,
+            None,
+            Some(false),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(!restricted(
+        service.lookup(&community_id, &deadline).await.unwrap()
+    ));
+}
+
+#[tokio::test]
+async fn test_seeded_communities_are_found_by_uuid() {
+    let relay_keys = Keys::generate();
+    let relay = FakeRelay::new(relay_keys.clone());
+    let admin = Keys::generate().public_key();
+    let seeded = relay.seed(&ProtocolConfig::default(), admin);
+    let (service, _) = service_against(relay, relay_keys, ExecutionMode::Execute).await;
+    let deadline = Deadline::after(Arc::new(SystemClock), 30);
+
+    for community in seeded {
+        let CommunityLookup::Existing(metadata) = service
+            .lookup(&community.community_id, &deadline)
+            .await
+            .unwrap()
+        else {
+            panic!("seeded community {} was not found", community.name);
+        };
+        assert!(metadata.has_member(&admin.to_hex()));
+    }
+}
+
+#[tokio::test]
+async fn test_read_only_mode_validates_without_touching_the_relay() {
+    let relay_keys = Keys::generate();
+    let relay = FakeRelay::new(relay_keys.clone());
+    let admin = Keys::generate().public_key();
+    let seeded = relay.seed(&ProtocolConfig::default(), admin);
+    let (service, writer) = service_against(relay.clone(), relay_keys, ExecutionMode::DryRun).await;
+    let deadline = Deadline::after(Arc::new(SystemClock), 60);
+    let stored = relay.event_count();
+    let creator = Keys::generate().public_key();
+
+    // A sticker beside a seeded community is declined just as it would be live
+    let beside_seed = service
+        .create(
+            Uuid::new_v4(),
+            Coordinates::new(37.7749, -122.4194).unwrap(),
+            creator.to_hex(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            &deadline,
+        )
+        .await;
+    assert!(matches!(beside_seed, Err(CommunityError::NearbyExists(_))));
+
+    // Elsewhere the creation is answered as if it happened, with a group id of its own
+    let created = service
+        .create(
+            Uuid::new_v4(),
+            Coordinates::new(37.8044, -122.2712).unwrap(),
+            creator.to_hex(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            &deadline,
+        )
+        .await
+        .unwrap();
+    assert!(created
+        .group_id
+        .starts_with(&ProtocolConfig::default().group_id_prefix));
+    assert!(created.metadata.has_member(&creator.to_hex()));
+
+    let joiner = Keys::generate().public_key();
+    writer
+        .add_group_member(&seeded[0].group_id, &joiner.to_hex(), false)
+        .await
+        .unwrap();
+
+    assert_eq!(relay.event_count(), stored);
+    assert_eq!(relay.members(&created.group_id), None);
+    assert_eq!(
+        relay
+            .members(&seeded[0].group_id)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![admin]
+    );
+}
+
+#[tokio::test]
+async fn test_a_crowd_of_previews_shares_one_read_per_kind() {
+    let relay_keys = Keys::generate();
+    let relay = FakeRelay::new(relay_keys.clone());
+    let seeded = relay.seed(&ProtocolConfig::default(), Keys::generate().public_key());
+    let (_, writer) = service_against(relay.clone(), relay_keys, ExecutionMode::Execute).await;
+    let reader = writer.reader();
+    let group_id = seeded[0].group_id.as_str();
+
+    relay.set_latency(Duration::from_millis(200));
+    let (metadata_reads, member_reads) = (relay.reqs_for_kind(39000), relay.reqs_for_kind(39002));
+    let previews = futures::future::join_all((0..20).map(|_| reader.get_group_metadata(group_id)));
+    for preview in previews.await {
+        assert_eq!(preview.unwrap().member_count, 1);
+    }
+
+    assert_eq!(relay.reqs_for_kind(39000) - metadata_reads, 1);
+    assert_eq!(relay.reqs_for_kind(39002) - member_reads, 1);
+}
+
+#[tokio::test]
+async fn test_a_group_deleted_elsewhere_is_recreated_on_the_next_join() {
+    let relay_keys = Keys::generate();
+    let relay = FakeRelay::new(relay_keys.clone());
+    let (service, writer) =
+        service_against(relay.clone(), relay_keys, ExecutionMode::Execute).await;
+    let reader = writer.reader();
+    let deadline = Deadline::after(Arc::new(SystemClock), 60);
+    let community_id = Uuid::new_v4();
+    let location = Coordinates::new(37.7749, -122.4194).unwrap();
+    let create = || {
+        service.create(
+            community_id,
+            location,
+            Keys::generate().public_key().to_hex(),
+            None,
+            None,
+            false,
+            None,
+            false,
+            &deadline,
+        )
+    };
+    let created = create().await.unwrap();
+
+    // An operator deletes the group with other tooling; the next add to it drops the mapping
+    relay.delete_group(&created.group_id);
+    assert!(writer
+        .add_group_member(
+            &created.group_id,
+            &Keys::generate().public_key().to_hex(),
+            false
+        )
+        .await
+        .is_err());
+    assert_eq!(
+        reader.find_group_by_uuid(&community_id).await.unwrap(),
+        None
+    );
+
+    // So the next join takes the creation path instead of erroring
+    assert!(matches!(
+        service.lookup(&community_id, &deadline).await.unwrap(),
+        CommunityLookup::Absent
+    ));
+    let recreated = create().await.unwrap();
+    assert_ne!(recreated.group_id, created.group_id);
+    assert_eq!(
+        service.group_id(&community_id, &deadline).await.unwrap(),
+        recreated.group_id
+    );
+    assert!(relay.members(&recreated.group_id).is_some());
+}
+
+#[tokio::test]
+async fn test_mapping_check_evicts_groups_deleted_elsewhere() {
+    let relay_keys = Keys::generate();
+    let relay = FakeRelay::new(relay_keys.clone());
+    let seeded = relay.seed(&ProtocolConfig::default(), Keys::generate().public_key());
+    let (service, writer) =
+        service_against(relay.clone(), relay_keys, ExecutionMode::Execute).await;
+    let reader = writer.reader();
+    let deadline = Deadline::after(Arc::new(SystemClock), 60);
+
+    // Looking the seeded communities up caches their mappings and confirms authentication
+    for community in &seeded {
+        assert!(matches!(
+            service
+                .lookup(&community.community_id, &deadline)
+                .await
+                .unwrap(),
+            CommunityLookup::Existing(_)
+        ));
+    }
+    let gone = &seeded[0];
+    relay.delete_group(&gone.group_id);
+
+    let check = reader.verify_group_mappings(seeded.len()).await.unwrap();
+    assert_eq!(check.checked, seeded.len());
+    assert_eq!(check.vanished, 1);
+    assert_eq!(check.remapped, 0);
+
+    // The deleted community now reads as new, and the others still resolve from the cache
+    assert!(matches!(
+        service.lookup(&gone.community_id, &deadline).await.unwrap(),
+        CommunityLookup::Absent
+    ));
+    let metadata_reads = relay.reqs_for_kind(39000);
+    for community in &seeded[1..] {
+        assert_eq!(
+            reader
+                .find_group_by_uuid(&community.community_id)
+                .await
+                .unwrap(),
+            Some(community.group_id.clone())
+        );
+    }
+    assert_eq!(relay.reqs_for_kind(39000), metadata_reads);
+}
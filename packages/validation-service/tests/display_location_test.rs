@@ -1,40 +1,14 @@
-use geohash::decode;
-use std::f64::consts::PI;
+use peek_geo::{haversine_meters, verify_display_location, FOG_RADIUS_METERS};
 use validation_service::libraries::display_location::generate_display_location;
-
-const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
-
-// Test helper function for calculating distance
-fn calculate_distance_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-    let lat1_rad = lat1 * PI / 180.0;
-    let lat2_rad = lat2 * PI / 180.0;
-    let delta_lat = (lat2 - lat1) * PI / 180.0;
-    let delta_lon = (lon2 - lon1) * PI / 180.0;
-
-    let a = (delta_lat / 2.0).sin().powi(2)
-        + lat1_rad.cos() * lat2_rad.cos() * (delta_lon / 2.0).sin().powi(2);
-
-    let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-
-    EARTH_RADIUS_METERS * c
-}
-
-// Test helper for verifying display location
-fn verify_display_location(
-    actual_lat: f64,
-    actual_lon: f64,
-    display_geohash: &str,
-) -> Result<bool, String> {
-    // Decode display location
-    let (display_coord, _, _) =
-        decode(display_geohash).map_err(|e| format!("Failed to decode display geohash: {}", e))?;
-
-    // Calculate distance
-    let distance =
-        calculate_distance_meters(actual_lat, actual_lon, display_coord.y, display_coord.x);
-
-    // Actual location must be within 1km of display location
-    Ok(distance <= 1000.0)
+use validation_service::models::Coordinates;
+
+// Distance from an actual location to the center of a display geohash
+fn distance_to_display(actual_lat: f64, actual_lon: f64, display_geohash: &str) -> f64 {
+    let actual = Coordinates::new(actual_lat, actual_lon).unwrap();
+    haversine_meters(
+        &actual,
+        &Coordinates::from_geohash(display_geohash).unwrap(),
+    )
 }
 
 #[test]
@@ -48,22 +22,22 @@ fn test_display_location_within_bounds() {
 
     // Generate 100 display locations to test randomness and bounds
     for i in 0..100 {
-        let display_geohash = generate_display_location(actual_lat, actual_lon)
-            .expect("Should generate display location");
+        let display_geohash =
+            generate_display_location(Coordinates::new(actual_lat, actual_lon).unwrap())
+                .expect("Should generate display location");
 
         // Display geohash should be 9 characters
         assert_eq!(display_geohash.len(), 9);
 
         // Verify actual location is within 1km fog circle
+        let actual = Coordinates::new(actual_lat, actual_lon).unwrap();
         assert!(
-            verify_display_location(actual_lat, actual_lon, &display_geohash).unwrap(),
+            verify_display_location(&actual, &display_geohash).unwrap(),
             "Actual location should be within 1km of display location"
         );
 
         // Decode and verify distance is within 750m
-        let (display_coord, _, _) = decode(&display_geohash).unwrap();
-        let distance =
-            calculate_distance_meters(actual_lat, actual_lon, display_coord.y, display_coord.x);
+        let distance = distance_to_display(actual_lat, actual_lon, &display_geohash);
 
         distances.push(distance);
         if distance > max_distance {
@@ -95,8 +69,9 @@ fn test_display_location_randomness() {
     // Generate multiple display locations
     let mut display_locations = Vec::new();
     for _ in 0..20 {
-        let display_geohash = generate_display_location(actual_lat, actual_lon)
-            .expect("Should generate display location");
+        let display_geohash =
+            generate_display_location(Coordinates::new(actual_lat, actual_lon).unwrap())
+                .expect("Should generate display location");
         display_locations.push(display_geohash);
     }
 
@@ -127,16 +102,15 @@ fn test_fog_circle_coverage() {
 
     for (lat, lon) in test_locations {
         for _ in 0..10 {
-            let display_geohash =
-                generate_display_location(lat, lon).expect("Should generate display location");
+            let display_geohash = generate_display_location(Coordinates::new(lat, lon).unwrap())
+                .expect("Should generate display location");
 
-            let (display_coord, _, _) = geohash::decode(&display_geohash).unwrap();
-            let distance = calculate_distance_meters(lat, lon, display_coord.y, display_coord.x);
+            let distance = distance_to_display(lat, lon, &display_geohash);
 
             // The actual location must be within 1km radius
             // Since we offset by max 750m, the actual is always within 1km fog circle
             assert!(
-                distance <= 1000.0,
+                distance <= FOG_RADIUS_METERS,
                 "Actual location at ({}, {}) should be within 1km fog circle, distance: {}m",
                 lat,
                 lon,
@@ -152,18 +126,15 @@ fn test_privacy_preservation() {
     let actual_lat = 37.7749;
     let actual_lon = -122.4194;
 
-    let display_geohash = generate_display_location(actual_lat, actual_lon)
-        .expect("Should generate display location");
+    let display_geohash =
+        generate_display_location(Coordinates::new(actual_lat, actual_lon).unwrap())
+            .expect("Should generate display location");
 
     // Decode both locations
-    let actual_geohash = geohash::encode(
-        geohash::Coord {
-            x: actual_lon,
-            y: actual_lat,
-        },
-        9,
-    )
-    .unwrap();
+    let actual_geohash = Coordinates::new(actual_lat, actual_lon)
+        .unwrap()
+        .geohash(9)
+        .unwrap();
 
     // Display and actual geohashes should be different
     assert_ne!(
@@ -172,9 +143,7 @@ fn test_privacy_preservation() {
     );
 
     // Calculate minimum distance (should be > 0)
-    let (display_coord, _, _) = decode(&display_geohash).unwrap();
-    let distance =
-        calculate_distance_meters(actual_lat, actual_lon, display_coord.y, display_coord.x);
+    let distance = distance_to_display(actual_lat, actual_lon, &display_geohash);
 
     assert!(
         distance > 0.0,
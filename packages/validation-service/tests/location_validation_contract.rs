@@ -0,0 +1,73 @@
+//! Location checks every join goes through before the community is looked up
+//!
+//! Joining is a gift-wrapped `location_validation` request; there is no HTTP validation
+//! endpoint. Proximity is decided by geohash cells, so GPS accuracy is only sanity-checked
+//! and a coarse but plausible fix is not refused here.
+
+use validation_service::models::location::MAX_REPORTED_ACCURACY_METERS;
+use validation_service::models::{check_location_data, AccuracyReading, InvalidLocationData};
+
+const SF_LAT: f64 = 37.7749;
+const SF_LON: f64 = -122.4194;
+
+#[test]
+fn test_out_of_range_coordinates_are_refused() {
+    let invalid = [
+        (91.0, 0.0),
+        (0.0, 181.0),
+        (-91.0, 0.0),
+        (0.0, -181.0),
+        (f64::NAN, 0.0),
+        (0.0, f64::INFINITY),
+    ];
+
+    for (latitude, longitude) in invalid {
+        assert_eq!(
+            check_location_data(latitude, longitude, Some(10.0)),
+            Err(InvalidLocationData::Coordinates),
+            "({}, {}) should be refused",
+            latitude,
+            longitude
+        );
+    }
+}
+
+#[test]
+fn test_range_boundaries_are_accepted() {
+    for (latitude, longitude) in [(90.0, 180.0), (-90.0, -180.0), (0.0, 0.0)] {
+        let fix = check_location_data(latitude, longitude, Some(10.0)).unwrap();
+        assert_eq!(
+            (fix.coordinates.latitude(), fix.coordinates.longitude()),
+            (latitude, longitude)
+        );
+    }
+}
+
+#[test]
+fn test_accuracy_is_sanity_checked_not_enforced() {
+    // Self-reported accuracy can be spoofed, so a 25m fix is not grounds for refusal
+    assert!(check_location_data(SF_LAT, SF_LON, Some(25.0)).is_ok());
+    assert!(check_location_data(SF_LAT, SF_LON, Some(MAX_REPORTED_ACCURACY_METERS)).is_ok());
+
+    assert_eq!(
+        check_location_data(SF_LAT, SF_LON, Some(MAX_REPORTED_ACCURACY_METERS + 1.0)),
+        Err(InvalidLocationData::AccuracyTooCoarse)
+    );
+    for accuracy in [-5.0, f64::NAN] {
+        assert_eq!(
+            check_location_data(SF_LAT, SF_LON, Some(accuracy)),
+            Err(InvalidLocationData::Accuracy)
+        );
+    }
+}
+
+#[test]
+fn test_unknown_accuracy_is_not_garbage() {
+    // -1 from iOS, 0 from Android and no accuracy at all from the web all pass the sanity check
+    for accuracy in [Some(-1.0), Some(0.0), None] {
+        assert_eq!(
+            check_location_data(SF_LAT, SF_LON, accuracy).map(|fix| fix.accuracy),
+            Ok(AccuracyReading::Unknown)
+        );
+    }
+}